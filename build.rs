@@ -0,0 +1,17 @@
+fn main() {
+    #[cfg(feature = "server")]
+    compile_protos();
+}
+
+/// Generate the gRPC service used by the `server` feature.
+#[cfg(feature = "server")]
+fn compile_protos() {
+    let protoc = protoc_bin_vendored::protoc_bin_path().expect("vendored protoc is available");
+    // SAFETY: build scripts are single-threaded at this point.
+    unsafe { std::env::set_var("PROTOC", protoc) };
+    println!("cargo:rerun-if-changed=proto/rneter.proto");
+    tonic_build::configure()
+        .build_client(true)
+        .compile_protos(&["proto/rneter.proto"], &["proto"])
+        .expect("failed to compile proto/rneter.proto");
+}
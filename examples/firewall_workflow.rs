@@ -195,6 +195,8 @@ async fn main() -> Result<(), Box<dyn Error>> {
         name: "fw-policy-publish".to_string(),
         blocks: vec![addr_block, svc_block, policy_block],
         fail_fast: true,
+        preflight: false,
+        idempotency_key: None,
     };
 
     if dry_run {
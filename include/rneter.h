@@ -0,0 +1,48 @@
+/*
+ * C ABI for rneter, enabled with the `ffi` Cargo feature.
+ *
+ * Build the shared library with:
+ *   cargo rustc --release --lib --features ffi --crate-type cdylib
+ *
+ * All strings are NUL-terminated UTF-8. Strings returned by the library must
+ * be released with rneter_string_free(). Calls block the calling thread.
+ */
+#ifndef RNETER_H
+#define RNETER_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Opaque handle to one device connection. */
+typedef struct RneterSession RneterSession;
+
+/*
+ * Open (or reuse) a pooled connection.
+ *
+ * request_json: {"host": "...", "username": "...", "password": "...",
+ *                "port": 22, "template": "cisco", "enable_password": null,
+ *                "sys": null, "affinity": null}
+ *
+ * Returns NULL on failure and, when error_out is not NULL, stores an error
+ * JSON string {"ok": false, "error": "...", "retryable": bool} there.
+ */
+RneterSession *rneter_connect(const char *request_json, char **error_out);
+
+/*
+ * Run one command. command_json: {"mode": "Enable", "command": "show version",
+ * "timeout": 30}. Returns {"ok": true, "output": {...}} or an error JSON.
+ */
+char *rneter_run_command(const RneterSession *session, const char *command_json);
+
+/* Disconnect and release the handle. NULL is ignored. */
+void rneter_close(RneterSession *session);
+
+/* Free a string returned by this library. NULL is ignored. */
+void rneter_string_free(char *value);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* RNETER_H */
@@ -0,0 +1,337 @@
+//! Golden-config compliance checks.
+//!
+//! A [`RuleSet`] lists required lines, forbidden patterns and golden config
+//! blocks. Rule sets can be scoped to device tags, so one [`ComplianceChecker`]
+//! carries per-role templates (e.g. `edge` vs `core`). Checking a config yields
+//! a [`ComplianceReport`] with one [`Finding`] per violation.
+
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::error::ConnectError;
+use crate::session::{Command, ConnectionRequest, ExecutionContext, SshConnectionManager};
+
+/// How serious a compliance violation is.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, JsonSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Info,
+    #[default]
+    Warning,
+    Critical,
+}
+
+/// What a rule checks.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum RuleKind {
+    /// This exact line (ignoring surrounding whitespace) must be present.
+    RequiredLine { line: String },
+    /// No line may match this regex.
+    ForbiddenPattern { pattern: String },
+    /// Every non-blank line of this block must be present.
+    Golden { config: String },
+}
+
+/// One compliance rule.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct ComplianceRule {
+    pub id: String,
+    #[serde(default)]
+    pub severity: Severity,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(flatten)]
+    pub kind: RuleKind,
+}
+
+impl ComplianceRule {
+    /// Require `line` to be present.
+    pub fn required_line(id: impl Into<String>, line: impl Into<String>) -> Self {
+        Self::new(id, RuleKind::RequiredLine { line: line.into() })
+    }
+
+    /// Forbid lines matching `pattern`.
+    pub fn forbidden_pattern(id: impl Into<String>, pattern: impl Into<String>) -> Self {
+        Self::new(
+            id,
+            RuleKind::ForbiddenPattern {
+                pattern: pattern.into(),
+            },
+        )
+    }
+
+    /// Require every non-blank line of `config`.
+    pub fn golden(id: impl Into<String>, config: impl Into<String>) -> Self {
+        Self::new(
+            id,
+            RuleKind::Golden {
+                config: config.into(),
+            },
+        )
+    }
+
+    fn new(id: impl Into<String>, kind: RuleKind) -> Self {
+        Self {
+            id: id.into(),
+            severity: Severity::default(),
+            description: None,
+            kind,
+        }
+    }
+
+    pub fn with_severity(mut self, severity: Severity) -> Self {
+        self.severity = severity;
+        self
+    }
+
+    pub fn with_description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+}
+
+/// Named group of rules, optionally limited to devices carrying some tags.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct RuleSet {
+    pub name: String,
+    /// Applies to devices with any of these tags; empty means every device.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub rules: Vec<ComplianceRule>,
+}
+
+impl RuleSet {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            tags: Vec::new(),
+            rules: Vec::new(),
+        }
+    }
+
+    pub fn with_tag(mut self, tag: impl Into<String>) -> Self {
+        self.tags.push(tag.into());
+        self
+    }
+
+    pub fn with_rule(mut self, rule: ComplianceRule) -> Self {
+        self.rules.push(rule);
+        self
+    }
+
+    /// Returns true when this rule set applies to a device with `tags`.
+    pub fn applies_to(&self, tags: &[String]) -> bool {
+        self.tags.is_empty() || self.tags.iter().any(|tag| tags.contains(tag))
+    }
+}
+
+/// A single violation found on a device.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct Finding {
+    pub rule_set: String,
+    pub rule_id: String,
+    pub severity: Severity,
+    pub message: String,
+    /// 1-based config line for forbidden-pattern hits.
+    pub line: Option<usize>,
+}
+
+/// Findings for one device.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct ComplianceReport {
+    pub device: String,
+    pub findings: Vec<Finding>,
+}
+
+impl ComplianceReport {
+    pub fn is_compliant(&self) -> bool {
+        self.findings.is_empty()
+    }
+
+    /// Most severe finding, if any.
+    pub fn max_severity(&self) -> Option<Severity> {
+        self.findings.iter().map(|finding| finding.severity).max()
+    }
+}
+
+/// Validated rule sets with their regexes compiled.
+#[derive(Debug, Clone)]
+pub struct ComplianceChecker {
+    rule_sets: Vec<RuleSet>,
+    // Compiled forbidden patterns, indexed like `rule_sets[i].rules[j]`.
+    patterns: Vec<Vec<Option<Regex>>>,
+}
+
+impl ComplianceChecker {
+    /// Compile `rule_sets`, rejecting invalid regexes.
+    pub fn new(rule_sets: Vec<RuleSet>) -> Result<Self, ConnectError> {
+        let patterns = rule_sets
+            .iter()
+            .map(|set| {
+                set.rules
+                    .iter()
+                    .map(|rule| match &rule.kind {
+                        RuleKind::ForbiddenPattern { pattern } => {
+                            Regex::new(pattern).map(Some).map_err(|e| {
+                                ConnectError::InvalidRequest(format!(
+                                    "rule '{}' in '{}': {e}",
+                                    rule.id, set.name
+                                ))
+                            })
+                        }
+                        _ => Ok(None),
+                    })
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self {
+            rule_sets,
+            patterns,
+        })
+    }
+
+    pub fn rule_sets(&self) -> &[RuleSet] {
+        &self.rule_sets
+    }
+
+    /// Check `config` for `device`, applying rule sets that match `tags`.
+    pub fn check(&self, device: &str, tags: &[String], config: &str) -> ComplianceReport {
+        let lines: Vec<&str> = config.lines().map(str::trim_end).collect();
+        let present = |wanted: &str| lines.iter().any(|line| line.trim() == wanted.trim());
+        let mut findings = Vec::new();
+
+        for (set, patterns) in self.rule_sets.iter().zip(&self.patterns) {
+            if !set.applies_to(tags) {
+                continue;
+            }
+            for (rule, pattern) in set.rules.iter().zip(patterns) {
+                let mut finding = |message: String, line: Option<usize>| {
+                    findings.push(Finding {
+                        rule_set: set.name.clone(),
+                        rule_id: rule.id.clone(),
+                        severity: rule.severity,
+                        message,
+                        line,
+                    })
+                };
+                match (&rule.kind, pattern) {
+                    (RuleKind::RequiredLine { line }, _) => {
+                        if !present(line) {
+                            finding(format!("missing required line '{}'", line.trim()), None);
+                        }
+                    }
+                    (RuleKind::ForbiddenPattern { .. }, Some(regex)) => {
+                        for (index, line) in lines.iter().enumerate() {
+                            if regex.is_match(line) {
+                                finding(
+                                    format!("forbidden line '{}'", line.trim()),
+                                    Some(index + 1),
+                                );
+                            }
+                        }
+                    }
+                    (RuleKind::Golden { config: golden }, _) => {
+                        for wanted in golden.lines().filter(|line| !line.trim().is_empty()) {
+                            if !present(wanted) {
+                                finding(format!("missing golden line '{}'", wanted.trim()), None);
+                            }
+                        }
+                    }
+                    (RuleKind::ForbiddenPattern { .. }, None) => {}
+                }
+            }
+        }
+
+        ComplianceReport {
+            device: device.to_string(),
+            findings,
+        }
+    }
+}
+
+impl SshConnectionManager {
+    /// Fetch a device's config with `fetch` (e.g. `show running-config`) and check it.
+    ///
+    /// Spilled output is read back from disk before checking.
+    pub async fn check_compliance_with_context(
+        &self,
+        request: ConnectionRequest,
+        fetch: Command,
+        context: ExecutionContext,
+        checker: &ComplianceChecker,
+        tags: &[String],
+    ) -> Result<ComplianceReport, ConnectError> {
+        let device_addr = request.device_addr();
+        let output = self
+            .execute_command_with_context(request, fetch, context)
+            .await?;
+        let config = crate::parsers::output_text(output)?;
+        Ok(checker.check(&device_addr, tags, &config))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = "hostname edge-1\n\
+                          service password-encryption\n\
+                          ip http server\n\
+                          ntp server 192.0.2.10\n";
+
+    fn checker() -> ComplianceChecker {
+        ComplianceChecker::new(vec![
+            RuleSet::new("baseline")
+                .with_rule(ComplianceRule::required_line(
+                    "pw-encryption",
+                    "service password-encryption",
+                ))
+                .with_rule(
+                    ComplianceRule::forbidden_pattern("no-http", r"^ip http server")
+                        .with_severity(Severity::Critical),
+                ),
+            RuleSet::new("edge")
+                .with_tag("edge")
+                .with_rule(ComplianceRule::golden(
+                    "edge-ntp",
+                    "ntp server 192.0.2.10\nntp server 192.0.2.11\n",
+                )),
+        ])
+        .expect("checker")
+    }
+
+    #[test]
+    fn reports_forbidden_and_missing_lines_with_severity() {
+        let report = checker().check("edge-1", &["edge".to_string()], CONFIG);
+
+        let ids: Vec<_> = report.findings.iter().map(|f| f.rule_id.as_str()).collect();
+        assert_eq!(ids, ["no-http", "edge-ntp"]);
+        assert_eq!(report.findings[0].line, Some(3));
+        assert!(report.findings[1].message.contains("192.0.2.11"));
+        assert_eq!(report.max_severity(), Some(Severity::Critical));
+    }
+
+    #[test]
+    fn tag_scoped_rule_sets_only_apply_to_matching_devices() {
+        let report = checker().check("core-1", &["core".to_string()], CONFIG);
+
+        assert_eq!(report.findings.len(), 1);
+        assert_eq!(report.findings[0].rule_set, "baseline");
+    }
+
+    #[test]
+    fn rule_sets_deserialize_and_reject_bad_patterns() {
+        let set: RuleSet = serde_json::from_str(
+            r#"{"name":"x","rules":[{"id":"r","kind":"forbidden_pattern","pattern":"("}]}"#,
+        )
+        .expect("json");
+
+        assert_eq!(set.rules[0].severity, Severity::Warning);
+        assert!(ComplianceChecker::new(vec![set]).is_err());
+    }
+}
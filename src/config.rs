@@ -187,3 +187,50 @@ pub const SECURE_KEY_TYPES: &[Algorithm] = &[
         hash: Some(HashAlg::Sha256),
     },
 ];
+
+/// FIPS-restricted key exchange algorithms (NIST curves and SHA-2 DH groups only).
+pub const FIPS_KEX_ORDER: &[kex::Name] = &[
+    kex::ECDH_SHA2_NISTP256,
+    kex::ECDH_SHA2_NISTP384,
+    kex::ECDH_SHA2_NISTP521,
+    kex::DH_G14_SHA256,
+    kex::DH_G15_SHA512,
+    kex::DH_G16_SHA512,
+    kex::DH_G17_SHA512,
+    kex::DH_G18_SHA512,
+];
+
+/// FIPS-restricted cipher algorithms (AES only).
+pub static FIPS_CIPHERS: &[cipher::Name] = &[
+    cipher::AES_256_GCM,
+    cipher::AES_256_CTR,
+    cipher::AES_192_CTR,
+    cipher::AES_128_CTR,
+];
+
+/// FIPS-restricted MAC algorithms (SHA-2 HMACs only).
+pub const FIPS_MAC_ALGORITHMS: &[mac::Name] = &[
+    mac::HMAC_SHA512_ETM,
+    mac::HMAC_SHA256_ETM,
+    mac::HMAC_SHA512,
+    mac::HMAC_SHA256,
+];
+
+/// FIPS-restricted host key algorithms (NIST ECDSA and RSA with SHA-2).
+pub const FIPS_KEY_TYPES: &[Algorithm] = &[
+    Algorithm::Ecdsa {
+        curve: EcdsaCurve::NistP256,
+    },
+    Algorithm::Ecdsa {
+        curve: EcdsaCurve::NistP384,
+    },
+    Algorithm::Ecdsa {
+        curve: EcdsaCurve::NistP521,
+    },
+    Algorithm::Rsa {
+        hash: Some(HashAlg::Sha512),
+    },
+    Algorithm::Rsa {
+        hash: Some(HashAlg::Sha256),
+    },
+];
@@ -1,8 +1,12 @@
 use std::collections::HashMap;
+use std::sync::Mutex;
 
 use regex::{Regex, RegexSet};
 
-use super::{CommandExecutionStrategy, DeviceHandler, DeviceHandlerConfig, PRE_STATE};
+use super::{
+    CommandExecutionStrategy, DEFAULT_LOGOUT_COMMAND, DEFAULT_TERMINAL_WIDTH, DeviceHandler,
+    DeviceHandlerConfig, EdgeRules, KeystrokePacing, LoginStage, PRE_STATE, syntax::StemTree,
+};
 use crate::error::ConnectError;
 
 impl DeviceHandler {
@@ -12,7 +16,7 @@ impl DeviceHandler {
             return false;
         }
 
-        if self.edges != other.edges {
+        if self.edges != other.edges || self.edge_rules != other.edge_rules {
             return false;
         }
 
@@ -50,12 +54,44 @@ impl DeviceHandler {
             return false;
         }
 
+        if self.terminal_emulation != other.terminal_emulation {
+            return false;
+        }
+
+        if self.login_chain != other.login_chain {
+            return false;
+        }
+
+        if self.terminal_width != other.terminal_width
+            || self.terminal_setup != other.terminal_setup
+            || self.logout_command != other.logout_command
+            || self.pacing != other.pacing
+            || !self
+                .config_lock_regex
+                .iter()
+                .map(Regex::as_str)
+                .eq(other.config_lock_regex.iter().map(Regex::as_str))
+            || self.syslog_regex.as_ref().map(RegexSet::patterns)
+                != other.syslog_regex.as_ref().map(RegexSet::patterns)
+            || self.comment_prefixes != other.comment_prefixes
+            || self.command_stems != other.command_stems
+            || self.unmodeled_prompt != other.unmodeled_prompt
+            || !self
+                .volatile_regex
+                .iter()
+                .map(Regex::as_str)
+                .eq(other.volatile_regex.iter().map(Regex::as_str))
+        {
+            return false;
+        }
+
         true
     }
 
     /// Creates a new `DeviceHandler` from a declarative configuration snapshot.
     pub fn new(config: DeviceHandlerConfig) -> Result<DeviceHandler, ConnectError> {
         let DeviceHandlerConfig {
+            name,
             prompt,
             prompt_with_sys,
             write,
@@ -65,8 +101,46 @@ impl DeviceHandler {
             ignore_errors,
             dyn_param,
             command_execution,
+            terminal_emulation,
+            login_chain,
+            terminal_width,
+            terminal_setup,
+            logout_command,
+            pacing,
+            config_lock_regex,
+            syslog_regex,
+            comment_prefixes,
+            command_stems,
+            unmodeled_prompt,
+            volatile_regex,
         } = config;
 
+        if terminal_emulation && !cfg!(feature = "vt100") {
+            return Err(ConnectError::InvalidDeviceHandlerConfig(
+                "terminal_emulation requires the `vt100` feature".to_string(),
+            ));
+        }
+
+        let login_chain = login_chain
+            .into_iter()
+            .enumerate()
+            .map(|(index, stage)| {
+                let compile = |patterns: &[String]| {
+                    RegexSet::new(patterns).map_err(|err| {
+                        ConnectError::InvalidDeviceHandlerConfig(format!(
+                            "invalid login_chain regex at stage {index}: {err}"
+                        ))
+                    })
+                };
+                Ok(LoginStage {
+                    username: compile(&stage.username_patterns)?,
+                    password: compile(&stage.password_patterns)?,
+                    username_param: stage.username_param,
+                    password_param: stage.password_param,
+                })
+            })
+            .collect::<Result<Vec<_>, ConnectError>>()?;
+
         let mut all_states: Vec<String> = PRE_STATE
             .iter()
             .map(|s| s.to_string().to_ascii_lowercase())
@@ -169,6 +243,36 @@ impl DeviceHandler {
             ))
         })?;
 
+        let config_lock_regex = config_lock_regex
+            .iter()
+            .map(|pattern| {
+                Regex::new(pattern).map_err(|err| {
+                    ConnectError::InvalidDeviceHandlerConfig(format!(
+                        "invalid config_lock_regex '{pattern}': {err}"
+                    ))
+                })
+            })
+            .collect::<Result<Vec<_>, ConnectError>>()?;
+
+        let volatile_regex = volatile_regex
+            .iter()
+            .map(|pattern| {
+                Regex::new(pattern).map_err(|err| {
+                    ConnectError::InvalidDeviceHandlerConfig(format!(
+                        "invalid volatile_regex '{pattern}': {err}"
+                    ))
+                })
+            })
+            .collect::<Result<Vec<_>, ConnectError>>()?;
+
+        let syslog_regex = if syslog_regex.is_empty() {
+            None
+        } else {
+            Some(RegexSet::new(&syslog_regex).map_err(|err| {
+                ConnectError::InvalidDeviceHandlerConfig(format!("invalid syslog_regex set: {err}"))
+            })?)
+        };
+
         let mut ignore_iter = ignore_errors.into_iter().peekable();
         let ignore_errors = if ignore_iter.peek().is_none() {
             None
@@ -181,7 +285,32 @@ impl DeviceHandler {
             })?)
         };
 
-        let edges = edges
+        let mut edge_rules = HashMap::new();
+        for rule in &edges {
+            if rule.ignore_errors.is_empty() && rule.error_regex.is_empty() {
+                continue;
+            }
+            let compile = |patterns: &[String]| {
+                RegexSet::new(patterns).map_err(|err| {
+                    ConnectError::InvalidDeviceHandlerConfig(format!(
+                        "invalid regex on edge '{}' -> '{}': {err}",
+                        rule.from_state, rule.to_state
+                    ))
+                })
+            };
+            edge_rules.insert(
+                (
+                    rule.from_state.to_ascii_lowercase(),
+                    rule.to_state.to_ascii_lowercase(),
+                ),
+                EdgeRules {
+                    ignore_errors: compile(&rule.ignore_errors)?,
+                    error_regex: compile(&rule.error_regex)?,
+                },
+            );
+        }
+
+        let edges: Vec<_> = edges
             .into_iter()
             .map(|rule| {
                 (
@@ -193,8 +322,13 @@ impl DeviceHandler {
                 )
             })
             .collect();
+        let mut adjacency: HashMap<String, Vec<usize>> = HashMap::new();
+        for (index, (from, ..)) in edges.iter().enumerate() {
+            adjacency.entry(from.clone()).or_default().push(index);
+        }
 
         Ok(Self {
+            name,
             current_state_index: 0,
             prompt_index,
             sys_prompt_index,
@@ -203,10 +337,14 @@ impl DeviceHandler {
             regex_index_map,
             input_map,
             edges,
+            adjacency,
+            path_cache: Mutex::new(HashMap::new()),
+            edge_rules,
             ignore_errors,
             dyn_param,
             catch_map,
             sys: None,
+            captures: HashMap::new(),
             current_prompt: None,
             prompt_patterns,
             command_execution: match command_execution {
@@ -220,9 +358,74 @@ impl DeviceHandler {
                     marker,
                     shell_flavor,
                 },
+                super::DeviceCommandExecutionConfig::ExecChannel => {
+                    CommandExecutionStrategy::ExecChannel
+                }
             },
+            terminal_emulation,
+            login_chain,
+            login_progress: (0, false),
+            terminal_width: terminal_width.unwrap_or(DEFAULT_TERMINAL_WIDTH),
+            terminal_setup,
+            logout_command: logout_command.unwrap_or_else(|| DEFAULT_LOGOUT_COMMAND.to_string()),
+            pacing: pacing.filter(|pacing| *pacing != KeystrokePacing::default()),
+            config_lock_regex,
+            syslog_regex,
+            comment_prefixes: comment_prefixes
+                .into_iter()
+                .filter(|prefix| !prefix.is_empty())
+                .collect(),
+            command_stems: command_stems
+                .into_iter()
+                .map(|(mode, stems)| (mode.to_ascii_lowercase(), StemTree::new(&stems)))
+                .collect(),
+            unmodeled_prompt,
+            volatile_regex,
+            password_attempts: None,
+            password_rejected: None,
         })
     }
+
+    /// Copy of this handler's rules in the initial state, with nothing read yet.
+    pub(crate) fn fresh(&self) -> DeviceHandler {
+        DeviceHandler {
+            name: self.name.clone(),
+            current_state_index: 0,
+            all_states: self.all_states.clone(),
+            edge_rules: self.edge_rules.clone(),
+            all_regex: self.all_regex.clone(),
+            regex_index_map: self.regex_index_map.clone(),
+            prompt_index: self.prompt_index,
+            sys_prompt_index: self.sys_prompt_index,
+            input_map: self.input_map.clone(),
+            edges: self.edges.clone(),
+            adjacency: self.adjacency.clone(),
+            path_cache: Mutex::new(HashMap::new()),
+            ignore_errors: self.ignore_errors.clone(),
+            dyn_param: self.dyn_param.clone(),
+            catch_map: self.catch_map.clone(),
+            sys: None,
+            captures: HashMap::new(),
+            current_prompt: None,
+            prompt_patterns: self.prompt_patterns.clone(),
+            command_execution: self.command_execution.clone(),
+            terminal_emulation: self.terminal_emulation,
+            login_chain: self.login_chain.clone(),
+            terminal_width: self.terminal_width,
+            terminal_setup: self.terminal_setup.clone(),
+            logout_command: self.logout_command.clone(),
+            pacing: self.pacing,
+            config_lock_regex: self.config_lock_regex.clone(),
+            syslog_regex: self.syslog_regex.clone(),
+            comment_prefixes: self.comment_prefixes.clone(),
+            command_stems: self.command_stems.clone(),
+            unmodeled_prompt: self.unmodeled_prompt.clone(),
+            volatile_regex: self.volatile_regex.clone(),
+            login_progress: (0, false),
+            password_attempts: None,
+            password_rejected: None,
+        }
+    }
 }
 
 #[cfg(test)]
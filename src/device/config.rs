@@ -1,5 +1,7 @@
 use std::collections::HashMap;
+use std::time::Duration;
 
+use regex::Regex;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
@@ -19,6 +21,9 @@ pub enum DeviceCommandExecutionConfig {
         #[serde(default)]
         shell_flavor: DeviceShellFlavor,
     },
+    /// Run each command on its own SSH exec channel and take success from the
+    /// channel's exit status; no prompt matching or mode transitions.
+    ExecChannel,
 }
 
 /// Shell flavor used when composing exit-status capture commands.
@@ -65,11 +70,144 @@ pub struct DeviceTransitionRule {
     pub to_state: String,
     pub is_exit: bool,
     pub needs_format: bool,
+    /// Lines this transition may print that `error_regex` would flag, e.g.
+    /// a notice that another user holds the configuration lock.
+    #[serde(default)]
+    pub ignore_errors: Vec<String>,
+    /// Lines that fail this transition in addition to `error_regex`.
+    #[serde(default)]
+    pub error_regex: Vec<String>,
+}
+
+impl DeviceTransitionRule {
+    pub fn with_ignore_errors<P: PatternSource>(mut self, patterns: &[P]) -> Self {
+        self.ignore_errors = self::patterns(patterns);
+        self
+    }
+
+    pub fn with_error_regex<P: PatternSource>(mut self, patterns: &[P]) -> Self {
+        self.error_regex = self::patterns(patterns);
+        self
+    }
+}
+
+/// One hop of an in-shell login chain, e.g. a console server port login.
+///
+/// Credentials are looked up in the handler's dyn_param map by key, so they
+/// can be supplied per connection or per command rather than baked into the
+/// template. Values are sent as-is; include the trailing newline.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct DeviceLoginStage {
+    /// dyn_param key answering the username prompt; `None` for password-only hops.
+    #[serde(default)]
+    pub username_param: Option<String>,
+    /// dyn_param key answering the password prompt.
+    pub password_param: String,
+    #[serde(default = "default_username_patterns")]
+    pub username_patterns: Vec<String>,
+    #[serde(default = "default_password_patterns")]
+    pub password_patterns: Vec<String>,
+}
+
+/// Write pacing for devices that drop characters when input arrives at
+/// full speed, e.g. long config lines pasted into a slow line card.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema, Default)]
+pub struct KeystrokePacing {
+    /// Bytes written per burst; 0 writes each line in one burst.
+    #[serde(default)]
+    pub chunk_bytes: usize,
+    /// Pause after each burst that does not end a line.
+    #[serde(default)]
+    pub chunk_delay_ms: u64,
+    /// Pause after each newline.
+    #[serde(default)]
+    pub line_delay_ms: u64,
+}
+
+impl KeystrokePacing {
+    /// Split `data` into bursts, each with the pause that follows it.
+    pub(crate) fn bursts<'a>(&self, data: &'a [u8]) -> Vec<(&'a [u8], Duration)> {
+        let mut bursts = Vec::new();
+        for line in data.split_inclusive(|byte| *byte == b'\n') {
+            let size = if self.chunk_bytes == 0 {
+                line.len()
+            } else {
+                self.chunk_bytes
+            };
+            for chunk in line.chunks(size) {
+                let delay = if chunk.ends_with(b"\n") {
+                    self.line_delay_ms
+                } else {
+                    self.chunk_delay_ms
+                };
+                bursts.push((chunk, Duration::from_millis(delay)));
+            }
+        }
+        bursts
+    }
+}
+
+/// Fallback for questions the template does not model, which would
+/// otherwise stall the command until it times out.
+///
+/// When output stops on a line that is neither a prompt nor a known input
+/// for `idle_ms`, the line is taken as a question: it is answered with
+/// `answer`, or the command fails with [`ConnectError::UnmodeledPrompt`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct UnmodeledPromptPolicy {
+    /// Silence after a pending line before it counts as a question.
+    pub idle_ms: u64,
+    /// Line sent as the answer; empty sends a bare newline. `None` fails
+    /// fast instead.
+    #[serde(default)]
+    pub answer: Option<String>,
+    /// Answers sent per command before failing.
+    #[serde(default = "default_max_answers")]
+    pub max_answers: u32,
+}
+
+impl UnmodeledPromptPolicy {
+    /// Fail commands stuck on a question for `idle`.
+    pub fn fail_after(idle: Duration) -> Self {
+        Self {
+            idle_ms: u64::try_from(idle.as_millis()).unwrap_or(u64::MAX),
+            answer: None,
+            max_answers: default_max_answers(),
+        }
+    }
+
+    /// Answer questions left pending for `idle` with `answer`.
+    pub fn answer_after(idle: Duration, answer: impl Into<String>) -> Self {
+        Self {
+            answer: Some(answer.into()),
+            ..Self::fail_after(idle)
+        }
+    }
+
+    pub(crate) fn idle(&self) -> Duration {
+        Duration::from_millis(self.idle_ms)
+    }
+}
+
+fn default_max_answers() -> u32 {
+    1
+}
+
+fn default_username_patterns() -> Vec<String> {
+    vec![r"(?i)^\s*(user\s*name|login)\s*:\s*$".to_string()]
+}
+
+fn default_password_patterns() -> Vec<String> {
+    vec![r"(?i)^\s*password\s*:\s*$".to_string()]
 }
 
 /// Serializable configuration used to build a [`DeviceHandler`].
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema, Default)]
 pub struct DeviceHandlerConfig {
+    /// Template name, e.g. `cisco`; groups the entries of
+    /// [`unmatched_lines_report`](crate::templates::unmatched_lines_report).
+    #[serde(default)]
+    pub name: Option<String>,
     pub prompt: Vec<DevicePromptRule>,
     pub prompt_with_sys: Vec<DevicePromptWithSysRule>,
     pub write: Vec<DeviceInputRule>,
@@ -82,6 +220,54 @@ pub struct DeviceHandlerConfig {
     pub dyn_param: HashMap<String, String>,
     #[serde(default)]
     pub command_execution: DeviceCommandExecutionConfig,
+    /// Rebuild lines from a VT100 screen model before matching prompts.
+    ///
+    /// For menus drawn with cursor positioning; requires the `vt100` feature.
+    #[serde(default)]
+    pub terminal_emulation: bool,
+    /// Nested logins answered in order, one stage per username/password pair.
+    #[serde(default)]
+    pub login_chain: Vec<DeviceLoginStage>,
+    /// PTY width in columns requested on connect; 800 when unset.
+    #[serde(default)]
+    pub terminal_width: Option<u32>,
+    /// Commands run once after the first prompt, e.g. `terminal width 512`,
+    /// so the device does not wrap long lines itself.
+    #[serde(default)]
+    pub terminal_setup: Vec<String>,
+    /// Command that ends the session once the exit edges are walked, e.g.
+    /// `quit` or `logout`; `exit` when unset.
+    #[serde(default)]
+    pub logout_command: Option<String>,
+    /// Slow down writes for devices that drop pasted characters.
+    #[serde(default)]
+    pub pacing: Option<KeystrokePacing>,
+    /// Messages meaning another user holds the configuration lock; a
+    /// `holder` named group captures who.
+    #[serde(default)]
+    pub config_lock_regex: Vec<String>,
+    /// Syslog lines printed by `terminal monitor`, moved out of command
+    /// output into [`Output::async_messages`](crate::session::Output::async_messages).
+    #[serde(default)]
+    pub syslog_regex: Vec<String>,
+    /// Line prefixes marking comments in command lists, e.g. `!` on Cisco
+    /// or `#` on Huawei; such lines are never sent to the device.
+    #[serde(default)]
+    pub comment_prefixes: Vec<String>,
+    /// Leading words of valid commands per mode, e.g. `show` or `ip route`,
+    /// used by [`DeviceHandler::check_syntax`] to flag typos before they are
+    /// sent. Modes without stems are not checked.
+    #[serde(default)]
+    pub command_stems: HashMap<String, Vec<String>>,
+    /// Fallback for output stuck on a question nobody modeled; `None`
+    /// waits for the command timeout.
+    #[serde(default)]
+    pub unmodeled_prompt: Option<UnmodeledPromptPolicy>,
+    /// Values in show output that change on every run, such as uptime,
+    /// counters and timestamps, masked by [`DeviceHandler::normalize_output`].
+    /// Patterns with capture groups mask only the groups.
+    #[serde(default)]
+    pub volatile_regex: Vec<String>,
 }
 
 impl DeviceHandlerConfig {
@@ -98,47 +284,90 @@ impl DeviceHandler {
     }
 }
 
+/// A regex accepted by the rule helpers: pattern text or a compiled [`Regex`].
+///
+/// Only the pattern text is kept, so options set through
+/// [`RegexBuilder`](regex::RegexBuilder) are lost; use inline flags such as
+/// `(?i)` instead.
+pub trait PatternSource {
+    fn pattern(&self) -> String;
+}
+
+impl PatternSource for str {
+    fn pattern(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl PatternSource for String {
+    fn pattern(&self) -> String {
+        self.clone()
+    }
+}
+
+impl PatternSource for Regex {
+    fn pattern(&self) -> String {
+        self.as_str().to_string()
+    }
+}
+
+impl<T: PatternSource + ?Sized> PatternSource for &T {
+    fn pattern(&self) -> String {
+        (**self).pattern()
+    }
+}
+
+/// Pattern texts of `patterns`, e.g. for `more_regex` or `error_regex`.
+pub fn patterns<P: PatternSource>(patterns: &[P]) -> Vec<String> {
+    patterns.iter().map(PatternSource::pattern).collect()
+}
+
 /// Convenience helper for concise template definitions.
-pub fn prompt_rule(state: &str, patterns: &[&str]) -> DevicePromptRule {
+pub fn prompt_rule<P: PatternSource>(state: &str, patterns: &[P]) -> DevicePromptRule {
     DevicePromptRule {
         state: state.to_string(),
-        patterns: patterns
-            .iter()
-            .map(|pattern| (*pattern).to_string())
-            .collect(),
+        patterns: self::patterns(patterns),
     }
 }
 
 /// Convenience helper for prompt rules that capture a sys value.
-pub fn prompt_with_sys_rule(
+pub fn prompt_with_sys_rule<P: PatternSource + ?Sized>(
     state: &str,
     capture_group: &str,
-    pattern: &str,
+    pattern: &P,
 ) -> DevicePromptWithSysRule {
     DevicePromptWithSysRule {
         state: state.to_string(),
         capture_group: capture_group.to_string(),
-        pattern: pattern.to_string(),
+        pattern: pattern.pattern(),
     }
 }
 
 /// Convenience helper for interactive input rules.
-pub fn input_rule(
+pub fn input_rule<P: PatternSource>(
     state: &str,
     dynamic: bool,
     value: &str,
     record_input: bool,
-    patterns: &[&str],
+    patterns: &[P],
 ) -> DeviceInputRule {
     DeviceInputRule {
         state: state.to_string(),
         dynamic,
         value: value.to_string(),
         record_input,
-        patterns: patterns
-            .iter()
-            .map(|pattern| (*pattern).to_string())
-            .collect(),
+        patterns: self::patterns(patterns),
+    }
+}
+
+/// Convenience helper for login-chain stages with the default
+/// `Username:`/`Password:` patterns.
+pub fn login_stage(username_param: Option<&str>, password_param: &str) -> DeviceLoginStage {
+    DeviceLoginStage {
+        username_param: username_param.map(str::to_string),
+        password_param: password_param.to_string(),
+        username_patterns: default_username_patterns(),
+        password_patterns: default_password_patterns(),
     }
 }
 
@@ -156,6 +385,8 @@ pub fn transition_rule(
         to_state: to_state.to_string(),
         is_exit,
         needs_format,
+        ignore_errors: Vec::new(),
+        error_regex: Vec::new(),
     }
 }
 
@@ -174,9 +405,137 @@ mod tests {
         assert!(handler.is_equivalent(&from_config));
     }
 
+    #[test]
+    fn terminal_width_defaults_and_setup_affect_equivalence() {
+        let config = templates::cisco_config();
+        let handler = config.build().expect("cisco handler");
+        assert_eq!(handler.terminal_width(), 800);
+        assert_eq!(handler.terminal_setup(), ["terminal width 512"]);
+
+        let wide = DeviceHandlerConfig {
+            terminal_width: Some(4096),
+            ..config
+        }
+        .build()
+        .expect("wide handler");
+        assert_eq!(wide.terminal_width(), 4096);
+        assert!(!handler.is_equivalent(&wide));
+    }
+
+    #[test]
+    fn keystroke_pacing_splits_lines_into_delayed_bursts() {
+        let pacing = KeystrokePacing {
+            chunk_bytes: 4,
+            chunk_delay_ms: 5,
+            line_delay_ms: 50,
+        };
+        let bursts = pacing.bursts(b"vlan 10\nname x\n");
+        let chunks: Vec<&[u8]> = bursts.iter().map(|(chunk, _)| *chunk).collect();
+        assert_eq!(chunks, [&b"vlan"[..], b" 10\n", b"name", b" x\n"]);
+        assert_eq!(bursts[0].1, Duration::from_millis(5));
+        assert_eq!(bursts[1].1, Duration::from_millis(50));
+
+        let per_line = KeystrokePacing {
+            line_delay_ms: 20,
+            ..KeystrokePacing::default()
+        };
+        assert_eq!(per_line.bursts(b"a\nb").len(), 2);
+
+        let config = DeviceHandlerConfig {
+            pacing: Some(KeystrokePacing::default()),
+            ..templates::cisco_config()
+        };
+        assert_eq!(config.build().expect("handler").pacing(), None);
+    }
+
+    #[test]
+    fn unmodeled_prompt_policy_defaults_to_one_answer() {
+        let policy: UnmodeledPromptPolicy =
+            serde_json::from_str(r#"{"idle_ms":3000,"answer":""}"#).expect("policy");
+        assert_eq!(
+            policy,
+            UnmodeledPromptPolicy::answer_after(Duration::from_secs(3), "")
+        );
+        assert_eq!(policy.max_answers, 1);
+
+        let handler = templates::cisco().expect("cisco");
+        let config = DeviceHandlerConfig {
+            unmodeled_prompt: Some(UnmodeledPromptPolicy::fail_after(Duration::from_secs(2))),
+            ..templates::cisco_config()
+        };
+        let guarded = config.build().expect("handler");
+        assert_eq!(
+            guarded.unmodeled_prompt().map(|p| p.idle()),
+            Some(Duration::from_secs(2))
+        );
+        assert!(!handler.is_equivalent(&guarded));
+    }
+
+    #[test]
+    fn rules_accept_compiled_regexes_and_expose_final_patterns() {
+        let prompt = Regex::new(r"^dev#\s*$").expect("prompt");
+        let more = Regex::new(r"^--More--$").expect("more");
+        let handler = DeviceHandlerConfig {
+            prompt: vec![
+                prompt_rule("Enable", &[&prompt]),
+                prompt_rule("Login", &[r"^dev>\s*$"]),
+            ],
+            prompt_with_sys: vec![prompt_with_sys_rule(
+                "Config",
+                "sys",
+                &Regex::new(r"^(?<sys>\w+)\(cfg\)#\s*$").expect("sys"),
+            )],
+            more_regex: patterns(&[more]),
+            edges: vec![
+                transition_rule("Enable", "exit", "Login", true, false)
+                    .with_error_regex(&[Regex::new("^% Denied").expect("error")]),
+            ],
+            ..Default::default()
+        }
+        .build()
+        .expect("handler");
+
+        assert_eq!(
+            handler.compiled_patterns(),
+            [
+                ("more".to_string(), r"^--More--$".to_string()),
+                ("enable".to_string(), r"^\x00*\r{0,1}dev#\s*$".to_string()),
+                ("login".to_string(), r"^\x00*\r{0,1}dev>\s*$".to_string()),
+                (
+                    "config".to_string(),
+                    r"^\x00*\r{0,1}(?<sys>\w+)\(cfg\)#\s*$".to_string()
+                ),
+            ]
+        );
+        assert!(
+            handler.is_equivalent(
+                &DeviceHandlerConfig {
+                    prompt: vec![
+                        prompt_rule("Enable", &[r"^dev#\s*$"]),
+                        prompt_rule("Login", &[r"^dev>\s*$"]),
+                    ],
+                    prompt_with_sys: vec![prompt_with_sys_rule(
+                        "Config",
+                        "sys",
+                        r"^(?<sys>\w+)\(cfg\)#\s*$",
+                    )],
+                    more_regex: vec![r"^--More--$".to_string()],
+                    edges: vec![
+                        transition_rule("Enable", "exit", "Login", true, false)
+                            .with_error_regex(&["^% Denied"]),
+                    ],
+                    ..Default::default()
+                }
+                .build()
+                .expect("string handler")
+            )
+        );
+    }
+
     #[test]
     fn config_build_supports_shell_exit_status_strategy() {
         let config = DeviceHandlerConfig {
+            name: None,
             prompt: vec![prompt_rule("Root", &[r"^root#\s*$"])],
             prompt_with_sys: Vec::new(),
             write: Vec::new(),
@@ -189,6 +548,18 @@ mod tests {
                 marker: "__MARK__".to_string(),
                 shell_flavor: DeviceShellFlavor::Posix,
             },
+            terminal_emulation: false,
+            login_chain: Vec::new(),
+            terminal_width: None,
+            terminal_setup: Vec::new(),
+            logout_command: None,
+            pacing: None,
+            config_lock_regex: Vec::new(),
+            syslog_regex: Vec::new(),
+            comment_prefixes: Vec::new(),
+            command_stems: HashMap::new(),
+            unmodeled_prompt: None,
+            volatile_regex: Vec::new(),
         };
 
         let handler = config.build().expect("build handler");
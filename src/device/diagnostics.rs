@@ -1,9 +1,15 @@
 use std::collections::{HashMap, HashSet, VecDeque};
 
+use regex::Regex;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use super::DeviceHandler;
+use super::lint::PromptLint;
+use super::runtime::sanitize_terminal_line;
+
+/// Prefix added to every prompt regex by the handler builder.
+pub(super) const PROMPT_REGEX_PREFIX: &str = r"^\x00*\r{0,1}";
 
 /// Diagnostics summary for a device state machine graph.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
@@ -28,6 +34,10 @@ pub struct StateMachineDiagnostics {
     pub potentially_ambiguous_prompt_states: Vec<String>,
     /// States whose outgoing transitions are only self-loop edges.
     pub self_loop_only_states: Vec<String>,
+    /// Prompt patterns that may match the wrong lines, from
+    /// [`DeviceHandler::lint_prompts`].
+    #[serde(default)]
+    pub prompt_lints: Vec<PromptLint>,
 }
 
 impl StateMachineDiagnostics {
@@ -39,10 +49,224 @@ impl StateMachineDiagnostics {
             || !self.dead_end_states.is_empty()
             || !self.duplicate_prompt_patterns.is_empty()
             || !self.self_loop_only_states.is_empty()
+            || !self.prompt_lints.is_empty()
+    }
+}
+
+/// Context attached to a prompt-not-matched failure.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct PromptMatchDiagnostics {
+    /// Command being executed, or `None` while waiting for the initial prompt.
+    pub command: Option<String>,
+    /// Last lines received before giving up.
+    pub last_lines: Vec<String>,
+    /// Active prompt patterns formatted as `state => regex`.
+    pub prompt_patterns: Vec<String>,
+    /// Prompt pattern that came closest to matching the last line.
+    pub near_miss: Option<PromptNearMiss>,
+}
+
+/// Closest prompt pattern to the last received line.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct PromptNearMiss {
+    pub state: String,
+    pub pattern: String,
+    /// Line that was compared against the pattern.
+    pub line: String,
+    /// Why this pattern was considered close.
+    pub reason: String,
+}
+
+impl std::fmt::Display for PromptMatchDiagnostics {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.command.as_deref() {
+            Some(command) => write!(f, "while running {command:?}")?,
+            None => write!(f, "while waiting for initial prompt")?,
+        }
+        if let Some(line) = self.last_lines.last() {
+            write!(f, ", last line {line:?}")?;
+        }
+        if let Some(near_miss) = self.near_miss.as_ref() {
+            write!(
+                f,
+                ", closest pattern {} => {} ({})",
+                near_miss.state, near_miss.pattern, near_miss.reason
+            )?;
+        }
+        Ok(())
     }
 }
 
+/// How the state machine reads one line, returned by [`DeviceHandler::test_line`].
+///
+/// Patterns are reported as written in the template, without the prefix the
+/// builder adds to prompt patterns.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum LineClassification {
+    /// A prompt of `state`; command output ends here.
+    Prompt { state: String, pattern: String },
+    /// A line that fails the command.
+    Error { pattern: String },
+    /// A pager line answered with a space.
+    More { pattern: String },
+    /// A prompt answered from the input rule of `state`, e.g. a password.
+    InputRequired { state: String, pattern: String },
+    /// Plain output, including error lines covered by `ignore_errors`.
+    Output,
+}
+
+/// Leading literal text of a regex, stopping at the first metacharacter.
+fn literal_prefix(pattern: &str) -> String {
+    let mut prefix = String::new();
+    let mut chars = pattern.chars();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\\' => match chars.next() {
+                Some(next) if next.is_ascii_punctuation() => prefix.push(next),
+                _ => break,
+            },
+            // A quantifier makes the preceding literal optional or repeated.
+            '*' | '?' | '{' => {
+                prefix.pop();
+                break;
+            }
+            '.' | '+' | '(' | ')' | '[' | ']' | '}' | '|' | '^' | '$' => break,
+            _ => prefix.push(ch),
+        }
+    }
+    prefix
+}
+
 impl DeviceHandler {
+    /// Explain why `output` did not end in a recognised prompt.
+    ///
+    /// Collects the last `max_lines` lines and finds the prompt pattern that came
+    /// closest to matching the final line, so templates can be debugged without
+    /// enabling trace logs.
+    pub fn diagnose_prompt_mismatch(
+        &self,
+        command: Option<&str>,
+        output: &str,
+        max_lines: usize,
+    ) -> PromptMatchDiagnostics {
+        let lines = output
+            .lines()
+            .map(sanitize_terminal_line)
+            .map(|line| line.trim_end().to_string())
+            .filter(|line| !line.is_empty())
+            .collect::<Vec<_>>();
+        let last_lines = lines[lines.len().saturating_sub(max_lines)..].to_vec();
+
+        let prompt_patterns = self
+            .prompt_patterns
+            .iter()
+            .map(|(state, pattern)| format!("{state} => {pattern}"))
+            .collect();
+
+        let near_miss = last_lines
+            .last()
+            .and_then(|line| self.closest_prompt_pattern(line));
+
+        PromptMatchDiagnostics {
+            command: command.map(str::to_string),
+            last_lines,
+            prompt_patterns,
+            near_miss,
+        }
+    }
+
+    /// Every pattern the handler matches lines against, in match order, with
+    /// the state it leads to.
+    ///
+    /// Prompt patterns appear as compiled, with the leading
+    /// `^\x00*\r{0,1}` the builder adds.
+    pub fn compiled_patterns(&self) -> Vec<(String, String)> {
+        self.all_regex
+            .patterns()
+            .iter()
+            .enumerate()
+            .map(|(index, pattern)| {
+                let state_index = self.regex_index_map.get(&index).copied().unwrap_or(0);
+                (self.all_states[state_index].clone(), pattern.clone())
+            })
+            .collect()
+    }
+
+    /// Classify `line` the way a running session would, without one.
+    ///
+    /// Lets template authors check their regexes with table-driven tests.
+    pub fn test_line(&self, line: &str) -> LineClassification {
+        let line = sanitize_terminal_line(line);
+        if self.ignore_error(&line) {
+            return LineClassification::Output;
+        }
+        let Some(index) = self.all_regex.matches(&line).into_iter().next() else {
+            return LineClassification::Output;
+        };
+        let written = &self.all_regex.patterns()[index];
+        let pattern = written
+            .strip_prefix(PROMPT_REGEX_PREFIX)
+            .map_or_else(|| written.clone(), |pattern| format!("^{pattern}"));
+        let state_index = self.regex_index_map.get(&index).copied().unwrap_or(0);
+        let state = self.all_states[state_index].clone();
+        match state_index {
+            0 => LineClassification::Output,
+            1 => LineClassification::More { pattern },
+            2 => LineClassification::Error { pattern },
+            _ if self.match_prompt(state_index) => LineClassification::Prompt { state, pattern },
+            _ => LineClassification::InputRequired { state, pattern },
+        }
+    }
+
+    fn closest_prompt_pattern(&self, line: &str) -> Option<PromptNearMiss> {
+        let mut best: Option<(usize, PromptNearMiss)> = None;
+
+        for (state, pattern) in &self.prompt_patterns {
+            let relaxed = pattern
+                .strip_prefix(PROMPT_REGEX_PREFIX)
+                .unwrap_or(pattern)
+                .trim_end_matches('$');
+
+            let candidate = match Regex::new(relaxed).ok().and_then(|regex| regex.find(line)) {
+                Some(found) => Some((
+                    line.len() + found.len(),
+                    format!("matches {:?} when unanchored", found.as_str()),
+                )),
+                None => {
+                    let literal = literal_prefix(relaxed);
+                    let shared = literal
+                        .chars()
+                        .zip(line.trim_start().chars())
+                        .take_while(|(a, b)| a == b)
+                        .count();
+                    (shared > 0).then(|| {
+                        let shared_text = literal.chars().take(shared).collect::<String>();
+                        (shared, format!("shares prefix {shared_text:?}"))
+                    })
+                }
+            };
+
+            if let Some((score, reason)) = candidate
+                && best
+                    .as_ref()
+                    .is_none_or(|(best_score, _)| score > *best_score)
+            {
+                best = Some((
+                    score,
+                    PromptNearMiss {
+                        state: state.clone(),
+                        pattern: pattern.clone(),
+                        line: line.to_string(),
+                        reason,
+                    },
+                ));
+            }
+        }
+
+        best.map(|(_, near_miss)| near_miss)
+    }
+
     /// Analyze the state transition graph for common template issues.
     pub fn diagnose_state_machine(&self) -> StateMachineDiagnostics {
         let all_states_set: HashSet<String> = self.all_states.iter().cloned().collect();
@@ -177,6 +401,7 @@ impl DeviceHandler {
             duplicate_prompt_patterns,
             potentially_ambiguous_prompt_states,
             self_loop_only_states,
+            prompt_lints: self.lint_prompts(),
         }
     }
 }
@@ -184,7 +409,7 @@ impl DeviceHandler {
 #[cfg(test)]
 mod tests {
     use super::super::build_test_handler;
-    use super::DeviceHandler;
+    use super::{DeviceHandler, LineClassification, literal_prefix};
     use crate::device::{DeviceHandlerConfig, prompt_rule, transition_rule};
 
     #[test]
@@ -271,4 +496,79 @@ mod tests {
         let report = handler.diagnose_state_machine();
         assert!(report.self_loop_only_states.contains(&"enable".to_string()));
     }
+
+    #[test]
+    fn literal_prefix_stops_at_metacharacters() {
+        assert_eq!(literal_prefix(r"router\(config\)#"), "router(config)#");
+        assert_eq!(literal_prefix(r"dev>\s*"), "dev>");
+        assert_eq!(literal_prefix(r"abc?"), "ab");
+        assert_eq!(literal_prefix(r"[\w-]+#"), "");
+    }
+
+    #[test]
+    fn prompt_mismatch_reports_last_lines_and_near_miss() {
+        let handler = build_test_handler();
+
+        let report = handler.diagnose_prompt_mismatch(
+            Some("show clock"),
+            "line 1\nline 2\n\n*** banner *** dev#",
+            2,
+        );
+
+        assert_eq!(report.command.as_deref(), Some("show clock"));
+        assert_eq!(report.last_lines, ["line 2", "*** banner *** dev#"]);
+        assert_eq!(report.prompt_patterns.len(), 3);
+        let near_miss = report.near_miss.expect("near miss");
+        assert_eq!(near_miss.state, "enable");
+        assert!(near_miss.reason.contains("unanchored"));
+    }
+
+    #[test]
+    fn prompt_mismatch_falls_back_to_shared_literal_prefix() {
+        let handler = build_test_handler();
+
+        let report = handler.diagnose_prompt_mismatch(None, "Password expired\nde", 5);
+
+        assert!(report.to_string().contains("initial prompt"));
+        assert!(report.to_string().contains("\"de\""));
+        let near_miss = report.near_miss.expect("near miss");
+        assert!(near_miss.reason.contains("shares prefix"));
+    }
+
+    #[test]
+    fn test_line_classifies_template_lines() {
+        let handler = crate::templates::cisco().expect("cisco handler");
+        let prompt = |state: &str, pattern: &str| LineClassification::Prompt {
+            state: state.to_string(),
+            pattern: pattern.to_string(),
+        };
+        let cases = [
+            ("R1#", prompt("enable", r"^[^\s#]+#\s*$")),
+            ("R1(config-if)# ", prompt("config", r"^\S+\(\S+\)#\s*$")),
+            ("R1>", prompt("login", r"^[^\s<]+>\s*$")),
+            (
+                "% Invalid input detected at '^' marker.",
+                LineClassification::Error {
+                    pattern: r"^%.+".to_string(),
+                },
+            ),
+            (
+                " <--- More --->",
+                LineClassification::More {
+                    pattern: r"\s*<--- More --->\s*".to_string(),
+                },
+            ),
+            (
+                "Interface GigabitEthernet0/1 is up",
+                LineClassification::Output,
+            ),
+        ];
+        for (line, expected) in cases {
+            assert_eq!(handler.test_line(line), expected, "{line:?}");
+        }
+        assert!(matches!(
+            handler.test_line("\rPassword: "),
+            LineClassification::InputRequired { state, .. } if state == "enablepassword"
+        ));
+    }
 }
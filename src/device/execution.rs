@@ -1,4 +1,7 @@
-use super::{CommandExecutionStrategy, DeviceHandler, DeviceShellFlavor};
+use super::{
+    CommandExecutionStrategy, DeviceHandler, DeviceShellFlavor, KeystrokePacing,
+    LineClassification, UnmodeledPromptPolicy,
+};
 
 const EXIT_STATUS_SUFFIX: &str = ":__";
 
@@ -24,6 +27,151 @@ impl DeviceHandler {
         self
     }
 
+    /// Returns true when commands default to SSH exec channels instead of the shell.
+    pub(crate) fn uses_exec_channel(&self) -> bool {
+        matches!(
+            self.command_execution,
+            CommandExecutionStrategy::ExecChannel
+        )
+    }
+
+    /// PTY width in columns requested on connect.
+    pub(crate) fn terminal_width(&self) -> u32 {
+        self.terminal_width
+    }
+
+    /// Commands run once after the first prompt to stop the device wrapping lines.
+    pub(crate) fn terminal_setup(&self) -> &[String] {
+        &self.terminal_setup
+    }
+
+    /// Write pacing for devices that drop characters, if configured.
+    pub(crate) fn pacing(&self) -> Option<KeystrokePacing> {
+        self.pacing
+    }
+
+    /// Fallback for questions no input rule answers, if configured.
+    pub(crate) fn unmodeled_prompt(&self) -> Option<&UnmodeledPromptPolicy> {
+        self.unmodeled_prompt.as_ref()
+    }
+
+    /// Whether the transition from `from` to `to` succeeded.
+    ///
+    /// Edges with their own patterns are judged line by line on `content`:
+    /// a line fails the transition when it matches the edge's `error_regex`,
+    /// or the global one without matching the edge's `ignore_errors`. Other
+    /// edges keep the command's `success`.
+    pub(crate) fn transition_succeeded(
+        &self,
+        from: &str,
+        to: &str,
+        content: &str,
+        success: bool,
+    ) -> bool {
+        let Some(rules) = self.edge_rules.get(&(from.to_string(), to.to_string())) else {
+            return success;
+        };
+        !content.lines().map(str::trim_end).any(|line| {
+            rules.error_regex.is_match(line)
+                || (!rules.ignore_errors.is_match(line)
+                    && matches!(self.test_line(line), LineClassification::Error { .. }))
+        })
+    }
+
+    /// State a command runs in when it names no mode.
+    ///
+    /// Show commands go to `enable` (`user` on Linux) and config commands
+    /// to `config` (`root`), falling back to the show state on single-mode
+    /// devices. With `in_sys` the state is picked among the sys prompt
+    /// states instead, e.g. `vsiteenable`/`vsiteconfig`.
+    pub fn default_mode(&self, config: bool, in_sys: bool) -> Option<String> {
+        let (start, end) = if in_sys {
+            self.sys_prompt_index
+        } else {
+            (
+                self.prompt_index.0,
+                self.sys_prompt_index.0.saturating_sub(1),
+            )
+        };
+        let states = self.all_states.get(start..=end).unwrap_or_default();
+        let find = |suffixes: &[&str]| {
+            suffixes
+                .iter()
+                .find_map(|suffix| states.iter().find(|state| state.ends_with(suffix)))
+        };
+        let show = find(&["enable", "user"]).or_else(|| states.first());
+        let mode = if config {
+            find(&["config", "root"]).or(show)
+        } else {
+            show
+        };
+        mode.cloned()
+    }
+
+    /// The line of `output` saying another user holds the configuration
+    /// lock, and the holder when the pattern captures one.
+    pub fn config_lock(&self, output: &str) -> Option<(String, Option<String>)> {
+        self.config_lock_regex.iter().find_map(|re| {
+            let captures = re.captures(output)?;
+            let found = captures.get(0)?;
+            let start = output[..found.start()].rfind('\n').map_or(0, |i| i + 1);
+            let end = output[found.start()..]
+                .find('\n')
+                .map_or(output.len(), |i| found.start() + i);
+            let holder = captures
+                .name("holder")
+                .map(|holder| holder.as_str().trim().to_string())
+                .filter(|holder| !holder.is_empty());
+            Some((output[start..end].trim().to_string(), holder))
+        })
+    }
+
+    /// True when `line` is an asynchronous syslog message rather than
+    /// command output.
+    pub fn is_syslog_line(&self, line: &str) -> bool {
+        self.syslog_regex
+            .as_ref()
+            .is_some_and(|syslog| syslog.is_match(line))
+    }
+
+    /// True when `line` is a comment in a command list, such as `!` on
+    /// Cisco, and must not be sent to the device.
+    pub fn is_comment_line(&self, line: &str) -> bool {
+        let line = line.trim_start();
+        self.comment_prefixes
+            .iter()
+            .any(|prefix| line.starts_with(prefix.as_str()))
+    }
+
+    /// Split a command list into the commands to send and the comments it
+    /// carried, dropping blank lines.
+    pub fn strip_comments<S: AsRef<str>>(&self, lines: &[S]) -> (Vec<String>, Vec<String>) {
+        let mut commands = Vec::new();
+        let mut comments = Vec::new();
+        for line in lines.iter().map(AsRef::as_ref) {
+            if line.trim().is_empty() {
+                continue;
+            }
+            if self.is_comment_line(line) {
+                comments.push(line.trim().to_string());
+            } else {
+                commands.push(line.to_string());
+            }
+        }
+        (commands, comments)
+    }
+
+    /// Command that ends the session, e.g. `quit` on Huawei VRP.
+    pub(crate) fn logout_command(&self) -> &str {
+        &self.logout_command
+    }
+
+    /// Returns true when output goes through the VT100 screen model.
+    #[cfg(feature = "vt100")]
+    pub(crate) fn terminal_emulation(&self) -> bool {
+        self.terminal_emulation
+    }
+
     pub(crate) fn prepare_command_for_execution(
         &self,
         command: &str,
@@ -34,7 +182,9 @@ impl DeviceHandler {
         }
 
         match &self.command_execution {
-            CommandExecutionStrategy::PromptDriven => command.to_string(),
+            CommandExecutionStrategy::PromptDriven | CommandExecutionStrategy::ExecChannel => {
+                command.to_string()
+            }
             CommandExecutionStrategy::ShellExitStatus {
                 marker,
                 shell_flavor,
@@ -66,11 +216,13 @@ impl DeviceHandler {
         }
 
         match &self.command_execution {
-            CommandExecutionStrategy::PromptDriven => ParsedCommandOutput {
-                success: fallback_success,
-                exit_code: None,
-                output: output.to_string(),
-            },
+            CommandExecutionStrategy::PromptDriven | CommandExecutionStrategy::ExecChannel => {
+                ParsedCommandOutput {
+                    success: fallback_success,
+                    exit_code: None,
+                    output: output.to_string(),
+                }
+            }
             CommandExecutionStrategy::ShellExitStatus { marker, .. } => {
                 if let Some((exit_code, sanitized)) = parse_shell_exit_status(output, marker) {
                     ParsedCommandOutput {
@@ -172,4 +324,67 @@ mod tests {
         assert_eq!(parsed.exit_code, Some(0));
         assert_eq!(parsed.output, "cmd\nboom\nuser@host$");
     }
+
+    #[test]
+    fn edges_with_own_patterns_judge_their_output() {
+        use crate::device::{DeviceHandlerConfig, prompt_rule, transition_rule};
+
+        let handler = DeviceHandlerConfig {
+            prompt: vec![
+                prompt_rule("Enable", &[r"^[^\s(]+#\s*$"]),
+                prompt_rule("Config", &[r"^\S+\(config\)#\s*$"]),
+            ],
+            error_regex: vec![r"^%.+".to_string()],
+            edges: vec![
+                transition_rule("Enable", "configure exclusive", "Config", false, false)
+                    .with_ignore_errors(&[r"^% Warning: .+ holds the configuration lock"])
+                    .with_error_regex(&[r"^Configuration database locked"]),
+                transition_rule("Config", "end", "Enable", true, false),
+            ],
+            ..DeviceHandlerConfig::default()
+        }
+        .build()
+        .expect("handler");
+
+        let notice = "% Warning: admin holds the configuration lock\nR1(config)#";
+        assert!(handler.transition_succeeded("enable", "config", notice, false));
+        let locked = "Configuration database locked by admin\nR1#";
+        assert!(!handler.transition_succeeded("enable", "config", locked, true));
+        let invalid = "% Invalid input detected\nR1#";
+        assert!(!handler.transition_succeeded("enable", "config", invalid, true));
+        // Edges without their own patterns keep the command's verdict.
+        assert!(!handler.transition_succeeded("config", "enable", notice, false));
+    }
+
+    #[test]
+    fn default_modes_follow_template_states() {
+        let cisco = crate::templates::cisco().expect("cisco");
+        assert_eq!(cisco.default_mode(false, false).as_deref(), Some("enable"));
+        assert_eq!(cisco.default_mode(true, false).as_deref(), Some("config"));
+        assert_eq!(cisco.default_mode(true, true), None);
+
+        let fortinet = crate::templates::fortinet().expect("fortinet");
+        assert_eq!(
+            fortinet.default_mode(true, false).as_deref(),
+            Some("enable")
+        );
+        assert_eq!(
+            fortinet.default_mode(true, true).as_deref(),
+            Some("vdomenable")
+        );
+
+        let array = crate::templates::array().expect("array");
+        assert_eq!(
+            array.default_mode(false, true).as_deref(),
+            Some("vsiteenable")
+        );
+        assert_eq!(
+            array.default_mode(true, true).as_deref(),
+            Some("vsiteconfig")
+        );
+
+        let linux = crate::templates::linux().expect("linux");
+        assert_eq!(linux.default_mode(false, false).as_deref(), Some("user"));
+        assert_eq!(linux.default_mode(true, false).as_deref(), Some("root"));
+    }
 }
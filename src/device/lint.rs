@@ -0,0 +1,218 @@
+//! Lint checks for prompt patterns.
+//!
+//! The handler builder strips a leading `^` from every prompt pattern and
+//! prepends `^\x00*\r{0,1}` instead. That prefix only binds to the first
+//! alternative of a top-level `|`, and nothing anchors the end of a pattern,
+//! so a prompt regex can match inside ordinary output lines without the
+//! template author noticing.
+
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use super::DeviceHandler;
+use super::diagnostics::PROMPT_REGEX_PREFIX;
+
+/// What a prompt lint found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PromptLintKind {
+    /// A top-level `|` leaves later alternatives without the start anchor.
+    UnanchoredAlternation,
+    /// An alternative does not end in `$`, so it also matches a prefix of a
+    /// longer line.
+    UnanchoredEnd,
+    /// A quantified group contains another quantifier, e.g. `(\S+)+`, which
+    /// backtracking engines evaluate in exponential time.
+    NestedQuantifier,
+    /// The pattern matches an empty line.
+    MatchesEmpty,
+}
+
+/// One finding for a prompt pattern.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct PromptLint {
+    pub state: String,
+    /// Pattern as written in the template.
+    pub pattern: String,
+    pub kind: PromptLintKind,
+    pub message: String,
+}
+
+/// Top-level alternatives of `pattern` and whether any quantified group
+/// contains a quantifier.
+fn scan(pattern: &str) -> (Vec<&str>, bool) {
+    let mut alternatives = Vec::new();
+    let mut start = 0;
+    // One entry per open group: whether it contains a quantifier.
+    let mut groups: Vec<bool> = Vec::new();
+    let mut closed_with_quantifier = false;
+    let mut nested = false;
+    let mut in_class = false;
+    let mut chars = pattern.char_indices().peekable();
+    while let Some((index, ch)) = chars.next() {
+        let after_group = std::mem::take(&mut closed_with_quantifier);
+        match ch {
+            '\\' => {
+                chars.next();
+            }
+            ']' if in_class => in_class = false,
+            _ if in_class => {}
+            '[' => in_class = true,
+            '(' => groups.push(false),
+            ')' => {
+                let inner = groups.pop().unwrap_or(false);
+                closed_with_quantifier = inner;
+                if inner && let Some(parent) = groups.last_mut() {
+                    *parent = true;
+                }
+            }
+            '*' | '+' | '{' => {
+                // `{` only counts as a quantifier when a repetition follows.
+                let is_quantifier =
+                    ch != '{' || chars.peek().is_some_and(|(_, next)| next.is_ascii_digit());
+                if is_quantifier {
+                    nested |= after_group;
+                    if let Some(group) = groups.last_mut() {
+                        *group = true;
+                    }
+                }
+            }
+            '|' if groups.is_empty() => {
+                alternatives.push(&pattern[start..index]);
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    alternatives.push(&pattern[start..]);
+    (alternatives, nested)
+}
+
+/// True when `alternative` ends in an unescaped `$`.
+fn ends_anchored(alternative: &str) -> bool {
+    let Some(body) = alternative.strip_suffix('$') else {
+        return false;
+    };
+    let escapes = body.chars().rev().take_while(|ch| *ch == '\\').count();
+    escapes % 2 == 0
+}
+
+/// Lint one prompt pattern of `state` as written in a template.
+pub fn lint_prompt_pattern(state: &str, pattern: &str) -> Vec<PromptLint> {
+    let lint = |kind, message: String| PromptLint {
+        state: state.to_string(),
+        pattern: pattern.to_string(),
+        kind,
+        message,
+    };
+    let mut lints = Vec::new();
+    let (alternatives, nested) = scan(pattern.trim_start_matches('^'));
+    if alternatives.len() > 1 {
+        lints.push(lint(
+            PromptLintKind::UnanchoredAlternation,
+            "only the first alternative is anchored to the line start; wrap them in (?:...)"
+                .to_string(),
+        ));
+    }
+    if let Some(open) = alternatives.iter().find(|alt| !ends_anchored(alt)) {
+        lints.push(lint(
+            PromptLintKind::UnanchoredEnd,
+            format!("{open:?} does not end in $ and also matches longer lines"),
+        ));
+    }
+    if nested {
+        lints.push(lint(
+            PromptLintKind::NestedQuantifier,
+            "quantified group contains a quantifier".to_string(),
+        ));
+    }
+    let compiled = format!("{PROMPT_REGEX_PREFIX}{}", pattern.trim_start_matches('^'));
+    if Regex::new(&compiled).is_ok_and(|regex| regex.is_match("")) {
+        lints.push(lint(
+            PromptLintKind::MatchesEmpty,
+            "matches an empty line".to_string(),
+        ));
+    }
+    lints
+}
+
+impl DeviceHandler {
+    /// Lint every prompt pattern of this handler.
+    pub fn lint_prompts(&self) -> Vec<PromptLint> {
+        self.prompt_patterns
+            .iter()
+            .flat_map(|(state, pattern)| {
+                let written = pattern.strip_prefix(PROMPT_REGEX_PREFIX).unwrap_or(pattern);
+                lint_prompt_pattern(state, &format!("^{written}"))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device::{DeviceHandlerConfig, prompt_rule, transition_rule};
+    use crate::templates;
+
+    fn kinds(pattern: &str) -> Vec<PromptLintKind> {
+        lint_prompt_pattern("enable", pattern)
+            .into_iter()
+            .map(|lint| lint.kind)
+            .collect()
+    }
+
+    #[test]
+    fn reports_anchoring_backtracking_and_empty_matches() {
+        assert!(kinds(r"^[^\s#]+#\s*$").is_empty());
+        assert!(kinds(r"^(?:\S+>|\S+#)\s*$").is_empty());
+        assert!(kinds(r"^\S+\$").contains(&PromptLintKind::UnanchoredEnd));
+        assert_eq!(
+            kinds(r"^\S+>\s*$|\S+#\s*$"),
+            [PromptLintKind::UnanchoredAlternation]
+        );
+        assert_eq!(kinds(r"^\S+#"), [PromptLintKind::UnanchoredEnd]);
+        assert_eq!(kinds(r"^(\w+\s?)+#$"), [PromptLintKind::NestedQuantifier]);
+        assert!(kinds(r"^(HRP_M|HRP_S){0,1}<.+>\s*$").is_empty());
+        assert_eq!(kinds(r"^.*$"), [PromptLintKind::MatchesEmpty]);
+    }
+
+    #[test]
+    fn state_machine_diagnostics_include_prompt_lints() {
+        let handler = DeviceHandler::new(DeviceHandlerConfig {
+            prompt: vec![
+                prompt_rule("Login", &[r"^dev>\s*$"]),
+                prompt_rule("Enable", &[r"^dev#|dev\(config\)#"]),
+            ],
+            edges: vec![
+                transition_rule("Login", "enable", "Enable", false, false),
+                transition_rule("Enable", "disable", "Login", true, false),
+            ],
+            ..Default::default()
+        })
+        .expect("handler");
+        let report = handler.diagnose_state_machine();
+        assert!(report.has_issues());
+        let lints: Vec<_> = report
+            .prompt_lints
+            .iter()
+            .map(|lint| (lint.state.as_str(), lint.kind))
+            .collect();
+        assert_eq!(
+            lints,
+            [
+                ("enable", PromptLintKind::UnanchoredAlternation),
+                ("enable", PromptLintKind::UnanchoredEnd),
+            ]
+        );
+    }
+
+    #[test]
+    fn builtin_templates_have_clean_prompts() {
+        for name in templates::available_templates() {
+            let handler = templates::by_name(name).expect("template");
+            assert_eq!(handler.lint_prompts(), [], "{name}");
+        }
+    }
+}
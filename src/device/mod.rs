@@ -5,6 +5,7 @@
 //! state transitions, and intelligent command routing based on the current device state.
 
 use std::collections::HashMap;
+use std::sync::Mutex;
 
 use once_cell::sync::Lazy;
 use regex::{Regex, RegexSet};
@@ -13,15 +14,26 @@ mod builder;
 mod config;
 mod diagnostics;
 mod execution;
+mod lint;
+mod normalize;
 mod runtime;
+mod syntax;
 mod transitions;
 
 pub use config::{
-    DeviceCommandExecutionConfig, DeviceHandlerConfig, DeviceInputRule, DevicePromptRule,
-    DevicePromptWithSysRule, DeviceShellFlavor, DeviceTransitionRule, input_rule, prompt_rule,
-    prompt_with_sys_rule, transition_rule,
+    DeviceCommandExecutionConfig, DeviceHandlerConfig, DeviceInputRule, DeviceLoginStage,
+    DevicePromptRule, DevicePromptWithSysRule, DeviceShellFlavor, DeviceTransitionRule,
+    KeystrokePacing, PatternSource, UnmodeledPromptPolicy, input_rule, login_stage, patterns,
+    prompt_rule, prompt_with_sys_rule, transition_rule,
 };
-pub use diagnostics::StateMachineDiagnostics;
+pub use diagnostics::{
+    LineClassification, PromptMatchDiagnostics, PromptNearMiss, StateMachineDiagnostics,
+};
+pub use lint::{PromptLint, PromptLintKind, lint_prompt_pattern};
+pub use normalize::VOLATILE_MASK;
+pub(crate) use syntax::edit_distance;
+pub use syntax::{SyntaxIssue, command_stems};
+pub use transitions::ModePath;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub(crate) enum CommandExecutionStrategy {
@@ -30,15 +42,54 @@ pub(crate) enum CommandExecutionStrategy {
         marker: String,
         shell_flavor: DeviceShellFlavor,
     },
+    ExecChannel,
+}
+
+/// Compiled [`DeviceLoginStage`].
+#[derive(Debug, Clone)]
+struct LoginStage {
+    username_param: Option<String>,
+    password_param: String,
+    username: RegexSet,
+    password: RegexSet,
+}
+
+impl PartialEq for LoginStage {
+    fn eq(&self, other: &Self) -> bool {
+        self.username_param == other.username_param
+            && self.password_param == other.password_param
+            && self.username.patterns() == other.username.patterns()
+            && self.password.patterns() == other.password.patterns()
+    }
+}
+
+/// Compiled per-edge judging rules of a [`DeviceTransitionRule`].
+#[derive(Debug, Clone)]
+struct EdgeRules {
+    ignore_errors: RegexSet,
+    error_regex: RegexSet,
+}
+
+impl PartialEq for EdgeRules {
+    fn eq(&self, other: &Self) -> bool {
+        self.ignore_errors.patterns() == other.ignore_errors.patterns()
+            && self.error_regex.patterns() == other.error_regex.patterns()
+    }
 }
 
 pub struct DeviceHandler {
+    /// Template name the handler was built from, if any.
+    name: Option<String>,
+
     /// Index of the current state in the `all_states` vector
     current_state_index: usize,
 
     /// All possible states the device can be in
     all_states: Vec<String>,
 
+    /// Judging rules of edges that have their own, keyed by (from, to) state.
+    edge_rules: HashMap<(String, String), EdgeRules>,
+
     /// Combined regex set for matching all state patterns
     all_regex: RegexSet,
 
@@ -61,6 +112,13 @@ pub struct DeviceHandler {
     /// Used for pathfinding during active state transitions
     edges: Vec<(String, String, String, bool, bool)>,
 
+    /// Indices into `edges` leaving each state, built once with the handler.
+    adjacency: HashMap<String, Vec<usize>>,
+
+    /// Memoized shortest paths as `edges` indices, keyed by (from, to) state;
+    /// `None` when unreachable. Dropped together with the handler.
+    path_cache: Mutex<PathCache>,
+
     /// Regex patterns for errors that should be ignored
     ignore_errors: Option<RegexSet>,
 
@@ -73,6 +131,9 @@ pub struct DeviceHandler {
     /// Captured system name from the prompt (e.g., hostname)
     sys: Option<String>,
 
+    /// Named groups captured from the last sys prompt.
+    captures: HashMap<String, String>,
+
     /// Last prompt text matched by the state machine.
     current_prompt: Option<String>,
 
@@ -81,10 +142,67 @@ pub struct DeviceHandler {
 
     /// Strategy used to determine command success for this handler.
     command_execution: CommandExecutionStrategy,
+
+    /// Whether output is replayed onto a VT100 screen before line parsing.
+    terminal_emulation: bool,
+
+    /// In-shell login stages answered in order.
+    login_chain: Vec<LoginStage>,
+
+    /// PTY width in columns.
+    terminal_width: u32,
+
+    /// Commands run once after the first prompt.
+    terminal_setup: Vec<String>,
+
+    /// Command sent last when the session closes.
+    logout_command: String,
+
+    /// Write pacing applied by the I/O task; `None` writes at full speed.
+    pacing: Option<KeystrokePacing>,
+
+    /// Messages meaning another user holds the configuration lock.
+    config_lock_regex: Vec<Regex>,
+
+    /// Asynchronous syslog lines kept out of command output.
+    syslog_regex: Option<RegexSet>,
+
+    /// Prefixes of comment lines in command lists.
+    comment_prefixes: Vec<String>,
+
+    /// Known command stems keyed by lowercase mode.
+    command_stems: HashMap<String, syntax::StemTree>,
+
+    /// Fallback for questions no input rule answers.
+    unmodeled_prompt: Option<UnmodeledPromptPolicy>,
+
+    /// Values masked by `normalize_output`.
+    volatile_regex: Vec<Regex>,
+
+    /// Index of the next login stage and whether its username was sent.
+    login_progress: (usize, bool),
+
+    /// Password parameter sent since the last prompt and how many times.
+    password_attempts: Option<(String, u32)>,
+
+    /// Password prompt seen again after the attempt limit was reached.
+    password_rejected: Option<String>,
 }
 
+/// PTY width used when a template does not set one.
+pub(crate) const DEFAULT_TERMINAL_WIDTH: u32 = 800;
+
+/// Logout command used when a template does not set one.
+pub(crate) const DEFAULT_LOGOUT_COMMAND: &str = "exit";
+
+/// Times a password parameter is sent before a repeated prompt is treated
+/// as a rejection.
+const MAX_PASSWORD_ATTEMPTS: u32 = 2;
+
 type ExitPath = Option<(String, Vec<(String, String)>)>;
 
+type PathCache = HashMap<(String, String), Option<Vec<usize>>>;
+
 /// Predefined states that exist in every device handler.
 static PRE_STATE: Lazy<Vec<String>> = Lazy::new(|| {
     vec![
@@ -0,0 +1,110 @@
+//! Masking of values that change between otherwise identical show outputs.
+
+use regex::Regex;
+
+use super::DeviceHandler;
+
+/// Replacement for every masked value.
+pub const VOLATILE_MASK: &str = "<volatile>";
+
+impl DeviceHandler {
+    /// Copy of `content` with every match of the template's
+    /// [`volatile_regex`](super::DeviceHandlerConfig::volatile_regex) replaced
+    /// by [`VOLATILE_MASK`], line by line, so outputs taken at different
+    /// times compare equal unless something else changed.
+    pub fn normalize_output(&self, content: &str) -> String {
+        if self.volatile_regex.is_empty() {
+            return content.to_string();
+        }
+        let mut normalized = String::with_capacity(content.len());
+        for line in content.split_inclusive('\n') {
+            let body = line.trim_end_matches(['\r', '\n']);
+            normalized.push_str(&mask_line(body, &self.volatile_regex));
+            normalized.push_str(&line[body.len()..]);
+        }
+        normalized
+    }
+}
+
+fn mask_line(line: &str, patterns: &[Regex]) -> String {
+    let mut spans = Vec::new();
+    for pattern in patterns {
+        for captures in pattern.captures_iter(line) {
+            if captures.len() == 1 {
+                spans.extend(captures.get(0).map(|m| m.range()));
+            } else {
+                spans.extend(captures.iter().skip(1).flatten().map(|m| m.range()));
+            }
+        }
+    }
+    if spans.is_empty() {
+        return line.to_string();
+    }
+    spans.sort_by_key(|span| (span.start, span.end));
+    let mut masked = String::with_capacity(line.len());
+    let mut cursor = 0;
+    for span in spans {
+        if span.end <= cursor {
+            continue;
+        }
+        if span.start >= cursor {
+            masked.push_str(&line[cursor..span.start]);
+            masked.push_str(VOLATILE_MASK);
+        }
+        cursor = span.end;
+    }
+    masked.push_str(&line[cursor..]);
+    masked
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::templates;
+
+    #[test]
+    fn cisco_uptime_counters_and_clock_are_masked() {
+        let cisco = templates::cisco().expect("cisco");
+        let before = "core-01 uptime is 3 weeks, 2 days, 4 hours\r\n\
+                      GigabitEthernet0/1 is up, line protocol is up\r\n\
+                      \x20 5 minute input rate 1000 bits/sec, 2 packets/sec\r\n\
+                      \x20    1234 packets input, 567890 bytes\r\n\
+                      *10:02:11.123 UTC Fri Oct 16 2026\r\n";
+        let after = "core-01 uptime is 3 weeks, 2 days, 5 hours\r\n\
+                     GigabitEthernet0/1 is up, line protocol is up\r\n\
+                     \x20 5 minute input rate 2000 bits/sec, 3 packets/sec\r\n\
+                     \x20    1300 packets input, 600000 bytes\r\n\
+                     *11:40:02.001 UTC Fri Oct 16 2026\r\n";
+        let normalized = cisco.normalize_output(before);
+        assert_eq!(normalized, cisco.normalize_output(after));
+        assert!(normalized.starts_with("core-01 uptime is <volatile>\r\n"));
+        assert!(normalized.contains("input rate <volatile> bits/sec, <volatile> packets/sec"));
+        assert!(normalized.contains("GigabitEthernet0/1 is up, line protocol is up\r\n"));
+
+        let down = after.replace("line protocol is up", "line protocol is down");
+        assert_ne!(normalized, cisco.normalize_output(&down));
+        let linux = templates::linux().expect("linux");
+        assert_eq!(linux.normalize_output(before), before);
+    }
+
+    #[test]
+    fn ipv6_addresses_are_not_taken_for_clocks() {
+        let cisco = templates::cisco().expect("cisco");
+        let output = "GigabitEthernet0/1     [up/up]\r\n\
+                      \x20   FE80::1:23:45\r\n\
+                      \x20   2001:DB8::12:34:56\r\n\
+                      .10:02:11.123 CEST Fri Oct 16 2026\r\n";
+        assert_eq!(
+            cisco.normalize_output(output),
+            "GigabitEthernet0/1     [up/up]\r\n\
+             \x20   FE80::1:23:45\r\n\
+             \x20   2001:DB8::12:34:56\r\n\
+             .<volatile> CEST Fri Oct 16 2026\r\n"
+        );
+
+        let huawei = templates::huawei().expect("huawei");
+        assert_eq!(
+            huawei.normalize_output("fe80::1:23:45\n2026-10-16 10:02:11+08:00\n"),
+            "fe80::1:23:45\n2026-10-16 <volatile>+08:00\n"
+        );
+    }
+}
@@ -1,10 +1,15 @@
-use log::trace;
+use std::collections::HashMap;
+
+use log::{trace, warn};
+
+use crate::error::ConnectError;
 
 use super::{
-    DeviceHandler, STRIP_CSI_ESCAPE, STRIP_DCS_ESCAPE, STRIP_OSC_ESCAPE, STRIP_SIMPLE_ESCAPE,
+    DeviceHandler, MAX_PASSWORD_ATTEMPTS, STRIP_CSI_ESCAPE, STRIP_DCS_ESCAPE, STRIP_OSC_ESCAPE,
+    STRIP_SIMPLE_ESCAPE,
 };
 
-fn sanitize_terminal_line(line: &str) -> String {
+pub(super) fn sanitize_terminal_line(line: &str) -> String {
     let without_osc = STRIP_OSC_ESCAPE.replace_all(line, "");
     let without_dcs = STRIP_DCS_ESCAPE.replace_all(without_osc.as_ref(), "");
     let without_csi = STRIP_CSI_ESCAPE.replace_all(without_dcs.as_ref(), "");
@@ -15,12 +20,21 @@ fn sanitize_terminal_line(line: &str) -> String {
         .collect()
 }
 
+/// Named groups captured from a sys prompt line.
+#[derive(Debug, Default)]
+struct PromptCaptures {
+    /// Value of the rule's `capture_group`.
+    sys: Option<String>,
+    /// Every named group that participated in the match.
+    groups: HashMap<String, String>,
+}
+
 impl DeviceHandler {
     /// Converts a line of output to a state.
     ///
     /// Matches the line against all known regex patterns and returns the corresponding state.
     /// If no match is found, defaults to the "Output" state.
-    fn line2state(&self, line: &str, need_catch: bool) -> (usize, &str, Option<String>) {
+    fn line2state(&self, line: &str, need_catch: bool) -> (usize, &str, PromptCaptures) {
         let matches: Vec<_> = self.all_regex.matches(line).into_iter().collect();
         if matches.is_empty() {
             let state = self
@@ -28,9 +42,9 @@ impl DeviceHandler {
                 .first()
                 .map(|s| s.as_str())
                 .unwrap_or("output");
-            return (0, state, None);
+            return (0, state, PromptCaptures::default());
         }
-        let mut current_state_catch = None;
+        let mut current_state_catch = PromptCaptures::default();
         let index = match matches.first() {
             Some(v) => *v,
             None => {
@@ -39,14 +53,19 @@ impl DeviceHandler {
                     .first()
                     .map(|s| s.as_str())
                     .unwrap_or("output");
-                return (0, state, None);
+                return (0, state, PromptCaptures::default());
             }
         };
         if need_catch
             && let Some((regex, catch)) = self.catch_map.get(&index)
             && let Some(caps) = regex.captures(line)
         {
-            current_state_catch = caps.name(catch).map(|s| s.as_str().to_string());
+            current_state_catch.sys = caps.name(catch).map(|s| s.as_str().to_string());
+            current_state_catch.groups = regex
+                .capture_names()
+                .flatten()
+                .filter_map(|name| Some((name.to_string(), caps.name(name)?.as_str().to_string())))
+                .collect();
         }
         let state_index = self.regex_index_map.get(&index).copied().unwrap_or(0);
         let state = self
@@ -69,7 +88,9 @@ impl DeviceHandler {
         } else {
             if self.match_prompt(state_index) {
                 trace!("State captured value: '{:?}'", catch);
-                self.sys = catch;
+                self.password_attempts = None;
+                self.sys = catch.sys;
+                self.captures = catch.groups;
                 self.current_prompt = Some(sanitized_line);
             }
 
@@ -77,14 +98,14 @@ impl DeviceHandler {
         }
     }
 
-    fn ignore_error(&self, line: &str) -> bool {
+    pub(super) fn ignore_error(&self, line: &str) -> bool {
         self.ignore_errors
             .as_ref()
             .map(|set| set.is_match(line))
             .unwrap_or(false)
     }
 
-    fn match_prompt(&self, index: usize) -> bool {
+    pub(super) fn match_prompt(&self, index: usize) -> bool {
         let (start, end) = self.prompt_index;
         index >= start && index <= end
     }
@@ -117,14 +138,82 @@ impl DeviceHandler {
     pub fn read_need_write(&mut self, line: &str) -> Option<(String, bool)> {
         let sanitized_line = sanitize_terminal_line(line);
         trace!("Checking if input is required: '{:?}'", sanitized_line);
+        if let Some(input) = self.read_login_chain(&sanitized_line) {
+            return Some((input, false));
+        }
         let (_, input, _) = self.line2state(&sanitized_line, false);
-        if let Some((is_dyn, s, is_record)) = self.input_map.get(input) {
-            if *is_dyn {
-                return self.dyn_param.get(s).map(|cmd| (cmd.clone(), *is_record));
+        let (is_dyn, s, is_record) = self.input_map.get(input)?.clone();
+        if !is_dyn {
+            return Some((s, is_record));
+        }
+        let cmd = self.dyn_param.get(&s)?.clone();
+        if s.to_ascii_lowercase().ends_with("password") && !self.count_password(&s) {
+            self.password_rejected = Some(sanitized_line);
+            return None;
+        }
+        Some((cmd, is_record))
+    }
+
+    /// Record one more send of password `param`; false once the limit is hit.
+    ///
+    /// The count starts over when a prompt is reached or another password
+    /// parameter is asked for.
+    fn count_password(&mut self, param: &str) -> bool {
+        match self.password_attempts.as_mut() {
+            Some((sent, attempts)) if sent == param => {
+                if *attempts >= MAX_PASSWORD_ATTEMPTS {
+                    return false;
+                }
+                *attempts += 1;
             }
-            return Some((s.clone(), *is_record));
+            _ => self.password_attempts = Some((param.to_string(), 1)),
+        }
+        true
+    }
+
+    /// Take the error for a password prompt that kept coming back.
+    ///
+    /// Set when [`read_need_write`](Self::read_need_write) declines to send a
+    /// password parameter again; callers should stop and return the error.
+    pub fn take_password_rejection(&mut self) -> Option<ConnectError> {
+        let prompt = self.password_rejected.take()?;
+        let (param, attempts) = self.password_attempts.take()?;
+        Some(ConnectError::EnableAuthFailed {
+            param,
+            attempts,
+            prompt,
+        })
+    }
+
+    /// Answer the current login-chain stage's username or password prompt.
+    ///
+    /// A stage's password is only sent after its username (when it has one),
+    /// so a later enable-password prompt is left to the input rules.
+    fn read_login_chain(&mut self, line: &str) -> Option<String> {
+        let (index, username_sent) = self.login_progress;
+        let stage = self.login_chain.get(index)?;
+        let param = if let Some(username_param) = stage.username_param.as_ref()
+            && stage.username.is_match(line)
+        {
+            self.login_progress.1 = true;
+            username_param
+        } else if (username_sent || stage.username_param.is_none()) && stage.password.is_match(line)
+        {
+            self.login_progress = (index + 1, false);
+            &stage.password_param
+        } else {
+            return None;
+        };
+        let value = self.dyn_param.get(param).cloned();
+        if value.is_none() {
+            warn!("login_chain stage {index}: dyn_param '{param}' is not set");
         }
-        None
+        value
+    }
+
+    /// Template name from [`DeviceHandlerConfig::name`](super::DeviceHandlerConfig::name).
+    pub fn template_name(&self) -> Option<&str> {
+        self.name.as_deref()
     }
 
     /// Returns the current state name.
@@ -140,6 +229,12 @@ impl DeviceHandler {
         self.sys.as_deref()
     }
 
+    /// Returns every named group captured from the current sys prompt, e.g.
+    /// `vsys` and `ha_role`; empty on prompts without captures.
+    pub fn current_captures(&self) -> &HashMap<String, String> {
+        &self.captures
+    }
+
     /// Returns last prompt text matched by the state machine.
     pub fn current_prompt(&self) -> Option<&str> {
         self.current_prompt.as_deref()
@@ -164,8 +259,94 @@ impl DeviceHandler {
 #[cfg(test)]
 mod tests {
     use super::super::build_test_handler;
+    use crate::device::{DeviceHandlerConfig, input_rule, login_stage, prompt_rule};
+    use crate::error::ConnectError;
     use crate::templates;
 
+    #[test]
+    fn login_chain_answers_nested_logins_in_order() {
+        let mut handler = DeviceHandlerConfig {
+            prompt: vec![prompt_rule("Enable", &[r"^dev#\s*$"])],
+            login_chain: vec![
+                login_stage(Some("ConsoleUser"), "ConsolePassword"),
+                login_stage(Some("DeviceUser"), "DevicePassword"),
+            ],
+            ..Default::default()
+        }
+        .build()
+        .expect("handler");
+        for (key, value) in [
+            ("ConsoleUser", "ops\n"),
+            ("ConsolePassword", "console-secret\n"),
+            ("DeviceUser", "admin\n"),
+            ("DevicePassword", "device-secret\n"),
+        ] {
+            handler.dyn_param.insert(key.to_string(), value.to_string());
+        }
+
+        // A password prompt before the stage's username is not answered.
+        assert_eq!(handler.read_need_write("Password:"), None);
+        let answers: Vec<_> = ["Username:", "Password: ", "login:", "Password:"]
+            .into_iter()
+            .map(|line| handler.read_need_write(line).map(|(value, _)| value))
+            .collect();
+        assert_eq!(
+            answers,
+            [
+                Some("ops\n".to_string()),
+                Some("console-secret\n".to_string()),
+                Some("admin\n".to_string()),
+                Some("device-secret\n".to_string()),
+            ]
+        );
+        // The chain is exhausted; later prompts fall through to input rules.
+        assert_eq!(handler.read_need_write("Username:"), None);
+    }
+
+    #[test]
+    fn repeated_enable_password_prompt_stops_resending() {
+        let mut handler = DeviceHandlerConfig {
+            prompt: vec![
+                prompt_rule("Login", &[r"^dev>\s*$"]),
+                prompt_rule("Enable", &[r"^dev#\s*$"]),
+            ],
+            write: vec![input_rule(
+                "EnablePassword",
+                true,
+                "EnablePassword",
+                false,
+                &[r"^Password:\s*$"],
+            )],
+            ..Default::default()
+        }
+        .build()
+        .expect("handler");
+        handler
+            .dyn_param
+            .insert("EnablePassword".to_string(), "wrong\n".to_string());
+
+        // A prompt between attempts means the password was accepted.
+        assert!(handler.read_need_write("Password:").is_some());
+        handler.read("dev#");
+        assert!(handler.read_need_write("Password:").is_some());
+        assert!(handler.read_need_write("Password:").is_some());
+        assert!(handler.take_password_rejection().is_none());
+
+        assert_eq!(handler.read_need_write("Password:"), None);
+        match handler.take_password_rejection() {
+            Some(ConnectError::EnableAuthFailed {
+                param,
+                attempts,
+                prompt,
+            }) => {
+                assert_eq!(param, "EnablePassword");
+                assert_eq!(attempts, 2);
+                assert_eq!(prompt, "Password:");
+            }
+            other => panic!("unexpected: {other:?}"),
+        }
+    }
+
     #[test]
     fn error_state_is_detected_after_error_line() {
         let mut handler = build_test_handler();
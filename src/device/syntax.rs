@@ -0,0 +1,187 @@
+//! Known command stems per mode, used to catch typos before anything is
+//! sent.
+//!
+//! A stem is one or more leading words of a valid command, e.g. `show` or
+//! `ip route`. A command passes when its words follow a stem, each word
+//! possibly abbreviated as the CLI allows (`sh ver`); once a stem ends, any
+//! further words are accepted. Modes without stems are not checked.
+
+use std::collections::{BTreeMap, HashMap};
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use super::DeviceHandler;
+
+/// Words of the stems of one mode, as a prefix tree.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct StemTree {
+    /// A stem ends here, so later words are arguments.
+    complete: bool,
+    children: BTreeMap<String, StemTree>,
+}
+
+impl StemTree {
+    pub(crate) fn new<S: AsRef<str>>(stems: &[S]) -> Self {
+        let mut tree = Self::default();
+        for stem in stems {
+            let mut node = &mut tree;
+            for word in stem.as_ref().split_whitespace() {
+                node = node.children.entry(word.to_ascii_lowercase()).or_default();
+            }
+            node.complete = true;
+        }
+        tree
+    }
+
+    /// The first word of `command` no stem continues with, and the closest
+    /// known word.
+    fn unknown_word(&self, command: &str) -> Option<(String, Option<String>)> {
+        let mut node = self;
+        for word in command.split_whitespace() {
+            if node.complete || node.children.is_empty() {
+                return None;
+            }
+            let lower = word.to_ascii_lowercase();
+            let mut matches = node
+                .children
+                .iter()
+                .filter(|(known, _)| known.starts_with(&lower));
+            node = match (matches.next(), matches.next()) {
+                (Some((_, next)), None) => next,
+                // Ambiguous abbreviations are left for the device to reject.
+                (Some(_), Some(_)) => node.children.get(&lower)?,
+                (None, _) => return Some((word.to_string(), node.closest(&lower))),
+            };
+        }
+        None
+    }
+
+    fn closest(&self, word: &str) -> Option<String> {
+        let word: Vec<char> = word.chars().collect();
+        self.children
+            .keys()
+            .map(|known| {
+                let known_chars: Vec<char> = known.chars().collect();
+                (edit_distance(&word, &known_chars), known)
+            })
+            .filter(|(distance, _)| *distance <= 2)
+            .min_by_key(|(distance, _)| *distance)
+            .map(|(_, known)| known.clone())
+    }
+}
+
+/// Levenshtein distance between two sequences, e.g. the characters of
+/// two words.
+pub(crate) fn edit_distance<T: PartialEq>(a: &[T], b: &[T]) -> usize {
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+    for (i, ca) in a.iter().enumerate() {
+        current[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    previous[b.len()]
+}
+
+/// A command that matches no known stem of its mode.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct SyntaxIssue {
+    pub mode: String,
+    pub command: String,
+    /// First word no stem continues with.
+    pub word: String,
+    /// Known word closest to `word`, e.g. `show` for `shw`.
+    pub suggestion: Option<String>,
+}
+
+/// Build [`DeviceHandlerConfig::command_stems`](super::DeviceHandlerConfig::command_stems)
+/// from `(mode, stems)` pairs.
+pub fn command_stems(modes: &[(&str, &[&str])]) -> HashMap<String, Vec<String>> {
+    modes
+        .iter()
+        .map(|(mode, stems)| {
+            (
+                mode.to_string(),
+                stems.iter().map(|stem| stem.to_string()).collect(),
+            )
+        })
+        .collect()
+}
+
+impl DeviceHandler {
+    /// Whether commands in `mode` are checked by [`Self::check_syntax`].
+    pub fn has_command_stems(&self, mode: &str) -> bool {
+        self.command_stems.contains_key(&mode.to_ascii_lowercase())
+    }
+
+    /// Flag `command` when it matches no known stem of `mode`.
+    ///
+    /// Returns `None` for modes without stems.
+    pub fn check_syntax(&self, mode: &str, command: &str) -> Option<SyntaxIssue> {
+        let tree = self.command_stems.get(&mode.to_ascii_lowercase())?;
+        let (word, suggestion) = tree.unknown_word(command)?;
+        Some(SyntaxIssue {
+            mode: mode.to_string(),
+            command: command.to_string(),
+            word,
+            suggestion,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::templates;
+
+    #[test]
+    fn abbreviations_pass_and_typos_get_suggestions() {
+        let tree = StemTree::new(&["show", "ip route", "ip address", "interface"]);
+        assert_eq!(tree.unknown_word("sh ver"), None);
+        assert_eq!(
+            tree.unknown_word("ip route 0.0.0.0 0.0.0.0 192.0.2.1"),
+            None
+        );
+        assert_eq!(tree.unknown_word("int Gi0/1"), None);
+        assert_eq!(
+            tree.unknown_word("shw version"),
+            Some(("shw".to_string(), Some("show".to_string())))
+        );
+        assert_eq!(
+            tree.unknown_word("ip rute 10.0.0.0/8"),
+            Some(("rute".to_string(), Some("route".to_string())))
+        );
+        assert_eq!(
+            tree.unknown_word("reload in 5"),
+            Some(("reload".to_string(), None))
+        );
+        assert_eq!(tree.unknown_word(""), None);
+    }
+
+    #[test]
+    fn builtin_stems_accept_template_transitions() {
+        for name in templates::available_templates() {
+            let config = templates::by_name_config(name).expect("template");
+            let handler = config.build().expect("handler");
+            for edge in &config.edges {
+                if handler.has_command_stems(&edge.from_state) {
+                    assert_eq!(
+                        handler.check_syntax(&edge.from_state, &edge.command),
+                        None,
+                        "{name}"
+                    );
+                }
+            }
+        }
+        let cisco = templates::cisco().expect("cisco");
+        let issue = cisco
+            .check_syntax("Enable", "shw version")
+            .expect("typo flagged");
+        assert_eq!(issue.suggestion.as_deref(), Some("show"));
+        assert_eq!(cisco.check_syntax("Config", "no shutdown"), None);
+    }
+}
@@ -1,10 +1,23 @@
 use std::collections::{HashMap, HashSet, VecDeque};
 
 use log::trace;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
 
 use super::{DeviceHandler, ExitPath};
 use crate::error::ConnectError;
 
+/// A mode the session can switch to from its current state.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct ModePath {
+    /// Lowercase state name, as accepted by [`Command::mode`](crate::session::Command::mode).
+    pub mode: String,
+    /// Transition commands sent to get there; empty for the current mode.
+    pub commands: Vec<String>,
+    /// Dyn_params the path needs but nobody set; `commands` is empty then.
+    pub missing_params: Vec<String>,
+}
+
 impl DeviceHandler {
     /// Finds the path to exit from system-specific prompts.
     fn exit_until_no_sys(&self, sys: Option<&String>) -> Result<ExitPath, ConnectError> {
@@ -21,7 +34,7 @@ impl DeviceHandler {
         loop {
             if let Some((cmd, end, format)) = edge_map.get(current) {
                 path.push((
-                    Self::format_cmd(**format, cmd, sys.map(|s| s.as_str())),
+                    self.format_cmd(**format, cmd, sys.map(|s| s.as_str())),
                     (*end).to_string(),
                 ));
                 if let Some(index) = self.all_states.iter().position(|v| v.eq(*end)) {
@@ -38,17 +51,124 @@ impl DeviceHandler {
         }
     }
 
+    /// Exit commands leading from the current state back to the state the
+    /// session logged in to, following exit edges until none is left.
+    ///
+    /// Stops early at an exit that needs a system name or an unresolved
+    /// placeholder.
+    pub(crate) fn logout_path(&self) -> Vec<String> {
+        let mut path = Vec::new();
+        let mut visited = HashSet::new();
+        let mut current = self.current_state();
+        while visited.insert(current) {
+            let Some((_, cmd, end, _, format)) = self
+                .edges
+                .iter()
+                .find(|(start, _, _, exit, _)| *exit && start == current)
+            else {
+                break;
+            };
+            let cmd = self.format_cmd(*format, cmd, None);
+            if cmd.is_empty() || placeholders(&cmd).next().is_some() {
+                break;
+            }
+            path.push(cmd);
+            current = end;
+        }
+        path
+    }
+
     /// Formats a command string with system name substitution.
-    fn format_cmd(format: bool, cmd: &str, sys: Option<&str>) -> String {
-        if format {
-            if let Some(s) = sys {
-                cmd.replace("{}", s)
-            } else {
-                String::new()
+    ///
+    /// `{}` takes the sys value and `{name}` the group of that name captured
+    /// from the current prompt, or else the dyn_param of that name. Unknown
+    /// names are left in place for [`Self::check_placeholders`].
+    fn format_cmd(&self, format: bool, cmd: &str, sys: Option<&str>) -> String {
+        if !format {
+            return cmd.to_string();
+        }
+        let mut cmd = if cmd.contains("{}") {
+            match sys {
+                Some(s) => cmd.replace("{}", s),
+                None => return String::new(),
             }
         } else {
             cmd.to_string()
+        };
+        let template = cmd.clone();
+        for name in placeholders(&template) {
+            let value = self.captures.get(name).map(String::as_str).or_else(|| {
+                // Parameters are stored ready to send, usually with a newline.
+                self.dyn_param
+                    .get(name)
+                    .map(|value| value.trim_end_matches(['\r', '\n']))
+            });
+            if let Some(value) = value {
+                cmd = cmd.replace(&format!("{{{name}}}"), value);
+            }
         }
+        cmd
+    }
+
+    /// Fail when a planned command still has unresolved placeholders.
+    fn check_placeholders(path: &[(String, String)]) -> Result<(), ConnectError> {
+        for (command, _) in path {
+            let keys: Vec<String> = placeholders(command).map(str::to_string).collect();
+            if !keys.is_empty() {
+                return Err(ConnectError::UnresolvedPlaceholders {
+                    command: command.clone(),
+                    keys,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Shortest edge path from `from` to `to` as indices into `edges`.
+    ///
+    /// Memoized per (from, to): the graph never changes after the handler
+    /// is built, and sys values and placeholders only affect how the edge
+    /// commands are formatted afterwards.
+    fn shortest_path(&self, from: &str, to: &str) -> Option<Vec<usize>> {
+        let key = (from.to_string(), to.to_string());
+        let mut cache = self
+            .path_cache
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Some(path) = cache.get(&key) {
+            return path.clone();
+        }
+
+        let mut queue = VecDeque::from([from]);
+        let mut visited = HashSet::from([from]);
+        // State -> edge index it was first reached by.
+        let mut reached_by: HashMap<&str, usize> = HashMap::new();
+        while let Some(current) = queue.pop_front() {
+            trace!("Current node: '{:?}'", current);
+            if current == to {
+                break;
+            }
+            for &index in self.adjacency.get(current).into_iter().flatten() {
+                let next = self.edges[index].2.as_str();
+                if visited.insert(next) {
+                    reached_by.insert(next, index);
+                    queue.push_back(next);
+                }
+            }
+        }
+
+        let path = reached_by.contains_key(to).then(|| {
+            let mut path = Vec::new();
+            let mut current = to;
+            while let Some(&index) = reached_by.get(current) {
+                path.push(index);
+                current = self.edges[index].0.as_str();
+            }
+            path.reverse();
+            path
+        });
+        cache.insert(key, path.clone());
+        path
     }
 
     /// Calculates the commands needed to transition to a target state.
@@ -72,71 +192,71 @@ impl DeviceHandler {
         }
 
         if start_node == end_node {
+            Self::check_placeholders(&switch_path)?;
             return Ok(switch_path);
         }
 
-        let mut adj_list: HashMap<String, Vec<(String, String)>> = HashMap::new();
-        for (from, label, to, _, format) in &self.edges {
-            adj_list.entry(from.clone()).or_default().push((
+        let path = self
+            .shortest_path(&start_node, end_node)
+            .ok_or_else(|| ConnectError::UnreachableState(end_node.to_string()))?;
+        for index in path {
+            let (_, label, to, _, format) = &self.edges[index];
+            switch_path.push((
+                self.format_cmd(*format, label, sys.map(|s| s.as_str())),
                 to.clone(),
-                Self::format_cmd(*format, label, sys.map(|s| s.as_str())),
             ));
         }
+        Self::check_placeholders(&switch_path)?;
+        trace!("Command path: '{:?}'", switch_path);
+        Ok(switch_path)
+    }
+}
 
-        let mut queue = VecDeque::new();
-        queue.push_back(start_node.clone());
-
-        let mut visited = HashSet::new();
-        visited.insert(start_node.clone());
-
-        let mut predecessors: HashMap<String, (String, String)> = HashMap::new();
-
-        while let Some(current_node) = queue.pop_front() {
-            trace!("Current node: '{:?}'", current_node);
-            if current_node == end_node {
-                break;
-            }
-
-            if let Some(neighbors) = adj_list.get(&current_node) {
-                for (neighbor_node, edge_label) in neighbors {
-                    if !visited.contains(neighbor_node) {
-                        visited.insert(neighbor_node.clone());
-                        predecessors.insert(
-                            neighbor_node.clone(),
-                            (current_node.clone(), edge_label.clone()),
-                        );
-                        queue.push_back(neighbor_node.clone());
-                    }
-                }
+impl DeviceHandler {
+    /// Prompt states reachable from the current state, each with the
+    /// commands leading there.
+    ///
+    /// Modes whose path needs missing dyn_params are listed with them;
+    /// unreachable modes are left out.
+    pub fn available_modes(&self, sys: Option<&String>) -> Vec<ModePath> {
+        let (first, last) = self.prompt_index;
+        let mut seen = HashSet::new();
+        let mut modes = Vec::new();
+        for mode in self.all_states.get(first..=last).unwrap_or_default() {
+            if !seen.insert(mode) {
+                continue;
             }
-        }
-
-        if !predecessors.contains_key(end_node) {
-            return Err(ConnectError::UnreachableState(end_node.to_string()));
-        }
-
-        let mut current = end_node.to_string();
-        let mut path = Vec::new();
-
-        while current != start_node {
-            if let Some((parent, edge_label)) = predecessors.get(&current) {
-                path.push((edge_label.clone(), current.clone()));
-                current = parent.clone();
-            } else {
-                return Err(ConnectError::InternalServerError(format!(
-                    "failed to backtrack path from '{}' to '{}'",
-                    end_node, start_node
-                )));
+            match self.trans_state_write(mode, sys) {
+                Ok(path) => modes.push(ModePath {
+                    mode: mode.clone(),
+                    commands: path.into_iter().map(|(command, _)| command).collect(),
+                    missing_params: Vec::new(),
+                }),
+                Err(ConnectError::UnresolvedPlaceholders { keys, .. }) => modes.push(ModePath {
+                    mode: mode.clone(),
+                    commands: Vec::new(),
+                    missing_params: keys,
+                }),
+                Err(_) => {}
             }
         }
-
-        path.reverse();
-        switch_path.extend(path);
-        trace!("Command path: '{:?}'", switch_path);
-        Ok(switch_path)
+        modes
     }
 }
 
+/// Names of the `{name}` placeholders in `cmd`; `{}` is not one.
+fn placeholders(cmd: &str) -> impl Iterator<Item = &str> {
+    cmd.split('{').skip(1).filter_map(|rest| {
+        let name = &rest[..rest.find('}')?];
+        let valid = name
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        valid.then_some(name)
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::super::build_test_handler;
@@ -173,4 +293,149 @@ mod tests {
             other => panic!("unexpected error type: {other}"),
         }
     }
+
+    #[test]
+    fn computed_paths_are_memoized_per_state_pair() {
+        let mut handler = build_test_handler();
+        handler.read("dev>");
+        let first = handler.trans_state_write("config", None).expect("path");
+        assert_eq!(
+            handler.trans_state_write("config", None).expect("path"),
+            first
+        );
+        assert!(handler.trans_state_write("missing", None).is_err());
+
+        let cache = handler.path_cache.lock().expect("cache");
+        assert_eq!(cache.len(), 2);
+        assert_eq!(
+            cache[&("login".to_string(), "missing".to_string())],
+            None::<Vec<usize>>
+        );
+    }
+
+    #[test]
+    fn available_modes_list_the_path_to_each_prompt_state() {
+        let mut handler = build_test_handler();
+        handler.read("dev#");
+
+        let modes = handler.available_modes(None);
+        let paths: Vec<_> = modes
+            .iter()
+            .map(|mode| (mode.mode.as_str(), mode.commands.clone()))
+            .collect();
+        assert_eq!(
+            paths,
+            [
+                ("login", vec!["exit".to_string()]),
+                ("enable", vec![]),
+                ("config", vec!["configure terminal".to_string()]),
+            ]
+        );
+        assert!(modes.iter().all(|mode| mode.missing_params.is_empty()));
+    }
+
+    #[test]
+    fn logout_path_walks_exit_edges_to_login_state() {
+        let mut handler = build_test_handler();
+        handler.read("dev(cfg)#");
+        assert_eq!(handler.logout_path(), ["exit", "exit"]);
+        assert_eq!(handler.logout_command(), "exit");
+
+        handler.read("dev>");
+        assert!(handler.logout_path().is_empty());
+
+        let huawei = crate::templates::huawei().expect("huawei");
+        assert_eq!(huawei.logout_command(), "quit");
+    }
+
+    #[test]
+    fn sys_prompt_captures_every_named_group() {
+        use crate::device::{
+            DeviceHandlerConfig, prompt_rule, prompt_with_sys_rule, transition_rule,
+        };
+
+        let mut handler = DeviceHandlerConfig {
+            prompt: vec![prompt_rule("Enable", &[r"^[^\s(]+#\s*$"])],
+            prompt_with_sys: vec![prompt_with_sys_rule(
+                "VsysEnable",
+                "vsys",
+                r"^\S+\((?<vsys>[^:)]+):(?<ha_role>active|passive)\)#\s*$",
+            )],
+            edges: vec![
+                transition_rule(
+                    "VsysEnable",
+                    "set vsys {vsys} role {ha_role}",
+                    "VsysEnable",
+                    false,
+                    true,
+                ),
+                transition_rule("VsysEnable", "exit", "Enable", true, false),
+                transition_rule("Enable", "switch {}", "VsysEnable", false, true),
+            ],
+            ..DeviceHandlerConfig::default()
+        }
+        .build()
+        .expect("handler");
+
+        handler.read("fw-01(vsys2:active)#");
+        assert_eq!(handler.current_sys(), Some("vsys2"));
+        assert_eq!(handler.current_captures()["ha_role"], "active");
+        assert_eq!(
+            handler.format_cmd(true, "set vsys {vsys} role {ha_role}", None),
+            "set vsys vsys2 role active"
+        );
+        assert_eq!(handler.format_cmd(true, "switch {}", None), "");
+
+        handler.read("fw-01#");
+        assert!(handler.current_captures().is_empty());
+    }
+
+    #[test]
+    fn named_placeholders_resolve_from_captures_and_dyn_param() {
+        use crate::device::{
+            DeviceHandlerConfig, prompt_rule, prompt_with_sys_rule, transition_rule,
+        };
+
+        let mut handler = DeviceHandlerConfig {
+            prompt: vec![
+                prompt_rule("Enable", &[r"^[^\s(]+#\s*$"]),
+                prompt_rule("Context", &[r"^\S+/\S+#\s*$"]),
+            ],
+            prompt_with_sys: vec![prompt_with_sys_rule(
+                "VsysEnable",
+                "vsys",
+                r"^\S+\((?<vsys>[^:)]+):(?<ha_role>\w+)\)#\s*$",
+            )],
+            edges: vec![
+                transition_rule("VsysEnable", "exit", "Enable", true, false),
+                transition_rule("Enable", "context {ctx} {timeout}", "Context", false, true),
+                transition_rule("VsysEnable", "peer {ha_role} {ctx}", "Context", false, true),
+            ],
+            ..DeviceHandlerConfig::default()
+        }
+        .build()
+        .expect("handler");
+
+        handler.read("fw-01#");
+        match handler.trans_state_write("context", None) {
+            Err(ConnectError::UnresolvedPlaceholders { command, keys }) => {
+                assert_eq!(command, "context {ctx} {timeout}");
+                assert_eq!(keys, ["ctx", "timeout"]);
+            }
+            other => panic!("unexpected result: {other:?}"),
+        }
+
+        handler
+            .dyn_param
+            .insert("ctx".to_string(), "admin\n".to_string());
+        handler
+            .dyn_param
+            .insert("timeout".to_string(), "30".to_string());
+        let path = handler.trans_state_write("context", None).expect("path");
+        assert_eq!(path[0].0, "context admin 30");
+
+        handler.read("fw-01(vsys2:standby)#");
+        let path = handler.trans_state_write("context", None).expect("path");
+        assert_eq!(path[0].0, "peer standby admin");
+    }
 }
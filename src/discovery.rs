@@ -0,0 +1,672 @@
+//! Template discovery before the first SSH login.
+//!
+//! [`discover_template`] asks the device for `sysDescr.0` and
+//! `sysObjectID.0` with one SNMPv2c GET and maps the vendor to a built-in
+//! template. When SNMP is closed, filtered or inconclusive it falls back to
+//! the SSH identification banner (`SSH-2.0-Cisco-1.25`, `SSH-2.0-HUAWEI-1.5`,
+//! ...), which the server sends before any authentication.
+
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncReadExt;
+use tokio::net::{TcpStream, UdpSocket};
+
+use crate::error::ConnectError;
+use crate::session::{AddressFamily, ResolvedAddrs, bare_host};
+
+const SYS_DESCR: &[u32] = &[1, 3, 6, 1, 2, 1, 1, 1, 0];
+const SYS_OBJECT_ID: &[u32] = &[1, 3, 6, 1, 2, 1, 1, 2, 0];
+
+/// `sysObjectID` prefix of vendor enterprise numbers.
+const ENTERPRISES: &str = "1.3.6.1.4.1.";
+
+/// Enterprise numbers of the vendors behind built-in templates.
+const ENTERPRISE_TEMPLATES: &[(u32, &str)] = &[
+    (9, "cisco"),
+    (2011, "huawei"),
+    (25506, "h3c"),
+    (2636, "juniper"),
+    (30065, "arista"),
+    (12356, "fortinet"),
+    (25461, "paloalto"),
+    (2620, "checkpoint"),
+    (28557, "hillstone"),
+    (5651, "maipu"),
+    (7564, "array"),
+    (8072, "linux"),
+];
+
+/// `sysDescr` patterns, tried when the enterprise number is unknown.
+const DESCR_TEMPLATES: &[(&str, &str)] = &[
+    (r"(?i)\bh3c\b|comware", "h3c"),
+    (r"(?i)huawei|versatile routing platform", "huawei"),
+    (r"(?i)cisco", "cisco"),
+    (r"(?i)junos|juniper", "juniper"),
+    (r"(?i)arista", "arista"),
+    (r"(?i)fortigate|fortios", "fortinet"),
+    (r"(?i)palo alto|pan-os", "paloalto"),
+    (r"(?i)hillstone|stoneos", "hillstone"),
+    (r"(?i)maipu", "maipu"),
+    (r"(?i)^linux\b", "linux"),
+];
+
+/// SSH banner patterns; plain OpenSSH banners are ambiguous and not matched.
+const BANNER_TEMPLATES: &[(&str, &str)] = &[
+    (r"(?i)^SSH-[\d.]+-Cisco", "cisco"),
+    (r"(?i)^SSH-[\d.]+-HUAWEI", "huawei"),
+    (r"(?i)^SSH-[\d.]+-Comware", "h3c"),
+    (r"(?i)^SSH-[\d.]+-PaloAltoNetworks", "paloalto"),
+    (
+        r"(?i)^SSH-[\d.]+-OpenSSH\S*\s+(Ubuntu|Debian|Raspbian)",
+        "linux",
+    ),
+];
+
+/// Where discovery looks and how long it waits.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiscoveryOptions {
+    /// SNMPv2c read community.
+    pub community: String,
+    pub snmp_port: u16,
+    /// SSH port read for the banner fallback.
+    pub ssh_port: u16,
+    /// Deadline for each of the SNMP request and the banner read.
+    pub timeout: Duration,
+    /// Skip SNMP and go straight to the banner.
+    pub snmp: bool,
+}
+
+impl Default for DiscoveryOptions {
+    fn default() -> Self {
+        Self {
+            community: "public".to_string(),
+            snmp_port: 161,
+            ssh_port: 22,
+            timeout: Duration::from_secs(2),
+            snmp: true,
+        }
+    }
+}
+
+impl DiscoveryOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_community(mut self, community: impl Into<String>) -> Self {
+        self.community = community.into();
+        self
+    }
+
+    pub fn with_snmp_port(mut self, snmp_port: u16) -> Self {
+        self.snmp_port = snmp_port;
+        self
+    }
+
+    pub fn with_ssh_port(mut self, ssh_port: u16) -> Self {
+        self.ssh_port = ssh_port;
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn with_snmp(mut self, snmp: bool) -> Self {
+        self.snmp = snmp;
+        self
+    }
+}
+
+/// `sysDescr.0` and `sysObjectID.0` of a device.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct SnmpSystem {
+    pub sys_descr: String,
+    /// Dotted OID, e.g. `1.3.6.1.4.1.9.1.1208`.
+    pub sys_object_id: String,
+}
+
+/// What identified the template.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DiscoverySource {
+    Snmp,
+    Banner,
+}
+
+/// Result of [`discover_template`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct Discovery {
+    /// Built-in template name, e.g. `cisco`.
+    pub template: String,
+    pub source: DiscoverySource,
+    /// SNMP answer, also kept when only the banner was conclusive.
+    pub snmp: Option<SnmpSystem>,
+    pub banner: Option<String>,
+}
+
+/// Pick the template for `host`: SNMP first, then the SSH banner.
+///
+/// Fails with [`ConnectError::TemplateNotDetected`] when neither names a
+/// known vendor.
+pub async fn discover_template(
+    host: &str,
+    options: &DiscoveryOptions,
+) -> Result<Discovery, ConnectError> {
+    let mut reasons = Vec::new();
+    let mut snmp = None;
+    if options.snmp {
+        match snmp_system(host, options).await {
+            Ok(system) => {
+                if let Some(template) = template_for_snmp(&system) {
+                    return Ok(Discovery {
+                        template: template.to_string(),
+                        source: DiscoverySource::Snmp,
+                        snmp: Some(system),
+                        banner: None,
+                    });
+                }
+                reasons.push(format!("unknown sysObjectID {}", system.sys_object_id));
+                snmp = Some(system);
+            }
+            Err(err) => reasons.push(err.to_string()),
+        }
+    }
+
+    match ssh_banner(host, options.ssh_port, options.timeout).await {
+        Ok(banner) => {
+            if let Some(template) = template_for_banner(&banner) {
+                return Ok(Discovery {
+                    template: template.to_string(),
+                    source: DiscoverySource::Banner,
+                    snmp,
+                    banner: Some(banner),
+                });
+            }
+            reasons.push(format!("unknown banner '{banner}'"));
+        }
+        Err(err) => reasons.push(err.to_string()),
+    }
+    Err(ConnectError::TemplateNotDetected {
+        addr: host.to_string(),
+        reason: reasons.join("; "),
+    })
+}
+
+/// Template for an SNMP system description, by enterprise number first.
+pub fn template_for_snmp(system: &SnmpSystem) -> Option<&'static str> {
+    let enterprise = system
+        .sys_object_id
+        .strip_prefix(ENTERPRISES)
+        .and_then(|rest| rest.split('.').next())
+        .and_then(|number| number.parse::<u32>().ok());
+    enterprise
+        .and_then(|enterprise| {
+            ENTERPRISE_TEMPLATES
+                .iter()
+                .find(|(number, _)| *number == enterprise)
+                .map(|(_, template)| *template)
+        })
+        .or_else(|| first_match(DESCR_TEMPLATES, &system.sys_descr))
+}
+
+/// Template for an SSH identification string.
+pub fn template_for_banner(banner: &str) -> Option<&'static str> {
+    first_match(BANNER_TEMPLATES, banner.trim())
+}
+
+fn first_match(patterns: &[(&str, &'static str)], text: &str) -> Option<&'static str> {
+    patterns
+        .iter()
+        .find(|(pattern, _)| Regex::new(pattern).is_ok_and(|re| re.is_match(text)))
+        .map(|(_, template)| *template)
+}
+
+fn unreachable(host: &str, reason: impl Into<String>) -> ConnectError {
+    ConnectError::Unreachable {
+        addr: bare_host(host).to_string(),
+        reason: reason.into(),
+    }
+}
+
+/// Read the SSH identification line the server sends on connect.
+pub async fn ssh_banner(host: &str, port: u16, timeout: Duration) -> Result<String, ConnectError> {
+    let read = async {
+        let resolved = ResolvedAddrs::lookup(host, port, AddressFamily::Auto, None)
+            .await
+            .map_err(std::io::Error::other)?;
+        let mut stream = TcpStream::connect(resolved.addrs()).await?;
+        let mut banner = Vec::new();
+        let mut buf = [0u8; 256];
+        // Servers may send other lines before the identification (RFC 4253 4.2).
+        loop {
+            let n = stream.read(&mut buf).await?;
+            if n == 0 {
+                break;
+            }
+            banner.extend_from_slice(&buf[..n]);
+            let text = String::from_utf8_lossy(&banner);
+            if let Some(line) = text
+                .split_inclusive('\n')
+                .find(|line| line.starts_with("SSH-") && line.ends_with('\n'))
+            {
+                return Ok(line.trim_end().to_string());
+            }
+            if banner.len() > 4096 {
+                break;
+            }
+        }
+        Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "no SSH identification line",
+        ))
+    };
+    match tokio::time::timeout(timeout, read).await {
+        Ok(Ok(banner)) => Ok(banner),
+        Ok(Err(err)) => Err(unreachable(host, format!("ssh banner tcp/{port}: {err}"))),
+        Err(_) => Err(unreachable(
+            host,
+            format!("ssh banner tcp/{port}: no answer within {timeout:?}"),
+        )),
+    }
+}
+
+/// A request id no other probe of this process uses, so each probe only
+/// accepts the response to its own GET. Starts at a clock-derived value so
+/// ids are not the same across runs either.
+fn next_request_id() -> i32 {
+    static NEXT: Lazy<AtomicU32> = Lazy::new(|| {
+        AtomicU32::new(
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .subsec_nanos()
+                ^ std::process::id(),
+        )
+    });
+    (NEXT.fetch_add(1, Ordering::Relaxed) & 0x7fff_ffff) as i32
+}
+
+/// Fetch `sysDescr.0` and `sysObjectID.0` with one SNMPv2c GET.
+pub async fn snmp_system(
+    host: &str,
+    options: &DiscoveryOptions,
+) -> Result<SnmpSystem, ConnectError> {
+    let port = options.snmp_port;
+    let request_id = next_request_id();
+    let request = ber::get_request(&options.community, request_id, &[SYS_DESCR, SYS_OBJECT_ID]);
+    let exchange = async {
+        let resolved = ResolvedAddrs::lookup(host, port, AddressFamily::Auto, None)
+            .await
+            .map_err(std::io::Error::other)?;
+        let target = *resolved
+            .addrs()
+            .first()
+            .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "no address"))?;
+        let socket = UdpSocket::bind(if target.is_ipv6() {
+            "[::]:0"
+        } else {
+            "0.0.0.0:0"
+        })
+        .await?;
+        socket.connect(target).await?;
+        socket.send(&request).await?;
+        let mut buf = vec![0u8; 65_535];
+        loop {
+            let n = socket.recv(&mut buf).await?;
+            // Ignore stray datagrams for other requests.
+            if let Some(varbinds) = ber::parse_response(&buf[..n], request_id) {
+                return Ok::<_, std::io::Error>(varbinds);
+            }
+        }
+    };
+    let varbinds: Vec<(String, ber::Value)> =
+        match tokio::time::timeout(options.timeout, exchange).await {
+            Ok(Ok(varbinds)) => varbinds,
+            Ok(Err(err)) => return Err(unreachable(host, format!("snmp udp/{port}: {err}"))),
+            Err(_) => {
+                return Err(unreachable(
+                    host,
+                    format!("snmp udp/{port}: no answer within {:?}", options.timeout),
+                ));
+            }
+        };
+
+    let mut system = SnmpSystem {
+        sys_descr: String::new(),
+        sys_object_id: String::new(),
+    };
+    for (oid, value) in varbinds {
+        match (oid.as_str(), value) {
+            ("1.3.6.1.2.1.1.1.0", ber::Value::Text(text)) => system.sys_descr = text,
+            ("1.3.6.1.2.1.1.2.0", ber::Value::Oid(id)) => system.sys_object_id = id,
+            _ => {}
+        }
+    }
+    if system.sys_descr.is_empty() && system.sys_object_id.is_empty() {
+        return Err(unreachable(host, "snmp: system group not readable"));
+    }
+    Ok(system)
+}
+
+/// The few BER encodings an SNMPv2c GET needs.
+mod ber {
+    const INTEGER: u8 = 0x02;
+    const OCTET_STRING: u8 = 0x04;
+    const NULL: u8 = 0x05;
+    const OID: u8 = 0x06;
+    const SEQUENCE: u8 = 0x30;
+    const GET_REQUEST: u8 = 0xa0;
+    const GET_RESPONSE: u8 = 0xa2;
+    const SNMP_V2C: i32 = 1;
+
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub(super) enum Value {
+        Text(String),
+        Oid(String),
+        Other,
+    }
+
+    pub(super) fn tlv(tag: u8, content: &[u8]) -> Vec<u8> {
+        let mut out = vec![tag];
+        let len = content.len();
+        if len < 0x80 {
+            out.push(len as u8);
+        } else {
+            let bytes: Vec<u8> = len
+                .to_be_bytes()
+                .into_iter()
+                .skip_while(|byte| *byte == 0)
+                .collect();
+            out.push(0x80 | bytes.len() as u8);
+            out.extend(bytes);
+        }
+        out.extend_from_slice(content);
+        out
+    }
+
+    pub(super) fn integer(value: i32) -> Vec<u8> {
+        let bytes = value.to_be_bytes();
+        // Drop redundant leading bytes while keeping the sign bit.
+        let mut start = 0;
+        while start < 3
+            && ((bytes[start] == 0 && bytes[start + 1] & 0x80 == 0)
+                || (bytes[start] == 0xff && bytes[start + 1] & 0x80 != 0))
+        {
+            start += 1;
+        }
+        tlv(INTEGER, &bytes[start..])
+    }
+
+    pub(super) fn oid(arcs: &[u32]) -> Vec<u8> {
+        let mut content = Vec::new();
+        if let [first, second, rest @ ..] = arcs {
+            push_base128(&mut content, first * 40 + second);
+            for arc in rest {
+                push_base128(&mut content, *arc);
+            }
+        }
+        tlv(OID, &content)
+    }
+
+    fn push_base128(out: &mut Vec<u8>, value: u32) {
+        let mut groups = vec![(value & 0x7f) as u8];
+        let mut rest = value >> 7;
+        while rest > 0 {
+            groups.push((rest & 0x7f) as u8 | 0x80);
+            rest >>= 7;
+        }
+        out.extend(groups.into_iter().rev());
+    }
+
+    pub(super) fn message(community: &str, pdu_tag: u8, pdu: &[u8]) -> Vec<u8> {
+        let mut body = integer(SNMP_V2C);
+        body.extend(tlv(OCTET_STRING, community.as_bytes()));
+        body.extend(tlv(pdu_tag, pdu));
+        tlv(SEQUENCE, &body)
+    }
+
+    pub(super) fn get_request(community: &str, request_id: i32, oids: &[&[u32]]) -> Vec<u8> {
+        let varbinds: Vec<u8> = oids
+            .iter()
+            .flat_map(|arcs| {
+                let mut varbind = oid(arcs);
+                varbind.extend(tlv(NULL, &[]));
+                tlv(SEQUENCE, &varbind)
+            })
+            .collect();
+        let mut pdu = integer(request_id);
+        pdu.extend(integer(0));
+        pdu.extend(integer(0));
+        pdu.extend(tlv(SEQUENCE, &varbinds));
+        message(community, GET_REQUEST, &pdu)
+    }
+
+    /// Reads consecutive TLVs from a byte slice.
+    struct Reader<'a>(&'a [u8]);
+
+    impl<'a> Reader<'a> {
+        fn next(&mut self) -> Option<(u8, &'a [u8])> {
+            let (&tag, rest) = self.0.split_first()?;
+            let (&first, rest) = rest.split_first()?;
+            let (len, rest) = if first & 0x80 == 0 {
+                (usize::from(first), rest)
+            } else {
+                let count = usize::from(first & 0x7f);
+                if count == 0 || count > 4 || rest.len() < count {
+                    return None;
+                }
+                let len = rest[..count]
+                    .iter()
+                    .fold(0usize, |len, byte| (len << 8) | usize::from(*byte));
+                (len, &rest[count..])
+            };
+            if rest.len() < len {
+                return None;
+            }
+            let (content, rest) = rest.split_at(len);
+            self.0 = rest;
+            Some((tag, content))
+        }
+
+        fn expect(&mut self, tag: u8) -> Option<&'a [u8]> {
+            self.next().filter(|(t, _)| *t == tag).map(|(_, c)| c)
+        }
+    }
+
+    fn parse_integer(content: &[u8]) -> Option<i64> {
+        if content.is_empty() || content.len() > 8 {
+            return None;
+        }
+        let negative = content[0] & 0x80 != 0;
+        let start = if negative { -1i64 } else { 0 };
+        Some(
+            content
+                .iter()
+                .fold(start, |value, byte| (value << 8) | i64::from(*byte)),
+        )
+    }
+
+    fn parse_oid(content: &[u8]) -> Option<String> {
+        let mut arcs = Vec::new();
+        let mut value: u64 = 0;
+        for byte in content {
+            value = (value << 7) | u64::from(byte & 0x7f);
+            if byte & 0x80 == 0 {
+                if arcs.is_empty() {
+                    let first = (value / 40).min(2);
+                    arcs.push(first);
+                    arcs.push(value - first * 40);
+                } else {
+                    arcs.push(value);
+                }
+                value = 0;
+            }
+        }
+        (!arcs.is_empty()).then(|| {
+            arcs.iter()
+                .map(u64::to_string)
+                .collect::<Vec<_>>()
+                .join(".")
+        })
+    }
+
+    /// Varbinds of a GetResponse to `request_id` without an error status.
+    pub(super) fn parse_response(packet: &[u8], request_id: i32) -> Option<Vec<(String, Value)>> {
+        let mut message = Reader(Reader(packet).expect(SEQUENCE)?);
+        message.expect(INTEGER)?;
+        message.expect(OCTET_STRING)?;
+        let mut pdu = Reader(message.expect(GET_RESPONSE)?);
+        if parse_integer(pdu.expect(INTEGER)?)? != i64::from(request_id) {
+            return None;
+        }
+        if parse_integer(pdu.expect(INTEGER)?)? != 0 {
+            return None;
+        }
+        pdu.expect(INTEGER)?;
+        let mut varbinds = Reader(pdu.expect(SEQUENCE)?);
+        let mut values = Vec::new();
+        while let Some(varbind) = varbinds.expect(SEQUENCE) {
+            let mut varbind = Reader(varbind);
+            let name = parse_oid(varbind.expect(OID)?)?;
+            let value = match varbind.next()? {
+                (OCTET_STRING, text) => {
+                    Value::Text(String::from_utf8_lossy(text).trim().to_string())
+                }
+                (OID, id) => parse_oid(id).map_or(Value::Other, Value::Oid),
+                _ => Value::Other,
+            };
+            values.push((name, value));
+        }
+        Some(values)
+    }
+
+    #[cfg(test)]
+    pub(super) fn request_id(packet: &[u8]) -> Option<i32> {
+        let mut message = Reader(Reader(packet).expect(SEQUENCE)?);
+        message.expect(INTEGER)?;
+        message.expect(OCTET_STRING)?;
+        let mut pdu = Reader(message.expect(GET_REQUEST)?);
+        i32::try_from(parse_integer(pdu.expect(INTEGER)?)?).ok()
+    }
+
+    #[cfg(test)]
+    pub(super) fn get_response(
+        community: &str,
+        request_id: i32,
+        descr: &str,
+        object_id: &[u32],
+    ) -> Vec<u8> {
+        let mut descr_bind = oid(super::SYS_DESCR);
+        descr_bind.extend(tlv(OCTET_STRING, descr.as_bytes()));
+        let mut id_bind = oid(super::SYS_OBJECT_ID);
+        id_bind.extend(oid(object_id));
+        let mut varbinds = tlv(SEQUENCE, &descr_bind);
+        varbinds.extend(tlv(SEQUENCE, &id_bind));
+        let mut pdu = integer(request_id);
+        pdu.extend(integer(0));
+        pdu.extend(integer(0));
+        pdu.extend(tlv(SEQUENCE, &varbinds));
+        message(community, GET_RESPONSE, &pdu)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncWriteExt;
+
+    #[test]
+    fn probes_use_distinct_request_ids() {
+        let first = next_request_id();
+        let second = next_request_id();
+        assert_ne!(first, second);
+        assert!(first >= 0 && second >= 0);
+    }
+
+    #[tokio::test]
+    async fn snmp_answer_selects_template_by_enterprise() {
+        let agent = UdpSocket::bind("127.0.0.1:0").await.expect("bind");
+        let port = agent.local_addr().expect("addr").port();
+        let descr = "Cisco IOS Software, C2960X Software (C2960X-UNIVERSALK9-M), ".repeat(3);
+        let answer = tokio::spawn(async move {
+            let mut buf = [0u8; 1500];
+            let (n, peer) = agent.recv_from(&mut buf).await.expect("request");
+            let request_id = ber::request_id(&buf[..n]).expect("request id");
+            // A late answer to another probe is ignored.
+            let stray = ber::get_response(
+                "public",
+                request_id.wrapping_add(1),
+                "Huawei Versatile Routing Platform",
+                &[1, 3, 6, 1, 4, 1, 2011, 2, 23],
+            );
+            agent.send_to(&stray, peer).await.expect("stray");
+            let response = ber::get_response(
+                "public",
+                request_id,
+                &descr,
+                &[1, 3, 6, 1, 4, 1, 9, 1, 1208],
+            );
+            agent.send_to(&response, peer).await.expect("response");
+        });
+
+        let options = DiscoveryOptions::new().with_snmp_port(port);
+        let discovery = discover_template("127.0.0.1", &options)
+            .await
+            .expect("discovered");
+        answer.await.expect("agent");
+        assert_eq!(discovery.template, "cisco");
+        assert_eq!(discovery.source, DiscoverySource::Snmp);
+        let snmp = discovery.snmp.expect("snmp");
+        assert_eq!(snmp.sys_object_id, "1.3.6.1.4.1.9.1.1208");
+        assert!(snmp.sys_descr.starts_with("Cisco IOS Software"));
+    }
+
+    #[tokio::test]
+    async fn falls_back_to_ssh_banner_when_snmp_is_closed() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("bind");
+        let ssh_port = listener.local_addr().expect("addr").port();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.expect("accept");
+            let _ = stream.write_all(b"SSH-2.0-HUAWEI-1.5\r\n").await;
+        });
+        // Nothing answers on this UDP port.
+        let silent = UdpSocket::bind("127.0.0.1:0").await.expect("bind");
+        let options = DiscoveryOptions::new()
+            .with_snmp_port(silent.local_addr().expect("addr").port())
+            .with_ssh_port(ssh_port)
+            .with_timeout(Duration::from_millis(300));
+
+        let discovery = discover_template("127.0.0.1", &options)
+            .await
+            .expect("discovered");
+        assert_eq!(discovery.template, "huawei");
+        assert_eq!(discovery.source, DiscoverySource::Banner);
+        assert_eq!(discovery.banner.as_deref(), Some("SSH-2.0-HUAWEI-1.5"));
+    }
+
+    #[test]
+    fn descr_and_banner_patterns_cover_unknown_enterprises() {
+        let system = SnmpSystem {
+            sys_descr: "H3C Comware Platform Software, Version 7.1.064".to_string(),
+            sys_object_id: "1.3.6.1.4.1.99999.1".to_string(),
+        };
+        assert_eq!(template_for_snmp(&system), Some("h3c"));
+        assert_eq!(template_for_banner("SSH-2.0-Comware-7.1\r\n"), Some("h3c"));
+        assert_eq!(
+            template_for_banner("SSH-2.0-OpenSSH_8.9p1 Ubuntu-3ubuntu0.6"),
+            Some("linux")
+        );
+        assert_eq!(template_for_banner("SSH-2.0-OpenSSH_7.5"), None);
+        assert_eq!(ber::integer(128), [0x02, 0x02, 0x00, 0x80]);
+        assert_eq!(ber::integer(-1), [0x02, 0x01, 0xff]);
+    }
+}
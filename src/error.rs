@@ -3,9 +3,14 @@
 //! This module defines all errors that can occur during SSH operations,
 //! device state transitions, and command execution.
 
+use std::time::Duration;
+
 use thiserror::Error;
 use tokio::sync::mpsc::error::SendError;
 
+use crate::device::PromptMatchDiagnostics;
+use crate::session::PreflightReport;
+
 /// Errors that can occur during SSH connection and device state management.
 #[derive(Error, Debug)]
 pub enum ConnectError {
@@ -21,10 +26,20 @@ pub enum ConnectError {
     #[error("channel disconnected while waiting for prompt")]
     ChannelDisconnectError,
 
+    /// The device closed the session after login, before the first prompt,
+    /// with the notice it printed, e.g. `Too many sessions`.
+    #[error("session rejected by device: {0}")]
+    SessionRejected(String),
+
     /// The SSH connection has been closed.
     #[error("connection closed")]
     ConnectClosedError,
 
+    /// A transition command on the planned path has `{name}` placeholders
+    /// that neither the prompt captures nor dyn_param resolve.
+    #[error("unresolved placeholders {keys:?} in transition command {command:?}")]
+    UnresolvedPlaceholders { command: String, keys: Vec<String> },
+
     /// No exit command is defined for the specified state.
     #[error("{0} no exit command")]
     NoExitCommandError(String),
@@ -37,6 +52,72 @@ pub enum ConnectError {
     #[error("connection initialization timeout: {0}")]
     InitTimeout(String),
 
+    /// A fan-out call gave up on one device after its per-device deadline.
+    #[error("device did not finish within {0:?}")]
+    DeviceTimeout(Duration),
+
+    /// A device came back from a reboot but failed post-reboot verification.
+    #[error("post-reboot verification failed: {0}")]
+    RebootVerifyFailed(String),
+
+    /// The device rejected the supplied credentials.
+    #[error("authentication failed for {user} using {method}")]
+    AuthenticationFailed { user: String, method: String },
+
+    /// The device kept asking for the enable (or sudo) password after it was sent.
+    ///
+    /// Raised instead of re-sending a rejected password until the command
+    /// times out, which could lock the account.
+    #[error("{param} rejected after {attempts} attempts at prompt {prompt:?}")]
+    EnableAuthFailed {
+        /// Dynamic parameter that supplied the password, e.g. `EnablePassword`.
+        param: String,
+        attempts: u32,
+        /// The password prompt that reappeared.
+        prompt: String,
+    },
+
+    /// The server host key failed verification.
+    #[error("host key rejected: {0}")]
+    HostKeyRejected(String),
+
+    /// Client and server share no algorithm of one kind.
+    #[error("no compatible {kind} algorithm (offered: {offered:?}, required: {required:?})")]
+    NoCompatibleAlgorithms {
+        /// Algorithm category such as `kex`, `cipher` or `mac`.
+        kind: String,
+        /// Algorithms offered by this client.
+        offered: Vec<String>,
+        /// Algorithms supported by the server.
+        required: Vec<String>,
+    },
+
+    /// The server does not accept the attempted authentication method.
+    #[error("authentication method not supported: {0}")]
+    AuthMethodNotSupported(String),
+
+    /// Output kept arriving but no prompt pattern matched before the timeout.
+    #[error("prompt not matched {0}")]
+    PromptNotMatched(Box<PromptMatchDiagnostics>),
+
+    /// Output stopped on a question no input rule answers, and the
+    /// template's [`UnmodeledPromptPolicy`](crate::device::UnmodeledPromptPolicy)
+    /// fails fast or ran out of answers.
+    #[error("command {command:?} stopped on unmodeled question {question:?}")]
+    UnmodeledPrompt { command: String, question: String },
+
+    /// A workflow's pre-flight checks found problems; nothing was executed.
+    #[error(
+        "pre-flight failed for workflow {}: {} issue(s)",
+        .0.workflow_name,
+        .0.issues.len()
+    )]
+    PreflightFailed(Box<PreflightReport>),
+
+    /// An HA pair's probed roles are not one active and one standby member.
+    #[error("HA roles unclear: {0}")]
+    HaRolesUnclear(String),
+
     /// Device handler configuration is invalid.
     #[error("invalid device handler config: {0}")]
     InvalidDeviceHandlerConfig(String),
@@ -49,6 +130,10 @@ pub enum ConnectError {
     #[error("invalid command flow template: {0}")]
     InvalidCommandFlowTemplate(String),
 
+    /// A `{{ placeholder }}` template could not be rendered.
+    #[error("template render error: {0}")]
+    TemplateRenderError(String),
+
     /// An error occurred in the async-ssh2-tokio library.
     #[error("async ssh2 error: {0}")]
     Ssh2Error(#[from] async_ssh2_tokio::Error),
@@ -73,7 +158,349 @@ pub enum ConnectError {
     #[error("invalid transaction block: {0}")]
     InvalidTransaction(String),
 
+    /// Command guardrail policy is invalid.
+    #[error("invalid command policy: {0}")]
+    InvalidCommandPolicy(String),
+
+    /// Command was rejected by the guardrail policy before reaching the device.
+    #[error("command blocked by policy: {command} (matched {pattern})")]
+    CommandBlockedByPolicy { command: String, pattern: String },
+
+    /// The device rejected a command loaded into its candidate config, or
+    /// the candidate failed validation or commit.
+    #[error("candidate config command {command:?} failed: {reason}")]
+    CandidateFailed { command: String, reason: String },
+
+    /// Another user holds the device's configuration lock.
+    #[error("configuration locked{}: {message}", holder.as_ref().map(|holder| format!(" by {holder}")).unwrap_or_default())]
+    ConfigLocked {
+        /// Lock holder, when the device names one.
+        holder: Option<String>,
+        message: String,
+    },
+
+    /// A config command or transaction block was rejected by a change freeze.
+    #[error("change freeze in effect ({reason}): {target} rejected")]
+    ChangeFrozen { target: String, reason: String },
+
+    /// A tag already has as many pooled connections as its quota allows.
+    #[error("tag {tag} is at its quota of {limit} pooled connections")]
+    QuotaExceeded { tag: String, limit: usize },
+
+    /// Writing spilled command output to disk failed.
+    #[error("output spill failed: {0}")]
+    OutputSpillError(std::io::Error),
+
+    /// A fan-out output sink could not store or upload an outcome.
+    #[error("output sink failed: {0}")]
+    OutputSinkError(String),
+
+    /// A request could not be decoded or failed validation.
+    #[error("invalid request: {0}")]
+    InvalidRequest(String),
+
+    /// The device did not answer a reachability probe or TCP connect.
+    ///
+    /// `addr` is the host as requested, without port or IPv6 brackets, e.g.
+    /// `192.0.2.1` or `2001:db8::1`; `reason` names the port when one was
+    /// tried.
+    #[error("device unreachable: {addr} ({reason})")]
+    Unreachable { addr: String, reason: String },
+
+    /// Neither SNMP nor the SSH banner identified a built-in template.
+    #[error("no template detected for {addr}: {reason}")]
+    TemplateNotDetected { addr: String, reason: String },
+
+    /// An SFTP operation failed.
+    #[error("SFTP error: {0}")]
+    SftpError(#[from] russh_sftp::client::error::Error),
+
+    /// Transferred content did not hash to the expected SHA-256.
+    #[error("checksum mismatch for {path}: expected {expected}, got {actual}")]
+    ChecksumMismatch {
+        path: String,
+        expected: String,
+        actual: String,
+    },
+
+    /// A NETCONF peer answered with `<rpc-error>` or sent a malformed message.
+    #[error("NETCONF error: {0}")]
+    NetconfError(String),
+
+    /// An HTTP API request failed or returned an unreadable reply.
+    #[error("HTTP API error: {0}")]
+    HttpApiError(String),
+
+    /// The execution journal could not be read or written.
+    #[error("execution journal error: {0}")]
+    JournalError(String),
+
     /// An internal server error occurred.
     #[error("Internal server error: {0}")]
     InternalServerError(String),
+
+    /// Another error annotated with the device, mode and command it came from.
+    #[error("{context}: {source}")]
+    WithContext {
+        context: Box<ErrorContext>,
+        source: Box<ConnectError>,
+    },
+}
+
+/// Where an error happened, attached by the connection manager.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ErrorContext {
+    /// Connection cache key (`user@addr:port`).
+    pub device_addr: String,
+    /// Device mode the command targeted, when known.
+    pub mode: Option<String>,
+    /// Command or operation being executed, when known.
+    pub command: Option<String>,
+    /// Time spent on the request before it failed.
+    pub elapsed: Duration,
+}
+
+impl ErrorContext {
+    /// Build a context for `device_addr` without command details.
+    pub fn new(device_addr: impl Into<String>, elapsed: Duration) -> Self {
+        Self {
+            device_addr: device_addr.into(),
+            mode: None,
+            command: None,
+            elapsed,
+        }
+    }
+
+    /// Attach the device mode.
+    pub fn with_mode(mut self, mode: Option<String>) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Attach the command or operation summary.
+    pub fn with_command(mut self, command: Option<String>) -> Self {
+        self.command = command;
+        self
+    }
+}
+
+impl std::fmt::Display for ErrorContext {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.device_addr)?;
+        if let Some(mode) = self.mode.as_deref() {
+            write!(f, " mode={mode}")?;
+        }
+        if let Some(command) = self.command.as_deref() {
+            write!(f, " command={command:?}")?;
+        }
+        write!(f, " after {:.3}s", self.elapsed.as_secs_f64())
+    }
+}
+
+impl ConnectError {
+    /// Attach device/command context.
+    ///
+    /// When the error already carries context, only missing fields are filled in
+    /// so the innermost (most specific) details win.
+    pub fn with_context(self, context: ErrorContext) -> Self {
+        match self {
+            Self::WithContext {
+                context: mut existing,
+                source,
+            } => {
+                if existing.mode.is_none() {
+                    existing.mode = context.mode;
+                }
+                if existing.command.is_none() {
+                    existing.command = context.command;
+                }
+                existing.elapsed = existing.elapsed.max(context.elapsed);
+                Self::WithContext {
+                    context: existing,
+                    source,
+                }
+            }
+            other => Self::WithContext {
+                context: Box::new(context),
+                source: Box::new(other),
+            },
+        }
+    }
+
+    /// Returns true when retrying the same request may succeed.
+    ///
+    /// Timeouts, including ones that ran out on output no prompt matched,
+    /// channel drops and transport errors are retryable; authentication,
+    /// host-key, policy, configuration and state-machine errors are not.
+    pub fn is_retryable(&self) -> bool {
+        match self.root_cause() {
+            Self::ExecTimeout(_)
+            | Self::PromptNotMatched(_)
+            | Self::InitTimeout(_)
+            | Self::DeviceTimeout(_)
+            | Self::ChannelDisconnectError
+            | Self::SessionRejected(_)
+            | Self::ConnectClosedError
+            | Self::SendDataError(_)
+            | Self::Unreachable { .. } => true,
+            Self::Ssh2Error(err) => !matches!(
+                err,
+                async_ssh2_tokio::Error::PasswordWrong
+                    | async_ssh2_tokio::Error::KeyAuthFailed
+                    | async_ssh2_tokio::Error::KeyInvalid(_)
+                    | async_ssh2_tokio::Error::KeyboardInteractiveAuthFailed
+                    | async_ssh2_tokio::Error::ServerCheckFailed
+                    | async_ssh2_tokio::Error::AddressInvalid(_)
+            ),
+            Self::RusshError(_) => true,
+            _ => false,
+        }
+    }
+
+    /// Returns true when the connection that produced this error cannot be reused.
+    ///
+    /// Channel drops, transport errors and failed logins invalidate the session;
+    /// command timeouts and state-machine errors leave it usable.
+    pub fn is_fatal_for_connection(&self) -> bool {
+        matches!(
+            self.root_cause(),
+            Self::InitTimeout(_)
+                | Self::ChannelDisconnectError
+                | Self::SessionRejected(_)
+                | Self::ConnectClosedError
+                | Self::SendDataError(_)
+                | Self::Ssh2Error(_)
+                | Self::RusshError(_)
+                | Self::AuthenticationFailed { .. }
+                | Self::EnableAuthFailed { .. }
+                | Self::HostKeyRejected(_)
+                | Self::NoCompatibleAlgorithms { .. }
+                | Self::AuthMethodNotSupported(_)
+                // The device still waits for an answer; the next command would be taken as one.
+                | Self::UnmodeledPrompt { .. }
+        )
+    }
+
+    /// Device/command context attached by the manager, if any.
+    pub fn context(&self) -> Option<&ErrorContext> {
+        match self {
+            Self::WithContext { context, .. } => Some(context),
+            _ => None,
+        }
+    }
+
+    /// The underlying error with any context layers removed.
+    pub fn root_cause(&self) -> &ConnectError {
+        match self {
+            Self::WithContext { source, .. } => source.root_cause(),
+            other => other,
+        }
+    }
+
+    /// Consume the error and return it without context layers.
+    pub fn into_root_cause(self) -> ConnectError {
+        match self {
+            Self::WithContext { source, .. } => source.into_root_cause(),
+            other => other,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn with_context_wraps_and_exposes_root_cause() {
+        let err = ConnectError::ExecTimeout("partial".to_string()).with_context(
+            ErrorContext::new("admin@192.0.2.1:22", Duration::from_millis(1500))
+                .with_mode(Some("enable".to_string()))
+                .with_command(Some("show version".to_string())),
+        );
+
+        assert!(matches!(err.root_cause(), ConnectError::ExecTimeout(_)));
+        let context = err.context().expect("context");
+        assert_eq!(context.device_addr, "admin@192.0.2.1:22");
+        assert_eq!(
+            err.to_string(),
+            "admin@192.0.2.1:22 mode=enable command=\"show version\" after 1.500s: exec command timeout: partial"
+        );
+        assert!(matches!(
+            err.into_root_cause(),
+            ConnectError::ExecTimeout(_)
+        ));
+    }
+
+    #[test]
+    fn retryability_classification_covers_error_families() {
+        assert!(ConnectError::ExecTimeout(String::new()).is_retryable());
+        assert!(!ConnectError::ExecTimeout(String::new()).is_fatal_for_connection());
+        let partial = ConnectError::PromptNotMatched(Box::new(PromptMatchDiagnostics {
+            command: Some("show tech-support".to_string()),
+            last_lines: vec!["--More--".to_string()],
+            prompt_patterns: Vec::new(),
+            near_miss: None,
+        }));
+        assert!(
+            partial
+                .with_context(ErrorContext::new("admin@192.0.2.1:22", Duration::ZERO))
+                .is_retryable()
+        );
+
+        assert!(ConnectError::ChannelDisconnectError.is_retryable());
+        assert!(ConnectError::ChannelDisconnectError.is_fatal_for_connection());
+        assert!(
+            ConnectError::UnmodeledPrompt {
+                command: "reload".to_string(),
+                question: "Proceed with reload? [confirm]".to_string(),
+            }
+            .is_fatal_for_connection()
+        );
+
+        let auth = ConnectError::AuthenticationFailed {
+            user: "admin".to_string(),
+            method: "password".to_string(),
+        };
+        assert!(!auth.is_retryable());
+        assert!(auth.is_fatal_for_connection());
+
+        let enable = ConnectError::EnableAuthFailed {
+            param: "EnablePassword".to_string(),
+            attempts: 2,
+            prompt: "Password:".to_string(),
+        };
+        assert!(!enable.is_retryable());
+        assert!(enable.is_fatal_for_connection());
+
+        let state = ConnectError::UnreachableState("config".to_string());
+        assert!(!state.is_retryable());
+        assert!(!state.is_fatal_for_connection());
+
+        assert!(!ConnectError::Ssh2Error(async_ssh2_tokio::Error::PasswordWrong).is_retryable());
+        assert!(
+            ConnectError::RusshError(russh::Error::HUP)
+                .with_context(ErrorContext::new("admin@192.0.2.1:22", Duration::ZERO))
+                .is_retryable()
+        );
+    }
+
+    #[test]
+    fn with_context_fills_missing_fields_without_double_wrapping() {
+        let err = ConnectError::ConnectClosedError
+            .with_context(ErrorContext::new(
+                "admin@192.0.2.1:22",
+                Duration::from_secs(1),
+            ))
+            .with_context(
+                ErrorContext::new("admin@192.0.2.1:22", Duration::from_secs(2))
+                    .with_command(Some("show clock".to_string())),
+            );
+
+        let ConnectError::WithContext { context, source } = &err else {
+            panic!("expected context wrapper");
+        };
+        assert!(matches!(**source, ConnectError::ConnectClosedError));
+        assert_eq!(context.command.as_deref(), Some("show clock"));
+        assert_eq!(context.elapsed, Duration::from_secs(2));
+    }
 }
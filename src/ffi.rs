@@ -0,0 +1,339 @@
+//! C ABI for embedding rneter in C/C++ applications.
+//!
+//! Enabled with the `ffi` feature; build the shared library with
+//! `cargo rustc --release --lib --features ffi --crate-type cdylib` and use the
+//! declarations in `include/rneter.h`. Requests and results are UTF-8 JSON
+//! strings, so the ABI stays stable as Rust types gain fields.
+//!
+//! Every call blocks the calling thread on a shared Tokio runtime. Strings
+//! returned by this module must be released with [`rneter_string_free`].
+
+use std::ffi::{CStr, CString, c_char};
+use std::ptr;
+
+use once_cell::sync::Lazy;
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+use crate::error::ConnectError;
+use crate::session::{Command, ConnectionRequest, ExecutionContext, MANAGER, Output};
+
+static RUNTIME: Lazy<tokio::runtime::Runtime> = Lazy::new(|| {
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .thread_name("rneter-ffi")
+        .build()
+        .expect("failed to build rneter FFI runtime")
+});
+
+/// JSON accepted by [`rneter_connect`].
+#[derive(Clone, Deserialize)]
+struct ConnectParams {
+    host: String,
+    username: String,
+    password: String,
+    #[serde(default = "crate::session::default_ssh_port")]
+    port: u16,
+    #[serde(default)]
+    enable_password: Option<String>,
+    #[serde(default = "default_template")]
+    template: String,
+    #[serde(default)]
+    sys: Option<String>,
+    #[serde(default)]
+    affinity: Option<String>,
+}
+
+impl std::fmt::Debug for ConnectParams {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ConnectParams")
+            .field("host", &self.host)
+            .field("username", &self.username)
+            .field("password", &"<redacted>")
+            .field("port", &self.port)
+            .field(
+                "enable_password",
+                &self.enable_password.as_ref().map(|_| "<redacted>"),
+            )
+            .field("template", &self.template)
+            .field("sys", &self.sys)
+            .field("affinity", &self.affinity)
+            .finish()
+    }
+}
+
+fn default_template() -> String {
+    "cisco".to_string()
+}
+
+/// Opaque handle to one device connection.
+pub struct RneterSession {
+    params: ConnectParams,
+}
+
+impl RneterSession {
+    fn request(&self) -> Result<ConnectionRequest, ConnectError> {
+        let mut request = ConnectionRequest::for_template(
+            self.params.username.clone(),
+            self.params.host.clone(),
+            self.params.port,
+            self.params.password.clone(),
+            self.params.enable_password.clone(),
+            &self.params.template,
+        )?;
+        request.affinity = self.params.affinity.clone();
+        Ok(request)
+    }
+
+    fn context(&self) -> ExecutionContext {
+        ExecutionContext::new().with_sys(self.params.sys.clone())
+    }
+}
+
+/// Borrow a NUL-terminated UTF-8 string from the caller.
+unsafe fn read_str<'a>(value: *const c_char, name: &str) -> Result<&'a str, ConnectError> {
+    if value.is_null() {
+        return Err(ConnectError::InvalidRequest(format!(
+            "{name} must not be null"
+        )));
+    }
+    // SAFETY: the caller guarantees `value` points to a NUL-terminated string.
+    unsafe { CStr::from_ptr(value) }
+        .to_str()
+        .map_err(|_| ConnectError::InvalidRequest(format!("{name} is not valid UTF-8")))
+}
+
+fn into_c_string(value: String) -> *mut c_char {
+    // JSON output never contains interior NUL bytes; fall back to an empty string if it does.
+    CString::new(value).unwrap_or_default().into_raw()
+}
+
+/// Run an `extern "C"` body, turning a panic into `fallback` instead of
+/// unwinding into the caller.
+fn catch_panic<T>(fallback: impl FnOnce() -> T, body: impl FnOnce() -> T) -> T {
+    std::panic::catch_unwind(std::panic::AssertUnwindSafe(body)).unwrap_or_else(|_| fallback())
+}
+
+fn panic_error() -> ConnectError {
+    ConnectError::InternalServerError("rneter panicked".to_string())
+}
+
+fn error_json(err: &ConnectError) -> Value {
+    json!({
+        "ok": false,
+        "error": err.to_string(),
+        "retryable": err.is_retryable(),
+    })
+}
+
+fn output_json(output: Output) -> Value {
+    let spilled = output.spilled.clone();
+    let async_messages = output.async_messages.clone();
+    json!({
+        "ok": true,
+        "output": {
+            "success": output.success,
+            "exit_code": output.exit_code,
+            "content": output.content,
+            "prompt": output.prompt,
+            "device_addr": output.device_addr,
+            "sys": output.sys,
+            "spilled": spilled,
+            "async_messages": async_messages,
+            "all": output.into_all(),
+        },
+    })
+}
+
+fn connect(request_json: &str) -> Result<RneterSession, ConnectError> {
+    let params: ConnectParams = serde_json::from_str(request_json)
+        .map_err(|e| ConnectError::InvalidRequest(format!("connect request: {e}")))?;
+    let session = RneterSession { params };
+    let request = session.request()?;
+    let context = session.context();
+    RUNTIME.block_on(MANAGER.get_with_context(request, context))?;
+    Ok(session)
+}
+
+fn run_command(session: &RneterSession, command_json: &str) -> Result<Output, ConnectError> {
+    let command: Command = serde_json::from_str(command_json)
+        .map_err(|e| ConnectError::InvalidRequest(format!("command: {e}")))?;
+    let request = session.request()?;
+    RUNTIME.block_on(MANAGER.execute_command_with_context(request, command, session.context()))
+}
+
+/// Open (or reuse) a pooled connection described by `request_json`.
+///
+/// `request_json` holds `host`, `username` and `password`, plus optional
+/// `port` (22), `template` (`"cisco"`), `enable_password`, `sys` and
+/// `affinity`. Returns
+/// NULL on failure; when `error_out` is not NULL it then receives an error JSON
+/// string that must be freed with [`rneter_string_free`].
+///
+/// # Safety
+///
+/// `request_json` must be a valid NUL-terminated string. `error_out` must be
+/// NULL or point to writable storage for one pointer.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn rneter_connect(
+    request_json: *const c_char,
+    error_out: *mut *mut c_char,
+) -> *mut RneterSession {
+    let result = catch_panic(
+        || Err(panic_error()),
+        // SAFETY: forwarded from the caller's contract.
+        || unsafe { read_str(request_json, "request_json") }.and_then(connect),
+    );
+    match result {
+        Ok(session) => Box::into_raw(Box::new(session)),
+        Err(err) => {
+            if !error_out.is_null() {
+                // SAFETY: `error_out` is non-null and writable per the contract.
+                unsafe { *error_out = into_c_string(error_json(&err).to_string()) };
+            }
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Run one command on `session`.
+///
+/// `command_json` uses the serde shape of [`Command`], for example
+/// `{"mode":"Enable","command":"show version","timeout":30}`. Always returns a
+/// JSON string: `{"ok":true,"output":{...}}` or
+/// `{"ok":false,"error":"...","retryable":false}`.
+///
+/// # Safety
+///
+/// `session` must come from [`rneter_connect`] and not be closed yet.
+/// `command_json` must be a valid NUL-terminated string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn rneter_run_command(
+    session: *const RneterSession,
+    command_json: *const c_char,
+) -> *mut c_char {
+    // SAFETY: `session` is NULL or a live handle per the contract.
+    let Some(session) = (unsafe { session.as_ref() }) else {
+        let err = ConnectError::InvalidRequest("session must not be null".to_string());
+        return into_c_string(error_json(&err).to_string());
+    };
+    let result = catch_panic(
+        || Err(panic_error()),
+        // SAFETY: forwarded from the caller's contract.
+        || {
+            unsafe { read_str(command_json, "command_json") }
+                .and_then(|command_json| run_command(session, command_json))
+        },
+    );
+    let body = match result {
+        Ok(output) => output_json(output),
+        Err(err) => error_json(&err),
+    };
+    into_c_string(body.to_string())
+}
+
+/// Disconnect `session` and release the handle. NULL is ignored.
+///
+/// # Safety
+///
+/// `session` must be NULL or come from [`rneter_connect`], and must not be
+/// used again afterwards.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn rneter_close(session: *mut RneterSession) {
+    if session.is_null() {
+        return;
+    }
+    // SAFETY: the handle was created by `Box::into_raw` in `rneter_connect`.
+    let session = unsafe { Box::from_raw(session) };
+    catch_panic(
+        || (),
+        || {
+            if let Ok(request) = session.request() {
+                RUNTIME.block_on(MANAGER.disconnect(&request.device_addr()));
+            }
+        },
+    );
+}
+
+/// Free a string returned by this library. NULL is ignored.
+///
+/// # Safety
+///
+/// `value` must be NULL or a string returned by this library that has not
+/// been freed yet.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn rneter_string_free(value: *mut c_char) {
+    if !value.is_null() {
+        // SAFETY: the string was created by `CString::into_raw` in this module.
+        catch_panic(|| (), || drop(unsafe { CString::from_raw(value) }));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn take_string(value: *mut c_char) -> Value {
+        assert!(!value.is_null());
+        let text = unsafe { CStr::from_ptr(value) }
+            .to_str()
+            .expect("utf-8")
+            .to_string();
+        unsafe { rneter_string_free(value) };
+        serde_json::from_str(&text).expect("json")
+    }
+
+    #[test]
+    fn connect_rejects_invalid_request_json() {
+        let request = CString::new(r#"{"host":"192.0.2.1"}"#).unwrap();
+        let mut error = ptr::null_mut();
+
+        let session = unsafe { rneter_connect(request.as_ptr(), &mut error) };
+
+        assert!(session.is_null());
+        let error = take_string(error);
+        assert_eq!(error["ok"], false);
+        assert!(error["error"].as_str().unwrap().contains("username"));
+    }
+
+    #[test]
+    fn run_command_reports_null_session_as_json_error() {
+        let command = CString::new(r#"{"mode":"Enable","command":"show version"}"#).unwrap();
+
+        let result = take_string(unsafe { rneter_run_command(ptr::null(), command.as_ptr()) });
+
+        assert_eq!(result["ok"], false);
+        assert_eq!(result["retryable"], false);
+        unsafe { rneter_close(ptr::null_mut()) };
+    }
+
+    #[test]
+    fn close_key_matches_the_pooled_request() {
+        let params: ConnectParams = serde_json::from_str(
+            r#"{"host":"::1","username":"admin","password":"secret","affinity":"backup"}"#,
+        )
+        .expect("params");
+        let session = RneterSession { params };
+
+        let request = session.request().expect("request");
+
+        assert_eq!(request.device_addr(), "admin@[::1]:22#backup");
+    }
+
+    #[test]
+    fn panics_become_the_fallback_value() {
+        assert_eq!(catch_panic(|| "fallback", || panic!("boom")), "fallback");
+    }
+
+    #[test]
+    fn connect_params_debug_redacts_passwords() {
+        let params: ConnectParams = serde_json::from_str(
+            r#"{"host":"r1","username":"admin","password":"secret","enable_password":"enable-secret"}"#,
+        )
+        .expect("params");
+
+        let debug = format!("{params:?}");
+        assert!(debug.contains("admin"));
+        assert!(!debug.contains("secret"));
+    }
+}
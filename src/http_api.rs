@@ -0,0 +1,830 @@
+//! HTTP API transport for API-first platforms.
+//!
+//! Enabled with the `http-api` feature. An [`HttpApiSession`] offers the same
+//! `run`/`configure` surface as a CLI session for Arista EOS eAPI, PAN-OS
+//! XML API and FortiOS REST API. [`DeviceTransport`] selects SSH or HTTP per
+//! device, so a mixed fleet is driven through one [`SshConnectionManager`]:
+//!
+//! ```rust,no_run
+//! # async fn demo(manager: &rneter::session::SshConnectionManager, cisco: rneter::session::ConnectionRequest) -> Result<(), rneter::error::ConnectError> {
+//! use rneter::http_api::{ApiAuth, ApiFlavor, DeviceTransport, HttpApiTarget};
+//! use rneter::session::{Command, ExecutionContext};
+//!
+//! let eos = HttpApiTarget::new(
+//!     "https://192.0.2.10",
+//!     ApiFlavor::Eapi,
+//!     ApiAuth::basic("admin", "secret"),
+//! );
+//! for transport in [DeviceTransport::from(cisco), DeviceTransport::from(eos)] {
+//!     let output = manager
+//!         .run_with_transport(transport, Command::show("show version"), ExecutionContext::default())
+//!         .await?;
+//!     println!("{}", output.content);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! What a command or configuration line means depends on the flavor:
+//!
+//! | Flavor | `run` | `configure` lines |
+//! |--------|-------|-------------------|
+//! | [`ApiFlavor::Eapi`] | CLI command | CLI config lines, run between `configure` and `end` |
+//! | [`ApiFlavor::PanOs`] | CLI op command, converted to the XML form | `set <xpath> <element>`, `edit <xpath> <element>` or `delete <xpath>`, then a commit |
+//! | [`ApiFlavor::FortiOs`] | API path under `/api/v2`, e.g. `monitor/system/status` | `<METHOD> <path> [json body]`, e.g. `POST cmdb/firewall/address {...}` |
+
+use std::time::Duration;
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+use crate::error::ConnectError;
+use crate::session::{
+    Command, CommandFlow, CommandFlowOutput, ConnectionRequest, ExecutionContext, Output,
+    SshConnectionManager,
+};
+
+static PAN_STATUS: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r#"<response[^>]*\bstatus="(\w+)""#).expect("status regex"));
+static PAN_RESULT: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?s)<result[^>]*>(.*)</result>").expect("result regex"));
+static PAN_MSG: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?s)<msg[^>]*>(.*?)</msg>").expect("msg regex"));
+
+/// HTTP API dialect spoken by the device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ApiFlavor {
+    /// Arista EOS eAPI (JSON-RPC `runCmds` on `/command-api`).
+    Eapi,
+    /// Palo Alto PAN-OS XML API on `/api/`.
+    PanOs,
+    /// Fortinet FortiOS REST API on `/api/v2/`.
+    FortiOs,
+}
+
+/// How requests authenticate.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ApiAuth {
+    /// HTTP basic authentication.
+    Basic { user: String, password: String },
+    /// API key: `X-PAN-KEY` on PAN-OS, a bearer token elsewhere.
+    Token(String),
+}
+
+impl ApiAuth {
+    pub fn basic(user: impl Into<String>, password: impl Into<String>) -> Self {
+        Self::Basic {
+            user: user.into(),
+            password: password.into(),
+        }
+    }
+
+    pub fn token(token: impl Into<String>) -> Self {
+        Self::Token(token.into())
+    }
+}
+
+impl std::fmt::Debug for ApiAuth {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Basic { user, .. } => f
+                .debug_struct("Basic")
+                .field("user", user)
+                .field("password", &"<redacted>")
+                .finish(),
+            Self::Token(_) => f.debug_tuple("Token").field(&"<redacted>").finish(),
+        }
+    }
+}
+
+fn default_api_timeout_secs() -> u64 {
+    60
+}
+
+/// One device reached over its HTTP API.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct HttpApiTarget {
+    /// Scheme, host and optional port, e.g. `https://192.0.2.10:8443`.
+    pub base_url: String,
+    pub flavor: ApiFlavor,
+    pub auth: ApiAuth,
+    /// Accept self-signed certificates, common on management interfaces.
+    #[serde(default)]
+    pub accept_invalid_certs: bool,
+    /// Per-request timeout when the command does not set one.
+    #[serde(default = "default_api_timeout_secs")]
+    pub timeout_secs: u64,
+}
+
+impl HttpApiTarget {
+    pub fn new(base_url: impl Into<String>, flavor: ApiFlavor, auth: ApiAuth) -> Self {
+        Self {
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            flavor,
+            auth,
+            accept_invalid_certs: false,
+            timeout_secs: default_api_timeout_secs(),
+        }
+    }
+
+    pub fn with_accept_invalid_certs(mut self, accept_invalid_certs: bool) -> Self {
+        self.accept_invalid_certs = accept_invalid_certs;
+        self
+    }
+
+    pub fn with_timeout_secs(mut self, timeout_secs: u64) -> Self {
+        self.timeout_secs = timeout_secs;
+        self
+    }
+}
+
+fn api_error(message: impl Into<String>) -> ConnectError {
+    ConnectError::HttpApiError(message.into())
+}
+
+/// Output of one API call, shaped like a CLI command result.
+fn api_output(target: &HttpApiTarget, success: bool, content: String) -> Output {
+    Output {
+        success,
+        exit_code: None,
+        segments: vec![content.clone()],
+        content,
+        prompt: None,
+        spilled: None,
+        device_addr: Some(target.base_url.clone()),
+        sys: None,
+        async_messages: Vec::new(),
+    }
+}
+
+/// A client for one [`HttpApiTarget`].
+#[derive(Debug, Clone)]
+pub struct HttpApiSession {
+    target: HttpApiTarget,
+    client: reqwest::Client,
+}
+
+impl HttpApiSession {
+    pub fn new(target: HttpApiTarget) -> Result<Self, ConnectError> {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(target.timeout_secs))
+            .danger_accept_invalid_certs(target.accept_invalid_certs)
+            .build()
+            .map_err(|e| api_error(format!("client: {e}")))?;
+        Ok(Self { target, client })
+    }
+
+    pub fn target(&self) -> &HttpApiTarget {
+        &self.target
+    }
+
+    /// Run one read-only command.
+    pub async fn run(&self, command: &str) -> Result<Output, ConnectError> {
+        self.run_with_timeout(command, None).await
+    }
+
+    async fn run_with_timeout(
+        &self,
+        command: &str,
+        timeout: Option<Duration>,
+    ) -> Result<Output, ConnectError> {
+        match self.target.flavor {
+            ApiFlavor::Eapi => {
+                let mut outputs = self
+                    .eapi(&["enable".to_string(), command.to_string()], timeout)
+                    .await?;
+                Ok(outputs.pop().unwrap_or_else(|| {
+                    api_output(&self.target, false, "no eAPI output".to_string())
+                }))
+            }
+            ApiFlavor::PanOs => {
+                let cmd = cli_to_pan_xml(command)?;
+                self.pan_os(&[("type", "op"), ("cmd", cmd.as_str())], timeout)
+                    .await
+            }
+            ApiFlavor::FortiOs => {
+                self.forti_os(reqwest::Method::GET, command.trim(), None, timeout)
+                    .await
+            }
+        }
+    }
+
+    /// Apply configuration lines, one output per line.
+    ///
+    /// Stops at the first failed line, like a command flow with
+    /// `stop_on_error`.
+    pub async fn configure(&self, lines: &[String]) -> Result<CommandFlowOutput, ConnectError> {
+        let mut outputs = Vec::with_capacity(lines.len() + 1);
+        match self.target.flavor {
+            ApiFlavor::Eapi => {
+                let mut cmds = vec!["enable".to_string(), "configure".to_string()];
+                cmds.extend(lines.iter().cloned());
+                cmds.push("end".to_string());
+                let results = self.eapi(&cmds, None).await?;
+                // Drop the `enable`/`configure`/`end` wrappers.
+                outputs.extend(results.into_iter().skip(2).take(lines.len()));
+            }
+            ApiFlavor::PanOs => {
+                for line in lines {
+                    let (action, xpath, element) = parse_pan_config_line(line)?;
+                    let mut query = vec![("type", "config"), ("action", action), ("xpath", xpath)];
+                    if let Some(element) = element {
+                        query.push(("element", element));
+                    }
+                    let output = self.pan_os(&query, None).await?;
+                    let failed = !output.success;
+                    outputs.push(output);
+                    if failed {
+                        break;
+                    }
+                }
+                if outputs.iter().all(|output| output.success) {
+                    outputs.push(
+                        self.pan_os(&[("type", "commit"), ("cmd", "<commit></commit>")], None)
+                            .await?,
+                    );
+                }
+            }
+            ApiFlavor::FortiOs => {
+                for line in lines {
+                    let (method, path, body) = parse_forti_config_line(line)?;
+                    let output = self.forti_os(method, path, body, None).await?;
+                    let failed = !output.success;
+                    outputs.push(output);
+                    if failed {
+                        break;
+                    }
+                }
+            }
+        }
+        Ok(CommandFlowOutput {
+            success: outputs.len() >= lines.len() && outputs.iter().all(|output| output.success),
+            outputs,
+        })
+    }
+
+    fn request(&self, method: reqwest::Method, url: String) -> reqwest::RequestBuilder {
+        let request = self.client.request(method, url);
+        match (&self.target.auth, self.target.flavor) {
+            (ApiAuth::Basic { user, password }, _) => request.basic_auth(user, Some(password)),
+            (ApiAuth::Token(token), ApiFlavor::PanOs) => request.header("X-PAN-KEY", token),
+            (ApiAuth::Token(token), _) => request.bearer_auth(token),
+        }
+    }
+
+    async fn send(
+        &self,
+        request: reqwest::RequestBuilder,
+        timeout: Option<Duration>,
+    ) -> Result<(reqwest::StatusCode, String), ConnectError> {
+        let request = match timeout {
+            Some(timeout) => request.timeout(timeout),
+            None => request,
+        };
+        let response = request.send().await.map_err(|e| {
+            if e.is_timeout() {
+                ConnectError::ExecTimeout(format!("{}: {e}", self.target.base_url))
+            } else {
+                api_error(format!("{}: {e}", self.target.base_url))
+            }
+        })?;
+        let status = response.status();
+        if status == reqwest::StatusCode::UNAUTHORIZED || status == reqwest::StatusCode::FORBIDDEN {
+            return Err(ConnectError::AuthenticationFailed {
+                user: match &self.target.auth {
+                    ApiAuth::Basic { user, .. } => user.clone(),
+                    ApiAuth::Token(_) => "api-token".to_string(),
+                },
+                method: "http".to_string(),
+            });
+        }
+        let body = response
+            .text()
+            .await
+            .map_err(|e| api_error(format!("{}: {e}", self.target.base_url)))?;
+        Ok((status, body))
+    }
+
+    /// Run `cmds` with `runCmds`; returns one output per executed command.
+    async fn eapi(
+        &self,
+        cmds: &[String],
+        timeout: Option<Duration>,
+    ) -> Result<Vec<Output>, ConnectError> {
+        let body = json!({
+            "jsonrpc": "2.0",
+            "method": "runCmds",
+            "params": {"version": 1, "cmds": cmds, "format": "text"},
+            "id": "rneter",
+        });
+        let url = format!("{}/command-api", self.target.base_url);
+        let (status, text) = self
+            .send(
+                self.request(reqwest::Method::POST, url).json(&body),
+                timeout,
+            )
+            .await?;
+        let reply: Value = serde_json::from_str(&text)
+            .map_err(|e| api_error(format!("eAPI HTTP {status}: {e}")))?;
+        Ok(eapi_outputs(&self.target, &reply))
+    }
+
+    async fn pan_os(
+        &self,
+        query: &[(&str, &str)],
+        timeout: Option<Duration>,
+    ) -> Result<Output, ConnectError> {
+        let url = format!("{}/api/", self.target.base_url);
+        let (status, text) = self
+            .send(
+                self.request(reqwest::Method::GET, url).query(query),
+                timeout,
+            )
+            .await?;
+        let (success, content) = pan_os_reply(&text);
+        Ok(api_output(
+            &self.target,
+            success && status.is_success(),
+            content,
+        ))
+    }
+
+    async fn forti_os(
+        &self,
+        method: reqwest::Method,
+        path: &str,
+        body: Option<Value>,
+        timeout: Option<Duration>,
+    ) -> Result<Output, ConnectError> {
+        let url = format!(
+            "{}/api/v2/{}",
+            self.target.base_url,
+            path.trim_start_matches('/')
+        );
+        let mut request = self.request(method, url);
+        if let Some(body) = body {
+            request = request.json(&body);
+        }
+        let (status, text) = self.send(request, timeout).await?;
+        let reply: Option<Value> = serde_json::from_str(&text).ok();
+        let rejected = reply
+            .as_ref()
+            .and_then(|reply| reply.get("status"))
+            .and_then(Value::as_str)
+            .is_some_and(|status| status == "error");
+        let content = match reply {
+            Some(reply) => serde_json::to_string_pretty(&reply).unwrap_or(text),
+            None => text,
+        };
+        Ok(api_output(
+            &self.target,
+            status.is_success() && !rejected,
+            content,
+        ))
+    }
+}
+
+/// Split a `runCmds` reply into per-command outputs.
+fn eapi_outputs(target: &HttpApiTarget, reply: &Value) -> Vec<Output> {
+    let text_of = |entry: &Value| {
+        entry
+            .get("output")
+            .and_then(Value::as_str)
+            .map(str::to_string)
+            .unwrap_or_else(|| entry.to_string())
+    };
+    if let Some(results) = reply.get("result").and_then(Value::as_array) {
+        return results
+            .iter()
+            .map(|entry| api_output(target, true, text_of(entry)))
+            .collect();
+    }
+
+    // On error `data` holds the results up to and including the failed command.
+    let error = reply.get("error").cloned().unwrap_or(Value::Null);
+    let message = error
+        .get("message")
+        .and_then(Value::as_str)
+        .unwrap_or("eAPI request failed")
+        .to_string();
+    let data = error
+        .get("data")
+        .and_then(Value::as_array)
+        .cloned()
+        .unwrap_or_default();
+    let mut outputs: Vec<Output> = data
+        .iter()
+        .map(|entry| api_output(target, true, text_of(entry)))
+        .collect();
+    match (outputs.last_mut(), data.last()) {
+        (Some(last), Some(entry)) if entry.get("errors").is_some() => {
+            last.success = false;
+            last.content = format!("{message}: {}", entry["errors"]);
+        }
+        _ => outputs.push(api_output(target, false, message)),
+    }
+    outputs
+}
+
+/// Status and `<result>` (or `<msg>` on failure) of a PAN-OS reply.
+fn pan_os_reply(text: &str) -> (bool, String) {
+    let success = PAN_STATUS
+        .captures(text)
+        .is_some_and(|caps| &caps[1] == "success");
+    let content = if success {
+        PAN_RESULT
+            .captures(text)
+            .map(|caps| caps[1].trim().to_string())
+    } else {
+        let messages: Vec<&str> = PAN_MSG
+            .captures_iter(text)
+            .filter_map(|caps| caps.get(1).map(|m| m.as_str().trim()))
+            .collect();
+        (!messages.is_empty()).then(|| messages.join("\n"))
+    };
+    (success, content.unwrap_or_else(|| text.trim().to_string()))
+}
+
+/// Convert a CLI op command into PAN-OS XML, e.g. `show interface "ethernet1/1"`
+/// becomes `<show><interface>ethernet1/1</interface></show>`.
+///
+/// The quoted value is escaped; words that are not valid element names,
+/// such as an unquoted `ethernet1/1`, are rejected.
+fn cli_to_pan_xml(command: &str) -> Result<String, ConnectError> {
+    let mut words: Vec<String> = Vec::new();
+    let mut value: Option<String> = None;
+    let mut rest = command.trim();
+    while !rest.is_empty() {
+        if let Some(quoted) = rest.strip_prefix('"') {
+            let end = quoted.find('"').unwrap_or(quoted.len());
+            value = Some(quoted[..end].to_string());
+            rest = quoted.get(end + 1..).unwrap_or("").trim_start();
+            continue;
+        }
+        let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        let word = &rest[..end];
+        if !is_xml_name(word) {
+            return Err(api_error(format!(
+                "'{command}': '{word}' is not an XML element name; quote values, e.g. \"{word}\""
+            )));
+        }
+        words.push(word.to_string());
+        rest = rest[end..].trim_start();
+    }
+    let open: String = words.iter().map(|word| format!("<{word}>")).collect();
+    let close: String = words
+        .iter()
+        .rev()
+        .map(|word| format!("</{word}>"))
+        .collect();
+    Ok(format!(
+        "{open}{}{close}",
+        escape_xml_text(&value.unwrap_or_default())
+    ))
+}
+
+/// Letters, digits, `-`, `_` and `.`, starting with a letter or `_`.
+fn is_xml_name(word: &str) -> bool {
+    let mut chars = word.chars();
+    chars
+        .next()
+        .is_some_and(|first| first.is_ascii_alphabetic() || first == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+fn escape_xml_text(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn parse_pan_config_line(line: &str) -> Result<(&str, &str, Option<&str>), ConnectError> {
+    let line = line.trim();
+    let (action, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    let rest = rest.trim();
+    match action {
+        "set" | "edit" => match rest.split_once(char::is_whitespace) {
+            Some((xpath, element)) => Ok((action, xpath, Some(element.trim()))),
+            None => Err(api_error(format!(
+                "'{line}': expected {action} <xpath> <element>"
+            ))),
+        },
+        "delete" if !rest.is_empty() => Ok((action, rest, None)),
+        _ => Err(api_error(format!(
+            "'{line}': expected set, edit or delete with an xpath"
+        ))),
+    }
+}
+
+fn parse_forti_config_line(
+    line: &str,
+) -> Result<(reqwest::Method, &str, Option<Value>), ConnectError> {
+    let line = line.trim();
+    let mut parts = line.splitn(3, char::is_whitespace);
+    let method = match parts.next().map(str::to_ascii_uppercase).as_deref() {
+        Some("POST") => reqwest::Method::POST,
+        Some("PUT") => reqwest::Method::PUT,
+        Some("DELETE") => reqwest::Method::DELETE,
+        _ => {
+            return Err(api_error(format!(
+                "'{line}': expected POST, PUT or DELETE <path> [json]"
+            )));
+        }
+    };
+    let path = parts
+        .next()
+        .filter(|path| !path.is_empty())
+        .ok_or_else(|| api_error(format!("'{line}': missing API path")))?;
+    let body = match parts.next().map(str::trim).filter(|body| !body.is_empty()) {
+        Some(body) => Some(
+            serde_json::from_str(body).map_err(|e| api_error(format!("'{line}': body: {e}")))?,
+        ),
+        None => None,
+    };
+    Ok((method, path, body))
+}
+
+/// How one device is reached.
+pub enum DeviceTransport {
+    /// Interactive CLI over a pooled SSH connection.
+    Ssh(Box<ConnectionRequest>),
+    Http(HttpApiTarget),
+}
+
+impl From<ConnectionRequest> for DeviceTransport {
+    fn from(request: ConnectionRequest) -> Self {
+        Self::Ssh(Box::new(request))
+    }
+}
+
+impl From<HttpApiTarget> for DeviceTransport {
+    fn from(target: HttpApiTarget) -> Self {
+        Self::Http(target)
+    }
+}
+
+impl SshConnectionManager {
+    /// Apply the guardrails an SSH session enforces to a command sent over
+    /// HTTP: the command policy merged with `context`'s, and the change
+    /// freeze unless `context` carries its override token.
+    ///
+    /// `read_only` skips the freeze for requests that cannot change config,
+    /// such as FortiOS GETs.
+    fn check_http_command(
+        &self,
+        command: &str,
+        read_only: bool,
+        context: &ExecutionContext,
+    ) -> Result<(), ConnectError> {
+        let policy = match context.command_policy.as_ref() {
+            Some(local) => self.command_policy().merge(local),
+            None => self.command_policy(),
+        };
+        policy.check(command)?;
+        match self.change_freeze() {
+            Some(freeze)
+                if !read_only && !freeze.is_overridden_by(context.freeze_override.as_deref()) =>
+            {
+                freeze.check_command(command, &[])
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Run `command` over the device's transport.
+    ///
+    /// For HTTP targets the command mode is ignored and `command.timeout`
+    /// bounds the request. The manager's command policy and change freeze
+    /// apply to both transports.
+    pub async fn run_with_transport(
+        &self,
+        transport: DeviceTransport,
+        command: Command,
+        context: ExecutionContext,
+    ) -> Result<Output, ConnectError> {
+        match transport {
+            DeviceTransport::Ssh(request) => {
+                self.execute_command_with_context(*request, command, context)
+                    .await
+            }
+            DeviceTransport::Http(target) => {
+                let read_only = target.flavor == ApiFlavor::FortiOs;
+                self.check_http_command(&command.command, read_only, &context)?;
+                HttpApiSession::new(target)?
+                    .run_with_timeout(&command.command, command.timeout.map(Duration::from_secs))
+                    .await
+            }
+        }
+    }
+
+    /// Apply configuration `lines` over the device's transport.
+    ///
+    /// Over SSH each line runs in the template's `Config` mode and the flow
+    /// stops at the first failure.
+    pub async fn configure_with_transport(
+        &self,
+        transport: DeviceTransport,
+        lines: Vec<String>,
+        context: ExecutionContext,
+    ) -> Result<CommandFlowOutput, ConnectError> {
+        match transport {
+            DeviceTransport::Ssh(request) => {
+                let steps = lines
+                    .into_iter()
+                    .map(|line| Command::new("Config", line))
+                    .collect();
+                self.execute_command_flow_with_context(*request, CommandFlow::new(steps), context)
+                    .await
+            }
+            DeviceTransport::Http(target) => {
+                for line in &lines {
+                    self.check_http_command(line, false, &context)?;
+                }
+                HttpApiSession::new(target)?.configure(&lines).await
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session::{ChangeFreeze, CommandPolicy};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    fn target(flavor: ApiFlavor) -> HttpApiTarget {
+        HttpApiTarget::new("http://192.0.2.10/", flavor, ApiAuth::token("key"))
+    }
+
+    #[test]
+    fn pan_os_commands_and_replies_are_translated() {
+        assert_eq!(
+            cli_to_pan_xml("show system info").expect("xml"),
+            "<show><system><info></info></system></show>"
+        );
+        assert_eq!(
+            cli_to_pan_xml(r#"show interface "ethernet1/1""#).expect("xml"),
+            "<show><interface>ethernet1/1</interface></show>"
+        );
+        assert_eq!(
+            cli_to_pan_xml(r#"show object "a<b>&c""#).expect("xml"),
+            "<show><object>a&lt;b&gt;&amp;c</object></show>"
+        );
+        for command in ["show interface ethernet1/1", "show <x/>", "show 1st"] {
+            assert!(
+                matches!(cli_to_pan_xml(command), Err(ConnectError::HttpApiError(_))),
+                "{command}"
+            );
+        }
+        assert_eq!(
+            pan_os_reply(
+                r#"<response status="success"><result><system><hostname>fw1</hostname></system></result></response>"#
+            ),
+            (
+                true,
+                "<system><hostname>fw1</hostname></system>".to_string()
+            )
+        );
+        assert_eq!(
+            pan_os_reply(
+                r#"<response status="error"><msg><line>Invalid command</line></msg></response>"#
+            ),
+            (false, "<line>Invalid command</line>".to_string())
+        );
+        assert_eq!(
+            parse_pan_config_line("set /config/devices/entry <hostname>fw1</hostname>")
+                .expect("set"),
+            (
+                "set",
+                "/config/devices/entry",
+                Some("<hostname>fw1</hostname>")
+            )
+        );
+        assert!(parse_pan_config_line("commit").is_err());
+        assert!(parse_forti_config_line("GET cmdb/firewall/address").is_err());
+        let (method, path, body) =
+            parse_forti_config_line(r#"post cmdb/firewall/address {"name":"WEB01"}"#)
+                .expect("post");
+        assert_eq!(
+            (method, path),
+            (reqwest::Method::POST, "cmdb/firewall/address")
+        );
+        assert_eq!(body, Some(json!({"name": "WEB01"})));
+    }
+
+    #[test]
+    fn eapi_errors_mark_the_failed_command() {
+        let target = target(ApiFlavor::Eapi);
+        let reply = json!({
+            "jsonrpc": "2.0",
+            "id": "rneter",
+            "error": {
+                "code": 1002,
+                "message": "CLI command 4 of 5 'vlan abc' failed: invalid command",
+                "data": [{}, {}, {"output": ""}, {"errors": ["Invalid input (at token 1: 'abc')"]}]
+            }
+        });
+        let outputs = eapi_outputs(&target, &reply);
+        assert_eq!(outputs.len(), 4);
+        assert!(outputs[2].success);
+        assert!(!outputs[3].success);
+        assert!(outputs[3].content.contains("Invalid input"));
+    }
+
+    #[tokio::test]
+    async fn http_commands_follow_the_command_policy_and_change_freeze() {
+        let manager = SshConnectionManager::new();
+        manager.set_command_policy(CommandPolicy::deny(["^reload"]).expect("policy"));
+        let err = manager
+            .run_with_transport(
+                target(ApiFlavor::Eapi).into(),
+                Command::show("reload now"),
+                ExecutionContext::new(),
+            )
+            .await
+            .expect_err("blocked");
+        assert!(matches!(err, ConnectError::CommandBlockedByPolicy { .. }));
+
+        manager.set_change_freeze(Some(
+            ChangeFreeze::new("year-end").with_override_token("CHG-1"),
+        ));
+        let err = manager
+            .configure_with_transport(
+                target(ApiFlavor::PanOs).into(),
+                vec!["set /config/devices/entry <hostname>fw1</hostname>".to_string()],
+                ExecutionContext::new(),
+            )
+            .await
+            .expect_err("frozen");
+        assert!(matches!(err, ConnectError::ChangeFrozen { .. }));
+
+        let context = ExecutionContext::new();
+        assert!(
+            manager
+                .check_http_command("vlan 10", false, &context)
+                .is_err()
+        );
+        assert!(
+            manager
+                .check_http_command("show version", false, &context)
+                .is_ok()
+        );
+        assert!(
+            manager
+                .check_http_command("monitor/system/status", true, &context)
+                .is_ok()
+        );
+        let emergency = ExecutionContext::new().with_freeze_override("CHG-1");
+        assert!(
+            manager
+                .check_http_command("vlan 10", false, &emergency)
+                .is_ok()
+        );
+    }
+
+    #[tokio::test]
+    async fn eapi_run_posts_run_cmds_and_returns_command_output() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.expect("bind");
+        let base_url = format!("http://{}", listener.local_addr().expect("addr"));
+        let server = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.expect("accept");
+            let mut request = Vec::new();
+            let mut buffer = [0u8; 4096];
+            while !String::from_utf8_lossy(&request).contains("\"id\":\"rneter\"") {
+                let n = socket.read(&mut buffer).await.expect("read");
+                if n == 0 {
+                    break;
+                }
+                request.extend_from_slice(&buffer[..n]);
+            }
+            let body = r#"{"jsonrpc":"2.0","id":"rneter","result":[{},{"output":"Arista vEOS\nSoftware image version: 4.30.1F\n"}]}"#;
+            let response = format!(
+                "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\n\r\n{body}",
+                body.len()
+            );
+            socket.write_all(response.as_bytes()).await.expect("write");
+            String::from_utf8(request).expect("utf8")
+        });
+
+        let session = HttpApiSession::new(HttpApiTarget::new(
+            base_url,
+            ApiFlavor::Eapi,
+            ApiAuth::basic("admin", "secret"),
+        ))
+        .expect("session");
+        let output = session.run("show version").await.expect("run");
+        assert!(output.success);
+        assert!(output.content.contains("4.30.1F"));
+
+        let request = server.await.expect("server").to_ascii_lowercase();
+        assert!(request.starts_with("post /command-api"));
+        assert!(request.contains("authorization: basic"));
+        assert!(request.contains(r#""cmds":["enable","show version"]"#));
+    }
+}
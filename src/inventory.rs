@@ -0,0 +1,541 @@
+//! Device inventories loaded from YAML or CSV.
+//!
+//! Enabled with the `inventory` feature. Each device names a template and a
+//! credentials reference; the secrets themselves are registered on the
+//! [`Inventory`] at runtime so inventory files can be committed safely.
+//!
+//! YAML:
+//!
+//! ```yaml
+//! devices:
+//!   - name: edge-1
+//!     host: 192.0.2.1
+//!     template: cisco
+//!     credentials: lab
+//!     tags: [edge]
+//!     groups: [dc1]
+//! ```
+//!
+//! CSV uses the same column names, with `;`-separated tags and groups:
+//!
+//! ```text
+//! name,host,port,template,credentials,tags,groups
+//! edge-1,192.0.2.1,22,cisco,lab,edge;wan,dc1
+//! ```
+//!
+//! `template: auto` defers the choice to [`Inventory::discover_templates`],
+//! which probes SNMP and the SSH banner before the first login.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::discovery::{self, Discovery, DiscoveryOptions};
+use crate::error::ConnectError;
+use crate::session::{
+    Command, ConnectionRequest, DEFAULT_SSH_PORT, DeviceCredentials, DeviceOutcome,
+    ExecutionContext, FanoutOptions, Output, SshConnectionManager, TxWorkflow, TxWorkflowResult,
+};
+use crate::templates;
+
+/// One device entry of an inventory.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InventoryDevice {
+    /// Unique device name.
+    pub name: String,
+    pub host: String,
+    #[serde(default = "crate::session::default_ssh_port")]
+    pub port: u16,
+    /// Built-in template name such as `cisco` or `huawei`, or
+    /// [`AUTO_TEMPLATE`] to detect it with [`Inventory::discover_templates`].
+    pub template: String,
+    /// Name of the credential set registered with [`Inventory::with_credentials`].
+    pub credentials: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub groups: Vec<String>,
+    /// System name for templates with dynamic transitions.
+    #[serde(default)]
+    pub sys: Option<String>,
+}
+
+/// Template value asking for discovery instead of naming a template.
+pub const AUTO_TEMPLATE: &str = "auto";
+
+fn is_auto(template: &str) -> bool {
+    template.eq_ignore_ascii_case(AUTO_TEMPLATE)
+}
+
+impl InventoryDevice {
+    /// Returns true when the device matches every term of `selector`.
+    pub fn matches(&self, selector: &DeviceSelector) -> bool {
+        selector.terms.iter().all(|term| match term {
+            SelectorTerm::All => true,
+            SelectorTerm::Name(name) => &self.name == name,
+            SelectorTerm::Tag(tag) => self.tags.iter().any(|t| t == tag),
+            SelectorTerm::Group(group) => self.groups.iter().any(|g| g == group),
+            SelectorTerm::Template(template) => self.template.eq_ignore_ascii_case(template),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum SelectorTerm {
+    All,
+    Name(String),
+    Tag(String),
+    Group(String),
+    Template(String),
+}
+
+/// Device filter such as `tag=edge` or `group=dc1,template=cisco`.
+///
+/// Terms are comma-separated and must all match. `all` (or an empty string)
+/// selects every device.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceSelector {
+    terms: Vec<SelectorTerm>,
+}
+
+impl DeviceSelector {
+    /// Parse a selector expression.
+    pub fn parse(expression: &str) -> Result<Self, ConnectError> {
+        let mut terms = Vec::new();
+        for term in expression
+            .split(',')
+            .map(str::trim)
+            .filter(|t| !t.is_empty())
+        {
+            if term.eq_ignore_ascii_case("all") {
+                terms.push(SelectorTerm::All);
+                continue;
+            }
+            let Some((key, value)) = term.split_once('=') else {
+                return Err(ConnectError::InvalidRequest(format!(
+                    "invalid selector term '{term}', expected key=value"
+                )));
+            };
+            let value = value.trim().to_string();
+            terms.push(match key.trim() {
+                "name" => SelectorTerm::Name(value),
+                "tag" => SelectorTerm::Tag(value),
+                "group" => SelectorTerm::Group(value),
+                "template" => SelectorTerm::Template(value),
+                other => {
+                    return Err(ConnectError::InvalidRequest(format!(
+                        "unknown selector key '{other}'"
+                    )));
+                }
+            });
+        }
+        Ok(Self { terms })
+    }
+}
+
+/// CSV row; list columns are `;`-separated.
+#[derive(Debug, Deserialize)]
+struct CsvDevice {
+    name: String,
+    host: String,
+    #[serde(default)]
+    port: Option<u16>,
+    template: String,
+    credentials: String,
+    #[serde(default)]
+    tags: String,
+    #[serde(default)]
+    groups: String,
+    #[serde(default)]
+    sys: Option<String>,
+}
+
+fn split_list(value: &str) -> Vec<String> {
+    value
+        .split(';')
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+#[derive(Debug, Deserialize)]
+struct YamlInventory {
+    devices: Vec<InventoryDevice>,
+}
+
+/// A set of devices plus the credentials they reference.
+#[derive(Debug, Clone, Default)]
+pub struct Inventory {
+    devices: Vec<InventoryDevice>,
+    credentials: HashMap<String, DeviceCredentials>,
+}
+
+impl Inventory {
+    /// Build an inventory from already parsed devices.
+    pub fn new(devices: Vec<InventoryDevice>) -> Result<Self, ConnectError> {
+        let mut seen = std::collections::HashSet::new();
+        for device in &devices {
+            if !seen.insert(device.name.as_str()) {
+                return Err(ConnectError::InvalidRequest(format!(
+                    "duplicate inventory device '{}'",
+                    device.name
+                )));
+            }
+            if !is_auto(&device.template) {
+                templates::template_metadata(&device.template)?;
+            }
+        }
+        Ok(Self {
+            devices,
+            credentials: HashMap::new(),
+        })
+    }
+
+    /// Parse a YAML document with a top-level `devices` list.
+    pub fn from_yaml_str(yaml: &str) -> Result<Self, ConnectError> {
+        let parsed: YamlInventory = serde_yaml::from_str(yaml)
+            .map_err(|e| ConnectError::InvalidRequest(format!("inventory YAML: {e}")))?;
+        Self::new(parsed.devices)
+    }
+
+    /// Parse CSV with a header row.
+    pub fn from_csv_str(csv: &str) -> Result<Self, ConnectError> {
+        let mut reader = csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .from_reader(csv.as_bytes());
+        let devices = reader
+            .deserialize::<CsvDevice>()
+            .map(|row| {
+                let row =
+                    row.map_err(|e| ConnectError::InvalidRequest(format!("inventory CSV: {e}")))?;
+                Ok(InventoryDevice {
+                    name: row.name,
+                    host: row.host,
+                    port: row.port.unwrap_or(DEFAULT_SSH_PORT),
+                    template: row.template,
+                    credentials: row.credentials,
+                    tags: split_list(&row.tags),
+                    groups: split_list(&row.groups),
+                    sys: row.sys.filter(|sys| !sys.is_empty()),
+                })
+            })
+            .collect::<Result<Vec<_>, ConnectError>>()?;
+        Self::new(devices)
+    }
+
+    /// Load a `.yaml`/`.yml` or `.csv` file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ConnectError> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path).map_err(|e| {
+            ConnectError::InvalidRequest(format!("cannot read {}: {e}", path.display()))
+        })?;
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("yaml" | "yml") => Self::from_yaml_str(&content),
+            Some("csv") => Self::from_csv_str(&content),
+            _ => Err(ConnectError::InvalidRequest(format!(
+                "unsupported inventory format: {}",
+                path.display()
+            ))),
+        }
+    }
+
+    /// Register the credential set referenced as `name` by devices.
+    ///
+    /// Key-based credentials need a manager-level credential provider; only
+    /// the user, password and enable password are used here.
+    pub fn with_credentials(
+        mut self,
+        name: impl Into<String>,
+        credentials: DeviceCredentials,
+    ) -> Self {
+        self.credentials.insert(name.into(), credentials);
+        self
+    }
+
+    pub fn devices(&self) -> &[InventoryDevice] {
+        &self.devices
+    }
+
+    pub fn get(&self, name: &str) -> Option<&InventoryDevice> {
+        self.devices.iter().find(|device| device.name == name)
+    }
+
+    /// Devices matching `selector`, in inventory order.
+    pub fn select(&self, selector: &DeviceSelector) -> Vec<&InventoryDevice> {
+        self.devices
+            .iter()
+            .filter(|device| device.matches(selector))
+            .collect()
+    }
+
+    /// Detect the template of every `template: auto` device.
+    ///
+    /// Devices are probed concurrently; each detected template replaces
+    /// `auto`. Results are keyed by device name in inventory order, and
+    /// devices that could not be identified keep `auto`.
+    pub async fn discover_templates(
+        &mut self,
+        options: &DiscoveryOptions,
+    ) -> Vec<(String, Result<Discovery, ConnectError>)> {
+        let mut probes = tokio::task::JoinSet::new();
+        for (index, device) in self.devices.iter().enumerate() {
+            if !is_auto(&device.template) {
+                continue;
+            }
+            let host = device.host.clone();
+            let options = options.clone().with_ssh_port(device.port);
+            probes
+                .spawn(async move { (index, discovery::discover_template(&host, &options).await) });
+        }
+
+        let mut results = Vec::with_capacity(probes.len());
+        while let Some(joined) = probes.join_next().await {
+            match joined {
+                Ok(result) => results.push(result),
+                Err(err) => warn!("template discovery task failed: {err}"),
+            }
+        }
+        results.sort_by_key(|(index, _)| *index);
+        results
+            .into_iter()
+            .map(|(index, result)| {
+                let device = &mut self.devices[index];
+                if let Ok(found) = &result {
+                    device.template = found.template.clone();
+                }
+                (device.name.clone(), result)
+            })
+            .collect()
+    }
+
+    /// Build a connection request for one device.
+    ///
+    /// Fails for `template: auto` devices not yet resolved by
+    /// [`Self::discover_templates`].
+    pub fn connection_request(
+        &self,
+        device: &InventoryDevice,
+    ) -> Result<ConnectionRequest, ConnectError> {
+        if is_auto(&device.template) {
+            return Err(ConnectError::InvalidRequest(format!(
+                "device '{}' has template 'auto'; run discover_templates first",
+                device.name
+            )));
+        }
+        let credentials = self.credentials.get(&device.credentials).ok_or_else(|| {
+            ConnectError::InvalidRequest(format!(
+                "device '{}' references unknown credentials '{}'",
+                device.name, device.credentials
+            ))
+        })?;
+        ConnectionRequest::for_template(
+            credentials.user.clone(),
+            device.host.clone(),
+            device.port,
+            credentials.password.clone().unwrap_or_default(),
+            credentials.enable_password.clone(),
+            &device.template,
+        )
+    }
+}
+
+/// Requests for the devices matching `selector`, grouped by effective `sys`.
+type SysBatches = Vec<(Option<String>, Vec<ConnectionRequest>)>;
+
+/// Group the selected devices by `sys`, returning the batches and the inventory order.
+fn sys_batches(
+    inventory: &Inventory,
+    selector: &str,
+    context: &ExecutionContext,
+) -> Result<(SysBatches, Vec<String>), ConnectError> {
+    let selector = DeviceSelector::parse(selector)?;
+    let selected = inventory.select(&selector);
+
+    // Group by `sys` so every device gets its own transition context.
+    let mut batches: SysBatches = Vec::new();
+    let mut order = Vec::with_capacity(selected.len());
+    for device in &selected {
+        let request = inventory.connection_request(device)?;
+        order.push(request.device_addr());
+        let sys = device.sys.clone().or_else(|| context.sys.clone());
+        match batches.iter_mut().find(|(batch_sys, _)| *batch_sys == sys) {
+            Some((_, requests)) => requests.push(request),
+            None => batches.push((sys, vec![request])),
+        }
+    }
+    Ok((batches, order))
+}
+
+/// Restore inventory order across `sys` batches.
+fn sort_by_inventory<T>(order: &[String], outcomes: &mut [DeviceOutcome<T>]) {
+    let position = |addr: &str| {
+        order
+            .iter()
+            .position(|known| known == addr)
+            .unwrap_or(usize::MAX)
+    };
+    outcomes.sort_by_key(|outcome| position(&outcome.device_addr));
+}
+
+impl SshConnectionManager {
+    /// Run `command` on every inventory device matching `selector`.
+    ///
+    /// `selector` uses [`DeviceSelector`] syntax, e.g. `"tag=edge"`. Outcomes
+    /// follow inventory order. Devices with a `sys` value override the one in
+    /// `context`, so mixed-`sys` selections run in one call per distinct value.
+    pub async fn run_on_inventory(
+        &self,
+        inventory: &Inventory,
+        selector: &str,
+        command: Command,
+        context: ExecutionContext,
+        options: FanoutOptions,
+    ) -> Result<Vec<DeviceOutcome<Output>>, ConnectError> {
+        let (batches, order) = sys_batches(inventory, selector, &context)?;
+        let mut outcomes = Vec::with_capacity(order.len());
+        for (sys, requests) in batches {
+            let context = context.clone().with_sys(sys);
+            outcomes.extend(
+                self.run_on_many(requests, command.clone(), context, options.clone())
+                    .await,
+            );
+        }
+        sort_by_inventory(&order, &mut outcomes);
+        Ok(outcomes)
+    }
+
+    /// Run a transaction workflow on every inventory device matching `selector`.
+    ///
+    /// Selection, `sys` handling and ordering match [`Self::run_on_inventory`].
+    pub async fn run_workflow_on_inventory(
+        &self,
+        inventory: &Inventory,
+        selector: &str,
+        workflow: TxWorkflow,
+        context: ExecutionContext,
+        options: FanoutOptions,
+    ) -> Result<Vec<DeviceOutcome<TxWorkflowResult>>, ConnectError> {
+        let (batches, order) = sys_batches(inventory, selector, &context)?;
+        let mut outcomes = Vec::with_capacity(order.len());
+        for (sys, requests) in batches {
+            let context = context.clone().with_sys(sys);
+            outcomes.extend(
+                self.run_workflow_on_many(requests, workflow.clone(), context, options.clone())
+                    .await,
+            );
+        }
+        sort_by_inventory(&order, &mut outcomes);
+        Ok(outcomes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const YAML: &str = r#"
+devices:
+  - name: edge-1
+    host: 192.0.2.1
+    template: cisco
+    credentials: lab
+    tags: [edge, wan]
+    groups: [dc1]
+  - name: core-1
+    host: 192.0.2.2
+    port: 2222
+    template: huawei
+    credentials: lab
+    tags: [core]
+    groups: [dc1]
+"#;
+
+    #[test]
+    fn yaml_and_csv_load_the_same_devices() {
+        let csv = "name,host,port,template,credentials,tags,groups\n\
+                   edge-1,192.0.2.1,,cisco,lab,edge;wan,dc1\n\
+                   core-1,192.0.2.2,2222,huawei,lab,core,dc1\n";
+
+        let from_yaml = Inventory::from_yaml_str(YAML).expect("yaml");
+        let from_csv = Inventory::from_csv_str(csv).expect("csv");
+
+        assert_eq!(from_yaml.devices(), from_csv.devices());
+        assert_eq!(from_yaml.get("core-1").map(|d| d.port), Some(2222));
+    }
+
+    #[test]
+    fn selector_filters_by_tag_group_and_template() {
+        let inventory = Inventory::from_yaml_str(YAML).expect("yaml");
+        let names = |expr: &str| {
+            inventory
+                .select(&DeviceSelector::parse(expr).expect("selector"))
+                .iter()
+                .map(|d| d.name.clone())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(names("tag=edge"), ["edge-1"]);
+        assert_eq!(names("group=dc1"), ["edge-1", "core-1"]);
+        assert_eq!(names("group=dc1,template=huawei"), ["core-1"]);
+        assert_eq!(names("all").len(), 2);
+        assert!(DeviceSelector::parse("site=x").is_err());
+    }
+
+    #[test]
+    fn connection_request_resolves_credentials_reference() {
+        let inventory = Inventory::from_yaml_str(YAML).expect("yaml");
+        let edge = inventory.get("edge-1").cloned().expect("device");
+        assert!(inventory.connection_request(&edge).is_err());
+
+        let inventory =
+            inventory.with_credentials("lab", DeviceCredentials::password("admin", "secret"));
+        let request = inventory.connection_request(&edge).expect("request");
+
+        assert_eq!(request.device_addr(), "admin@192.0.2.1:22");
+        assert_eq!(request.password, "secret");
+    }
+
+    #[test]
+    fn rejects_duplicate_names_and_unknown_templates() {
+        let duplicate = "devices:\n  - {name: a, host: h, template: cisco, credentials: c}\n  - {name: a, host: h2, template: cisco, credentials: c}\n";
+        assert!(Inventory::from_yaml_str(duplicate).is_err());
+
+        let unknown = "devices:\n  - {name: a, host: h, template: nope, credentials: c}\n";
+        assert!(Inventory::from_yaml_str(unknown).is_err());
+    }
+
+    #[tokio::test]
+    async fn auto_templates_are_resolved_by_discovery() {
+        use tokio::io::AsyncWriteExt;
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("bind");
+        let port = listener.local_addr().expect("addr").port();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.expect("accept");
+            let _ = stream.write_all(b"SSH-2.0-Cisco-1.25\r\n").await;
+        });
+        let yaml = format!(
+            "devices:\n  - {{name: a, host: 127.0.0.1, port: {port}, template: auto, credentials: c}}\n  - {{name: b, host: h, template: huawei, credentials: c}}\n"
+        );
+        let mut inventory = Inventory::from_yaml_str(&yaml)
+            .expect("inventory")
+            .with_credentials("c", DeviceCredentials::password("admin", "secret"));
+        let device = inventory.get("a").expect("a").clone();
+        assert!(inventory.connection_request(&device).is_err());
+
+        let options = DiscoveryOptions::new()
+            .with_snmp(false)
+            .with_timeout(std::time::Duration::from_secs(1));
+        let results = inventory.discover_templates(&options).await;
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].0, "a");
+        assert_eq!(inventory.get("a").expect("a").template, "cisco");
+        let device = inventory.get("a").expect("a").clone();
+        assert!(inventory.connection_request(&device).is_ok());
+    }
+}
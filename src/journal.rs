@@ -0,0 +1,299 @@
+//! SQLite-backed execution journal that survives process restarts.
+//!
+//! Enabled with the `journal` feature. Install a [`SqliteJournal`] on the
+//! manager and give jobs an idempotency key; after a crash, reopen the same
+//! file and list [`ExecutionJournal::in_doubt`] entries to see which changes
+//! were sent but never reported back:
+//!
+//! ```rust,no_run
+//! use std::sync::Arc;
+//! use rneter::journal::SqliteJournal;
+//! use rneter::session::{ExecutionJournal, MANAGER};
+//!
+//! let journal = Arc::new(SqliteJournal::open("rneter-journal.db")?);
+//! for entry in journal.in_doubt()? {
+//!     println!("{} on {} did not finish", entry.key, entry.device_addr);
+//! }
+//! MANAGER.set_journal(journal);
+//! # Ok::<(), rneter::error::ConnectError>(())
+//! ```
+
+use std::path::Path;
+use std::sync::Mutex;
+
+use rusqlite::{Connection, OptionalExtension, Row, params};
+
+use crate::error::ConnectError;
+use crate::session::{ExecutionJournal, JournalEntry, JournalState};
+
+const SCHEMA: &str = "CREATE TABLE IF NOT EXISTS journal (
+    key TEXT PRIMARY KEY,
+    device_addr TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    job TEXT NOT NULL,
+    state TEXT NOT NULL,
+    result TEXT,
+    error TEXT,
+    submitted_at INTEGER NOT NULL,
+    finished_at INTEGER
+)";
+
+const COLUMNS: &str =
+    "key, device_addr, kind, job, state, result, error, submitted_at, finished_at";
+
+fn journal_error(err: impl std::fmt::Display) -> ConnectError {
+    ConnectError::JournalError(err.to_string())
+}
+
+/// Journal stored in one SQLite table.
+#[derive(Debug)]
+pub struct SqliteJournal {
+    conn: Mutex<Connection>,
+}
+
+impl SqliteJournal {
+    /// Open or create the journal database at `path`.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, ConnectError> {
+        Self::with_connection(Connection::open(path).map_err(journal_error)?)
+    }
+
+    /// Journal that lives only as long as this value, e.g. for tests.
+    pub fn in_memory() -> Result<Self, ConnectError> {
+        Self::with_connection(Connection::open_in_memory().map_err(journal_error)?)
+    }
+
+    fn with_connection(conn: Connection) -> Result<Self, ConnectError> {
+        conn.execute(SCHEMA, []).map_err(journal_error)?;
+        Ok(Self {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    fn conn(&self) -> std::sync::MutexGuard<'_, Connection> {
+        self.conn
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+fn read_entry(row: &Row<'_>) -> rusqlite::Result<Result<JournalEntry, ConnectError>> {
+    let job: String = row.get(3)?;
+    let state: String = row.get(4)?;
+    let result: Option<String> = row.get(5)?;
+    let decode = || -> Result<JournalEntry, ConnectError> {
+        Ok(JournalEntry {
+            key: row.get(0).map_err(journal_error)?,
+            device_addr: row.get(1).map_err(journal_error)?,
+            kind: row.get(2).map_err(journal_error)?,
+            job: serde_json::from_str(&job).map_err(journal_error)?,
+            state: JournalState::parse(&state)
+                .ok_or_else(|| journal_error(format!("unknown state {state:?}")))?,
+            result: result
+                .as_deref()
+                .map(serde_json::from_str)
+                .transpose()
+                .map_err(journal_error)?,
+            error: row.get(6).map_err(journal_error)?,
+            submitted_at: row.get(7).map_err(journal_error)?,
+            finished_at: row.get(8).map_err(journal_error)?,
+        })
+    };
+    Ok(decode())
+}
+
+impl ExecutionJournal for SqliteJournal {
+    fn record(&self, entry: &JournalEntry) -> Result<(), ConnectError> {
+        self.conn()
+            .execute(
+                &format!("INSERT OR REPLACE INTO journal ({COLUMNS}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)"),
+                params![
+                    entry.key,
+                    entry.device_addr,
+                    entry.kind,
+                    entry.job.to_string(),
+                    entry.state.as_str(),
+                    entry.result.as_ref().map(|result| result.to_string()),
+                    entry.error,
+                    entry.submitted_at,
+                    entry.finished_at,
+                ],
+            )
+            .map_err(journal_error)?;
+        Ok(())
+    }
+
+    fn try_submit(&self, entry: &JournalEntry, rerun_failed: bool) -> Result<bool, ConnectError> {
+        let conn = self.conn();
+        let values = params![
+            entry.key,
+            entry.device_addr,
+            entry.kind,
+            entry.job.to_string(),
+            entry.state.as_str(),
+            entry.result.as_ref().map(|result| result.to_string()),
+            entry.error,
+            entry.submitted_at,
+            entry.finished_at,
+        ];
+        let inserted = conn
+            .execute(
+                &format!("INSERT INTO journal ({COLUMNS}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9) ON CONFLICT (key) DO NOTHING"),
+                values,
+            )
+            .map_err(journal_error)?;
+        if inserted > 0 {
+            return Ok(true);
+        }
+        // Only an entry that errored without a result is taken over, and one
+        // that errored on the device only when the caller asks for it.
+        let replaced = conn
+            .execute(
+                "UPDATE journal SET job = ?4, state = ?5, result = ?6, error = ?7, submitted_at = ?8, finished_at = ?9 \
+                 WHERE key = ?1 AND device_addr = ?2 AND kind = ?3 AND result IS NULL \
+                 AND (state = 'not_sent' OR (?10 AND state = 'failed'))",
+                params![
+                    entry.key,
+                    entry.device_addr,
+                    entry.kind,
+                    entry.job.to_string(),
+                    entry.state.as_str(),
+                    entry.result.as_ref().map(|result| result.to_string()),
+                    entry.error,
+                    entry.submitted_at,
+                    entry.finished_at,
+                    rerun_failed,
+                ],
+            )
+            .map_err(journal_error)?;
+        Ok(replaced > 0)
+    }
+
+    fn get(&self, key: &str) -> Result<Option<JournalEntry>, ConnectError> {
+        self.conn()
+            .query_row(
+                &format!("SELECT {COLUMNS} FROM journal WHERE key = ?1"),
+                [key],
+                read_entry,
+            )
+            .optional()
+            .map_err(journal_error)?
+            .transpose()
+    }
+
+    fn entries(&self) -> Result<Vec<JournalEntry>, ConnectError> {
+        let conn = self.conn();
+        let mut statement = conn
+            .prepare(&format!(
+                "SELECT {COLUMNS} FROM journal ORDER BY submitted_at, rowid"
+            ))
+            .map_err(journal_error)?;
+        let rows = statement.query_map([], read_entry).map_err(journal_error)?;
+        rows.map(|row| row.map_err(journal_error)?).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(key: &str, submitted_at: u64) -> JournalEntry {
+        JournalEntry {
+            key: key.to_string(),
+            device_addr: "admin@192.0.2.1:22".to_string(),
+            kind: "tx_workflow".to_string(),
+            job: serde_json::json!({"name": "core-vlans"}),
+            state: JournalState::Submitted,
+            result: None,
+            error: None,
+            submitted_at,
+            finished_at: None,
+        }
+    }
+
+    #[test]
+    fn entries_survive_reopening_the_database() {
+        let path = std::env::temp_dir().join(format!(
+            "rneter-journal-{}-{:?}.db",
+            std::process::id(),
+            std::thread::current().id()
+        ));
+        let _ = std::fs::remove_file(&path);
+        {
+            let journal = SqliteJournal::open(&path).expect("open");
+            journal.record(&entry("chg-2", 20)).expect("record");
+            journal.record(&entry("chg-1", 10)).expect("record");
+            let mut done = entry("chg-2", 20);
+            done.state = JournalState::Succeeded;
+            done.result = Some(serde_json::json!({"committed": true}));
+            done.finished_at = Some(25);
+            journal.record(&done).expect("finish");
+        }
+
+        let journal = SqliteJournal::open(&path).expect("reopen");
+        let keys: Vec<_> = journal
+            .entries()
+            .expect("entries")
+            .into_iter()
+            .map(|entry| (entry.key, entry.state))
+            .collect();
+        assert_eq!(
+            keys,
+            [
+                ("chg-1".to_string(), JournalState::Submitted),
+                ("chg-2".to_string(), JournalState::Succeeded),
+            ]
+        );
+        assert_eq!(journal.in_doubt().expect("in doubt"), [entry("chg-1", 10)]);
+        let done = journal.get("chg-2").expect("get").expect("entry");
+        assert_eq!(done.result, Some(serde_json::json!({"committed": true})));
+        assert_eq!(journal.get("chg-3").expect("get"), None);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn only_one_submission_claims_a_key() {
+        let journal = SqliteJournal::in_memory().expect("open");
+        assert!(
+            journal
+                .try_submit(&entry("chg-1", 10), false)
+                .expect("claim")
+        );
+        assert!(
+            !journal
+                .try_submit(&entry("chg-1", 11), true)
+                .expect("claim")
+        );
+        assert_eq!(journal.get("chg-1").expect("get"), Some(entry("chg-1", 10)));
+
+        let mut unsent = entry("chg-1", 10);
+        unsent.state = JournalState::NotSent;
+        unsent.error = Some("authentication failed".to_string());
+        journal.record(&unsent).expect("finish");
+        let mut elsewhere = entry("chg-1", 12);
+        elsewhere.device_addr = "admin@192.0.2.2:22".to_string();
+        assert!(!journal.try_submit(&elsewhere, false).expect("claim"));
+        assert!(
+            journal
+                .try_submit(&entry("chg-1", 12), false)
+                .expect("claim")
+        );
+        assert_eq!(journal.get("chg-1").expect("get"), Some(entry("chg-1", 12)));
+
+        let mut failed = entry("chg-1", 12);
+        failed.state = JournalState::Failed;
+        failed.error = Some("exec command timeout".to_string());
+        journal.record(&failed).expect("finish");
+        assert!(
+            !journal
+                .try_submit(&entry("chg-1", 13), false)
+                .expect("claim")
+        );
+        assert_eq!(journal.get("chg-1").expect("get"), Some(failed));
+        assert!(
+            journal
+                .try_submit(&entry("chg-1", 13), true)
+                .expect("claim")
+        );
+        assert_eq!(journal.get("chg-1").expect("get"), Some(entry("chg-1", 13)));
+    }
+}
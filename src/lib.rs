@@ -19,7 +19,7 @@
 //! ## Quick Start
 //!
 //! ```rust,no_run
-//! use rneter::session::{ConnectionRequest, ExecutionContext, MANAGER, Command, CmdJob};
+//! use rneter::session::{ConnectionRequest, ExecutionContext, MANAGER, Command, CmdJob, JobScope};
 //! use rneter::templates;
 //!
 //! #[tokio::main]
@@ -45,14 +45,17 @@
 //!     // Execute a command
 //!     let (tx, rx) = tokio::sync::oneshot::channel();
 //!     let cmd = CmdJob {
-//!         data: Command {
-//!             mode: "Enable".to_string(), // Cisco template uses "Enable" mode
-//!             command: "show version".to_string(),
-//!             timeout: Some(60),
-//!             ..Command::default()
-//!         },
+//!         // Cisco template uses "Enable" mode
+//!         data: Command::builder()
+//!             .mode("Enable")
+//!             .command("show version")
+//!             .timeout(60)
+//!             .build(),
 //!         sys: None,
+//!         recorder: None,
 //!         responder: tx,
+//!         idempotency_key: None,
+//!         scope: JobScope::default(),
 //!     };
 //!     
 //!     sender.send(cmd).await?;
@@ -69,11 +72,47 @@
 //! - [`device::DeviceHandler`] - Handles device state machine and transitions
 //! - [`error::ConnectError`] - Error types for connection and state operations
 //! - [`session::SessionOperationExecutionError`] - Operation-level execution error with partial outputs
+//! - [`compliance`] - Golden-config compliance rules and per-device findings
 //! - [`config`] - SSH configuration constants
+//! - [`discovery`] - SNMP and SSH-banner template detection before the first login
+//! - [`parsers`] - Structured parsers for device facts and common show output
+//! - [`settings`] - Global defaults from a config file and environment variables
 //! - [`templates`] - Predefined device configurations for common vendors for maximum compatibility
 
+pub mod compliance;
 pub mod config;
 pub mod device;
+pub mod discovery;
 pub mod error;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "http-api")]
+pub mod http_api;
+#[cfg(feature = "inventory")]
+pub mod inventory;
+#[cfg(feature = "journal")]
+pub mod journal;
+#[cfg(feature = "mcp")]
+pub mod mcp;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+#[cfg(feature = "netconf")]
+pub mod netconf;
+pub mod parsers;
+pub mod probe;
+#[cfg(feature = "python")]
+pub mod python;
+#[cfg(feature = "render")]
+pub mod render;
+pub mod report;
+#[cfg(feature = "scheduler")]
+pub mod scheduler;
+#[cfg(feature = "server")]
+pub mod server;
 pub mod session;
+pub mod settings;
 pub mod templates;
+#[cfg(feature = "upload")]
+pub mod upload;
+#[cfg(feature = "webhook")]
+pub mod webhook;
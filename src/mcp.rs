@@ -0,0 +1,575 @@
+//! MCP-style tool server for LLM-based operations assistants.
+//!
+//! Enabled with the `mcp` feature. [`McpServer`] speaks JSON-RPC 2.0 and
+//! implements the Model Context Protocol tool methods (`initialize`,
+//! `tools/list`, `tools/call`). Tool input schemas are generated from the
+//! same `JsonSchema` derives used elsewhere in the crate.
+//!
+//! Devices are registered by name on the server, so credentials never pass
+//! through the assistant, and every device operation runs with the server's
+//! [`CommandPolicy`] (destructive defaults unless overridden) merged with the
+//! manager-wide policy.
+
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use schemars::{JsonSchema, schema_for};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+use crate::error::ConnectError;
+use crate::session::{
+    Command, CommandPolicy, ConnectionRequest, DEFAULT_SSH_PORT, ExecutionContext, RollbackPolicy,
+    SessionOperation, SshConnectionManager, TxWorkflow,
+};
+use crate::templates;
+
+/// MCP protocol revision reported by `initialize`.
+pub const MCP_PROTOCOL_VERSION: &str = "2024-11-05";
+
+/// Connection details for a device the assistant may operate on.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct McpDevice {
+    pub host: String,
+    pub username: String,
+    pub password: String,
+    #[serde(default = "crate::session::default_ssh_port")]
+    pub port: u16,
+    #[serde(default)]
+    pub enable_password: Option<String>,
+    /// Built-in template name such as `cisco` or `huawei`.
+    pub template: String,
+    #[serde(default)]
+    pub sys: Option<String>,
+}
+
+impl std::fmt::Debug for McpDevice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("McpDevice")
+            .field("host", &self.host)
+            .field("username", &self.username)
+            .field("password", &"<redacted>")
+            .field("port", &self.port)
+            .field(
+                "enable_password",
+                &self.enable_password.as_ref().map(|_| "<redacted>"),
+            )
+            .field("template", &self.template)
+            .field("sys", &self.sys)
+            .finish()
+    }
+}
+
+impl McpDevice {
+    /// Describe a device on port 22 without an enable password.
+    pub fn new(
+        host: impl Into<String>,
+        username: impl Into<String>,
+        password: impl Into<String>,
+        template: impl Into<String>,
+    ) -> Self {
+        Self {
+            host: host.into(),
+            username: username.into(),
+            password: password.into(),
+            port: DEFAULT_SSH_PORT,
+            enable_password: None,
+            template: template.into(),
+            sys: None,
+        }
+    }
+
+    fn request(&self) -> Result<ConnectionRequest, ConnectError> {
+        ConnectionRequest::for_template(
+            self.username.clone(),
+            self.host.clone(),
+            self.port,
+            self.password.clone(),
+            self.enable_password.clone(),
+            &self.template,
+        )
+    }
+}
+
+/// Arguments of the `execute_command` tool.
+#[derive(Debug, Deserialize, JsonSchema)]
+struct ExecuteCommandArgs {
+    /// Registered device name (see `list_devices`).
+    device: String,
+    command: Command,
+}
+
+/// Arguments of the `execute_workflow` tool.
+#[derive(Debug, Deserialize, JsonSchema)]
+struct ExecuteWorkflowArgs {
+    /// Registered device name (see `list_devices`).
+    device: String,
+    workflow: TxWorkflow,
+}
+
+/// Arguments of tools that take no input.
+#[derive(Debug, Deserialize, JsonSchema)]
+struct NoArgs {}
+
+/// JSON-RPC / MCP tool server over an [`SshConnectionManager`].
+#[derive(Clone)]
+pub struct McpServer {
+    manager: SshConnectionManager,
+    devices: Arc<BTreeMap<String, McpDevice>>,
+    command_policy: CommandPolicy,
+    /// Bearer token HTTP clients must send; see [`Self::with_http_token`].
+    http_token: Option<String>,
+}
+
+impl McpServer {
+    /// Serve `manager` with destructive-command guardrails and no devices.
+    pub fn new(manager: SshConnectionManager) -> Self {
+        Self {
+            manager,
+            devices: Arc::new(BTreeMap::new()),
+            command_policy: CommandPolicy::destructive_defaults(),
+            http_token: None,
+        }
+    }
+
+    /// Register a device the assistant may address by `name`.
+    pub fn with_device(mut self, name: impl Into<String>, device: McpDevice) -> Self {
+        Arc::make_mut(&mut self.devices).insert(name.into(), device);
+        self
+    }
+
+    /// Replace the guardrail policy applied to every tool call.
+    pub fn with_command_policy(mut self, command_policy: CommandPolicy) -> Self {
+        self.command_policy = command_policy;
+        self
+    }
+
+    /// Require `Authorization: Bearer <token>` on every HTTP request.
+    pub fn with_http_token(mut self, token: impl Into<String>) -> Self {
+        self.http_token = Some(token.into());
+        self
+    }
+
+    /// Tool descriptors returned by `tools/list`.
+    pub fn tools(&self) -> Vec<Value> {
+        vec![
+            tool::<NoArgs>(
+                "list_templates",
+                "List built-in device templates and their capabilities.",
+            ),
+            tool::<NoArgs>(
+                "list_devices",
+                "List devices registered on this server (without credentials).",
+            ),
+            tool::<NoArgs>(
+                "list_connections",
+                "List pooled SSH connections with traffic and command statistics.",
+            ),
+            tool::<ExecuteCommandArgs>(
+                "execute_command",
+                "Run one command on a registered device in the given mode.",
+            ),
+            tool::<ExecuteWorkflowArgs>(
+                "execute_workflow",
+                "Run a transaction workflow with rollback on a registered device.",
+            ),
+        ]
+    }
+
+    /// Handle one JSON-RPC message; notifications yield `None`.
+    pub async fn handle(&self, message: Value) -> Option<Value> {
+        let id = message.get("id").cloned();
+        let method = message.get("method").and_then(Value::as_str);
+        let params = message.get("params").cloned().unwrap_or(Value::Null);
+
+        let result = match method {
+            Some("initialize") => Ok(json!({
+                "protocolVersion": MCP_PROTOCOL_VERSION,
+                "capabilities": { "tools": {} },
+                "serverInfo": { "name": "rneter", "version": env!("CARGO_PKG_VERSION") },
+            })),
+            Some("ping") => Ok(json!({})),
+            Some("tools/list") => Ok(json!({ "tools": self.tools() })),
+            Some("tools/call") => Ok(self.call_tool(params).await),
+            Some(method) if method.starts_with("notifications/") => return None,
+            Some(method) => Err((-32601, format!("method not found: {method}"))),
+            None => Err((-32600, "invalid request: missing method".to_string())),
+        };
+
+        // Requests without an id are notifications and get no reply.
+        let id = id?;
+        Some(match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err((code, message)) => json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": { "code": code, "message": message },
+            }),
+        })
+    }
+
+    /// Serve newline-delimited JSON-RPC on stdin/stdout until stdin closes.
+    pub async fn serve_stdio(&self) -> Result<(), ConnectError> {
+        let mut lines = BufReader::new(tokio::io::stdin()).lines();
+        let mut stdout = tokio::io::stdout();
+        while let Some(line) = lines.next_line().await.map_err(io_error)? {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let response = match serde_json::from_str::<Value>(&line) {
+                Ok(message) => self.handle(message).await,
+                Err(err) => Some(parse_error(&err)),
+            };
+            if let Some(response) = response {
+                stdout
+                    .write_all(format!("{response}\n").as_bytes())
+                    .await
+                    .map_err(io_error)?;
+                stdout.flush().await.map_err(io_error)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Serve JSON-RPC over HTTP: each `POST /mcp` body is one message.
+    ///
+    /// The tools run commands on the registered devices, so without
+    /// [`Self::with_http_token`] only loopback addresses may be served.
+    pub async fn serve_http(self, addr: SocketAddr) -> Result<(), ConnectError> {
+        use axum::Json;
+        use axum::extract::State;
+        use axum::http::{HeaderMap, StatusCode};
+        use axum::response::IntoResponse;
+
+        async fn rpc(
+            State(server): State<McpServer>,
+            headers: HeaderMap,
+            body: String,
+        ) -> axum::response::Response {
+            if !server.authorized(&headers) {
+                return StatusCode::UNAUTHORIZED.into_response();
+            }
+            let response = match serde_json::from_str::<Value>(&body) {
+                Ok(message) => server.handle(message).await,
+                Err(err) => Some(parse_error(&err)),
+            };
+            match response {
+                Some(response) => Json(response).into_response(),
+                None => StatusCode::ACCEPTED.into_response(),
+            }
+        }
+
+        if self.http_token.is_none() && !addr.ip().is_loopback() {
+            return Err(ConnectError::InvalidRequest(format!(
+                "refusing to serve MCP on {addr} without an HTTP token; bind to loopback or set one with with_http_token"
+            )));
+        }
+        let app = axum::Router::new()
+            .route("/mcp", axum::routing::post(rpc))
+            .with_state(self);
+        let listener = tokio::net::TcpListener::bind(addr)
+            .await
+            .map_err(io_error)?;
+        axum::serve(listener, app).await.map_err(io_error)
+    }
+
+    /// Whether an HTTP request carries the configured bearer token.
+    fn authorized(&self, headers: &axum::http::HeaderMap) -> bool {
+        let Some(expected) = self.http_token.as_deref() else {
+            return true;
+        };
+        headers
+            .get(axum::http::header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .is_some_and(|token| token == expected)
+    }
+
+    async fn call_tool(&self, params: Value) -> Value {
+        let name = params.get("name").and_then(Value::as_str).unwrap_or("");
+        let arguments = params
+            .get("arguments")
+            .cloned()
+            .unwrap_or_else(|| json!({}));
+        match self.run_tool(name, arguments).await {
+            Ok(value) => json!({
+                "content": [{ "type": "text", "text": value.to_string() }],
+                "isError": false,
+            }),
+            Err(err) => json!({
+                "content": [{ "type": "text", "text": err.to_string() }],
+                "isError": true,
+            }),
+        }
+    }
+
+    async fn run_tool(&self, name: &str, arguments: Value) -> Result<Value, ConnectError> {
+        match name {
+            "list_templates" => to_value(&templates::template_catalog()),
+            "list_devices" => Ok(Value::Array(
+                self.devices
+                    .iter()
+                    .map(|(name, device)| {
+                        json!({
+                            "name": name,
+                            "host": device.host,
+                            "port": device.port,
+                            "template": device.template,
+                        })
+                    })
+                    .collect(),
+            )),
+            "list_connections" => Ok(Value::Array(
+                self.manager
+                    .connections()
+                    .into_iter()
+                    .map(|info| {
+                        json!({
+                            "device_addr": info.device_addr,
+                            "connected": info.connected,
+                            "session_age_ms": info.session_age.as_millis() as u64,
+                            "stats": info.stats,
+                        })
+                    })
+                    .collect(),
+            )),
+            "execute_command" => {
+                let args: ExecuteCommandArgs = from_arguments(arguments)?;
+                self.effective_policy().check(&args.command.command)?;
+                let (request, context) = self.device(&args.device)?;
+                let output = self
+                    .manager
+                    .execute_command_with_context(request, args.command, context)
+                    .await?;
+                Ok(json!({
+                    "success": output.success,
+                    "exit_code": output.exit_code,
+                    "content": output.content,
+                    "prompt": output.prompt,
+                    "device_addr": output.device_addr,
+                    "sys": output.sys,
+                }))
+            }
+            "execute_workflow" => {
+                let args: ExecuteWorkflowArgs = from_arguments(arguments)?;
+                let policy = self.effective_policy();
+                for block in &args.workflow.blocks {
+                    match &block.rollback_policy {
+                        RollbackPolicy::WholeResource { rollback, .. } => {
+                            check_operation(&policy, rollback)?;
+                        }
+                        RollbackPolicy::Candidate(candidate) => {
+                            for operation in candidate.operations() {
+                                check_operation(&policy, &operation)?;
+                            }
+                        }
+                        _ => {}
+                    }
+                    for step in &block.steps {
+                        check_operation(&policy, &step.run)?;
+                        if let Some(rollback) = &step.rollback {
+                            check_operation(&policy, rollback)?;
+                        }
+                    }
+                }
+                let (request, context) = self.device(&args.device)?;
+                let result = self
+                    .manager
+                    .execute_tx_workflow_with_context(request, args.workflow, context)
+                    .await?;
+                to_value(&result)
+            }
+            other => Err(ConnectError::InvalidRequest(format!(
+                "unknown tool: {other}"
+            ))),
+        }
+    }
+
+    /// Server policy merged with the manager-wide one, checked before connecting.
+    fn effective_policy(&self) -> CommandPolicy {
+        self.command_policy.merge(&self.manager.command_policy())
+    }
+
+    fn device(&self, name: &str) -> Result<(ConnectionRequest, ExecutionContext), ConnectError> {
+        let device = self
+            .devices
+            .get(name)
+            .ok_or_else(|| ConnectError::InvalidRequest(format!("unknown device: {name}")))?;
+        let context = ExecutionContext::new()
+            .with_sys(device.sys.clone())
+            .with_command_policy(self.command_policy.clone());
+        Ok((device.request()?, context))
+    }
+}
+
+/// Reject an operation whose literal commands match `policy`.
+///
+/// Template operations are resolved on the device side and checked there.
+fn check_operation(
+    policy: &CommandPolicy,
+    operation: &SessionOperation,
+) -> Result<(), ConnectError> {
+    match operation {
+        SessionOperation::Command(command) => policy.check(&command.command),
+        SessionOperation::Flow(flow) => flow
+            .steps
+            .iter()
+            .try_for_each(|command| policy.check(&command.command)),
+        SessionOperation::Template { .. } => Ok(()),
+    }
+}
+
+fn tool<T: JsonSchema>(name: &str, description: &str) -> Value {
+    let mut schema = serde_json::to_value(schema_for!(T)).unwrap_or_else(|_| json!({}));
+    if let Some(object) = schema.as_object_mut() {
+        object.remove("$schema");
+        object.remove("title");
+    }
+    json!({
+        "name": name,
+        "description": description,
+        "inputSchema": schema,
+    })
+}
+
+fn from_arguments<T: serde::de::DeserializeOwned>(arguments: Value) -> Result<T, ConnectError> {
+    serde_json::from_value(arguments)
+        .map_err(|e| ConnectError::InvalidRequest(format!("invalid tool arguments: {e}")))
+}
+
+fn to_value<T: Serialize>(value: &T) -> Result<Value, ConnectError> {
+    serde_json::to_value(value).map_err(|e| ConnectError::InternalServerError(e.to_string()))
+}
+
+fn parse_error(err: &serde_json::Error) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": null,
+        "error": { "code": -32700, "message": format!("parse error: {err}") },
+    })
+}
+
+fn io_error(err: std::io::Error) -> ConnectError {
+    ConnectError::InternalServerError(format!("MCP transport error: {err}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn server() -> McpServer {
+        McpServer::new(SshConnectionManager::new()).with_device(
+            "edge-1",
+            McpDevice::new("192.0.2.1", "admin", "secret", "cisco"),
+        )
+    }
+
+    #[tokio::test]
+    async fn lists_tools_with_generated_input_schemas() {
+        let response = server()
+            .handle(json!({ "jsonrpc": "2.0", "id": 1, "method": "tools/list" }))
+            .await
+            .expect("response");
+
+        let tools = response["result"]["tools"].as_array().expect("tools");
+        let execute = tools
+            .iter()
+            .find(|tool| tool["name"] == "execute_command")
+            .expect("execute_command tool");
+        assert_eq!(execute["inputSchema"]["type"], "object");
+        assert!(execute["inputSchema"]["properties"]["command"].is_object());
+    }
+
+    #[tokio::test]
+    async fn notifications_get_no_reply_and_unknown_methods_error() {
+        let server = server();
+        assert!(
+            server
+                .handle(json!({ "jsonrpc": "2.0", "method": "notifications/initialized" }))
+                .await
+                .is_none()
+        );
+
+        let response = server
+            .handle(json!({ "jsonrpc": "2.0", "id": 2, "method": "resources/list" }))
+            .await
+            .expect("response");
+        assert_eq!(response["error"]["code"], -32601);
+    }
+
+    #[tokio::test]
+    async fn guardrails_block_destructive_commands_before_connecting() {
+        let response = server()
+            .handle(json!({
+                "jsonrpc": "2.0",
+                "id": 3,
+                "method": "tools/call",
+                "params": {
+                    "name": "execute_command",
+                    "arguments": {
+                        "device": "edge-1",
+                        "command": { "mode": "Enable", "command": "reload" },
+                    },
+                },
+            }))
+            .await
+            .expect("response");
+
+        assert_eq!(response["result"]["isError"], true);
+        let text = response["result"]["content"][0]["text"].as_str().unwrap();
+        assert!(text.contains("blocked by policy"), "{text}");
+    }
+
+    #[tokio::test]
+    async fn list_devices_hides_credentials() {
+        let response = server()
+            .handle(json!({
+                "jsonrpc": "2.0",
+                "id": 4,
+                "method": "tools/call",
+                "params": { "name": "list_devices" },
+            }))
+            .await
+            .expect("response");
+
+        let text = response["result"]["content"][0]["text"].as_str().unwrap();
+        assert!(text.contains("edge-1"));
+        assert!(!text.contains("secret"));
+    }
+
+    #[test]
+    fn device_debug_redacts_passwords() {
+        let mut device = McpDevice::new("192.0.2.1", "admin", "secret", "cisco");
+        device.enable_password = Some("enable-secret".to_string());
+
+        let rendered = format!("{device:?}");
+
+        assert!(rendered.contains("admin"));
+        assert!(!rendered.contains("secret"));
+    }
+
+    #[tokio::test]
+    async fn http_needs_a_token_beyond_loopback() {
+        let err = server()
+            .serve_http("0.0.0.0:0".parse().expect("addr"))
+            .await
+            .expect_err("refused");
+        assert!(matches!(err, ConnectError::InvalidRequest(_)));
+
+        let server = server().with_http_token("t0ken");
+        let mut headers = axum::http::HeaderMap::new();
+        assert!(!server.authorized(&headers));
+        headers.insert(
+            axum::http::header::AUTHORIZATION,
+            "Bearer wrong".parse().expect("header"),
+        );
+        assert!(!server.authorized(&headers));
+        headers.insert(
+            axum::http::header::AUTHORIZATION,
+            "Bearer t0ken".parse().expect("header"),
+        );
+        assert!(server.authorized(&headers));
+    }
+}
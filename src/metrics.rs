@@ -0,0 +1,149 @@
+//! Prometheus metrics for connection, command and transaction activity.
+//!
+//! Enabled with the `metrics` feature. Metrics are registered in a dedicated
+//! registry; scrape them with [`render`] or merge [`gather`] into your own
+//! exporter.
+
+use std::time::Duration;
+
+use once_cell::sync::Lazy;
+use prometheus::{
+    Encoder, Histogram, HistogramOpts, IntCounter, IntCounterVec, IntGauge, Opts, Registry,
+    TextEncoder,
+};
+
+struct Metrics {
+    registry: Registry,
+    connections_active: IntGauge,
+    connections_opened: IntCounter,
+    command_duration: Histogram,
+    commands: IntCounterVec,
+    command_timeouts: IntCounter,
+    rollbacks_triggered: IntCounter,
+}
+
+static METRICS: Lazy<Metrics> = Lazy::new(|| {
+    let registry = Registry::new_custom(Some("rneter".to_string()), None)
+        .expect("metrics registry prefix is valid");
+
+    let connections_active = IntGauge::new(
+        "connections_active",
+        "Number of established SSH sessions currently alive.",
+    )
+    .expect("valid metric");
+    let connections_opened = IntCounter::new(
+        "connections_opened_total",
+        "Total number of SSH sessions established.",
+    )
+    .expect("valid metric");
+    let command_duration = Histogram::with_opts(
+        HistogramOpts::new(
+            "command_duration_seconds",
+            "Command latency including mode transitions.",
+        )
+        .buckets(vec![
+            0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 120.0,
+        ]),
+    )
+    .expect("valid metric");
+    let commands = IntCounterVec::new(
+        Opts::new("commands_total", "Commands executed, by outcome."),
+        &["outcome"],
+    )
+    .expect("valid metric");
+    let command_timeouts = IntCounter::new(
+        "command_timeouts_total",
+        "Commands that timed out waiting for a prompt.",
+    )
+    .expect("valid metric");
+    let rollbacks_triggered = IntCounter::new(
+        "tx_rollbacks_triggered_total",
+        "Transaction blocks that started a rollback.",
+    )
+    .expect("valid metric");
+
+    for collector in [
+        Box::new(connections_active.clone()) as Box<dyn prometheus::core::Collector>,
+        Box::new(connections_opened.clone()),
+        Box::new(command_duration.clone()),
+        Box::new(commands.clone()),
+        Box::new(command_timeouts.clone()),
+        Box::new(rollbacks_triggered.clone()),
+    ] {
+        registry
+            .register(collector)
+            .expect("metric names are unique");
+    }
+
+    Metrics {
+        registry,
+        connections_active,
+        connections_opened,
+        command_duration,
+        commands,
+        command_timeouts,
+        rollbacks_triggered,
+    }
+});
+
+/// Collect all rneter metric families.
+pub fn gather() -> Vec<prometheus::proto::MetricFamily> {
+    METRICS.registry.gather()
+}
+
+/// Render all rneter metrics in the Prometheus text exposition format.
+pub fn render() -> String {
+    let mut buffer = Vec::new();
+    let _ = TextEncoder::new().encode(&gather(), &mut buffer);
+    String::from_utf8(buffer).unwrap_or_default()
+}
+
+/// The registry holding rneter metrics, for registering into a parent exporter.
+pub fn registry() -> &'static Registry {
+    &METRICS.registry
+}
+
+pub(crate) fn connection_opened() {
+    METRICS.connections_opened.inc();
+    METRICS.connections_active.inc();
+}
+
+pub(crate) fn connection_dropped() {
+    METRICS.connections_active.dec();
+}
+
+pub(crate) fn command_finished(elapsed: Duration, success: bool, timed_out: bool) {
+    METRICS.command_duration.observe(elapsed.as_secs_f64());
+    let outcome = if success { "success" } else { "failure" };
+    METRICS.commands.with_label_values(&[outcome]).inc();
+    if timed_out {
+        METRICS.command_timeouts.inc();
+    }
+}
+
+pub(crate) fn rollback_triggered() {
+    METRICS.rollbacks_triggered.inc();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_exposes_recorded_metrics() {
+        connection_opened();
+        command_finished(Duration::from_millis(120), true, false);
+        command_finished(Duration::from_secs(61), false, true);
+        rollback_triggered();
+        connection_dropped();
+
+        let rendered = render();
+
+        assert!(rendered.contains("rneter_connections_opened_total"));
+        assert!(rendered.contains("rneter_command_duration_seconds_bucket"));
+        assert!(rendered.contains("rneter_commands_total{outcome=\"failure\"}"));
+        assert!(rendered.contains("rneter_command_timeouts_total"));
+        assert!(rendered.contains("rneter_tx_rollbacks_triggered_total"));
+        assert!(!gather().is_empty());
+    }
+}
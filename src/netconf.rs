@@ -0,0 +1,493 @@
+//! NETCONF client over the SSH `netconf` subsystem.
+//!
+//! Enabled with the `netconf` feature. A [`NetconfSession`] runs on its own
+//! channel of a pooled connection (see
+//! [`SshConnectionManager::netconf_with_context`]), so CLI commands and
+//! NETCONF RPCs share one SSH session. Both RFC 6242 framings are supported:
+//! end-of-message (`]]>]]>`) for base:1.0 peers and chunked framing once both
+//! sides advertise base:1.1.
+//!
+//! Payloads are raw XML strings; this module only frames, wraps and checks
+//! replies for `<rpc-error>`.
+
+use std::time::Duration;
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::error::ConnectError;
+use crate::session::{ConnectionRequest, ExecutionContext, SshConnectionManager};
+
+pub const BASE_1_0: &str = "urn:ietf:params:netconf:base:1.0";
+pub const BASE_1_1: &str = "urn:ietf:params:netconf:base:1.1";
+pub const CANDIDATE: &str = "urn:ietf:params:netconf:capability:candidate:1.0";
+
+const NAMESPACE: &str = "urn:ietf:params:xml:ns:netconf:base:1.0";
+const END_OF_MESSAGE: &[u8] = b"]]>]]>";
+
+static CAPABILITY: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"<(?:\w+:)?capability>\s*([^<]+?)\s*</(?:\w+:)?capability>")
+        .expect("capability regex")
+});
+static SESSION_ID: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"<(?:\w+:)?session-id>\s*(\d+)\s*</(?:\w+:)?session-id>").expect("session-id regex")
+});
+static RPC_ERROR: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"<(?:\w+:)?rpc-error[\s>]").expect("rpc-error regex"));
+static ERROR_MESSAGE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?s)<(?:\w+:)?error-message[^>]*>(.*?)</(?:\w+:)?error-message>")
+        .expect("error-message regex")
+});
+static DATA: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?s)<(?:\w+:)?data(?:\s[^>]*)?>(.*)</(?:\w+:)?data>").expect("data regex")
+});
+
+/// Message framing in use on the session.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Framing {
+    /// base:1.0 `]]>]]>` delimiter.
+    EndOfMessage,
+    /// base:1.1 chunked framing.
+    Chunked,
+}
+
+/// Configuration datastore.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Datastore {
+    Running,
+    Candidate,
+    Startup,
+}
+
+impl Datastore {
+    fn element(self) -> &'static str {
+        match self {
+            Self::Running => "<running/>",
+            Self::Candidate => "<candidate/>",
+            Self::Startup => "<startup/>",
+        }
+    }
+}
+
+fn netconf_error(message: impl Into<String>) -> ConnectError {
+    ConnectError::NetconfError(message.into())
+}
+
+fn encode(framing: Framing, body: &str) -> Vec<u8> {
+    match framing {
+        Framing::EndOfMessage => [body.as_bytes(), END_OF_MESSAGE].concat(),
+        Framing::Chunked => format!("\n#{}\n{body}\n##\n", body.len()).into_bytes(),
+    }
+}
+
+/// Split one complete message off the front of `buffer`, if present.
+fn decode(framing: Framing, buffer: &mut Vec<u8>) -> Result<Option<String>, ConnectError> {
+    let (body, consumed) = match framing {
+        Framing::EndOfMessage => {
+            let Some(end) = buffer
+                .windows(END_OF_MESSAGE.len())
+                .position(|window| window == END_OF_MESSAGE)
+            else {
+                return Ok(None);
+            };
+            (buffer[..end].to_vec(), end + END_OF_MESSAGE.len())
+        }
+        Framing::Chunked => match decode_chunks(buffer)? {
+            Some(decoded) => decoded,
+            None => return Ok(None),
+        },
+    };
+    buffer.drain(..consumed);
+    String::from_utf8(body)
+        .map(Some)
+        .map_err(|_| netconf_error("message is not valid UTF-8"))
+}
+
+fn decode_chunks(buffer: &[u8]) -> Result<Option<(Vec<u8>, usize)>, ConnectError> {
+    let mut body = Vec::new();
+    let mut pos = 0;
+    loop {
+        let Some(header) = buffer.get(pos..pos + 3) else {
+            return Ok(None);
+        };
+        if &header[..2] != b"\n#" {
+            return Err(netconf_error("malformed chunk header"));
+        }
+        if header[2] == b'#' {
+            return match buffer.get(pos + 3) {
+                None => Ok(None),
+                Some(b'\n') => Ok(Some((body, pos + 4))),
+                Some(_) => Err(netconf_error("malformed end-of-chunks marker")),
+            };
+        }
+        let digits_start = pos + 2;
+        let Some(newline) = buffer[digits_start..].iter().position(|b| *b == b'\n') else {
+            // chunk-size is at most 10 digits.
+            if buffer.len() - digits_start > 10 {
+                return Err(netconf_error("chunk size too long"));
+            }
+            return Ok(None);
+        };
+        let size: usize = std::str::from_utf8(&buffer[digits_start..digits_start + newline])
+            .ok()
+            .and_then(|digits| digits.parse().ok())
+            .filter(|size| *size > 0)
+            .ok_or_else(|| netconf_error("invalid chunk size"))?;
+        let data_start = digits_start + newline + 1;
+        let Some(data) = buffer.get(data_start..data_start + size) else {
+            return Ok(None);
+        };
+        body.extend_from_slice(data);
+        pos = data_start + size;
+    }
+}
+
+/// Turn an `<rpc-reply>` carrying `<rpc-error>` into an error.
+fn check_reply(reply: String) -> Result<String, ConnectError> {
+    if !RPC_ERROR.is_match(&reply) {
+        return Ok(reply);
+    }
+    let message = ERROR_MESSAGE
+        .captures(&reply)
+        .map(|caps| caps[1].trim().to_string())
+        .unwrap_or_else(|| reply.trim().to_string());
+    Err(netconf_error(message))
+}
+
+/// An established NETCONF session.
+pub struct NetconfSession<S> {
+    stream: S,
+    buffer: Vec<u8>,
+    framing: Framing,
+    next_message_id: u64,
+    session_id: Option<String>,
+    capabilities: Vec<String>,
+    timeout: Duration,
+}
+
+impl<S> NetconfSession<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    /// Exchange `<hello>` messages on `stream`.
+    pub async fn handshake(stream: S) -> Result<Self, ConnectError> {
+        let mut session = Self {
+            stream,
+            buffer: Vec::new(),
+            framing: Framing::EndOfMessage,
+            next_message_id: 1,
+            session_id: None,
+            capabilities: Vec::new(),
+            timeout: Duration::from_secs(60),
+        };
+        let hello = format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?><hello xmlns="{NAMESPACE}"><capabilities><capability>{BASE_1_0}</capability><capability>{BASE_1_1}</capability></capabilities></hello>"#
+        );
+        session.send(&hello).await?;
+        let server_hello = session.receive().await?;
+
+        session.capabilities = CAPABILITY
+            .captures_iter(&server_hello)
+            .map(|caps| caps[1].to_string())
+            .collect();
+        if session.capabilities.is_empty() {
+            return Err(netconf_error("server hello has no capabilities"));
+        }
+        session.session_id = SESSION_ID
+            .captures(&server_hello)
+            .map(|caps| caps[1].to_string());
+        if session.supports(BASE_1_1) {
+            session.framing = Framing::Chunked;
+        }
+        Ok(session)
+    }
+
+    /// Override the per-RPC reply deadline (default 60 seconds).
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn session_id(&self) -> Option<&str> {
+        self.session_id.as_deref()
+    }
+
+    /// Capabilities advertised by the server.
+    pub fn capabilities(&self) -> &[String] {
+        &self.capabilities
+    }
+
+    pub fn framing(&self) -> Framing {
+        self.framing
+    }
+
+    /// Returns true when the server advertised `capability` (ignoring query parameters).
+    pub fn supports(&self, capability: &str) -> bool {
+        self.capabilities
+            .iter()
+            .any(|cap| cap.split('?').next() == Some(capability))
+    }
+
+    async fn send(&mut self, body: &str) -> Result<(), ConnectError> {
+        let frame = encode(self.framing, body);
+        self.stream.write_all(&frame).await.map_err(io_error)?;
+        self.stream.flush().await.map_err(io_error)
+    }
+
+    async fn receive(&mut self) -> Result<String, ConnectError> {
+        let timeout = self.timeout;
+        tokio::time::timeout(timeout, async {
+            let mut chunk = vec![0u8; 8192];
+            loop {
+                if let Some(message) = decode(self.framing, &mut self.buffer)? {
+                    return Ok(message);
+                }
+                let n = self.stream.read(&mut chunk).await.map_err(io_error)?;
+                if n == 0 {
+                    return Err(ConnectError::ConnectClosedError);
+                }
+                self.buffer.extend_from_slice(&chunk[..n]);
+            }
+        })
+        .await
+        .map_err(|_| ConnectError::ExecTimeout(format!("no NETCONF reply within {timeout:?}")))?
+    }
+
+    /// Send one RPC (`operation` is the XML inside `<rpc>`) and return the `<rpc-reply>`.
+    pub async fn rpc(&mut self, operation: &str) -> Result<String, ConnectError> {
+        let message_id = self.next_message_id;
+        self.next_message_id += 1;
+        self.send(&format!(
+            r#"<rpc message-id="{message_id}" xmlns="{NAMESPACE}">{operation}</rpc>"#
+        ))
+        .await?;
+        check_reply(self.receive().await?)
+    }
+
+    /// Fetch configuration, optionally narrowed by a subtree `filter`.
+    ///
+    /// Returns the XML inside `<data>`.
+    pub async fn get_config(
+        &mut self,
+        source: Datastore,
+        filter: Option<&str>,
+    ) -> Result<String, ConnectError> {
+        let filter = filter
+            .map(|filter| format!(r#"<filter type="subtree">{filter}</filter>"#))
+            .unwrap_or_default();
+        let reply = self
+            .rpc(&format!(
+                "<get-config><source>{}</source>{filter}</get-config>",
+                source.element()
+            ))
+            .await?;
+        Ok(DATA
+            .captures(&reply)
+            .map(|caps| caps[1].to_string())
+            .unwrap_or_default())
+    }
+
+    /// Merge `config` (the XML inside `<config>`) into `target`.
+    pub async fn edit_config(
+        &mut self,
+        target: Datastore,
+        config: &str,
+    ) -> Result<(), ConnectError> {
+        self.rpc(&format!(
+            "<edit-config><target>{}</target><config>{config}</config></edit-config>",
+            target.element()
+        ))
+        .await
+        .map(|_| ())
+    }
+
+    pub async fn commit(&mut self) -> Result<(), ConnectError> {
+        self.rpc("<commit/>").await.map(|_| ())
+    }
+
+    pub async fn discard_changes(&mut self) -> Result<(), ConnectError> {
+        self.rpc("<discard-changes/>").await.map(|_| ())
+    }
+
+    pub async fn lock(&mut self, target: Datastore) -> Result<(), ConnectError> {
+        self.rpc(&format!(
+            "<lock><target>{}</target></lock>",
+            target.element()
+        ))
+        .await
+        .map(|_| ())
+    }
+
+    pub async fn unlock(&mut self, target: Datastore) -> Result<(), ConnectError> {
+        self.rpc(&format!(
+            "<unlock><target>{}</target></unlock>",
+            target.element()
+        ))
+        .await
+        .map(|_| ())
+    }
+
+    /// Apply `config` transactionally.
+    ///
+    /// With the candidate capability: lock, edit the candidate, commit and
+    /// unlock, discarding the candidate if any step fails. Otherwise the
+    /// running datastore is locked and edited directly.
+    pub async fn apply_config(&mut self, config: &str) -> Result<(), ConnectError> {
+        let target = if self.supports(CANDIDATE) {
+            Datastore::Candidate
+        } else {
+            Datastore::Running
+        };
+        self.lock(target).await?;
+        let result = async {
+            self.edit_config(target, config).await?;
+            if target == Datastore::Candidate {
+                self.commit().await?;
+            }
+            Ok(())
+        }
+        .await;
+        if result.is_err() && target == Datastore::Candidate {
+            let _ = self.discard_changes().await;
+        }
+        let unlocked = self.unlock(target).await;
+        result.and(unlocked)
+    }
+
+    /// Send `<close-session>` and shut the stream down.
+    pub async fn close(mut self) -> Result<(), ConnectError> {
+        self.rpc("<close-session/>").await?;
+        self.stream.shutdown().await.map_err(io_error)
+    }
+}
+
+fn io_error(err: std::io::Error) -> ConnectError {
+    netconf_error(format!("I/O failed: {err}"))
+}
+
+impl SshConnectionManager {
+    /// Open a NETCONF session on the pooled connection for `request`.
+    pub async fn netconf_with_context(
+        &self,
+        request: ConnectionRequest,
+        context: ExecutionContext,
+    ) -> Result<NetconfSession<russh::ChannelStream<russh::client::Msg>>, ConnectError> {
+        let stream = self
+            .open_subsystem_with_context(request, context, "netconf")
+            .await?;
+        NetconfSession::handshake(stream).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SERVER_HELLO: &str = "<hello xmlns=\"urn:ietf:params:xml:ns:netconf:base:1.0\"><capabilities>\
+        <capability>urn:ietf:params:netconf:base:1.0</capability>\
+        <capability>urn:ietf:params:netconf:base:1.1</capability>\
+        <capability>urn:ietf:params:netconf:capability:candidate:1.0</capability>\
+        </capabilities><session-id>42</session-id></hello>";
+
+    async fn read_until(
+        server: &mut tokio::io::DuplexStream,
+        buffer: &mut Vec<u8>,
+        framing: Framing,
+    ) -> String {
+        let mut chunk = [0u8; 1024];
+        loop {
+            if let Some(message) = decode(framing, buffer).expect("decode") {
+                return message;
+            }
+            let n = server.read(&mut chunk).await.expect("read");
+            buffer.extend_from_slice(&chunk[..n]);
+        }
+    }
+
+    #[test]
+    fn chunked_decoding_waits_for_complete_frames() {
+        let mut buffer = b"\n#4\n<rpc\n#3".to_vec();
+        assert_eq!(
+            decode(Framing::Chunked, &mut buffer).expect("partial"),
+            None
+        );
+
+        buffer.extend_from_slice(b"\n/>x\n##\n\n#1\n");
+        assert_eq!(
+            decode(Framing::Chunked, &mut buffer).expect("complete"),
+            Some("<rpc/>x".to_string())
+        );
+        assert_eq!(buffer, b"\n#1\n");
+        assert!(decode(Framing::Chunked, &mut b"garbage".to_vec()).is_err());
+    }
+
+    #[tokio::test]
+    async fn apply_config_uses_candidate_and_switches_to_chunked_framing() {
+        let (client, mut server) = tokio::io::duplex(8192);
+        let device = tokio::spawn(async move {
+            let mut buffer = Vec::new();
+            let hello = read_until(&mut server, &mut buffer, Framing::EndOfMessage).await;
+            assert!(hello.contains(BASE_1_1));
+            server
+                .write_all(&encode(Framing::EndOfMessage, SERVER_HELLO))
+                .await
+                .expect("hello");
+
+            let mut operations = Vec::new();
+            // lock, edit-config, commit (fails), discard-changes, unlock.
+            for _ in 0..5 {
+                let rpc = read_until(&mut server, &mut buffer, Framing::Chunked).await;
+                let reply = if rpc.contains("<commit/>") {
+                    "<rpc-reply><rpc-error><error-message>commit failed</error-message></rpc-error></rpc-reply>"
+                } else {
+                    "<rpc-reply><ok/></rpc-reply>"
+                };
+                operations.push(rpc);
+                server
+                    .write_all(&encode(Framing::Chunked, reply))
+                    .await
+                    .expect("reply");
+            }
+            operations
+        });
+
+        let mut session = NetconfSession::handshake(client).await.expect("handshake");
+        assert_eq!(session.session_id(), Some("42"));
+        assert_eq!(session.framing(), Framing::Chunked);
+
+        let err = session
+            .apply_config("<system><hostname>r1</hostname></system>")
+            .await
+            .expect_err("commit fails");
+        assert!(matches!(err, ConnectError::NetconfError(ref msg) if msg == "commit failed"));
+
+        let operations = device.await.expect("device");
+        let kinds: Vec<_> = operations
+            .iter()
+            .map(|rpc| {
+                [
+                    "<lock>",
+                    "<edit-config>",
+                    "<commit/>",
+                    "<discard-changes/>",
+                    "<unlock>",
+                ]
+                .into_iter()
+                .find(|op| rpc.contains(op))
+                .unwrap_or("?")
+            })
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                "<lock>",
+                "<edit-config>",
+                "<commit/>",
+                "<discard-changes/>",
+                "<unlock>"
+            ]
+        );
+        assert!(operations[1].contains("<candidate/>"));
+    }
+}
@@ -0,0 +1,284 @@
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::error::ConnectError;
+use crate::session::{
+    Command, CommandFlow, ConnectionRequest, ExecutionContext, SshConnectionManager,
+};
+use crate::templates::template_metadata;
+
+use super::{output_text, show_mode};
+
+/// Normalized identity of a device.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct DeviceFacts {
+    pub hostname: Option<String>,
+    /// Vendor from the template catalog.
+    pub vendor: String,
+    pub model: Option<String>,
+    pub os_version: Option<String>,
+    pub serial: Option<String>,
+    /// Uptime as reported by the device (formats differ per vendor).
+    pub uptime: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Field {
+    Hostname,
+    Model,
+    OsVersion,
+    Serial,
+    Uptime,
+}
+
+struct FactsSpec {
+    commands: &'static [&'static str],
+    /// Patterns tried in order; the first capture group of the first match wins.
+    patterns: &'static [(Field, &'static str)],
+}
+
+const LINUX_FACTS: &str = "echo hostname=$(hostname); echo os=$(uname -sr); \
+echo model=$(cat /sys/class/dmi/id/product_name 2>/dev/null); \
+echo serial=$(cat /sys/class/dmi/id/product_serial 2>/dev/null); echo uptime=$(uptime -p)";
+
+fn spec(template: &str) -> Option<FactsSpec> {
+    use Field::*;
+
+    let spec = match template {
+        "cisco" => FactsSpec {
+            commands: &["show version"],
+            patterns: &[
+                (Hostname, r"(?m)^(\S+) uptime is"),
+                (Uptime, r"(?m)^\S+ uptime is ([^\r\n]+)"),
+                (OsVersion, r"Version ([^,\s]+)"),
+                (Model, r"(?m)^[Cc]isco (\S+) \(.*\) processor"),
+                (Model, r"(?m)^Model [Nn]umber\s*:\s*(\S+)"),
+                (Serial, r"(?m)^System [Ss]erial [Nn]umber\s*:\s*(\S+)"),
+                (Serial, r"(?m)^Processor board ID (\S+)"),
+            ],
+        },
+        "arista" => FactsSpec {
+            commands: &["show version", "show hostname"],
+            patterns: &[
+                (Hostname, r"(?m)^Hostname:\s*(\S+)"),
+                (Model, r"(?m)^Arista (\S+)"),
+                (Serial, r"(?m)^Serial number:\s*(\S+)"),
+                (OsVersion, r"(?m)^Software image version:\s*(\S+)"),
+                (Uptime, r"(?m)^Uptime:\s*([^\r\n]+)"),
+            ],
+        },
+        "huawei" => FactsSpec {
+            commands: &["display version", "display esn"],
+            patterns: &[
+                (OsVersion, r"VRP \(R\) software, Version ([^\r\n]+)"),
+                (Model, r"(?m)^HUAWEI (\S+)[^\r\n]* uptime is"),
+                (Uptime, r"uptime is ([^\r\n]+)"),
+                (Serial, r"(?m)^ESN of [^:]+:\s*(\S+)"),
+            ],
+        },
+        "h3c" => FactsSpec {
+            commands: &["display version", "display device manuinfo"],
+            patterns: &[
+                (OsVersion, r"Comware Software, Version ([^\r\n]+)"),
+                (Model, r"(?m)^H3C (\S+)[^\r\n]* uptime is"),
+                (Uptime, r"uptime is ([^\r\n]+)"),
+                (Serial, r"(?m)^\s*DEVICE_SERIAL_NUMBER\s*:\s*(\S+)"),
+            ],
+        },
+        "juniper" => FactsSpec {
+            commands: &[
+                "show version",
+                "show chassis hardware",
+                "show system uptime",
+            ],
+            patterns: &[
+                (Hostname, r"(?m)^Hostname:\s*(\S+)"),
+                (Model, r"(?m)^Model:\s*(\S+)"),
+                (OsVersion, r"(?m)^Junos:\s*(\S+)"),
+                (OsVersion, r"JUNOS Base OS boot \[([^\]]+)\]"),
+                (Serial, r"(?m)^Chassis\s+(\S+)"),
+                (Uptime, r"(?m)^System booted:[^(\r\n]*\(([^)]+) ago\)"),
+            ],
+        },
+        "paloalto" => FactsSpec {
+            commands: &["show system info"],
+            patterns: &[
+                (Hostname, r"(?m)^hostname:\s*(\S+)"),
+                (Model, r"(?m)^model:\s*(\S+)"),
+                (Serial, r"(?m)^serial:\s*(\S+)"),
+                (OsVersion, r"(?m)^sw-version:\s*(\S+)"),
+                (Uptime, r"(?m)^uptime:\s*([^\r\n]+)"),
+            ],
+        },
+        "fortinet" => FactsSpec {
+            commands: &["get system status"],
+            patterns: &[
+                (Hostname, r"(?m)^Hostname:\s*(\S+)"),
+                (Model, r"(?m)^Version:\s*(\S+) v"),
+                (OsVersion, r"(?m)^Version:\s*\S+ (v[^,\s]+)"),
+                (Serial, r"(?m)^Serial-Number:\s*(\S+)"),
+            ],
+        },
+        "linux" => FactsSpec {
+            commands: &[LINUX_FACTS],
+            patterns: &[
+                (Hostname, r"(?m)^hostname=(\S+)"),
+                (OsVersion, r"(?m)^os=([^\r\n]+)"),
+                (Model, r"(?m)^model=([^\r\n]+)"),
+                (Serial, r"(?m)^serial=([^\r\n]+)"),
+                (Uptime, r"(?m)^uptime=(?:up )?([^\r\n]+)"),
+            ],
+        },
+        _ => return None,
+    };
+    Some(spec)
+}
+
+/// Show commands whose combined output [`parse_facts`] expects.
+pub fn facts_commands(template: &str) -> Result<&'static [&'static str], ConnectError> {
+    let template_key = template.to_ascii_lowercase();
+    let _ = template_metadata(&template_key)?;
+    spec(&template_key)
+        .map(|spec| spec.commands)
+        .ok_or_else(|| {
+            ConnectError::InvalidRequest(format!(
+                "facts are not supported for template '{template}'"
+            ))
+        })
+}
+
+/// Parse the combined output of [`facts_commands`].
+///
+/// `prompt` is used as a hostname fallback for vendors whose version output
+/// does not carry one (e.g. `<HUAWEI>` yields `HUAWEI`).
+pub fn parse_facts(
+    template: &str,
+    output: &str,
+    prompt: Option<&str>,
+) -> Result<DeviceFacts, ConnectError> {
+    let template_key = template.to_ascii_lowercase();
+    let metadata = template_metadata(&template_key)?;
+    let spec = spec(&template_key).ok_or_else(|| {
+        ConnectError::InvalidRequest(format!("facts are not supported for template '{template}'"))
+    })?;
+
+    let mut facts = DeviceFacts {
+        vendor: metadata.vendor,
+        ..DeviceFacts::default()
+    };
+    for (field, pattern) in spec.patterns {
+        let slot = match field {
+            Field::Hostname => &mut facts.hostname,
+            Field::Model => &mut facts.model,
+            Field::OsVersion => &mut facts.os_version,
+            Field::Serial => &mut facts.serial,
+            Field::Uptime => &mut facts.uptime,
+        };
+        if slot.is_some() {
+            continue;
+        }
+        let regex = Regex::new(pattern).expect("built-in facts pattern");
+        *slot = regex
+            .captures(output)
+            .and_then(|caps| caps.get(1))
+            .map(|value| value.as_str().trim().to_string())
+            .filter(|value| !value.is_empty());
+    }
+    if facts.hostname.is_none() {
+        facts.hostname = prompt.and_then(hostname_from_prompt);
+    }
+    Ok(facts)
+}
+
+/// Strip prompt decoration: `r1#`, `<r1>`, `[r1]`, `admin@r1>`, `r1(config)#`.
+fn hostname_from_prompt(prompt: &str) -> Option<String> {
+    let prompt = prompt
+        .trim()
+        .trim_end_matches(['#', '>', '$', '%', ']', ' '])
+        .trim_start_matches(['<', '[']);
+    let prompt = prompt.rsplit_once('@').map_or(prompt, |(_, host)| host);
+    let host = prompt.split(['(', ' ']).next().unwrap_or_default();
+    (!host.is_empty()).then(|| host.to_string())
+}
+
+impl SshConnectionManager {
+    /// Collect [`DeviceFacts`] from a device running the built-in `template`.
+    pub async fn facts_with_context(
+        &self,
+        request: ConnectionRequest,
+        template: &str,
+        context: ExecutionContext,
+    ) -> Result<DeviceFacts, ConnectError> {
+        let mode = show_mode(template);
+        let flow = CommandFlow::new(
+            facts_commands(template)?
+                .iter()
+                .map(|command| Command::new(mode, *command))
+                .collect(),
+        )
+        .with_stop_on_error(false);
+        let result = self
+            .execute_command_flow_with_context(request, flow, context)
+            .await?;
+
+        let prompt = result
+            .outputs
+            .last()
+            .and_then(|output| output.prompt.clone());
+        let mut text = String::new();
+        for output in result.outputs {
+            text.push_str(&output_text(output)?);
+            text.push('\n');
+        }
+        parse_facts(template, &text, prompt.as_deref())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_cisco_show_version() {
+        let output = "Cisco IOS XE Software, Version 16.09.04\n\
+                      r1 uptime is 3 weeks, 2 days, 4 hours\n\
+                      cisco ISR4431/K9 (1RU) processor with 1795999K/6147K bytes of memory.\n\
+                      Processor board ID FOC21234ABC\n";
+
+        let facts = parse_facts("cisco", output, Some("r1#")).expect("facts");
+
+        assert_eq!(facts.hostname.as_deref(), Some("r1"));
+        assert_eq!(facts.vendor, "Cisco");
+        assert_eq!(facts.model.as_deref(), Some("ISR4431/K9"));
+        assert_eq!(facts.os_version.as_deref(), Some("16.09.04"));
+        assert_eq!(facts.serial.as_deref(), Some("FOC21234ABC"));
+        assert_eq!(facts.uptime.as_deref(), Some("3 weeks, 2 days, 4 hours"));
+    }
+
+    #[test]
+    fn huawei_hostname_falls_back_to_prompt() {
+        let output = "Huawei Versatile Routing Platform Software\n\
+                      VRP (R) software, Version 5.170 (S5700 V200R011C10SPC500)\n\
+                      HUAWEI S5700-28C-EI Routing Switch uptime is 10 weeks, 1 day\n\
+                      ESN of slot 0: 2102351931DMF4000123\n";
+
+        let facts = parse_facts("huawei", output, Some("<core-sw1>")).expect("facts");
+
+        assert_eq!(facts.hostname.as_deref(), Some("core-sw1"));
+        assert_eq!(facts.model.as_deref(), Some("S5700-28C-EI"));
+        assert_eq!(
+            facts.os_version.as_deref(),
+            Some("5.170 (S5700 V200R011C10SPC500)")
+        );
+        assert_eq!(facts.serial.as_deref(), Some("2102351931DMF4000123"));
+    }
+
+    #[test]
+    fn unsupported_templates_are_rejected() {
+        assert!(facts_commands("checkpoint").is_err());
+        assert!(facts_commands("no-such-vendor").is_err());
+        assert_eq!(facts_commands("juniper").expect("juniper").len(), 3);
+        assert_eq!(hostname_from_prompt("admin@fw1> ").as_deref(), Some("fw1"));
+    }
+}
@@ -0,0 +1,221 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::error::ConnectError;
+use crate::session::{Command, ConnectionRequest, ExecutionContext, SshConnectionManager};
+use crate::templates::template_metadata;
+
+use super::{output_text, show_mode};
+
+/// One row of an interface brief table.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct InterfaceStatus {
+    pub name: String,
+    /// Address as printed (`10.0.0.1` or `10.0.0.1/24`); `None` when unassigned.
+    pub ip_address: Option<String>,
+    /// Raw status/physical/admin column.
+    pub status: String,
+    /// Raw protocol/link column.
+    pub protocol: String,
+    pub admin_up: bool,
+    pub oper_up: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Column {
+    Name,
+    Ip,
+    /// Physical state that also marks admin-down (`administratively down`, `*down`).
+    Status,
+    /// Pure admin state (Junos `Admin`).
+    Admin,
+    Protocol,
+    Other,
+}
+
+/// Brief interface command for a built-in template.
+pub fn interface_brief_command(template: &str) -> Result<&'static str, ConnectError> {
+    let template_key = template.to_ascii_lowercase();
+    let _ = template_metadata(&template_key)?;
+    match template_key.as_str() {
+        "cisco" | "arista" | "array" | "hillstone" | "maipu" | "venustech" | "chaitin"
+        | "qianxin" => Ok("show ip interface brief"),
+        "huawei" | "h3c" | "dptech" => Ok("display ip interface brief"),
+        "juniper" => Ok("show interfaces terse"),
+        _ => Err(ConnectError::InvalidRequest(format!(
+            "interface brief is not supported for template '{template}'"
+        ))),
+    }
+}
+
+/// Parse `show ip interface brief`, `display ip interface brief` or
+/// `show interfaces terse` output.
+///
+/// The layout is detected from the header row, so legend lines before it and
+/// vendor-specific extra columns are ignored.
+pub fn parse_interface_brief(output: &str) -> Vec<InterfaceStatus> {
+    let mut lines = output.lines();
+    let Some(columns) = lines.by_ref().find_map(header_columns) else {
+        return Vec::new();
+    };
+
+    lines
+        .filter(|line| !line.starts_with(char::is_whitespace))
+        .filter_map(|line| parse_row(&columns, line))
+        .collect()
+}
+
+fn header_columns(line: &str) -> Option<Vec<Column>> {
+    let normalized = line
+        .replace("IP Address/Mask", "IP-Address")
+        .replace("IP Address", "IP-Address")
+        .replace("Primary IP", "IP-Address");
+    let words: Vec<&str> = normalized.split_whitespace().collect();
+    if words.first() != Some(&"Interface")
+        || !words
+            .iter()
+            .any(|word| matches!(*word, "Protocol" | "Link"))
+    {
+        return None;
+    }
+    Some(
+        words
+            .iter()
+            .map(|word| match *word {
+                "Interface" => Column::Name,
+                "IP-Address" | "Local" => Column::Ip,
+                "Status" | "Physical" => Column::Status,
+                "Admin" => Column::Admin,
+                "Protocol" | "Link" => Column::Protocol,
+                _ => Column::Other,
+            })
+            .collect(),
+    )
+}
+
+fn parse_row(columns: &[Column], line: &str) -> Option<InterfaceStatus> {
+    // Keep two-word states in one token.
+    let line = line.replace("administratively down", "administratively-down");
+    let tokens: Vec<&str> = line.split_whitespace().collect();
+    if tokens.len() < 2 {
+        return None;
+    }
+
+    let mut row = InterfaceStatus {
+        name: String::new(),
+        ip_address: None,
+        status: String::new(),
+        protocol: String::new(),
+        admin_up: true,
+        oper_up: false,
+    };
+    let mut admin_column = false;
+    for (column, token) in columns.iter().zip(&tokens) {
+        match column {
+            Column::Name => row.name = token.to_string(),
+            Column::Ip => {
+                row.ip_address = (!token.eq_ignore_ascii_case("unassigned")
+                    && !token.eq_ignore_ascii_case("unnumbered"))
+                .then(|| token.to_string())
+            }
+            Column::Status => row.status = token.replace('-', " "),
+            Column::Admin => {
+                row.status = token.to_string();
+                admin_column = true;
+            }
+            Column::Protocol => row.protocol = token.to_string(),
+            Column::Other => {}
+        }
+    }
+    if row.name.is_empty() || row.protocol.is_empty() {
+        return None;
+    }
+
+    let status = row.status.to_ascii_lowercase();
+    row.admin_up = if admin_column {
+        status == "up"
+    } else {
+        !(status.starts_with('*') || (status.contains("admin") && status.contains("down")))
+    };
+    row.oper_up = row.protocol.to_ascii_lowercase().starts_with("up");
+    Some(row)
+}
+
+impl SshConnectionManager {
+    /// Collect interface status from a device running the built-in `template`.
+    pub async fn interfaces_with_context(
+        &self,
+        request: ConnectionRequest,
+        template: &str,
+        context: ExecutionContext,
+    ) -> Result<Vec<InterfaceStatus>, ConnectError> {
+        let command = Command::new(show_mode(template), interface_brief_command(template)?);
+        let output = self
+            .execute_command_with_context(request, command, context)
+            .await?;
+        Ok(parse_interface_brief(&output_text(output)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_cisco_brief_with_admin_down() {
+        let output = "\
+Interface              IP-Address      OK? Method Status                Protocol
+GigabitEthernet0/0     10.0.0.1        YES NVRAM  up                    up
+GigabitEthernet0/1     unassigned      YES unset  administratively down down
+Loopback0              192.0.2.1       YES manual up                    up
+";
+        let rows = parse_interface_brief(output);
+
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[0].ip_address.as_deref(), Some("10.0.0.1"));
+        assert!(rows[0].admin_up && rows[0].oper_up);
+        assert_eq!(rows[1].status, "administratively down");
+        assert_eq!(rows[1].ip_address, None);
+        assert!(!rows[1].admin_up && !rows[1].oper_up);
+    }
+
+    #[test]
+    fn parses_huawei_brief_after_legend() {
+        let output = "\
+*down: administratively down
+^down: standby
+(s): spoofing
+The number of interface that is UP in Physical is 2
+Interface                         IP Address/Mask      Physical   Protocol
+GigabitEthernet0/0/1              10.1.1.1/24          up         up
+GigabitEthernet0/0/2              unassigned           *down      down
+NULL0                             unassigned           up         up(s)
+";
+        let rows = parse_interface_brief(output);
+
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[0].ip_address.as_deref(), Some("10.1.1.1/24"));
+        assert!(!rows[1].admin_up);
+        assert!(rows[2].oper_up);
+    }
+
+    #[test]
+    fn parses_junos_terse_admin_and_link_columns() {
+        let output = "\
+Interface               Admin Link Proto    Local                 Remote
+ge-0/0/0                up    up
+ge-0/0/0.0              up    up   inet     10.0.0.1/30
+                                   inet6    fe80::1/64
+ge-0/0/1                down  down
+";
+        let rows = parse_interface_brief(output);
+
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[1].ip_address.as_deref(), Some("10.0.0.1/30"));
+        assert!(!rows[2].admin_up);
+        assert_eq!(
+            interface_brief_command("juniper").expect("juniper"),
+            "show interfaces terse"
+        );
+    }
+}
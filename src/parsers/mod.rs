@@ -0,0 +1,36 @@
+//! Structured parsers for common show-command output.
+//!
+//! Parsers are plain functions over captured text, so they work on live
+//! output and on recordings alike. Each data type also gets a manager
+//! method that runs the right commands for a built-in template.
+
+mod facts;
+mod interfaces;
+mod tables;
+
+pub use facts::{DeviceFacts, facts_commands, parse_facts};
+pub use interfaces::{InterfaceStatus, interface_brief_command, parse_interface_brief};
+pub use tables::{
+    ArpEntry, MacEntry, arp_table_command, mac_table_command, nd_table_command, parse_arp_table,
+    parse_mac_table,
+};
+
+use crate::error::ConnectError;
+use crate::session::Output;
+
+/// Mode read-only commands run in for `template`.
+pub(crate) fn show_mode(template: &str) -> &'static str {
+    if template.eq_ignore_ascii_case("linux") {
+        "User"
+    } else {
+        "Enable"
+    }
+}
+
+/// Command output text, reading spilled transcripts back from disk.
+pub(crate) fn output_text(output: Output) -> Result<String, ConnectError> {
+    match output.spilled.as_ref() {
+        Some(spilled) => spilled.read_to_string(),
+        None => Ok(output.content),
+    }
+}
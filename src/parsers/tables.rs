@@ -0,0 +1,308 @@
+use std::net::IpAddr;
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::error::ConnectError;
+use crate::session::{Command, ConnectionRequest, ExecutionContext, SshConnectionManager};
+use crate::templates::template_metadata;
+
+use super::{output_text, show_mode};
+
+/// One learned MAC address.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct MacEntry {
+    /// VLAN ID; `None` when the table shows a VLAN name or nothing.
+    pub vlan: Option<u16>,
+    /// Lowercase, colon-separated (`00:e0:fc:12:34:56`).
+    pub mac: String,
+    pub interface: Option<String>,
+}
+
+/// One ARP (IPv4) or ND (IPv6) neighbor.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct ArpEntry {
+    pub ip: String,
+    /// Lowercase, colon-separated (`00:e0:fc:12:34:56`).
+    pub mac: String,
+    pub interface: Option<String>,
+}
+
+static MAC_TOKEN: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r"^(?:[0-9a-fA-F]{4}[.-][0-9a-fA-F]{4}[.-][0-9a-fA-F]{4}|(?:[0-9a-fA-F]{2}[:-]){5}[0-9a-fA-F]{2})$",
+    )
+    .expect("mac regex")
+});
+
+// `Gi0/1`, `ge-0/0/0.0`, `Vlanif10`, `Eth-Trunk1`, `eth0`; not `D-0` or `ARPA`.
+static INTERFACE_TOKEN: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^(?:[A-Za-z][\w-]*/[\w/.:-]*|[A-Za-z][A-Za-z-]*[A-Za-z]\d+(?:[.:]\d+)*)$")
+        .expect("interface regex")
+});
+
+static ND_BLOCK: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?m)^\s*IPv6 Address\s*:").expect("nd block regex"));
+
+static ND_INTERFACE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?m)^\s*Interface\s*:\s*(\S+)").expect("nd interface regex"));
+
+fn normalize_mac(token: &str) -> Option<String> {
+    if !MAC_TOKEN.is_match(token) {
+        return None;
+    }
+    let hex: String = token
+        .chars()
+        .filter(char::is_ascii_hexdigit)
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    let octets: Vec<&str> = (0..12).step_by(2).map(|i| &hex[i..i + 2]).collect();
+    Some(octets.join(":"))
+}
+
+fn interface_token(token: &str) -> Option<String> {
+    let token = token.trim_end_matches(',');
+    (INTERFACE_TOKEN.is_match(token)
+        && !MAC_TOKEN.is_match(token)
+        && token.parse::<IpAddr>().is_err())
+    .then(|| token.to_string())
+}
+
+/// VLAN column value: `10`, or the leading ID of Huawei's `10/-/-`.
+fn vlan_token(token: &str) -> Option<u16> {
+    let id = token.split('/').next()?;
+    id.parse::<u16>()
+        .ok()
+        .filter(|vlan| (1..=4094).contains(vlan))
+}
+
+fn unsupported(table: &str, template: &str) -> ConnectError {
+    ConnectError::InvalidRequest(format!(
+        "{table} table is not supported for template '{template}'"
+    ))
+}
+
+/// MAC address table command for a built-in template.
+pub fn mac_table_command(template: &str) -> Result<&'static str, ConnectError> {
+    let template_key = template.to_ascii_lowercase();
+    let _ = template_metadata(&template_key)?;
+    match template_key.as_str() {
+        "cisco" | "arista" | "maipu" | "venustech" => Ok("show mac address-table"),
+        "huawei" | "h3c" => Ok("display mac-address"),
+        "juniper" => Ok("show ethernet-switching table"),
+        _ => Err(unsupported("MAC", template)),
+    }
+}
+
+/// IPv4 ARP table command for a built-in template.
+pub fn arp_table_command(template: &str) -> Result<&'static str, ConnectError> {
+    let template_key = template.to_ascii_lowercase();
+    let _ = template_metadata(&template_key)?;
+    match template_key.as_str() {
+        "cisco" | "arista" => Ok("show ip arp"),
+        "maipu" | "venustech" => Ok("show arp"),
+        "huawei" | "h3c" => Ok("display arp"),
+        "juniper" => Ok("show arp no-resolve"),
+        "fortinet" => Ok("get system arp"),
+        "linux" => Ok("ip -4 neigh show"),
+        _ => Err(unsupported("ARP", template)),
+    }
+}
+
+/// IPv6 neighbor table command for a built-in template.
+pub fn nd_table_command(template: &str) -> Result<&'static str, ConnectError> {
+    let template_key = template.to_ascii_lowercase();
+    let _ = template_metadata(&template_key)?;
+    match template_key.as_str() {
+        "cisco" | "arista" | "juniper" => Ok("show ipv6 neighbors"),
+        "huawei" | "h3c" => Ok("display ipv6 neighbors"),
+        "linux" => Ok("ip -6 neigh show"),
+        _ => Err(unsupported("ND", template)),
+    }
+}
+
+/// Parse a MAC address table; rows without a MAC address are skipped.
+pub fn parse_mac_table(output: &str) -> Vec<MacEntry> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let tokens: Vec<&str> = line.split_whitespace().collect();
+            let mac = tokens.iter().find_map(|token| normalize_mac(token))?;
+            Some(MacEntry {
+                vlan: tokens.iter().find_map(|token| vlan_token(token)),
+                mac,
+                interface: tokens.iter().find_map(|token| interface_token(token)),
+            })
+        })
+        .collect()
+}
+
+/// Parse an ARP or IPv6 neighbor table.
+///
+/// Handles one-row-per-neighbor tables as well as the Huawei block layout
+/// (`IPv6 Address : ...` followed by `Link-layer`/`Interface` lines).
+/// Incomplete entries without a MAC address are skipped.
+pub fn parse_arp_table(output: &str) -> Vec<ArpEntry> {
+    if ND_BLOCK.is_match(output) {
+        return ND_BLOCK
+            .split(output)
+            .skip(1)
+            .filter_map(|block| {
+                let ip = block.split_whitespace().next()?.parse::<IpAddr>().ok()?;
+                let mac = block.split_whitespace().find_map(normalize_mac)?;
+                Some(ArpEntry {
+                    ip: ip.to_string(),
+                    mac,
+                    interface: ND_INTERFACE.captures(block).map(|caps| caps[1].to_string()),
+                })
+            })
+            .collect();
+    }
+
+    output
+        .lines()
+        .filter_map(|line| {
+            let tokens: Vec<&str> = line.split_whitespace().collect();
+            let mac = tokens.iter().find_map(|token| normalize_mac(token))?;
+            let ip = tokens
+                .iter()
+                .find_map(|token| token.parse::<IpAddr>().ok())?;
+            Some(ArpEntry {
+                ip: ip.to_string(),
+                mac,
+                interface: tokens.iter().find_map(|token| interface_token(token)),
+            })
+        })
+        .collect()
+}
+
+impl SshConnectionManager {
+    /// Collect the MAC address table from a device running the built-in `template`.
+    pub async fn mac_table_with_context(
+        &self,
+        request: ConnectionRequest,
+        template: &str,
+        context: ExecutionContext,
+    ) -> Result<Vec<MacEntry>, ConnectError> {
+        let command = Command::new(show_mode(template), mac_table_command(template)?);
+        let output = self
+            .execute_command_with_context(request, command, context)
+            .await?;
+        Ok(parse_mac_table(&output_text(output)?))
+    }
+
+    /// Collect the IPv4 ARP table from a device running the built-in `template`.
+    pub async fn arp_table_with_context(
+        &self,
+        request: ConnectionRequest,
+        template: &str,
+        context: ExecutionContext,
+    ) -> Result<Vec<ArpEntry>, ConnectError> {
+        let command = Command::new(show_mode(template), arp_table_command(template)?);
+        let output = self
+            .execute_command_with_context(request, command, context)
+            .await?;
+        Ok(parse_arp_table(&output_text(output)?))
+    }
+
+    /// Collect the IPv6 neighbor table from a device running the built-in `template`.
+    pub async fn nd_table_with_context(
+        &self,
+        request: ConnectionRequest,
+        template: &str,
+        context: ExecutionContext,
+    ) -> Result<Vec<ArpEntry>, ConnectError> {
+        let command = Command::new(show_mode(template), nd_table_command(template)?);
+        let output = self
+            .execute_command_with_context(request, command, context)
+            .await?;
+        Ok(parse_arp_table(&output_text(output)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_cisco_and_huawei_mac_tables() {
+        let cisco = "\
+          Mac Address Table
+-------------------------------------------
+Vlan    Mac Address       Type        Ports
+----    -----------       --------    -----
+ All    0100.0ccc.cccc    STATIC      CPU
+  10    aabb.cc00.0100    DYNAMIC     Gi0/1
+";
+        let huawei = "\
+MAC Address    VLAN/VSI/BD   Learned-From        Type      LSP/LSR-ID
+00e0-fc12-3456 20/-/-        GE0/0/1             dynamic   0/-
+";
+        let cisco = parse_mac_table(cisco);
+        let huawei = parse_mac_table(huawei);
+
+        assert_eq!(cisco.len(), 2);
+        assert_eq!(cisco[0].vlan, None);
+        assert_eq!(cisco[0].interface, None);
+        assert_eq!(
+            cisco[1],
+            MacEntry {
+                vlan: Some(10),
+                mac: "aa:bb:cc:00:01:00".to_string(),
+                interface: Some("Gi0/1".to_string()),
+            }
+        );
+        assert_eq!(huawei[0].vlan, Some(20));
+        assert_eq!(huawei[0].interface.as_deref(), Some("GE0/0/1"));
+    }
+
+    #[test]
+    fn parses_arp_tables_across_layouts() {
+        let cisco = "\
+Protocol  Address          Age (min)  Hardware Addr   Type   Interface
+Internet  10.0.0.1                -   aabb.cc00.0100  ARPA   GigabitEthernet0/0
+Internet  10.0.0.9                0   Incomplete      ARPA
+";
+        let huawei = "\
+IP ADDRESS      MAC ADDRESS     EXPIRE(M) TYPE        INTERFACE      VPN-INSTANCE
+10.1.1.2        00e0-fc12-3456  20        D-0         GE0/0/1
+";
+        let linux = "10.2.0.1 dev eth0 lladdr 52:54:00:12:34:56 REACHABLE\n";
+
+        let cisco = parse_arp_table(cisco);
+        assert_eq!(cisco.len(), 1);
+        assert_eq!(cisco[0].interface.as_deref(), Some("GigabitEthernet0/0"));
+        assert_eq!(
+            parse_arp_table(huawei)[0].interface.as_deref(),
+            Some("GE0/0/1")
+        );
+        assert_eq!(
+            parse_arp_table(linux),
+            vec![ArpEntry {
+                ip: "10.2.0.1".to_string(),
+                mac: "52:54:00:12:34:56".to_string(),
+                interface: Some("eth0".to_string()),
+            }]
+        );
+    }
+
+    #[test]
+    fn parses_huawei_nd_blocks() {
+        let output = "\
+IPv6 Address : FE80::2E0:FCFF:FE12:3456
+Link-layer   : 00e0-fc12-3456                     State : STALE
+Interface    : GE0/0/1                             Age   : 7
+VLAN         : -                                   CEVLAN: -
+------------------------------------------------------------------------------
+Total: 1         Dynamic: 1         Static: 0
+";
+        let entries = parse_arp_table(output);
+
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].ip, "fe80::2e0:fcff:fe12:3456");
+        assert_eq!(entries[0].interface.as_deref(), Some("GE0/0/1"));
+        assert!(nd_table_command("checkpoint").is_err());
+    }
+}
@@ -0,0 +1,253 @@
+//! Reachability prechecks.
+//!
+//! A TCP connect to the SSH port (and optionally an ICMP ping) fails fast on
+//! dead or filtered hosts, where an SSH handshake would sit until its timeout.
+//! Failures are reported as [`ConnectError::Unreachable`], distinct from SSH
+//! and authentication errors. Bulk jobs can run the check per device through
+//! [`crate::session::FanoutOptions::with_precheck`].
+
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+use tokio::net::TcpStream;
+
+use crate::error::ConnectError;
+use crate::session::{AddressFamily, HostResolver, ResolvedAddrs, bare_host};
+
+/// What a reachability probe checks.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProbeOptions {
+    /// Deadline for each check.
+    pub timeout: Duration,
+    /// Also require a reply to one ICMP echo, using the system `ping` binary.
+    pub icmp: bool,
+}
+
+impl Default for ProbeOptions {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(3),
+            icmp: false,
+        }
+    }
+}
+
+impl ProbeOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn with_icmp(mut self, icmp: bool) -> Self {
+        self.icmp = icmp;
+        self
+    }
+}
+
+fn unreachable(host: &str, reason: impl Into<String>) -> ConnectError {
+    ConnectError::Unreachable {
+        addr: bare_host(host).to_string(),
+        reason: reason.into(),
+    }
+}
+
+/// Addresses of `host` the way the SSH connection would resolve them:
+/// through `resolver`, then system DNS, ordered by `family`.
+async fn resolve(
+    host: &str,
+    port: u16,
+    timeout: Duration,
+    family: AddressFamily,
+    resolver: Option<&dyn HostResolver>,
+) -> Result<Vec<SocketAddr>, ConnectError> {
+    match tokio::time::timeout(timeout, ResolvedAddrs::lookup(host, port, family, resolver)).await {
+        Ok(Ok(resolved)) => Ok(resolved.addrs().to_vec()),
+        Ok(Err(err)) => Err(unreachable(host, format!("resolve: {err}"))),
+        Err(_) => Err(unreachable(
+            host,
+            format!("resolve: no answer within {timeout:?}"),
+        )),
+    }
+}
+
+/// Open and close a TCP connection to `host:port`, returning the connect time.
+///
+/// `host` may be a hostname or an IPv4 or IPv6 literal, with or without
+/// brackets.
+pub async fn tcp_check(host: &str, port: u16, timeout: Duration) -> Result<Duration, ConnectError> {
+    tcp_check_with(host, port, timeout, AddressFamily::Auto, None).await
+}
+
+/// [`tcp_check`] resolving `host` like the manager's connections do.
+pub(crate) async fn tcp_check_with(
+    host: &str,
+    port: u16,
+    timeout: Duration,
+    family: AddressFamily,
+    resolver: Option<&dyn HostResolver>,
+) -> Result<Duration, ConnectError> {
+    let addrs = resolve(host, port, timeout, family, resolver).await?;
+    connect(host, port, &addrs, timeout).await
+}
+
+async fn connect(
+    host: &str,
+    port: u16,
+    addrs: &[SocketAddr],
+    timeout: Duration,
+) -> Result<Duration, ConnectError> {
+    let started = Instant::now();
+    match tokio::time::timeout(timeout, TcpStream::connect(addrs)).await {
+        Ok(Ok(_stream)) => Ok(started.elapsed()),
+        Ok(Err(err)) => Err(unreachable(host, format!("tcp/{port}: {err}"))),
+        Err(_) => Err(unreachable(
+            host,
+            format!("tcp/{port}: no answer within {timeout:?}"),
+        )),
+    }
+}
+
+/// Send one ICMP echo with the system `ping` binary, returning the round-trip time.
+///
+/// Hosts starting with `-` are rejected so they cannot be read as options.
+pub async fn ping(host: &str, timeout: Duration) -> Result<Duration, ConnectError> {
+    if host.starts_with('-') {
+        return Err(ConnectError::InvalidRequest(format!(
+            "invalid host for ping: {host:?}"
+        )));
+    }
+    let mut command = tokio::process::Command::new("ping");
+    let secs = timeout.as_secs().max(1).to_string();
+    if cfg!(windows) {
+        command.args(["-n", "1", "-w", &timeout.as_millis().to_string()]);
+    } else if cfg!(target_os = "macos") {
+        command.args(["-c", "1", "-t", &secs]);
+    } else {
+        command.args(["-c", "1", "-W", &secs]);
+    }
+    command
+        .arg(host)
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .kill_on_drop(true);
+
+    let started = Instant::now();
+    let mut child = command
+        .spawn()
+        .map_err(|e| ConnectError::InternalServerError(format!("cannot run ping: {e}")))?;
+    // `ping` enforces its own deadline; the outer timeout only guards a hung
+    // binary, which is killed when `child` drops.
+    let status = tokio::time::timeout(timeout + Duration::from_secs(1), child.wait())
+        .await
+        .map_err(|_| unreachable(host, "icmp: ping did not exit"))?
+        .map_err(|e| ConnectError::InternalServerError(format!("cannot run ping: {e}")))?;
+
+    if status.success() {
+        Ok(started.elapsed())
+    } else {
+        Err(unreachable(host, "icmp: no echo reply"))
+    }
+}
+
+/// Run the checks selected by `options` against `host:port`.
+pub async fn probe(host: &str, port: u16, options: &ProbeOptions) -> Result<(), ConnectError> {
+    probe_with(host, port, options, AddressFamily::Auto, None).await
+}
+
+/// [`probe`] resolving `host` like the manager's connections do; the ping
+/// goes to the first address the connection would try.
+pub(crate) async fn probe_with(
+    host: &str,
+    port: u16,
+    options: &ProbeOptions,
+    family: AddressFamily,
+    resolver: Option<&dyn HostResolver>,
+) -> Result<(), ConnectError> {
+    let addrs = resolve(host, port, options.timeout, family, resolver).await?;
+    if options.icmp
+        && let Some(first) = addrs.first()
+    {
+        ping(&first.ip().to_string(), options.timeout).await?;
+    }
+    connect(host, port, &addrs, options.timeout)
+        .await
+        .map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn tcp_check_distinguishes_open_and_closed_ports() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("bind");
+        let open = listener.local_addr().expect("addr").port();
+
+        assert!(
+            tcp_check("127.0.0.1", open, Duration::from_secs(1))
+                .await
+                .is_ok()
+        );
+
+        // Bind and release a port so nothing is listening on it.
+        let closed = {
+            let released = tokio::net::TcpListener::bind("127.0.0.1:0")
+                .await
+                .expect("bind");
+            released.local_addr().expect("addr").port()
+        };
+        let err = tcp_check("127.0.0.1", closed, Duration::from_secs(1))
+            .await
+            .expect_err("closed port");
+        assert!(matches!(err, ConnectError::Unreachable { .. }));
+        assert!(err.is_retryable());
+        assert!(!err.is_fatal_for_connection());
+    }
+
+    #[tokio::test]
+    async fn probes_resolve_bracketed_literals_and_the_host_resolver() {
+        let listener = tokio::net::TcpListener::bind("[::1]:0").await;
+        // Hosts without IPv6 loopback cannot run the literal case.
+        if let Ok(listener) = listener {
+            let port = listener.local_addr().expect("addr").port();
+            assert!(
+                tcp_check("[::1]", port, Duration::from_secs(1))
+                    .await
+                    .is_ok()
+            );
+        }
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("bind");
+        let port = listener.local_addr().expect("addr").port();
+        let resolver = crate::session::StaticHostResolver::new()
+            .with_host("core1.lab.invalid", "127.0.0.1".parse().expect("ip"));
+        assert!(
+            probe_with(
+                "core1.lab.invalid",
+                port,
+                &ProbeOptions::new(),
+                AddressFamily::Auto,
+                Some(&resolver),
+            )
+            .await
+            .is_ok()
+        );
+    }
+
+    #[tokio::test]
+    async fn ping_rejects_hosts_read_as_options() {
+        let err = ping("-f", Duration::from_secs(1))
+            .await
+            .expect_err("option-like host");
+        assert!(matches!(err, ConnectError::InvalidRequest(_)));
+    }
+}
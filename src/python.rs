@@ -0,0 +1,381 @@
+//! Python bindings.
+//!
+//! Enabled with the `python` feature and built as an extension module with
+//! `maturin build --features python`. Every call blocks the calling Python
+//! thread on a shared Tokio runtime with the GIL released, so Python code can
+//! fan work out with ordinary threads. Structured values (transaction blocks,
+//! workflows and their results) cross the boundary as plain dicts/lists using
+//! the same JSON shape as the Rust serde types.
+
+use std::future::Future;
+use std::time::Duration;
+
+use once_cell::sync::Lazy;
+use pyo3::create_exception;
+use pyo3::exceptions::{PyException, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+use crate::error::ConnectError;
+use crate::session::{
+    Command, ConnectionRequest, DEFAULT_SSH_PORT, ExecutionContext, MANAGER, Output,
+    SshConnectionManager, TxBlock, TxWorkflow,
+};
+use crate::templates;
+
+create_exception!(rneter, RneterError, PyException);
+
+static RUNTIME: Lazy<tokio::runtime::Runtime> = Lazy::new(|| {
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .thread_name("rneter-python")
+        .build()
+        .expect("failed to build rneter Python runtime")
+});
+
+fn to_py_err(err: ConnectError) -> PyErr {
+    RneterError::new_err(err.to_string())
+}
+
+/// Run `future` on the shared runtime with the GIL released.
+fn block_on<T, F>(py: Python<'_>, future: F) -> PyResult<T>
+where
+    T: Send,
+    F: Future<Output = Result<T, ConnectError>> + Send,
+{
+    py.allow_threads(|| RUNTIME.block_on(future))
+        .map_err(to_py_err)
+}
+
+/// Convert a serde value into native Python objects.
+fn to_py<T: Serialize>(py: Python<'_>, value: &T) -> PyResult<PyObject> {
+    let text = serde_json::to_string(value).map_err(|e| PyValueError::new_err(e.to_string()))?;
+    Ok(py.import("json")?.call_method1("loads", (text,))?.unbind())
+}
+
+/// Convert native Python objects into a serde value.
+fn from_py<T: DeserializeOwned>(py: Python<'_>, value: &Bound<'_, PyAny>) -> PyResult<T> {
+    let text: String = py
+        .import("json")?
+        .call_method1("dumps", (value,))?
+        .extract()?;
+    serde_json::from_str(&text).map_err(|e| PyValueError::new_err(e.to_string()))
+}
+
+fn output_to_py(py: Python<'_>, output: Output) -> PyResult<PyObject> {
+    let dict = PyDict::new(py);
+    dict.set_item("success", output.success)?;
+    dict.set_item("exit_code", output.exit_code)?;
+    dict.set_item("content", &output.content)?;
+    dict.set_item("prompt", &output.prompt)?;
+    dict.set_item("device_addr", &output.device_addr)?;
+    dict.set_item("sys", &output.sys)?;
+    dict.set_item(
+        "spilled_path",
+        output
+            .spilled
+            .as_ref()
+            .map(|spilled| spilled.path.display().to_string()),
+    )?;
+    dict.set_item("all", output.into_all())?;
+    Ok(dict.into_any().unbind())
+}
+
+/// Connection pool shared by [`PyDevice`] objects.
+#[pyclass(module = "rneter", name = "Manager")]
+#[derive(Clone)]
+pub struct PyManager {
+    inner: SshConnectionManager,
+}
+
+#[pymethods]
+impl PyManager {
+    /// Use the process-wide manager, or a private pool when `shared` is false.
+    #[new]
+    #[pyo3(signature = (shared = true))]
+    fn new(shared: bool) -> Self {
+        let inner = if shared {
+            MANAGER.clone()
+        } else {
+            SshConnectionManager::new()
+        };
+        Self { inner }
+    }
+
+    /// Describe a device reachable through this manager.
+    #[pyo3(signature = (host, username, password, template = "cisco", port = DEFAULT_SSH_PORT, enable_password = None, sys = None))]
+    #[allow(clippy::too_many_arguments)]
+    fn device(
+        &self,
+        host: String,
+        username: String,
+        password: String,
+        template: &str,
+        port: u16,
+        enable_password: Option<String>,
+        sys: Option<String>,
+    ) -> PyResult<PyDevice> {
+        PyDevice::build(
+            self.inner.clone(),
+            host,
+            username,
+            password,
+            template,
+            port,
+            enable_password,
+            sys,
+        )
+    }
+
+    /// Live pooled connections as a list of dicts.
+    fn connections<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyList>> {
+        let list = PyList::empty(py);
+        for info in self.inner.connections() {
+            let dict = PyDict::new(py);
+            dict.set_item("device_addr", &info.device_addr)?;
+            dict.set_item("connected", info.connected)?;
+            dict.set_item("session_age_secs", info.session_age.as_secs_f64())?;
+            dict.set_item("stats", to_py(py, &info.stats)?)?;
+            list.append(dict)?;
+        }
+        Ok(list)
+    }
+
+    /// Recycle connections older than `seconds`; `None` disables the limit.
+    #[pyo3(signature = (seconds = None))]
+    fn set_max_session_lifetime(&self, seconds: Option<f64>) {
+        self.inner
+            .set_max_session_lifetime(seconds.map(Duration::from_secs_f64));
+    }
+}
+
+/// One network device addressed through a [`PyManager`].
+#[pyclass(module = "rneter", name = "Device")]
+pub struct PyDevice {
+    manager: SshConnectionManager,
+    host: String,
+    username: String,
+    password: String,
+    template: String,
+    port: u16,
+    enable_password: Option<String>,
+    sys: Option<String>,
+}
+
+impl PyDevice {
+    #[allow(clippy::too_many_arguments)]
+    fn build(
+        manager: SshConnectionManager,
+        host: String,
+        username: String,
+        password: String,
+        template: &str,
+        port: u16,
+        enable_password: Option<String>,
+        sys: Option<String>,
+    ) -> PyResult<Self> {
+        templates::template_metadata(template).map_err(to_py_err)?;
+        Ok(Self {
+            manager,
+            host,
+            username,
+            password,
+            template: template.to_ascii_lowercase(),
+            port,
+            enable_password,
+            sys,
+        })
+    }
+
+    fn request(&self) -> PyResult<ConnectionRequest> {
+        ConnectionRequest::for_template(
+            self.username.clone(),
+            self.host.clone(),
+            self.port,
+            self.password.clone(),
+            self.enable_password.clone(),
+            &self.template,
+        )
+        .map_err(to_py_err)
+    }
+
+    fn context(&self) -> ExecutionContext {
+        ExecutionContext::new().with_sys(self.sys.clone())
+    }
+}
+
+#[pymethods]
+impl PyDevice {
+    /// Describe a device reachable through the process-wide manager.
+    #[new]
+    #[pyo3(signature = (host, username, password, template = "cisco", port = DEFAULT_SSH_PORT, enable_password = None, sys = None))]
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        host: String,
+        username: String,
+        password: String,
+        template: &str,
+        port: u16,
+        enable_password: Option<String>,
+        sys: Option<String>,
+    ) -> PyResult<Self> {
+        Self::build(
+            MANAGER.clone(),
+            host,
+            username,
+            password,
+            template,
+            port,
+            enable_password,
+            sys,
+        )
+    }
+
+    /// Connection cache key (`user@addr:port`).
+    #[getter]
+    fn device_addr(&self) -> String {
+        format!(
+            "{}@{}",
+            self.username,
+            crate::session::host_port(&self.host, self.port)
+        )
+    }
+
+    /// Open (or reuse) the pooled connection.
+    fn connect(&self, py: Python<'_>) -> PyResult<()> {
+        let request = self.request()?;
+        let (manager, context) = (self.manager.clone(), self.context());
+        block_on(py, async move {
+            manager.get_with_context(request, context).await.map(|_| ())
+        })
+    }
+
+    /// Run one command, switching to `mode` first.
+    #[pyo3(signature = (command, mode = "Enable", timeout = None))]
+    fn send_command(
+        &self,
+        py: Python<'_>,
+        command: &str,
+        mode: &str,
+        timeout: Option<u64>,
+    ) -> PyResult<PyObject> {
+        let mut command = Command::new(mode, command);
+        command.timeout = timeout;
+        let request = self.request()?;
+        let (manager, context) = (self.manager.clone(), self.context());
+        let output = block_on(py, async move {
+            manager
+                .execute_command_with_context(request, command, context)
+                .await
+        })?;
+        output_to_py(py, output)
+    }
+
+    /// Collect normalized device facts (hostname, vendor, model, version, serial, uptime).
+    fn facts(&self, py: Python<'_>) -> PyResult<PyObject> {
+        let request = self.request()?;
+        let template = self.template.clone();
+        let (manager, context) = (self.manager.clone(), self.context());
+        let facts = block_on(py, async move {
+            manager
+                .facts_with_context(request, &template, context)
+                .await
+        })?;
+        to_py(py, &facts)
+    }
+
+    /// Collect interface status rows from the brief interface table.
+    fn interfaces(&self, py: Python<'_>) -> PyResult<PyObject> {
+        let request = self.request()?;
+        let template = self.template.clone();
+        let (manager, context) = (self.manager.clone(), self.context());
+        let interfaces = block_on(py, async move {
+            manager
+                .interfaces_with_context(request, &template, context)
+                .await
+        })?;
+        to_py(py, &interfaces)
+    }
+
+    /// Execute a transaction block given as a dict (see `build_tx_block`).
+    fn execute_tx_block(&self, py: Python<'_>, block: &Bound<'_, PyAny>) -> PyResult<PyObject> {
+        let block: TxBlock = from_py(py, block)?;
+        let request = self.request()?;
+        let (manager, context) = (self.manager.clone(), self.context());
+        let result = block_on(py, async move {
+            manager
+                .execute_tx_block_with_context(request, block, context)
+                .await
+        })?;
+        to_py(py, &result)
+    }
+
+    /// Execute a multi-block transaction workflow given as a dict.
+    fn execute_tx_workflow(
+        &self,
+        py: Python<'_>,
+        workflow: &Bound<'_, PyAny>,
+    ) -> PyResult<PyObject> {
+        let workflow: TxWorkflow = from_py(py, workflow)?;
+        let request = self.request()?;
+        let (manager, context) = (self.manager.clone(), self.context());
+        let result = block_on(py, async move {
+            manager
+                .execute_tx_workflow_with_context(request, workflow, context)
+                .await
+        })?;
+        to_py(py, &result)
+    }
+}
+
+/// Names of the built-in device templates.
+#[pyfunction]
+fn available_templates() -> Vec<&'static str> {
+    templates::available_templates().to_vec()
+}
+
+/// Metadata for every built-in template.
+#[pyfunction]
+fn template_catalog(py: Python<'_>) -> PyResult<PyObject> {
+    to_py(py, &templates::template_catalog())
+}
+
+/// Classify `command` as `"show"` or `"config"` for `template`.
+#[pyfunction]
+fn classify_command(py: Python<'_>, template: &str, command: &str) -> PyResult<PyObject> {
+    let kind = templates::classify_command(template, command).map_err(to_py_err)?;
+    to_py(py, &kind)
+}
+
+/// Build a transaction block dict from a command list.
+#[pyfunction]
+#[pyo3(signature = (template, name, mode, commands, timeout = None, rollback_command = None))]
+fn build_tx_block(
+    py: Python<'_>,
+    template: &str,
+    name: &str,
+    mode: &str,
+    commands: Vec<String>,
+    timeout: Option<u64>,
+    rollback_command: Option<String>,
+) -> PyResult<PyObject> {
+    let block =
+        templates::build_tx_block(template, name, mode, &commands, timeout, rollback_command)
+            .map_err(to_py_err)?;
+    to_py(py, &block)
+}
+
+/// Python module entry point.
+#[pymodule]
+fn rneter(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add("RneterError", m.py().get_type::<RneterError>())?;
+    m.add_class::<PyManager>()?;
+    m.add_class::<PyDevice>()?;
+    m.add_function(wrap_pyfunction!(available_templates, m)?)?;
+    m.add_function(wrap_pyfunction!(template_catalog, m)?)?;
+    m.add_function(wrap_pyfunction!(classify_command, m)?)?;
+    m.add_function(wrap_pyfunction!(build_tx_block, m)?)?;
+    Ok(())
+}
@@ -0,0 +1,295 @@
+//! Jinja-style `{{ placeholder }}` rendering for command lists and workflows.
+//!
+//! Enabled with the `render` feature. This is a deliberately small subset of
+//! Jinja: expressions only, no `{% %}` control blocks. Rendering happens at
+//! plan time, and an undefined variable is an error rather than an empty
+//! string, so a typo never reaches a device.
+//!
+//! ```text
+//! vlan {{ vlan_id }}
+//!  name {{ vlan.name | upper }}
+//! interface {{ uplinks | join(",") }}
+//! description {{ description | default("managed by rneter") }}
+//! {# comments are dropped #}
+//! ```
+//!
+//! Expressions are a variable path (`a.b.0.c`) or a quoted string literal,
+//! followed by any number of filters: `upper`, `lower`, `trim`,
+//! `default("...")` and `join("...")`.
+
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use crate::error::ConnectError;
+use crate::session::{TxBlock, TxWorkflow};
+
+fn render_error(message: impl Into<String>) -> ConnectError {
+    ConnectError::TemplateRenderError(message.into())
+}
+
+fn value_text(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(value) => value.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn lookup<'a>(params: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.').try_fold(params, |value, key| match value {
+        Value::Object(map) => map.get(key),
+        Value::Array(items) => key.parse::<usize>().ok().and_then(|index| items.get(index)),
+        _ => None,
+    })
+}
+
+/// Parse a quoted string literal (`"..."` or `'...'`).
+fn string_literal(text: &str) -> Option<&str> {
+    let text = text.trim();
+    let quote = text.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+    text.strip_prefix(quote)?.strip_suffix(quote)
+}
+
+/// Split an expression on `|`, ignoring pipes inside string literals.
+fn split_filters(expression: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut quote = None;
+    let mut start = 0;
+    for (index, c) in expression.char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '|') => {
+                parts.push(&expression[start..index]);
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&expression[start..]);
+    parts
+}
+
+fn evaluate(expression: &str, params: &Value, line: usize) -> Result<String, ConnectError> {
+    let mut parts = split_filters(expression).into_iter();
+    let head = parts.next().unwrap_or_default().trim();
+    if head.is_empty() {
+        return Err(render_error(format!("empty expression on line {line}")));
+    }
+
+    let mut value: Option<Value> = match string_literal(head) {
+        Some(literal) => Some(Value::String(literal.to_string())),
+        None => lookup(params, head).cloned(),
+    };
+
+    for filter in parts {
+        let filter = filter.trim();
+        let (name, argument) = match filter.split_once('(') {
+            Some((name, rest)) => {
+                let argument =
+                    rest.strip_suffix(')')
+                        .and_then(string_literal)
+                        .ok_or_else(|| {
+                            render_error(format!(
+                                "filter '{filter}' on line {line} needs one quoted argument"
+                            ))
+                        })?;
+                (name.trim(), Some(argument))
+            }
+            None => (filter, None),
+        };
+        value = match (name, argument) {
+            ("default", Some(fallback)) => match value {
+                None | Some(Value::Null) => Some(Value::String(fallback.to_string())),
+                defined => defined,
+            },
+            ("join", Some(separator)) => value.map(|value| match value {
+                Value::Array(items) => Value::String(
+                    items
+                        .iter()
+                        .map(value_text)
+                        .collect::<Vec<_>>()
+                        .join(separator),
+                ),
+                other => other,
+            }),
+            ("upper", None) => value.map(|value| Value::String(value_text(&value).to_uppercase())),
+            ("lower", None) => value.map(|value| Value::String(value_text(&value).to_lowercase())),
+            ("trim", None) => {
+                value.map(|value| Value::String(value_text(&value).trim().to_string()))
+            }
+            _ => {
+                return Err(render_error(format!(
+                    "unknown filter '{filter}' on line {line}"
+                )));
+            }
+        };
+    }
+
+    value
+        .map(|value| value_text(&value))
+        .ok_or_else(|| render_error(format!("undefined variable '{head}' on line {line}")))
+}
+
+/// Render `template`, resolving every `{{ expression }}` from `params`.
+///
+/// `params` is usually a JSON object; nested objects and arrays are reached
+/// with dotted paths.
+pub fn render_str(template: &str, params: &Value) -> Result<String, ConnectError> {
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+    let line_of = |rest: &str| {
+        template[..template.len() - rest.len()]
+            .matches('\n')
+            .count()
+            + 1
+    };
+
+    while let Some(open) = rest.find('{') {
+        let tag = &rest[open..];
+        let close = if tag.starts_with("{{") {
+            "}}"
+        } else if tag.starts_with("{#") {
+            "#}"
+        } else if tag.starts_with("{%") {
+            return Err(render_error(format!(
+                "control blocks are not supported (line {})",
+                line_of(tag)
+            )));
+        } else {
+            rendered.push_str(&rest[..=open]);
+            rest = &rest[open + 1..];
+            continue;
+        };
+
+        rendered.push_str(&rest[..open]);
+        let line = line_of(tag);
+        let end = tag[2..]
+            .find(close)
+            .ok_or_else(|| render_error(format!("unclosed tag on line {line}")))?;
+        if close == "}}" {
+            rendered.push_str(&evaluate(&tag[2..2 + end], params, line)?);
+        }
+        rest = &tag[2 + end + 2..];
+    }
+    rendered.push_str(rest);
+    Ok(rendered)
+}
+
+/// Render each command of a list.
+pub fn render_commands<S: AsRef<str>>(
+    commands: &[S],
+    params: &Value,
+) -> Result<Vec<String>, ConnectError> {
+    commands
+        .iter()
+        .map(|command| render_str(command.as_ref(), params))
+        .collect()
+}
+
+/// Render every string of a serializable value, e.g. a workflow definition.
+fn render_strings<T: Serialize + DeserializeOwned>(
+    value: &T,
+    params: &Value,
+) -> Result<T, ConnectError> {
+    fn walk(value: &mut Value, params: &Value) -> Result<(), ConnectError> {
+        match value {
+            Value::String(text) if text.contains('{') => *text = render_str(text, params)?,
+            Value::Array(items) => items.iter_mut().try_for_each(|item| walk(item, params))?,
+            Value::Object(map) => map.values_mut().try_for_each(|item| walk(item, params))?,
+            _ => {}
+        }
+        Ok(())
+    }
+
+    let mut tree = serde_json::to_value(value).map_err(|e| render_error(e.to_string()))?;
+    walk(&mut tree, params)?;
+    serde_json::from_value(tree).map_err(|e| render_error(e.to_string()))
+}
+
+/// Render placeholders in every command, rollback command and name of `block`.
+pub fn render_tx_block(block: &TxBlock, params: &Value) -> Result<TxBlock, ConnectError> {
+    render_strings(block, params)
+}
+
+/// Render placeholders in every block of `workflow`.
+pub fn render_tx_workflow(
+    workflow: &TxWorkflow,
+    params: &Value,
+) -> Result<TxWorkflow, ConnectError> {
+    render_strings(workflow, params)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn renders_paths_filters_and_comments() {
+        let params = json!({
+            "vlan_id": 120,
+            "vlan": {"name": "users"},
+            "uplinks": ["Gi0/1", "Gi0/2"],
+            "description": null,
+        });
+        let rendered = render_str(
+            "vlan {{ vlan_id }}\n name {{vlan.name|upper}}{# note #}\n\
+             interface range {{ uplinks | join(\",\") }}\n\
+             description {{ description | default('managed | rneter') }}\n\
+             {{ uplinks.1 }} {x}",
+            &params,
+        )
+        .expect("render");
+
+        assert_eq!(
+            rendered,
+            "vlan 120\n name USERS\ninterface range Gi0/1,Gi0/2\n\
+             description managed | rneter\nGi0/2 {x}"
+        );
+    }
+
+    #[test]
+    fn undefined_variables_and_bad_syntax_fail_with_line_numbers() {
+        let params = json!({"vlan_id": 10});
+        let err = render_commands(&["vlan {{ vlan_id }}", "name {{ vlan_name }}"], &params)
+            .expect_err("missing variable");
+        assert!(
+            err.to_string()
+                .contains("undefined variable 'vlan_name' on line 1")
+        );
+
+        let err = render_str("a\nb {{ vlan_id | shout }}", &params).expect_err("filter");
+        assert!(err.to_string().contains("unknown filter 'shout' on line 2"));
+        assert!(render_str("{{ vlan_id", &params).is_err());
+        assert!(render_str("{% if x %}", &params).is_err());
+    }
+
+    #[test]
+    fn renders_commands_inside_tx_workflows() {
+        let block = crate::templates::build_tx_block(
+            "cisco",
+            "vlan-{{ vlan_id }}",
+            "Config",
+            &["vlan {{ vlan_id }}".to_string()],
+            None,
+            Some("no vlan {{ vlan_id }}".to_string()),
+        )
+        .expect("block");
+        let workflow = TxWorkflow {
+            name: "add-vlan".to_string(),
+            blocks: vec![block],
+            fail_fast: true,
+            preflight: false,
+            idempotency_key: None,
+        };
+
+        let rendered = render_tx_workflow(&workflow, &json!({"vlan_id": 30})).expect("render");
+        let json = serde_json::to_string(&rendered).expect("json");
+        assert!(json.contains("vlan 30"));
+        assert!(json.contains("no vlan 30"));
+        assert!(!json.contains("{{"));
+        assert!(render_tx_block(&workflow.blocks[0], &json!({})).is_err());
+    }
+}
@@ -0,0 +1,711 @@
+//! JUnit XML, CSV and HTML exports of workflow, fan-out and session results.
+//!
+//! CI systems render JUnit XML natively and change tickets take CSV or HTML
+//! attachments, so bulk runs can be published without custom glue:
+//!
+//! - [`workflow_to_junit`] / [`workflow_to_csv`]: one test suite per
+//!   transaction block and one test case (or row) per step.
+//! - [`outcomes_to_junit`] / [`outcomes_to_csv`]: one test case (or row) per
+//!   device of a fan-out call such as
+//!   [`run_on_many`](crate::session::SshConnectionManager::run_on_many).
+//! - [`recording_to_html`]: a standalone page with the commands, outputs,
+//!   state changes and rollbacks of a session recording, also available as
+//!   [`SessionRecorder::to_html_report`](crate::session::SessionRecorder::to_html_report).
+
+use std::fmt::Write as _;
+
+use serde::Serialize;
+
+use crate::session::{
+    DeviceOutcome, Output, SessionEvent, SessionRecordEntry, TxResult, TxStepExecutionState,
+    TxWorkflowResult,
+};
+
+const HTML_STYLE: &str = "\
+body{font-family:system-ui,sans-serif;margin:2em;color:#1f2328}\
+h1{font-size:1.4em;margin-bottom:.2em}\
+.meta{color:#59636e;margin-bottom:1.5em}\
+table{border-collapse:collapse;width:100%}\
+td{border-top:1px solid #d1d9e0;padding:.4em .6em;vertical-align:top}\
+td.time{color:#59636e;white-space:nowrap;font-variant-numeric:tabular-nums}\
+.badge{display:inline-block;min-width:6em;padding:.1em .5em;border-radius:1em;font-size:.8em;text-align:center;background:#eff2f5}\
+.ok{background:#dafbe1}.fail{background:#ffebe9}.rollback{background:#fff8c5}.state{background:#ddf4ff}\
+code,pre{font-family:ui-monospace,monospace;font-size:.9em}\
+pre{background:#f6f8fa;padding:.6em;margin:.4em 0 0;overflow-x:auto;white-space:pre-wrap}\
+";
+
+/// Result types that can tell whether they count as a failure in a report.
+pub trait ReportStatus {
+    /// Failure message, or `None` when the result is a pass.
+    fn failure_message(&self) -> Option<String>;
+}
+
+impl ReportStatus for () {
+    fn failure_message(&self) -> Option<String> {
+        None
+    }
+}
+
+impl ReportStatus for Output {
+    fn failure_message(&self) -> Option<String> {
+        (!self.success).then(|| match self.exit_code {
+            Some(code) => format!("command failed with exit code {code}"),
+            None => "command failed".to_string(),
+        })
+    }
+}
+
+impl ReportStatus for TxResult {
+    fn failure_message(&self) -> Option<String> {
+        (!self.committed).then(|| {
+            self.failure_reason
+                .clone()
+                .unwrap_or_else(|| format!("block '{}' not committed", self.block_name))
+        })
+    }
+}
+
+impl ReportStatus for TxWorkflowResult {
+    fn failure_message(&self) -> Option<String> {
+        if self.committed {
+            return None;
+        }
+        let failed = self
+            .failed_block
+            .and_then(|index| self.block_results.get(index));
+        Some(match failed.and_then(ReportStatus::failure_message) {
+            Some(reason) => reason,
+            None => format!("workflow '{}' not committed", self.workflow_name),
+        })
+    }
+}
+
+/// Render a workflow result as a JUnit `<testsuites>` document.
+pub fn workflow_to_junit(result: &TxWorkflowResult) -> String {
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    let (tests, failures, skipped) = result
+        .block_results
+        .iter()
+        .map(block_counts)
+        .fold((0, 0, 0), |acc, c| (acc.0 + c.0, acc.1 + c.1, acc.2 + c.2));
+    let _ = writeln!(
+        xml,
+        "<testsuites name=\"{}\" tests=\"{tests}\" failures=\"{failures}\" skipped=\"{skipped}\">",
+        escape_xml(&result.workflow_name)
+    );
+    for block in &result.block_results {
+        let (tests, failures, skipped) = block_counts(block);
+        let _ = writeln!(
+            xml,
+            "  <testsuite name=\"{}\" tests=\"{tests}\" failures=\"{failures}\" skipped=\"{skipped}\">",
+            escape_xml(&block.block_name)
+        );
+        let classname = escape_xml(&format!("{}.{}", result.workflow_name, block.block_name));
+        for step in &block.step_results {
+            let _ = write!(
+                xml,
+                "    <testcase classname=\"{classname}\" name=\"{}\"",
+                escape_xml(&format!(
+                    "step {}: {}",
+                    step.step_index, step.operation_summary
+                ))
+            );
+            match step.execution_state {
+                TxStepExecutionState::Succeeded => xml.push_str("/>\n"),
+                TxStepExecutionState::NotRun => {
+                    xml.push_str(">\n      <skipped/>\n    </testcase>\n")
+                }
+                TxStepExecutionState::Failed => {
+                    let message = step.failure_reason.as_deref().unwrap_or("step failed");
+                    let _ = write!(
+                        xml,
+                        ">\n      <failure message=\"{}\">rollback: {}",
+                        escape_xml(message),
+                        state_name(&step.rollback_state)
+                    );
+                    if let Some(reason) = step.rollback_reason.as_deref() {
+                        let _ = write!(xml, " ({})", escape_xml(reason));
+                    }
+                    xml.push_str("</failure>\n    </testcase>\n");
+                }
+            }
+        }
+        xml.push_str("  </testsuite>\n");
+    }
+    xml.push_str("</testsuites>\n");
+    xml
+}
+
+/// Render a workflow result as CSV with one row per step.
+pub fn workflow_to_csv(result: &TxWorkflowResult) -> String {
+    let mut csv = String::from(
+        "workflow,block,step,mode,operation,state,failure_reason,rollback_state,rollback_reason\n",
+    );
+    for block in &result.block_results {
+        for step in &block.step_results {
+            push_csv_row(
+                &mut csv,
+                &[
+                    &result.workflow_name,
+                    &block.block_name,
+                    &step.step_index.to_string(),
+                    &step.mode,
+                    &step.operation_summary,
+                    &state_name(&step.execution_state),
+                    step.failure_reason.as_deref().unwrap_or_default(),
+                    &state_name(&step.rollback_state),
+                    step.rollback_reason.as_deref().unwrap_or_default(),
+                ],
+            );
+        }
+    }
+    csv
+}
+
+/// Render fan-out outcomes as a JUnit `<testsuite>` with one case per device.
+pub fn outcomes_to_junit<T: ReportStatus>(suite: &str, outcomes: &[DeviceOutcome<T>]) -> String {
+    let failures = outcomes
+        .iter()
+        .filter(|outcome| outcome_failure(outcome).is_some())
+        .count();
+    let total: f64 = outcomes.iter().map(|o| o.elapsed.as_secs_f64()).sum();
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    let _ = writeln!(
+        xml,
+        "<testsuite name=\"{}\" tests=\"{}\" failures=\"{failures}\" time=\"{total:.3}\">",
+        escape_xml(suite),
+        outcomes.len()
+    );
+    for outcome in outcomes {
+        let _ = write!(
+            xml,
+            "  <testcase classname=\"{}\" name=\"{}\" time=\"{:.3}\"",
+            escape_xml(suite),
+            escape_xml(&outcome.device_addr),
+            outcome.elapsed.as_secs_f64()
+        );
+        match outcome_failure(outcome) {
+            None => xml.push_str("/>\n"),
+            Some(message) => {
+                let _ = write!(
+                    xml,
+                    ">\n    <failure message=\"{}\"/>\n  </testcase>\n",
+                    escape_xml(&message)
+                );
+            }
+        }
+    }
+    xml.push_str("</testsuite>\n");
+    xml
+}
+
+/// Render fan-out outcomes as CSV with one row per device.
+pub fn outcomes_to_csv<T: ReportStatus>(outcomes: &[DeviceOutcome<T>]) -> String {
+    let mut csv = String::from("device_addr,success,elapsed_ms,error\n");
+    for outcome in outcomes {
+        let failure = outcome_failure(outcome);
+        push_csv_row(
+            &mut csv,
+            &[
+                &outcome.device_addr,
+                if failure.is_none() { "true" } else { "false" },
+                &outcome.elapsed.as_millis().to_string(),
+                failure.as_deref().unwrap_or_default(),
+            ],
+        );
+    }
+    csv
+}
+
+fn outcome_failure<T: ReportStatus>(outcome: &DeviceOutcome<T>) -> Option<String> {
+    match &outcome.result {
+        Ok(value) => value.failure_message(),
+        Err(err) => Some(err.to_string()),
+    }
+}
+
+/// Test, failure and skipped counts of one block.
+fn block_counts(block: &TxResult) -> (usize, usize, usize) {
+    let count = |state| {
+        block
+            .step_results
+            .iter()
+            .filter(|step| step.execution_state == state)
+            .count()
+    };
+    (
+        block.step_results.len(),
+        count(TxStepExecutionState::Failed),
+        count(TxStepExecutionState::NotRun),
+    )
+}
+
+/// Serialized (snake_case) name of a state enum.
+/// Render a session recording as a standalone HTML page.
+///
+/// Raw shell chunks are left out; command outputs carry the same text.
+pub fn recording_to_html(entries: &[SessionRecordEntry]) -> String {
+    let device = entries.iter().find_map(|entry| match &entry.event {
+        SessionEvent::ConnectionEstablished { device_addr, .. } => Some(device_addr.as_str()),
+        _ => None,
+    });
+    let title = match device {
+        Some(device) => format!("Session transcript: {device}"),
+        None => "Session transcript".to_string(),
+    };
+    let commands = entries
+        .iter()
+        .filter(|entry| matches!(entry.event, SessionEvent::CommandOutput { .. }))
+        .count();
+
+    let mut html =
+        String::from("<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n");
+    let _ = writeln!(html, "<title>{}</title>", escape_xml(&title));
+    let _ = writeln!(html, "<style>{HTML_STYLE}</style>\n</head>\n<body>");
+    let _ = writeln!(html, "<h1>{}</h1>", escape_xml(&title));
+    let _ = write!(
+        html,
+        "<p class=\"meta\">{} events, {commands} commands",
+        entries.len()
+    );
+    if let Some(first) = entries.first() {
+        let _ = write!(html, ", started {}", utc_timestamp(first.ts_ms));
+    }
+    html.push_str("</p>\n<table>\n");
+    for entry in entries {
+        let Some((class, label, body)) = html_event(&entry.event) else {
+            continue;
+        };
+        let _ = writeln!(
+            html,
+            "<tr><td class=\"time\">+{}.{:03}s</td><td><span class=\"badge {class}\">{label}</span></td><td>{body}</td></tr>",
+            entry.mono_ms / 1000,
+            entry.mono_ms % 1000
+        );
+    }
+    html.push_str("</table>\n</body>\n</html>\n");
+    html
+}
+
+/// Badge class, badge label and escaped body of one event row.
+fn html_event(event: &SessionEvent) -> Option<(&'static str, &'static str, String)> {
+    let code = |text: &str| format!("<code>{}</code>", escape_xml(text));
+    let outcome = |success: bool| if success { "ok" } else { "fail" };
+    let row = match event {
+        SessionEvent::ConnectionEstablished {
+            device_addr,
+            prompt_after,
+            ..
+        } => (
+            "state",
+            "connected",
+            format!("{} at {}", escape_xml(device_addr), code(prompt_after)),
+        ),
+        SessionEvent::ConnectionClosed { reason, .. } => ("state", "closed", escape_xml(reason)),
+        SessionEvent::CommandOutput {
+            command,
+            mode,
+            success,
+            exit_code,
+            duration_ms,
+            content,
+            all,
+            ..
+        } => {
+            let mut body = format!("{} in {}", code(command), escape_xml(mode));
+            if let Some(code) = exit_code {
+                let _ = write!(body, ", exit code {code}");
+            }
+            if let Some(ms) = duration_ms {
+                let _ = write!(body, ", {ms} ms");
+            }
+            let output = if content.is_empty() { all } else { content };
+            if !output.is_empty() {
+                let open = if *success { "" } else { " open" };
+                let _ = write!(
+                    body,
+                    "<details{open}><summary>{} lines</summary><pre>{}</pre></details>",
+                    output.lines().count(),
+                    escape_xml(output)
+                );
+            }
+            (outcome(*success), "command", body)
+        }
+        SessionEvent::PromptChanged { prompt } => ("state", "prompt", code(prompt)),
+        SessionEvent::StateChanged { state } => ("state", "state", code(state)),
+        SessionEvent::FileUploadStarted {
+            local_path,
+            remote_path,
+        } => (
+            "",
+            "upload",
+            format!("{} to {}", code(local_path), code(remote_path)),
+        ),
+        SessionEvent::FileDownloadStarted {
+            remote_path,
+            local_path,
+        } => (
+            "",
+            "download",
+            format!("{} to {}", code(remote_path), code(local_path)),
+        ),
+        SessionEvent::FileUploadFinished { success, error, .. }
+        | SessionEvent::FileDownloadFinished { success, error, .. } => (
+            outcome(*success),
+            "transfer",
+            escape_xml(error.as_deref().unwrap_or("finished")),
+        ),
+        SessionEvent::TxWorkflowStarted {
+            workflow_name,
+            total_blocks,
+        } => (
+            "",
+            "workflow",
+            format!(
+                "{} started, {total_blocks} blocks",
+                escape_xml(workflow_name)
+            ),
+        ),
+        SessionEvent::TxBlockStarted {
+            block_name,
+            block_kind,
+        } => (
+            "",
+            "block",
+            format!(
+                "{} ({}) started",
+                escape_xml(block_name),
+                state_name(block_kind)
+            ),
+        ),
+        SessionEvent::TxStepSucceeded {
+            step_index,
+            operation_summary,
+            ..
+        } => (
+            "ok",
+            "step",
+            format!("{step_index}: {}", code(operation_summary)),
+        ),
+        SessionEvent::TxStepFailed {
+            step_index,
+            operation_summary,
+            reason,
+            ..
+        } => (
+            "fail",
+            "step",
+            format!(
+                "{step_index}: {} failed: {}",
+                code(operation_summary),
+                escape_xml(reason)
+            ),
+        ),
+        SessionEvent::TxRollbackStarted { block_name } => (
+            "rollback",
+            "rollback",
+            format!("{} rolling back", escape_xml(block_name)),
+        ),
+        SessionEvent::TxRollbackStepSucceeded {
+            operation_summary, ..
+        } => (
+            "rollback",
+            "rollback",
+            format!("{} succeeded", code(operation_summary)),
+        ),
+        SessionEvent::TxRollbackStepFailed {
+            operation_summary,
+            reason,
+            ..
+        } => (
+            "fail",
+            "rollback",
+            format!("{} failed: {}", code(operation_summary), escape_xml(reason)),
+        ),
+        SessionEvent::TxBlockFinished {
+            block_name,
+            committed,
+            rollback_attempted,
+            rollback_succeeded,
+        }
+        | SessionEvent::TxWorkflowFinished {
+            workflow_name: block_name,
+            committed,
+            rollback_attempted,
+            rollback_succeeded,
+        } => {
+            let label = if matches!(event, SessionEvent::TxBlockFinished { .. }) {
+                "block"
+            } else {
+                "workflow"
+            };
+            let status = match (committed, rollback_attempted, rollback_succeeded) {
+                (true, _, _) => "committed",
+                (false, false, _) => "failed",
+                (false, true, true) => "failed, rolled back",
+                (false, true, false) => "failed, rollback failed",
+            };
+            (
+                outcome(*committed),
+                label,
+                format!("{} {status}", escape_xml(block_name)),
+            )
+        }
+        SessionEvent::CommandBlocked {
+            command, pattern, ..
+        } => (
+            "fail",
+            "blocked",
+            format!("{} matched {}", code(command), code(pattern)),
+        ),
+        SessionEvent::ResidualOutput { content, .. } => (
+            "",
+            "residual",
+            format!("<pre>{}</pre>", escape_xml(content)),
+        ),
+        SessionEvent::SlowCommand {
+            command,
+            elapsed_ms,
+            timeout_ms,
+            ..
+        } => (
+            "rollback",
+            "slow",
+            format!("{} running {elapsed_ms} of {timeout_ms} ms", code(command)),
+        ),
+        SessionEvent::UnmodeledPrompt {
+            question, answer, ..
+        } => match answer {
+            Some(answer) => (
+                "rollback",
+                "question",
+                format!("{} answered {}", code(question), code(answer)),
+            ),
+            None => ("fail", "question", format!("{} unanswered", code(question))),
+        },
+        SessionEvent::Annotation { text } => ("", "note", escape_xml(text)),
+        SessionEvent::RawChunk { .. } => return None,
+    };
+    Some(row)
+}
+
+/// `YYYY-MM-DD HH:MM:SS UTC` for Unix milliseconds.
+fn utc_timestamp(ts_ms: u128) -> String {
+    let secs = (ts_ms / 1000) as i64;
+    let (days, rem) = (secs.div_euclid(86_400), secs.rem_euclid(86_400));
+    // Civil date from days since 1970-01-01 (proleptic Gregorian).
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{year:04}-{month:02}-{day:02} {:02}:{:02}:{:02} UTC",
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    )
+}
+
+fn state_name<T: Serialize>(state: &T) -> String {
+    serde_json::to_value(state)
+        .ok()
+        .and_then(|value| value.as_str().map(str::to_string))
+        .unwrap_or_default()
+}
+
+fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            // Control characters other than whitespace are not allowed in XML 1.0.
+            c if c.is_control() && !matches!(c, '\n' | '\r' | '\t') => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn push_csv_row(csv: &mut String, fields: &[&str]) {
+    for (index, field) in fields.iter().enumerate() {
+        if index > 0 {
+            csv.push(',');
+        }
+        if field.contains([',', '"', '\n', '\r']) {
+            csv.push('"');
+            csv.push_str(&field.replace('"', "\"\""));
+            csv.push('"');
+        } else {
+            csv.push_str(field);
+        }
+    }
+    csv.push('\n');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ConnectError;
+    use crate::session::{TxStepResult, TxStepRollbackState};
+    use std::time::Duration;
+
+    fn step(index: usize, state: TxStepExecutionState) -> TxStepResult {
+        TxStepResult {
+            step_index: index,
+            mode: "Config".to_string(),
+            operation_summary: format!("vlan 1{index}"),
+            execution_state: state,
+            failure_reason: (state == TxStepExecutionState::Failed)
+                .then(|| "% Invalid input, \"vlan\"".to_string()),
+            forward_operation_steps: Vec::new(),
+            rollback_state: TxStepRollbackState::NotNeeded,
+            rollback_operation_summary: None,
+            rollback_reason: None,
+            rollback_operation_steps: Vec::new(),
+        }
+    }
+
+    fn workflow() -> TxWorkflowResult {
+        TxWorkflowResult {
+            workflow_name: "vlans".to_string(),
+            committed: false,
+            failed_block: Some(0),
+            block_results: vec![TxResult {
+                block_name: "add<vlans>".to_string(),
+                committed: false,
+                failed_step: Some(1),
+                executed_steps: 1,
+                rollback_attempted: true,
+                rollback_succeeded: true,
+                rollback_steps: 1,
+                failure_reason: Some("step 1 failed".to_string()),
+                rollback_errors: Vec::new(),
+                block_rollback_operation_summary: None,
+                block_rollback_steps: Vec::new(),
+                step_results: vec![
+                    step(0, TxStepExecutionState::Succeeded),
+                    step(1, TxStepExecutionState::Failed),
+                    step(2, TxStepExecutionState::NotRun),
+                ],
+                config_snapshot: None,
+            }],
+            rollback_attempted: true,
+            rollback_succeeded: true,
+            rollback_errors: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn workflow_exports_one_case_or_row_per_step() {
+        let xml = workflow_to_junit(&workflow());
+        assert!(xml.contains(
+            "<testsuite name=\"add&lt;vlans&gt;\" tests=\"3\" failures=\"1\" skipped=\"1\">"
+        ));
+        assert!(xml.contains("<failure message=\"% Invalid input, &quot;vlan&quot;\">"));
+        assert!(xml.contains("name=\"step 2: vlan 12\">\n      <skipped/>"));
+
+        let csv = workflow_to_csv(&workflow());
+        let rows: Vec<_> = csv.lines().collect();
+        assert_eq!(rows.len(), 4);
+        assert_eq!(
+            rows[2],
+            "vlans,add<vlans>,1,Config,vlan 11,failed,\"% Invalid input, \"\"vlan\"\"\",not_needed,"
+        );
+    }
+
+    #[test]
+    fn outcomes_report_errors_and_uncommitted_results_as_failures() {
+        let outcomes = vec![
+            DeviceOutcome {
+                device_addr: "admin@192.0.2.1:22".to_string(),
+                result: Ok(workflow()),
+                elapsed: Duration::from_millis(1500),
+            },
+            DeviceOutcome {
+                device_addr: "admin@192.0.2.2:22".to_string(),
+                result: Err(ConnectError::DeviceTimeout(Duration::from_secs(120))),
+                elapsed: Duration::from_secs(120),
+            },
+        ];
+        let xml = outcomes_to_junit("nightly", &outcomes);
+        assert!(xml.contains("tests=\"2\" failures=\"2\""));
+        assert!(xml.contains("<failure message=\"step 1 failed\"/>"));
+
+        let csv = outcomes_to_csv(&outcomes);
+        assert_eq!(
+            csv.lines().nth(2),
+            Some("admin@192.0.2.2:22,false,120000,device did not finish within 120s")
+        );
+    }
+
+    #[test]
+    fn recording_html_escapes_output_and_marks_rollbacks() {
+        let entry = |seq: u64, event| SessionRecordEntry {
+            seq,
+            mono_ms: seq * 1250,
+            ts_ms: 1_790_000_000_000,
+            event,
+        };
+        let entries = vec![
+            entry(
+                1,
+                SessionEvent::ConnectionEstablished {
+                    device_addr: "admin@192.0.2.1:22".to_string(),
+                    remote_addr: None,
+                    prompt_after: "sw1#".to_string(),
+                    fsm_prompt_after: "enable".to_string(),
+                },
+            ),
+            entry(
+                2,
+                SessionEvent::CommandOutput {
+                    command: "vlan 10".to_string(),
+                    mode: "Config".to_string(),
+                    prompt_before: None,
+                    prompt_after: None,
+                    fsm_prompt_before: None,
+                    fsm_prompt_after: None,
+                    success: false,
+                    exit_code: None,
+                    duration_ms: Some(40),
+                    content: "% Invalid <input>".to_string(),
+                    all: String::new(),
+                },
+            ),
+            entry(
+                3,
+                SessionEvent::RawChunk {
+                    data: "raw bytes".to_string(),
+                },
+            ),
+            entry(
+                4,
+                SessionEvent::TxRollbackStepSucceeded {
+                    block_name: "vlans".to_string(),
+                    step_index: Some(0),
+                    mode: "Config".to_string(),
+                    operation_summary: "no vlan 10".to_string(),
+                    operation_steps: Vec::new(),
+                },
+            ),
+        ];
+        let html = recording_to_html(&entries);
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<h1>Session transcript: admin@192.0.2.1:22</h1>"));
+        assert!(html.contains("4 events, 1 commands, started 2026-09-21 14:13:20 UTC"));
+        assert!(html.contains(
+            "<details open><summary>1 lines</summary><pre>% Invalid &lt;input&gt;</pre>"
+        ));
+        assert!(html.contains("+5.000s</td><td><span class=\"badge rollback\">rollback</span>"));
+        assert!(!html.contains("raw bytes"));
+    }
+}
@@ -0,0 +1,1019 @@
+//! Cron-scheduled commands and workflows against inventory targets.
+//!
+//! Enabled with the `scheduler` feature (which implies `inventory`). A
+//! [`Scheduler`] owns a set of [`ScheduledJob`]s; once started it wakes at each
+//! due minute and runs the job on every device matching the job's selector
+//! through the shared manager, bounded by the job's fan-out limits and by a
+//! scheduler-wide cap on concurrently running jobs. Each run is kept in a
+//! bounded per-job history.
+//!
+//! A drift job fetches every selected device's config with the library's
+//! `backup_config` command and compares it with the baseline last approved
+//! for that device, reporting the changed sections in [`JobRun::drift`].
+//!
+//! Schedules use the classic five-field cron syntax evaluated in UTC:
+//!
+//! ```text
+//! ┌ minute (0-59)
+//! │ ┌ hour (0-23)
+//! │ │ ┌ day of month (1-31)
+//! │ │ │ ┌ month (1-12)
+//! │ │ │ │ ┌ day of week (0-7, 0 and 7 are Sunday)
+//! 0 2 * * 1-5      weekdays at 02:00
+//! ```
+//!
+//! Fields accept `*`, numbers, ranges (`1-5`), lists (`1,15`) and steps
+//! (`*/15`, `0-30/10`). `@hourly`, `@daily`, `@weekly`, `@monthly` and
+//! `@yearly` are shorthands.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{Notify, Semaphore};
+use tokio::task::JoinHandle;
+
+use crate::error::ConnectError;
+use crate::inventory::{DeviceSelector, Inventory};
+use crate::session::{
+    Command, DeviceOutcome, ExecutionContext, FanoutOptions, Output, SshConnectionManager,
+    TxWorkflow,
+};
+use crate::templates::{ConfigGrammar, DriftSection};
+
+/// Days scanned before a schedule is declared unsatisfiable (e.g. `0 0 30 2 *`).
+const MAX_SEARCH_DAYS: i64 = 366 * 5;
+
+/// A parsed five-field cron expression.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct CronSchedule {
+    expression: String,
+    minutes: u64,
+    hours: u64,
+    days_of_month: u64,
+    months: u64,
+    days_of_week: u64,
+    /// Day-of-month and day-of-week were both restricted, so either may match.
+    either_day: bool,
+}
+
+fn cron_error(expression: &str, reason: impl fmt::Display) -> ConnectError {
+    ConnectError::InvalidRequest(format!("invalid cron schedule '{expression}': {reason}"))
+}
+
+/// Parse one cron field into a bit set of allowed values.
+fn parse_field(field: &str, min: u32, max: u32) -> Result<u64, String> {
+    let mut bits = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => {
+                let step: u32 = step.parse().map_err(|_| format!("bad step '{step}'"))?;
+                if step == 0 {
+                    return Err("step must be positive".to_string());
+                }
+                (range, step)
+            }
+            None => (part, 1),
+        };
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some((start, end)) = range.split_once('-') {
+            let start = start.parse().map_err(|_| format!("bad value '{start}'"))?;
+            let end = end.parse().map_err(|_| format!("bad value '{end}'"))?;
+            (start, end)
+        } else {
+            let value = range.parse().map_err(|_| format!("bad value '{range}'"))?;
+            // `5/10` means "from 5 to the end, every 10".
+            (value, if part.contains('/') { max } else { value })
+        };
+        if start < min || end > max || start > end {
+            return Err(format!("'{part}' is outside {min}-{max}"));
+        }
+        for value in (start..=end).step_by(step as usize) {
+            bits |= 1 << value;
+        }
+    }
+    Ok(bits)
+}
+
+impl CronSchedule {
+    /// Parse a cron expression such as `*/15 * * * *` or `@daily`.
+    pub fn parse(expression: &str) -> Result<Self, ConnectError> {
+        let expanded = match expression.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            other => other,
+        };
+        let fields: Vec<&str> = expanded.split_whitespace().collect();
+        let [minute, hour, dom, month, dow] = fields.as_slice() else {
+            return Err(cron_error(expression, "expected five fields"));
+        };
+        let parse = |field: &str, min, max| {
+            parse_field(field, min, max).map_err(|reason| cron_error(expression, reason))
+        };
+
+        let mut days_of_week = parse(dow, 0, 7)?;
+        if days_of_week & (1 << 7) != 0 {
+            days_of_week = (days_of_week | 1) & !(1 << 7);
+        }
+        Ok(Self {
+            expression: expression.trim().to_string(),
+            minutes: parse(minute, 0, 59)?,
+            hours: parse(hour, 0, 23)?,
+            days_of_month: parse(dom, 1, 31)?,
+            months: parse(month, 1, 12)?,
+            days_of_week,
+            either_day: !dom.starts_with('*') && !dow.starts_with('*'),
+        })
+    }
+
+    /// The expression as written.
+    pub fn expression(&self) -> &str {
+        &self.expression
+    }
+
+    fn day_matches(&self, days: i64) -> bool {
+        let (_, month, day) = civil_from_days(days);
+        if self.months & (1 << month) == 0 {
+            return false;
+        }
+        // 1970-01-01 was a Thursday.
+        let weekday = (days + 4).rem_euclid(7);
+        let dom = self.days_of_month & (1 << day) != 0;
+        let dow = self.days_of_week & (1 << weekday) != 0;
+        if self.either_day {
+            dom || dow
+        } else {
+            dom && dow
+        }
+    }
+
+    /// First matching minute strictly after `after`, or `None` if the schedule never fires.
+    pub fn next_after(&self, after: SystemTime) -> Option<SystemTime> {
+        let secs = after.duration_since(UNIX_EPOCH).ok()?.as_secs() as i64;
+        let start_minute = secs / 60 + 1;
+        let first_day = start_minute.div_euclid(1440);
+        let first_minute = start_minute.rem_euclid(1440);
+
+        for days in first_day..first_day + MAX_SEARCH_DAYS {
+            if !self.day_matches(days) {
+                continue;
+            }
+            let from = if days == first_day { first_minute } else { 0 };
+            let found = (from..1440).find(|minute| {
+                self.hours & (1 << (minute / 60)) != 0 && self.minutes & (1 << (minute % 60)) != 0
+            });
+            if let Some(minute) = found {
+                let secs = (days * 1440 + minute) * 60;
+                return Some(UNIX_EPOCH + Duration::from_secs(secs as u64));
+            }
+        }
+        None
+    }
+}
+
+/// Convert days since 1970-01-01 to a `(year, month, day)` civil date.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+impl FromStr for CronSchedule {
+    type Err = ConnectError;
+
+    fn from_str(expression: &str) -> Result<Self, Self::Err> {
+        Self::parse(expression)
+    }
+}
+
+impl TryFrom<String> for CronSchedule {
+    type Error = ConnectError;
+
+    fn try_from(expression: String) -> Result<Self, Self::Error> {
+        Self::parse(&expression)
+    }
+}
+
+impl From<CronSchedule> for String {
+    fn from(schedule: CronSchedule) -> Self {
+        schedule.expression
+    }
+}
+
+impl fmt::Display for CronSchedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.expression)
+    }
+}
+
+/// What a job runs on each selected device.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum JobAction {
+    Command {
+        command: Command,
+    },
+    Workflow {
+        workflow: TxWorkflow,
+    },
+    /// Compare each device's config with its approved baseline.
+    Drift,
+}
+
+/// A named job: what to run, where and when.
+#[derive(Clone)]
+pub struct ScheduledJob {
+    /// Unique job name, used as the history key.
+    pub name: String,
+    pub schedule: CronSchedule,
+    /// Inventory selector such as `tag=edge,group=dc1`.
+    pub selector: String,
+    pub action: JobAction,
+    pub context: ExecutionContext,
+    pub fanout: FanoutOptions,
+}
+
+impl ScheduledJob {
+    /// Schedule `command` on the devices matching `selector`.
+    pub fn command(
+        name: impl Into<String>,
+        schedule: &str,
+        selector: impl Into<String>,
+        command: Command,
+    ) -> Result<Self, ConnectError> {
+        Self::new(name, schedule, selector, JobAction::Command { command })
+    }
+
+    /// Schedule `workflow` on the devices matching `selector`.
+    pub fn workflow(
+        name: impl Into<String>,
+        schedule: &str,
+        selector: impl Into<String>,
+        workflow: TxWorkflow,
+    ) -> Result<Self, ConnectError> {
+        Self::new(name, schedule, selector, JobAction::Workflow { workflow })
+    }
+
+    /// Schedule a config drift check on the devices matching `selector`.
+    pub fn drift(
+        name: impl Into<String>,
+        schedule: &str,
+        selector: impl Into<String>,
+    ) -> Result<Self, ConnectError> {
+        Self::new(name, schedule, selector, JobAction::Drift)
+    }
+
+    fn new(
+        name: impl Into<String>,
+        schedule: &str,
+        selector: impl Into<String>,
+        action: JobAction,
+    ) -> Result<Self, ConnectError> {
+        Ok(Self {
+            name: name.into(),
+            schedule: CronSchedule::parse(schedule)?,
+            selector: selector.into(),
+            action,
+            context: ExecutionContext::default(),
+            fanout: FanoutOptions::default(),
+        })
+    }
+
+    pub fn with_context(mut self, context: ExecutionContext) -> Self {
+        self.context = context;
+        self
+    }
+
+    /// Override the per-run device concurrency and deadlines.
+    pub fn with_fanout(mut self, fanout: FanoutOptions) -> Self {
+        self.fanout = fanout;
+        self
+    }
+}
+
+/// Result of one job run on one device.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JobDeviceResult {
+    /// Connection cache key (`user@addr:port`).
+    pub device_addr: String,
+    /// Command succeeded, workflow committed, or config matches its baseline.
+    pub success: bool,
+    /// Command output content, or the fetched config of a drift job; `None`
+    /// for workflows and failed devices.
+    pub output: Option<String>,
+    pub error: Option<String>,
+}
+
+/// One completed run of a job.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JobRun {
+    pub job: String,
+    pub started_at: SystemTime,
+    pub elapsed: Duration,
+    /// Set when the run could not start, e.g. an invalid selector.
+    pub error: Option<String>,
+    /// Per-device results in inventory order.
+    pub devices: Vec<JobDeviceResult>,
+    /// Fleet drift report of a drift job.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub drift: Option<DriftReport>,
+}
+
+/// Drift of one device against its approved baseline.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeviceDrift {
+    /// Inventory device name.
+    pub device: String,
+    /// Connection cache key (`user@addr:port`).
+    pub device_addr: String,
+    /// Changed sections; empty when the config matches the baseline.
+    pub sections: Vec<DriftSection>,
+}
+
+/// Result of a drift job over the devices that could be compared.
+///
+/// Devices without a baseline or whose config could not be fetched appear
+/// only as failed entries of [`JobRun::devices`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DriftReport {
+    /// Compared devices in inventory order.
+    pub devices: Vec<DeviceDrift>,
+}
+
+impl DriftReport {
+    /// Devices whose config changed since its baseline.
+    pub fn drifted(&self) -> impl Iterator<Item = &DeviceDrift> {
+        self.devices
+            .iter()
+            .filter(|device| !device.sections.is_empty())
+    }
+}
+
+impl JobRun {
+    /// Returns true when the run started and every device succeeded.
+    pub fn is_success(&self) -> bool {
+        self.error.is_none() && self.devices.iter().all(|device| device.success)
+    }
+}
+
+fn device_results<T>(
+    outcomes: Vec<DeviceOutcome<T>>,
+    summarize: impl Fn(T) -> (bool, Option<String>),
+) -> Vec<JobDeviceResult> {
+    outcomes
+        .into_iter()
+        .map(|outcome| match outcome.result {
+            Ok(value) => {
+                let (success, output) = summarize(value);
+                JobDeviceResult {
+                    device_addr: outcome.device_addr,
+                    success,
+                    output,
+                    error: None,
+                }
+            }
+            Err(err) => JobDeviceResult {
+                device_addr: outcome.device_addr,
+                success: false,
+                output: None,
+                error: Some(err.to_string()),
+            },
+        })
+        .collect()
+}
+
+struct SchedulerState {
+    jobs: Vec<ScheduledJob>,
+    history: HashMap<String, VecDeque<JobRun>>,
+    running: HashSet<String>,
+    /// Approved configs keyed by inventory device name.
+    baselines: HashMap<String, String>,
+    /// Configs fetched by the latest drift run, keyed by device name.
+    latest: HashMap<String, String>,
+}
+
+/// Runs [`ScheduledJob`]s on their schedules and keeps their history.
+///
+/// Cloning is cheap; clones share jobs, history and limits.
+#[derive(Clone)]
+pub struct Scheduler {
+    manager: SshConnectionManager,
+    inventory: Arc<Inventory>,
+    state: Arc<Mutex<SchedulerState>>,
+    job_slots: Arc<Semaphore>,
+    history_limit: usize,
+    /// Wakes the timer loop when jobs are added or removed.
+    jobs_changed: Arc<Notify>,
+}
+
+/// Clears the running mark of a job when dropped, also when its run is
+/// cancelled.
+struct RunningGuard {
+    state: Arc<Mutex<SchedulerState>>,
+    job: String,
+}
+
+impl Drop for RunningGuard {
+    fn drop(&mut self) {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .running
+            .remove(&self.job);
+    }
+}
+
+impl Scheduler {
+    /// Build a scheduler running jobs through `manager` against `inventory`.
+    ///
+    /// Defaults: four jobs at a time, 50 runs of history per job.
+    pub fn new(manager: SshConnectionManager, inventory: Inventory) -> Self {
+        Self {
+            manager,
+            inventory: Arc::new(inventory),
+            state: Arc::new(Mutex::new(SchedulerState {
+                jobs: Vec::new(),
+                history: HashMap::new(),
+                running: HashSet::new(),
+                baselines: HashMap::new(),
+                latest: HashMap::new(),
+            })),
+            job_slots: Arc::new(Semaphore::new(4)),
+            history_limit: 50,
+            jobs_changed: Arc::new(Notify::new()),
+        }
+    }
+
+    /// Override how many jobs may run at the same time (at least one).
+    pub fn with_max_concurrent_jobs(mut self, max_jobs: usize) -> Self {
+        self.job_slots = Arc::new(Semaphore::new(max_jobs.max(1)));
+        self
+    }
+
+    /// Override how many runs are kept per job (at least one).
+    pub fn with_history_limit(mut self, history_limit: usize) -> Self {
+        self.history_limit = history_limit.max(1);
+        self
+    }
+
+    fn running_guard(&self, job: &str) -> RunningGuard {
+        RunningGuard {
+            state: self.state.clone(),
+            job: job.to_string(),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, SchedulerState> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Register a job; names must be unique.
+    pub fn add_job(&self, job: ScheduledJob) -> Result<(), ConnectError> {
+        let mut state = self.lock();
+        if state.jobs.iter().any(|existing| existing.name == job.name) {
+            return Err(ConnectError::InvalidRequest(format!(
+                "duplicate scheduled job '{}'",
+                job.name
+            )));
+        }
+        state.jobs.push(job);
+        self.jobs_changed.notify_one();
+        Ok(())
+    }
+
+    /// Unregister a job, keeping its history. Returns false when unknown.
+    pub fn remove_job(&self, name: &str) -> bool {
+        let mut state = self.lock();
+        let before = state.jobs.len();
+        state.jobs.retain(|job| job.name != name);
+        self.jobs_changed.notify_one();
+        state.jobs.len() != before
+    }
+
+    /// Names of the registered jobs.
+    pub fn job_names(&self) -> Vec<String> {
+        self.lock()
+            .jobs
+            .iter()
+            .map(|job| job.name.clone())
+            .collect()
+    }
+
+    /// Completed runs of `job`, oldest first.
+    pub fn history(&self, job: &str) -> Vec<JobRun> {
+        self.lock()
+            .history
+            .get(job)
+            .map(|runs| runs.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Approve `config` as the baseline drift jobs compare `device` with.
+    pub fn approve_baseline(&self, device: impl Into<String>, config: impl Into<String>) {
+        self.lock().baselines.insert(device.into(), config.into());
+    }
+
+    /// Approve the config of `device` fetched by the latest drift run.
+    ///
+    /// Returns false when no drift run has fetched it yet.
+    pub fn approve_latest(&self, device: &str) -> bool {
+        let mut state = self.lock();
+        match state.latest.get(device).cloned() {
+            Some(config) => {
+                state.baselines.insert(device.to_string(), config);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Approved baseline of `device`, if any.
+    pub fn baseline(&self, device: &str) -> Option<String> {
+        self.lock().baselines.get(device).cloned()
+    }
+
+    /// Next fire time of each job after `after`.
+    pub fn next_runs(&self, after: SystemTime) -> Vec<(String, Option<SystemTime>)> {
+        self.lock()
+            .jobs
+            .iter()
+            .map(|job| (job.name.clone(), job.schedule.next_after(after)))
+            .collect()
+    }
+
+    /// Run `job` immediately, outside its schedule, and return the recorded run.
+    ///
+    /// Fails when the job is unknown or a run of it is already in progress.
+    pub async fn run_now(&self, job: &str) -> Result<JobRun, ConnectError> {
+        let job = {
+            let mut state = self.lock();
+            let job = state
+                .jobs
+                .iter()
+                .find(|candidate| candidate.name == job)
+                .cloned()
+                .ok_or_else(|| {
+                    ConnectError::InvalidRequest(format!("unknown scheduled job '{job}'"))
+                })?;
+            if !state.running.insert(job.name.clone()) {
+                return Err(ConnectError::InvalidRequest(format!(
+                    "scheduled job '{}' is already running",
+                    job.name
+                )));
+            }
+            job
+        };
+        let running = self.running_guard(&job.name);
+        Ok(self.execute(job, running).await)
+    }
+
+    /// Run `job` (already marked running) and record it; `running` clears
+    /// the mark when the run ends or is cancelled.
+    async fn execute(&self, job: ScheduledJob, running: RunningGuard) -> JobRun {
+        let _slot = self.job_slots.acquire().await;
+        let started_at = SystemTime::now();
+        let started = std::time::Instant::now();
+        debug!("Scheduled job '{}' started", job.name);
+
+        let devices = match job.action.clone() {
+            JobAction::Command { command } => self
+                .manager
+                .run_on_inventory(
+                    &self.inventory,
+                    &job.selector,
+                    command,
+                    job.context.clone(),
+                    job.fanout.clone(),
+                )
+                .await
+                .map(|outcomes| {
+                    let devices =
+                        device_results(outcomes, |output| (output.success, Some(output.content)));
+                    (devices, None)
+                }),
+            JobAction::Workflow { workflow } => self
+                .manager
+                .run_workflow_on_inventory(
+                    &self.inventory,
+                    &job.selector,
+                    workflow,
+                    job.context.clone(),
+                    job.fanout.clone(),
+                )
+                .await
+                .map(|outcomes| {
+                    let devices = device_results(outcomes, |result| (result.committed, None));
+                    (devices, None)
+                }),
+            JobAction::Drift => self
+                .run_drift(&job)
+                .await
+                .map(|(devices, report)| (devices, Some(report))),
+        };
+
+        let (devices, drift, error) = match devices {
+            Ok((devices, drift)) => (devices, drift, None),
+            Err(err) => (Vec::new(), None, Some(err.to_string())),
+        };
+        let run = JobRun {
+            job: job.name.clone(),
+            started_at,
+            elapsed: started.elapsed(),
+            error,
+            devices,
+            drift,
+        };
+        if !run.is_success() {
+            warn!("Scheduled job '{}' finished with failures", job.name);
+        }
+
+        {
+            let mut state = self.lock();
+            let runs = state.history.entry(job.name).or_default();
+            runs.push_back(run.clone());
+            while runs.len() > self.history_limit {
+                runs.pop_front();
+            }
+        }
+        drop(running);
+        run
+    }
+
+    /// Fetch the config of every device selected by `job`, one template at a
+    /// time, and compare it with the device's baseline.
+    async fn run_drift(
+        &self,
+        job: &ScheduledJob,
+    ) -> Result<(Vec<JobDeviceResult>, DriftReport), ConnectError> {
+        let selector = DeviceSelector::parse(&job.selector)?;
+        let devices = self.inventory.select(&selector);
+        let library = self.manager.command_library();
+
+        let mut fetched: HashMap<String, Result<Output, String>> = HashMap::new();
+        let mut templates: Vec<String> = Vec::new();
+        for device in &devices {
+            let template = device.template.to_ascii_lowercase();
+            if !templates.contains(&template) {
+                templates.push(template);
+            }
+        }
+        for template in &templates {
+            let outcomes = match library.resolve("backup_config", template) {
+                Ok(command) => {
+                    self.manager
+                        .run_on_inventory(
+                            &self.inventory,
+                            &format!("{},template={template}", job.selector),
+                            command,
+                            job.context.clone(),
+                            job.fanout.clone(),
+                        )
+                        .await?
+                }
+                Err(err) => {
+                    for device in devices
+                        .iter()
+                        .filter(|device| device.template.eq_ignore_ascii_case(template))
+                    {
+                        let addr = self.inventory.connection_request(device)?.device_addr();
+                        fetched.insert(addr, Err(err.to_string()));
+                    }
+                    continue;
+                }
+            };
+            for outcome in outcomes {
+                fetched.insert(
+                    outcome.device_addr,
+                    outcome.result.map_err(|err| err.to_string()),
+                );
+            }
+        }
+
+        let mut results = Vec::with_capacity(devices.len());
+        let mut report = DriftReport::default();
+        for device in devices {
+            let device_addr = self.inventory.connection_request(device)?.device_addr();
+            let compared = match fetched.remove(&device_addr) {
+                Some(Ok(output)) if output.success => {
+                    let config = output.content;
+                    let baseline = {
+                        let mut state = self.lock();
+                        state.latest.insert(device.name.clone(), config.clone());
+                        state.baselines.get(&device.name).cloned()
+                    };
+                    match baseline {
+                        Some(baseline) => ConfigGrammar::for_template(&device.template)
+                            .map(|grammar| (grammar.drift_sections(&baseline, &config), config))
+                            .map_err(|err| err.to_string()),
+                        None => Err(format!("device '{}' has no approved baseline", device.name)),
+                    }
+                }
+                Some(Ok(output)) => {
+                    Err(format!("backup command failed: {}", output.content.trim()))
+                }
+                Some(Err(err)) => Err(err),
+                None => Err(format!("no config fetched for device '{}'", device.name)),
+            };
+            results.push(match compared {
+                Ok((sections, config)) => {
+                    let success = sections.is_empty();
+                    report.devices.push(DeviceDrift {
+                        device: device.name.clone(),
+                        device_addr: device_addr.clone(),
+                        sections,
+                    });
+                    JobDeviceResult {
+                        device_addr,
+                        success,
+                        output: Some(config),
+                        error: None,
+                    }
+                }
+                Err(error) => JobDeviceResult {
+                    device_addr,
+                    success: false,
+                    output: None,
+                    error: Some(error),
+                },
+            });
+        }
+        Ok((results, report))
+    }
+
+    /// Start the timer loop on the current Tokio runtime.
+    ///
+    /// A job whose previous run is still in progress skips that tick. Adding
+    /// or removing a job wakes the loop, so the next due time is recomputed
+    /// at once.
+    pub fn start(&self) -> SchedulerHandle {
+        let scheduler = self.clone();
+        let task = tokio::spawn(async move {
+            loop {
+                let now = SystemTime::now();
+                let jobs_changed = scheduler.jobs_changed.notified();
+                let Some(due_at) = scheduler
+                    .next_runs(now)
+                    .into_iter()
+                    .filter_map(|(_, next)| next)
+                    .min()
+                else {
+                    // Nothing scheduled yet; wait for a job to be added.
+                    jobs_changed.await;
+                    continue;
+                };
+                let wait = due_at.duration_since(now).unwrap_or_default();
+                tokio::select! {
+                    _ = tokio::time::sleep(wait) => scheduler.dispatch_due(due_at),
+                    _ = jobs_changed => {}
+                }
+            }
+        });
+        SchedulerHandle { task }
+    }
+
+    /// Spawn every job whose schedule fires at `due_at`.
+    fn dispatch_due(&self, due_at: SystemTime) {
+        let just_before = due_at - Duration::from_secs(1);
+        let due: Vec<ScheduledJob> = {
+            let mut state = self.lock();
+            let due: Vec<ScheduledJob> = state
+                .jobs
+                .iter()
+                .filter(|job| job.schedule.next_after(just_before) == Some(due_at))
+                .cloned()
+                .collect();
+            due.into_iter()
+                .filter(|job| {
+                    let idle = state.running.insert(job.name.clone());
+                    if !idle {
+                        warn!(
+                            "Scheduled job '{}' skipped: previous run still in progress",
+                            job.name
+                        );
+                    }
+                    idle
+                })
+                .collect()
+        };
+        for job in due {
+            let scheduler = self.clone();
+            let running = self.running_guard(&job.name);
+            tokio::spawn(async move {
+                scheduler.execute(job, running).await;
+            });
+        }
+    }
+}
+
+/// Handle to a started [`Scheduler`] timer loop.
+pub struct SchedulerHandle {
+    task: JoinHandle<()>,
+}
+
+impl SchedulerHandle {
+    /// Stop scheduling new runs; runs already in progress complete.
+    pub fn stop(self) {
+        self.task.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(secs: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn parses_fields_and_rejects_bad_expressions() {
+        let schedule = CronSchedule::parse("*/15 2 * * 1-5").expect("parse");
+        assert_eq!(schedule.minutes, 1 | 1 << 15 | 1 << 30 | 1 << 45);
+        assert_eq!(schedule.days_of_week, 0b11_1110);
+        assert_eq!(
+            CronSchedule::parse("0 0 * * 7")
+                .expect("sunday")
+                .days_of_week,
+            1
+        );
+
+        for bad in [
+            "* * * *",
+            "60 * * * *",
+            "*/0 * * * *",
+            "5-1 * * * *",
+            "x * * * *",
+        ] {
+            assert!(
+                matches!(
+                    CronSchedule::parse(bad),
+                    Err(ConnectError::InvalidRequest(_))
+                ),
+                "{bad}"
+            );
+        }
+    }
+
+    #[test]
+    fn next_after_walks_minutes_days_and_weekdays() {
+        // 2024-01-01T00:00:30Z, a Monday.
+        let monday = 1_704_067_230;
+
+        let every_15 = CronSchedule::parse("*/15 * * * *").expect("parse");
+        assert_eq!(
+            every_15.next_after(at(monday)),
+            Some(at(1_704_067_200 + 900))
+        );
+
+        let weekend = CronSchedule::parse("30 2 * * 6,0").expect("parse");
+        // Saturday 2024-01-06T02:30Z.
+        assert_eq!(
+            weekend.next_after(at(monday)),
+            Some(at(1_704_067_200 + 5 * 86_400 + 2 * 3600 + 1800))
+        );
+
+        let leap_day = CronSchedule::parse("0 0 29 2 *").expect("parse");
+        assert_eq!(leap_day.next_after(at(monday)), Some(at(1_709_164_800)));
+        assert_eq!(
+            CronSchedule::parse("0 0 30 2 *")
+                .expect("parse")
+                .next_after(at(monday)),
+            None
+        );
+    }
+
+    #[test]
+    fn restricted_day_of_month_and_week_match_either() {
+        // The 15th or any Friday; from Monday 2024-01-01 the first hit is Friday the 5th.
+        let schedule = CronSchedule::parse("0 0 15 * 5").expect("parse");
+        assert_eq!(
+            schedule.next_after(at(1_704_067_230)),
+            Some(at(1_704_067_200 + 4 * 86_400))
+        );
+    }
+
+    #[tokio::test]
+    async fn run_now_records_bounded_history() {
+        let scheduler =
+            Scheduler::new(SshConnectionManager::new(), Inventory::default()).with_history_limit(2);
+        scheduler
+            .add_job(
+                ScheduledJob::command("backup", "@daily", "tag=none", Command::show("show run"))
+                    .expect("job"),
+            )
+            .expect("add");
+        assert!(
+            scheduler
+                .add_job(
+                    ScheduledJob::command("backup", "@hourly", "all", Command::show("x"))
+                        .expect("job")
+                )
+                .is_err()
+        );
+
+        for _ in 0..3 {
+            let run = scheduler.run_now("backup").await.expect("run");
+            assert!(run.is_success());
+            assert!(run.devices.is_empty());
+        }
+        assert_eq!(scheduler.history("backup").len(), 2);
+        assert!(scheduler.run_now("missing").await.is_err());
+
+        scheduler
+            .add_job(
+                ScheduledJob::command("broken", "@daily", "color=red", Command::show("x"))
+                    .expect("job"),
+            )
+            .expect("add");
+        let run = scheduler.run_now("broken").await.expect("run");
+        assert!(!run.is_success());
+        assert!(run.error.is_some());
+    }
+
+    #[tokio::test]
+    async fn cancelled_run_now_clears_the_running_mark() {
+        let scheduler = Scheduler::new(SshConnectionManager::new(), Inventory::default())
+            .with_max_concurrent_jobs(1);
+        scheduler
+            .add_job(
+                ScheduledJob::command("backup", "@daily", "tag=none", Command::show("show run"))
+                    .expect("job"),
+            )
+            .expect("add");
+
+        let slot = scheduler.job_slots.acquire().await.expect("slot");
+        let cancelled =
+            tokio::time::timeout(Duration::from_millis(20), scheduler.run_now("backup")).await;
+        assert!(cancelled.is_err());
+        drop(slot);
+
+        assert!(scheduler.run_now("backup").await.expect("run").is_success());
+    }
+
+    #[tokio::test]
+    async fn adding_a_job_wakes_the_timer_loop() {
+        let scheduler = Scheduler::new(SshConnectionManager::new(), Inventory::default());
+        let woken = scheduler.jobs_changed.notified();
+        scheduler
+            .add_job(
+                ScheduledJob::command("backup", "@daily", "tag=none", Command::show("show run"))
+                    .expect("job"),
+            )
+            .expect("add");
+        tokio::time::timeout(Duration::from_millis(100), woken)
+            .await
+            .expect("woken");
+    }
+
+    #[tokio::test]
+    async fn drift_job_reports_devices_it_cannot_compare() {
+        let inventory = Inventory::from_yaml_str(
+            "devices:\n  - {name: web-1, host: 192.0.2.9, template: linux, credentials: lab}\n",
+        )
+        .expect("inventory")
+        .with_credentials(
+            "lab",
+            crate::session::DeviceCredentials::password("admin", "secret"),
+        );
+        let scheduler = Scheduler::new(SshConnectionManager::new(), inventory);
+        scheduler
+            .add_job(ScheduledJob::drift("drift", "@daily", "all").expect("job"))
+            .expect("add");
+
+        let run = scheduler.run_now("drift").await.expect("run");
+        assert!(run.error.is_none());
+        assert!(!run.is_success());
+        assert!(
+            run.devices[0]
+                .error
+                .as_deref()
+                .is_some_and(|e| e.contains("backup_config"))
+        );
+        assert_eq!(run.drift, Some(DriftReport::default()));
+
+        assert!(!scheduler.approve_latest("web-1"));
+        scheduler.approve_baseline("web-1", "hostname web-1\n");
+        assert_eq!(
+            scheduler.baseline("web-1").as_deref(),
+            Some("hostname web-1\n")
+        );
+    }
+}
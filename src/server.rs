@@ -0,0 +1,321 @@
+//! gRPC service exposing the connection pool as a shared daemon.
+//!
+//! Enabled with the `server` feature. The service definition lives in
+//! `proto/rneter.proto`; [`proto`] holds the generated server and client
+//! types. Run a daemon with [`serve`], or mount [`RneterService`] into an
+//! existing tonic router.
+
+use std::net::SocketAddr;
+use std::pin::Pin;
+
+use tokio::sync::{broadcast, mpsc, oneshot};
+use tokio_stream::Stream;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{Request, Response, Status};
+
+use crate::error::ConnectError;
+use crate::session::{
+    CmdJob, Command, ConnectionRequest, ExecutionContext, JobScope, Output, SessionEvent,
+    SessionRecordLevel, SessionRecorder, SshConnectionManager, TxWorkflow,
+};
+use crate::templates;
+
+/// Generated protobuf messages, server trait and client.
+pub mod proto {
+    tonic::include_proto!("rneter.v1");
+}
+
+use proto::rneter_server::{Rneter, RneterServer};
+
+/// gRPC front end for an [`SshConnectionManager`].
+#[derive(Clone)]
+pub struct RneterService {
+    manager: SshConnectionManager,
+}
+
+impl RneterService {
+    /// Serve `manager` (usually a clone of [`crate::session::MANAGER`]).
+    pub fn new(manager: SshConnectionManager) -> Self {
+        Self { manager }
+    }
+
+    /// Wrap the service for [`tonic::transport::Server::add_service`].
+    pub fn into_server(self) -> RneterServer<Self> {
+        RneterServer::new(self)
+    }
+}
+
+/// Serve `manager` over gRPC on `addr` until the process exits.
+pub async fn serve(addr: SocketAddr, manager: SshConnectionManager) -> Result<(), ConnectError> {
+    tonic::transport::Server::builder()
+        .add_service(RneterService::new(manager).into_server())
+        .serve(addr)
+        .await
+        .map_err(|e| ConnectError::InternalServerError(format!("gRPC server failed: {e}")))
+}
+
+/// Map a connection error onto the closest gRPC status code.
+fn to_status(err: ConnectError) -> Status {
+    let message = err.to_string();
+    match err.root_cause() {
+        ConnectError::InvalidRequest(_)
+        | ConnectError::TemplateNotFound(_)
+        | ConnectError::InvalidTransaction(_)
+        | ConnectError::InvalidCommandInteraction(_) => Status::invalid_argument(message),
+        ConnectError::CommandBlockedByPolicy { .. } => Status::permission_denied(message),
+        ConnectError::QuotaExceeded { .. } => Status::resource_exhausted(message),
+        ConnectError::AuthenticationFailed { .. } => Status::unauthenticated(message),
+        ConnectError::ExecTimeout(_)
+        | ConnectError::InitTimeout(_)
+        | ConnectError::DeviceTimeout(_) => Status::deadline_exceeded(message),
+        _ if err.is_retryable() => Status::unavailable(message),
+        _ => Status::internal(message),
+    }
+}
+
+/// Streamed output chunk for a recorded raw chunk event.
+fn raw_chunk(event: SessionEvent) -> Option<proto::OutputChunk> {
+    match event {
+        SessionEvent::RawChunk { data } => Some(proto::OutputChunk {
+            payload: Some(proto::output_chunk::Payload::Data(data)),
+        }),
+        _ => None,
+    }
+}
+
+fn connection_request(
+    device: Option<proto::DeviceTarget>,
+) -> Result<(ConnectionRequest, ExecutionContext), ConnectError> {
+    let device =
+        device.ok_or_else(|| ConnectError::InvalidRequest("device is required".to_string()))?;
+    let template = if device.template.is_empty() {
+        "cisco"
+    } else {
+        device.template.as_str()
+    };
+    let port = match device.port {
+        0 => 22,
+        port => u16::try_from(port)
+            .map_err(|_| ConnectError::InvalidRequest(format!("invalid port {port}")))?,
+    };
+    let request = ConnectionRequest::new(
+        device.username,
+        device.host,
+        port,
+        device.password,
+        device.enable_password,
+        templates::by_name(template)?,
+    );
+    Ok((request, ExecutionContext::new().with_sys(device.sys)))
+}
+
+fn command_from_request(request: &proto::ExecuteCommandRequest) -> Command {
+    let mut command = Command::new(request.mode.clone(), request.command.clone());
+    command.timeout = request.timeout_secs;
+    command
+}
+
+fn command_result(output: Output) -> proto::CommandResult {
+    proto::CommandResult {
+        success: output.success,
+        exit_code: output.exit_code,
+        content: output.content.clone(),
+        prompt: output.prompt.clone(),
+        device_addr: output.device_addr.clone(),
+        sys: output.sys.clone(),
+        all: output.into_all(),
+    }
+}
+
+type OutputStream = Pin<Box<dyn Stream<Item = Result<proto::OutputChunk, Status>> + Send>>;
+
+#[tonic::async_trait]
+impl Rneter for RneterService {
+    async fn execute_command(
+        &self,
+        request: Request<proto::ExecuteCommandRequest>,
+    ) -> Result<Response<proto::CommandResult>, Status> {
+        let request = request.into_inner();
+        let command = command_from_request(&request);
+        let (connection, context) = connection_request(request.device).map_err(to_status)?;
+        let output = self
+            .manager
+            .execute_command_with_context(connection, command, context)
+            .await
+            .map_err(to_status)?;
+        Ok(Response::new(command_result(output)))
+    }
+
+    async fn execute_workflow(
+        &self,
+        request: Request<proto::ExecuteWorkflowRequest>,
+    ) -> Result<Response<proto::WorkflowResult>, Status> {
+        let request = request.into_inner();
+        let workflow: TxWorkflow = serde_json::from_str(&request.workflow_json)
+            .map_err(|e| Status::invalid_argument(format!("workflow_json: {e}")))?;
+        let (connection, context) = connection_request(request.device).map_err(to_status)?;
+        let result = self
+            .manager
+            .execute_tx_workflow_with_context(connection, workflow, context)
+            .await
+            .map_err(to_status)?;
+        let result_json =
+            serde_json::to_string(&result).map_err(|e| Status::internal(e.to_string()))?;
+        Ok(Response::new(proto::WorkflowResult {
+            committed: result.committed,
+            result_json,
+        }))
+    }
+
+    type StreamOutputStream = OutputStream;
+
+    async fn stream_output(
+        &self,
+        request: Request<proto::ExecuteCommandRequest>,
+    ) -> Result<Response<Self::StreamOutputStream>, Status> {
+        let request = request.into_inner();
+        let command = command_from_request(&request);
+        let (connection, context) = connection_request(request.device).map_err(to_status)?;
+        let sys = context.sys.clone();
+        let scope = JobScope::from(&context);
+        let sender = self
+            .manager
+            .get_with_context(connection, context)
+            .await
+            .map_err(to_status)?;
+
+        // The worker drops this job recorder when the command finishes,
+        // which closes the event stream.
+        let recorder = SessionRecorder::new(SessionRecordLevel::Full);
+        let mut events = recorder.subscribe();
+        let (responder, mut result) = oneshot::channel();
+        sender
+            .send(CmdJob {
+                data: command,
+                sys,
+                recorder: Some(recorder),
+                responder,
+                idempotency_key: None,
+                scope,
+            })
+            .await
+            .map_err(|_| Status::unavailable("connection worker stopped"))?;
+
+        let (tx, rx) = mpsc::channel(64);
+        tokio::spawn(async move {
+            let outcome = loop {
+                tokio::select! {
+                    outcome = &mut result => break outcome,
+                    event = events.recv() => match event {
+                        Ok(entry) => {
+                            if let Some(chunk) = raw_chunk(entry.event)
+                                && tx.send(Ok(chunk)).await.is_err()
+                            {
+                                return;
+                            }
+                        }
+                        Err(broadcast::error::RecvError::Lagged(_)) => {}
+                        // The job recorder was dropped; only the final result is left to send.
+                        Err(broadcast::error::RecvError::Closed) => break (&mut result).await,
+                    },
+                }
+            };
+            // Chunks recorded just before the result are still buffered.
+            loop {
+                match events.try_recv() {
+                    Ok(entry) => {
+                        if let Some(chunk) = raw_chunk(entry.event)
+                            && tx.send(Ok(chunk)).await.is_err()
+                        {
+                            return;
+                        }
+                    }
+                    Err(broadcast::error::TryRecvError::Lagged(_)) => {}
+                    Err(_) => break,
+                }
+            }
+            let last = match outcome {
+                Ok(Ok(output)) => Ok(proto::OutputChunk {
+                    payload: Some(proto::output_chunk::Payload::Result(command_result(output))),
+                }),
+                Ok(Err(err)) => Err(to_status(err)),
+                Err(_) => Err(Status::unavailable("connection worker dropped the command")),
+            };
+            let _ = tx.send(last).await;
+        });
+
+        Ok(Response::new(Box::pin(ReceiverStream::new(rx))))
+    }
+
+    async fn list_connections(
+        &self,
+        _request: Request<proto::ListConnectionsRequest>,
+    ) -> Result<Response<proto::ListConnectionsResponse>, Status> {
+        let connections = self
+            .manager
+            .connections()
+            .into_iter()
+            .map(|info| proto::ConnectionSummary {
+                device_addr: info.device_addr,
+                connected: info.connected,
+                session_age_ms: u64::try_from(info.session_age.as_millis()).unwrap_or(u64::MAX),
+                bytes_sent: info.stats.bytes_sent,
+                bytes_received: info.stats.bytes_received,
+                commands_executed: info.stats.commands_executed,
+                commands_failed: info.stats.commands_failed,
+            })
+            .collect();
+        Ok(Response::new(proto::ListConnectionsResponse {
+            connections,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn list_connections_returns_empty_pool() {
+        let service = RneterService::new(SshConnectionManager::new());
+
+        let response = service
+            .list_connections(Request::new(proto::ListConnectionsRequest {}))
+            .await
+            .expect("list");
+
+        assert!(response.into_inner().connections.is_empty());
+    }
+
+    #[tokio::test]
+    async fn execute_command_rejects_missing_device_and_unknown_template() {
+        let service = RneterService::new(SshConnectionManager::new());
+
+        let missing = service
+            .execute_command(Request::new(proto::ExecuteCommandRequest {
+                device: None,
+                mode: "Enable".to_string(),
+                command: "show version".to_string(),
+                timeout_secs: None,
+            }))
+            .await
+            .expect_err("missing device");
+        assert_eq!(missing.code(), tonic::Code::InvalidArgument);
+
+        let unknown = service
+            .execute_command(Request::new(proto::ExecuteCommandRequest {
+                device: Some(proto::DeviceTarget {
+                    host: "192.0.2.1".to_string(),
+                    username: "admin".to_string(),
+                    template: "no-such-vendor".to_string(),
+                    ..Default::default()
+                }),
+                mode: "Enable".to_string(),
+                command: "show version".to_string(),
+                timeout_secs: None,
+            }))
+            .await
+            .expect_err("unknown template");
+        assert_eq!(unknown.code(), tonic::Code::InvalidArgument);
+    }
+}
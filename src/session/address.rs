@@ -0,0 +1,157 @@
+//! Device address resolution for IPv4, IPv6 and dual-stack hostnames.
+//!
+//! A request's `addr` may be a hostname, an IPv4 literal or an IPv6 literal
+//! with or without brackets. Hostnames go to the manager's [`HostResolver`]
+//! first, then to system DNS without blocking, and their addresses are
+//! ordered by the context's [`AddressFamily`]; every address is tried in that
+//! order until one accepts the SSH connection.
+
+use std::net::{IpAddr, SocketAddr};
+
+use async_ssh2_tokio::ToSocketAddrsWithHostname;
+
+use super::*;
+
+/// Which address family is tried first when a hostname has both.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AddressFamily {
+    /// Keep the resolver's order.
+    #[default]
+    Auto,
+    /// IPv4 addresses first, IPv6 as fallback.
+    PreferV4,
+    /// IPv6 addresses first, IPv4 as fallback.
+    PreferV6,
+}
+
+impl AddressFamily {
+    /// Reorder `addrs` so the preferred family comes first, keeping the
+    /// resolver's order within each family.
+    pub fn order(self, mut addrs: Vec<SocketAddr>) -> Vec<SocketAddr> {
+        match self {
+            Self::Auto => {}
+            Self::PreferV4 => addrs.sort_by_key(|addr| !addr.is_ipv4()),
+            Self::PreferV6 => addrs.sort_by_key(|addr| !addr.is_ipv6()),
+        }
+        addrs
+    }
+}
+
+/// `addr` without the brackets of an IPv6 literal.
+pub(crate) fn bare_host(addr: &str) -> &str {
+    addr.strip_prefix('[')
+        .and_then(|addr| addr.strip_suffix(']'))
+        .unwrap_or(addr)
+}
+
+/// `host:port`, bracketing IPv6 literals so the port stays unambiguous.
+pub(crate) fn host_port(addr: &str, port: u16) -> String {
+    let host = bare_host(addr);
+    if host.contains(':') {
+        format!("[{host}]:{port}")
+    } else {
+        format!("{host}:{port}")
+    }
+}
+
+/// Addresses of one device, tried in order, and the name host keys are
+/// checked against.
+#[derive(Debug, Clone)]
+pub(crate) struct ResolvedAddrs {
+    host: String,
+    addrs: Vec<SocketAddr>,
+}
+
+impl ResolvedAddrs {
+    /// Resolve `addr` through `resolver`, then system DNS, and order the
+    /// result by `family`.
+    pub(crate) async fn lookup(
+        addr: &str,
+        port: u16,
+        family: AddressFamily,
+        resolver: Option<&dyn HostResolver>,
+    ) -> Result<Self, ConnectError> {
+        let host = bare_host(addr);
+        let mut addrs = match host.parse::<IpAddr>() {
+            Ok(ip) => vec![SocketAddr::new(ip, port)],
+            Err(_) => match resolver {
+                Some(resolver) => resolver
+                    .resolve(host)
+                    .await?
+                    .into_iter()
+                    .map(|ip| SocketAddr::new(ip, port))
+                    .collect(),
+                None => Vec::new(),
+            },
+        };
+        if addrs.is_empty() {
+            addrs = tokio::net::lookup_host((host, port))
+                .await
+                .map_err(|err| ConnectError::Unreachable {
+                    addr: host.to_string(),
+                    reason: err.to_string(),
+                })?
+                .collect();
+        }
+        Ok(Self {
+            host: host.to_string(),
+            addrs: family.order(addrs),
+        })
+    }
+
+    /// The addresses in the order they are tried.
+    pub(crate) fn addrs(&self) -> &[SocketAddr] {
+        &self.addrs
+    }
+}
+
+impl ToSocketAddrsWithHostname for ResolvedAddrs {
+    fn to_socket_addrs(&self) -> std::io::Result<Vec<SocketAddr>> {
+        Ok(self.addrs.clone())
+    }
+
+    fn hostname(&self) -> String {
+        self.host.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ipv6_literals_are_bracketed_once() {
+        assert_eq!(host_port("2001:db8::1", 22), "[2001:db8::1]:22");
+        assert_eq!(host_port("[2001:db8::1]", 830), "[2001:db8::1]:830");
+        assert_eq!(host_port("192.0.2.1", 22), "192.0.2.1:22");
+        assert_eq!(host_port("router1.lab", 22), "router1.lab:22");
+        assert_eq!(bare_host("[::1]"), "::1");
+    }
+
+    #[test]
+    fn preferred_family_comes_first() {
+        let v6: SocketAddr = "[2001:db8::1]:22".parse().expect("v6");
+        let v4: SocketAddr = "192.0.2.1:22".parse().expect("v4");
+        let v4b: SocketAddr = "192.0.2.2:22".parse().expect("v4");
+        let resolved = vec![v6, v4, v4b];
+        assert_eq!(AddressFamily::Auto.order(resolved.clone()), [v6, v4, v4b]);
+        assert_eq!(
+            AddressFamily::PreferV4.order(resolved.clone()),
+            [v4, v4b, v6]
+        );
+        assert_eq!(AddressFamily::PreferV6.order(vec![v4, v6]), [v6, v4]);
+    }
+
+    #[tokio::test]
+    async fn literals_resolve_without_dns() {
+        let resolved = ResolvedAddrs::lookup("[::1]", 2222, AddressFamily::PreferV4, None)
+            .await
+            .expect("resolved");
+        assert_eq!(resolved.hostname(), "::1");
+        assert_eq!(
+            resolved.to_socket_addrs().expect("addrs"),
+            ["[::1]:2222".parse::<SocketAddr>().expect("addr")]
+        );
+    }
+}
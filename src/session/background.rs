@@ -0,0 +1,287 @@
+//! Long-running device-side operations polled to completion.
+//!
+//! Commands such as `copy` or `install activate` return a prompt long before
+//! the device is done. A [`BackgroundJob`] sends the start command, then runs
+//! a status command every poll interval until its output matches the done or
+//! failed pattern. The connection is free for other commands between polls;
+//! if it drops (for example because the operation reloads the device), the
+//! job ends with the connection error.
+
+use regex::Regex;
+use tokio::task::JoinHandle;
+
+use super::*;
+
+fn default_poll_interval_secs() -> u64 {
+    10
+}
+
+fn default_background_timeout_secs() -> u64 {
+    1800
+}
+
+/// Start command plus the status command that tracks it.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq, Eq)]
+pub struct BackgroundJob {
+    /// Command that kicks off the operation.
+    pub start: Command,
+    /// Command whose output reports the operation's state.
+    pub status: Command,
+    /// Regex on status output meaning the operation finished successfully.
+    pub done_pattern: String,
+    /// Regex on status output meaning the operation failed.
+    #[serde(default)]
+    pub failed_pattern: Option<String>,
+    /// Regex with a `progress` named group, e.g. `(?P<progress>\d+)%`,
+    /// reported with each poll.
+    #[serde(default)]
+    pub progress_pattern: Option<String>,
+    #[serde(default = "default_poll_interval_secs")]
+    pub poll_interval_secs: u64,
+    /// Overall deadline, counted from the start command.
+    #[serde(default = "default_background_timeout_secs")]
+    pub timeout_secs: u64,
+}
+
+impl BackgroundJob {
+    /// Poll `status` every 10 seconds for up to 30 minutes.
+    pub fn new(start: Command, status: Command, done_pattern: impl Into<String>) -> Self {
+        Self {
+            start,
+            status,
+            done_pattern: done_pattern.into(),
+            failed_pattern: None,
+            progress_pattern: None,
+            poll_interval_secs: default_poll_interval_secs(),
+            timeout_secs: default_background_timeout_secs(),
+        }
+    }
+
+    /// Stop with a failed result when status output matches `pattern`.
+    pub fn with_failed_pattern(mut self, pattern: impl Into<String>) -> Self {
+        self.failed_pattern = Some(pattern.into());
+        self
+    }
+
+    /// Extract the `progress` capture group from each status output.
+    pub fn with_progress_pattern(mut self, pattern: impl Into<String>) -> Self {
+        self.progress_pattern = Some(pattern.into());
+        self
+    }
+
+    /// Override the delay between status polls.
+    pub fn with_poll_interval_secs(mut self, poll_interval_secs: u64) -> Self {
+        self.poll_interval_secs = poll_interval_secs;
+        self
+    }
+
+    /// Override the overall deadline.
+    pub fn with_timeout_secs(mut self, timeout_secs: u64) -> Self {
+        self.timeout_secs = timeout_secs;
+        self
+    }
+}
+
+/// Reported after every status poll.
+#[derive(Debug, Clone)]
+pub struct BackgroundJobProgress {
+    /// Poll number, starting at 1.
+    pub poll: u32,
+    /// Time since the start command was sent.
+    pub elapsed: Duration,
+    /// `progress` group captured by [`BackgroundJob::progress_pattern`].
+    pub progress: Option<String>,
+    /// Status command output.
+    pub output: String,
+}
+
+/// Final outcome of a [`BackgroundJob`].
+#[derive(Debug, Clone)]
+pub struct BackgroundJobResult {
+    /// False when the start command failed or the failed pattern matched.
+    pub success: bool,
+    pub start: Output,
+    /// Last status output; `None` when the start command failed.
+    pub status: Option<Output>,
+    pub polls: u32,
+    pub elapsed: Duration,
+}
+
+/// Handle to a started [`BackgroundJob`].
+pub struct BackgroundJobHandle {
+    progress: mpsc::UnboundedReceiver<BackgroundJobProgress>,
+    task: JoinHandle<Result<BackgroundJobResult, ConnectError>>,
+}
+
+impl BackgroundJobHandle {
+    /// Next poll report; `None` once the job has finished.
+    pub async fn next_progress(&mut self) -> Option<BackgroundJobProgress> {
+        self.progress.recv().await
+    }
+
+    /// Wait for the job to finish.
+    pub async fn wait(self) -> Result<BackgroundJobResult, ConnectError> {
+        self.task
+            .await
+            .map_err(|e| ConnectError::InternalServerError(format!("background job: {e}")))?
+    }
+
+    /// Stop polling. The device-side operation keeps running.
+    pub fn abort(self) {
+        self.task.abort();
+    }
+}
+
+/// Compiled patterns of a [`BackgroundJob`].
+struct StatusMatcher {
+    done: Regex,
+    failed: Option<Regex>,
+    progress: Option<Regex>,
+}
+
+impl StatusMatcher {
+    fn new(job: &BackgroundJob) -> Result<Self, ConnectError> {
+        let compile = |pattern: &str| {
+            Regex::new(pattern).map_err(|err| {
+                ConnectError::InvalidRequest(format!(
+                    "invalid background job pattern '{pattern}': {err}"
+                ))
+            })
+        };
+        Ok(Self {
+            done: compile(&job.done_pattern)?,
+            failed: job.failed_pattern.as_deref().map(compile).transpose()?,
+            progress: job.progress_pattern.as_deref().map(compile).transpose()?,
+        })
+    }
+
+    /// `Some(success)` once the output shows the operation has finished.
+    fn finished(&self, output: &str) -> Option<bool> {
+        if self.failed.as_ref().is_some_and(|re| re.is_match(output)) {
+            return Some(false);
+        }
+        self.done.is_match(output).then_some(true)
+    }
+
+    fn progress(&self, output: &str) -> Option<String> {
+        self.progress
+            .as_ref()?
+            .captures_iter(output)
+            .filter_map(|caps| caps.name("progress"))
+            .last()
+            .map(|m| m.as_str().to_string())
+    }
+}
+
+impl SshConnectionManager {
+    /// Start a long-running operation and poll it in the background.
+    ///
+    /// Returns as soon as the job's patterns are validated; the start command,
+    /// the polls and any error are reported through the handle.
+    pub fn start_background_job(
+        &self,
+        request: ConnectionRequest,
+        job: BackgroundJob,
+        context: ExecutionContext,
+    ) -> Result<BackgroundJobHandle, ConnectError> {
+        let matcher = StatusMatcher::new(&job)?;
+        let manager = self.clone();
+        let (progress_tx, progress) = mpsc::unbounded_channel();
+        let task = tokio::spawn(async move {
+            let started = Instant::now();
+            let deadline = Duration::from_secs(job.timeout_secs);
+            let sys = context.sys.clone();
+            let scope = JobScope::from(&context);
+            let sender = manager.get_with_context(request, context).await?;
+            let start = run_job(&sender, job.start, sys.clone(), &scope).await?;
+            if !start.success {
+                return Ok(BackgroundJobResult {
+                    success: false,
+                    start,
+                    status: None,
+                    polls: 0,
+                    elapsed: started.elapsed(),
+                });
+            }
+
+            let mut polls = 0;
+            loop {
+                let wait = Duration::from_secs(job.poll_interval_secs);
+                if started.elapsed() + wait > deadline {
+                    return Err(ConnectError::DeviceTimeout(deadline));
+                }
+                tokio::time::sleep(wait).await;
+
+                let status = run_job(&sender, job.status.clone(), sys.clone(), &scope).await?;
+                polls += 1;
+                let _ = progress_tx.send(BackgroundJobProgress {
+                    poll: polls,
+                    elapsed: started.elapsed(),
+                    progress: matcher.progress(&status.content),
+                    output: status.content.clone(),
+                });
+                if let Some(success) = matcher.finished(&status.content) {
+                    return Ok(BackgroundJobResult {
+                        success,
+                        start,
+                        status: Some(status),
+                        polls,
+                        elapsed: started.elapsed(),
+                    });
+                }
+            }
+        });
+        Ok(BackgroundJobHandle { progress, task })
+    }
+}
+
+/// Queue `command` on the connection worker and wait for its output.
+async fn run_job(
+    sender: &mpsc::Sender<CmdJob>,
+    command: Command,
+    sys: Option<String>,
+    scope: &JobScope,
+) -> Result<Output, ConnectError> {
+    let (responder, result) = oneshot::channel();
+    sender
+        .send(CmdJob {
+            data: command,
+            sys,
+            recorder: None,
+            responder,
+            idempotency_key: None,
+            scope: scope.clone(),
+        })
+        .await
+        .map_err(|_| ConnectError::ConnectClosedError)?;
+    result.await.map_err(|_| ConnectError::ConnectClosedError)?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn status_matcher_reports_progress_and_completion() {
+        let job = BackgroundJob::new(
+            Command::show("install activate"),
+            Command::show("show install summary"),
+            r"(?m)^Activation complete",
+        )
+        .with_failed_pattern(r"(?i)activation failed")
+        .with_progress_pattern(r"(?P<progress>\d+)%");
+        let matcher = StatusMatcher::new(&job).expect("patterns");
+
+        let running = "Activating...\n 10% done\n 45% done";
+        assert_eq!(matcher.finished(running), None);
+        assert_eq!(matcher.progress(running).as_deref(), Some("45"));
+        assert_eq!(matcher.finished("Activation complete"), Some(true));
+        assert_eq!(matcher.finished("ERROR: Activation FAILED"), Some(false));
+
+        let invalid = job.with_progress_pattern("(");
+        assert!(matches!(
+            StatusMatcher::new(&invalid),
+            Err(ConnectError::InvalidRequest(_))
+        ));
+    }
+}
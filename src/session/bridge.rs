@@ -0,0 +1,252 @@
+//! Interactive takeover of an automated shell session.
+//!
+//! [`SharedSshClient::bridge`] connects the remote shell to any async byte
+//! duplex, such as a WebSocket adapter in a web terminal. Keystrokes are
+//! forwarded verbatim, so they bypass [`CommandPolicy`] checks. Because they
+//! could carry config, [`SshConnectionManager::bridge_with_context`] refuses
+//! to bridge during a change freeze unless the context presents its override
+//! token. Shell output is still fed through the device state machine, so
+//! automation resumes in the right mode once the interactive user disconnects.
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use super::*;
+
+/// What happened while a shell was bridged to an interactive stream.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BridgeSummary {
+    /// Bytes typed by the interactive user and sent to the shell.
+    pub bytes_from_user: u64,
+    /// Bytes of shell output forwarded to the user.
+    pub bytes_to_user: u64,
+    /// Last prompt recognized by the state machine, if any.
+    pub prompt: Option<String>,
+    /// State machine state when the bridge ended.
+    pub state: String,
+}
+
+impl SharedSshClient {
+    /// Hand the shell to an interactive byte stream until it reaches EOF.
+    ///
+    /// The current prompt is written to the stream first so the user sees
+    /// where the session stands.
+    pub async fn bridge<S>(&mut self, stream: S) -> Result<BridgeSummary, ConnectError>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        self.resync_residual_output();
+        let recorder = self.status.recorder();
+        let summary = bridge_stream(
+            &self.sender,
+            &mut self.recv,
+            &mut self.handler,
+            recorder.as_ref(),
+            &self.prompt,
+            stream,
+        )
+        .await?;
+        if let Some(prompt) = summary.prompt.as_ref() {
+            self.prompt = prompt.clone();
+        }
+        Ok(summary)
+    }
+}
+
+impl SshConnectionManager {
+    /// Bridge the pooled shell for `request` to an interactive stream.
+    ///
+    /// The connection is locked for the duration, so automated commands queue
+    /// behind the interactive user and resume afterwards. Fails with
+    /// [`ConnectError::ChangeFrozen`] while a change freeze is active and
+    /// `context` lacks its override token.
+    pub async fn bridge_with_context<S>(
+        &self,
+        request: ConnectionRequest,
+        context: ExecutionContext,
+        stream: S,
+    ) -> Result<BridgeSummary, ConnectError>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let device_addr = request.device_addr();
+        if let Some(freeze) = self.change_freeze()
+            && !freeze.is_overridden_by(context.freeze_override.as_deref())
+        {
+            return Err(freeze.rejection(format!("interactive bridge to {device_addr}")));
+        }
+        let scope = JobScope::from(&context);
+        self.get_with_context(request, context).await?;
+        let client = self
+            .cache
+            .get(&device_addr)
+            .await
+            .map(|pooled| pooled.client)
+            .ok_or_else(|| {
+                ConnectError::InternalServerError("connection cache miss".to_string())
+            })?;
+        let mut client_guard = client.write().await;
+        client_guard.set_job_scope(scope);
+        client_guard.bridge(stream).await
+    }
+}
+
+/// Pump bytes between the shell channels and `stream`, observing output.
+async fn bridge_stream<S>(
+    sender: &Sender<String>,
+    recv: &mut Receiver<Bytes>,
+    handler: &mut DeviceHandler,
+    recorder: Option<&SessionRecorder>,
+    prompt: &str,
+    mut stream: S,
+) -> Result<BridgeSummary, ConnectError>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let mut summary = BridgeSummary::default();
+    let mut lines = lines::LineSplitter::for_handler(handler);
+    let mut input = vec![0u8; 4096];
+
+    if !prompt.is_empty() {
+        stream
+            .write_all(prompt.as_bytes())
+            .await
+            .map_err(bridge_io_error)?;
+        stream.flush().await.map_err(bridge_io_error)?;
+    }
+
+    loop {
+        tokio::select! {
+            read = stream.read(&mut input) => {
+                let n = read.map_err(bridge_io_error)?;
+                if n == 0 {
+                    break;
+                }
+                summary.bytes_from_user += n as u64;
+                sender
+                    .send(String::from_utf8_lossy(&input[..n]).into_owned())
+                    .await
+                    .map_err(|_| ConnectError::ChannelDisconnectError)?;
+            }
+            output = recv.recv() => {
+                let Some(output) = output else {
+                    return Err(ConnectError::ChannelDisconnectError);
+                };
+                stream.write_all(&output).await.map_err(bridge_io_error)?;
+                stream.flush().await.map_err(bridge_io_error)?;
+                summary.bytes_to_user += output.len() as u64;
+                if let Some(recorder) = recorder {
+                    let _ = recorder.record_raw_chunk(lines::decode_line(&output).into_owned());
+                }
+
+                lines.push(&output);
+                while let Some(line) = lines.next_line() {
+                    let line = lines::decode_line(&line);
+                    let trimmed = IGNORE_START_LINE.replace(&line, "");
+                    handler.read(trimmed.trim_end());
+                }
+                let pending = lines.pending();
+                if !pending.is_empty() && handler.read_prompt(&pending) {
+                    handler.read(&pending);
+                    summary.prompt = Some(handler.current_prompt().unwrap_or(&pending).to_string());
+                }
+            }
+        }
+    }
+
+    summary.state = handler.current_state().to_string();
+    Ok(summary)
+}
+
+fn bridge_io_error(err: std::io::Error) -> ConnectError {
+    ConnectError::InternalServerError(format!("interactive bridge I/O failed: {err}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn bridge_forwards_keystrokes_and_tracks_mode_changes() {
+        let (to_shell, mut shell_input) = mpsc::channel::<String>(8);
+        let (shell_output, mut recv) = mpsc::channel::<Bytes>(8);
+        let mut handler = crate::templates::cisco().expect("template");
+        handler.read("router#");
+        let before = handler.current_state().to_string();
+        let (user, bridged) = tokio::io::duplex(1024);
+
+        let shell = tokio::spawn(async move {
+            let typed = shell_input.recv().await.expect("keystrokes");
+            shell_output
+                .send(Bytes::from(format!("{typed}\r\nrouter(config)#")))
+                .await
+                .expect("output");
+            // Keep the shell open until the bridge lets go of it.
+            while shell_input.recv().await.is_some() {}
+        });
+        let client = tokio::spawn(async move {
+            let (mut reader, mut writer) = tokio::io::split(user);
+            let mut seen = Vec::new();
+            writer.write_all(b"conf t").await.expect("type");
+            let mut buf = [0u8; 256];
+            while !String::from_utf8_lossy(&seen).contains("(config)#") {
+                let n = reader.read(&mut buf).await.expect("read");
+                seen.extend_from_slice(&buf[..n]);
+            }
+            drop(writer);
+            drop(reader);
+            String::from_utf8(seen).expect("utf-8")
+        });
+
+        let summary = bridge_stream(&to_shell, &mut recv, &mut handler, None, "router#", bridged)
+            .await
+            .expect("bridge");
+        drop(to_shell);
+        shell.await.expect("shell");
+        let seen = client.await.expect("client");
+
+        assert!(seen.starts_with("router#conf t"));
+        assert_eq!(summary.bytes_from_user, 6);
+        assert_eq!(summary.prompt.as_deref(), Some("router(config)#"));
+        assert_ne!(summary.state, before);
+    }
+
+    #[tokio::test]
+    async fn bridge_is_refused_during_a_change_freeze_without_the_override() {
+        let manager = SshConnectionManager::new();
+        manager.set_connect_timeout(Duration::from_millis(200));
+        manager.set_change_freeze(Some(
+            ChangeFreeze::new("year-end freeze").with_override_token("CHG-1"),
+        ));
+        let request = || {
+            ConnectionRequest::new(
+                "admin".to_string(),
+                "127.0.0.1".to_string(),
+                1,
+                "secret".to_string(),
+                None,
+                crate::templates::cisco().expect("cisco"),
+            )
+        };
+
+        let (_user, bridged) = tokio::io::duplex(64);
+        let result = manager
+            .bridge_with_context(request(), ExecutionContext::new(), bridged)
+            .await;
+        assert!(matches!(
+            result,
+            Err(ConnectError::ChangeFrozen { target, .. })
+                if target == "interactive bridge to admin@127.0.0.1:1"
+        ));
+
+        // With the override the bridge proceeds to connect.
+        let (_user, bridged) = tokio::io::duplex(64);
+        let result = manager
+            .bridge_with_context(
+                request(),
+                ExecutionContext::new().with_freeze_override("CHG-1"),
+                bridged,
+            )
+            .await;
+        assert!(!matches!(result, Err(ConnectError::ChangeFrozen { .. })));
+    }
+}
@@ -0,0 +1,518 @@
+//! Bulk configuration push.
+//!
+//! Sending thousands of ACL lines one [`SharedSshClient::write_with_timeout`]
+//! at a time costs a full round trip per line. A bulk push writes a batch of
+//! lines at once and stays in sync by counting prompts: after the first line,
+//! every line's echo arrives right behind the prompt that closed the previous
+//! line, so output is split per line and errors are attributed to the line
+//! that caused them.
+//!
+//! Lines must not prompt for input; use a [`CommandFlow`] for those.
+
+use super::*;
+
+fn default_batch_size() -> usize {
+    50
+}
+
+fn default_stop_on_error() -> bool {
+    true
+}
+
+/// What a bulk push does with comment lines such as `!` or `#`.
+///
+/// Blank lines are always dropped.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum CommentHandling {
+    /// Drop them.
+    #[default]
+    Strip,
+    /// Drop them and record each as a [`SessionEvent::Annotation`].
+    Record,
+    /// Send them to the device like any other line.
+    Send,
+}
+
+/// How [`SshConnectionManager::push_config_with_context`] batches lines.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq, Eq)]
+pub struct BulkPushOptions {
+    /// Lines written per batch.
+    #[serde(default = "default_batch_size")]
+    pub batch_size: usize,
+    /// Limit for one batch; the connection's command timeout when unset.
+    #[serde(default)]
+    pub batch_timeout_secs: Option<u64>,
+    /// Send no further batches once a line failed.
+    #[serde(default = "default_stop_on_error")]
+    pub stop_on_error: bool,
+    /// Comment lines, recognized by the template's
+    /// [`comment_prefixes`](crate::device::DeviceHandlerConfig::comment_prefixes).
+    #[serde(default)]
+    pub comments: CommentHandling,
+}
+
+impl Default for BulkPushOptions {
+    fn default() -> Self {
+        Self {
+            batch_size: default_batch_size(),
+            batch_timeout_secs: None,
+            stop_on_error: default_stop_on_error(),
+            comments: CommentHandling::default(),
+        }
+    }
+}
+
+impl BulkPushOptions {
+    /// Batches of 50 lines, stopping after the first failed batch.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size;
+        self
+    }
+
+    pub fn with_batch_timeout_secs(mut self, batch_timeout_secs: u64) -> Self {
+        self.batch_timeout_secs = Some(batch_timeout_secs);
+        self
+    }
+
+    pub fn with_stop_on_error(mut self, stop_on_error: bool) -> Self {
+        self.stop_on_error = stop_on_error;
+        self
+    }
+
+    pub fn with_comments(mut self, comments: CommentHandling) -> Self {
+        self.comments = comments;
+        self
+    }
+}
+
+/// Result of one pushed line.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq, Eq)]
+pub struct BulkPushLine {
+    pub line: String,
+    pub success: bool,
+    /// Device output for this line, without echo and prompt.
+    pub output: String,
+}
+
+/// Result of [`SshConnectionManager::push_config_with_context`].
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq, Eq)]
+pub struct BulkPushOutput {
+    /// Every line was sent and none failed.
+    pub success: bool,
+    /// Results of the lines sent, in order.
+    pub lines: Vec<BulkPushLine>,
+    pub batches: usize,
+    pub elapsed_ms: u64,
+}
+
+impl BulkPushOutput {
+    /// Lines the device rejected.
+    pub fn failures(&self) -> impl Iterator<Item = &BulkPushLine> {
+        self.lines.iter().filter(|line| !line.success)
+    }
+}
+
+/// Splits the output of one batch per line by counting prompts.
+struct BatchTracker<'a> {
+    lines: &'a [String],
+    current: usize,
+    outputs: Vec<String>,
+    failed: Vec<bool>,
+}
+
+impl<'a> BatchTracker<'a> {
+    fn new(lines: &'a [String]) -> Self {
+        Self {
+            lines,
+            current: 0,
+            outputs: vec![String::new(); lines.len()],
+            failed: vec![false; lines.len()],
+        }
+    }
+
+    /// Lines whose closing prompt has been seen.
+    fn acknowledged(&self) -> usize {
+        self.current
+    }
+
+    /// Feed one complete output line.
+    fn read_line(&mut self, handler: &mut DeviceHandler, line: &str) {
+        if handler.is_syslog_line(line) {
+            return;
+        }
+        if let Some(next) = self.lines.get(self.current + 1)
+            && let Some(prefix) = line.strip_suffix(next.trim())
+            && !prefix.is_empty()
+            && handler.read_prompt(prefix)
+        {
+            // Prompt of the previous line followed by the echo of the next.
+            handler.read(prefix);
+            self.current += 1;
+            return;
+        }
+        handler.read(line);
+        if handler.error() {
+            self.failed[self.current] = true;
+        }
+        let output = &mut self.outputs[self.current];
+        output.push_str(line);
+        output.push('\n');
+    }
+
+    /// Feed the unterminated tail; true once the last line's prompt arrived.
+    fn read_pending(&mut self, handler: &mut DeviceHandler, pending: &str) -> bool {
+        if self.current + 1 == self.lines.len() && handler.read_prompt(pending) {
+            handler.read(pending);
+            self.current += 1;
+            return true;
+        }
+        false
+    }
+
+    fn finish(self) -> Vec<BulkPushLine> {
+        self.lines
+            .iter()
+            .zip(self.outputs)
+            .zip(self.failed)
+            .enumerate()
+            .map(|(index, ((line, output), failed))| {
+                let output = if index == 0 {
+                    echo::strip_echo(&output, line)
+                } else {
+                    &output
+                };
+                BulkPushLine {
+                    line: line.clone(),
+                    success: !failed,
+                    output: output.trim_end().to_string(),
+                }
+            })
+            .collect()
+    }
+}
+
+impl SharedSshClient {
+    /// Push `lines` in `mode` batch by batch.
+    ///
+    /// Blank lines and, unless `options` sends them, comment lines are left
+    /// out first. Every remaining line is checked against the command policy
+    /// before anything is sent. A batch that does not finish within the
+    /// timeout fails the push with [`ConnectError::ExecTimeout`].
+    pub async fn push_lines(
+        &mut self,
+        lines: &[String],
+        mode: &str,
+        sys: Option<&String>,
+        options: &BulkPushOptions,
+    ) -> Result<BulkPushOutput, ConnectError> {
+        let lines = &match options.comments {
+            CommentHandling::Send => lines
+                .iter()
+                .filter(|line| !line.trim().is_empty())
+                .cloned()
+                .collect(),
+            CommentHandling::Strip | CommentHandling::Record => {
+                let (commands, comments) = self.handler.strip_comments(lines);
+                if options.comments == CommentHandling::Record
+                    && let Some(recorder) = self.status.recorder()
+                {
+                    for text in comments {
+                        let _ = recorder.record_event(SessionEvent::Annotation { text });
+                    }
+                }
+                commands
+            }
+        };
+        for line in lines {
+            self.enforce_command_policy(line, mode)?;
+        }
+        let timeout = options
+            .batch_timeout_secs
+            .map(Duration::from_secs)
+            .unwrap_or_else(|| self.status.command_timeout());
+        self.enter_mode(mode, sys, timeout).await?;
+
+        let started = Instant::now();
+        let mut results = Vec::with_capacity(lines.len());
+        let mut batches = 0;
+        for batch in lines.chunks(options.batch_size.max(1)) {
+            batches += 1;
+            let pushed = self.push_batch(batch, mode, timeout).await?;
+            let failed = pushed.iter().any(|line| !line.success);
+            results.extend(pushed);
+            if failed && options.stop_on_error {
+                break;
+            }
+        }
+        Ok(BulkPushOutput {
+            success: results.len() == lines.len() && results.iter().all(|line| line.success),
+            lines: results,
+            batches,
+            elapsed_ms: u64::try_from(started.elapsed().as_millis()).unwrap_or(u64::MAX),
+        })
+    }
+
+    /// Run the transitions into `mode`, failing when one does not land.
+    async fn enter_mode(
+        &mut self,
+        mode: &str,
+        sys: Option<&String>,
+        timeout: Duration,
+    ) -> Result<(), ConnectError> {
+        let mode = mode.to_ascii_lowercase();
+        for (t_cmd, target_state) in self.handler.trans_state_write(&mode, sys)? {
+            debug!("Trans state command: {}", t_cmd);
+            let from = self.handler.current_state().to_string();
+            let output = self
+                .write_with_timeout_internal(
+                    &t_cmd,
+                    timeout,
+                    false,
+                    &CommandInteraction::default(),
+                    None,
+                    false,
+                    false,
+                )
+                .await?;
+            let success = self.handler.transition_succeeded(
+                &from,
+                &target_state,
+                &output.content,
+                output.success,
+            );
+            if !success || self.handler.current_state() != target_state {
+                return Err(ConnectError::UnreachableState(mode));
+            }
+        }
+        Ok(())
+    }
+
+    async fn push_batch(
+        &mut self,
+        batch: &[String],
+        mode: &str,
+        timeout: Duration,
+    ) -> Result<Vec<BulkPushLine>, ConnectError> {
+        self.resync_residual_output();
+        let recorder = self.status.recorder();
+        let prompt_before = self.prompt.clone();
+        let fsm_prompt_before = self.handler.current_state().to_string();
+        let started = Instant::now();
+        let payload: String = batch.iter().map(|line| format!("{line}\n")).collect();
+        self.sender.send(payload).await?;
+
+        let mut tracker = BatchTracker::new(batch);
+        let mut lines = lines::LineSplitter::for_handler(&self.handler);
+        let handler = &mut self.handler;
+        let recv = &mut self.recv;
+        let result = tokio::time::timeout(timeout, async {
+            loop {
+                let Some(data) = recv.recv().await else {
+                    return Err(ConnectError::ChannelDisconnectError);
+                };
+                if let Some(recorder) = recorder.as_ref() {
+                    let _ = recorder.record_raw_chunk(lines::decode_line(&data).into_owned());
+                }
+                lines.push(&data);
+                while let Some(line) = lines.next_line() {
+                    let line = lines::decode_line(&line);
+                    let line = IGNORE_START_LINE.replace(&line, "");
+                    tracker.read_line(handler, line.trim_end());
+                }
+                if !lines.is_empty() && tracker.read_pending(handler, &lines.pending()) {
+                    return Ok(());
+                }
+            }
+        })
+        .await;
+        match result {
+            Ok(Ok(())) => {}
+            Ok(Err(err)) => return Err(err),
+            Err(_) => {
+                return Err(ConnectError::ExecTimeout(format!(
+                    "bulk push batch: {} of {} lines acknowledged",
+                    tracker.acknowledged(),
+                    batch.len()
+                )));
+            }
+        }
+        if let Some(prompt) = self.handler.current_prompt() {
+            self.prompt = prompt.to_string();
+        }
+        self.status
+            .publish_state(self.handler.current_state(), &self.prompt);
+        self.status.record_sys(self.handler.current_sys());
+
+        let pushed = tracker.finish();
+        let per_line = started.elapsed() / u32::try_from(batch.len()).unwrap_or(u32::MAX);
+        for line in &pushed {
+            self.status.stats().record_command(per_line, line.success);
+            if let Some(recorder) = recorder.as_ref() {
+                let _ = recorder.record_event(SessionEvent::CommandOutput {
+                    command: line.line.clone(),
+                    mode: mode.to_string(),
+                    prompt_before: Some(prompt_before.clone()),
+                    prompt_after: Some(self.prompt.clone()),
+                    fsm_prompt_before: Some(fsm_prompt_before.clone()),
+                    fsm_prompt_after: Some(self.handler.current_state().to_string()),
+                    success: line.success,
+                    exit_code: None,
+                    duration_ms: Some(u64::try_from(per_line.as_millis()).unwrap_or(u64::MAX)),
+                    content: line.output.clone(),
+                    all: line.output.clone(),
+                });
+            }
+        }
+        Ok(pushed)
+    }
+}
+
+impl SshConnectionManager {
+    /// Push configuration `lines` in `mode` (e.g. `Config`) in batches.
+    ///
+    /// Much faster than one command per line for large policies; see
+    /// [`BulkPushOptions`]. Rejected lines are reported per line in
+    /// [`BulkPushOutput::lines`] rather than as an error.
+    pub async fn push_config_with_context(
+        &self,
+        request: ConnectionRequest,
+        mode: &str,
+        lines: Vec<String>,
+        options: BulkPushOptions,
+        context: ExecutionContext,
+    ) -> Result<BulkPushOutput, ConnectError> {
+        let device_addr = request.device_addr();
+        let sys = context.sys.clone();
+        let started = Instant::now();
+        let result = async {
+            self.get_with_request_and_recording(request, &context, None)
+                .await?;
+            let client = self
+                .cache
+                .get(&device_addr)
+                .await
+                .map(|pooled| pooled.client)
+                .ok_or_else(|| {
+                    ConnectError::InternalServerError("connection cache miss".to_string())
+                })?;
+            let mut client_guard = client.write().await;
+            client_guard.set_job_scope(JobScope::from(&context));
+            client_guard
+                .push_lines(&lines, mode, sys.as_ref(), &options)
+                .await
+        }
+        .await;
+        result.map_err(|err| {
+            let err = err.with_context(
+                ErrorContext::new(device_addr.clone(), started.elapsed())
+                    .with_mode(Some(mode.to_string()))
+                    .with_command(Some(format!("bulk push of {} lines", lines.len()))),
+            );
+            self.emit_command_failed(&device_addr, &err);
+            err
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::templates;
+
+    fn feed(tracker: &mut BatchTracker<'_>, handler: &mut DeviceHandler, output: &str) -> bool {
+        let (complete, pending) = output.rsplit_once('\n').unwrap_or(("", output));
+        for line in complete.lines() {
+            tracker.read_line(handler, line.trim_end());
+        }
+        tracker.read_pending(handler, pending)
+    }
+
+    #[test]
+    fn prompts_split_batch_output_per_line() {
+        let mut handler = templates::cisco().expect("cisco");
+        handler.read("R1(config)#");
+        let batch = vec![
+            "access-list 100 permit ip any host 10.0.0.1".to_string(),
+            "access-list 100 permit tcp any any eq bogus".to_string(),
+            "access-list 100 deny ip any any".to_string(),
+        ];
+        let mut tracker = BatchTracker::new(&batch);
+
+        // The last prompt has not arrived yet.
+        let partial = "access-list 100 permit ip any host 10.0.0.1\n\
+             R1(config)#access-list 100 permit tcp any any eq bogus\n\
+             \x20                                          ^\n\
+             % Invalid input detected at '^' marker.\n\
+             \n\
+             R1(config)#";
+        assert!(!feed(&mut tracker, &mut handler, partial));
+        assert_eq!(tracker.acknowledged(), 1);
+
+        assert!(feed(
+            &mut tracker,
+            &mut handler,
+            "R1(config)#access-list 100 deny ip any any\nR1(config)#"
+        ));
+        let pushed = tracker.finish();
+        assert!(pushed[0].success);
+        assert_eq!(pushed[0].output, "");
+        assert!(!pushed[1].success);
+        assert!(pushed[1].output.contains("Invalid input"));
+        assert!(pushed[2].success);
+        assert_eq!(handler.current_state(), "config");
+    }
+
+    #[test]
+    fn syslog_lines_are_not_attributed_to_pushed_lines() {
+        let mut handler = templates::cisco().expect("cisco");
+        handler.read("R1(config)#");
+        let batch = vec!["interface Gi0/1".to_string(), "shutdown".to_string()];
+        let mut tracker = BatchTracker::new(&batch);
+        assert!(feed(
+            &mut tracker,
+            &mut handler,
+            "interface Gi0/1\n\
+             R1(config-if)#shutdown\n\
+             *Oct 16 10:02:11.123: %LINK-5-CHANGED: Interface Gi0/1, changed state to administratively down\n\
+             %LINEPROTO-5-UPDOWN: Line protocol on Interface Gi0/1, changed state to down\n\
+             R1(config-if)#"
+        ));
+        let pushed = tracker.finish();
+        assert!(pushed.iter().all(|line| line.success));
+        assert_eq!(pushed[1].output, "");
+        assert!(!handler.is_syslog_line("% Invalid input detected at '^' marker."));
+    }
+
+    #[test]
+    fn options_default_to_batches_of_fifty() {
+        let options: BulkPushOptions = serde_json::from_str("{}").expect("options");
+        assert_eq!(options, BulkPushOptions::new());
+        assert_eq!(options.batch_size, 50);
+        assert!(options.stop_on_error);
+        assert_eq!(options.comments, CommentHandling::Strip);
+    }
+
+    #[test]
+    fn template_comment_lines_are_split_from_commands() {
+        let handler = templates::cisco().expect("cisco");
+        let lines = [
+            "! uplinks",
+            "",
+            "interface Gi0/1",
+            " description uplink",
+            "  !",
+        ];
+        let (commands, comments) = handler.strip_comments(&lines);
+        assert_eq!(commands, ["interface Gi0/1", " description uplink"]);
+        assert_eq!(comments, ["! uplinks", "!"]);
+
+        let huawei = templates::huawei().expect("huawei");
+        assert!(huawei.is_comment_line("#"));
+        assert!(!huawei.is_comment_line("! not a vrp comment"));
+    }
+}
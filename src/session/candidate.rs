@@ -0,0 +1,194 @@
+//! Native candidate-config sessions.
+//!
+//! On Junos, IOS-XR and PAN-OS, config commands edit a candidate that only
+//! takes effect on commit. [`SshConnectionManager::candidate_session`] holds
+//! a pooled session for one change, loads commands into the candidate and
+//! then validates and commits or aborts it, so a failed change never
+//! reaches the running config.
+
+use tokio::sync::OwnedRwLockWriteGuard;
+
+use super::*;
+use crate::templates::CandidateConfig;
+
+/// One change staged in a device's candidate config.
+///
+/// Dropping the session before [`commit`](Self::commit) or
+/// [`abort`](Self::abort) aborts the candidate in the background.
+pub struct CandidateConfigSession {
+    device_addr: String,
+    candidate: CandidateConfig,
+    client: Option<OwnedRwLockWriteGuard<SharedSshClient>>,
+    sys: Option<String>,
+}
+
+impl CandidateConfigSession {
+    /// Cache key of the session.
+    pub fn device_addr(&self) -> &str {
+        &self.device_addr
+    }
+
+    pub fn candidate(&self) -> &CandidateConfig {
+        &self.candidate
+    }
+
+    /// Load `commands` into the candidate, stopping at the first one the
+    /// device rejects.
+    pub async fn load(&mut self, commands: &[String]) -> Result<Vec<Output>, ConnectError> {
+        let mut outputs = Vec::with_capacity(commands.len());
+        for command in commands {
+            let command = Command::new(self.candidate.mode.clone(), command.as_str());
+            let output = self.run(&command).await?;
+            if !output.success {
+                return Err(ConnectError::CandidateFailed {
+                    command: command.command,
+                    reason: output.content.trim().to_string(),
+                });
+            }
+            outputs.push(output);
+        }
+        Ok(outputs)
+    }
+
+    /// Check the candidate without activating it; `None` when the vendor
+    /// only validates on commit.
+    pub async fn validate(&mut self) -> Result<Option<Output>, ConnectError> {
+        match self.candidate.validate_command() {
+            Some(command) => self.run_checked(&command).await.map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Activate the candidate. When the commit fails the session is dropped,
+    /// which aborts the candidate.
+    pub async fn commit(mut self) -> Result<Output, ConnectError> {
+        let output = self.run_checked(&self.candidate.commit_command()).await?;
+        self.client = None;
+        Ok(output)
+    }
+
+    /// Discard everything loaded so far.
+    pub async fn abort(mut self) -> Result<Output, ConnectError> {
+        let output = self.run(&self.candidate.abort_command()).await;
+        self.client = None;
+        output
+    }
+
+    async fn run(&mut self, command: &Command) -> Result<Output, ConnectError> {
+        let client = self.client.as_mut().ok_or_else(|| {
+            ConnectError::InvalidRequest(format!(
+                "candidate session on {} is finished",
+                self.device_addr
+            ))
+        })?;
+        let timeout = command
+            .timeout
+            .map(Duration::from_secs)
+            .unwrap_or_else(|| client.status.command_timeout());
+        client
+            .write_with_mode_and_timeout_using_command(command, self.sys.as_ref(), timeout)
+            .await
+    }
+
+    async fn run_checked(&mut self, command: &Command) -> Result<Output, ConnectError> {
+        let output = self.run(command).await?;
+        match self.candidate.check_output(output.success, &output.content) {
+            Some(reason) => Err(ConnectError::CandidateFailed {
+                command: command.command.clone(),
+                reason,
+            }),
+            None => Ok(output),
+        }
+    }
+}
+
+impl Drop for CandidateConfigSession {
+    fn drop(&mut self) {
+        let Some(mut client) = self.client.take() else {
+            return;
+        };
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+        let abort = self.candidate.abort_command();
+        let sys = self.sys.take();
+        let device_addr = self.device_addr.clone();
+        runtime.spawn(async move {
+            let timeout = client.status.command_timeout();
+            if let Err(err) = client
+                .write_with_mode_and_timeout_using_command(&abort, sys.as_ref(), timeout)
+                .await
+            {
+                warn!("Aborting candidate on {} failed: {}", device_addr, err);
+            }
+        });
+    }
+}
+
+impl SshConnectionManager {
+    /// Hold the pooled session for `request` and stage a change in its
+    /// candidate config, connecting first when none is pooled.
+    ///
+    /// Other jobs for the same cache key queue until the session commits,
+    /// aborts or is dropped. Commands run in `context.sys`, and
+    /// `context.operator` becomes the commit comment unless `candidate`
+    /// sets one.
+    pub async fn candidate_session(
+        &self,
+        request: ConnectionRequest,
+        context: ExecutionContext,
+        mut candidate: CandidateConfig,
+    ) -> Result<CandidateConfigSession, ConnectError> {
+        candidate.validate_spec()?;
+        if candidate.comment.is_none() {
+            candidate.comment = context.operator.clone();
+        }
+        let device_addr = request.device_addr();
+        self.get_with_request_and_recording(request, &context, None)
+            .await?;
+        let pooled = self.cache.get(&device_addr).await.ok_or_else(|| {
+            ConnectError::InternalServerError("connection cache miss".to_string())
+        })?;
+        let mut guard = pooled.client.write_owned().await;
+        guard.set_job_scope(JobScope::from(&context));
+        debug!("Candidate session opened on {}", device_addr);
+        Ok(CandidateConfigSession {
+            device_addr,
+            candidate,
+            client: Some(guard),
+            sys: context.sys,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn candidate_session_rejects_incomplete_candidates() {
+        let manager = SshConnectionManager::new();
+        manager.set_connect_timeout(Duration::from_millis(200));
+        let request = || {
+            ConnectionRequest::new(
+                "admin".to_string(),
+                "127.0.0.1".to_string(),
+                1,
+                "secret".to_string(),
+                None,
+                crate::templates::juniper().expect("juniper"),
+            )
+        };
+        let mut candidate = CandidateConfig::junos();
+        candidate.abort.clear();
+        let session = manager
+            .candidate_session(request(), ExecutionContext::new(), candidate)
+            .await;
+        assert!(matches!(session, Err(ConnectError::InvalidTransaction(_))));
+
+        let session = manager
+            .candidate_session(request(), ExecutionContext::new(), CandidateConfig::junos())
+            .await;
+        assert!(session.is_err());
+    }
+}
@@ -1,7 +1,7 @@
 use super::super::*;
 use super::tx::{
     OperationRunError, OperationRunFuture, TxCommandRunner, execute_tx_block_with_runner,
-    execute_tx_workflow_with_runner,
+    execute_tx_workflow_with_runner, rollback_tx_workflow_with_runner,
 };
 use crate::device::{STRIP_CSI_ESCAPE, STRIP_DCS_ESCAPE, STRIP_OSC_ESCAPE, STRIP_SIMPLE_ESCAPE};
 use regex::RegexSet;
@@ -25,12 +25,20 @@ struct RuntimePromptMatcher {
 }
 
 #[derive(Debug, Default)]
-struct RuntimeCommandInteraction {
+pub(super) struct RuntimeCommandInteraction {
     prompts: Vec<RuntimePromptMatcher>,
 }
 
+/// Check that every rule has at least one valid regex pattern.
+pub(crate) fn validate_prompt_rules(rules: &[PromptResponseRule]) -> Result<(), ConnectError> {
+    RuntimeCommandInteraction::build(&CommandInteraction {
+        prompts: rules.to_vec(),
+    })
+    .map(|_| ())
+}
+
 impl RuntimeCommandInteraction {
-    fn build(interaction: &CommandInteraction) -> Result<Self, ConnectError> {
+    pub(super) fn build(interaction: &CommandInteraction) -> Result<Self, ConnectError> {
         let mut prompts = Vec::with_capacity(interaction.prompts.len());
 
         for (index, prompt) in interaction.prompts.iter().enumerate() {
@@ -56,7 +64,7 @@ impl RuntimeCommandInteraction {
         Ok(Self { prompts })
     }
 
-    fn read_need_write(&self, line: &str) -> Option<(String, bool)> {
+    pub(super) fn read_need_write(&self, line: &str) -> Option<(String, bool)> {
         let sanitized = sanitize_runtime_prompt(line);
         self.prompts
             .iter()
@@ -72,16 +80,12 @@ impl SharedSshClient {
         command: &Command,
         sys: Option<&String>,
     ) -> Result<SessionOperationStepOutput, ConnectError> {
-        let timeout = Duration::from_secs(command.timeout.unwrap_or(60));
+        let timeout = command
+            .timeout
+            .map(Duration::from_secs)
+            .unwrap_or_else(|| self.status.command_timeout());
         let output = self
-            .write_with_mode_and_timeout_using_command(
-                &command.command,
-                &command.mode,
-                sys,
-                timeout,
-                &command.dyn_params,
-                &command.interaction,
-            )
+            .write_with_mode_and_timeout_using_command(command, sys, timeout)
             .await?;
 
         Ok(SessionOperationStepOutput {
@@ -91,8 +95,9 @@ impl SharedSshClient {
             success: output.success,
             exit_code: output.exit_code,
             content: output.content,
-            all: output.all,
+            all: join_segments(output.segments),
             prompt: output.prompt,
+            spilled: output.spilled,
         })
     }
 
@@ -200,18 +205,97 @@ impl SharedSshClient {
         command: &str,
         timeout: Duration,
     ) -> Result<Output, ConnectError> {
-        self.write_with_timeout_internal(command, timeout, true, &CommandInteraction::default())
-            .await
+        let mode = self.handler.current_state().to_string();
+        self.enforce_command_policy(command, &mode)?;
+        self.write_with_timeout_internal(
+            command,
+            timeout,
+            true,
+            &CommandInteraction::default(),
+            None,
+            false,
+            false,
+        )
+        .await
+    }
+
+    pub(in crate::session) fn enforce_command_policy(
+        &self,
+        command: &str,
+        mode: &str,
+    ) -> Result<(), ConnectError> {
+        let checked = self
+            .status
+            .check_command(command)
+            .and_then(|()| match &self.scope.command_policy {
+                Some(policy) => policy.check(command),
+                None => Ok(()),
+            })
+            .and_then(|()| match self.change_freeze() {
+                Some(freeze) => freeze.check_command(command, self.handler.terminal_setup()),
+                None => Ok(()),
+            });
+        let Err(err) = checked else {
+            return Ok(());
+        };
+        if let ConnectError::CommandBlockedByPolicy { pattern, .. } = &err {
+            debug!(
+                "Command '{}' blocked by policy pattern '{}'",
+                command, pattern
+            );
+            if let Some(recorder) = self.recorder() {
+                let _ = recorder.record_event(SessionEvent::CommandBlocked {
+                    command: command.to_string(),
+                    mode: mode.to_string(),
+                    pattern: pattern.clone(),
+                });
+            }
+        }
+        Err(err)
     }
 
-    async fn write_with_timeout_internal(
+    /// Parse output that arrived since the last command, such as syslog lines
+    /// or late prompts, through the state machine instead of discarding it.
+    pub(crate) fn resync_residual_output(&mut self) {
+        let mut residual = Vec::new();
+        while let Ok(data) = self.recv.try_recv() {
+            residual.extend_from_slice(&data);
+        }
+        if residual.is_empty() {
+            return;
+        }
+
+        let content = lines::decode_line(&residual).into_owned();
+        debug!("Resyncing on residual output: {:?}", content);
+        if let Some(prompt) = apply_residual_output(&mut self.handler, &residual) {
+            self.prompt = prompt;
+        }
+        if let Some(recorder) = self.status.recorder() {
+            let _ = recorder.record_raw_chunk(content.clone());
+            let _ = recorder.record_event(SessionEvent::ResidualOutput {
+                content,
+                fsm_prompt_after: Some(self.handler.current_state().to_string()),
+            });
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub(in crate::session) async fn write_with_timeout_internal(
         &mut self,
         command: &str,
         timeout: Duration,
         capture_exit_status: bool,
         interaction: &CommandInteraction,
+        spill_threshold: Option<usize>,
+        expect_disconnect: bool,
+        keep_echo: bool,
     ) -> Result<Output, ConnectError> {
         let runtime_interaction = RuntimeCommandInteraction::build(interaction)?;
+        let mut spill = spill_threshold.map(spill::OutputSpill::new);
+        self.resync_residual_output();
+        let recorder = self.status.recorder();
+        let slow_threshold = self.status.slow_command_threshold();
+        let unmodeled = self.handler.unmodeled_prompt().cloned();
         let handler = &mut self.handler;
 
         let recv = &mut self.recv;
@@ -220,30 +304,96 @@ impl SharedSshClient {
         let mode = handler.current_state().to_string();
         let fsm_prompt_before = handler.current_state().to_string();
 
-        while recv.try_recv().is_ok() {}
-
         let sent_command = handler.prepare_command_for_execution(command, capture_exit_status);
         let full_command = format!("{}\n", sent_command);
+        let started = Instant::now();
         self.sender.send(full_command).await?;
 
         let mut clean_output = String::new();
-        let mut line_buffer = String::new();
-        let mut line = String::new();
+        let mut async_messages = Vec::new();
+        let mut lines = lines::LineSplitter::for_handler(handler);
+
+        let slow_deadline = slow_threshold
+            .filter(|threshold| *threshold < timeout)
+            .map(|threshold| tokio::time::Instant::now() + threshold);
+        let mut slow_reported = false;
+        // Set while output waits on a line that is neither prompt nor known input.
+        let mut question_deadline = None;
+        let mut answers = 0;
 
         let result = tokio::time::timeout(timeout, async {
             let mut is_error = false;
             loop {
-                if let Some(data) = recv.recv().await {
-                    if let Some(recorder) = self.recorder.as_ref() {
-                        let _ = recorder.record_raw_chunk(data.clone());
+                let received = tokio::select! {
+                    data = recv.recv() => data,
+                    _ = sleep_until_some(slow_deadline.filter(|_| !slow_reported)) => {
+                        slow_reported = true;
+                        report_slow_command(
+                            self.status.device_addr(),
+                            recorder.as_ref(),
+                            self.events.as_ref(),
+                            command,
+                            &mode,
+                            slow_threshold.unwrap_or_default(),
+                            timeout,
+                        );
+                        continue;
+                    }
+                    _ = sleep_until_some(question_deadline) => {
+                        question_deadline = None;
+                        let Some(policy) = unmodeled.as_ref() else {
+                            continue;
+                        };
+                        let question = lines.pending().trim().to_string();
+                        crate::templates::record_unmatched_line(
+                            handler.template_name(),
+                            crate::templates::UnmatchedLineKind::UnmodeledPrompt,
+                            &question,
+                            command,
+                        );
+                        let answer = policy
+                            .answer
+                            .clone()
+                            .filter(|_| answers < policy.max_answers);
+                        report_unmodeled_prompt(
+                            self.status.device_addr(),
+                            recorder.as_ref(),
+                            self.events.as_ref(),
+                            command,
+                            &mode,
+                            &question,
+                            answer.as_deref(),
+                        );
+                        match answer {
+                            Some(answer) => {
+                                answers += 1;
+                                self.sender.send(format!("{answer}\n")).await?;
+                            }
+                            None => {
+                                return Err(ConnectError::UnmodeledPrompt {
+                                    command: command.to_string(),
+                                    question,
+                                });
+                            }
+                        }
+                        continue;
+                    }
+                };
+                question_deadline = None;
+                if let Some(data) = received {
+                    if let Some(recorder) = recorder.as_ref() {
+                        let _ = recorder.record_raw_chunk(lines::decode_line(&data).into_owned());
                     }
-                    line_buffer.push_str(&data);
+                    lines.push(&data);
 
-                    while let Some(newline_pos) = line_buffer.find('\n') {
-                        line.clear();
-                        line.extend(line_buffer.drain(..=newline_pos));
+                    while let Some(line) = lines.next_line() {
+                        let line = lines::decode_line(&line);
                         let trim_start = IGNORE_START_LINE.replace(&line, "");
                         let trimmed_line = trim_start.trim_end();
+                        if handler.is_syslog_line(trimmed_line) {
+                            async_messages.push(trimmed_line.to_string());
+                            continue;
+                        }
 
                         handler.read(trimmed_line);
 
@@ -253,14 +403,21 @@ impl SharedSshClient {
 
                         clean_output.push_str(&trim_start);
                     }
+                    self.status
+                        .publish_state(handler.current_state(), prompt.as_str());
+                    self.status.record_sys(handler.current_sys());
+                    if let Some(spill) = spill.as_mut() {
+                        spill.relieve(&mut clean_output)?;
+                    }
 
-                    if !line_buffer.is_empty() {
+                    if !lines.is_empty() {
+                        let line_buffer = lines.pending();
                         if handler.read_prompt(&line_buffer) {
                             handler.read(&line_buffer);
                             let matched_prompt =
                                 handler.current_prompt().unwrap_or(&line_buffer).to_string();
                             clean_output.push_str(&line_buffer);
-                            if let Some(recorder) = self.recorder.as_ref()
+                            if let Some(recorder) = recorder.as_ref()
                                 && *prompt != matched_prompt
                             {
                                 let _ = recorder.record_event(SessionEvent::PromptChanged {
@@ -268,27 +425,36 @@ impl SharedSshClient {
                                 });
                             }
                             *prompt = matched_prompt;
+                            self.status
+                                .publish_state(handler.current_state(), prompt.as_str());
+                            self.status.record_sys(handler.current_sys());
                             if is_error {
                                 return Ok(false);
                             }
                             return Ok(true);
                         }
-                        if let Some((c, is_record)) =
+                        let input = if let Some((c, is_record)) =
                             runtime_interaction.read_need_write(&line_buffer)
                         {
-                            handler.read(&line_buffer);
-                            if !is_record {
-                                line_buffer.clear();
-                            }
                             trace!("Runtime input required: '{:?}'", c);
-                            self.sender.send(c).await?;
+                            Some((c, is_record))
                         } else if let Some((c, is_record)) = handler.read_need_write(&line_buffer) {
+                            trace!("Input required: '{:?}'", c);
+                            Some((c, is_record))
+                        } else {
+                            None
+                        };
+                        if let Some(err) = handler.take_password_rejection() {
+                            return Err(err);
+                        }
+                        if let Some((c, is_record)) = input {
                             handler.read(&line_buffer);
                             if !is_record {
-                                line_buffer.clear();
+                                lines.clear();
                             }
-                            trace!("Input required: '{:?}'", c);
                             self.sender.send(c).await?;
+                        } else if let Some(policy) = unmodeled.as_ref() {
+                            question_deadline = Some(tokio::time::Instant::now() + policy.idle());
                         }
                     }
                 } else {
@@ -298,9 +464,10 @@ impl SharedSshClient {
         })
         .await;
 
+        let mut disconnected = false;
         let success = match result {
             Err(_) => {
-                if let Some(recorder) = self.recorder.as_ref() {
+                if let Some(recorder) = recorder.as_ref() {
                     let _ = recorder.record_event(SessionEvent::CommandOutput {
                         command: command.to_string(),
                         mode: mode.clone(),
@@ -310,14 +477,47 @@ impl SharedSshClient {
                         fsm_prompt_after: Some(self.handler.current_state().to_string()),
                         success: false,
                         exit_code: None,
+                        duration_ms: Some(
+                            u64::try_from(started.elapsed().as_millis()).unwrap_or(u64::MAX),
+                        ),
                         content: clean_output.clone(),
                         all: clean_output.clone(),
                     });
                 }
-                return Err(ConnectError::ExecTimeout(clean_output));
+                discard_spill(spill);
+                let received = format!("{clean_output}{}", lines.pending());
+                if received.trim().is_empty() {
+                    return Err(ConnectError::ExecTimeout(clean_output));
+                }
+                if let Some(last_line) = received.lines().rev().find(|line| !line.trim().is_empty())
+                {
+                    crate::templates::record_unmatched_line(
+                        self.handler.template_name(),
+                        crate::templates::UnmatchedLineKind::PromptNotMatched,
+                        last_line,
+                        command,
+                    );
+                }
+                return Err(ConnectError::PromptNotMatched(Box::new(
+                    self.handler.diagnose_prompt_mismatch(
+                        Some(command),
+                        &received,
+                        PROMPT_DIAGNOSTIC_LINES,
+                    ),
+                )));
+            }
+            Ok(Err(ConnectError::ChannelDisconnectError)) if expect_disconnect => {
+                debug!(
+                    "{} disconnected as expected after '{}'",
+                    self.status.device_addr(),
+                    command
+                );
+                self.status.mark_disconnected();
+                disconnected = true;
+                true
             }
             Ok(Err(err)) => {
-                if let Some(recorder) = self.recorder.as_ref() {
+                if let Some(recorder) = recorder.as_ref() {
                     let _ = recorder.record_event(SessionEvent::CommandOutput {
                         command: command.to_string(),
                         mode: mode.clone(),
@@ -327,10 +527,14 @@ impl SharedSshClient {
                         fsm_prompt_after: Some(self.handler.current_state().to_string()),
                         success: false,
                         exit_code: None,
+                        duration_ms: Some(
+                            u64::try_from(started.elapsed().as_millis()).unwrap_or(u64::MAX),
+                        ),
                         content: clean_output.clone(),
                         all: clean_output.clone(),
                     });
                 }
+                discard_spill(spill);
                 return Err(err);
             }
             Ok(Ok(success)) => success,
@@ -339,19 +543,28 @@ impl SharedSshClient {
         let parsed =
             self.handler
                 .finalize_command_output(&clean_output, success, capture_exit_status);
-        let success = parsed.success;
+        let success = parsed.success || disconnected;
         let exit_code = parsed.exit_code;
-        let all = parsed.output;
-
-        let mut content = all.as_str();
-        if !sent_command.is_empty() && content.starts_with(&sent_command) {
-            content = content
-                .strip_prefix(&sent_command)
-                .unwrap_or(content)
-                .trim_start_matches(['\n', '\r']);
-        }
+        let spilled = match spill {
+            Some(spill) if spill.is_spilled() => spill.finish(&parsed.output)?,
+            _ => None,
+        };
+        let all = if spilled.is_some() {
+            String::new()
+        } else {
+            parsed.output
+        };
+
+        let content = if keep_echo {
+            all.as_str()
+        } else {
+            echo::strip_echo(&all, &sent_command)
+        };
 
-        let content = if let Some(pos) = content.rfind('\n') {
+        // Without a closing prompt the last line is still command output.
+        let content = if disconnected {
+            content.trim_end()
+        } else if let Some(pos) = content.rfind('\n') {
             &content[..pos]
         } else {
             ""
@@ -361,11 +574,19 @@ impl SharedSshClient {
             success,
             exit_code,
             content: content.to_string(),
-            all,
+            segments: if spilled.is_some() {
+                Vec::new()
+            } else {
+                vec![all]
+            },
             prompt: self.handler.current_prompt().map(|v| v.to_string()),
+            spilled,
+            device_addr: Some(self.status.device_addr().to_string()),
+            sys: self.handler.current_sys().map(str::to_string),
+            async_messages,
         };
 
-        if let Some(recorder) = self.recorder.as_ref() {
+        if let Some(recorder) = recorder.as_ref() {
             let _ = recorder.record_event(SessionEvent::CommandOutput {
                 command: command.to_string(),
                 mode,
@@ -375,8 +596,9 @@ impl SharedSshClient {
                 fsm_prompt_after: Some(self.handler.current_state().to_string()),
                 success: output.success,
                 exit_code: output.exit_code,
+                duration_ms: Some(u64::try_from(started.elapsed().as_millis()).unwrap_or(u64::MAX)),
                 content: output.content.clone(),
-                all: output.all.clone(),
+                all: output.all().into_owned(),
             });
         }
 
@@ -404,71 +626,208 @@ impl SharedSshClient {
         sys: Option<&String>,
         timeout: Duration,
     ) -> Result<Output, ConnectError> {
-        self.write_with_mode_and_timeout_using_command(
-            command,
-            mode,
-            sys,
-            timeout,
-            &CommandDynamicParams::default(),
-            &CommandInteraction::default(),
-        )
-        .await
+        let command = Command::new(mode, command);
+        self.write_with_mode_and_timeout_using_command(&command, sys, timeout)
+            .await
     }
 
     /// Executes a command in a specific device mode with per-command overrides.
     pub(crate) async fn write_with_mode_and_timeout_using_command(
         &mut self,
-        command: &str,
-        mode: &str,
+        command: &Command,
         sys: Option<&String>,
         timeout: Duration,
-        dyn_params: &CommandDynamicParams,
-        interaction: &CommandInteraction,
     ) -> Result<Output, ConnectError> {
-        let previous = self.merge_command_dyn_params(dyn_params);
-        let result = self
-            .write_with_mode_and_timeout_without_overrides(command, mode, sys, timeout, interaction)
-            .await;
-        self.restore_command_dyn_params(previous);
+        self.enforce_command_policy(&command.command, &command.mode)?;
+        let _slots = self.status.acquire_command_slots().await;
+        let started = Instant::now();
+        let result = if command
+            .exec_channel
+            .unwrap_or_else(|| self.handler.uses_exec_channel())
+        {
+            self.write_on_exec_channel(command, timeout).await
+        } else {
+            let retry = self.scope.config_lock_retry;
+            let mut retries = 0;
+            loop {
+                let previous = self.merge_command_dyn_params(&command.dyn_params);
+                let result = self
+                    .write_with_mode_and_timeout_without_overrides(command, sys, timeout)
+                    .await;
+                self.restore_command_dyn_params(previous);
+                match (&result, retry) {
+                    (Err(ConnectError::ConfigLocked { holder, .. }), Some(retry))
+                        if retries < retry.attempts =>
+                    {
+                        retries += 1;
+                        debug!(
+                            "Configuration locked by {:?}, retry {}/{} in {}s",
+                            holder, retries, retry.attempts, retry.interval_secs
+                        );
+                        tokio::time::sleep(retry.interval()).await;
+                    }
+                    _ => break result,
+                }
+            }
+        };
+        self.status.stats().record_command(
+            started.elapsed(),
+            matches!(&result, Ok(output) if output.success),
+        );
+        #[cfg(feature = "metrics")]
+        crate::metrics::command_finished(
+            started.elapsed(),
+            matches!(&result, Ok(output) if output.success),
+            matches!(
+                result.as_ref().map_err(ConnectError::root_cause),
+                Err(ConnectError::ExecTimeout(_) | ConnectError::PromptNotMatched(_))
+            ),
+        );
         result
     }
 
+    /// Walks the session back to `state`, e.g. out of a config sub-mode.
+    ///
+    /// Does nothing when already there. Fails when a transition command
+    /// fails or leaves the state machine somewhere else.
+    pub(crate) async fn return_to_state(
+        &mut self,
+        state: &str,
+        timeout: Duration,
+    ) -> Result<(), ConnectError> {
+        let state = state.to_ascii_lowercase();
+        if self.handler.current_state() == state {
+            return Ok(());
+        }
+        for (t_cmd, target_state) in self.handler.trans_state_write(&state, None)? {
+            debug!("Return to {} command: {}", state, t_cmd);
+            let from = self.handler.current_state().to_string();
+            let output = self
+                .write_with_timeout_internal(
+                    &t_cmd,
+                    timeout,
+                    false,
+                    &CommandInteraction::default(),
+                    None,
+                    false,
+                    false,
+                )
+                .await?;
+            let success = self.handler.transition_succeeded(
+                &from,
+                &target_state,
+                &output.content,
+                output.success,
+            );
+            if !success || self.handler.current_state() != target_state {
+                return Err(ConnectError::UnreachableState(state));
+            }
+        }
+        if let Some(recorder) = self.recorder() {
+            let _ = recorder.record_event(SessionEvent::StateChanged { state });
+        }
+        Ok(())
+    }
+
+    /// Runs a command on a fresh SSH exec channel, bypassing the shell and prompt FSM.
+    ///
+    /// Content is stdout followed by stderr; success comes from the exit status.
+    async fn write_on_exec_channel(
+        &self,
+        command: &Command,
+        timeout: Duration,
+    ) -> Result<Output, ConnectError> {
+        debug!("Exec channel command: {}", command.command);
+        let started = Instant::now();
+        let result = tokio::time::timeout(timeout, self.client.execute(&command.command))
+            .await
+            .map_err(|_| ConnectError::ExecTimeout(String::new()))??;
+
+        let mut content = result.stdout;
+        content.push_str(&result.stderr);
+        let output = Output {
+            success: result.exit_status == 0,
+            exit_code: i32::try_from(result.exit_status).ok(),
+            content: content.clone(),
+            segments: vec![content],
+            prompt: None,
+            spilled: None,
+            device_addr: Some(self.status.device_addr().to_string()),
+            sys: None,
+            async_messages: Vec::new(),
+        };
+
+        if let Some(recorder) = self.recorder() {
+            let _ = recorder.record_event(SessionEvent::CommandOutput {
+                command: command.command.clone(),
+                mode: command.mode.clone(),
+                prompt_before: None,
+                prompt_after: None,
+                fsm_prompt_before: None,
+                fsm_prompt_after: None,
+                success: output.success,
+                exit_code: output.exit_code,
+                duration_ms: Some(u64::try_from(started.elapsed().as_millis()).unwrap_or(u64::MAX)),
+                content: output.content.clone(),
+                all: output.all().into_owned(),
+            });
+        }
+        Ok(output)
+    }
+
     async fn write_with_mode_and_timeout_without_overrides(
         &mut self,
-        command: &str,
-        mode: &str,
+        command: &Command,
         sys: Option<&String>,
         timeout: Duration,
-        interaction: &CommandInteraction,
     ) -> Result<Output, ConnectError> {
         let handler = &self.handler;
 
-        let temp_mode = mode.to_ascii_lowercase();
+        let temp_mode = command.mode.to_ascii_lowercase();
         let mode = temp_mode.as_str();
         let mut last_state = self.handler.current_state().to_string();
 
         let trans_cmds = handler.trans_state_write(mode, sys)?;
-        let mut all = self.prompt.clone();
+        let mut segments = Vec::new();
+        if !self.prompt.is_empty() {
+            segments.push(self.prompt.clone());
+        }
 
         for (t_cmd, target_state) in trans_cmds {
             debug!("Trans state command: {}", t_cmd);
+            let from = self.handler.current_state().to_string();
             let mut mode_output = self
-                .write_with_timeout_internal(&t_cmd, timeout, false, &CommandInteraction::default())
+                .write_with_timeout_internal(
+                    &t_cmd,
+                    timeout,
+                    false,
+                    &CommandInteraction::default(),
+                    None,
+                    false,
+                    false,
+                )
                 .await?;
-            all.push_str(mode_output.all.as_str());
+            self.check_config_lock(&mode_output.content)?;
+            segments.append(&mut mode_output.segments);
+            mode_output.success = self.handler.transition_succeeded(
+                &from,
+                &target_state,
+                &mode_output.content,
+                mode_output.success,
+            );
             if !mode_output.success {
-                mode_output.all = all;
+                mode_output.segments = segments;
                 return Ok(mode_output);
             }
 
             if !self.handler.current_state().eq(&target_state) {
                 mode_output.success = false;
-                mode_output.all = all;
+                mode_output.segments = segments;
                 return Ok(mode_output);
             }
 
             let current_state = self.handler.current_state().to_string();
-            if let Some(recorder) = self.recorder.as_ref()
+            if let Some(recorder) = self.recorder()
                 && current_state != last_state
             {
                 let _ = recorder.record_event(SessionEvent::StateChanged {
@@ -479,11 +838,22 @@ impl SharedSshClient {
         }
 
         let mut cmd_output = self
-            .write_with_timeout_internal(command, timeout, true, interaction)
+            .write_with_timeout_internal(
+                &command.command,
+                timeout,
+                true,
+                &command.interaction,
+                command.spill_threshold,
+                command.expect_disconnect,
+                command.keep_echo,
+            )
             .await?;
-        all.push_str(cmd_output.all.as_str());
+        if !cmd_output.success {
+            self.check_config_lock(&cmd_output.content)?;
+        }
+        segments.append(&mut cmd_output.segments);
 
-        cmd_output.all = all;
+        cmd_output.segments = segments;
         Ok(cmd_output)
     }
 
@@ -496,7 +866,12 @@ impl SharedSshClient {
         block: &TxBlock,
         sys: Option<&String>,
     ) -> Result<TxResult, ConnectError> {
-        execute_tx_block_with_runner(self, block, sys).await
+        if let Some(freeze) = self.change_freeze() {
+            freeze.check_blocks([block], self.handler.terminal_setup())?;
+        }
+        let mut block = block.clone();
+        self.fill_missing_modes(&mut block, sys);
+        execute_tx_block_with_runner(self, &block, sys).await
     }
 
     /// Execute multi-block workflow with global rollback on failure.
@@ -505,13 +880,53 @@ impl SharedSshClient {
         workflow: &TxWorkflow,
         sys: Option<&String>,
     ) -> Result<TxWorkflowResult, ConnectError> {
-        execute_tx_workflow_with_runner(self, workflow, sys).await
+        let mut workflow = workflow.clone();
+        for block in &mut workflow.blocks {
+            self.fill_missing_modes(block, sys);
+        }
+        if let Some(freeze) = self.change_freeze() {
+            freeze.check_blocks(&workflow.blocks, self.handler.terminal_setup())?;
+        }
+        if workflow.preflight {
+            self.preflight_tx_workflow(&workflow, sys).into_result()?;
+        }
+        execute_tx_workflow_with_runner(self, &workflow, sys).await
+    }
+
+    /// Undo a workflow that committed, e.g. when a later check fails.
+    ///
+    /// Every committed block is rolled back, last first, and `result` is
+    /// updated as if the workflow had failed after its last block.
+    pub async fn rollback_tx_workflow(
+        &mut self,
+        workflow: &TxWorkflow,
+        result: &mut TxWorkflowResult,
+        sys: Option<&String>,
+    ) -> Result<(), ConnectError> {
+        let mut workflow = workflow.clone();
+        for block in &mut workflow.blocks {
+            self.fill_missing_modes(block, sys);
+        }
+        // Undoing a change is never frozen.
+        rollback_tx_workflow_with_runner(self, &workflow, result, sys).await
+    }
+
+    /// Derive the modes `block` omits from this session's handler; with a
+    /// `sys` the steps run in the sys states.
+    pub(in crate::session) fn fill_missing_modes(&self, block: &mut TxBlock, sys: Option<&String>) {
+        let in_sys = sys.is_some();
+        if let (Some(show), Some(config)) = (
+            self.handler.default_mode(false, in_sys),
+            self.handler.default_mode(true, in_sys),
+        ) {
+            block.fill_missing_modes(&show, &config);
+        }
     }
 }
 
 impl TxCommandRunner for SharedSshClient {
-    fn recorder(&self) -> Option<&SessionRecorder> {
-        self.recorder.as_ref()
+    fn recorder(&self) -> Option<SessionRecorder> {
+        self.status.recorder()
     }
 
     fn run_operation<'a>(
@@ -523,10 +938,165 @@ impl TxCommandRunner for SharedSshClient {
     }
 }
 
+/// Remove the temp file of a command that failed before finishing.
+fn discard_spill(spill: Option<spill::OutputSpill>) {
+    if let Some(Ok(Some(spilled))) = spill.map(|spill| spill.finish("")) {
+        let _ = std::fs::remove_file(spilled.path);
+    }
+}
+
+/// Feed residual output through `handler`, returning the prompt it ended on, if any.
+fn apply_residual_output(handler: &mut DeviceHandler, residual: &[u8]) -> Option<String> {
+    let mut lines = lines::LineSplitter::for_handler(handler);
+    lines.push(residual);
+    while let Some(line) = lines.next_line() {
+        let line = lines::decode_line(&line);
+        let trimmed = IGNORE_START_LINE.replace(&line, "");
+        if !handler.is_syslog_line(trimmed.trim_end()) {
+            handler.read(trimmed.trim_end());
+        }
+    }
+
+    let pending = lines.pending();
+    if !pending.is_empty() && handler.read_prompt(&pending) {
+        handler.read(&pending);
+        return Some(handler.current_prompt().unwrap_or(&pending).to_string());
+    }
+    None
+}
+
+/// Sleep until `deadline`, or forever without one.
+async fn sleep_until_some(deadline: Option<tokio::time::Instant>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep_until(deadline).await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Emit an unmodeled question to the log, the recorder and the event bus.
+fn report_unmodeled_prompt(
+    device_addr: &str,
+    recorder: Option<&SessionRecorder>,
+    events: Option<&events::ConnectionEventBus>,
+    command: &str,
+    mode: &str,
+    question: &str,
+    answer: Option<&str>,
+) {
+    match answer {
+        Some(answer) => warn!(
+            "{} command '{}' stopped on unmodeled question '{}', answering '{}'",
+            device_addr, command, question, answer
+        ),
+        None => warn!(
+            "{} command '{}' stopped on unmodeled question '{}'",
+            device_addr, command, question
+        ),
+    }
+    if let Some(recorder) = recorder {
+        let _ = recorder.record_event(SessionEvent::UnmodeledPrompt {
+            command: command.to_string(),
+            mode: mode.to_string(),
+            question: question.to_string(),
+            answer: answer.map(str::to_string),
+        });
+    }
+    if let Some(events) = events {
+        events.emit(ConnectionEvent::UnmodeledPrompt {
+            device_addr: device_addr.to_string(),
+            mode: mode.to_string(),
+            command: command.to_string(),
+            question: question.to_string(),
+            answer: answer.map(str::to_string),
+        });
+    }
+}
+
+/// Emit the slow-command warning to the log, the recorder and the event bus.
+fn report_slow_command(
+    device_addr: &str,
+    recorder: Option<&SessionRecorder>,
+    events: Option<&events::ConnectionEventBus>,
+    command: &str,
+    mode: &str,
+    elapsed: Duration,
+    timeout: Duration,
+) {
+    let elapsed_ms = u64::try_from(elapsed.as_millis()).unwrap_or(u64::MAX);
+    let timeout_ms = u64::try_from(timeout.as_millis()).unwrap_or(u64::MAX);
+    warn!(
+        "{} command '{}' still running after {}ms (timeout {}ms)",
+        device_addr, command, elapsed_ms, timeout_ms
+    );
+    if let Some(recorder) = recorder {
+        let _ = recorder.record_event(SessionEvent::SlowCommand {
+            command: command.to_string(),
+            mode: mode.to_string(),
+            elapsed_ms,
+            timeout_ms,
+        });
+    }
+    if let Some(events) = events {
+        events.emit(ConnectionEvent::SlowCommand {
+            device_addr: device_addr.to_string(),
+            mode: mode.to_string(),
+            command: command.to_string(),
+            elapsed_ms,
+            timeout_ms,
+        });
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn residual_output_updates_state_instead_of_being_dropped() {
+        let mut handler = crate::templates::cisco().expect("template");
+        handler.read("router#");
+        let before = handler.current_state().to_string();
+
+        let prompt = apply_residual_output(
+            &mut handler,
+            b"\r\n%SYS-5-CONFIG_I: Configured from console\r\nrouter(config)#",
+        );
+
+        assert_eq!(prompt.as_deref(), Some("router(config)#"));
+        assert_ne!(handler.current_state(), before);
+    }
+
+    #[tokio::test]
+    async fn slow_command_report_reaches_recorder_and_event_bus() {
+        let recorder = SessionRecorder::new(SessionRecordLevel::KeyEventsOnly);
+        let bus = events::ConnectionEventBus::new();
+        let mut subscriber = bus.subscribe();
+
+        report_slow_command(
+            "admin@192.0.2.1:22",
+            Some(&recorder),
+            Some(&bus),
+            "show tech-support",
+            "enable",
+            Duration::from_secs(10),
+            Duration::from_secs(60),
+        );
+
+        let entries = recorder.entries().expect("entries");
+        assert!(matches!(
+            &entries[0].event,
+            SessionEvent::SlowCommand { command, elapsed_ms: 10_000, timeout_ms: 60_000, .. }
+                if command == "show tech-support"
+        ));
+        assert!(matches!(
+            subscriber.recv().await.expect("event"),
+            ConnectionEvent::SlowCommand {
+                elapsed_ms: 10_000,
+                ..
+            }
+        ));
+    }
+
     #[test]
     fn runtime_command_interaction_matches_sanitized_prompt() {
         let interaction = RuntimeCommandInteraction::build(&CommandInteraction {
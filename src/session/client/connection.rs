@@ -52,42 +52,99 @@ impl SharedSshClient {
 
     /// Safely closes the connection.
     pub async fn close(&mut self) -> Result<(), ConnectError> {
-        debug!("Safely closing SSH connection...");
+        self.close_with_reason("client_close_called").await
+    }
+
+    /// Safely closes the connection, recording why it was closed.
+    pub(crate) async fn close_with_reason(&mut self, reason: &str) -> Result<(), ConnectError> {
+        let stats = self.stats();
+        debug!(
+            "Safely closing SSH connection ({}), stats: {:?}",
+            reason, stats
+        );
+        self.status.set_close_reason(reason);
 
-        if let Some(recorder) = self.recorder.as_ref() {
+        if let Some(recorder) = self.status.recorder() {
             let _ = recorder.record_event(SessionEvent::ConnectionClosed {
-                reason: "client_close_called".to_string(),
+                reason: reason.to_string(),
                 prompt_before: Some(self.prompt.clone()),
                 fsm_prompt_before: Some(self.handler.current_state().to_string()),
+                stats: Some(stats),
             });
         }
 
+        // Leave config and sys contexts first so the device (and TACACS
+        // accounting) sees a clean logout rather than a dropped session.
+        for command in self.handler.logout_path() {
+            if !self.is_connected() {
+                break;
+            }
+            let exited = self
+                .write_with_timeout_internal(
+                    &command,
+                    Duration::from_secs(5),
+                    false,
+                    &CommandInteraction::default(),
+                    None,
+                    true,
+                    false,
+                )
+                .await;
+            match exited {
+                Ok(output) if output.success => {}
+                Ok(output) => {
+                    debug!("Exit command '{}' failed: {}", command, output.content);
+                    break;
+                }
+                Err(e) => {
+                    debug!("Exit command '{}' failed: {:?}", command, e);
+                    break;
+                }
+            }
+        }
+
         self.recv.close();
 
         if self.is_connected() {
-            if let Err(e) = self.sender.send("exit\n".to_string()).await {
-                debug!("Failed to send exit command: {:?}", e);
+            let logout = format!("{}\n", self.handler.logout_command());
+            if let Err(e) = self.sender.send(logout).await {
+                debug!("Failed to send logout command: {:?}", e);
             }
 
             tokio::time::sleep(Duration::from_millis(100)).await;
         }
+        self.status.mark_disconnected();
 
         debug!("SSH connection safely closed");
         Ok(())
     }
 
+    /// Connects and waits for the first prompt.
+    ///
+    /// `password` and `enable_password` identify the request for cache matching;
+    /// when `credentials` is set, they are used for authentication instead.
+    /// The connection removes itself from `pool` when its shell closes.
     #[allow(clippy::too_many_arguments)]
-    pub(crate) async fn new(
+    pub(in crate::session) async fn new(
         user: String,
         addr: String,
         port: u16,
+        affinity: Option<String>,
         password: String,
         enable_password: Option<String>,
+        credentials: Option<DeviceCredentials>,
         mut handler: DeviceHandler,
         security_options: ConnectionSecurityOptions,
+        resolved: address::ResolvedAddrs,
         recorder: Option<SessionRecorder>,
+        login_interstitials: &[PromptResponseRule],
+        pool: std::sync::Weak<ConnectionCache>,
     ) -> Result<SharedSshClient, ConnectError> {
-        let device_addr = format!("{user}@{addr}:{port}");
+        let device_addr = cache_key(&user, &addr, port, affinity.as_deref());
+        let interstitials =
+            super::command::RuntimeCommandInteraction::build(&CommandInteraction {
+                prompts: login_interstitials.to_vec(),
+            })?;
 
         let config = Config {
             preferred: security_options.preferred(),
@@ -95,48 +152,105 @@ impl SharedSshClient {
             ..Default::default()
         };
 
+        let credentials = credentials.unwrap_or_else(|| DeviceCredentials {
+            user: user.clone(),
+            password: Some(password.clone()),
+            enable_password: enable_password.clone(),
+            ..DeviceCredentials::default()
+        });
+        let login_enable_password = credentials
+            .enable_password
+            .clone()
+            .or_else(|| enable_password.clone());
+
         let client = Client::connect_with_config(
-            (addr, port),
-            &user,
-            AuthMethod::with_password(&password),
+            resolved,
+            &credentials.user,
+            credentials.auth_method(),
             security_options.server_check.clone(),
             config,
         )
-        .await?;
-        debug!("{} TCP connection successful", device_addr);
+        .await
+        .map_err(|err| {
+            classify_connect_error(
+                err,
+                &addr,
+                &credentials.user,
+                credentials.auth_method_name(),
+            )
+        })?;
+        let remote_addr = *client.get_connection_address();
+        debug!(
+            "{} TCP connection successful to {}",
+            device_addr, remote_addr
+        );
 
-        let mut channel = client.get_channel().await?;
+        let channel = client.get_channel().await?;
         channel
-            .request_pty(false, "xterm", 800, 600, 0, 0, &[])
+            .request_pty(false, "xterm", handler.terminal_width(), 600, 0, 0, &[])
             .await?;
         channel.request_shell(false).await?;
         debug!("{} Shell request successful", device_addr);
 
         let (sender_to_shell, mut receiver_from_user) = mpsc::channel::<String>(256);
-        let (sender_to_user, mut receiver_from_shell) = mpsc::channel::<String>(256);
+        let (sender_to_user, mut receiver_from_shell) = mpsc::channel::<Bytes>(256);
 
+        let (closed_tx, closed_rx) = oneshot::channel::<()>();
+        let status = Arc::new(status::ConnectionStatus::new(
+            device_addr.clone(),
+            recorder.clone(),
+        ));
+        status.set_remote_addr(remote_addr);
+        let io_task_status = status.clone();
         let io_task_device_addr = device_addr.clone();
+        let pacing = handler.pacing();
+        // Writes run in their own task so paced bursts never stop the shell
+        // output from being drained.
+        let (mut channel_reader, channel_writer) = channel.split();
+        let channel_writer = Arc::new(channel_writer);
+        let writer = channel_writer.clone();
+        let writer_status = status.clone();
+        let writer_device_addr = device_addr.clone();
+        let mut writer_task = tokio::spawn(async move {
+            while let Some(data) = receiver_from_user.recv().await {
+                writer_status.stats().record_sent(data.len());
+                if let Err(e) = write_paced(&writer, data.as_bytes(), pacing).await {
+                    debug!(
+                        "{} Failed to send data to shell: {:?}",
+                        writer_device_addr, e
+                    );
+                    return true;
+                }
+            }
+            false
+        });
         tokio::spawn(async move {
+            let mut writer_done = false;
             loop {
                 tokio::select! {
-                    Some(data) = receiver_from_user.recv() => {
-                        if let Err(e) = channel.data(data.as_bytes()).await {
-                            debug!("{} Failed to send data to shell: {:?}", io_task_device_addr, e);
+                    failed = &mut writer_task, if !writer_done => {
+                        writer_done = true;
+                        // A dropped client only stops input; output is still read to EOF.
+                        if failed.unwrap_or(true) {
                             break;
                         }
                     },
-                    Some(msg) = channel.wait() => {
+                    msg = channel_reader.wait() => {
+                        let Some(msg) = msg else {
+                            debug!("{} Shell channel closed.", io_task_device_addr);
+                            break;
+                        };
                         match msg {
                             ChannelMsg::Data { ref data } => {
-                                if let Ok(s) = std::str::from_utf8(data)
-                                    && sender_to_user.send(s.to_string()).await.is_err() {
-                                        debug!("{} Shell output receiver dropped. Closing task.", io_task_device_addr);
-                                        break;
-                                    }
+                                io_task_status.stats().record_received(data.len());
+                                if sender_to_user.send(Bytes::copy_from_slice(data)).await.is_err() {
+                                    debug!("{} Shell output receiver dropped. Closing task.", io_task_device_addr);
+                                    break;
+                                }
                             }
                             ChannelMsg::ExitStatus { exit_status } => {
                                 debug!("{} Shell exited with status code: {}", io_task_device_addr, exit_status);
-                                let _ = channel.eof().await;
+                                let _ = channel_writer.eof().await;
                                 break;
                             }
                             ChannelMsg::Eof => {
@@ -148,16 +262,26 @@ impl SharedSshClient {
                     }
                 }
             }
-            let _ = MANAGER.cache.invalidate(&io_task_device_addr).await;
+            writer_task.abort();
+            io_task_status.mark_disconnected();
+            // Drop this connection from the owning pool, unless the pool is
+            // gone or already holds a replacement under the same key.
+            if let Some(cache) = pool.upgrade()
+                && let Some(pooled) = cache.get(&io_task_device_addr).await
+                && Arc::ptr_eq(&pooled.status, &io_task_status)
+            {
+                cache.invalidate(&io_task_device_addr).await;
+            }
             debug!("{} SSH I/O task ended.", io_task_device_addr);
+            let _ = closed_tx.send(());
         });
 
-        let mut buffer = String::new();
+        let mut lines = lines::LineSplitter::for_handler(&handler);
         let mut prompt = String::new();
         let mut initial_output = String::new();
 
         let mut params = handler.dyn_param.clone();
-        if let Some(enable) = enable_password.as_ref() {
+        if let Some(enable) = login_enable_password.as_ref() {
             params.insert("EnablePassword".to_string(), format!("{}\n", enable));
         }
         handler.dyn_param = params;
@@ -166,29 +290,42 @@ impl SharedSshClient {
             loop {
                 if let Some(data) = receiver_from_shell.recv().await {
                     trace!("{:?}", data);
-                    buffer.push_str(&data);
-                    initial_output.push_str(&data);
+                    lines.push(&data);
+                    initial_output.push_str(&lines::decode_line(&data));
 
-                    while let Some(newline_pos) = buffer.find('\n') {
-                        let line = buffer.drain(..=newline_pos).collect::<String>();
-                        let trimmed_line = line.trim_end();
-                        handler.read(trimmed_line);
+                    while let Some(line) = lines.next_line() {
+                        handler.read(lines::decode_line(&line).trim_end());
                     }
 
-                    if !buffer.is_empty() {
-                        if handler.read_prompt(&buffer) {
-                            handler.read(&buffer);
+                    if !lines.is_empty() {
+                        let pending = lines.pending();
+                        if handler.read_prompt(&pending) {
+                            handler.read(&pending);
                             prompt.clear();
-                            prompt.push_str(handler.current_prompt().unwrap_or(&buffer));
+                            prompt.push_str(handler.current_prompt().unwrap_or(&pending));
                             return Ok(());
                         }
-                        if let Some((c, _)) = handler.read_need_write(&buffer) {
-                            handler.read(&buffer);
+                        if let Some((c, _)) = handler.read_need_write(&pending) {
+                            handler.read(&pending);
+                            sender_to_shell.send(c).await?;
+                        } else if let Some(err) = handler.take_password_rejection() {
+                            return Err(err);
+                        } else if let Some((c, _)) = interstitials.read_need_write(&pending) {
+                            debug!(
+                                "{} answering login interstitial: {:?}",
+                                device_addr, pending
+                            );
+                            // Drop the banner so it is not answered twice.
+                            lines.clear();
                             sender_to_shell.send(c).await?;
                         }
                     }
-                } else {
+                } else if initial_output.trim().is_empty() {
                     return Err(ConnectError::ChannelDisconnectError);
+                } else {
+                    return Err(ConnectError::SessionRejected(rejection_reason(
+                        &initial_output,
+                    )));
                 }
             }
         })
@@ -197,26 +334,38 @@ impl SharedSshClient {
         match init_result {
             Ok(Ok(())) => {}
             Ok(Err(err)) => return Err(err),
+            Err(_) if initial_output.trim().is_empty() => {
+                return Err(ConnectError::InitTimeout(
+                    "waiting for initial prompt".to_string(),
+                ));
+            }
             Err(_) => {
-                return Err(ConnectError::InitTimeout(if initial_output.is_empty() {
-                    "waiting for initial prompt".to_string()
-                } else {
-                    initial_output.clone()
-                }));
+                return Err(ConnectError::PromptNotMatched(Box::new(
+                    handler.diagnose_prompt_mismatch(
+                        None,
+                        &initial_output,
+                        PROMPT_DIAGNOSTIC_LINES,
+                    ),
+                )));
             }
         }
 
+        status.publish_state(handler.current_state(), &prompt);
+        status.record_sys(handler.current_sys());
         let password_hash = Self::calculate_password_hash(&password);
         let enable_password_hash = Self::calculate_enable_password_hash(&enable_password);
+        #[cfg(feature = "metrics")]
+        crate::metrics::connection_opened();
         if let Some(session_recorder) = recorder.as_ref() {
             let _ = session_recorder.record_event(SessionEvent::ConnectionEstablished {
                 device_addr: device_addr.clone(),
+                remote_addr: Some(remote_addr.to_string()),
                 prompt_after: prompt.clone(),
                 fsm_prompt_after: handler.current_state().to_string(),
             });
         }
 
-        Ok(Self {
+        let mut client = Self {
             client,
             sender: sender_to_shell,
             recv: receiver_from_shell,
@@ -225,12 +374,267 @@ impl SharedSshClient {
             password_hash,
             enable_password_hash,
             security_options,
-            recorder,
-        })
+            closed_signal: Some(closed_rx),
+            events: None,
+            status,
+            scope: JobScope::default(),
+        };
+        client.run_terminal_setup().await?;
+        Ok(client)
+    }
+
+    /// Run the template's terminal setup commands, e.g. `terminal width 512`.
+    ///
+    /// A rejected or timed-out command is only logged: the session still
+    /// works, long lines may just wrap. Only errors that leave the
+    /// connection unusable fail the connect.
+    async fn run_terminal_setup(&mut self) -> Result<(), ConnectError> {
+        for command in self.handler.terminal_setup().to_vec() {
+            match self
+                .write_with_timeout(&command, Duration::from_secs(10))
+                .await
+            {
+                Ok(output) if !output.success => warn!(
+                    "{} terminal setup command '{}' failed: {}",
+                    self.status.device_addr(),
+                    command,
+                    output.content
+                ),
+                Ok(_) => {}
+                Err(err) if err.is_fatal_for_connection() => return Err(err),
+                Err(err) => warn!(
+                    "{} terminal setup command '{}' failed: {}",
+                    self.status.device_addr(),
+                    command,
+                    err
+                ),
+            }
+        }
+        Ok(())
+    }
+
+    /// Time elapsed since the SSH session was established.
+    pub fn session_age(&self) -> Duration {
+        self.status.session_age()
+    }
+
+    /// Snapshot of this connection's traffic and command counters.
+    pub fn stats(&self) -> ConnectionStats {
+        self.status.stats().snapshot()
+    }
+
+    /// Recorders currently attached to this connection, combined into one.
+    pub fn recorder(&self) -> Option<SessionRecorder> {
+        self.status.recorder()
     }
 
     /// Checks if the underlying SSH connection is still active.
     pub fn is_connected(&self) -> bool {
-        !self.client.is_closed()
+        self.status.is_connected() && !self.client.is_closed()
+    }
+}
+
+#[cfg(feature = "metrics")]
+impl Drop for SharedSshClient {
+    fn drop(&mut self) {
+        crate::metrics::connection_dropped();
+    }
+}
+
+/// Writes `data` to the shell, in paced bursts when the template asks for it.
+async fn write_paced(
+    channel: &russh::ChannelWriteHalf<russh::client::Msg>,
+    data: &[u8],
+    pacing: Option<crate::device::KeystrokePacing>,
+) -> Result<(), russh::Error> {
+    let Some(pacing) = pacing else {
+        return channel.data(data).await;
+    };
+    for (burst, delay) in pacing.bursts(data) {
+        channel.data(burst).await?;
+        if !delay.is_zero() {
+            tokio::time::sleep(delay).await;
+        }
+    }
+    Ok(())
+}
+
+/// Maps opaque SSH library errors raised while connecting to structured variants.
+///
+/// TCP connects that were refused, timed out or found no route become
+/// [`ConnectError::Unreachable`] for `host`: nothing reached the device.
+pub(in crate::session) fn classify_connect_error(
+    err: async_ssh2_tokio::Error,
+    host: &str,
+    user: &str,
+    method: &str,
+) -> ConnectError {
+    use async_ssh2_tokio::Error as SshError;
+    use std::io::ErrorKind;
+
+    let unreachable = |reason: String| ConnectError::Unreachable {
+        addr: bare_host(host).to_string(),
+        reason,
+    };
+    let authentication_failed = || ConnectError::AuthenticationFailed {
+        user: user.to_string(),
+        method: method.to_string(),
+    };
+
+    match err {
+        SshError::PasswordWrong
+        | SshError::KeyAuthFailed
+        | SshError::KeyboardInteractiveAuthFailed
+        | SshError::AgentAuthenticationFailed => authentication_failed(),
+        SshError::ServerCheckFailed => {
+            ConnectError::HostKeyRejected("server host key check failed".to_string())
+        }
+        SshError::AddressInvalid(err) => unreachable(err.to_string()),
+        SshError::SshError(err) => match err {
+            russh::Error::IO(err)
+                if matches!(
+                    err.kind(),
+                    ErrorKind::ConnectionRefused
+                        | ErrorKind::HostUnreachable
+                        | ErrorKind::NetworkUnreachable
+                        | ErrorKind::AddrNotAvailable
+                        | ErrorKind::TimedOut
+                ) =>
+            {
+                unreachable(err.to_string())
+            }
+            russh::Error::ConnectionTimeout => unreachable(err.to_string()),
+            russh::Error::NoCommonAlgo { kind, ours, theirs } => {
+                ConnectError::NoCompatibleAlgorithms {
+                    kind: format!("{kind:?}").to_ascii_lowercase(),
+                    offered: ours,
+                    required: theirs,
+                }
+            }
+            russh::Error::UnknownKey
+            | russh::Error::WrongServerSig
+            | russh::Error::KeyChanged { .. } => ConnectError::HostKeyRejected(err.to_string()),
+            russh::Error::UnsupportedAuthMethod | russh::Error::NoAuthMethod => {
+                ConnectError::AuthMethodNotSupported(method.to_string())
+            }
+            russh::Error::NotAuthenticated => authentication_failed(),
+            other => ConnectError::Ssh2Error(SshError::SshError(other)),
+        },
+        other => ConnectError::Ssh2Error(other),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classify_connect_error_maps_auth_failures() {
+        let err = classify_connect_error(
+            async_ssh2_tokio::Error::PasswordWrong,
+            "r1",
+            "admin",
+            "password",
+        );
+        assert!(matches!(
+            err,
+            ConnectError::AuthenticationFailed { ref user, ref method }
+                if user == "admin" && method == "password"
+        ));
+    }
+
+    #[test]
+    fn classify_connect_error_maps_host_key_and_method_errors() {
+        assert!(matches!(
+            classify_connect_error(
+                async_ssh2_tokio::Error::ServerCheckFailed,
+                "r1",
+                "admin",
+                "password"
+            ),
+            ConnectError::HostKeyRejected(_)
+        ));
+        assert!(matches!(
+            classify_connect_error(
+                async_ssh2_tokio::Error::SshError(russh::Error::KeyChanged { line: 3 }),
+                "r1",
+                "admin",
+                "password"
+            ),
+            ConnectError::HostKeyRejected(_)
+        ));
+        assert!(matches!(
+            classify_connect_error(
+                async_ssh2_tokio::Error::SshError(russh::Error::NoAuthMethod),
+                "r1",
+                "admin",
+                "publickey"
+            ),
+            ConnectError::AuthMethodNotSupported(ref method) if method == "publickey"
+        ));
+    }
+
+    #[test]
+    fn classify_connect_error_reports_algorithm_lists() {
+        let err = classify_connect_error(
+            async_ssh2_tokio::Error::SshError(russh::Error::NoCommonAlgo {
+                kind: russh::AlgorithmKind::Cipher,
+                ours: vec!["aes256-gcm@openssh.com".to_string()],
+                theirs: vec!["aes128-cbc".to_string()],
+            }),
+            "r1",
+            "admin",
+            "password",
+        );
+
+        match err {
+            ConnectError::NoCompatibleAlgorithms {
+                kind,
+                offered,
+                required,
+            } => {
+                assert_eq!(kind, "cipher");
+                assert_eq!(offered, ["aes256-gcm@openssh.com"]);
+                assert_eq!(required, ["aes128-cbc"]);
+            }
+            other => panic!("unexpected error: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn classify_connect_error_maps_failed_tcp_connects_to_unreachable() {
+        let refused = std::io::Error::from(std::io::ErrorKind::ConnectionRefused);
+        assert!(matches!(
+            classify_connect_error(
+                async_ssh2_tokio::Error::SshError(russh::Error::IO(refused)),
+                "[2001:db8::1]",
+                "admin",
+                "password"
+            ),
+            ConnectError::Unreachable { ref addr, .. } if addr == "2001:db8::1"
+        ));
+        let reset = std::io::Error::from(std::io::ErrorKind::ConnectionReset);
+        assert!(matches!(
+            classify_connect_error(
+                async_ssh2_tokio::Error::SshError(russh::Error::IO(reset)),
+                "r1",
+                "admin",
+                "password"
+            ),
+            ConnectError::Ssh2Error(_)
+        ));
+    }
+
+    #[test]
+    fn classify_connect_error_keeps_other_errors_opaque() {
+        assert!(matches!(
+            classify_connect_error(
+                async_ssh2_tokio::Error::SshError(russh::Error::HUP),
+                "r1",
+                "admin",
+                "password"
+            ),
+            ConnectError::Ssh2Error(_)
+        ));
     }
 }
@@ -2,3 +2,6 @@ mod command;
 mod connection;
 mod transfer;
 mod tx;
+
+pub(crate) use command::validate_prompt_rules;
+pub(in crate::session) use connection::classify_connect_error;
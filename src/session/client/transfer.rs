@@ -1,53 +1,310 @@
 use super::super::*;
 
+use russh_sftp::client::SftpSession;
+use russh_sftp::protocol::OpenFlags;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// Default SFTP transfer buffer, matching the upstream upload helper.
+const DEFAULT_TRANSFER_BUFFER: usize = 4096;
+
+fn hex_digest(hasher: Sha256) -> String {
+    hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+fn transfer_io_error(path: &str, err: std::io::Error) -> ConnectError {
+    ConnectError::InternalServerError(format!("transfer of {path} failed: {err}"))
+}
+
+/// Copy `reader` into `writer`, hashing the content and reporting progress.
+///
+/// Returns the byte count and the lowercase hex SHA-256.
+pub(crate) async fn copy_with_progress<R, W>(
+    reader: &mut R,
+    writer: &mut W,
+    remote_path: &str,
+    total: Option<u64>,
+    buffer_size: usize,
+    progress: Option<&TransferProgressFn>,
+    log_progress: bool,
+) -> std::io::Result<(u64, String)>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let mut buffer = vec![0u8; buffer_size.max(1)];
+    let mut hasher = Sha256::new();
+    let mut transferred = 0u64;
+    let mut next_percent = 5;
+    loop {
+        let n = reader.read(&mut buffer).await?;
+        if n == 0 {
+            break;
+        }
+        writer.write_all(&buffer[..n]).await?;
+        hasher.update(&buffer[..n]);
+        transferred += n as u64;
+
+        if let Some(progress) = progress {
+            progress(&TransferProgress {
+                remote_path: remote_path.to_string(),
+                transferred,
+                total,
+            });
+        }
+        if log_progress && let Some(total) = total.filter(|total| *total > 0) {
+            let percent = transferred * 100 / total;
+            if percent >= next_percent {
+                log::info!("transfer of {remote_path}: {percent}% ({transferred}/{total} bytes)");
+                next_percent = percent - percent % 5 + 5;
+            }
+        }
+    }
+    writer.flush().await?;
+    Ok((transferred, hex_digest(hasher)))
+}
+
+/// Compare a computed hex digest with an expected one, ignoring case.
+pub(crate) fn check_checksum(path: &str, expected: &str, actual: &str) -> Result<(), ConnectError> {
+    if expected.trim().eq_ignore_ascii_case(actual) {
+        Ok(())
+    } else {
+        Err(ConnectError::ChecksumMismatch {
+            path: path.to_string(),
+            expected: expected.trim().to_ascii_lowercase(),
+            actual: actual.to_string(),
+        })
+    }
+}
+
 impl SharedSshClient {
+    /// Opens a new channel running the SSH `subsystem` (e.g. `netconf`).
+    ///
+    /// The channel is independent of the interactive shell.
+    pub async fn open_subsystem(
+        &self,
+        subsystem: &str,
+    ) -> Result<russh::ChannelStream<russh::client::Msg>, ConnectError> {
+        let channel = self.client.get_channel().await?;
+        channel.request_subsystem(true, subsystem).await?;
+        Ok(channel.into_stream())
+    }
+
+    async fn sftp_session(&self, timeout_secs: Option<u64>) -> Result<SftpSession, ConnectError> {
+        let stream = self.open_subsystem("sftp").await?;
+        Ok(SftpSession::new_opts(stream, timeout_secs).await?)
+    }
+
+    fn record_transfer(&self, event: SessionEvent) {
+        if let Some(recorder) = self.recorder() {
+            let _ = recorder.record_event(event);
+        }
+    }
+
     /// Uploads a local file to the remote host using the SSH `sftp` subsystem.
-    pub async fn upload_file(&mut self, upload: &FileUploadRequest) -> Result<(), ConnectError> {
+    pub async fn upload_file(
+        &mut self,
+        upload: &FileUploadRequest,
+        progress: Option<&TransferProgressFn>,
+    ) -> Result<FileTransferReport, ConnectError> {
         let local_path = upload.local_path.clone();
         let remote_path = upload.remote_path.clone();
+        self.record_transfer(SessionEvent::FileUploadStarted {
+            local_path: local_path.clone(),
+            remote_path: remote_path.clone(),
+        });
 
-        if let Some(recorder) = self.recorder.as_ref() {
-            let _ = recorder.record_event(SessionEvent::FileUploadStarted {
-                local_path: local_path.clone(),
-                remote_path: remote_path.clone(),
-            });
-        }
+        let result = self.upload_file_inner(upload, progress).await;
+        self.record_transfer(SessionEvent::FileUploadFinished {
+            local_path,
+            remote_path,
+            success: result.is_ok(),
+            error: result.as_ref().err().map(ToString::to_string),
+        });
+        result
+    }
+
+    async fn upload_file_inner(
+        &self,
+        upload: &FileUploadRequest,
+        progress: Option<&TransferProgressFn>,
+    ) -> Result<FileTransferReport, ConnectError> {
+        let remote_path = upload.remote_path.as_str();
+        let sftp = self.sftp_session(upload.timeout_secs).await?;
 
-        let result = self
-            .client
-            .upload_file(
-                local_path.as_str(),
-                remote_path.clone(),
-                upload.timeout_secs,
-                upload.buffer_size,
-                upload.show_progress,
+        let mut local_file = tokio::fs::File::open(&upload.local_path)
+            .await
+            .map_err(|e| transfer_io_error(&upload.local_path, e))?;
+        let total = local_file
+            .metadata()
+            .await
+            .map_err(|e| transfer_io_error(&upload.local_path, e))?
+            .len();
+        let mut remote_file = sftp
+            .open_with_flags(
+                remote_path,
+                OpenFlags::CREATE | OpenFlags::TRUNCATE | OpenFlags::WRITE,
             )
-            .await;
-
-        match result {
-            Ok(()) => {
-                if let Some(recorder) = self.recorder.as_ref() {
-                    let _ = recorder.record_event(SessionEvent::FileUploadFinished {
-                        local_path,
-                        remote_path,
-                        success: true,
-                        error: None,
-                    });
-                }
-                Ok(())
-            }
+            .await?;
+
+        let (bytes, sha256) = copy_with_progress(
+            &mut local_file,
+            &mut remote_file,
+            remote_path,
+            Some(total),
+            upload.buffer_size.unwrap_or(DEFAULT_TRANSFER_BUFFER),
+            progress,
+            upload.show_progress,
+        )
+        .await
+        .map_err(|e| transfer_io_error(remote_path, e))?;
+        remote_file
+            .shutdown()
+            .await
+            .map_err(|e| transfer_io_error(remote_path, e))?;
+
+        if upload.verify_checksum {
+            let mut remote_file = sftp.open_with_flags(remote_path, OpenFlags::READ).await?;
+            let (_, remote_sha256) = copy_with_progress(
+                &mut remote_file,
+                &mut tokio::io::sink(),
+                remote_path,
+                Some(total),
+                upload.buffer_size.unwrap_or(DEFAULT_TRANSFER_BUFFER),
+                None,
+                false,
+            )
+            .await
+            .map_err(|e| transfer_io_error(remote_path, e))?;
+            check_checksum(remote_path, &sha256, &remote_sha256)?;
+        }
+        let _ = sftp.close().await;
+
+        Ok(FileTransferReport {
+            bytes,
+            sha256,
+            verified: upload.verify_checksum,
+        })
+    }
+
+    /// Downloads a remote file to the local machine using the SSH `sftp` subsystem.
+    pub async fn download_file(
+        &mut self,
+        download: &FileDownloadRequest,
+        progress: Option<&TransferProgressFn>,
+    ) -> Result<FileTransferReport, ConnectError> {
+        let local_path = download.local_path.clone();
+        let remote_path = download.remote_path.clone();
+        self.record_transfer(SessionEvent::FileDownloadStarted {
+            remote_path: remote_path.clone(),
+            local_path: local_path.clone(),
+        });
+
+        let result = self.download_file_inner(download, progress).await;
+        self.record_transfer(SessionEvent::FileDownloadFinished {
+            remote_path,
+            local_path,
+            success: result.is_ok(),
+            error: result.as_ref().err().map(ToString::to_string),
+        });
+        result
+    }
+
+    async fn download_file_inner(
+        &self,
+        download: &FileDownloadRequest,
+        progress: Option<&TransferProgressFn>,
+    ) -> Result<FileTransferReport, ConnectError> {
+        let remote_path = download.remote_path.as_str();
+        let sftp = self.sftp_session(download.timeout_secs).await?;
+
+        let total = sftp.metadata(remote_path).await?.size;
+        let mut remote_file = sftp.open_with_flags(remote_path, OpenFlags::READ).await?;
+        let mut local_file = tokio::fs::File::create(&download.local_path)
+            .await
+            .map_err(|e| transfer_io_error(&download.local_path, e))?;
+
+        let copied = copy_with_progress(
+            &mut remote_file,
+            &mut local_file,
+            remote_path,
+            total,
+            download.buffer_size.unwrap_or(DEFAULT_TRANSFER_BUFFER),
+            progress,
+            false,
+        )
+        .await;
+        let _ = sftp.close().await;
+        // A partial file must not be mistaken for a finished download.
+        let (bytes, sha256) = match copied {
+            Ok(copied) => copied,
             Err(err) => {
-                let reason = err.to_string();
-                if let Some(recorder) = self.recorder.as_ref() {
-                    let _ = recorder.record_event(SessionEvent::FileUploadFinished {
-                        local_path,
-                        remote_path,
-                        success: false,
-                        error: Some(reason),
-                    });
-                }
-                Err(err.into())
+                drop(local_file);
+                let _ = tokio::fs::remove_file(&download.local_path).await;
+                return Err(transfer_io_error(remote_path, err));
             }
+        };
+
+        if let Some(expected) = &download.expected_sha256
+            && let Err(err) = check_checksum(remote_path, expected, &sha256)
+        {
+            drop(local_file);
+            let _ = tokio::fs::remove_file(&download.local_path).await;
+            return Err(err);
         }
+
+        Ok(FileTransferReport {
+            bytes,
+            sha256,
+            verified: download.expected_sha256.is_some(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[tokio::test]
+    async fn copy_with_progress_hashes_and_reports_each_buffer() {
+        let content = b"hostname edge-1\ninterface Gi0/1\n".repeat(10);
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let progress: TransferProgressFn = {
+            let seen = seen.clone();
+            Arc::new(move |p: &TransferProgress| seen.lock().unwrap().push(p.transferred))
+        };
+
+        let mut copied = Vec::new();
+        let (bytes, sha256) = copy_with_progress(
+            &mut content.as_slice(),
+            &mut copied,
+            "/flash/edge-1.cfg",
+            Some(content.len() as u64),
+            128,
+            Some(&progress),
+            false,
+        )
+        .await
+        .expect("copy");
+
+        assert_eq!(copied, content);
+        assert_eq!(bytes, content.len() as u64);
+        assert_eq!(sha256, hex_digest(Sha256::new_with_prefix(&content)));
+        assert_eq!(*seen.lock().unwrap(), vec![128, 256, 320]);
+    }
+
+    #[test]
+    fn checksum_comparison_ignores_case_and_reports_mismatch() {
+        let digest = hex_digest(Sha256::new_with_prefix(b"image"));
+        assert!(check_checksum("/flash/image.bin", &digest.to_uppercase(), &digest).is_ok());
+
+        let err = check_checksum("/flash/image.bin", "00", &digest).expect_err("mismatch");
+        assert!(
+            matches!(err, ConnectError::ChecksumMismatch { ref path, .. } if path == "/flash/image.bin")
+        );
     }
 }
@@ -45,7 +45,7 @@ impl From<ConnectError> for OperationRunError {
 }
 
 pub(super) trait TxCommandRunner {
-    fn recorder(&self) -> Option<&SessionRecorder>;
+    fn recorder(&self) -> Option<SessionRecorder>;
 
     fn run_operation<'a>(
         &'a mut self,
@@ -85,7 +85,8 @@ fn annotate_step_results_for_rollback(
     rollback_failed_step: Option<usize>,
 ) -> Result<(), ConnectError> {
     match &block.rollback_policy {
-        RollbackPolicy::None => {}
+        RollbackPolicy::None | RollbackPolicy::ConfigDiff { .. } | RollbackPolicy::Candidate(_) => {
+        }
         RollbackPolicy::WholeResource { rollback, .. } => {
             let (_, rollback_operation_summary) = rollback.display_summary()?;
             for idx in attempted_step_indices(executed_indices, failed_step_indices) {
@@ -143,6 +144,123 @@ fn annotate_step_results_for_rollback(
     Ok(())
 }
 
+/// Output of a [`RollbackPolicy::ConfigDiff`] snapshot command.
+async fn capture_config<R: TxCommandRunner + ?Sized>(
+    runner: &mut R,
+    snapshot: &Command,
+    sys: Option<&String>,
+) -> Result<String, ConnectError> {
+    let operation = SessionOperation::Command(snapshot.clone());
+    let output = runner
+        .run_operation(&operation, sys)
+        .await
+        .map_err(|err| err.into_parts().0)?;
+    if !output.success {
+        return Err(ConnectError::InvalidTransaction(format!(
+            "config snapshot '{}' failed: {}",
+            snapshot.command,
+            operation_failure_output(&output)
+        )));
+    }
+    Ok(output
+        .steps
+        .iter()
+        .map(|step| step.content.as_str())
+        .collect::<Vec<_>>()
+        .join("\n"))
+}
+
+/// Validate and commit the candidate loaded by a block's steps; the error
+/// is the failure reason.
+async fn commit_candidate<R: TxCommandRunner + ?Sized>(
+    runner: &mut R,
+    candidate: &crate::templates::CandidateConfig,
+    sys: Option<&String>,
+) -> Result<(), String> {
+    let commands = candidate
+        .validate_command()
+        .into_iter()
+        .chain(std::iter::once(candidate.commit_command()));
+    for command in commands {
+        let operation = SessionOperation::Command(command);
+        let summary = operation
+            .display_summary()
+            .map(|(_, summary)| summary)
+            .map_err(|err| err.to_string())?;
+        let output = runner
+            .run_operation(&operation, sys)
+            .await
+            .map_err(|err| format!("candidate '{summary}' error: {}", err.into_parts().0))?;
+        let content = output
+            .steps
+            .iter()
+            .map(|step| step.content.as_str())
+            .collect::<Vec<_>>()
+            .join("\n");
+        if let Some(failure) = candidate.check_output(output.success, &content) {
+            return Err(format!("candidate '{summary}' failed: output='{failure}'"));
+        }
+    }
+    Ok(())
+}
+
+/// Rollback plan for a failed or committed block, plus why it is empty.
+///
+/// `ConfigDiff` blocks snapshot the config again and restore `config_snapshot`.
+/// `Candidate` blocks abort the candidate, or revert once `committed`.
+async fn plan_block_rollback<R: TxCommandRunner + ?Sized>(
+    runner: &mut R,
+    block: &TxBlock,
+    config_snapshot: Option<&str>,
+    executed_indices: &[usize],
+    failed_step: Option<usize>,
+    committed: bool,
+    sys: Option<&String>,
+) -> Result<(Vec<transaction::PlannedRollback>, Vec<String>), ConnectError> {
+    let plan = match (&block.rollback_policy, config_snapshot) {
+        (RollbackPolicy::Candidate(candidate), _) => {
+            let operation = if committed {
+                candidate.revert_operation()
+            } else {
+                Some(candidate.abort_command().into())
+            };
+            operation
+                .map(|operation| transaction::PlannedRollback {
+                    step_index: None,
+                    operation,
+                })
+                .into_iter()
+                .collect()
+        }
+        (RollbackPolicy::ConfigDiff { snapshot, grammar }, Some(before)) => {
+            let after = match capture_config(runner, snapshot, sys).await {
+                Ok(after) => after,
+                Err(err) => {
+                    return Ok((
+                        Vec::new(),
+                        vec![format!("config_diff rollback skipped: {err}")],
+                    ));
+                }
+            };
+            grammar
+                .restore_operation(&after, before, snapshot.timeout)
+                .map(|operation| transaction::PlannedRollback {
+                    step_index: None,
+                    operation,
+                })
+                .into_iter()
+                .collect()
+        }
+        _ => block.plan_rollback(executed_indices, failed_step)?,
+    };
+    let reasons = if plan.is_empty() {
+        block.explain_missing_rollback_plan(executed_indices, failed_step)
+    } else {
+        Vec::new()
+    };
+    Ok((plan, reasons))
+}
+
 fn apply_step_rollback_outcome(
     step_results: &mut [TxStepResult],
     step_index: usize,
@@ -218,7 +336,17 @@ pub(super) async fn rollback_committed_block_with_runner<R: TxCommandRunner + ?S
     let executed = (0..block.steps.len()).collect::<Vec<_>>();
     let failed = Vec::new();
     annotate_step_results_for_rollback(block, &mut result.step_results, &executed, &failed, None)?;
-    let plan = block.plan_rollback(&executed, None)?;
+    let config_snapshot = result.config_snapshot.clone();
+    let (plan, missing_reasons) = plan_block_rollback(
+        runner,
+        block,
+        config_snapshot.as_deref(),
+        &executed,
+        None,
+        true,
+        sys,
+    )
+    .await?;
     result.rollback_attempted = !plan.is_empty();
     result.rollback_succeeded = result.rollback_attempted;
     result.rollback_steps = 0;
@@ -228,9 +356,7 @@ pub(super) async fn rollback_committed_block_with_runner<R: TxCommandRunner + ?S
     let block_level_indices = attempted_step_indices(&executed, &failed);
     if !result.rollback_attempted {
         result.rollback_succeeded = false;
-        result
-            .rollback_errors
-            .extend(block.explain_missing_rollback_plan(&executed, None));
+        result.rollback_errors.extend(missing_reasons);
         if let RollbackPolicy::WholeResource { rollback, .. } = &block.rollback_policy {
             let (_, rollback_operation_summary) = rollback.display_summary()?;
             result.block_rollback_operation_summary = Some(rollback_operation_summary.clone());
@@ -244,6 +370,8 @@ pub(super) async fn rollback_committed_block_with_runner<R: TxCommandRunner + ?S
         }
         return Ok(());
     }
+    #[cfg(feature = "metrics")]
+    crate::metrics::rollback_triggered();
     if let Some(recorder) = runner.recorder() {
         let _ = recorder.record_event(SessionEvent::TxRollbackStarted {
             block_name: block.name.clone(),
@@ -387,6 +515,12 @@ pub(super) async fn execute_tx_block_with_runner<R: TxCommandRunner + ?Sized>(
             block_kind: block.kind,
         });
     }
+    let config_snapshot = match &block.rollback_policy {
+        RollbackPolicy::ConfigDiff { snapshot, .. } => {
+            Some(capture_config(runner, snapshot, sys).await?)
+        }
+        _ => None,
+    };
 
     let mut executed_indices = Vec::new();
     let mut failed_step_indices = Vec::new();
@@ -479,9 +613,17 @@ pub(super) async fn execute_tx_block_with_runner<R: TxCommandRunner + ?Sized>(
         }
     }
 
-    if failed_step.is_none() {
-        let result = TxResult::committed(block.name.clone(), executed_indices.len())
+    if failed_step.is_none()
+        && let RollbackPolicy::Candidate(candidate) = &block.rollback_policy
+        && let Err(reason) = commit_candidate(runner, candidate, sys).await
+    {
+        failure_reason = Some(reason);
+    }
+
+    if failure_reason.is_none() {
+        let mut result = TxResult::committed(block.name.clone(), executed_indices.len())
             .with_step_results(step_results);
+        result.config_snapshot = config_snapshot;
         if let Some(recorder) = runner.recorder() {
             let _ = recorder.record_event(SessionEvent::TxBlockFinished {
                 block_name: block.name.clone(),
@@ -507,6 +649,7 @@ pub(super) async fn execute_tx_block_with_runner<R: TxCommandRunner + ?Sized>(
             block_rollback_operation_summary: None,
             block_rollback_steps: Vec::new(),
             step_results,
+            config_snapshot: None,
         };
         if let Some(recorder) = runner.recorder() {
             let _ = recorder.record_event(SessionEvent::TxBlockFinished {
@@ -526,8 +669,21 @@ pub(super) async fn execute_tx_block_with_runner<R: TxCommandRunner + ?Sized>(
         &failed_step_indices,
         rollback_failed_step,
     )?;
-    let rollback_plan = block.plan_rollback(&executed_indices, rollback_failed_step)?;
+    let (rollback_plan, missing_rollback_reasons) = plan_block_rollback(
+        runner,
+        block,
+        config_snapshot.as_deref(),
+        &executed_indices,
+        rollback_failed_step,
+        false,
+        sys,
+    )
+    .await?;
     let rollback_attempted = !rollback_plan.is_empty();
+    #[cfg(feature = "metrics")]
+    if rollback_attempted {
+        crate::metrics::rollback_triggered();
+    }
     if rollback_attempted && let Some(recorder) = runner.recorder() {
         let _ = recorder.record_event(SessionEvent::TxRollbackStarted {
             block_name: block.name.clone(),
@@ -538,8 +694,6 @@ pub(super) async fn execute_tx_block_with_runner<R: TxCommandRunner + ?Sized>(
     let mut rollback_steps = 0;
     let mut block_rollback_operation_summary = None;
     let mut block_rollback_steps = Vec::new();
-    let missing_rollback_reasons =
-        block.explain_missing_rollback_plan(&executed_indices, rollback_failed_step);
     let block_level_indices = attempted_step_indices(&executed_indices, &failed_step_indices);
     if !rollback_attempted {
         rollback_errors.extend(missing_rollback_reasons.clone());
@@ -694,6 +848,7 @@ pub(super) async fn execute_tx_block_with_runner<R: TxCommandRunner + ?Sized>(
         block_rollback_operation_summary,
         block_rollback_steps,
         step_results,
+        config_snapshot,
     };
 
     if let Some(recorder) = runner.recorder() {
@@ -797,6 +952,36 @@ pub(super) async fn execute_tx_workflow_with_runner<R: TxCommandRunner + ?Sized>
     })
 }
 
+/// Roll back every committed block of an already committed workflow, last first.
+pub(super) async fn rollback_tx_workflow_with_runner<R: TxCommandRunner + ?Sized>(
+    runner: &mut R,
+    workflow: &TxWorkflow,
+    result: &mut TxWorkflowResult,
+    sys: Option<&String>,
+) -> Result<(), ConnectError> {
+    result.rollback_attempted = false;
+    result.rollback_succeeded = true;
+    result.rollback_errors.clear();
+    for idx in (0..workflow.blocks.len()).rev() {
+        if let (Some(block), Some(block_result)) =
+            (workflow.blocks.get(idx), result.block_results.get_mut(idx))
+            && block_result.committed
+        {
+            rollback_committed_block_with_runner(runner, block, sys, block_result).await?;
+            result.rollback_attempted |= block_result.rollback_attempted;
+            if !block_result.rollback_succeeded {
+                result.rollback_succeeded = false;
+            }
+            result
+                .rollback_errors
+                .extend(block_result.rollback_errors.clone());
+        }
+    }
+    result.committed = false;
+    result.rollback_succeeded &= result.rollback_attempted;
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -828,8 +1013,8 @@ mod tests {
     }
 
     impl TxCommandRunner for FakeRunner {
-        fn recorder(&self) -> Option<&SessionRecorder> {
-            self.recorder.as_ref()
+        fn recorder(&self) -> Option<SessionRecorder> {
+            self.recorder.clone()
         }
 
         fn run_operation<'a>(
@@ -856,8 +1041,12 @@ mod tests {
             success: true,
             exit_code: None,
             content: content.to_string(),
-            all: content.to_string(),
+            segments: vec![content.to_string()],
             prompt: None,
+            spilled: None,
+            device_addr: None,
+            sys: None,
+            async_messages: Vec::new(),
         }
     }
 
@@ -866,8 +1055,12 @@ mod tests {
             success: false,
             exit_code: None,
             content: content.to_string(),
-            all: content.to_string(),
+            segments: vec![content.to_string()],
             prompt: None,
+            spilled: None,
+            device_addr: None,
+            sys: None,
+            async_messages: Vec::new(),
         }
     }
 
@@ -884,8 +1077,9 @@ mod tests {
             success: output.success,
             exit_code: output.exit_code,
             content: output.content,
-            all: output.all,
+            all: join_segments(output.segments),
             prompt: output.prompt,
+            spilled: output.spilled,
         }
     }
 
@@ -920,32 +1114,172 @@ mod tests {
             kind: CommandBlockKind::Config,
             rollback_policy: RollbackPolicy::PerStep,
             steps: vec![
-                TxStep::new(Command {
-                    mode: "Config".to_string(),
-                    command: "set addr 1".to_string(),
-                    ..Command::default()
-                })
-                .with_rollback(Command {
-                    mode: "Config".to_string(),
-                    command: "unset addr 1".to_string(),
-                    ..Command::default()
-                }),
-                TxStep::new(Command {
-                    mode: "Config".to_string(),
-                    command: "set addr 2".to_string(),
-                    ..Command::default()
-                })
-                .with_rollback(Command {
-                    mode: "Config".to_string(),
-                    command: "unset addr 2".to_string(),
-                    ..Command::default()
-                })
-                .with_rollback_on_failure(rollback_on_failure),
+                TxStep::new(Command::config("set addr 1"))
+                    .with_rollback(Command::config("unset addr 1")),
+                TxStep::new(Command::config("set addr 2"))
+                    .with_rollback(Command::config("unset addr 2"))
+                    .with_rollback_on_failure(rollback_on_failure),
             ],
             fail_fast: true,
         }
     }
 
+    #[tokio::test]
+    async fn candidate_block_commits_or_aborts_the_candidate() {
+        let block = TxBlock {
+            name: "policy".to_string(),
+            kind: CommandBlockKind::Config,
+            rollback_policy: RollbackPolicy::Candidate(
+                crate::templates::CandidateConfig::for_template("juniper").expect("candidate"),
+            ),
+            steps: vec![
+                TxStep::new(Command::config("set system host-name edge1")),
+                TxStep::new(Command::config("set interfaces ge-0/0/1 disable")),
+            ],
+            fail_fast: true,
+        };
+        let scripted = |command: &str, output: Output| ScriptedOperation {
+            command: command.to_string(),
+            mode: "Config".to_string(),
+            result: Ok(single_output(command, "Config", output)),
+        };
+        let mut runner = FakeRunner::new(vec![
+            scripted("set system host-name edge1", ok_output("")),
+            scripted("set interfaces ge-0/0/1 disable", ok_output("")),
+            scripted("commit check", ok_output("configuration check succeeds")),
+            scripted("commit", ok_output("commit complete")),
+        ]);
+        let result = execute_tx_block_with_runner(&mut runner, &block, None)
+            .await
+            .expect("execute block");
+        assert!(result.committed);
+        assert!(runner.scripted.is_empty());
+
+        // Validation fails after every step loaded: abort instead of commit.
+        let mut runner = FakeRunner::new(vec![
+            scripted("set system host-name edge1", ok_output("")),
+            scripted("set interfaces ge-0/0/1 disable", ok_output("")),
+            scripted(
+                "commit check",
+                ok_output("error: interface ge-0/0/1 in use\nerror: commit check failed"),
+            ),
+            scripted("rollback 0", ok_output("load complete")),
+        ]);
+        let result = execute_tx_block_with_runner(&mut runner, &block, None)
+            .await
+            .expect("execute block");
+        assert!(!result.committed);
+        assert_eq!(result.failed_step, None);
+        assert!(
+            result
+                .failure_reason
+                .as_deref()
+                .is_some_and(|reason| reason.contains("commit check failed")),
+            "{:?}",
+            result.failure_reason
+        );
+        assert!(result.rollback_attempted);
+        assert!(result.rollback_succeeded);
+        assert_eq!(
+            result.block_rollback_operation_summary.as_deref(),
+            Some("rollback 0")
+        );
+        assert_eq!(
+            result.step_results[1].rollback_state,
+            TxStepRollbackState::BlockSucceeded
+        );
+        assert!(runner.scripted.is_empty());
+
+        // A rejected step aborts without validating.
+        let mut runner = FakeRunner::new(vec![
+            scripted("set system host-name edge1", failed_output("syntax error")),
+            scripted("rollback 0", ok_output("load complete")),
+        ]);
+        let result = execute_tx_block_with_runner(&mut runner, &block, None)
+            .await
+            .expect("execute block");
+        assert_eq!(result.failed_step, Some(0));
+        assert!(result.rollback_succeeded);
+        assert!(runner.scripted.is_empty());
+    }
+
+    #[tokio::test]
+    async fn config_diff_block_restores_the_snapshot_taken_before_it() {
+        let before = "interface Gi1\n description old\n";
+        let after = "interface Gi1\n description new\n shutdown\n";
+        let snapshot = "show running-config interface Gi1";
+        let block = TxBlock {
+            name: "uplink".to_string(),
+            kind: CommandBlockKind::Config,
+            rollback_policy: RollbackPolicy::ConfigDiff {
+                snapshot: Box::new(Command::new("Enable", snapshot)),
+                grammar: crate::templates::ConfigGrammar::for_template("cisco").expect("grammar"),
+            },
+            steps: vec![
+                TxStep::new(Command::config("description new")),
+                TxStep::new(Command::config("shutdown")),
+                TxStep::new(Command::config("speed 100")),
+            ],
+            fail_fast: true,
+        };
+        let scripted = |command: &str, mode: &str, output: Output| ScriptedOperation {
+            command: command.to_string(),
+            mode: mode.to_string(),
+            result: Ok(single_output(command, mode, output)),
+        };
+        let mut runner = FakeRunner::new(vec![
+            scripted(snapshot, "Enable", ok_output(before)),
+            scripted("description new", "Config", ok_output("")),
+            scripted("shutdown", "Config", ok_output("")),
+            scripted("speed 100", "Config", failed_output("% Invalid input")),
+            scripted(snapshot, "Enable", ok_output(after)),
+            ScriptedOperation {
+                command: "<flow:9 steps>".to_string(),
+                mode: "Config".to_string(),
+                result: Ok(flow_output(vec![step_output(
+                    0,
+                    "Config",
+                    "interface Gi1",
+                    ok_output(""),
+                )])),
+            },
+        ]);
+
+        let result = execute_tx_block_with_runner(&mut runner, &block, None)
+            .await
+            .expect("execute block");
+
+        assert_eq!(result.failed_step, Some(2));
+        assert_eq!(result.config_snapshot.as_deref(), Some(before));
+        assert!(result.rollback_attempted);
+        assert!(result.rollback_succeeded);
+        assert_eq!(
+            result.block_rollback_operation_summary.as_deref(),
+            Some("<flow:9 steps>")
+        );
+        assert_eq!(
+            result.step_results[0].rollback_state,
+            TxStepRollbackState::BlockSucceeded
+        );
+        assert!(runner.scripted.is_empty());
+
+        // Unchanged config: nothing to undo.
+        let mut runner = FakeRunner::new(vec![
+            scripted(snapshot, "Enable", ok_output(before)),
+            scripted("description new", "Config", failed_output("% Invalid")),
+            scripted(snapshot, "Enable", ok_output(before)),
+        ]);
+        let result = execute_tx_block_with_runner(&mut runner, &block, None)
+            .await
+            .expect("execute block");
+        assert!(!result.rollback_attempted);
+        assert!(
+            result.rollback_errors[0].contains("config unchanged"),
+            "{:?}",
+            result.rollback_errors
+        );
+    }
+
     #[tokio::test]
     async fn execute_tx_block_skips_failed_step_rollback_by_default() {
         let mut runner = FakeRunner::new(vec![
@@ -1092,27 +1426,12 @@ mod tests {
             name: "policy-create".to_string(),
             kind: CommandBlockKind::Config,
             rollback_policy: RollbackPolicy::WholeResource {
-                rollback: Box::new(
-                    Command {
-                        mode: "Config".to_string(),
-                        command: "delete policy P1".to_string(),
-                        ..Command::default()
-                    }
-                    .into(),
-                ),
+                rollback: Box::new(Command::config("delete policy P1").into()),
                 trigger_step_index: 1,
             },
             steps: vec![
-                TxStep::new(Command {
-                    mode: "Config".to_string(),
-                    command: "set addr A".to_string(),
-                    ..Command::default()
-                }),
-                TxStep::new(Command {
-                    mode: "Config".to_string(),
-                    command: "set policy P1".to_string(),
-                    ..Command::default()
-                }),
+                TxStep::new(Command::config("set addr A")),
+                TxStep::new(Command::config("set policy P1")),
             ],
             fail_fast: true,
         };
@@ -1172,16 +1491,8 @@ mod tests {
                     kind: CommandBlockKind::Config,
                     rollback_policy: RollbackPolicy::PerStep,
                     steps: vec![
-                        TxStep::new(Command {
-                            mode: "Config".to_string(),
-                            command: "set addr 1".to_string(),
-                            ..Command::default()
-                        })
-                        .with_rollback(Command {
-                            mode: "Config".to_string(),
-                            command: "unset addr 1".to_string(),
-                            ..Command::default()
-                        }),
+                        TxStep::new(Command::config("set addr 1"))
+                            .with_rollback(Command::config("unset addr 1")),
                     ],
                     fail_fast: true,
                 },
@@ -1190,22 +1501,16 @@ mod tests {
                     kind: CommandBlockKind::Config,
                     rollback_policy: RollbackPolicy::PerStep,
                     steps: vec![
-                        TxStep::new(Command {
-                            mode: "Config".to_string(),
-                            command: "set policy 1".to_string(),
-                            ..Command::default()
-                        })
-                        .with_rollback(Command {
-                            mode: "Config".to_string(),
-                            command: "unset policy 1".to_string(),
-                            ..Command::default()
-                        })
-                        .with_rollback_on_failure(true),
+                        TxStep::new(Command::config("set policy 1"))
+                            .with_rollback(Command::config("unset policy 1"))
+                            .with_rollback_on_failure(true),
                     ],
                     fail_fast: true,
                 },
             ],
             fail_fast: true,
+            preflight: false,
+            idempotency_key: None,
         };
 
         let mut runner = FakeRunner::new(vec![
@@ -1316,16 +1621,8 @@ mod tests {
             kind: CommandBlockKind::Show,
             rollback_policy: RollbackPolicy::None,
             steps: vec![TxStep::new(CommandFlow::new(vec![
-                Command {
-                    mode: "Enable".to_string(),
-                    command: "terminal length 0".to_string(),
-                    ..Command::default()
-                },
-                Command {
-                    mode: "Enable".to_string(),
-                    command: "show version".to_string(),
-                    ..Command::default()
-                },
+                Command::show("terminal length 0"),
+                Command::show("show version"),
             ]))],
             fail_fast: true,
         };
@@ -1372,32 +1669,16 @@ mod tests {
             rollback_policy: RollbackPolicy::WholeResource {
                 rollback: Box::new(
                     CommandFlow::new(vec![
-                        Command {
-                            mode: "Enable".to_string(),
-                            command: "delete flash:/image.bin".to_string(),
-                            ..Command::default()
-                        },
-                        Command {
-                            mode: "Enable".to_string(),
-                            command: "verify /md5 flash:/image.bin".to_string(),
-                            ..Command::default()
-                        },
+                        Command::new("Enable", "delete flash:/image.bin"),
+                        Command::show("verify /md5 flash:/image.bin"),
                     ])
                     .into(),
                 ),
                 trigger_step_index: 0,
             },
             steps: vec![
-                TxStep::new(Command {
-                    mode: "Enable".to_string(),
-                    command: "copy tftp: flash:/image.bin".to_string(),
-                    ..Command::default()
-                }),
-                TxStep::new(Command {
-                    mode: "Enable".to_string(),
-                    command: "verify /md5 flash:/image.bin".to_string(),
-                    ..Command::default()
-                }),
+                TxStep::new(Command::new("Enable", "copy tftp: flash:/image.bin")),
+                TxStep::new(Command::show("verify /md5 flash:/image.bin")),
             ],
             fail_fast: true,
         };
@@ -1477,16 +1758,8 @@ mod tests {
             kind: CommandBlockKind::Show,
             rollback_policy: RollbackPolicy::None,
             steps: vec![TxStep::new(CommandFlow::new(vec![
-                Command {
-                    mode: "Enable".to_string(),
-                    command: "terminal length 0".to_string(),
-                    ..Command::default()
-                },
-                Command {
-                    mode: "Enable".to_string(),
-                    command: "show version".to_string(),
-                    ..Command::default()
-                },
+                Command::show("terminal length 0"),
+                Command::show("show version"),
             ]))],
             fail_fast: true,
         };
@@ -1538,32 +1811,16 @@ mod tests {
             rollback_policy: RollbackPolicy::WholeResource {
                 rollback: Box::new(
                     CommandFlow::new(vec![
-                        Command {
-                            mode: "Enable".to_string(),
-                            command: "delete flash:/image.bin".to_string(),
-                            ..Command::default()
-                        },
-                        Command {
-                            mode: "Enable".to_string(),
-                            command: "verify /md5 flash:/image.bin".to_string(),
-                            ..Command::default()
-                        },
+                        Command::new("Enable", "delete flash:/image.bin"),
+                        Command::show("verify /md5 flash:/image.bin"),
                     ])
                     .into(),
                 ),
                 trigger_step_index: 0,
             },
             steps: vec![
-                TxStep::new(Command {
-                    mode: "Enable".to_string(),
-                    command: "copy tftp: flash:/image.bin".to_string(),
-                    ..Command::default()
-                }),
-                TxStep::new(Command {
-                    mode: "Enable".to_string(),
-                    command: "verify /md5 flash:/image.bin".to_string(),
-                    ..Command::default()
-                }),
+                TxStep::new(Command::new("Enable", "copy tftp: flash:/image.bin")),
+                TxStep::new(Command::show("verify /md5 flash:/image.bin")),
             ],
             fail_fast: true,
         };
@@ -1628,32 +1885,16 @@ mod tests {
             rollback_policy: RollbackPolicy::WholeResource {
                 rollback: Box::new(
                     CommandFlow::new(vec![
-                        Command {
-                            mode: "Config".to_string(),
-                            command: "delete policy P1".to_string(),
-                            ..Command::default()
-                        },
-                        Command {
-                            mode: "Config".to_string(),
-                            command: "clear policy-cache".to_string(),
-                            ..Command::default()
-                        },
+                        Command::config("delete policy P1"),
+                        Command::config("clear policy-cache"),
                     ])
                     .into(),
                 ),
                 trigger_step_index: 0,
             },
             steps: vec![
-                TxStep::new(Command {
-                    mode: "Config".to_string(),
-                    command: "set policy P1".to_string(),
-                    ..Command::default()
-                }),
-                TxStep::new(Command {
-                    mode: "Config".to_string(),
-                    command: "commit".to_string(),
-                    ..Command::default()
-                }),
+                TxStep::new(Command::config("set policy P1")),
+                TxStep::new(Command::config("commit")),
             ],
             fail_fast: true,
         };
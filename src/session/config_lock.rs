@@ -0,0 +1,66 @@
+//! Detection of configuration locks held by other administrators.
+//!
+//! Templates list the messages their devices print when another user holds
+//! the configuration lock in
+//! [`DeviceHandlerConfig::config_lock_regex`](crate::device::DeviceHandlerConfig::config_lock_regex).
+//! A mode change or failed command
+//! printing one fails with [`ConnectError::ConfigLocked`] instead of a
+//! generic failure; with a
+//! [config lock retry](ExecutionContext::with_config_lock_retry) the command
+//! is retried until the lock is released.
+
+use super::*;
+
+impl SharedSshClient {
+    /// Fails with [`ConnectError::ConfigLocked`] when `output` reports a
+    /// configuration lock held by someone else.
+    pub(in crate::session) fn check_config_lock(&self, output: &str) -> Result<(), ConnectError> {
+        match self.handler.config_lock(output) {
+            Some((message, holder)) => Err(ConnectError::ConfigLocked { holder, message }),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::templates;
+
+    #[test]
+    fn templates_parse_the_lock_holder() {
+        let cisco = templates::cisco().expect("cisco");
+        assert_eq!(
+            cisco.config_lock(
+                "configure terminal\nConfiguration mode locked exclusively by user 'netops' process '412' from terminal '2'. Please try later.\nR1#"
+            ),
+            Some((
+                "Configuration mode locked exclusively by user 'netops' process '412' from terminal '2'. Please try later.".to_string(),
+                Some("netops".to_string())
+            ))
+        );
+
+        let juniper = templates::juniper().expect("juniper");
+        let output = "configure exclusive\nerror: configuration database locked by:\n  jdoe terminal p0 (pid 6523) on since 2026-10-16 09:12:01 UTC\n";
+        assert_eq!(
+            juniper.config_lock(output),
+            Some((
+                "error: configuration database locked by:".to_string(),
+                Some("jdoe".to_string())
+            ))
+        );
+
+        let huawei = templates::huawei().expect("huawei");
+        assert_eq!(
+            huawei
+                .config_lock("Error: The system is locked by other user. Please try again later."),
+            Some((
+                "Error: The system is locked by other user. Please try again later.".to_string(),
+                None
+            ))
+        );
+        assert_eq!(
+            huawei.config_lock("Error: Unrecognized command found at '^' position."),
+            None
+        );
+    }
+}
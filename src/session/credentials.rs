@@ -0,0 +1,185 @@
+//! Pluggable credential resolution for connection establishment.
+
+use std::future::Future;
+use std::pin::Pin;
+
+use super::*;
+
+/// Boxed future returned by [`CredentialProvider::resolve`].
+pub type CredentialFuture<'a> =
+    Pin<Box<dyn Future<Output = Result<DeviceCredentials, ConnectError>> + Send + 'a>>;
+
+/// Device identity handed to a credential provider.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CredentialTarget {
+    /// User name from the original connection request.
+    pub user: String,
+    pub addr: String,
+    pub port: u16,
+}
+
+impl CredentialTarget {
+    /// Build the credential lookup target for a connection request.
+    pub fn from_request(request: &ConnectionRequest) -> Self {
+        Self {
+            user: request.user.clone(),
+            addr: request.addr.clone(),
+            port: request.port,
+        }
+    }
+}
+
+/// Credentials used to authenticate one SSH connection.
+///
+/// When `private_key` is set it takes precedence over `password`.
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct DeviceCredentials {
+    pub user: String,
+    pub password: Option<String>,
+    pub enable_password: Option<String>,
+    /// Private key contents in OpenSSH or PEM format.
+    pub private_key: Option<String>,
+    pub private_key_passphrase: Option<String>,
+}
+
+impl DeviceCredentials {
+    /// Build password-based credentials.
+    pub fn password(user: impl Into<String>, password: impl Into<String>) -> Self {
+        Self {
+            user: user.into(),
+            password: Some(password.into()),
+            ..Self::default()
+        }
+    }
+
+    /// Build private-key-based credentials.
+    pub fn private_key(
+        user: impl Into<String>,
+        private_key: impl Into<String>,
+        passphrase: Option<String>,
+    ) -> Self {
+        Self {
+            user: user.into(),
+            private_key: Some(private_key.into()),
+            private_key_passphrase: passphrase,
+            ..Self::default()
+        }
+    }
+
+    /// Attach the enable password used for privileged mode transitions.
+    pub fn with_enable_password(mut self, enable_password: Option<String>) -> Self {
+        self.enable_password = enable_password;
+        self
+    }
+
+    /// SSH authentication method name implied by these credentials.
+    pub fn auth_method_name(&self) -> &'static str {
+        if self.private_key.is_some() {
+            "publickey"
+        } else {
+            "password"
+        }
+    }
+
+    pub(crate) fn auth_method(&self) -> AuthMethod {
+        match self.private_key.as_deref() {
+            Some(key) => AuthMethod::with_key(key, self.private_key_passphrase.as_deref()),
+            None => AuthMethod::with_password(self.password.as_deref().unwrap_or_default()),
+        }
+    }
+}
+
+impl std::fmt::Debug for DeviceCredentials {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let redact = |value: &Option<String>| value.as_ref().map(|_| "<redacted>");
+        f.debug_struct("DeviceCredentials")
+            .field("user", &self.user)
+            .field("password", &redact(&self.password))
+            .field("enable_password", &redact(&self.enable_password))
+            .field("private_key", &redact(&self.private_key))
+            .field(
+                "private_key_passphrase",
+                &redact(&self.private_key_passphrase),
+            )
+            .finish()
+    }
+}
+
+/// Source of device credentials consulted whenever the manager opens a connection.
+///
+/// Implement this to fetch secrets from Vault, CyberArk or any other store.
+/// The provider is called on every new connection and every reconnect, so
+/// rotated passwords are picked up without changing call sites.
+pub trait CredentialProvider: Send + Sync {
+    /// Resolve credentials for one device.
+    fn resolve<'a>(&'a self, target: &'a CredentialTarget) -> CredentialFuture<'a>;
+}
+
+/// Provider returning one fixed credential set for every device.
+#[derive(Debug, Clone)]
+pub struct StaticCredentialProvider {
+    credentials: DeviceCredentials,
+}
+
+impl StaticCredentialProvider {
+    /// Build a provider that always returns `credentials`.
+    pub fn new(credentials: DeviceCredentials) -> Self {
+        Self { credentials }
+    }
+}
+
+impl CredentialProvider for StaticCredentialProvider {
+    fn resolve<'a>(&'a self, _target: &'a CredentialTarget) -> CredentialFuture<'a> {
+        Box::pin(async move { Ok(self.credentials.clone()) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn static_provider_returns_configured_credentials() {
+        let provider = StaticCredentialProvider::new(
+            DeviceCredentials::password("admin", "secret")
+                .with_enable_password(Some("enable".to_string())),
+        );
+        let target = CredentialTarget {
+            user: "ops".to_string(),
+            addr: "192.0.2.1".to_string(),
+            port: 22,
+        };
+
+        let resolved = provider.resolve(&target).await.expect("resolve");
+
+        assert_eq!(resolved.user, "admin");
+        assert_eq!(resolved.password.as_deref(), Some("secret"));
+        assert_eq!(resolved.enable_password.as_deref(), Some("enable"));
+    }
+
+    #[test]
+    fn debug_output_redacts_secrets() {
+        let credentials = DeviceCredentials::private_key("admin", "KEY", Some("pass".to_string()));
+
+        let rendered = format!("{credentials:?}");
+
+        assert!(rendered.contains("admin"));
+        assert!(!rendered.contains("KEY"));
+        assert!(!rendered.contains("pass\""));
+        assert!(rendered.contains("<redacted>"));
+    }
+
+    #[test]
+    fn manager_provider_can_be_set_and_cleared() {
+        let manager = SshConnectionManager::new();
+        assert!(manager.credential_provider().is_none());
+
+        manager.set_credential_provider(Arc::new(StaticCredentialProvider::new(
+            DeviceCredentials::password("admin", "secret"),
+        )));
+        assert!(manager.credential_provider().is_some());
+
+        manager.clear_credential_provider();
+        assert!(manager.credential_provider().is_none());
+    }
+}
@@ -0,0 +1,90 @@
+//! Command echo removal.
+//!
+//! Most devices echo the command verbatim on the first output line, but some
+//! wrap long commands across lines, scroll them horizontally (`$` markers and
+//! backspaces) or drop characters. The echo is then matched loosely: leading
+//! lines are joined with whitespace and control characters removed, and
+//! accepted when within a small edit distance of the command.
+
+use crate::device::edit_distance;
+
+/// Lines a wrapped echo may span.
+const MAX_ECHO_LINES: usize = 8;
+
+/// Allowed edit distance is the squashed command length divided by this.
+const ECHO_DISTANCE_DIVISOR: usize = 4;
+
+/// `content` with the echo of `command` removed from its start.
+///
+/// Returns `content` unchanged when no leading lines resemble the command.
+pub(crate) fn strip_echo<'a>(content: &'a str, command: &str) -> &'a str {
+    if command.is_empty() {
+        return content;
+    }
+    if let Some(rest) = content.strip_prefix(command) {
+        return rest.trim_start_matches(['\n', '\r']);
+    }
+
+    let expected: Vec<char> = squash(command).collect();
+    if expected.is_empty() {
+        return content;
+    }
+    let allowed = (expected.len() / ECHO_DISTANCE_DIVISOR).max(1);
+
+    let mut seen = Vec::new();
+    let mut consumed = 0;
+    // (distance, end offset) of the closest leading run of lines.
+    let mut best: Option<(usize, usize)> = None;
+    for line in content.split_inclusive('\n').take(MAX_ECHO_LINES) {
+        consumed += line.len();
+        seen.extend(squash(line));
+        if seen.is_empty() {
+            continue;
+        }
+        let distance = edit_distance(&seen, &expected);
+        if distance <= allowed && best.is_none_or(|(closest, _)| distance < closest) {
+            best = Some((distance, consumed));
+        }
+        if seen.len() >= expected.len() + allowed {
+            break;
+        }
+    }
+    match best {
+        Some((_, end)) => content[end..].trim_start_matches(['\n', '\r']),
+        None => content,
+    }
+}
+
+fn squash(text: &str) -> impl Iterator<Item = char> + '_ {
+    text.chars()
+        .filter(|c| !c.is_whitespace() && !c.is_control())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strips_exact_wrapped_and_scrolled_echo() {
+        assert_eq!(
+            strip_echo("show clock\n*10:00:00 UTC\nR1#", "show clock"),
+            "*10:00:00 UTC\nR1#"
+        );
+
+        let command = "interface GigabitEthernet0/1 description uplink-to-core-switch-01";
+        let wrapped = "interface GigabitEthernet0/1 description upli\r\nnk-to-core-switch-01\r\nR1(config-if)#";
+        assert_eq!(strip_echo(wrapped, command), "R1(config-if)#");
+
+        let scrolled =
+            "$ce GigabitEthernet0/1 description uplink-to-core-switch-01\nR1(config-if)#";
+        assert_eq!(strip_echo(scrolled, command), "R1(config-if)#");
+    }
+
+    #[test]
+    fn leaves_output_without_echo_untouched() {
+        let output = "Cisco IOS XE Software, Version 17.9.4\nR1#";
+        assert_eq!(strip_echo(output, "show version"), output);
+        assert_eq!(strip_echo("", "show version"), "");
+        assert_eq!(strip_echo(output, ""), output);
+    }
+}
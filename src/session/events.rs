@@ -0,0 +1,217 @@
+//! Connection lifecycle notifications published by the connection manager.
+
+use moka::notification::RemovalCause;
+use tokio::sync::broadcast;
+
+use super::*;
+
+/// Number of events buffered per subscriber before slow receivers start lagging.
+const EVENT_CHANNEL_CAPACITY: usize = 256;
+
+/// Lifecycle event for one managed connection.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ConnectionEvent {
+    /// A new connection was established with no previous cached session.
+    Connected { device_addr: String },
+    /// A cached connection was replaced by a freshly established one.
+    Reconnected { device_addr: String, reason: String },
+    /// The SSH session ended; `stats` are the connection's final counters.
+    Disconnected {
+        device_addr: String,
+        reason: String,
+        stats: ConnectionStats,
+    },
+    /// The pool dropped the connection because it was idle or over capacity.
+    Evicted { device_addr: String, cause: String },
+    /// A command is still running after the slow-command threshold.
+    SlowCommand {
+        device_addr: String,
+        mode: String,
+        command: String,
+        elapsed_ms: u64,
+        timeout_ms: u64,
+    },
+    /// A command stopped on a question no input rule answers.
+    UnmodeledPrompt {
+        device_addr: String,
+        mode: String,
+        command: String,
+        question: String,
+        /// Answer sent, or `None` when the command failed instead.
+        #[serde(default)]
+        answer: Option<String>,
+    },
+    /// A command or operation failed on the connection.
+    CommandFailed {
+        device_addr: String,
+        #[serde(default)]
+        mode: Option<String>,
+        #[serde(default)]
+        command: Option<String>,
+        error: String,
+    },
+    /// A transaction block or workflow finished, committed or not.
+    TransactionFinished {
+        device_addr: String,
+        /// Block or workflow name.
+        name: String,
+        committed: bool,
+        rollback_attempted: bool,
+        rollback_succeeded: bool,
+        #[serde(default)]
+        failure_reason: Option<String>,
+    },
+}
+
+impl ConnectionEvent {
+    /// Connection cache key (`user@addr:port`) the event refers to.
+    pub fn device_addr(&self) -> &str {
+        match self {
+            Self::Connected { device_addr }
+            | Self::Reconnected { device_addr, .. }
+            | Self::Disconnected { device_addr, .. }
+            | Self::Evicted { device_addr, .. }
+            | Self::SlowCommand { device_addr, .. }
+            | Self::UnmodeledPrompt { device_addr, .. }
+            | Self::CommandFailed { device_addr, .. }
+            | Self::TransactionFinished { device_addr, .. } => device_addr,
+        }
+    }
+
+    /// Build a [`ConnectionEvent::CommandFailed`] from a manager error.
+    ///
+    /// Mode and command come from the error context when present.
+    pub(crate) fn command_failed(device_addr: &str, err: &ConnectError) -> Self {
+        let context = err.context();
+        Self::CommandFailed {
+            device_addr: device_addr.to_string(),
+            mode: context.and_then(|context| context.mode.clone()),
+            command: context.and_then(|context| context.command.clone()),
+            error: err.root_cause().to_string(),
+        }
+    }
+
+    /// Build a [`ConnectionEvent::TransactionFinished`] from a block result.
+    pub(crate) fn tx_block_finished(device_addr: &str, result: &TxResult) -> Self {
+        Self::TransactionFinished {
+            device_addr: device_addr.to_string(),
+            name: result.block_name.clone(),
+            committed: result.committed,
+            rollback_attempted: result.rollback_attempted,
+            rollback_succeeded: result.rollback_succeeded,
+            failure_reason: result.failure_reason.clone(),
+        }
+    }
+
+    /// Build a [`ConnectionEvent::TransactionFinished`] from a workflow result.
+    ///
+    /// The failure reason is the one of the first failed block.
+    pub(crate) fn tx_workflow_finished(device_addr: &str, result: &TxWorkflowResult) -> Self {
+        Self::TransactionFinished {
+            device_addr: device_addr.to_string(),
+            name: result.workflow_name.clone(),
+            committed: result.committed,
+            rollback_attempted: result.rollback_attempted,
+            rollback_succeeded: result.rollback_succeeded,
+            failure_reason: result
+                .failed_block
+                .and_then(|index| result.block_results.get(index))
+                .and_then(|block| block.failure_reason.clone()),
+        }
+    }
+
+    /// Map a cache removal to an eviction event; explicit removals are reported elsewhere.
+    pub(crate) fn from_removal(device_addr: &str, cause: RemovalCause) -> Option<Self> {
+        let cause = match cause {
+            RemovalCause::Expired => "idle_timeout",
+            RemovalCause::Size => "capacity",
+            RemovalCause::Explicit | RemovalCause::Replaced => return None,
+        };
+        Some(Self::Evicted {
+            device_addr: device_addr.to_string(),
+            cause: cause.to_string(),
+        })
+    }
+}
+
+/// Broadcast channel carrying [`ConnectionEvent`]s to every subscriber.
+#[derive(Debug, Clone)]
+pub(crate) struct ConnectionEventBus {
+    sender: broadcast::Sender<ConnectionEvent>,
+}
+
+impl ConnectionEventBus {
+    pub(crate) fn new() -> Self {
+        let (sender, _) = broadcast::channel(EVENT_CHANNEL_CAPACITY);
+        Self { sender }
+    }
+
+    pub(crate) fn subscribe(&self) -> broadcast::Receiver<ConnectionEvent> {
+        self.sender.subscribe()
+    }
+
+    /// Publish an event; dropped silently when nobody is subscribed.
+    pub(crate) fn emit(&self, event: ConnectionEvent) {
+        trace!("connection event: {:?}", event);
+        let _ = self.sender.send(event);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn removal_causes_map_to_eviction_events() {
+        assert_eq!(
+            ConnectionEvent::from_removal("admin@192.0.2.1:22", RemovalCause::Expired),
+            Some(ConnectionEvent::Evicted {
+                device_addr: "admin@192.0.2.1:22".to_string(),
+                cause: "idle_timeout".to_string(),
+            })
+        );
+        assert!(
+            ConnectionEvent::from_removal("admin@192.0.2.1:22", RemovalCause::Explicit).is_none()
+        );
+    }
+
+    #[test]
+    fn command_failed_uses_error_context() {
+        let err = ConnectError::ExecTimeout("partial".to_string()).with_context(
+            ErrorContext::new("admin@192.0.2.1:22", Duration::from_secs(3))
+                .with_mode(Some("enable".to_string()))
+                .with_command(Some("show version".to_string())),
+        );
+
+        let event = ConnectionEvent::command_failed("admin@192.0.2.1:22", &err);
+
+        assert_eq!(
+            event,
+            ConnectionEvent::CommandFailed {
+                device_addr: "admin@192.0.2.1:22".to_string(),
+                mode: Some("enable".to_string()),
+                command: Some("show version".to_string()),
+                error: "exec command timeout: partial".to_string(),
+            }
+        );
+        assert_eq!(event.device_addr(), "admin@192.0.2.1:22");
+    }
+
+    #[tokio::test]
+    async fn manager_subscribers_receive_emitted_events() {
+        let manager = SshConnectionManager::new();
+        let mut events = manager.subscribe_events();
+
+        manager.events.emit(ConnectionEvent::Connected {
+            device_addr: "admin@192.0.2.1:22".to_string(),
+        });
+
+        assert_eq!(
+            events.recv().await.expect("event"),
+            ConnectionEvent::Connected {
+                device_addr: "admin@192.0.2.1:22".to_string(),
+            }
+        );
+    }
+}
@@ -0,0 +1,702 @@
+//! Bounded-concurrency fan-out across many devices.
+
+use std::future::Future;
+
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+use super::*;
+use crate::probe::{self, ProbeOptions};
+
+/// Limits applied when connecting to or running commands on many devices.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FanoutOptions {
+    /// Maximum number of devices worked on at the same time.
+    pub max_concurrency: usize,
+    /// Deadline for each device.
+    ///
+    /// Covers connection and execution of commands. Workflows are bounded
+    /// only until they hold the device session: once a transaction has
+    /// started it always commits or rolls back, however long that takes.
+    pub per_device_timeout: Duration,
+    /// Reachability check run before touching each device.
+    pub precheck: Option<ProbeOptions>,
+}
+
+impl Default for FanoutOptions {
+    fn default() -> Self {
+        Self {
+            max_concurrency: 16,
+            per_device_timeout: Duration::from_secs(120),
+            precheck: None,
+        }
+    }
+}
+
+impl FanoutOptions {
+    /// Build the default fan-out limits.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Override the number of devices worked on concurrently (at least one).
+    pub fn with_max_concurrency(mut self, max_concurrency: usize) -> Self {
+        self.max_concurrency = max_concurrency.max(1);
+        self
+    }
+
+    /// Override the per-device deadline.
+    pub fn with_per_device_timeout(mut self, per_device_timeout: Duration) -> Self {
+        self.per_device_timeout = per_device_timeout;
+        self
+    }
+
+    /// Probe each device first so unreachable ones fail fast with
+    /// [`ConnectError::Unreachable`] instead of waiting out the SSH handshake.
+    pub fn with_precheck(mut self, precheck: Option<ProbeOptions>) -> Self {
+        self.precheck = precheck;
+        self
+    }
+}
+
+/// Result for one device of a fan-out call.
+#[derive(Debug)]
+pub struct DeviceOutcome<T> {
+    /// Connection cache key (`user@addr:port`).
+    pub device_addr: String,
+    pub result: Result<T, ConnectError>,
+    /// Wall time spent on this device, including time queued for a slot.
+    pub elapsed: Duration,
+}
+
+impl<T> DeviceOutcome<T> {
+    /// Returns true when the device finished successfully.
+    pub fn is_ok(&self) -> bool {
+        self.result.is_ok()
+    }
+}
+
+impl SshConnectionManager {
+    /// Establish connections to many devices concurrently.
+    ///
+    /// At most `options.max_concurrency` handshakes run at once. Outcomes are
+    /// returned in the same order as `requests`.
+    pub async fn preconnect(
+        &self,
+        requests: Vec<ConnectionRequest>,
+        context: ExecutionContext,
+        options: FanoutOptions,
+    ) -> Vec<DeviceOutcome<()>> {
+        let manager = self.clone();
+        let deadline = Some(options.per_device_timeout);
+        run_bounded(
+            requests,
+            options,
+            Lookup::new(self, &context),
+            move |request| {
+                let manager = manager.clone();
+                let context = context.clone();
+                within(deadline, async move {
+                    manager.get_with_context(request, context).await.map(|_| ())
+                })
+            },
+        )
+        .await
+    }
+
+    /// Run one command on many devices concurrently.
+    ///
+    /// Connections are reused from the pool when possible. Outcomes are
+    /// returned in the same order as `requests`.
+    pub async fn run_on_many(
+        &self,
+        requests: Vec<ConnectionRequest>,
+        command: Command,
+        context: ExecutionContext,
+        options: FanoutOptions,
+    ) -> Vec<DeviceOutcome<Output>> {
+        let manager = self.clone();
+        let deadline = Some(options.per_device_timeout);
+        run_bounded(
+            requests,
+            options,
+            Lookup::new(self, &context),
+            move |request| {
+                let manager = manager.clone();
+                let command = command.clone();
+                let context = context.clone();
+                within(deadline, async move {
+                    manager
+                        .execute_command_with_context(request, command, context)
+                        .await
+                })
+            },
+        )
+        .await
+    }
+
+    /// Run one transaction workflow on many devices concurrently.
+    ///
+    /// The per-device deadline stops at acquiring the device session, so
+    /// no workflow is cut off between blocks. Outcomes are returned in the
+    /// same order as `requests`.
+    pub async fn run_workflow_on_many(
+        &self,
+        requests: Vec<ConnectionRequest>,
+        workflow: TxWorkflow,
+        context: ExecutionContext,
+        options: FanoutOptions,
+    ) -> Vec<DeviceOutcome<TxWorkflowResult>> {
+        let manager = self.clone();
+        let deadline = Some(options.per_device_timeout);
+        run_bounded(
+            requests,
+            options,
+            Lookup::new(self, &context),
+            move |request| {
+                let manager = manager.clone();
+                let workflow = workflow.clone();
+                let context = context.clone();
+                async move {
+                    manager
+                        .execute_tx_workflow_within(request, workflow, context, deadline)
+                        .await
+                }
+            },
+        )
+        .await
+    }
+
+    /// Run one command on many devices concurrently, writing each outcome
+    /// to `sink` as soon as the device finishes.
+    ///
+    /// Outcomes arrive in completion order, tagged with their index in
+    /// `requests`. After a sink error no further device is started; devices
+    /// already running finish so no change is cut off halfway, and the
+    /// error is returned.
+    pub async fn run_on_many_into(
+        &self,
+        requests: Vec<ConnectionRequest>,
+        command: Command,
+        context: ExecutionContext,
+        options: FanoutOptions,
+        sink: &mut dyn OutcomeSink<Output>,
+    ) -> Result<FanoutSummary, ConnectError> {
+        let manager = self.clone();
+        let deadline = Some(options.per_device_timeout);
+        stream_bounded(
+            requests,
+            options,
+            Lookup::new(self, &context),
+            sink,
+            |output: &Output| output.success,
+            move |request| {
+                let manager = manager.clone();
+                let command = command.clone();
+                let context = context.clone();
+                within(deadline, async move {
+                    manager
+                        .execute_command_with_context(request, command, context)
+                        .await
+                })
+            },
+        )
+        .await
+    }
+
+    /// Run one transaction workflow on many devices concurrently, writing
+    /// each outcome to `sink` as soon as the device finishes.
+    ///
+    /// A sink error stops the fan-out as in [`Self::run_on_many_into`]:
+    /// workflows already running commit or roll back before it returns.
+    pub async fn run_workflow_on_many_into(
+        &self,
+        requests: Vec<ConnectionRequest>,
+        workflow: TxWorkflow,
+        context: ExecutionContext,
+        options: FanoutOptions,
+        sink: &mut dyn OutcomeSink<TxWorkflowResult>,
+    ) -> Result<FanoutSummary, ConnectError> {
+        let manager = self.clone();
+        let deadline = Some(options.per_device_timeout);
+        stream_bounded(
+            requests,
+            options,
+            Lookup::new(self, &context),
+            sink,
+            |result: &TxWorkflowResult| result.committed,
+            move |request| {
+                let manager = manager.clone();
+                let workflow = workflow.clone();
+                let context = context.clone();
+                async move {
+                    manager
+                        .execute_tx_workflow_within(request, workflow, context, deadline)
+                        .await
+                }
+            },
+        )
+        .await
+    }
+}
+
+/// Bound `work` by `deadline`, if any.
+async fn within<T>(
+    deadline: Option<Duration>,
+    work: impl Future<Output = Result<T, ConnectError>>,
+) -> Result<T, ConnectError> {
+    match deadline {
+        Some(deadline) => tokio::time::timeout(deadline, work)
+            .await
+            .unwrap_or(Err(ConnectError::DeviceTimeout(deadline))),
+        None => work.await,
+    }
+}
+
+/// Acquire a device session within `deadline`, then run `work` on it to
+/// the end.
+///
+/// Dropping a workflow partway would leave its committed blocks on the
+/// device without rolling them back, so the deadline stops at the session.
+pub(super) async fn acquire_then<S, T, Fut>(
+    deadline: Option<Duration>,
+    acquire: impl Future<Output = Result<S, ConnectError>>,
+    work: impl FnOnce(S) -> Fut,
+) -> Result<T, ConnectError>
+where
+    Fut: Future<Output = Result<T, ConnectError>>,
+{
+    let session = within(deadline, acquire).await?;
+    work(session).await
+}
+
+/// How prechecks resolve device addresses: like the connections they
+/// guard, through the manager's [`HostResolver`] and the context's
+/// [`AddressFamily`].
+#[derive(Clone, Default)]
+struct Lookup {
+    family: AddressFamily,
+    resolver: Option<Arc<dyn HostResolver>>,
+}
+
+impl Lookup {
+    fn new(manager: &SshConnectionManager, context: &ExecutionContext) -> Self {
+        Self {
+            family: context.address_family,
+            resolver: manager.host_resolver(),
+        }
+    }
+}
+
+/// [`run_bounded_each`] into `sink`, finishing it after the last outcome.
+///
+/// `succeeded` tells devices that answered with a failure, such as a
+/// rejected command, apart in the returned summary.
+async fn stream_bounded<T, F, Fut>(
+    requests: Vec<ConnectionRequest>,
+    options: FanoutOptions,
+    lookup: Lookup,
+    sink: &mut dyn OutcomeSink<T>,
+    succeeded: fn(&T) -> bool,
+    task: F,
+) -> Result<FanoutSummary, ConnectError>
+where
+    T: Send + 'static,
+    F: Fn(ConnectionRequest) -> Fut,
+    Fut: Future<Output = Result<T, ConnectError>> + Send + 'static,
+{
+    let mut summary = FanoutSummary::default();
+    run_bounded_each(requests, options, lookup, task, |index, outcome| {
+        summary.add(&outcome, succeeded);
+        sink.write(index, &outcome)
+    })
+    .await?;
+    sink.finish().await?;
+    Ok(summary)
+}
+
+/// Run `task` for every request with bounded concurrency.
+///
+/// Tasks apply the per-device deadline themselves, see [`within`] and
+/// [`acquire_then`].
+async fn run_bounded<T, F, Fut>(
+    requests: Vec<ConnectionRequest>,
+    options: FanoutOptions,
+    lookup: Lookup,
+    task: F,
+) -> Vec<DeviceOutcome<T>>
+where
+    T: Send + 'static,
+    F: Fn(ConnectionRequest) -> Fut,
+    Fut: Future<Output = Result<T, ConnectError>> + Send + 'static,
+{
+    let mut outcomes: Vec<Option<DeviceOutcome<T>>> = Vec::with_capacity(requests.len());
+    outcomes.resize_with(requests.len(), || None);
+    let _ = run_bounded_each(requests, options, lookup, task, |index, outcome| {
+        outcomes[index] = Some(outcome);
+        Ok(())
+    })
+    .await;
+    outcomes.into_iter().flatten().collect()
+}
+
+/// Like [`run_bounded`], handing each outcome with its request index to
+/// `on_outcome` as soon as the device finishes.
+///
+/// When `on_outcome` fails, devices waiting for a slot are not started,
+/// devices already running are awaited without handing their outcomes
+/// over, and the error is returned.
+async fn run_bounded_each<T, F, Fut, O>(
+    requests: Vec<ConnectionRequest>,
+    options: FanoutOptions,
+    lookup: Lookup,
+    task: F,
+    mut on_outcome: O,
+) -> Result<(), ConnectError>
+where
+    T: Send + 'static,
+    F: Fn(ConnectionRequest) -> Fut,
+    Fut: Future<Output = Result<T, ConnectError>> + Send + 'static,
+    O: FnMut(usize, DeviceOutcome<T>) -> Result<(), ConnectError>,
+{
+    let semaphore = Arc::new(Semaphore::new(options.max_concurrency.max(1)));
+    let mut tasks = JoinSet::new();
+    let mut spawned = HashMap::with_capacity(requests.len());
+
+    for (index, request) in requests.into_iter().enumerate() {
+        let device_addr = request.device_addr();
+        let semaphore = semaphore.clone();
+        let precheck = options
+            .precheck
+            .clone()
+            .map(|probe| (request.addr.clone(), request.port, probe, lookup.clone()));
+        let work = task(request);
+        let task_addr = device_addr.clone();
+        let handle = tasks.spawn(async move {
+            let started = Instant::now();
+            let result = match semaphore.acquire_owned().await {
+                Ok(_permit) => {
+                    let reachable = match precheck {
+                        Some((host, port, probe, lookup)) => {
+                            probe::probe_with(
+                                &host,
+                                port,
+                                &probe,
+                                lookup.family,
+                                lookup.resolver.as_deref(),
+                            )
+                            .await
+                        }
+                        None => Ok(()),
+                    };
+                    match reachable {
+                        Ok(()) => work.await,
+                        Err(err) => Err(err),
+                    }
+                }
+                Err(_) => Err(ConnectError::InternalServerError(
+                    "fan-out stopped before the device started".to_string(),
+                )),
+            };
+            DeviceOutcome {
+                device_addr: task_addr,
+                result,
+                elapsed: started.elapsed(),
+            }
+        });
+        spawned.insert(handle.id(), (index, device_addr));
+    }
+
+    let mut stopped = None;
+    while let Some(joined) = tasks.join_next_with_id().await {
+        let (id, outcome) = match joined {
+            Ok((id, outcome)) => (id, Some(outcome)),
+            Err(err) => {
+                debug!("Fan-out task failed to complete: {}", err);
+                (err.id(), None)
+            }
+        };
+        let Some((index, device_addr)) = spawned.remove(&id) else {
+            continue;
+        };
+        let outcome = outcome.unwrap_or_else(|| DeviceOutcome {
+            device_addr,
+            result: Err(ConnectError::InternalServerError(
+                "fan-out task panicked".to_string(),
+            )),
+            elapsed: Duration::ZERO,
+        });
+        if stopped.is_some() {
+            continue;
+        }
+        if let Err(err) = on_outcome(index, outcome) {
+            debug!("Fan-out stopped, waiting for running devices: {}", err);
+            semaphore.close();
+            stopped = Some(err);
+        }
+    }
+    stopped.map_or(Ok(()), Err)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn request(addr: &str) -> ConnectionRequest {
+        ConnectionRequest::new(
+            "admin".to_string(),
+            addr.to_string(),
+            22,
+            "secret".to_string(),
+            None,
+            crate::templates::cisco().expect("template"),
+        )
+    }
+
+    #[tokio::test]
+    async fn run_bounded_limits_concurrency_and_keeps_order() {
+        let running = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+        let requests = (1..=6).map(|i| request(&format!("192.0.2.{i}"))).collect();
+
+        let outcomes = run_bounded(
+            requests,
+            FanoutOptions::new().with_max_concurrency(2),
+            Lookup::default(),
+            |request| {
+                let running = running.clone();
+                let peak = peak.clone();
+                async move {
+                    let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                    peak.fetch_max(now, Ordering::SeqCst);
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                    running.fetch_sub(1, Ordering::SeqCst);
+                    Ok(request.addr)
+                }
+            },
+        )
+        .await;
+
+        assert!(peak.load(Ordering::SeqCst) <= 2);
+        let addrs: Vec<_> = outcomes
+            .iter()
+            .map(|outcome| outcome.result.as_deref().expect("ok"))
+            .collect();
+        assert_eq!(
+            addrs,
+            [
+                "192.0.2.1",
+                "192.0.2.2",
+                "192.0.2.3",
+                "192.0.2.4",
+                "192.0.2.5",
+                "192.0.2.6"
+            ]
+        );
+        assert_eq!(outcomes[0].device_addr, "admin@192.0.2.1:22");
+    }
+
+    struct CollectSink {
+        indices: Vec<usize>,
+        capacity: usize,
+        finished: bool,
+    }
+
+    impl Default for CollectSink {
+        fn default() -> Self {
+            Self {
+                indices: Vec::new(),
+                capacity: 2,
+                finished: false,
+            }
+        }
+    }
+
+    impl OutcomeSink<String> for CollectSink {
+        fn write(
+            &mut self,
+            index: usize,
+            _outcome: &DeviceOutcome<String>,
+        ) -> Result<(), ConnectError> {
+            if self.indices.len() == self.capacity {
+                return Err(ConnectError::OutputSinkError("disk full".to_string()));
+            }
+            self.indices.push(index);
+            Ok(())
+        }
+
+        fn finish(&mut self) -> SinkFuture<'_> {
+            self.finished = true;
+            Box::pin(async { Ok(()) })
+        }
+    }
+
+    #[tokio::test]
+    async fn stream_bounded_hands_outcomes_over_in_completion_order() {
+        let requests = (1..=2).map(|i| request(&format!("192.0.2.{i}"))).collect();
+        let mut sink = CollectSink::default();
+        let summary = stream_bounded(
+            requests,
+            FanoutOptions::new(),
+            Lookup::default(),
+            &mut sink,
+            |_| true,
+            |request| async move {
+                if request.addr == "192.0.2.1" {
+                    tokio::time::sleep(Duration::from_millis(50)).await;
+                    return Err(ConnectError::DeviceTimeout(Duration::from_millis(50)));
+                }
+                Ok(request.addr)
+            },
+        )
+        .await
+        .expect("stream");
+        assert_eq!(sink.indices, [1, 0]);
+        assert!(sink.finished);
+        assert_eq!(
+            (summary.total, summary.succeeded, summary.failed),
+            (2, 1, 1)
+        );
+
+        let requests = (1..=3).map(|i| request(&format!("192.0.2.{i}"))).collect();
+        let mut sink = CollectSink::default();
+        let err = stream_bounded(
+            requests,
+            FanoutOptions::new(),
+            Lookup::default(),
+            &mut sink,
+            |_| true,
+            |request| async move { Ok(request.addr) },
+        )
+        .await
+        .expect_err("sink full");
+        assert!(matches!(err, ConnectError::OutputSinkError(_)));
+        assert!(!sink.finished);
+    }
+
+    #[tokio::test]
+    async fn sink_errors_let_running_devices_finish() {
+        let started = Arc::new(AtomicUsize::new(0));
+        let finished = Arc::new(AtomicUsize::new(0));
+        let requests = (1..=5).map(|i| request(&format!("192.0.2.{i}"))).collect();
+        let mut sink = CollectSink {
+            capacity: 0,
+            ..CollectSink::default()
+        };
+        let err = stream_bounded(
+            requests,
+            FanoutOptions::new().with_max_concurrency(2),
+            Lookup::default(),
+            &mut sink,
+            |_| true,
+            |request| {
+                let started = started.clone();
+                let finished = finished.clone();
+                async move {
+                    started.fetch_add(1, Ordering::SeqCst);
+                    if request.addr != "192.0.2.1" {
+                        tokio::time::sleep(Duration::from_millis(50)).await;
+                    }
+                    finished.fetch_add(1, Ordering::SeqCst);
+                    Ok(request.addr)
+                }
+            },
+        )
+        .await
+        .expect_err("sink full");
+        assert!(matches!(err, ConnectError::OutputSinkError(_)));
+        // The first device fails the sink; the two then running finish and
+        // the rest never start.
+        assert_eq!(started.load(Ordering::SeqCst), 3);
+        assert_eq!(finished.load(Ordering::SeqCst), 3);
+        assert!(sink.indices.is_empty());
+    }
+
+    #[tokio::test]
+    async fn run_bounded_reports_per_device_timeouts() {
+        let outcomes = run_bounded(
+            vec![request("192.0.2.1")],
+            FanoutOptions::new().with_per_device_timeout(Duration::from_millis(10)),
+            Lookup::default(),
+            |_| {
+                within(Some(Duration::from_millis(10)), async {
+                    tokio::time::sleep(Duration::from_secs(5)).await;
+                    Ok(())
+                })
+            },
+        )
+        .await;
+
+        assert!(matches!(
+            outcomes[0].result,
+            Err(ConnectError::DeviceTimeout(timeout)) if timeout == Duration::from_millis(10)
+        ));
+        assert!(outcomes[0].result.as_ref().unwrap_err().is_retryable());
+    }
+
+    #[tokio::test]
+    async fn timed_out_workflows_leave_no_committed_blocks_behind() {
+        let deadline = Some(Duration::from_millis(10));
+        let session = Arc::new(tokio::sync::RwLock::new(Vec::<&'static str>::new()));
+        // Stands in for a workflow committing two blocks with a slow step
+        // in between.
+        let workflow = |mut committed: tokio::sync::OwnedRwLockWriteGuard<Vec<_>>| async move {
+            committed.push("vlan");
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            committed.push("interface");
+            Ok(())
+        };
+
+        let acquire = |session: &Arc<tokio::sync::RwLock<Vec<_>>>| {
+            let session = session.clone();
+            async move { Ok(session.write_owned().await) }
+        };
+
+        // Another job holds the session past the deadline: nothing runs.
+        let busy = session.clone().write_owned().await;
+        let timed_out = acquire_then(deadline, acquire(&session), workflow).await;
+        assert!(matches!(timed_out, Err(ConnectError::DeviceTimeout(_))));
+        drop(busy);
+        assert!(session.read().await.is_empty());
+
+        // Once the session is held the workflow outlives the deadline.
+        acquire_then(deadline, acquire(&session), workflow)
+            .await
+            .expect("workflow finishes");
+        assert_eq!(*session.read().await, vec!["vlan", "interface"]);
+    }
+
+    #[tokio::test]
+    async fn run_bounded_precheck_skips_unreachable_devices() {
+        let closed = {
+            let released = tokio::net::TcpListener::bind("127.0.0.1:0")
+                .await
+                .expect("bind");
+            released.local_addr().expect("addr").port()
+        };
+        let mut unreachable = request("127.0.0.1");
+        unreachable.port = closed;
+        let ran = Arc::new(AtomicUsize::new(0));
+
+        let outcomes = run_bounded(
+            vec![unreachable],
+            FanoutOptions::new().with_precheck(Some(ProbeOptions::new())),
+            Lookup::default(),
+            |_| {
+                let ran = ran.clone();
+                async move {
+                    ran.fetch_add(1, Ordering::SeqCst);
+                    Ok(())
+                }
+            },
+        )
+        .await;
+
+        assert!(matches!(
+            outcomes[0].result,
+            Err(ConnectError::Unreachable { .. })
+        ));
+        assert_eq!(ran.load(Ordering::SeqCst), 0);
+    }
+}
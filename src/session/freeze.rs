@@ -0,0 +1,277 @@
+//! Organization-wide change freezes.
+//!
+//! [`SshConnectionManager::set_change_freeze`] rejects every config command
+//! and config transaction block on every connection of the manager, pooled
+//! or not, until the freeze is lifted. Read-only commands keep working, and
+//! so do the template's terminal setup and other commands that only affect
+//! the session, e.g. `terminal length 0` or `dir`.
+//! Jobs whose [`ExecutionContext::freeze_override`] carries the freeze's
+//! override token are let through, e.g. for an approved emergency change.
+
+use super::*;
+use crate::templates::is_read_only_command;
+
+/// The manager's freeze switch, shared with every connection it pools.
+pub(crate) type FreezeSwitch = Arc<std::sync::RwLock<Option<ChangeFreeze>>>;
+
+/// Leading words of commands that change nothing beyond the session, on
+/// top of [`is_read_only_command`].
+const NON_CONFIG_STEMS: &[&str] = &[
+    "dir",
+    "get",
+    "more",
+    "pwd",
+    "screen-length 0 temporary",
+    "terminal length",
+    "terminal pager",
+    "terminal width",
+];
+
+/// True when a freeze lets `command` through: it is read-only, one of
+/// `terminal_setup`, or starts with a [`NON_CONFIG_STEMS`] entry.
+fn is_exempt(command: &str, terminal_setup: &[String]) -> bool {
+    let command = command.trim();
+    if is_read_only_command(command)
+        || terminal_setup
+            .iter()
+            .any(|setup| setup.trim().eq_ignore_ascii_case(command))
+    {
+        return true;
+    }
+    let words: Vec<String> = command
+        .split_whitespace()
+        .map(str::to_ascii_lowercase)
+        .collect();
+    NON_CONFIG_STEMS.iter().any(|stem| {
+        let stem: Vec<&str> = stem.split_whitespace().collect();
+        words.len() >= stem.len() && words.iter().zip(&stem).all(|(word, stem)| word == stem)
+    })
+}
+
+/// An active change freeze.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct ChangeFreeze {
+    /// Shown in rejections, e.g. `year-end freeze until 2027-01-04`.
+    pub reason: String,
+    /// Token that lets a job through; `None` admits no exceptions.
+    pub override_token: Option<String>,
+}
+
+impl std::fmt::Debug for ChangeFreeze {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ChangeFreeze")
+            .field("reason", &self.reason)
+            .field(
+                "override_token",
+                &self.override_token.as_ref().map(|_| "<redacted>"),
+            )
+            .finish()
+    }
+}
+
+impl ChangeFreeze {
+    /// Freeze without exceptions.
+    pub fn new(reason: impl Into<String>) -> Self {
+        Self {
+            reason: reason.into(),
+            override_token: None,
+        }
+    }
+
+    /// Let jobs presenting `token` through.
+    pub fn with_override_token(mut self, token: impl Into<String>) -> Self {
+        self.override_token = Some(token.into());
+        self
+    }
+
+    /// True when `token` matches the override token.
+    pub fn is_overridden_by(&self, token: Option<&str>) -> bool {
+        matches!((self.override_token.as_deref(), token), (Some(expected), Some(token)) if expected == token)
+    }
+
+    /// Rejects `command` unless it is read-only, one of the template's
+    /// `terminal_setup` commands or another command that only affects the
+    /// session.
+    pub(crate) fn check_command(
+        &self,
+        command: &str,
+        terminal_setup: &[String],
+    ) -> Result<(), ConnectError> {
+        if is_exempt(command, terminal_setup) {
+            return Ok(());
+        }
+        Err(self.rejection(command.trim().to_string()))
+    }
+
+    /// Rejects the first config block of `blocks`, and the first block
+    /// running or rolling back with a command [`Self::check_command`]
+    /// rejects, whatever its kind.
+    pub(crate) fn check_blocks<'a>(
+        &self,
+        blocks: impl IntoIterator<Item = &'a TxBlock>,
+        terminal_setup: &[String],
+    ) -> Result<(), ConnectError> {
+        for block in blocks {
+            if block.kind == CommandBlockKind::Config {
+                return Err(self.rejection(format!("tx block '{}'", block.name)));
+            }
+            let whole_resource = match &block.rollback_policy {
+                RollbackPolicy::WholeResource { rollback, .. } => Some(rollback.as_ref()),
+                _ => None,
+            };
+            let operations = block
+                .steps
+                .iter()
+                .flat_map(|step| std::iter::once(&step.run).chain(step.rollback.as_ref()))
+                .chain(whole_resource);
+            for operation in operations {
+                // Operations that do not render are rejected when they run.
+                let Ok(flow) = operation.to_command_flow() else {
+                    continue;
+                };
+                if let Some(command) = flow
+                    .steps
+                    .iter()
+                    .find(|command| !is_exempt(&command.command, terminal_setup))
+                {
+                    return Err(self.rejection(format!(
+                        "tx block '{}': {}",
+                        block.name,
+                        command.command.trim()
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn rejection(&self, target: String) -> ConnectError {
+        ConnectError::ChangeFrozen {
+            target,
+            reason: self.reason.clone(),
+        }
+    }
+}
+
+impl SshConnectionManager {
+    /// Start a change freeze on every connection, or lift it with `None`.
+    ///
+    /// Unlike other settings, the freeze applies to pooled connections at
+    /// once, including command senders handed out earlier.
+    pub fn set_change_freeze(&self, freeze: Option<ChangeFreeze>) {
+        *self
+            .change_freeze
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = freeze;
+    }
+
+    /// The active change freeze, if any.
+    pub fn change_freeze(&self) -> Option<ChangeFreeze> {
+        self.change_freeze
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn freeze_rejects_config_commands_and_blocks() {
+        let freeze = ChangeFreeze::new("year-end freeze").with_override_token("CHG-1234");
+        assert!(freeze.check_command("show running-config", &[]).is_ok());
+        assert!(freeze.check_command("display version", &[]).is_ok());
+        assert!(matches!(
+            freeze.check_command(" vlan 10 ", &[]),
+            Err(ConnectError::ChangeFrozen { target, reason })
+                if target == "vlan 10" && reason == "year-end freeze"
+        ));
+
+        let block = |name: &str, kind| TxBlock {
+            name: name.to_string(),
+            kind,
+            rollback_policy: RollbackPolicy::None,
+            steps: vec![TxStep::new(Command::show("show clock"))],
+            fail_fast: true,
+        };
+        let show = block("audit", CommandBlockKind::Show);
+        let config = block("vlans", CommandBlockKind::Config);
+        assert!(freeze.check_blocks([&show], &[]).is_ok());
+        assert!(matches!(
+            freeze.check_blocks([&show, &config], &[]),
+            Err(ConnectError::ChangeFrozen { target, .. }) if target == "tx block 'vlans'"
+        ));
+
+        // The block kind is not trusted: every step and rollback is checked.
+        let mut mislabeled = block("audit", CommandBlockKind::Show);
+        mislabeled
+            .steps
+            .push(TxStep::new(Command::show("no vlan 10")));
+        assert!(matches!(
+            freeze.check_blocks([&mislabeled], &[]),
+            Err(ConnectError::ChangeFrozen { target, .. }) if target == "tx block 'audit': no vlan 10"
+        ));
+        let mut undo = block("audit", CommandBlockKind::Show);
+        undo.steps[0].rollback = Some(SessionOperation::Command(Command::show("reload")));
+        assert!(matches!(
+            freeze.check_blocks([&undo], &[]),
+            Err(ConnectError::ChangeFrozen { target, .. }) if target == "tx block 'audit': reload"
+        ));
+        let mut whole = block("audit", CommandBlockKind::Show);
+        whole.rollback_policy = RollbackPolicy::WholeResource {
+            rollback: Box::new(SessionOperation::Command(Command::show(
+                "erase startup-config",
+            ))),
+            trigger_step_index: 0,
+        };
+        assert!(freeze.check_blocks([&whole], &[]).is_err());
+
+        assert!(freeze.is_overridden_by(Some("CHG-1234")));
+        assert!(!freeze.is_overridden_by(Some("CHG-9999")));
+        assert!(!freeze.is_overridden_by(None));
+        assert!(!ChangeFreeze::new("audit").is_overridden_by(Some("")));
+        assert!(!format!("{freeze:?}").contains("CHG-1234"));
+    }
+
+    #[test]
+    fn freeze_lets_session_commands_through() {
+        let freeze = ChangeFreeze::new("year-end freeze");
+        let cisco = crate::templates::cisco().expect("cisco");
+        for setup in cisco.terminal_setup() {
+            assert!(freeze.check_command(setup, cisco.terminal_setup()).is_ok());
+        }
+        let setup = ["terminal monitor".to_string()];
+        assert!(freeze.check_command("Terminal Monitor", &setup).is_ok());
+        assert!(freeze.check_command("terminal monitor", &[]).is_err());
+
+        for command in [
+            "terminal length 0",
+            "terminal width 512",
+            "screen-length 0 temporary",
+            "dir flash:",
+            "get system status",
+        ] {
+            assert!(freeze.check_command(command, &[]).is_ok(), "{command}");
+        }
+        for command in [
+            "screen-length 50",
+            "terminal",
+            "directory-set",
+            "set system",
+        ] {
+            assert!(freeze.check_command(command, &[]).is_err(), "{command}");
+        }
+
+        let mut block = TxBlock {
+            name: "audit".to_string(),
+            kind: CommandBlockKind::Show,
+            rollback_policy: RollbackPolicy::None,
+            steps: vec![TxStep::new(Command::show("terminal length 0"))],
+            fail_fast: true,
+        };
+        block.steps.push(TxStep::new(Command::show("dir")));
+        assert!(freeze.check_blocks([&block], &[]).is_ok());
+    }
+}
@@ -0,0 +1,238 @@
+//! Config changes on HA pairs.
+//!
+//! [`SshConnectionManager::execute_ha_workflow`] probes both members with a
+//! [`HaRoleProbe`], then runs the workflow on the active member only or on
+//! both in the [`HaApplyOrder`], re-probing around every member. When a role
+//! flips mid-change, or a member fails, the remaining members are skipped
+//! and the ones already changed are rolled back.
+
+use super::*;
+use crate::templates::{HaRole, HaRoleProbe};
+
+/// Which pair members get the change, and in what order.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum HaApplyOrder {
+    /// Only the active member; the standby picks the change up by config sync.
+    #[default]
+    ActiveOnly,
+    ActiveFirst,
+    StandbyFirst,
+}
+
+/// What happened on one member of the pair.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct HaMemberResult {
+    /// Index passed to `make_request`.
+    pub member: usize,
+    pub device_addr: String,
+    /// Role probed before the change started.
+    pub role: HaRole,
+    /// `None` when the workflow did not run on this member.
+    pub result: Option<TxWorkflowResult>,
+    /// Why the workflow did not run here, or why the change stopped after it.
+    pub stopped: Option<String>,
+}
+
+/// Result of [`SshConnectionManager::execute_ha_workflow`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct HaWorkflowResult {
+    /// True when every planned member committed and no role changed.
+    pub committed: bool,
+    /// A member's role changed while the change was in progress.
+    pub roles_flipped: bool,
+    /// Planned members in the order they were handled.
+    pub members: Vec<HaMemberResult>,
+}
+
+impl SshConnectionManager {
+    /// Role of one device as reported by `probe`; failed probes are
+    /// [`HaRole::Unknown`].
+    pub async fn probe_ha_role(
+        &self,
+        request: ConnectionRequest,
+        probe: &HaRoleProbe,
+        context: ExecutionContext,
+    ) -> Result<HaRole, ConnectError> {
+        let output = self
+            .execute_command_with_context(request, probe.command(), context)
+            .await?;
+        if !output.success {
+            return Ok(HaRole::Unknown);
+        }
+        probe.classify(&output.content)
+    }
+
+    /// Run `workflow` on an HA pair according to `order`.
+    ///
+    /// `make_request(0)` and `make_request(1)` build requests for the two
+    /// members; they are called again for every step, since a request is
+    /// consumed by each call. Fails with [`ConnectError::HaRolesUnclear`]
+    /// before changing anything unless exactly one member is active and the
+    /// other standby.
+    pub async fn execute_ha_workflow(
+        &self,
+        make_request: impl Fn(usize) -> Result<ConnectionRequest, ConnectError>,
+        probe: &HaRoleProbe,
+        order: HaApplyOrder,
+        workflow: TxWorkflow,
+        context: ExecutionContext,
+    ) -> Result<HaWorkflowResult, ConnectError> {
+        let mut roles = [HaRole::Unknown; 2];
+        for (member, role) in roles.iter_mut().enumerate() {
+            *role = self
+                .probe_ha_role(make_request(member)?, probe, context.clone())
+                .await?;
+        }
+        let plan = ha_apply_plan(roles, order)?;
+
+        let mut result = HaWorkflowResult {
+            committed: false,
+            roles_flipped: false,
+            members: Vec::new(),
+        };
+        let mut stopped: Option<String> = None;
+        for (position, &member) in plan.iter().enumerate() {
+            let mut entry = HaMemberResult {
+                member,
+                device_addr: make_request(member)?.device_addr(),
+                role: roles[member],
+                result: None,
+                stopped: stopped.clone(),
+            };
+            if stopped.is_some() {
+                result.members.push(entry);
+                continue;
+            }
+
+            // The first member was probed just now.
+            if position > 0 {
+                let role = self
+                    .probe_ha_role(make_request(member)?, probe, context.clone())
+                    .await;
+                if let Some(reason) = role_change(roles[member], role) {
+                    result.roles_flipped = true;
+                    stopped = Some(format!("member {member}: {reason} before the change"));
+                    entry.stopped = stopped.clone();
+                    result.members.push(entry);
+                    continue;
+                }
+            }
+
+            match self
+                .execute_tx_workflow_with_context(
+                    make_request(member)?,
+                    workflow.clone(),
+                    context.clone(),
+                )
+                .await
+            {
+                Ok(outcome) if outcome.committed => entry.result = Some(outcome),
+                Ok(outcome) => {
+                    stopped = Some(format!("member {member}: workflow failed"));
+                    entry.result = Some(outcome);
+                }
+                Err(err) => stopped = Some(format!("member {member}: {err}")),
+            }
+            if stopped.is_none() {
+                let role = self
+                    .probe_ha_role(make_request(member)?, probe, context.clone())
+                    .await;
+                if let Some(reason) = role_change(roles[member], role) {
+                    result.roles_flipped = true;
+                    stopped = Some(format!("member {member}: {reason} during the change"));
+                }
+            }
+            entry.stopped = stopped.clone();
+            result.members.push(entry);
+        }
+
+        if stopped.is_none() {
+            result.committed = true;
+            return Ok(result);
+        }
+        // Undo the members that committed, last first.
+        for entry in result.members.iter_mut().rev() {
+            if let Some(outcome) = entry.result.as_mut().filter(|outcome| outcome.committed) {
+                self.rollback_tx_workflow_with_context(
+                    make_request(entry.member)?,
+                    &workflow,
+                    outcome,
+                    context.clone(),
+                )
+                .await?;
+            }
+        }
+        Ok(result)
+    }
+}
+
+/// Member indices to change, in order, for the probed `roles`.
+fn ha_apply_plan(roles: [HaRole; 2], order: HaApplyOrder) -> Result<Vec<usize>, ConnectError> {
+    let active = roles.iter().position(|role| *role == HaRole::Active);
+    let standby = roles.iter().position(|role| *role == HaRole::Standby);
+    let (Some(active), Some(standby)) = (active, standby) else {
+        return Err(ConnectError::HaRolesUnclear(format!(
+            "expected one active and one standby member, probed {roles:?}"
+        )));
+    };
+    Ok(match order {
+        HaApplyOrder::ActiveOnly => vec![active],
+        HaApplyOrder::ActiveFirst => vec![active, standby],
+        HaApplyOrder::StandbyFirst => vec![standby, active],
+    })
+}
+
+/// Why a re-probe does not confirm `expected`, if it does not.
+fn role_change(expected: HaRole, probed: Result<HaRole, ConnectError>) -> Option<String> {
+    match probed {
+        Ok(role) if role == expected => None,
+        Ok(role) => Some(format!("role changed from {expected:?} to {role:?}")),
+        Err(err) => Some(format!("role probe failed: {err}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plan_orders_members_by_role() {
+        let roles = [HaRole::Standby, HaRole::Active];
+        assert_eq!(
+            ha_apply_plan(roles, HaApplyOrder::ActiveOnly).expect("plan"),
+            [1]
+        );
+        assert_eq!(
+            ha_apply_plan(roles, HaApplyOrder::ActiveFirst).expect("plan"),
+            [1, 0]
+        );
+        assert_eq!(
+            ha_apply_plan(roles, HaApplyOrder::StandbyFirst).expect("plan"),
+            [0, 1]
+        );
+        for roles in [
+            [HaRole::Active, HaRole::Active],
+            [HaRole::Standby, HaRole::Unknown],
+        ] {
+            assert!(matches!(
+                ha_apply_plan(roles, HaApplyOrder::ActiveOnly),
+                Err(ConnectError::HaRolesUnclear(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn role_change_reports_flips_and_probe_failures() {
+        assert_eq!(role_change(HaRole::Active, Ok(HaRole::Active)), None);
+        assert_eq!(
+            role_change(HaRole::Active, Ok(HaRole::Standby)).as_deref(),
+            Some("role changed from Active to Standby")
+        );
+        assert!(
+            role_change(HaRole::Standby, Err(ConnectError::ConnectClosedError))
+                .expect("reason")
+                .starts_with("role probe failed")
+        );
+    }
+}
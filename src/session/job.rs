@@ -0,0 +1,85 @@
+//! Settings that belong to one job rather than to the connection it runs on.
+//!
+//! A pooled connection serves jobs from many callers. What one caller's
+//! [`ExecutionContext`] asks for, such as stricter guardrails, a home state,
+//! configuration lock retries or a change freeze override token, travels
+//! with that caller's jobs and is installed on the client only while the job
+//! holds the execution lock, so it never leaks into another caller's jobs.
+
+use super::*;
+
+/// Per-job settings taken from the caller's [`ExecutionContext`].
+///
+/// The default applies only the manager's settings.
+#[derive(Debug, Clone, Default)]
+pub struct JobScope {
+    /// Guardrails checked on top of the manager-wide policy.
+    pub command_policy: Option<CommandPolicy>,
+    /// State the session is walked back to after the job.
+    pub home_state: Option<String>,
+    /// Retry commands that hit a configuration lock held by another user.
+    pub config_lock_retry: Option<RetryPolicy>,
+    /// Token presented to an active [`ChangeFreeze`].
+    pub freeze_override: Option<String>,
+    /// Run a journaled job again after an earlier run errored on the device.
+    pub rerun_failed: bool,
+}
+
+impl From<&ExecutionContext> for JobScope {
+    fn from(context: &ExecutionContext) -> Self {
+        Self {
+            command_policy: context.command_policy.clone(),
+            home_state: context.home_state.clone(),
+            config_lock_retry: context.config_lock_retry,
+            freeze_override: context.freeze_override.clone(),
+            rerun_failed: context.rerun_failed,
+        }
+    }
+}
+
+impl SharedSshClient {
+    /// Install the settings of the job now holding this client's lock.
+    pub(crate) fn set_job_scope(&mut self, scope: JobScope) {
+        self.scope = scope;
+    }
+
+    /// The change freeze binding on the current job, unless it presents
+    /// the override token.
+    pub(crate) fn change_freeze(&self) -> Option<ChangeFreeze> {
+        self.status
+            .change_freeze()
+            .filter(|freeze| !freeze.is_overridden_by(self.scope.freeze_override.as_deref()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scope_takes_the_callers_settings_only() {
+        let strict = ExecutionContext::new()
+            .with_command_policy(CommandPolicy::destructive_defaults())
+            .with_home_state("Enable")
+            .with_config_lock_retry(RetryPolicy::new(5, 30))
+            .with_freeze_override("CHG-1")
+            .with_rerun_failed(true);
+        let scope = JobScope::from(&strict);
+        assert!(
+            scope
+                .command_policy
+                .is_some_and(|policy| policy.check("reload").is_err())
+        );
+        assert_eq!(scope.home_state.as_deref(), Some("Enable"));
+        assert_eq!(scope.config_lock_retry.map(|retry| retry.attempts), Some(5));
+        assert_eq!(scope.freeze_override.as_deref(), Some("CHG-1"));
+        assert!(scope.rerun_failed);
+
+        let plain = JobScope::from(&ExecutionContext::new());
+        assert!(plain.command_policy.is_none());
+        assert!(plain.home_state.is_none());
+        assert!(plain.config_lock_retry.is_none());
+        assert!(plain.freeze_override.is_none());
+        assert!(!plain.rerun_failed);
+    }
+}
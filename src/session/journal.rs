@@ -0,0 +1,674 @@
+//! Durable record of submitted jobs and their results.
+//!
+//! With an [`ExecutionJournal`] installed on the manager, every operation,
+//! transaction block and workflow whose context carries an
+//! [idempotency key](ExecutionContext::with_idempotency_key) is written to
+//! the journal before it is sent and again when it finishes. After a crash,
+//! entries still [`JournalState::Submitted`] are the changes that may or may
+//! not have reached their device.
+//!
+//! A job submitted again under a key whose entry has a result is not sent;
+//! the stored result is returned instead, so a client retrying after a lost
+//! reply cannot push the same change twice. [`CmdJob::idempotency_key`] and
+//! [`TxWorkflow::idempotency_key`] set the key on the job itself.
+//!
+//! A key whose job errored before anything was sent
+//! ([`JournalState::NotSent`]) may be submitted again. One whose job errored
+//! after reaching the device ([`JournalState::Failed`]) may have applied part
+//! of its change, so it is only run again under
+//! [`ExecutionContext::with_rerun_failed`].
+//!
+//! [`MemoryJournal`] keeps entries for the life of the process; the
+//! SQLite-backed `SqliteJournal` in [`crate::journal`] (feature `journal`)
+//! survives restarts.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::*;
+
+/// Where a journaled job got to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum JournalState {
+    /// Recorded before sending; no result was written back.
+    Submitted,
+    /// Finished and reported success.
+    Succeeded,
+    /// Finished with an unsuccessful result, or with an error that may
+    /// have been raised after reaching the device.
+    Failed,
+    /// Finished with an error raised before anything was sent, e.g. a
+    /// refused login or failed pre-flight checks.
+    NotSent,
+}
+
+impl JournalState {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Submitted => "submitted",
+            Self::Succeeded => "succeeded",
+            Self::Failed => "failed",
+            Self::NotSent => "not_sent",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "submitted" => Some(Self::Submitted),
+            "succeeded" => Some(Self::Succeeded),
+            "failed" => Some(Self::Failed),
+            "not_sent" => Some(Self::NotSent),
+            _ => None,
+        }
+    }
+}
+
+/// One journaled job.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct JournalEntry {
+    /// Idempotency key the job was submitted with.
+    pub key: String,
+    /// Connection cache key of the target device.
+    pub device_addr: String,
+    /// `operation`, `tx_block` or `tx_workflow`.
+    pub kind: String,
+    /// The submitted job as JSON.
+    pub job: serde_json::Value,
+    pub state: JournalState,
+    /// The job's result as JSON, once finished without an error.
+    #[serde(default)]
+    pub result: Option<serde_json::Value>,
+    /// Error message, once finished with an error.
+    #[serde(default)]
+    pub error: Option<String>,
+    /// Unix seconds when the job was submitted.
+    pub submitted_at: u64,
+    /// Unix seconds when the job finished.
+    #[serde(default)]
+    pub finished_at: Option<u64>,
+}
+
+/// Store for [`JournalEntry`] records, keyed by idempotency key.
+///
+/// Calls are synchronous and made on the executing task, so
+/// implementations should keep writes short.
+pub trait ExecutionJournal: Send + Sync {
+    /// Insert `entry`, replacing any entry with the same key.
+    fn record(&self, entry: &JournalEntry) -> Result<(), ConnectError>;
+
+    /// Claim `entry.key` for a new submission and record `entry`.
+    ///
+    /// The claim succeeds when no entry has the key, or when the entry for
+    /// the same device and job kind ended with an error and no result,
+    /// which is then replaced. An entry [`JournalState::Failed`] is only
+    /// replaced when `rerun_failed` is set; one [`JournalState::NotSent`]
+    /// always is. Returns `false`, recording nothing, in every other case.
+    /// Of two concurrent claims for one key, at most one may succeed.
+    fn try_submit(&self, entry: &JournalEntry, rerun_failed: bool) -> Result<bool, ConnectError>;
+
+    /// The entry recorded under `key`, if any.
+    fn get(&self, key: &str) -> Result<Option<JournalEntry>, ConnectError>;
+
+    /// Every entry, oldest submission first.
+    fn entries(&self) -> Result<Vec<JournalEntry>, ConnectError>;
+
+    /// Entries submitted but never finished, e.g. because the process
+    /// crashed while they ran.
+    fn in_doubt(&self) -> Result<Vec<JournalEntry>, ConnectError> {
+        Ok(self
+            .entries()?
+            .into_iter()
+            .filter(|entry| entry.state == JournalState::Submitted)
+            .collect())
+    }
+}
+
+/// Journal kept in memory for the life of the process.
+#[derive(Debug, Default)]
+pub struct MemoryJournal {
+    entries: std::sync::Mutex<Vec<JournalEntry>>,
+}
+
+impl MemoryJournal {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl ExecutionJournal for MemoryJournal {
+    fn record(&self, entry: &JournalEntry) -> Result<(), ConnectError> {
+        let mut entries = self
+            .entries
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        match entries
+            .iter_mut()
+            .find(|existing| existing.key == entry.key)
+        {
+            Some(existing) => *existing = entry.clone(),
+            None => entries.push(entry.clone()),
+        }
+        Ok(())
+    }
+
+    fn try_submit(&self, entry: &JournalEntry, rerun_failed: bool) -> Result<bool, ConnectError> {
+        let mut entries = self
+            .entries
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        match entries
+            .iter_mut()
+            .find(|existing| existing.key == entry.key)
+        {
+            Some(existing) if is_rerunnable(existing, entry, rerun_failed) => {
+                *existing = entry.clone()
+            }
+            Some(_) => return Ok(false),
+            None => entries.push(entry.clone()),
+        }
+        Ok(true)
+    }
+
+    fn get(&self, key: &str) -> Result<Option<JournalEntry>, ConnectError> {
+        Ok(self
+            .entries
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .iter()
+            .find(|entry| entry.key == key)
+            .cloned())
+    }
+
+    fn entries(&self) -> Result<Vec<JournalEntry>, ConnectError> {
+        Ok(self
+            .entries
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone())
+    }
+}
+
+/// True when `existing` ended with an error on the device and job kind of
+/// `entry`, so a new submission may take its key. Jobs that errored after
+/// reaching the device only qualify with `rerun_failed`.
+fn is_rerunnable(existing: &JournalEntry, entry: &JournalEntry, rerun_failed: bool) -> bool {
+    let errored = match existing.state {
+        JournalState::NotSent => true,
+        JournalState::Failed => rerun_failed,
+        JournalState::Submitted | JournalState::Succeeded => false,
+    };
+    errored
+        && existing.result.is_none()
+        && existing.device_addr == entry.device_addr
+        && existing.kind == entry.kind
+}
+
+/// True when `err` was raised before the job sent anything to the device.
+fn never_sent(err: &ConnectError) -> bool {
+    matches!(
+        err.root_cause(),
+        ConnectError::Unreachable { .. }
+            | ConnectError::AuthenticationFailed { .. }
+            | ConnectError::HostKeyRejected(_)
+            | ConnectError::NoCompatibleAlgorithms { .. }
+            | ConnectError::AuthMethodNotSupported(_)
+            | ConnectError::InitTimeout(_)
+            | ConnectError::SessionRejected(_)
+            | ConnectError::QuotaExceeded { .. }
+            | ConnectError::TemplateNotFound(_)
+            | ConnectError::PreflightFailed(_)
+    )
+}
+
+pub(crate) fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+fn to_json<T: Serialize>(value: &T) -> Result<serde_json::Value, ConnectError> {
+    serde_json::to_value(value)
+        .map_err(|err| ConnectError::JournalError(format!("cannot encode job: {err}")))
+}
+
+/// How a journaled job proceeds.
+pub(crate) enum JournalStart<R> {
+    /// Already completed under this key; return the stored result.
+    Replay(Box<R>),
+    /// Send the job, then record its outcome on the pending entry.
+    Run(Option<PendingEntry>),
+}
+
+/// Look `key` up in `journal` and either replay its result or claim the key
+/// and record `job` as submitted.
+///
+/// Entries that errored before sending are run again, and so are ones that
+/// errored on the device when `rerun_failed` is set. Fails without sending
+/// when the key is still marked submitted, errored on the device without
+/// `rerun_failed`, belongs to another device or job kind, or the journal
+/// cannot be written. When another submission claims
+/// the key first, its result is replayed if it has one and the job fails
+/// otherwise.
+pub(crate) fn start<T: Serialize, R: serde::de::DeserializeOwned>(
+    journal: Option<Arc<dyn ExecutionJournal>>,
+    key: Option<&str>,
+    device_addr: &str,
+    kind: &str,
+    job: &T,
+    rerun_failed: bool,
+) -> Result<JournalStart<R>, ConnectError> {
+    let (Some(journal), Some(key)) = (journal, key) else {
+        return Ok(JournalStart::Run(None));
+    };
+    if let Some(previous) = journal.get(key)?
+        && let Some(replay) = resume(previous, device_addr, kind, rerun_failed)?
+    {
+        return Ok(replay);
+    }
+    let entry = JournalEntry {
+        key: key.to_string(),
+        device_addr: device_addr.to_string(),
+        kind: kind.to_string(),
+        job: to_json(job)?,
+        state: JournalState::Submitted,
+        result: None,
+        error: None,
+        submitted_at: unix_now(),
+        finished_at: None,
+    };
+    if journal.try_submit(&entry, rerun_failed)? {
+        return Ok(JournalStart::Run(Some(PendingEntry { journal, entry })));
+    }
+    debug!(
+        "Idempotency key {} claimed concurrently on {}",
+        key, device_addr
+    );
+    match journal.get(key)? {
+        Some(previous) => resume(previous, device_addr, kind, rerun_failed)?,
+        None => None,
+    }
+    .ok_or_else(|| {
+        ConnectError::JournalError(format!(
+            "job {key} was claimed by another submission; check {device_addr} before retrying"
+        ))
+    })
+}
+
+/// What to do with the job whose key already has `previous`: fail, replay
+/// its result, or `None` to run it again.
+fn resume<R: serde::de::DeserializeOwned>(
+    previous: JournalEntry,
+    device_addr: &str,
+    kind: &str,
+    rerun_failed: bool,
+) -> Result<Option<JournalStart<R>>, ConnectError> {
+    let key = &previous.key;
+    if previous.device_addr != device_addr || previous.kind != kind {
+        return Err(ConnectError::JournalError(format!(
+            "idempotency key {key} belongs to a {} job on {}",
+            previous.kind, previous.device_addr
+        )));
+    }
+    if previous.state == JournalState::Submitted {
+        return Err(ConnectError::JournalError(format!(
+            "job {key} was submitted before and never finished; check {device_addr} before retrying"
+        )));
+    }
+    let Some(result) = previous.result else {
+        if previous.state == JournalState::Failed && !rerun_failed {
+            return Err(ConnectError::JournalError(format!(
+                "job {key} failed after reaching {device_addr}; check the device, then retry with ExecutionContext::with_rerun_failed"
+            )));
+        }
+        return Ok(None);
+    };
+    debug!("Replaying journaled result of {} on {}", key, device_addr);
+    serde_json::from_value(result)
+        .map(|result| Some(JournalStart::Replay(Box::new(result))))
+        .map_err(|err| ConnectError::JournalError(format!("cannot decode result of {key}: {err}")))
+}
+
+/// A submitted entry waiting for its result.
+pub(crate) struct PendingEntry {
+    journal: Arc<dyn ExecutionJournal>,
+    entry: JournalEntry,
+}
+
+impl PendingEntry {
+    /// Record the outcome; a failed write is logged, since the job already ran.
+    pub(crate) fn finish<T: Serialize>(
+        mut self,
+        result: Result<&T, &ConnectError>,
+        success: impl FnOnce(&T) -> bool,
+    ) {
+        match result {
+            Ok(output) => {
+                self.entry.state = if success(output) {
+                    JournalState::Succeeded
+                } else {
+                    JournalState::Failed
+                };
+                match to_json(output) {
+                    Ok(value) => self.entry.result = Some(value),
+                    Err(err) => self.entry.error = Some(err.to_string()),
+                }
+            }
+            Err(err) => {
+                self.entry.state = if never_sent(err) {
+                    JournalState::NotSent
+                } else {
+                    JournalState::Failed
+                };
+                self.entry.error = Some(err.to_string());
+            }
+        }
+        self.entry.finished_at = Some(unix_now());
+        if let Err(err) = self.journal.record(&self.entry) {
+            warn!("Journal entry {} not finished: {}", self.entry.key, err);
+        }
+    }
+}
+
+impl SshConnectionManager {
+    /// Record jobs carrying an idempotency key in `journal`.
+    pub fn set_journal(&self, journal: Arc<dyn ExecutionJournal>) {
+        *self
+            .journal
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(journal);
+    }
+
+    /// Stop journaling jobs.
+    pub fn clear_journal(&self) {
+        *self
+            .journal
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = None;
+    }
+
+    /// Returns the currently installed journal, if any.
+    pub fn journal(&self) -> Option<Arc<dyn ExecutionJournal>> {
+        self.journal
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+    }
+
+    /// [`start`] against the installed journal.
+    pub(crate) fn journal_start<T: Serialize, R: serde::de::DeserializeOwned>(
+        &self,
+        key: Option<&str>,
+        device_addr: &str,
+        kind: &str,
+        job: &T,
+        rerun_failed: bool,
+    ) -> Result<JournalStart<R>, ConnectError> {
+        start(self.journal(), key, device_addr, kind, job, rerun_failed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn memory_journal_replaces_entries_by_key() {
+        let journal = MemoryJournal::new();
+        let mut entry = JournalEntry {
+            key: "chg-1".to_string(),
+            device_addr: "admin@192.0.2.1:22".to_string(),
+            kind: "tx_block".to_string(),
+            job: serde_json::json!({"name": "vlan"}),
+            state: JournalState::Submitted,
+            result: None,
+            error: None,
+            submitted_at: 1,
+            finished_at: None,
+        };
+        journal.record(&entry).expect("record");
+        assert_eq!(journal.in_doubt().expect("in doubt").len(), 1);
+
+        entry.state = JournalState::Succeeded;
+        entry.finished_at = Some(2);
+        journal.record(&entry).expect("record");
+        assert_eq!(journal.entries().expect("entries"), [entry.clone()]);
+        assert!(journal.in_doubt().expect("in doubt").is_empty());
+        assert_eq!(journal.get("chg-1").expect("get"), Some(entry));
+        assert_eq!(journal.get("chg-2").expect("get"), None);
+    }
+
+    #[test]
+    fn concurrent_submissions_claim_a_key_once() {
+        let journal: Arc<dyn ExecutionJournal> = Arc::new(MemoryJournal::new());
+        let started: Vec<_> = (0..8)
+            .map(|_| {
+                let journal = journal.clone();
+                std::thread::spawn(move || {
+                    start::<_, Output>(
+                        Some(journal),
+                        Some("chg-1"),
+                        "admin@192.0.2.1:22",
+                        "operation",
+                        &serde_json::json!({"command": "vlan 10"}),
+                        false,
+                    )
+                })
+            })
+            .collect();
+        let claimed = started
+            .into_iter()
+            .map(|handle| handle.join().expect("thread"))
+            .filter(|start| matches!(start, Ok(JournalStart::Run(Some(_)))))
+            .count();
+        assert_eq!(claimed, 1);
+        assert_eq!(journal.in_doubt().expect("in doubt").len(), 1);
+    }
+
+    #[tokio::test]
+    async fn unreachable_jobs_are_journaled_as_not_sent() {
+        let manager = SshConnectionManager::new();
+        manager.set_connect_timeout(Duration::from_millis(200));
+        let journal = Arc::new(MemoryJournal::new());
+        manager.set_journal(journal.clone());
+        let request = || {
+            ConnectionRequest::new(
+                "admin".to_string(),
+                "127.0.0.1".to_string(),
+                1,
+                "secret".to_string(),
+                None,
+                crate::templates::cisco().expect("cisco"),
+            )
+        };
+        let block = TxBlock {
+            name: "vlan".to_string(),
+            kind: CommandBlockKind::Config,
+            rollback_policy: RollbackPolicy::None,
+            steps: Vec::new(),
+            fail_fast: true,
+        };
+
+        let result = manager
+            .execute_tx_block_with_context(request(), block.clone(), ExecutionContext::new())
+            .await;
+        assert!(result.is_err());
+        assert!(journal.entries().expect("entries").is_empty());
+
+        let result = manager
+            .execute_tx_block_with_context(
+                request(),
+                block,
+                ExecutionContext::new().with_idempotency_key("chg-1"),
+            )
+            .await;
+        assert!(result.is_err());
+        let entry = journal.get("chg-1").expect("get").expect("entry");
+        assert_eq!(entry.state, JournalState::NotSent);
+        assert_eq!(entry.kind, "tx_block");
+        assert_eq!(entry.device_addr, "admin@127.0.0.1:1");
+        assert_eq!(entry.job["name"], "vlan");
+        assert!(entry.error.is_some());
+    }
+
+    #[tokio::test]
+    async fn completed_keys_replay_their_result_without_connecting() {
+        let manager = SshConnectionManager::new();
+        manager.set_connect_timeout(Duration::from_millis(200));
+        let journal = Arc::new(MemoryJournal::new());
+        manager.set_journal(journal.clone());
+        let request = || {
+            ConnectionRequest::new(
+                "admin".to_string(),
+                "127.0.0.1".to_string(),
+                1,
+                "secret".to_string(),
+                None,
+                crate::templates::cisco().expect("cisco"),
+            )
+        };
+        let workflow = |key: &str| TxWorkflow {
+            name: "core-vlans".to_string(),
+            blocks: Vec::new(),
+            fail_fast: true,
+            preflight: false,
+            idempotency_key: Some(key.to_string()),
+        };
+        let stored = TxWorkflowResult {
+            workflow_name: "core-vlans".to_string(),
+            committed: true,
+            failed_block: None,
+            block_results: Vec::new(),
+            rollback_attempted: false,
+            rollback_succeeded: false,
+            rollback_errors: Vec::new(),
+        };
+        let entry = |key: &str, state, result: Option<&TxWorkflowResult>| JournalEntry {
+            key: key.to_string(),
+            device_addr: "admin@127.0.0.1:1".to_string(),
+            kind: "tx_workflow".to_string(),
+            job: serde_json::json!({}),
+            state,
+            result: result.map(|result| serde_json::to_value(result).expect("json")),
+            error: None,
+            submitted_at: 1,
+            finished_at: Some(2),
+        };
+        journal
+            .record(&entry("done", JournalState::Succeeded, Some(&stored)))
+            .expect("record");
+        journal
+            .record(&entry("in-flight", JournalState::Submitted, None))
+            .expect("record");
+        journal
+            .record(&entry("errored", JournalState::Failed, None))
+            .expect("record");
+        journal
+            .record(&entry("not-sent", JournalState::NotSent, None))
+            .expect("record");
+
+        let replayed = manager
+            .execute_tx_workflow_with_context(request(), workflow("done"), ExecutionContext::new())
+            .await
+            .expect("replayed");
+        assert_eq!(replayed, stored);
+
+        let in_flight = manager
+            .execute_tx_workflow_with_context(
+                request(),
+                workflow("in-flight"),
+                ExecutionContext::new(),
+            )
+            .await;
+        assert!(matches!(in_flight, Err(ConnectError::JournalError(_))));
+
+        let wrong_kind = manager
+            .execute_tx_block_with_context(
+                request(),
+                TxBlock {
+                    name: "vlan".to_string(),
+                    kind: CommandBlockKind::Config,
+                    rollback_policy: RollbackPolicy::None,
+                    steps: Vec::new(),
+                    fail_fast: true,
+                },
+                ExecutionContext::new().with_idempotency_key("done"),
+            )
+            .await;
+        assert!(matches!(wrong_kind, Err(ConnectError::JournalError(_))));
+
+        let unsent = manager
+            .execute_tx_workflow_with_context(
+                request(),
+                workflow("not-sent"),
+                ExecutionContext::new(),
+            )
+            .await;
+        assert!(!matches!(unsent, Err(ConnectError::JournalError(_))));
+        let entry = journal.get("not-sent").expect("get").expect("entry");
+        assert!(entry.error.is_some());
+        assert_eq!(entry.job["name"], "core-vlans");
+
+        let held = manager
+            .execute_tx_workflow_with_context(
+                request(),
+                workflow("errored"),
+                ExecutionContext::new(),
+            )
+            .await;
+        assert!(matches!(held, Err(ConnectError::JournalError(_))));
+        let entry = journal.get("errored").expect("get").expect("entry");
+        assert!(entry.error.is_none());
+
+        let rerun = manager
+            .execute_tx_workflow_with_context(
+                request(),
+                workflow("errored"),
+                ExecutionContext::new().with_rerun_failed(true),
+            )
+            .await;
+        assert!(!matches!(rerun, Err(ConnectError::JournalError(_))));
+        let entry = journal.get("errored").expect("get").expect("entry");
+        assert!(entry.error.is_some());
+        assert_eq!(entry.job["name"], "core-vlans");
+    }
+
+    #[test]
+    fn only_errors_raised_before_sending_are_marked_not_sent() {
+        let journal: Arc<dyn ExecutionJournal> = Arc::new(MemoryJournal::new());
+        let finish = |key: &str, err: ConnectError| {
+            let Ok(JournalStart::Run(Some(pending))) = start::<_, Output>(
+                Some(journal.clone()),
+                Some(key),
+                "admin@192.0.2.1:22",
+                "operation",
+                &serde_json::json!({"command": "vlan 10"}),
+                false,
+            ) else {
+                panic!("key {key} not claimed");
+            };
+            pending.finish::<Output>(Err(&err), |output| output.success);
+            journal.get(key).expect("get").expect("entry").state
+        };
+
+        let refused = ConnectError::AuthenticationFailed {
+            user: "admin".to_string(),
+            method: "password".to_string(),
+        }
+        .with_context(ErrorContext::new("admin@192.0.2.1:22", Duration::ZERO));
+        assert_eq!(finish("login", refused), JournalState::NotSent);
+        assert_eq!(
+            finish("timeout", ConnectError::ExecTimeout("vlan 10".to_string())),
+            JournalState::Failed
+        );
+
+        let claim = |key: &str, rerun_failed| {
+            let mut entry = journal.get(key).expect("get").expect("entry");
+            entry.state = JournalState::Submitted;
+            journal.try_submit(&entry, rerun_failed).expect("claim")
+        };
+        assert!(claim("login", false));
+        assert!(!claim("timeout", false));
+        assert!(claim("timeout", true));
+    }
+}
@@ -0,0 +1,240 @@
+//! Prompt learning for devices no template matches.
+//!
+//! [`SshConnectionManager::learn_handler`] logs in with a bare shell, sends
+//! empty lines and takes the line the device repeats after each one as its
+//! prompt. The synthesized handler has a single state matching exactly that
+//! prompt, so commands can run and complete, but nothing more: see
+//! [`LearnedLimitation`].
+
+use super::*;
+use crate::device::{DeviceHandlerConfig, prompt_rule};
+
+/// State name of learned handlers.
+pub const LEARNED_STATE: &str = "Learned";
+
+/// Bare newlines sent before giving up.
+const LEARN_PROBES: usize = 3;
+
+/// Silence that ends one read.
+const LEARN_QUIET: Duration = Duration::from_millis(800);
+
+/// What a learned handler cannot do compared to a template.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum LearnedLimitation {
+    /// Only the learned state exists; commands needing another mode fail.
+    NoTransitions,
+    /// No error patterns are known; every command that returns to the
+    /// prompt is reported successful.
+    NoErrorDetection,
+}
+
+/// Result of [`SshConnectionManager::learn_handler`].
+pub struct LearnedHandler {
+    /// The repeated trailing line, e.g. `edge-01>`.
+    pub prompt: String,
+    /// Configuration the handler was built from; extend it and rebuild to
+    /// lift some of the limitations.
+    pub config: DeviceHandlerConfig,
+    pub handler: DeviceHandler,
+    pub limitations: Vec<LearnedLimitation>,
+}
+
+impl LearnedHandler {
+    /// Handler for `prompt` with its single state named [`LEARNED_STATE`].
+    pub fn from_prompt(prompt: &str) -> Result<Self, ConnectError> {
+        let config = learned_handler_config(prompt);
+        Ok(Self {
+            prompt: prompt.to_string(),
+            handler: config.build()?,
+            config,
+            limitations: vec![
+                LearnedLimitation::NoTransitions,
+                LearnedLimitation::NoErrorDetection,
+            ],
+        })
+    }
+}
+
+impl SshConnectionManager {
+    /// Log in to `addr` without a template and learn its prompt.
+    ///
+    /// Use the returned [`LearnedHandler::handler`] in a [`ConnectionRequest`]
+    /// like any template. Fails with [`ConnectError::TemplateNotDetected`]
+    /// when no line repeats after a few empty lines.
+    pub async fn learn_handler(
+        &self,
+        addr: &str,
+        port: u16,
+        credentials: DeviceCredentials,
+        context: ExecutionContext,
+    ) -> Result<LearnedHandler, ConnectError> {
+        let device_addr = format!("{}@{}", credentials.user, host_port(addr, port));
+        let config = Config {
+            preferred: context.security_options.preferred(),
+            inactivity_timeout: Some(Duration::from_secs(60)),
+            ..Default::default()
+        };
+        let connect_timeout = self.connect_timeout();
+        let client = tokio::time::timeout(connect_timeout, async {
+            let resolved = address::ResolvedAddrs::lookup(
+                addr,
+                port,
+                context.address_family,
+                self.host_resolver().as_deref(),
+            )
+            .await?;
+            Client::connect_with_config(
+                resolved,
+                &credentials.user,
+                credentials.auth_method(),
+                context.security_options.server_check.clone(),
+                config,
+            )
+            .await
+            .map_err(|err| {
+                client::classify_connect_error(
+                    err,
+                    addr,
+                    &credentials.user,
+                    credentials.auth_method_name(),
+                )
+            })
+        })
+        .await
+        .map_err(|_| {
+            ConnectError::InitTimeout(format!(
+                "{device_addr} not ready within {}s",
+                connect_timeout.as_secs()
+            ))
+        })??;
+        let mut channel = client.get_channel().await?;
+        channel
+            .request_pty(
+                false,
+                "xterm",
+                crate::device::DEFAULT_TERMINAL_WIDTH,
+                600,
+                0,
+                0,
+                &[],
+            )
+            .await?;
+        channel.request_shell(false).await?;
+
+        let mut output = Vec::new();
+        let mut learned = None;
+        // The login banner and first prompt, then one empty line per probe.
+        for probe in 0..=LEARN_PROBES {
+            if probe > 0 {
+                channel.data(&b"\n"[..]).await?;
+            }
+            read_until_quiet(&mut channel, &mut output).await;
+            learned = learned_prompt(&String::from_utf8_lossy(&output));
+            if learned.is_some() {
+                break;
+            }
+        }
+        let _ = channel.eof().await;
+        let _ = client.disconnect().await;
+
+        let prompt = learned.ok_or_else(|| ConnectError::TemplateNotDetected {
+            addr: device_addr.clone(),
+            reason: format!("no prompt repeated after {LEARN_PROBES} empty lines"),
+        })?;
+        debug!("{} learned prompt {:?}", device_addr, prompt);
+        LearnedHandler::from_prompt(&prompt)
+    }
+}
+
+async fn read_until_quiet(channel: &mut russh::Channel<russh::client::Msg>, output: &mut Vec<u8>) {
+    while let Ok(Some(msg)) = tokio::time::timeout(LEARN_QUIET, channel.wait()).await {
+        match msg {
+            ChannelMsg::Data { ref data } => output.extend_from_slice(data),
+            ChannelMsg::Eof | ChannelMsg::ExitStatus { .. } => break,
+            _ => {}
+        }
+    }
+}
+
+/// The trailing line of `output` when the line before it is the same.
+fn learned_prompt(output: &str) -> Option<String> {
+    let mut lines = output
+        .split('\n')
+        .map(|line| {
+            // A carriage return redraws the line; keep what was drawn last.
+            let line = line.trim_end_matches('\r');
+            line.rsplit('\r').next().unwrap_or(line).trim()
+        })
+        .filter(|line| !line.is_empty())
+        .rev();
+    let last = lines.next()?;
+    (lines.next() == Some(last)).then(|| last.to_string())
+}
+
+fn learned_handler_config(prompt: &str) -> DeviceHandlerConfig {
+    let pattern = format!(r"^{}\s*$", regex::escape(prompt));
+    DeviceHandlerConfig {
+        prompt: vec![prompt_rule(LEARNED_STATE, &[&pattern])],
+        more_regex: vec![r"(?i)-+\s*more\s*-+".to_string()],
+        ..DeviceHandlerConfig::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn learns_the_repeated_trailing_line() {
+        let output = "\r\nWelcome to edge-01\r\n\r\nedge-01> \r\nedge-01> \r\nedge-01> ";
+        assert_eq!(learned_prompt(output).as_deref(), Some("edge-01>"));
+        assert_eq!(
+            learned_prompt("banner\r\n\r[admin@fw ~]$ \r\n[admin@fw ~]$ ").as_deref(),
+            Some("[admin@fw ~]$")
+        );
+        assert_eq!(learned_prompt("Last login: today\r\nedge-01> "), None);
+        assert_eq!(learned_prompt(""), None);
+    }
+
+    #[test]
+    fn learned_handler_has_a_single_state_matching_the_prompt() {
+        let learned = LearnedHandler::from_prompt("[admin@fw ~]$").expect("handler");
+        let mut handler = learned.handler;
+        assert!(handler.read_prompt("[admin@fw ~]$ "));
+        assert!(!handler.read_prompt("[admin@fw ~]# "));
+        handler.read("[admin@fw ~]$ ");
+        assert_eq!(handler.current_state(), "learned");
+        assert!(learned.config.edges.is_empty());
+        assert!(learned.config.error_regex.is_empty());
+        assert_eq!(
+            learned.limitations,
+            [
+                LearnedLimitation::NoTransitions,
+                LearnedLimitation::NoErrorDetection
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn learning_gives_up_after_the_connect_timeout() {
+        // Accepts TCP but never sends an SSH banner.
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("bind");
+        let port = listener.local_addr().expect("addr").port();
+        let manager = SshConnectionManager::new();
+        manager.set_connect_timeout(Duration::from_millis(200));
+
+        let result = manager
+            .learn_handler(
+                "127.0.0.1",
+                port,
+                DeviceCredentials::password("admin", "secret"),
+                ExecutionContext::new(),
+            )
+            .await;
+        assert!(matches!(result, Err(ConnectError::InitTimeout(_))));
+        drop(listener);
+    }
+}
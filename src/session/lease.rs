@@ -0,0 +1,224 @@
+//! Exclusive, time-bounded use of a pooled connection.
+//!
+//! [`SshConnectionManager::lease`] locks a device's pooled session for one
+//! caller, such as an interactive troubleshooting tool. Commands, flows and
+//! transactions sent to that device through the manager wait until the
+//! lease is dropped, released or expires.
+//!
+//! A lease can pin a sys context, e.g. a vsys, so commands sent through it
+//! target that context without passing `sys` each time.
+
+use tokio::sync::{Mutex, OwnedRwLockWriteGuard};
+use tokio::task::JoinHandle;
+
+use super::*;
+
+/// A write guard given back when its deadline passes or the hold is dropped.
+struct Hold<T> {
+    guard: Arc<Mutex<Option<OwnedRwLockWriteGuard<T>>>>,
+    expiry: JoinHandle<()>,
+}
+
+impl<T: Send + Sync + 'static> Hold<T> {
+    fn new(guard: OwnedRwLockWriteGuard<T>, duration: Duration, device_addr: String) -> Self {
+        let guard = Arc::new(Mutex::new(Some(guard)));
+        let expiring = guard.clone();
+        let expiry = tokio::spawn(async move {
+            tokio::time::sleep(duration).await;
+            if expiring.lock().await.take().is_some() {
+                debug!("Lease on {} expired", device_addr);
+            }
+        });
+        Self { guard, expiry }
+    }
+}
+
+impl<T> Drop for Hold<T> {
+    fn drop(&mut self) {
+        self.expiry.abort();
+        if let Ok(mut guard) = self.guard.try_lock() {
+            guard.take();
+        }
+    }
+}
+
+/// Exclusive hold on one pooled session, released on drop or expiry.
+pub struct SessionLease {
+    device_addr: String,
+    expires_at: Instant,
+    client: Hold<SharedSshClient>,
+    status: Arc<status::ConnectionStatus>,
+    pinned_sys: Option<String>,
+}
+
+impl SessionLease {
+    /// Cache key of the leased session.
+    pub fn device_addr(&self) -> &str {
+        &self.device_addr
+    }
+
+    /// Time left before the lease is released on its own.
+    pub fn remaining(&self) -> Duration {
+        self.expires_at.saturating_duration_since(Instant::now())
+    }
+
+    /// Send later commands that pass no `sys` to `sys`; `None` unpins.
+    pub fn pin_sys(&mut self, sys: Option<String>) {
+        self.pinned_sys = sys;
+    }
+
+    /// Sys context used by commands that pass no `sys`.
+    pub fn pinned_sys(&self) -> Option<&str> {
+        self.pinned_sys.as_deref()
+    }
+
+    /// Sys values captured from prompts on this session since it connected,
+    /// sorted.
+    pub fn seen_sys(&self) -> Vec<String> {
+        self.status.seen_sys()
+    }
+
+    /// True once the lease has been released by its deadline.
+    pub async fn is_expired(&self) -> bool {
+        self.client.guard.lock().await.is_none()
+    }
+
+    /// Run `command` on the leased session, in `sys` or else the pinned sys.
+    ///
+    /// Fails with [`ConnectError::InvalidRequest`] once the lease expired.
+    pub async fn execute_command(
+        &self,
+        command: &Command,
+        sys: Option<&String>,
+    ) -> Result<Output, ConnectError> {
+        let sys = sys.or(self.pinned_sys.as_ref());
+        let mut client = self.client.guard.lock().await;
+        let client = client.as_mut().ok_or_else(|| self.expired())?;
+        let timeout = command
+            .timeout
+            .map(Duration::from_secs)
+            .unwrap_or_else(|| client.status.command_timeout());
+        client
+            .write_with_mode_and_timeout_using_command(command, sys, timeout)
+            .await
+    }
+
+    /// Run any session operation on the leased session, in `sys` or else the
+    /// pinned sys.
+    pub async fn execute_operation(
+        &self,
+        operation: &SessionOperation,
+        sys: Option<&String>,
+    ) -> Result<SessionOperationOutput, ConnectError> {
+        let sys = sys.or(self.pinned_sys.as_ref());
+        let mut client = self.client.guard.lock().await;
+        let client = client.as_mut().ok_or_else(|| self.expired())?;
+        client
+            .execute_operation_detailed(operation, sys)
+            .await
+            .map_err(|err| err.into_parts().0)
+    }
+
+    /// Give the session back to the pool before the deadline.
+    pub fn release(self) {}
+
+    fn expired(&self) -> ConnectError {
+        ConnectError::InvalidRequest(format!("lease on {} has expired", self.device_addr))
+    }
+}
+
+impl SshConnectionManager {
+    /// Take exclusive use of the pooled session for `request` for at most
+    /// `duration`, connecting first when none is pooled.
+    ///
+    /// The lease starts pinned to `context.sys`; see [`SessionLease::pin_sys`].
+    ///
+    /// Waits for the command running on the session, if any. Other jobs for
+    /// the same cache key queue until the lease is dropped, released or
+    /// expires; requests with a different
+    /// [affinity](ConnectionRequest::with_affinity) use their own session.
+    pub async fn lease(
+        &self,
+        request: ConnectionRequest,
+        context: ExecutionContext,
+        duration: Duration,
+    ) -> Result<SessionLease, ConnectError> {
+        let device_addr = request.device_addr();
+        self.get_with_request_and_recording(request, &context, None)
+            .await?;
+        let pooled = self.cache.get(&device_addr).await.ok_or_else(|| {
+            ConnectError::InternalServerError("connection cache miss".to_string())
+        })?;
+        let mut guard = pooled.client.write_owned().await;
+        guard.set_job_scope(JobScope::from(&context));
+        debug!("Leased {} for {:?}", device_addr, duration);
+
+        Ok(SessionLease {
+            client: Hold::new(guard, duration, device_addr.clone()),
+            device_addr,
+            expires_at: Instant::now() + duration,
+            status: pooled.status.clone(),
+            pinned_sys: context.sys,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn lease_needs_a_reachable_device() {
+        let manager = SshConnectionManager::new();
+        manager.set_connect_timeout(Duration::from_millis(200));
+        let request = ConnectionRequest::new(
+            "admin".to_string(),
+            "127.0.0.1".to_string(),
+            1,
+            "secret".to_string(),
+            None,
+            crate::templates::cisco().expect("cisco"),
+        );
+        let lease = manager
+            .lease(request, ExecutionContext::new(), Duration::from_secs(5))
+            .await;
+        assert!(lease.is_err());
+    }
+
+    #[tokio::test]
+    async fn other_jobs_wait_until_the_lease_expires() {
+        let client = Arc::new(RwLock::new(0u32));
+        let hold = Hold::new(
+            client.clone().write_owned().await,
+            Duration::from_millis(200),
+            "admin@192.0.2.1:22".to_string(),
+        );
+        **hold.guard.lock().await.as_mut().expect("held") += 1;
+        assert!(
+            tokio::time::timeout(Duration::from_millis(50), client.write())
+                .await
+                .is_err()
+        );
+
+        let mut job = tokio::time::timeout(Duration::from_secs(2), client.write())
+            .await
+            .expect("released on expiry");
+        *job += 1;
+        drop(job);
+        assert!(hold.guard.lock().await.is_none());
+        assert_eq!(*client.read().await, 2);
+    }
+
+    #[tokio::test]
+    async fn dropping_the_lease_releases_it_early() {
+        let client = Arc::new(RwLock::new(()));
+        let hold = Hold::new(
+            client.clone().write_owned().await,
+            Duration::from_secs(60),
+            "admin@192.0.2.1:22".to_string(),
+        );
+        assert!(client.try_write().is_err());
+        drop(hold);
+        assert!(client.try_write().is_ok());
+    }
+}
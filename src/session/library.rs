@@ -0,0 +1,300 @@
+//! Named commands resolved to per-template syntax.
+//!
+//! A [`CommandLibrary`] maps names such as `backup_config` to a
+//! [`NamedCommand`] holding one [`Command`] per template, so callers ask for
+//! an operation instead of spelling `show running-config` or
+//! `display current-configuration` themselves.
+
+use std::collections::BTreeMap;
+
+use super::*;
+use crate::parsers::{
+    self, arp_table_command, interface_brief_command, mac_table_command, parse_arp_table,
+    parse_interface_brief, parse_mac_table,
+};
+use crate::templates::BUILTIN_TEMPLATES;
+
+/// Structured parser applied to a named command's output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum NamedCommandParser {
+    /// [`parse_interface_brief`](crate::parsers::parse_interface_brief).
+    InterfaceBrief,
+    /// [`parse_mac_table`](crate::parsers::parse_mac_table).
+    MacTable,
+    /// [`parse_arp_table`](crate::parsers::parse_arp_table).
+    ArpTable,
+}
+
+impl NamedCommandParser {
+    fn parse(self, text: &str) -> Result<serde_json::Value, ConnectError> {
+        let value = match self {
+            Self::InterfaceBrief => serde_json::to_value(parse_interface_brief(text)),
+            Self::MacTable => serde_json::to_value(parse_mac_table(text)),
+            Self::ArpTable => serde_json::to_value(parse_arp_table(text)),
+        };
+        value.map_err(|e| ConnectError::InternalServerError(format!("encode parsed output: {e}")))
+    }
+}
+
+/// A command registered by name, with per-template variants.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct NamedCommand {
+    /// Used for templates without a variant.
+    #[serde(default)]
+    pub default: Option<Command>,
+    /// Commands keyed by lowercase template name.
+    #[serde(default)]
+    pub variants: BTreeMap<String, Command>,
+    #[serde(default)]
+    pub parser: Option<NamedCommandParser>,
+}
+
+impl NamedCommand {
+    /// A command used as-is for every template.
+    pub fn new(command: Command) -> Self {
+        Self {
+            default: Some(command),
+            ..Self::default()
+        }
+    }
+
+    /// A command with no default; add variants with [`Self::with_variant`].
+    pub fn per_template() -> Self {
+        Self::default()
+    }
+
+    /// Use `command` for `template`.
+    pub fn with_variant(mut self, template: &str, command: Command) -> Self {
+        self.variants.insert(template.to_ascii_lowercase(), command);
+        self
+    }
+
+    /// Parse output with `parser`.
+    pub fn with_parser(mut self, parser: NamedCommandParser) -> Self {
+        self.parser = Some(parser);
+        self
+    }
+
+    /// The command for `template`, falling back to the default.
+    pub fn resolve(&self, template: &str) -> Option<&Command> {
+        self.variants
+            .get(&template.to_ascii_lowercase())
+            .or(self.default.as_ref())
+    }
+}
+
+/// Output of [`SshConnectionManager::run_named_with_context`].
+#[derive(Debug, Clone)]
+pub struct NamedCommandOutput {
+    pub output: Output,
+    /// Parser result when the named command has a parser.
+    pub parsed: Option<serde_json::Value>,
+}
+
+/// Named commands available to [`SshConnectionManager::run_named_with_context`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct CommandLibrary {
+    commands: BTreeMap<String, NamedCommand>,
+}
+
+impl CommandLibrary {
+    /// An empty library.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Library preloaded for the built-in templates with `backup_config`,
+    /// `show_version`, `interfaces`, `mac_table` and `arp_table`.
+    pub fn builtin() -> Self {
+        let per_template = |command_for: fn(&str) -> Option<&'static str>| {
+            BUILTIN_TEMPLATES
+                .iter()
+                .filter_map(|template| command_for(template).map(|command| (template, command)))
+                .fold(
+                    NamedCommand::per_template(),
+                    |named, (template, command)| {
+                        named.with_variant(
+                            template,
+                            Command::new(parsers::show_mode(template), command),
+                        )
+                    },
+                )
+        };
+        let backup_config = per_template(|template| match template {
+            "cisco" | "arista" | "array" | "hillstone" | "maipu" | "venustech" | "chaitin"
+            | "qianxin" => Some("show running-config"),
+            "huawei" | "h3c" | "dptech" => Some("display current-configuration"),
+            "juniper" => Some("show configuration | display set"),
+            "fortinet" => Some("show full-configuration"),
+            _ => None,
+        });
+        let show_version = per_template(|template| match template {
+            "huawei" | "h3c" | "dptech" => Some("display version"),
+            "fortinet" => Some("get system status"),
+            "linux" => Some("uname -a"),
+            "paloalto" => Some("show system info"),
+            "checkpoint" | "topsec" => None,
+            _ => Some("show version"),
+        });
+
+        let mut library = Self::new()
+            .with_command("backup_config", backup_config)
+            .with_command("show_version", show_version)
+            .with_command(
+                "interfaces",
+                per_template(|template| interface_brief_command(template).ok())
+                    .with_parser(NamedCommandParser::InterfaceBrief),
+            )
+            .with_command(
+                "mac_table",
+                per_template(|template| mac_table_command(template).ok())
+                    .with_parser(NamedCommandParser::MacTable),
+            )
+            .with_command(
+                "arp_table",
+                per_template(|template| arp_table_command(template).ok())
+                    .with_parser(NamedCommandParser::ArpTable),
+            );
+        // Full configurations can be large on busy devices.
+        if let Some(backup) = library.commands.get_mut("backup_config") {
+            for command in backup.variants.values_mut() {
+                command.timeout = Some(120);
+            }
+        }
+        library
+    }
+
+    /// Register `command` under `name`, replacing any previous entry.
+    pub fn with_command(mut self, name: impl Into<String>, command: NamedCommand) -> Self {
+        self.register(name, command);
+        self
+    }
+
+    /// Register `command` under `name`, replacing any previous entry.
+    pub fn register(&mut self, name: impl Into<String>, command: NamedCommand) {
+        self.commands.insert(name.into(), command);
+    }
+
+    /// Look up a named command.
+    pub fn get(&self, name: &str) -> Option<&NamedCommand> {
+        self.commands.get(name)
+    }
+
+    /// Registered names in sorted order.
+    pub fn names(&self) -> Vec<String> {
+        self.commands.keys().cloned().collect()
+    }
+
+    /// The concrete command `name` runs on `template`.
+    pub fn resolve(&self, name: &str, template: &str) -> Result<Command, ConnectError> {
+        let named = self.get(name).ok_or_else(|| {
+            ConnectError::InvalidRequest(format!("unknown named command '{name}'"))
+        })?;
+        named.resolve(template).cloned().ok_or_else(|| {
+            ConnectError::InvalidRequest(format!(
+                "named command '{name}' has no variant for template '{template}'"
+            ))
+        })
+    }
+}
+
+impl SshConnectionManager {
+    /// Replace the library used by [`Self::run_named_with_context`].
+    ///
+    /// Defaults to [`CommandLibrary::builtin`].
+    pub fn set_command_library(&self, library: CommandLibrary) {
+        *self
+            .command_library
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = library;
+    }
+
+    /// Returns the configured command library.
+    pub fn command_library(&self) -> CommandLibrary {
+        self.command_library
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+    }
+
+    /// Add or replace one entry of the command library.
+    pub fn register_named_command(&self, name: impl Into<String>, command: NamedCommand) {
+        self.command_library
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .register(name, command);
+    }
+
+    /// Run the library command `name` in `template`'s syntax.
+    ///
+    /// `template` names the request's handler template, e.g. `cisco`, and
+    /// selects the variant. Output is parsed when the entry has a parser.
+    pub async fn run_named_with_context(
+        &self,
+        request: ConnectionRequest,
+        template: &str,
+        name: &str,
+        context: ExecutionContext,
+    ) -> Result<NamedCommandOutput, ConnectError> {
+        let library = self.command_library();
+        let command = library.resolve(name, template)?;
+        let parser = library.get(name).and_then(|named| named.parser);
+        let output = self
+            .execute_command_with_context(request, command, context)
+            .await?;
+        let parsed = match parser {
+            Some(parser) => Some(parser.parse(&parsers::output_text(output.clone())?)?),
+            None => None,
+        };
+        Ok(NamedCommandOutput { output, parsed })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtin_library_resolves_vendor_syntax() {
+        let library = CommandLibrary::builtin();
+        let cisco = library.resolve("backup_config", "Cisco").expect("cisco");
+        assert_eq!(cisco.command, "show running-config");
+        assert_eq!(cisco.mode, "Enable");
+        assert_eq!(cisco.timeout, Some(120));
+        assert_eq!(
+            library
+                .resolve("backup_config", "huawei")
+                .expect("huawei")
+                .command,
+            "display current-configuration"
+        );
+        assert_eq!(
+            library.get("interfaces").and_then(|named| named.parser),
+            Some(NamedCommandParser::InterfaceBrief)
+        );
+        assert!(matches!(
+            library.resolve("backup_config", "linux"),
+            Err(ConnectError::InvalidRequest(_))
+        ));
+        assert!(library.resolve("reboot", "cisco").is_err());
+    }
+
+    #[test]
+    fn custom_entries_fall_back_to_default_command() {
+        let library = CommandLibrary::new().with_command(
+            "save",
+            NamedCommand::new(Command::show("write memory"))
+                .with_variant("huawei", Command::new("Enable", "save")),
+        );
+        assert_eq!(library.names(), ["save"]);
+        assert_eq!(
+            library.resolve("save", "arista").expect("default").command,
+            "write memory"
+        );
+        assert_eq!(
+            library.resolve("save", "HUAWEI").expect("variant").command,
+            "save"
+        );
+    }
+}
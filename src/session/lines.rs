@@ -0,0 +1,127 @@
+//! Byte-level line splitting for shell output.
+//!
+//! Shell output arrives as raw [`Bytes`] chunks. Complete lines are split off
+//! without copying, and the trailing partial line (usually the prompt) is only
+//! decoded on demand. Multi-byte UTF-8 sequences split across chunks are kept
+//! intact until the rest of the sequence arrives.
+
+use std::borrow::Cow;
+
+use bytes::{Bytes, BytesMut};
+
+use crate::device::DeviceHandler;
+
+/// Incremental splitter turning raw output chunks into lines.
+#[derive(Debug, Default)]
+pub(crate) struct LineSplitter {
+    buffer: BytesMut,
+    /// Screen model used instead of raw splitting for redraw-heavy devices.
+    #[cfg(feature = "vt100")]
+    screen: Option<Box<super::terminal::Screen>>,
+}
+
+impl LineSplitter {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Splitter for `handler`'s output, emulating a terminal when it asks for it.
+    pub(crate) fn for_handler(handler: &DeviceHandler) -> Self {
+        #[cfg(feature = "vt100")]
+        if handler.terminal_emulation() {
+            return Self {
+                screen: Some(Box::new(super::terminal::Screen::new(
+                    handler.terminal_width(),
+                ))),
+                ..Self::default()
+            };
+        }
+        #[cfg(not(feature = "vt100"))]
+        let _ = handler;
+        Self::new()
+    }
+
+    /// Append a raw output chunk.
+    pub(crate) fn push(&mut self, chunk: &[u8]) {
+        #[cfg(feature = "vt100")]
+        if let Some(screen) = self.screen.as_mut() {
+            // The screen re-emits the cursor row, so drop the stale copy.
+            let complete = self
+                .buffer
+                .iter()
+                .rposition(|byte| *byte == b'\n')
+                .map_or(0, |pos| pos + 1);
+            self.buffer.truncate(complete);
+            let mut text = String::new();
+            screen.feed(chunk, &mut text);
+            self.buffer.extend_from_slice(text.as_bytes());
+            return;
+        }
+        self.buffer.extend_from_slice(chunk);
+    }
+
+    /// Split off the next complete line, including its trailing `\n`.
+    pub(crate) fn next_line(&mut self) -> Option<Bytes> {
+        let newline_pos = self.buffer.iter().position(|byte| *byte == b'\n')?;
+        Some(self.buffer.split_to(newline_pos + 1).freeze())
+    }
+
+    /// Trailing bytes not yet terminated by a newline, decoded lossily.
+    pub(crate) fn pending(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(&self.buffer)
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.buffer.is_empty()
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.buffer.clear();
+        #[cfg(feature = "vt100")]
+        if let Some(screen) = self.screen.as_mut() {
+            screen.consume_pending();
+        }
+    }
+}
+
+/// Decode one line of shell output, borrowing when it is valid UTF-8.
+pub(crate) fn decode_line(line: &[u8]) -> Cow<'_, str> {
+    String::from_utf8_lossy(line)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_lines_across_chunks() {
+        let mut splitter = LineSplitter::new();
+        splitter.push(b"show ver");
+        assert!(splitter.next_line().is_none());
+        assert_eq!(splitter.pending(), "show ver");
+
+        splitter.push(b"sion\r\nline two\nrouter#");
+
+        assert_eq!(
+            splitter.next_line().as_deref(),
+            Some(&b"show version\r\n"[..])
+        );
+        assert_eq!(splitter.next_line().as_deref(), Some(&b"line two\n"[..]));
+        assert!(splitter.next_line().is_none());
+        assert_eq!(splitter.pending(), "router#");
+
+        splitter.clear();
+        assert!(splitter.is_empty());
+    }
+
+    #[test]
+    fn keeps_multibyte_characters_split_across_chunks() {
+        let text = "接口 up\n".as_bytes();
+        let mut splitter = LineSplitter::new();
+        splitter.push(&text[..2]);
+        splitter.push(&text[2..]);
+
+        let line = splitter.next_line().expect("line");
+        assert_eq!(decode_line(&line), "接口 up\n");
+    }
+}
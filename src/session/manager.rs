@@ -1,15 +1,264 @@
 use super::*;
 
 impl SshConnectionManager {
-    /// Creates a new SSH connection manager.
+    /// Creates a new SSH connection manager with [`Settings`] defaults.
     pub fn new() -> Self {
-        // Cache up to 100 connections. Evict after 5 minutes of inactivity.
+        Self::with_settings(&Settings::default())
+    }
+
+    /// Creates a manager whose pool and timeouts follow `settings`.
+    pub fn with_settings(settings: &Settings) -> Self {
+        let events = events::ConnectionEventBus::new();
+        let eviction_events = events.clone();
+
+        // Evict connections after the configured idle time.
         let cache = Cache::builder()
-            .max_capacity(100)
-            .time_to_idle(Duration::from_secs(5 * 60)) // Evict after 5 minutes idle
+            .max_capacity(settings.pool_max_connections)
+            .time_to_idle(Duration::from_secs(settings.pool_idle_timeout_secs))
+            .eviction_listener(move |device_addr: Arc<String>, _, cause| {
+                if let Some(event) = ConnectionEvent::from_removal(&device_addr, cause) {
+                    eviction_events.emit(event);
+                }
+            })
             .build();
 
-        Self { cache }
+        Self {
+            cache: Arc::new(cache),
+            credential_provider: Arc::new(std::sync::RwLock::new(None)),
+            host_resolver: Arc::new(std::sync::RwLock::new(None)),
+            command_policy: Arc::new(std::sync::RwLock::new(CommandPolicy::default())),
+            change_freeze: Arc::new(std::sync::RwLock::new(None)),
+            tag_quotas: Arc::new(std::sync::RwLock::new(HashMap::new())),
+            connection_reservations: Arc::new(std::sync::Mutex::new(HashMap::new())),
+            max_session_lifetime: Arc::new(std::sync::RwLock::new(None)),
+            slow_command_threshold: Arc::new(std::sync::RwLock::new(None)),
+            login_interstitials: Arc::new(std::sync::RwLock::new(default_login_interstitials())),
+            command_library: Arc::new(std::sync::RwLock::new(CommandLibrary::builtin())),
+            command_timeout: Arc::new(std::sync::RwLock::new(Duration::from_secs(
+                settings.command_timeout_secs,
+            ))),
+            connect_timeout: Arc::new(std::sync::RwLock::new(Duration::from_secs(
+                settings.connect_timeout_secs,
+            ))),
+            journal: Arc::new(std::sync::RwLock::new(None)),
+            events,
+        }
+    }
+
+    /// Subscribe to connection lifecycle events.
+    ///
+    /// Each subscriber receives every event published after it subscribed.
+    /// Receivers that fall too far behind observe `RecvError::Lagged`.
+    pub fn subscribe_events(&self) -> tokio::sync::broadcast::Receiver<ConnectionEvent> {
+        self.events.subscribe()
+    }
+
+    /// List pooled connections with their current statistics.
+    ///
+    /// This never waits for running commands to finish.
+    pub fn connections(&self) -> Vec<ConnectionInfo> {
+        let mut connections: Vec<_> = self
+            .cache
+            .iter()
+            .map(|(_, pooled)| pooled.status.info())
+            .collect();
+        connections.sort_by(|a, b| a.device_addr.cmp(&b.device_addr));
+        connections
+    }
+
+    /// Watch the FSM state and prompt of the pooled connection for `device_addr`.
+    ///
+    /// The receiver is updated as output is read, so UIs can show which mode
+    /// a device is in while a job runs. `None` when nothing is pooled.
+    pub async fn watch_state(
+        &self,
+        device_addr: &str,
+    ) -> Option<tokio::sync::watch::Receiver<SessionState>> {
+        let pooled = self.cache.get(device_addr).await?;
+        Some(pooled.status.watch_state())
+    }
+
+    /// Sys values (e.g. vsys names) captured from prompts on the pooled
+    /// connection for `device_addr` since it connected, sorted.
+    ///
+    /// Does not wait for a running command. `None` when nothing is pooled.
+    pub async fn seen_sys(&self, device_addr: &str) -> Option<Vec<String>> {
+        let pooled = self.cache.get(device_addr).await?;
+        Some(pooled.status.seen_sys())
+    }
+
+    /// Modes the pooled connection for `device_addr` can switch to, with the
+    /// commands leading to each; see [`DeviceHandler::available_modes`].
+    ///
+    /// Waits for a running command on the connection to finish. `None` when
+    /// nothing is pooled.
+    pub async fn available_modes(
+        &self,
+        device_addr: &str,
+        sys: Option<&String>,
+    ) -> Option<Vec<crate::device::ModePath>> {
+        let pooled = self.cache.get(device_addr).await?;
+        let client = pooled.client.read().await;
+        Some(client.handler.available_modes(sys))
+    }
+
+    /// Close and forget the pooled connection for `device_addr` (`user@addr:port`).
+    ///
+    /// Returns false when no connection was pooled under that key. Waits for a
+    /// running command on the connection to finish first.
+    pub async fn disconnect(&self, device_addr: &str) -> bool {
+        let Some(pooled) = self.cache.get(device_addr).await else {
+            return false;
+        };
+        let _ = self
+            .safely_disconnect_cached_connection(device_addr, pooled.client, "disconnect_called")
+            .await;
+        self.cache.invalidate(device_addr).await;
+        true
+    }
+
+    /// Install a credential provider consulted whenever a connection is (re)established.
+    ///
+    /// Provider-supplied credentials are used for authentication only; cached
+    /// connections are still matched against the caller's original request.
+    pub fn set_credential_provider(&self, provider: Arc<dyn CredentialProvider>) {
+        *self
+            .credential_provider
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(provider);
+    }
+
+    /// Remove the installed credential provider and fall back to request credentials.
+    pub fn clear_credential_provider(&self) {
+        *self
+            .credential_provider
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = None;
+    }
+
+    /// Returns the currently installed credential provider, if any.
+    pub fn credential_provider(&self) -> Option<Arc<dyn CredentialProvider>> {
+        self.credential_provider
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+    }
+
+    /// Install the manager-wide command guardrail policy.
+    ///
+    /// It is merged with any per-connection policy from [`ExecutionContext`] and
+    /// applied to connections the next time they are looked up.
+    pub fn set_command_policy(&self, policy: CommandPolicy) {
+        *self
+            .command_policy
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = policy;
+    }
+
+    /// Returns the manager-wide command guardrail policy.
+    pub fn command_policy(&self) -> CommandPolicy {
+        self.command_policy
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+    }
+
+    /// Set a hard maximum age for cached connections.
+    ///
+    /// Connections older than this are logged out and rebuilt on their next use,
+    /// regardless of activity. `None` disables the limit.
+    pub fn set_max_session_lifetime(&self, max_lifetime: Option<Duration>) {
+        *self
+            .max_session_lifetime
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = max_lifetime;
+    }
+
+    /// Returns the configured maximum connection age, if any.
+    pub fn max_session_lifetime(&self) -> Option<Duration> {
+        *self
+            .max_session_lifetime
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Report commands still running after `threshold` as slow.
+    ///
+    /// A [`ConnectionEvent::SlowCommand`] event and a recorder entry are emitted
+    /// once per command, well before its hard timeout fires. `None` disables
+    /// the watchdog.
+    pub fn set_slow_command_threshold(&self, threshold: Option<Duration>) {
+        *self
+            .slow_command_threshold
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = threshold;
+    }
+
+    /// Returns the configured slow-command threshold, if any.
+    pub fn slow_command_threshold(&self) -> Option<Duration> {
+        *self
+            .slow_command_threshold
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Timeout for commands that do not set one, applied to connections as
+    /// they are acquired.
+    pub fn set_default_command_timeout(&self, timeout: Duration) {
+        *self
+            .command_timeout
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = timeout;
+    }
+
+    /// Returns the timeout for commands that do not set one.
+    pub fn default_command_timeout(&self) -> Duration {
+        *self
+            .command_timeout
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Limit for establishing a connection, including login and prompt detection.
+    pub fn set_connect_timeout(&self, timeout: Duration) {
+        *self
+            .connect_timeout
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = timeout;
+    }
+
+    /// Returns the connection establishment limit.
+    pub fn connect_timeout(&self) -> Duration {
+        *self
+            .connect_timeout
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Replace the prompt-response rules answered during login, before the
+    /// template's first prompt appears.
+    ///
+    /// They apply to connections established afterwards and are tried after
+    /// the template's own input rules. Defaults to
+    /// [`default_login_interstitials`]; pass an empty list to disable.
+    pub fn set_login_interstitials(
+        &self,
+        rules: Vec<PromptResponseRule>,
+    ) -> Result<(), ConnectError> {
+        client::validate_prompt_rules(&rules)?;
+        *self
+            .login_interstitials
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = rules;
+        Ok(())
+    }
+
+    /// Returns the configured login interstitial rules.
+    pub fn login_interstitials(&self) -> Vec<PromptResponseRule> {
+        self.login_interstitials
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
     }
 
     /// Gets a cached SSH client using a structured request/context pair.
@@ -18,7 +267,7 @@ impl SshConnectionManager {
         request: ConnectionRequest,
         context: ExecutionContext,
     ) -> Result<mpsc::Sender<CmdJob>, ConnectError> {
-        self.get_with_request_and_recording(request, context.security_options, None)
+        self.get_with_request_and_recording(request, &context, None)
             .await
     }
 
@@ -55,12 +304,58 @@ impl SshConnectionManager {
         request: ConnectionRequest,
         operation: SessionOperation,
         context: ExecutionContext,
+    ) -> Result<SessionOperationOutput, SessionOperationExecutionError> {
+        let started = self
+            .journal_start(
+                context.idempotency_key.as_deref(),
+                &request.device_addr(),
+                "operation",
+                &operation,
+                context.rerun_failed,
+            )
+            .map_err(|err| {
+                SessionOperationExecutionError::new(
+                    err,
+                    SessionOperationOutput {
+                        success: false,
+                        steps: Vec::new(),
+                    },
+                )
+            })?;
+        let pending = match started {
+            journal::JournalStart::Replay(output) => return Ok(*output),
+            journal::JournalStart::Run(pending) => pending,
+        };
+        let result = self
+            .execute_operation_inner(request, operation, context)
+            .await;
+        if let Some(pending) = pending {
+            pending.finish(result.as_ref().map_err(|err| err.error()), |output| {
+                output.success
+            });
+        }
+        result
+    }
+
+    async fn execute_operation_inner(
+        &self,
+        request: ConnectionRequest,
+        operation: SessionOperation,
+        context: ExecutionContext,
     ) -> Result<SessionOperationOutput, SessionOperationExecutionError> {
         let device_addr = request.device_addr();
         let sys = context.sys.clone();
-        self.get_with_request_and_recording(request, context.security_options, None)
+        let started = Instant::now();
+        let (mode, command) = failed_step_hint(&operation, 0);
+        self.get_with_request_and_recording(request, &context, None)
             .await
             .map_err(|err| {
+                let err = err.with_context(
+                    ErrorContext::new(device_addr.clone(), started.elapsed())
+                        .with_mode(mode)
+                        .with_command(command),
+                );
+                self.emit_command_failed(&device_addr, &err);
                 SessionOperationExecutionError::new(
                     err,
                     SessionOperationOutput {
@@ -70,22 +365,35 @@ impl SshConnectionManager {
                 )
             })?;
 
-        let (_sender, client) = self.cache.get(&device_addr).await.ok_or_else(|| {
-            SessionOperationExecutionError::new(
-                ConnectError::InternalServerError("connection cache miss".to_string()),
-                SessionOperationOutput {
-                    success: false,
-                    steps: Vec::new(),
-                },
-            )
-        })?;
+        let client = self
+            .cache
+            .get(&device_addr)
+            .await
+            .map(|pooled| pooled.client)
+            .ok_or_else(|| {
+                SessionOperationExecutionError::new(
+                    ConnectError::InternalServerError("connection cache miss".to_string()),
+                    SessionOperationOutput {
+                        success: false,
+                        steps: Vec::new(),
+                    },
+                )
+            })?;
 
         let mut client_guard = client.write().await;
+        client_guard.set_job_scope(JobScope::from(&context));
         client_guard
             .execute_operation_detailed(&operation, sys.as_ref())
             .await
             .map_err(|err| {
                 let (error, partial_output) = err.into_parts();
+                let (mode, command) = failed_step_hint(&operation, partial_output.steps.len());
+                let error = error.with_context(
+                    ErrorContext::new(device_addr.clone(), started.elapsed())
+                        .with_mode(mode)
+                        .with_command(command),
+                );
+                self.emit_command_failed(&device_addr, &error);
                 SessionOperationExecutionError::new(error, partial_output)
             })
     }
@@ -110,16 +418,65 @@ impl SshConnectionManager {
         block: TxBlock,
         context: ExecutionContext,
     ) -> Result<TxResult, ConnectError> {
+        let device_addr = request.device_addr();
+        let started = Instant::now();
+        let label = format!("tx block '{}'", block.name);
+        let pending = match self.journal_start(
+            context.idempotency_key.as_deref(),
+            &device_addr,
+            "tx_block",
+            &block,
+            context.rerun_failed,
+        )? {
+            journal::JournalStart::Replay(result) => return Ok(*result),
+            journal::JournalStart::Run(pending) => pending,
+        };
+        let result = self
+            .execute_tx_block_inner(request, block, context)
+            .await
+            .inspect(|result| {
+                self.events
+                    .emit(ConnectionEvent::tx_block_finished(&device_addr, result));
+            })
+            .map_err(|err| {
+                let err = err.with_context(
+                    ErrorContext::new(device_addr.clone(), started.elapsed())
+                        .with_command(Some(label)),
+                );
+                self.emit_command_failed(&device_addr, &err);
+                err
+            });
+        if let Some(pending) = pending {
+            pending.finish(result.as_ref(), |result| result.committed);
+        }
+        result
+    }
+
+    async fn execute_tx_block_inner(
+        &self,
+        request: ConnectionRequest,
+        mut block: TxBlock,
+        context: ExecutionContext,
+    ) -> Result<TxResult, ConnectError> {
+        if let Some(operator) = &context.operator {
+            block.attach_operator(operator);
+        }
         let device_addr = request.device_addr();
         let sys = context.sys.clone();
-        self.get_with_request_and_recording(request, context.security_options, None)
+        self.get_with_request_and_recording(request, &context, None)
             .await?;
 
-        let (_sender, client) = self.cache.get(&device_addr).await.ok_or_else(|| {
-            ConnectError::InternalServerError("connection cache miss".to_string())
-        })?;
+        let client = self
+            .cache
+            .get(&device_addr)
+            .await
+            .map(|pooled| pooled.client)
+            .ok_or_else(|| {
+                ConnectError::InternalServerError("connection cache miss".to_string())
+            })?;
 
         let mut client_guard = client.write().await;
+        client_guard.set_job_scope(JobScope::from(&context));
         client_guard.execute_tx_block(&block, sys.as_ref()).await
     }
 
@@ -130,18 +487,132 @@ impl SshConnectionManager {
         workflow: TxWorkflow,
         context: ExecutionContext,
     ) -> Result<TxWorkflowResult, ConnectError> {
+        self.execute_tx_workflow_within(request, workflow, context, None)
+            .await
+    }
+
+    /// [`Self::execute_tx_workflow_with_context`], giving up with
+    /// [`ConnectError::DeviceTimeout`] when the device session cannot be
+    /// acquired within `acquire_deadline`. Once the workflow has started it
+    /// is never cut off.
+    pub(super) async fn execute_tx_workflow_within(
+        &self,
+        request: ConnectionRequest,
+        workflow: TxWorkflow,
+        context: ExecutionContext,
+        acquire_deadline: Option<Duration>,
+    ) -> Result<TxWorkflowResult, ConnectError> {
+        let device_addr = request.device_addr();
+        let started = Instant::now();
+        let label = format!("tx workflow '{}'", workflow.name);
+        let key = workflow
+            .idempotency_key
+            .as_deref()
+            .or(context.idempotency_key.as_deref());
+        let pending = match self.journal_start(
+            key,
+            &device_addr,
+            "tx_workflow",
+            &workflow,
+            context.rerun_failed,
+        )? {
+            journal::JournalStart::Replay(result) => return Ok(*result),
+            journal::JournalStart::Run(pending) => pending,
+        };
+        let result = self
+            .execute_tx_workflow_inner(request, workflow, context, acquire_deadline)
+            .await
+            .inspect(|result| {
+                self.events
+                    .emit(ConnectionEvent::tx_workflow_finished(&device_addr, result));
+            })
+            .map_err(|err| {
+                let err = err.with_context(
+                    ErrorContext::new(device_addr.clone(), started.elapsed())
+                        .with_command(Some(label)),
+                );
+                self.emit_command_failed(&device_addr, &err);
+                err
+            });
+        if let Some(pending) = pending {
+            pending.finish(result.as_ref(), |result| result.committed);
+        }
+        result
+    }
+
+    async fn execute_tx_workflow_inner(
+        &self,
+        request: ConnectionRequest,
+        mut workflow: TxWorkflow,
+        context: ExecutionContext,
+        acquire_deadline: Option<Duration>,
+    ) -> Result<TxWorkflowResult, ConnectError> {
+        if let Some(operator) = &context.operator {
+            for block in &mut workflow.blocks {
+                block.attach_operator(operator);
+            }
+        }
         let device_addr = request.device_addr();
         let sys = context.sys.clone();
-        self.get_with_request_and_recording(request, context.security_options, None)
+        let (workflow, context) = (&workflow, &context);
+        let acquire = async {
+            if let Err(err) = self
+                .get_with_request_and_recording(request, context, None)
+                .await
+            {
+                if workflow.preflight {
+                    return Err(ConnectError::PreflightFailed(Box::new(
+                        PreflightReport::unreachable(workflow, &err),
+                    )));
+                }
+                return Err(err);
+            }
+
+            let client = self
+                .cache
+                .get(&device_addr)
+                .await
+                .map(|pooled| pooled.client)
+                .ok_or_else(|| {
+                    ConnectError::InternalServerError("connection cache miss".to_string())
+                })?;
+            Ok(client.write_owned().await)
+        };
+
+        fanout::acquire_then(acquire_deadline, acquire, |mut client_guard| async move {
+            client_guard.set_job_scope(JobScope::from(context));
+            client_guard
+                .execute_tx_workflow(workflow, sys.as_ref())
+                .await
+        })
+        .await
+    }
+
+    /// Roll back a committed workflow on the device it ran on.
+    pub async fn rollback_tx_workflow_with_context(
+        &self,
+        request: ConnectionRequest,
+        workflow: &TxWorkflow,
+        result: &mut TxWorkflowResult,
+        context: ExecutionContext,
+    ) -> Result<(), ConnectError> {
+        let device_addr = request.device_addr();
+        self.get_with_request_and_recording(request, &context, None)
             .await?;
 
-        let (_sender, client) = self.cache.get(&device_addr).await.ok_or_else(|| {
-            ConnectError::InternalServerError("connection cache miss".to_string())
-        })?;
+        let client = self
+            .cache
+            .get(&device_addr)
+            .await
+            .map(|pooled| pooled.client)
+            .ok_or_else(|| {
+                ConnectError::InternalServerError("connection cache miss".to_string())
+            })?;
 
         let mut client_guard = client.write().await;
+        client_guard.set_job_scope(JobScope::from(&context));
         client_guard
-            .execute_tx_workflow(&workflow, sys.as_ref())
+            .rollback_tx_workflow(workflow, result, context.sys.as_ref())
             .await
     }
 
@@ -151,17 +622,116 @@ impl SshConnectionManager {
         request: ConnectionRequest,
         upload: FileUploadRequest,
         context: ExecutionContext,
-    ) -> Result<(), ConnectError> {
+    ) -> Result<FileTransferReport, ConnectError> {
+        self.upload_file_with_progress(request, upload, context, None)
+            .await
+    }
+
+    /// Upload a local file over SFTP, calling `progress` after every buffer sent.
+    pub async fn upload_file_with_progress(
+        &self,
+        request: ConnectionRequest,
+        upload: FileUploadRequest,
+        context: ExecutionContext,
+        progress: Option<TransferProgressFn>,
+    ) -> Result<FileTransferReport, ConnectError> {
+        let label = format!("upload {} -> {}", upload.local_path, upload.remote_path);
+        self.transfer_with_context(request, context, label, |client| {
+            Box::pin(async move { client.upload_file(&upload, progress.as_ref()).await })
+        })
+        .await
+    }
+
+    /// Download a remote file over SFTP using a structured request/context pair.
+    pub async fn download_file_with_context(
+        &self,
+        request: ConnectionRequest,
+        download: FileDownloadRequest,
+        context: ExecutionContext,
+    ) -> Result<FileTransferReport, ConnectError> {
+        self.download_file_with_progress(request, download, context, None)
+            .await
+    }
+
+    /// Download a remote file over SFTP, calling `progress` after every buffer received.
+    pub async fn download_file_with_progress(
+        &self,
+        request: ConnectionRequest,
+        download: FileDownloadRequest,
+        context: ExecutionContext,
+        progress: Option<TransferProgressFn>,
+    ) -> Result<FileTransferReport, ConnectError> {
+        let label = format!(
+            "download {} -> {}",
+            download.remote_path, download.local_path
+        );
+        self.transfer_with_context(request, context, label, |client| {
+            Box::pin(async move { client.download_file(&download, progress.as_ref()).await })
+        })
+        .await
+    }
+
+    async fn transfer_with_context<F>(
+        &self,
+        request: ConnectionRequest,
+        context: ExecutionContext,
+        label: String,
+        transfer: F,
+    ) -> Result<FileTransferReport, ConnectError>
+    where
+        F: for<'a> FnOnce(&'a mut SharedSshClient) -> TransferFuture<'a>,
+    {
         let device_addr = request.device_addr();
-        self.get_with_request_and_recording(request, context.security_options, None)
-            .await?;
+        let started = Instant::now();
+        let result = async {
+            self.get_with_request_and_recording(request, &context, None)
+                .await?;
 
-        let (_sender, client) = self.cache.get(&device_addr).await.ok_or_else(|| {
-            ConnectError::InternalServerError("connection cache miss".to_string())
-        })?;
+            let client = self
+                .cache
+                .get(&device_addr)
+                .await
+                .map(|pooled| pooled.client)
+                .ok_or_else(|| {
+                    ConnectError::InternalServerError("connection cache miss".to_string())
+                })?;
 
-        let mut client_guard = client.write().await;
-        client_guard.upload_file(&upload).await
+            let mut client_guard = client.write().await;
+            client_guard.set_job_scope(JobScope::from(&context));
+            transfer(&mut client_guard).await
+        }
+        .await;
+        result.map_err(|err| {
+            let err = err.with_context(
+                ErrorContext::new(device_addr.clone(), started.elapsed()).with_command(Some(label)),
+            );
+            self.emit_command_failed(&device_addr, &err);
+            err
+        })
+    }
+
+    /// Open an SSH subsystem (e.g. `netconf`) on the pooled connection for `request`.
+    ///
+    /// The subsystem runs on its own channel, so CLI commands keep working
+    /// while the returned stream is in use.
+    pub async fn open_subsystem_with_context(
+        &self,
+        request: ConnectionRequest,
+        context: ExecutionContext,
+        subsystem: &str,
+    ) -> Result<russh::ChannelStream<russh::client::Msg>, ConnectError> {
+        let device_addr = request.device_addr();
+        self.get_with_context(request, context).await?;
+        let client = self
+            .cache
+            .get(&device_addr)
+            .await
+            .map(|pooled| pooled.client)
+            .ok_or_else(|| {
+                ConnectError::InternalServerError("connection cache miss".to_string())
+            })?;
+        let client_guard = client.read().await;
+        client_guard.open_subsystem(subsystem).await
     }
 
     /// Gets a cached SSH client with recording using a structured request/context pair.
@@ -177,6 +747,11 @@ impl SshConnectionManager {
     }
 
     /// Gets a cached SSH client with recording using a structured request/context pair.
+    ///
+    /// On a cache hit the new recorder is attached next to those of earlier
+    /// callers, and each receives every later event. The connection only keeps
+    /// a weak reference: recording stops once all clones of the returned
+    /// recorder are dropped.
     pub async fn get_with_recording_level_and_context(
         &self,
         request: ConnectionRequest,
@@ -185,22 +760,69 @@ impl SshConnectionManager {
     ) -> Result<(mpsc::Sender<CmdJob>, SessionRecorder), ConnectError> {
         let recorder = SessionRecorder::new(level);
         let sender = self
-            .get_with_request_and_recording(
-                request,
-                context.security_options,
-                Some(recorder.clone()),
-            )
+            .get_with_request_and_recording(request, &context, Some(recorder.clone()))
             .await?;
         Ok((sender, recorder))
     }
 
-    async fn get_with_request_and_recording(
+    pub(super) async fn get_with_request_and_recording(
         &self,
         request: ConnectionRequest,
-        security_options: ConnectionSecurityOptions,
+        context: &ExecutionContext,
         recorder: Option<SessionRecorder>,
     ) -> Result<mpsc::Sender<CmdJob>, ConnectError> {
         let device_addr = request.device_addr();
+        let started = Instant::now();
+        self.connect_or_reuse(request, context, recorder)
+            .await
+            .map_err(|err| err.with_context(ErrorContext::new(device_addr, started.elapsed())))
+    }
+
+    /// Apply this request and the manager's settings to a reused connection.
+    ///
+    /// The settings go to the shared status and need no client lock, but
+    /// only once the tag quotas admit the reuse; a refused request leaves the
+    /// status as it was.
+    pub(super) fn adopt_pooled(
+        &self,
+        device_addr: &str,
+        status: &status::ConnectionStatus,
+        tags: &[String],
+        recorder: Option<&SessionRecorder>,
+        command_policy: CommandPolicy,
+    ) -> Result<(), ConnectError> {
+        let new_tags: Vec<String> = tags
+            .iter()
+            .filter(|tag| !status.has_tag(tag))
+            .cloned()
+            .collect();
+        let _reservation = self.reserve_connection_slot(device_addr, &new_tags)?;
+        // Attached next to any existing recorder, never in place of it.
+        if let Some(recorder) = recorder {
+            status.attach_recorder(recorder);
+        }
+        status.add_tags(tags);
+        status.set_tag_quotas(self.tag_quotas.clone());
+        status.set_command_policy(command_policy);
+        status.set_change_freeze(self.change_freeze.clone());
+        status.set_slow_command_threshold(self.slow_command_threshold());
+        status.set_command_timeout(self.default_command_timeout());
+        Ok(())
+    }
+
+    async fn connect_or_reuse(
+        &self,
+        request: ConnectionRequest,
+        context: &ExecutionContext,
+        recorder: Option<SessionRecorder>,
+    ) -> Result<mpsc::Sender<CmdJob>, ConnectError> {
+        let device_addr = request.device_addr();
+        let security_options = context.security_options.clone();
+        // Only manager-wide settings go on the shared connection; the
+        // context's own travel with each job as a `JobScope`.
+        let command_policy = self.command_policy();
+        let home_state = context.home_state.as_deref();
+        let credential_target = CredentialTarget::from_request(&request);
         let ConnectionRequest {
             user,
             addr,
@@ -208,109 +830,299 @@ impl SshConnectionManager {
             password,
             enable_password,
             handler,
+            affinity,
+            tags,
         } = request;
+        if let Some(home) = home_state
+            && !handler
+                .states()
+                .iter()
+                .any(|state| state.eq_ignore_ascii_case(home))
+        {
+            return Err(ConnectError::UnreachableState(home.to_string()));
+        }
+
+        // Why an existing cached connection had to be replaced, if any.
+        let mut replaced_reason = None;
 
         // Check if a healthy, usable connection exists in the cache
-        if let Some((sender, client)) = self.cache.get(&device_addr).await {
+        if let Some(pooled) = self.cache.get(&device_addr).await {
             debug!("Cache hit: {}", device_addr);
 
-            let client_guard = client.read().await;
-            if client_guard.is_connected() {
-                // Check if connection parameters match
-                if client_guard.matches_connection_params(
-                    &password,
-                    &enable_password,
-                    &handler,
-                    &security_options,
-                ) {
-                    debug!("Cached connection params match, reusing: {}", device_addr);
-                    if recorder.is_some() {
-                        drop(client_guard);
-                        let mut client_guard = client.write().await;
-                        client_guard.recorder = recorder.clone();
-                    }
-                    return Ok(sender);
-                } else {
-                    debug!(
-                        "Cached connection params mismatch, recreating: {}",
-                        device_addr
-                    );
-                    // Release read lock
-                    drop(client_guard);
-
-                    // Safely disconnect the old connection
-                    match self
-                        .safely_disconnect_cached_connection(&device_addr, client.clone())
-                        .await
-                    {
-                        Ok(_) => debug!("Old connection safely disconnected: {}", device_addr),
-                        Err(e) => debug!(
-                            "Error disconnecting old connection: {} - {}",
-                            device_addr, e
-                        ),
-                    }
-
-                    // Remove from cache
-                    self.cache.invalidate(&device_addr).await;
-                }
-            } else {
+            if !pooled.status.is_connected() {
                 // If connection is closed, remove from cache
                 debug!("Cached connection {} is closed. Removing.", device_addr);
                 self.cache.invalidate(&device_addr).await;
+                replaced_reason = Some("connection_closed");
+            } else if session_lifetime_exceeded(
+                pooled.status.session_age(),
+                self.max_session_lifetime(),
+            ) {
+                debug!(
+                    "Cached connection {} exceeded max session lifetime, recreating",
+                    device_addr
+                );
+                let _ = self
+                    .safely_disconnect_cached_connection(
+                        &device_addr,
+                        pooled.client.clone(),
+                        "max_session_lifetime_exceeded",
+                    )
+                    .await;
+                self.cache.invalidate(&device_addr).await;
+                replaced_reason = Some("max_session_lifetime_exceeded");
+            } else if pooled.client.read().await.matches_connection_params(
+                &password,
+                &enable_password,
+                &handler,
+                &security_options,
+            ) {
+                debug!("Cached connection params match, reusing: {}", device_addr);
+                // Comparing the device handler took the client lock, so a
+                // reuse waits for a running command.
+                self.adopt_pooled(
+                    &device_addr,
+                    &pooled.status,
+                    &tags,
+                    recorder.as_ref(),
+                    command_policy,
+                )?;
+                return Ok(pooled.sender);
+            } else {
+                debug!(
+                    "Cached connection params mismatch, recreating: {}",
+                    device_addr
+                );
+
+                // Safely disconnect the old connection
+                match self
+                    .safely_disconnect_cached_connection(
+                        &device_addr,
+                        pooled.client.clone(),
+                        "connection_params_changed",
+                    )
+                    .await
+                {
+                    Ok(_) => debug!("Old connection safely disconnected: {}", device_addr),
+                    Err(e) => debug!(
+                        "Error disconnecting old connection: {} - {}",
+                        device_addr, e
+                    ),
+                }
+
+                // Remove from cache
+                self.cache.invalidate(&device_addr).await;
+                replaced_reason = Some("connection_params_changed");
             }
         } else {
             debug!("Cache miss, creating new connection for {}...", device_addr);
         }
+        // Held until the new connection is pooled, or released if it fails.
+        let _reservation = self.reserve_connection_slot(&device_addr, &tags)?;
+
+        let credentials = match self.credential_provider() {
+            Some(provider) => {
+                debug!("Resolving credentials for {} from provider", device_addr);
+                Some(provider.resolve(&credential_target).await?)
+            }
+            None => None,
+        };
 
         // Create a new client. `new` automatically detects prompt and ensures shell is ready.
-        let ssh_client = SharedSshClient::new(
-            user,
-            addr,
-            port,
-            password,
-            enable_password,
-            handler,
-            security_options,
-            recorder,
-        )
-        .await?;
+        let connect_timeout = self.connect_timeout();
+        let resolver = self.host_resolver();
+        let mut rejections = 0;
+        let mut ssh_client = loop {
+            let connected = tokio::time::timeout(connect_timeout, async {
+                let resolved = address::ResolvedAddrs::lookup(
+                    &addr,
+                    port,
+                    context.address_family,
+                    resolver.as_deref(),
+                )
+                .await?;
+                SharedSshClient::new(
+                    user.clone(),
+                    addr.clone(),
+                    port,
+                    affinity.clone(),
+                    password.clone(),
+                    enable_password.clone(),
+                    credentials.clone(),
+                    handler.fresh(),
+                    security_options.clone(),
+                    resolved,
+                    recorder.clone(),
+                    &self.login_interstitials(),
+                    Arc::downgrade(&self.cache),
+                )
+                .await
+            })
+            .await
+            .map_err(|_| {
+                ConnectError::InitTimeout(format!(
+                    "{device_addr} not ready within {}s",
+                    connect_timeout.as_secs()
+                ))
+            })?;
+            match (connected, context.session_reject_retry) {
+                (Err(ConnectError::SessionRejected(reason)), Some(retry))
+                    if rejections < retry.attempts =>
+                {
+                    rejections += 1;
+                    debug!(
+                        "{} rejected the session ({}), retry {}/{} in {}s",
+                        device_addr, reason, rejections, retry.attempts, retry.interval_secs
+                    );
+                    tokio::time::sleep(retry.interval()).await;
+                }
+                (connected, _) => break connected?,
+            }
+        };
+        ssh_client.status.add_tags(&tags);
+        ssh_client.status.set_tag_quotas(self.tag_quotas.clone());
+        ssh_client.status.set_command_policy(command_policy);
+        ssh_client
+            .status
+            .set_change_freeze(self.change_freeze.clone());
+        ssh_client
+            .status
+            .set_slow_command_threshold(self.slow_command_threshold());
+        ssh_client
+            .status
+            .set_command_timeout(self.default_command_timeout());
+        ssh_client.events = Some(self.events.clone());
+        self.watch_disconnect(&device_addr, &mut ssh_client);
+        self.events.emit(match replaced_reason {
+            Some(reason) => ConnectionEvent::Reconnected {
+                device_addr: device_addr.clone(),
+                reason: reason.to_string(),
+            },
+            None => ConnectionEvent::Connected {
+                device_addr: device_addr.clone(),
+            },
+        });
+        let status = ssh_client.status.clone();
         let client_arc = Arc::new(RwLock::new(ssh_client));
 
         let (tx, mut rx) = mpsc::channel::<CmdJob>(32);
 
         let client_clone = client_arc.clone();
+        let worker_status = status.clone();
         let worker_device_addr = device_addr.clone();
+        let worker_cache = self.cache.clone();
+        let worker_events = self.events.clone();
+        let worker_journal = self.journal.clone();
 
         tokio::spawn(async move {
             loop {
                 if let Some(job) = rx.recv().await {
-                    if !client_clone.read().await.is_connected() {
-                        let _ = job.responder.send(Err(ConnectError::ConnectClosedError));
+                    if !worker_status.is_connected() {
+                        let _ = job.responder.send(Err(ConnectError::ConnectClosedError
+                            .with_context(
+                                ErrorContext::new(worker_device_addr.clone(), Duration::ZERO)
+                                    .with_mode(Some(job.data.mode))
+                                    .with_command(Some(job.data.command)),
+                            )));
                         break;
                     }
-                    let res = {
+                    let journal = worker_journal
+                        .read()
+                        .unwrap_or_else(|poisoned| poisoned.into_inner())
+                        .clone();
+                    let pending = match journal::start(
+                        journal,
+                        job.idempotency_key.as_deref(),
+                        &worker_device_addr,
+                        "command",
+                        &job.data,
+                        job.scope.rerun_failed,
+                    ) {
+                        Ok(journal::JournalStart::Run(pending)) => pending,
+                        Ok(journal::JournalStart::Replay(output)) => {
+                            let _ = job.responder.send(Ok(*output));
+                            continue;
+                        }
+                        Err(err) => {
+                            let _ = job.responder.send(Err(err));
+                            continue;
+                        }
+                    };
+                    let (res, homed) = {
+                        let started = Instant::now();
                         let mut client_guard = client_clone.write().await;
-                        let Command {
-                            mode,
-                            command,
-                            timeout,
-                            dyn_params,
-                            interaction,
-                        } = job.data;
-                        let timeout = Duration::from_secs(timeout.unwrap_or(60));
-                        client_guard
+                        let home_state = job.scope.home_state.clone();
+                        client_guard.set_job_scope(job.scope);
+                        let command = job.data;
+                        let timeout = command
+                            .timeout
+                            .map(Duration::from_secs)
+                            .unwrap_or_else(|| worker_status.command_timeout());
+                        // A per-job recorder is attached only while the write lock
+                        // is held, so no other command lands in it.
+                        let job_recorder = job
+                            .recorder
+                            .as_ref()
+                            .map(|recorder| client_guard.status.attach_recorder(recorder));
+                        let res = client_guard
                             .write_with_mode_and_timeout_using_command(
                                 &command,
-                                &mode,
                                 job.sys.as_ref(),
                                 timeout,
-                                &dyn_params,
-                                &interaction,
                             )
-                            .await
+                            .await;
+                        if let Some(attachment) = job_recorder {
+                            client_guard.status.detach_recorder(attachment);
+                        }
+                        // A rebooting device is never homed or reused.
+                        let homed = match home_state {
+                            _ if command.expect_disconnect => false,
+                            Some(home) if !matches!(&res, Err(err) if err.is_fatal_for_connection()) => {
+                                client_guard
+                                    .return_to_state(&home, timeout)
+                                    .await
+                                    .inspect_err(|err| {
+                                        warn!(
+                                            "Failed to return {} to {}: {}",
+                                            worker_device_addr, home, err
+                                        )
+                                    })
+                                    .is_ok()
+                            }
+                            _ => true,
+                        };
+                        let res = res.map_err(|err| {
+                            err.with_context(
+                                ErrorContext::new(worker_device_addr.clone(), started.elapsed())
+                                    .with_mode(Some(command.mode))
+                                    .with_command(Some(command.command)),
+                            )
+                        });
+                        (res, homed)
                     };
 
+                    if let Err(err) = &res {
+                        worker_events
+                            .emit(ConnectionEvent::command_failed(&worker_device_addr, err));
+                    }
+                    if let Some(pending) = pending {
+                        pending.finish(res.as_ref(), |output| output.success);
+                    }
+                    // A session stuck outside its home state is not reused.
+                    let fatal = !homed || matches!(&res, Err(err) if err.is_fatal_for_connection());
                     let _ = job.responder.send(res);
+                    if fatal {
+                        debug!(
+                            "Fatal error on {}, dropping cached connection.",
+                            worker_device_addr
+                        );
+                        if let Some(cached) = worker_cache.get(&worker_device_addr).await
+                            && Arc::ptr_eq(&cached.client, &client_clone)
+                        {
+                            worker_cache.invalidate(&worker_device_addr).await;
+                        }
+                        break;
+                    }
                 } else {
                     debug!(
                         "Command channel closed for {}, stopping worker.",
@@ -322,18 +1134,52 @@ impl SshConnectionManager {
         });
 
         self.cache
-            .insert(device_addr.clone(), (tx.clone(), client_arc))
+            .insert(
+                device_addr.clone(),
+                PooledConnection {
+                    sender: tx.clone(),
+                    client: client_arc,
+                    status,
+                },
+            )
             .await;
         debug!("New connection for {} has been cached.", device_addr);
 
         Ok(tx)
     }
 
+    /// Publish [`ConnectionEvent::Disconnected`] once the client's SSH session ends.
+    fn watch_disconnect(&self, device_addr: &str, client: &mut SharedSshClient) {
+        let Some(closed) = client.closed_signal.take() else {
+            return;
+        };
+        let status = client.status.clone();
+        let events = self.events.clone();
+        let device_addr = device_addr.to_string();
+        tokio::spawn(async move {
+            let _ = closed.await;
+            let reason = status
+                .take_close_reason()
+                .unwrap_or_else(|| "channel_closed".to_string());
+            events.emit(ConnectionEvent::Disconnected {
+                device_addr,
+                reason,
+                stats: status.stats().snapshot(),
+            });
+        });
+    }
+
+    pub(super) fn emit_command_failed(&self, device_addr: &str, err: &ConnectError) {
+        self.events
+            .emit(ConnectionEvent::command_failed(device_addr, err));
+    }
+
     /// Safely disconnects a cached connection.
-    async fn safely_disconnect_cached_connection(
+    pub(super) async fn safely_disconnect_cached_connection(
         &self,
         device_addr: &str,
         client_arc: Arc<RwLock<SharedSshClient>>,
+        reason: &str,
     ) -> Result<(), ConnectError> {
         debug!("Safely disconnecting cached connection: {}", device_addr);
 
@@ -347,7 +1193,7 @@ impl SshConnectionManager {
         }
 
         // Safely close connection
-        match client_guard.close().await {
+        match client_guard.close_with_reason(reason).await {
             Ok(_) => {
                 debug!("Connection {} safely closed", device_addr);
                 Ok(())
@@ -361,6 +1207,30 @@ impl SshConnectionManager {
     }
 }
 
+/// Mode and command of the step that failed after `completed_steps` succeeded.
+pub(super) fn failed_step_hint(
+    operation: &SessionOperation,
+    completed_steps: usize,
+) -> (Option<String>, Option<String>) {
+    let step = match operation {
+        SessionOperation::Command(command) => Some(command),
+        SessionOperation::Flow(flow) => flow.steps.get(completed_steps),
+        SessionOperation::Template { .. } => {
+            return operation
+                .summary()
+                .map(|summary| (Some(summary.mode), Some(summary.description)))
+                .unwrap_or_default();
+        }
+    };
+    step.map(|command| (Some(command.mode.clone()), Some(command.command.clone())))
+        .unwrap_or_default()
+}
+
+/// Returns true when a connection of `age` must be rebuilt under `max_lifetime`.
+pub(super) fn session_lifetime_exceeded(age: Duration, max_lifetime: Option<Duration>) -> bool {
+    max_lifetime.is_some_and(|max| age >= max)
+}
+
 impl Default for SshConnectionManager {
     fn default() -> Self {
         Self::new()
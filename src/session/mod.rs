@@ -11,12 +11,13 @@
 //! - [`Command`] - Command configuration for device execution
 //! - [`CommandFlow`] - Multi-step interactive command flow
 //! - [`SessionOperationOutput`] - Generic execution result for any session operation
-//! - [`FileUploadRequest`] - SFTP upload configuration
+//! - [`FileUploadRequest`] / [`FileDownloadRequest`] - SFTP transfer configuration
 //! - [`Output`] - Command execution results
 
 use async_ssh2_tokio::client::{AuthMethod, Client};
 use async_ssh2_tokio::{Config, ServerCheckMethod};
-use log::{debug, trace};
+use bytes::Bytes;
+use log::{debug, trace, warn};
 use moka::future::Cache;
 use once_cell::sync::Lazy;
 use sha2::{Digest, Sha256};
@@ -27,28 +28,77 @@ use serde::{Deserialize, Serialize};
 use std::borrow::Cow;
 use std::collections::HashMap;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tokio::sync::mpsc::{self, Receiver, Sender};
 use tokio::sync::{RwLock, oneshot};
 
 use crate::config;
-use crate::error::ConnectError;
+use crate::error::{ConnectError, ErrorContext};
+use crate::settings::Settings;
 
 use super::device::{DeviceHandler, IGNORE_START_LINE};
 
+pub use address::AddressFamily;
+pub(crate) use address::{ResolvedAddrs, bare_host, host_port};
+pub use background::{
+    BackgroundJob, BackgroundJobHandle, BackgroundJobProgress, BackgroundJobResult,
+};
+pub use bridge::BridgeSummary;
+pub use bulk::{BulkPushLine, BulkPushOptions, BulkPushOutput, CommentHandling};
+pub use candidate::CandidateConfigSession;
+pub use credentials::{
+    CredentialFuture, CredentialProvider, CredentialTarget, DeviceCredentials,
+    StaticCredentialProvider,
+};
+pub use events::ConnectionEvent;
+pub use fanout::{DeviceOutcome, FanoutOptions};
+pub use freeze::ChangeFreeze;
+pub use ha::{HaApplyOrder, HaMemberResult, HaWorkflowResult};
+pub use job::JobScope;
+pub use journal::{ExecutionJournal, JournalEntry, JournalState, MemoryJournal};
+pub use learn::{LEARNED_STATE, LearnedHandler, LearnedLimitation};
+pub use lease::SessionLease;
+pub use library::{CommandLibrary, NamedCommand, NamedCommandOutput, NamedCommandParser};
+pub use policy::CommandPolicy;
+pub use preflight::{PreflightIssue, PreflightReport};
+pub use quota::TagQuota;
+pub use reboot::{RebootOutcome, RebootWait};
 pub use recording::{
-    NormalizeOptions, ReplayContext, SessionEvent, SessionRecordEntry, SessionRecordLevel,
-    SessionRecorder, SessionReplayer,
+    NormalizeOptions, RecordingSampling, ReplayConformance, ReplayContext, ReplayDivergence,
+    SessionEvent, SessionRecordEntry, SessionRecordLevel, SessionRecorder, SessionReplayer,
 };
+pub(crate) use rejection::rejection_reason;
+pub use resolver::{HostResolver, ResolveFuture, StaticHostResolver};
+pub use retry::RetryPolicy;
 pub use security::{ConnectionSecurityOptions, SecurityLevel};
+pub use sink::{FanoutSummary, JsonLinesSink, OutcomeRecord, OutcomeSink, SinkFuture};
+pub use snapshot::{SnapshotDiff, Snapshotted};
+pub use spill::SpilledOutput;
+#[cfg(feature = "upload")]
+pub(crate) use spill::create_temp_file;
+pub use stats::{ConnectionInfo, ConnectionStats};
+pub use status::SessionState;
 pub use transaction::{
     CommandBlockKind, RollbackPolicy, TxBlock, TxOperationStepResult, TxResult, TxStep,
     TxStepExecutionState, TxStepResult, TxStepRollbackState, TxWorkflow, TxWorkflowResult,
     failed_block_rollback_summary, workflow_rollback_order,
 };
 
-/// Global singleton SSH connection manager.
-pub static MANAGER: Lazy<SshConnectionManager> = Lazy::new(SshConnectionManager::new);
+/// Number of trailing output lines kept in prompt-mismatch diagnostics.
+const PROMPT_DIAGNOSTIC_LINES: usize = 10;
+
+/// Global singleton SSH connection manager, configured by [`Settings::load`].
+pub static MANAGER: Lazy<SshConnectionManager> =
+    Lazy::new(|| SshConnectionManager::with_settings(&Settings::load_or_default()));
+
+/// SSH port used when a device description does not name one.
+pub const DEFAULT_SSH_PORT: u16 = 22;
+
+/// Serde default for `port` fields of device descriptions.
+#[cfg(any(feature = "ffi", feature = "mcp", feature = "inventory"))]
+pub(crate) fn default_ssh_port() -> u16 {
+    DEFAULT_SSH_PORT
+}
 
 /// Connection request describing how to reach a device and which handler to use.
 pub struct ConnectionRequest {
@@ -58,6 +108,20 @@ pub struct ConnectionRequest {
     pub password: String,
     pub enable_password: Option<String>,
     pub handler: DeviceHandler,
+    /// Discriminator added to the cache key, e.g. `backup` or a vsys name,
+    /// so independent workloads get their own session to the device.
+    pub affinity: Option<String>,
+    /// Labels such as `site:fra1`, `role:edge` or `tenant:acme` attached to
+    /// the pooled connection; see [`SshConnectionManager::connections_by_tag`].
+    pub tags: Vec<String>,
+}
+
+/// Cache key `user@host:port`, with `#affinity` appended when set.
+pub(crate) fn cache_key(user: &str, addr: &str, port: u16, affinity: Option<&str>) -> String {
+    match affinity.filter(|affinity| !affinity.is_empty()) {
+        Some(affinity) => format!("{user}@{}#{affinity}", host_port(addr, port)),
+        None => format!("{user}@{}", host_port(addr, port)),
+    }
 }
 
 impl ConnectionRequest {
@@ -77,12 +141,53 @@ impl ConnectionRequest {
             password,
             enable_password,
             handler,
+            affinity: None,
+            tags: Vec::new(),
         }
     }
 
+    /// Build a request using the built-in template named `template`, e.g.
+    /// `cisco`; fails with [`ConnectError::TemplateNotFound`] for unknown names.
+    pub fn for_template(
+        user: String,
+        addr: String,
+        port: u16,
+        password: String,
+        enable_password: Option<String>,
+        template: &str,
+    ) -> Result<Self, ConnectError> {
+        Ok(Self::new(
+            user,
+            addr,
+            port,
+            password,
+            enable_password,
+            crate::templates::by_name(template)?,
+        ))
+    }
+
+    /// Keep this request's session apart from other workloads on the same
+    /// device, e.g. `with_affinity("backup")` next to `with_affinity("config")`.
+    pub fn with_affinity(mut self, affinity: impl Into<String>) -> Self {
+        self.affinity = Some(affinity.into());
+        self
+    }
+
+    /// Tag the pooled connection, e.g. `with_tag("tenant:acme")`.
+    ///
+    /// Tags of every request served by a connection accumulate on it.
+    pub fn with_tag(mut self, tag: impl Into<String>) -> Self {
+        self.tags.push(tag.into());
+        self
+    }
+
     /// Stable cache key used by the connection manager.
+    ///
+    /// IPv6 literals are bracketed, e.g. `admin@[2001:db8::1]:22`, whether
+    /// or not `addr` was. An affinity is appended after `#`, e.g.
+    /// `admin@192.0.2.1:22#backup`.
     pub fn device_addr(&self) -> String {
-        format!("{}@{}:{}", self.user, self.addr, self.port)
+        cache_key(&self.user, &self.addr, self.port, self.affinity.as_deref())
     }
 }
 
@@ -93,6 +198,28 @@ pub struct ExecutionContext {
     pub security_options: ConnectionSecurityOptions,
     /// Optional system name used by templates with dynamic transitions.
     pub sys: Option<String>,
+    /// Per-job command guardrails, checked on top of the manager-wide policy.
+    pub command_policy: Option<CommandPolicy>,
+    /// State the session is walked back to after every job, e.g. `Enable`.
+    pub home_state: Option<String>,
+    /// Override token presented to an active [`ChangeFreeze`].
+    pub freeze_override: Option<String>,
+    /// Retry commands that hit a configuration lock held by another user.
+    pub config_lock_retry: Option<RetryPolicy>,
+    /// Address family tried first when the device's hostname has both.
+    pub address_family: AddressFamily,
+    /// Reconnect when the device closes the session right after login.
+    pub session_reject_retry: Option<RetryPolicy>,
+    /// Key under which the job is recorded in the manager's
+    /// [`ExecutionJournal`]; a job whose key already has a result returns
+    /// that result without running.
+    pub idempotency_key: Option<String>,
+    /// Run the job again when its key's journal entry errored after
+    /// reaching the device.
+    pub rerun_failed: bool,
+    /// Operator or job identity written into device-side change records,
+    /// e.g. the Junos commit comment.
+    pub operator: Option<String>,
 }
 
 impl ExecutionContext {
@@ -112,13 +239,77 @@ impl ExecutionContext {
         self.sys = sys;
         self
     }
+
+    /// Attach command guardrails enforced for this connection.
+    pub fn with_command_policy(mut self, command_policy: CommandPolicy) -> Self {
+        self.command_policy = Some(command_policy);
+        self
+    }
+
+    /// Present `token` to an active [`ChangeFreeze`] so this job's changes
+    /// go through.
+    pub fn with_freeze_override(mut self, token: impl Into<String>) -> Self {
+        self.freeze_override = Some(token.into());
+        self
+    }
+
+    /// Wait for configuration locks held by other users to be released.
+    pub fn with_config_lock_retry(mut self, retry: RetryPolicy) -> Self {
+        self.config_lock_retry = Some(retry);
+        self
+    }
+
+    /// Wait and reconnect when the device rejects the session after login,
+    /// e.g. because all its vty lines are in use.
+    pub fn with_session_reject_retry(mut self, retry: RetryPolicy) -> Self {
+        self.session_reject_retry = Some(retry);
+        self
+    }
+
+    /// Record the job in the manager's journal under `key`.
+    pub fn with_idempotency_key(mut self, key: impl Into<String>) -> Self {
+        self.idempotency_key = Some(key.into());
+        self
+    }
+
+    /// Run the job again even if its key's earlier run errored after
+    /// reaching the device and may have applied part of its change.
+    pub fn with_rerun_failed(mut self, rerun_failed: bool) -> Self {
+        self.rerun_failed = rerun_failed;
+        self
+    }
+
+    /// Attribute this job's commits to `operator` where the vendor records
+    /// a comment, see [`CandidateConfig::commit_with_comment`].
+    ///
+    /// [`CandidateConfig::commit_with_comment`]: crate::templates::CandidateConfig::commit_with_comment
+    pub fn with_operator(mut self, operator: impl Into<String>) -> Self {
+        self.operator = Some(operator.into());
+        self
+    }
+
+    /// Try `family` first when connecting to a dual-stack hostname.
+    pub fn with_address_family(mut self, family: AddressFamily) -> Self {
+        self.address_family = family;
+        self
+    }
+
+    /// Return the session to `state` after each job on this connection.
+    ///
+    /// A job that leaves the device in a config sub-mode then cannot affect
+    /// the next one. If the way back fails, the connection is dropped and
+    /// the next job reconnects.
+    pub fn with_home_state(mut self, state: impl Into<String>) -> Self {
+        self.home_state = Some(state.into());
+        self
+    }
 }
 
 /// A shared SSH client instance with state machine tracking.
 pub struct SharedSshClient {
     client: Client,
     sender: Sender<String>,
-    recv: Receiver<String>,
+    recv: Receiver<Bytes>,
     handler: DeviceHandler,
     prompt: String,
 
@@ -131,8 +322,18 @@ pub struct SharedSshClient {
     /// Effective security options used when the connection was established.
     security_options: ConnectionSecurityOptions,
 
-    /// Optional session recorder bound to this connection.
-    recorder: Option<SessionRecorder>,
+    /// Resolves once the SSH I/O task has ended; taken by the manager.
+    closed_signal: Option<oneshot::Receiver<()>>,
+
+    /// Lifecycle event bus of the owning manager.
+    events: Option<events::ConnectionEventBus>,
+
+    /// State readable without this client's lock: liveness, stats, recorder
+    /// and guardrail settings.
+    status: Arc<status::ConnectionStatus>,
+
+    /// Settings of the job holding this client's lock.
+    scope: JobScope,
 }
 
 /// Structured prompt-response overrides for a single command execution.
@@ -212,6 +413,28 @@ impl PromptResponseRule {
     }
 }
 
+/// Generic login interstitials answered while waiting for the first prompt.
+///
+/// Covers "press any key" banners and legal-acceptance `[y/n]` questions
+/// regardless of the device template. Replace or clear them with
+/// [`SshConnectionManager::set_login_interstitials`].
+pub fn default_login_interstitials() -> Vec<PromptResponseRule> {
+    vec![
+        PromptResponseRule::new(
+            vec![
+                r"(?i)press any key to continue".to_string(),
+                r"(?i)press <?(enter|return)>? to continue".to_string(),
+                r"(?i)hit <?(enter|return|any key)>? to continue".to_string(),
+            ],
+            "\n".to_string(),
+        ),
+        PromptResponseRule::new(
+            vec![r"(?i)(accept|agree).*[\[(]\s*y(es)?\s*/\s*n(o)?\s*[\])]\s*[:?]?\s*$".to_string()],
+            "y\n".to_string(),
+        ),
+    ]
+}
+
 /// Runtime interactive behavior for a single command execution.
 #[derive(Default, Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq, Eq)]
 pub struct CommandInteraction {
@@ -242,6 +465,10 @@ pub struct Command {
     /// - "Enable": Privileged mode (admin privileges)
     /// - "Config": Configuration mode (for modifying settings)
     /// - Specific mode names depend on the device type and vendor
+    ///
+    /// May be left empty inside transaction steps; the session then derives
+    /// it from the block kind (see [`DeviceHandler::default_mode`]).
+    #[serde(default)]
     pub mode: String,
 
     /// The actual command content to execute on the device
@@ -270,6 +497,157 @@ pub struct Command {
     /// `copy tftp:`, or future HTTP-style wizards that should not require template edits.
     #[serde(default)]
     pub interaction: CommandInteraction,
+
+    /// Output size in bytes above which the transcript is streamed to a temp file.
+    ///
+    /// The file is reported in [`Output::spilled`]. `None` keeps everything in memory.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub spill_threshold: Option<usize>,
+
+    /// Run on a dedicated SSH exec channel (`true`) or the interactive shell (`false`).
+    ///
+    /// `None` follows the template's command execution strategy. Exec-channel
+    /// commands skip mode transitions and take success from the exit status.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exec_channel: Option<bool>,
+
+    /// The command drops the connection, e.g. `reload` or `reboot`.
+    ///
+    /// A channel close is then reported as success with the output seen so
+    /// far, and the connection is not reused. Pair with
+    /// [`SshConnectionManager::wait_for_reboot`] to reconnect.
+    #[serde(default)]
+    pub expect_disconnect: bool,
+
+    /// Keep the device's echo of the command at the start of `content`.
+    ///
+    /// By default the echo is removed even when the device wrapped or
+    /// scrolled it across lines.
+    #[serde(default)]
+    pub keep_echo: bool,
+}
+
+impl Command {
+    /// Build a command with default timeout and no per-command overrides.
+    pub fn new(mode: impl Into<String>, command: impl Into<String>) -> Self {
+        Self {
+            mode: mode.into(),
+            command: command.into(),
+            ..Self::default()
+        }
+    }
+
+    /// Build a read-only command run in `Enable` mode.
+    pub fn show(command: impl Into<String>) -> Self {
+        Self::new("Enable", command)
+    }
+
+    /// Build a configuration command run in `Config` mode.
+    pub fn config(command: impl Into<String>) -> Self {
+        Self::new("Config", command)
+    }
+
+    /// Start building a command field by field.
+    pub fn builder() -> CommandBuilder {
+        CommandBuilder::default()
+    }
+
+    /// Override the timeout in seconds.
+    pub fn with_timeout(mut self, timeout_secs: u64) -> Self {
+        self.timeout = Some(timeout_secs);
+        self
+    }
+
+    /// Attach runtime prompt-response rules.
+    pub fn with_interaction(mut self, interaction: CommandInteraction) -> Self {
+        self.interaction = interaction;
+        self
+    }
+
+    /// Force (or forbid) running this command on its own SSH exec channel.
+    pub fn with_exec_channel(mut self, exec_channel: bool) -> Self {
+        self.exec_channel = Some(exec_channel);
+        self
+    }
+
+    /// Treat the connection dropping as the expected outcome.
+    pub fn with_expect_disconnect(mut self) -> Self {
+        self.expect_disconnect = true;
+        self
+    }
+
+    /// Leave the command echo in the output.
+    pub fn with_keep_echo(mut self) -> Self {
+        self.keep_echo = true;
+        self
+    }
+}
+
+/// Step-by-step builder for [`Command`], created by [`Command::builder`].
+#[derive(Debug, Clone, Default)]
+pub struct CommandBuilder {
+    command: Command,
+}
+
+impl CommandBuilder {
+    /// Device mode to run in, e.g. `Enable`.
+    pub fn mode(mut self, mode: impl Into<String>) -> Self {
+        self.command.mode = mode.into();
+        self
+    }
+
+    /// Command line to send.
+    pub fn command(mut self, command: impl Into<String>) -> Self {
+        self.command.command = command.into();
+        self
+    }
+
+    /// Timeout in seconds.
+    pub fn timeout(mut self, timeout_secs: u64) -> Self {
+        self.command.timeout = Some(timeout_secs);
+        self
+    }
+
+    /// Prompt responses for this command only.
+    pub fn dyn_params(mut self, dyn_params: CommandDynamicParams) -> Self {
+        self.command.dyn_params = dyn_params;
+        self
+    }
+
+    /// Prompt-response rules checked before the template's input rules.
+    pub fn interaction(mut self, interaction: CommandInteraction) -> Self {
+        self.command.interaction = interaction;
+        self
+    }
+
+    /// Output size in bytes above which the transcript is spilled to disk.
+    pub fn spill_threshold(mut self, bytes: usize) -> Self {
+        self.command.spill_threshold = Some(bytes);
+        self
+    }
+
+    /// Run on a dedicated SSH exec channel instead of the interactive shell.
+    pub fn exec_channel(mut self, exec_channel: bool) -> Self {
+        self.command.exec_channel = Some(exec_channel);
+        self
+    }
+
+    /// Report a channel close as success, for `reload`/`reboot`.
+    pub fn expect_disconnect(mut self, expect_disconnect: bool) -> Self {
+        self.command.expect_disconnect = expect_disconnect;
+        self
+    }
+
+    /// Leave the command echo in the output.
+    pub fn keep_echo(mut self, keep_echo: bool) -> Self {
+        self.command.keep_echo = keep_echo;
+        self
+    }
+
+    /// Finish the command.
+    pub fn build(self) -> Command {
+        self.command
+    }
 }
 
 /// Higher-level executable operation supported by the session layer.
@@ -356,6 +734,9 @@ pub struct FileUploadRequest {
     pub buffer_size: Option<usize>,
     /// Emit progress logs during upload when set.
     pub show_progress: bool,
+    /// Read the remote file back and compare its SHA-256 with the local file.
+    #[serde(default)]
+    pub verify_checksum: bool,
 }
 
 impl FileUploadRequest {
@@ -367,6 +748,7 @@ impl FileUploadRequest {
             timeout_secs: None,
             buffer_size: None,
             show_progress: false,
+            verify_checksum: false,
         }
     }
 
@@ -387,6 +769,90 @@ impl FileUploadRequest {
         self.show_progress = show_progress;
         self
     }
+
+    /// Read the uploaded file back and fail on a SHA-256 mismatch.
+    pub fn with_checksum_verification(mut self, verify_checksum: bool) -> Self {
+        self.verify_checksum = verify_checksum;
+        self
+    }
+}
+
+/// Configuration for downloading a remote file over SFTP.
+///
+/// Has the same `sftp` subsystem requirement as [`FileUploadRequest`].
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct FileDownloadRequest {
+    /// Source file path on the remote host.
+    pub remote_path: String,
+    /// Local destination path on the machine running rneter.
+    pub local_path: String,
+    /// Optional SFTP operation timeout in seconds.
+    pub timeout_secs: Option<u64>,
+    /// Optional transfer buffer size in bytes.
+    pub buffer_size: Option<usize>,
+    /// Expected hex SHA-256 of the file; the local copy is removed on mismatch.
+    #[serde(default)]
+    pub expected_sha256: Option<String>,
+}
+
+impl FileDownloadRequest {
+    /// Build a new download request with conservative defaults.
+    pub fn new(remote_path: String, local_path: String) -> Self {
+        Self {
+            remote_path,
+            local_path,
+            timeout_secs: None,
+            buffer_size: None,
+            expected_sha256: None,
+        }
+    }
+
+    /// Override the SFTP timeout in seconds.
+    pub fn with_timeout_secs(mut self, timeout_secs: u64) -> Self {
+        self.timeout_secs = Some(timeout_secs);
+        self
+    }
+
+    /// Override the transfer buffer size in bytes.
+    pub fn with_buffer_size(mut self, buffer_size: usize) -> Self {
+        self.buffer_size = Some(buffer_size);
+        self
+    }
+
+    /// Fail the download unless the file hashes to `sha256` (hex, case-insensitive).
+    pub fn with_expected_sha256(mut self, sha256: impl Into<String>) -> Self {
+        self.expected_sha256 = Some(sha256.into());
+        self
+    }
+}
+
+/// Progress of an in-flight SFTP transfer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransferProgress {
+    /// Remote path being transferred.
+    pub remote_path: String,
+    /// Bytes copied so far.
+    pub transferred: u64,
+    /// File size, when known.
+    pub total: Option<u64>,
+}
+
+/// Callback invoked after every buffer copied during a transfer.
+pub type TransferProgressFn = Arc<dyn Fn(&TransferProgress) + Send + Sync>;
+
+pub(crate) type TransferFuture<'a> = std::pin::Pin<
+    Box<dyn std::future::Future<Output = Result<FileTransferReport, ConnectError>> + Send + 'a>,
+>;
+
+/// Result of a completed SFTP transfer.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct FileTransferReport {
+    /// Bytes copied.
+    pub bytes: u64,
+    /// Lowercase hex SHA-256 of the transferred content.
+    pub sha256: String,
+    /// True when the checksum was compared against the other side or an expected value.
+    pub verified: bool,
 }
 
 fn default_stop_on_error() -> bool {
@@ -433,20 +899,76 @@ impl CommandFlow {
 pub struct CmdJob {
     pub data: Command,
     pub sys: Option<String>,
+    /// Recorder attached for this job only, alongside the connection's recorders.
+    ///
+    /// Lets a sensitive command be captured at [`SessionRecordLevel::Full`]
+    /// while the connection records at a lighter level.
+    pub recorder: Option<SessionRecorder>,
     /// Oneshot channel sender for returning the execution result
     pub responder: oneshot::Sender<Result<Output, ConnectError>>,
+    /// Caller-provided key; when the manager's [`ExecutionJournal`] already
+    /// has a result under it, that result is returned and nothing is sent.
+    pub idempotency_key: Option<String>,
+    /// Guardrails and session settings of the submitting caller, e.g.
+    /// `JobScope::from(&context)`; they apply to this job only.
+    pub scope: JobScope,
 }
 
 /// The output result of a command execution.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Output {
     pub success: bool,
     /// Exit code captured from shell execution when supported by the active handler.
     pub exit_code: Option<i32>,
     pub content: String,
-    pub all: String,
+    /// Full transcript split into stages: the prompt before execution, each
+    /// mode-transition command, then the command itself.
+    ///
+    /// Stages are kept apart so transition-heavy commands are not copied into
+    /// one growing buffer; use [`Output::all`] to join them.
+    pub segments: Vec<String>,
     /// Prompt captured by the internal state machine after command execution.
     pub prompt: Option<String>,
+    /// Set when the command's transcript exceeded [`Command::spill_threshold`].
+    ///
+    /// The command's own transcript is then only in this file; `content` is
+    /// empty and `segments` holds just the mode-transition stages.
+    pub spilled: Option<SpilledOutput>,
+    /// `user@host:port` of the connection that ran the command.
+    ///
+    /// `None` for outputs rebuilt from replay or operation step results.
+    pub device_addr: Option<String>,
+    /// System name (vsys/context/hostname) captured from the prompt when the
+    /// command finished.
+    pub sys: Option<String>,
+    /// Syslog lines the device interleaved into the output, in arrival
+    /// order; they are left out of `content` and the transcript.
+    pub async_messages: Vec<String>,
+}
+
+impl Output {
+    /// Full transcript, joined from [`Output::segments`] only when needed.
+    pub fn all(&self) -> Cow<'_, str> {
+        match self.segments.as_slice() {
+            [] => Cow::Borrowed(""),
+            [single] => Cow::Borrowed(single),
+            segments => Cow::Owned(segments.concat()),
+        }
+    }
+
+    /// Consume the output and return the full transcript.
+    pub fn into_all(self) -> String {
+        join_segments(self.segments)
+    }
+}
+
+/// Join transcript stages, reusing the buffer when there is only one.
+pub(crate) fn join_segments(mut segments: Vec<String>) -> String {
+    match segments.len() {
+        0 => String::new(),
+        1 => segments.pop().unwrap_or_default(),
+        _ => segments.concat(),
+    }
 }
 
 /// Detailed execution result for one concrete child step inside a session operation.
@@ -468,6 +990,9 @@ pub struct SessionOperationStepOutput {
     pub all: String,
     /// Prompt observed after the child step finished.
     pub prompt: Option<String>,
+    /// Temp file holding the transcript when it exceeded the spill threshold.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub spilled: Option<SpilledOutput>,
 }
 
 impl SessionOperationStepOutput {
@@ -477,8 +1002,12 @@ impl SessionOperationStepOutput {
             success: self.success,
             exit_code: self.exit_code,
             content: self.content,
-            all: self.all,
+            segments: vec![self.all],
             prompt: self.prompt,
+            spilled: self.spilled,
+            device_addr: None,
+            sys: None,
+            async_messages: Vec::new(),
         }
     }
 
@@ -487,8 +1016,12 @@ impl SessionOperationStepOutput {
             success: self.success,
             exit_code: self.exit_code,
             content: self.content.clone(),
-            all: self.all.clone(),
+            segments: vec![self.all.clone()],
             prompt: self.prompt.clone(),
+            spilled: self.spilled.clone(),
+            device_addr: None,
+            sys: None,
+            async_messages: Vec::new(),
         }
     }
 }
@@ -580,19 +1113,80 @@ pub struct CommandFlowOutput {
     pub outputs: Vec<Output>,
 }
 
+/// One pooled connection as stored in the manager cache.
+#[derive(Clone)]
+struct PooledConnection {
+    /// Job queue drained by the connection's worker task.
+    sender: mpsc::Sender<CmdJob>,
+    /// Execution state, locked for the duration of each command.
+    client: Arc<RwLock<SharedSshClient>>,
+    /// Lock-free view shared with `client`.
+    status: Arc<status::ConnectionStatus>,
+}
+
+/// Pooled connections keyed by [`ConnectionRequest::device_addr`].
+type ConnectionCache = Cache<String, PooledConnection>;
+
 /// SSH connection pool manager.
 ///
 /// Manages a cache of SSH connections with automatic reconnection and
 /// connection pooling. Connections are cached for 5 minutes of inactivity.
 #[derive(Clone)]
 pub struct SshConnectionManager {
-    cache: Cache<String, (mpsc::Sender<CmdJob>, Arc<RwLock<SharedSshClient>>)>,
+    cache: Arc<ConnectionCache>,
+    credential_provider: Arc<std::sync::RwLock<Option<Arc<dyn CredentialProvider>>>>,
+    host_resolver: Arc<std::sync::RwLock<Option<Arc<dyn HostResolver>>>>,
+    command_policy: Arc<std::sync::RwLock<CommandPolicy>>,
+    change_freeze: freeze::FreezeSwitch,
+    tag_quotas: quota::TagQuotas,
+    connection_reservations: quota::ConnectionReservations,
+    max_session_lifetime: Arc<std::sync::RwLock<Option<Duration>>>,
+    slow_command_threshold: Arc<std::sync::RwLock<Option<Duration>>>,
+    login_interstitials: Arc<std::sync::RwLock<Vec<PromptResponseRule>>>,
+    command_library: Arc<std::sync::RwLock<CommandLibrary>>,
+    command_timeout: Arc<std::sync::RwLock<Duration>>,
+    connect_timeout: Arc<std::sync::RwLock<Duration>>,
+    journal: Arc<std::sync::RwLock<Option<Arc<dyn ExecutionJournal>>>>,
+    events: events::ConnectionEventBus,
 }
 
+mod address;
+mod background;
+mod bridge;
+mod bulk;
+mod candidate;
 mod client;
+mod config_lock;
+mod credentials;
+mod echo;
+mod events;
+mod fanout;
+mod freeze;
+mod ha;
+mod job;
+mod journal;
+mod learn;
+mod lease;
+mod library;
+mod lines;
 mod manager;
+mod policy;
+mod preflight;
+mod quota;
+mod reboot;
 mod recording;
+mod rejection;
+mod resolver;
+mod retry;
 mod security;
+mod sink;
+mod snapshot;
+mod spill;
+mod stats;
+mod status;
+mod tags;
+#[cfg(feature = "vt100")]
+mod terminal;
 mod transaction;
 
 #[cfg(test)]
@@ -600,6 +1194,75 @@ mod tests {
     use super::*;
     use crate::templates;
 
+    #[test]
+    fn output_joins_segments_on_demand() {
+        let single = Output {
+            success: true,
+            exit_code: None,
+            content: "ok".to_string(),
+            segments: vec!["show clock\nok\nrouter#".to_string()],
+            prompt: None,
+            spilled: None,
+            device_addr: None,
+            sys: None,
+            async_messages: Vec::new(),
+        };
+        assert!(matches!(single.all(), Cow::Borrowed(_)));
+
+        let staged = Output {
+            segments: vec![
+                "router>".to_string(),
+                "enable\nrouter#".to_string(),
+                "show clock\nok\nrouter#".to_string(),
+            ],
+            ..single
+        };
+        assert_eq!(
+            staged.all(),
+            "router>enable\nrouter#show clock\nok\nrouter#"
+        );
+        assert_eq!(
+            staged.into_all(),
+            "router>enable\nrouter#show clock\nok\nrouter#"
+        );
+    }
+
+    #[test]
+    fn default_login_interstitials_match_common_banners() {
+        let rules = default_login_interstitials();
+        let response = |line: &str| {
+            rules
+                .iter()
+                .find(|rule| {
+                    rule.patterns
+                        .iter()
+                        .any(|pattern| regex::Regex::new(pattern).unwrap().is_match(line))
+                })
+                .map(|rule| rule.response.as_str())
+        };
+
+        assert_eq!(response("Press any key to continue"), Some("\n"));
+        assert_eq!(response("-- Press <ENTER> to continue --"), Some("\n"));
+        assert_eq!(
+            response("Do you accept the terms of use? [y/n]: "),
+            Some("y\n")
+        );
+        assert_eq!(response("router>"), None);
+
+        let manager = SshConnectionManager::new();
+        assert!(
+            manager
+                .set_login_interstitials(vec![PromptResponseRule::new(
+                    vec!["[".to_string()],
+                    "\n".to_string(),
+                )])
+                .is_err()
+        );
+        assert_eq!(manager.login_interstitials(), rules);
+        manager.set_login_interstitials(Vec::new()).expect("clear");
+        assert!(manager.login_interstitials().is_empty());
+    }
+
     #[test]
     fn connection_request_formats_device_addr() {
         let request = ConnectionRequest::new(
@@ -611,6 +1274,37 @@ mod tests {
             templates::cisco().expect("template"),
         );
         assert_eq!(request.device_addr(), "admin@192.168.1.1:22");
+        let backup = request.with_affinity("backup");
+        assert_eq!(backup.device_addr(), "admin@192.168.1.1:22#backup");
+        assert_eq!(
+            backup.with_affinity("").device_addr(),
+            "admin@192.168.1.1:22"
+        );
+    }
+
+    #[test]
+    fn connection_request_for_template_resolves_built_in_names() {
+        let request = ConnectionRequest::for_template(
+            "admin".to_string(),
+            "192.168.1.1".to_string(),
+            DEFAULT_SSH_PORT,
+            "password".to_string(),
+            None,
+            "CiScO",
+        )
+        .expect("cisco");
+        assert_eq!(request.device_addr(), "admin@192.168.1.1:22");
+        assert!(matches!(
+            ConnectionRequest::for_template(
+                "admin".to_string(),
+                "192.168.1.1".to_string(),
+                DEFAULT_SSH_PORT,
+                "password".to_string(),
+                None,
+                "unknown-vendor",
+            ),
+            Err(ConnectError::TemplateNotFound(_))
+        ));
     }
 
     #[test]
@@ -623,6 +1317,96 @@ mod tests {
             ConnectionSecurityOptions::legacy_compatible()
         );
         assert_eq!(context.sys.as_deref(), Some("vsys1"));
+        assert!(context.command_policy.is_none());
+
+        let context = context.with_command_policy(CommandPolicy::destructive_defaults());
+        assert!(
+            context
+                .command_policy
+                .as_ref()
+                .is_some_and(|policy| policy.check("reload").is_err())
+        );
+    }
+
+    #[tokio::test]
+    async fn unknown_home_state_is_rejected_before_connecting() {
+        let request = ConnectionRequest::new(
+            "admin".to_string(),
+            "192.0.2.1".to_string(),
+            22,
+            "password".to_string(),
+            None,
+            templates::cisco().expect("template"),
+        );
+        let context = ExecutionContext::new().with_home_state("Nowhere");
+        assert_eq!(context.home_state.as_deref(), Some("Nowhere"));
+
+        let err = SshConnectionManager::new()
+            .get_with_context(request, context)
+            .await
+            .expect_err("unknown home state");
+        assert!(
+            matches!(err.root_cause(), ConnectError::UnreachableState(state) if state == "Nowhere")
+        );
+    }
+
+    #[test]
+    fn session_lifetime_exceeded_respects_optional_limit() {
+        use manager::session_lifetime_exceeded;
+
+        let max = Some(Duration::from_secs(3600));
+        assert!(!session_lifetime_exceeded(Duration::from_secs(10), None));
+        assert!(!session_lifetime_exceeded(Duration::from_secs(3599), max));
+        assert!(session_lifetime_exceeded(Duration::from_secs(3600), max));
+    }
+
+    #[test]
+    fn command_builder_and_shortcuts_fill_fields() {
+        let built = Command::builder()
+            .mode("Enable")
+            .command("show version")
+            .timeout(30)
+            .build();
+        assert_eq!(built, Command::show("show version").with_timeout(30));
+        assert_eq!(built.mode, "Enable");
+        assert_eq!(built.timeout, Some(30));
+
+        let config = Command::config("hostname edge-1");
+        assert_eq!(config.mode, "Config");
+        assert_eq!(config.command, "hostname edge-1");
+        assert_eq!(config.timeout, None);
+    }
+
+    #[test]
+    fn failed_step_hint_points_at_first_incomplete_flow_step() {
+        use manager::failed_step_hint;
+
+        let flow = CommandFlow::new(vec![
+            Command::show("terminal length 0"),
+            Command::config("hostname edge-1"),
+        ]);
+        let operation = SessionOperation::from(flow);
+
+        assert_eq!(
+            failed_step_hint(&operation, 1),
+            (
+                Some("Config".to_string()),
+                Some("hostname edge-1".to_string())
+            )
+        );
+        assert_eq!(failed_step_hint(&operation, 2), (None, None));
+    }
+
+    #[test]
+    fn manager_max_session_lifetime_can_be_updated() {
+        let manager = SshConnectionManager::new();
+        assert_eq!(manager.max_session_lifetime(), None);
+
+        manager.set_max_session_lifetime(Some(Duration::from_secs(8 * 3600)));
+        assert_eq!(
+            manager.max_session_lifetime(),
+            Some(Duration::from_secs(8 * 3600))
+        );
     }
 
     #[test]
@@ -657,6 +1441,7 @@ mod tests {
                     content: "ok".to_string(),
                     all: "ok".to_string(),
                     prompt: Some("router#".to_string()),
+                    spilled: None,
                 }],
             },
         );
@@ -0,0 +1,150 @@
+//! Command guardrails enforced before anything is sent to a device.
+
+use regex::RegexSet;
+
+use super::*;
+
+/// Deny-list of command regexes that must never reach a device.
+///
+/// Patterns are matched against the trimmed command text. Use inline flags
+/// such as `(?i)` for case-insensitive matching.
+#[derive(Debug, Clone, Default)]
+pub struct CommandPolicy {
+    patterns: Vec<String>,
+    deny: Option<RegexSet>,
+}
+
+impl CommandPolicy {
+    /// Build a policy rejecting every command that matches one of `patterns`.
+    pub fn deny<I, S>(patterns: I) -> Result<Self, ConnectError>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let patterns: Vec<String> = patterns.into_iter().map(Into::into).collect();
+        if patterns.is_empty() {
+            return Ok(Self::default());
+        }
+        let deny = RegexSet::new(&patterns)
+            .map_err(|err| ConnectError::InvalidCommandPolicy(err.to_string()))?;
+        Ok(Self {
+            patterns,
+            deny: Some(deny),
+        })
+    }
+
+    /// Policy blocking common destructive network device commands.
+    pub fn destructive_defaults() -> Self {
+        Self::deny([
+            r"(?i)^reload\b",
+            r"(?i)^reboot\b",
+            r"(?i)^erase\s+(startup-config|nvram:|flash:)",
+            r"(?i)^write\s+erase\b",
+            r"(?i)^format\b",
+            r"(?i)^delete\s+/force\b",
+        ])
+        .expect("built-in command policy patterns are valid")
+    }
+
+    /// Configured deny patterns.
+    pub fn patterns(&self) -> &[String] {
+        &self.patterns
+    }
+
+    /// Returns true when no command is blocked.
+    pub fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+    }
+
+    /// Combine two policies; a command is blocked when either policy blocks it.
+    pub fn merge(&self, other: &CommandPolicy) -> CommandPolicy {
+        if other.is_empty() {
+            return self.clone();
+        }
+        if self.is_empty() {
+            return other.clone();
+        }
+        let mut patterns = self.patterns.clone();
+        for pattern in &other.patterns {
+            if !patterns.contains(pattern) {
+                patterns.push(pattern.clone());
+            }
+        }
+        Self::deny(patterns).expect("merged command policy patterns were already validated")
+    }
+
+    /// Returns the first deny pattern matching `command`, if any.
+    pub fn blocking_pattern(&self, command: &str) -> Option<&str> {
+        let deny = self.deny.as_ref()?;
+        deny.matches(command.trim())
+            .iter()
+            .next()
+            .map(|index| self.patterns[index].as_str())
+    }
+
+    /// Fail with [`ConnectError::CommandBlockedByPolicy`] when `command` is denied.
+    pub fn check(&self, command: &str) -> Result<(), ConnectError> {
+        match self.blocking_pattern(command) {
+            Some(pattern) => Err(ConnectError::CommandBlockedByPolicy {
+                command: command.to_string(),
+                pattern: pattern.to_string(),
+            }),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deny_policy_blocks_matching_commands() {
+        let policy =
+            CommandPolicy::deny([r"^reload\b", r"^erase\s+startup-config"]).expect("policy");
+
+        assert_eq!(policy.blocking_pattern("  reload in 5"), Some(r"^reload\b"));
+        assert!(policy.check("show running-config").is_ok());
+
+        let err = policy
+            .check("erase startup-config")
+            .expect_err("erase should be blocked");
+        assert!(matches!(
+            err,
+            ConnectError::CommandBlockedByPolicy { ref pattern, .. }
+                if pattern == r"^erase\s+startup-config"
+        ));
+    }
+
+    #[test]
+    fn deny_policy_rejects_invalid_regex() {
+        let err = CommandPolicy::deny(["("]).expect_err("invalid regex");
+        assert!(matches!(err, ConnectError::InvalidCommandPolicy(_)));
+    }
+
+    #[test]
+    fn destructive_defaults_are_case_insensitive() {
+        let policy = CommandPolicy::destructive_defaults();
+        assert!(policy.check("RELOAD").is_err());
+        assert!(policy.check("format flash:").is_err());
+        assert!(policy.check("show reload").is_ok());
+    }
+
+    #[test]
+    fn merge_blocks_commands_from_both_policies() {
+        let global = CommandPolicy::deny([r"^reload"]).expect("policy");
+        let local = CommandPolicy::deny([r"^format", r"^reload"]).expect("policy");
+
+        let merged = global.merge(&local);
+
+        assert_eq!(merged.patterns(), [r"^reload", r"^format"]);
+        assert!(merged.check("format disk0:").is_err());
+        assert!(merged.check("reload").is_err());
+        assert!(
+            global
+                .merge(&CommandPolicy::default())
+                .check("format")
+                .is_ok()
+        );
+    }
+}
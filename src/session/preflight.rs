@@ -0,0 +1,446 @@
+//! Pre-flight checks run before a workflow touches the device.
+//!
+//! With [`TxWorkflow::preflight`] set, every mode the workflow's commands
+//! and rollbacks need is planned from the session's current state before
+//! the first block runs. Unreachable devices, modes without a path and
+//! transitions missing dyn_params fail the workflow with
+//! [`ConnectError::PreflightFailed`] and nothing is sent. Templates with
+//! [`command_stems`](crate::device::DeviceHandlerConfig::command_stems) also
+//! warn about commands whose words match no known stem, such as
+//! `shw version`; stem lists are never complete, so these warnings do not
+//! fail the workflow.
+
+use std::collections::HashSet;
+
+use super::*;
+
+/// Something that would stop the workflow part-way through.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum PreflightIssue {
+    /// The connection could not be established.
+    Unreachable { reason: String },
+    /// The workflow or one of its operations is malformed.
+    InvalidWorkflow { reason: String },
+    /// No transition path leads from the current state to `mode`.
+    UnreachableMode {
+        block: usize,
+        /// `None` for the block's whole-resource rollback.
+        step: Option<usize>,
+        mode: String,
+    },
+    /// A transition on the way to `mode` needs dyn_params nobody supplies.
+    MissingParams {
+        block: usize,
+        /// `None` for the block's whole-resource rollback.
+        step: Option<usize>,
+        mode: String,
+        command: String,
+        keys: Vec<String>,
+    },
+    /// `command` matches none of the template's command stems for `mode`.
+    ///
+    /// Only ever reported in [`PreflightReport::warnings`].
+    UnknownCommand {
+        block: usize,
+        /// `None` for the block's rollback or candidate commands.
+        step: Option<usize>,
+        mode: String,
+        command: String,
+        /// First word no stem continues with.
+        word: String,
+        /// Known word closest to `word`.
+        suggestion: Option<String>,
+    },
+}
+
+/// Outcome of the pre-flight checks of one workflow.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct PreflightReport {
+    pub workflow_name: String,
+    pub reachable: bool,
+    /// State the paths were planned from; empty when unreachable.
+    pub current_state: String,
+    pub issues: Vec<PreflightIssue>,
+    /// Likely typos that do not fail the workflow, see
+    /// [`PreflightIssue::UnknownCommand`].
+    #[serde(default)]
+    pub warnings: Vec<PreflightIssue>,
+}
+
+impl PreflightReport {
+    /// Report for a device the connection attempt failed on.
+    pub fn unreachable(workflow: &TxWorkflow, err: &ConnectError) -> Self {
+        Self {
+            workflow_name: workflow.name.clone(),
+            reachable: false,
+            current_state: String::new(),
+            issues: vec![PreflightIssue::Unreachable {
+                reason: err.to_string(),
+            }],
+            warnings: Vec::new(),
+        }
+    }
+
+    /// True when no issue was found; warnings do not count.
+    pub fn passed(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    /// `Ok` when passed, otherwise [`ConnectError::PreflightFailed`].
+    pub fn into_result(self) -> Result<(), ConnectError> {
+        if self.passed() {
+            Ok(())
+        } else {
+            Err(ConnectError::PreflightFailed(Box::new(self)))
+        }
+    }
+}
+
+impl SharedSshClient {
+    /// Check `workflow` against this session without sending anything.
+    ///
+    /// Modes the workflow omits are derived first, as when executing it.
+    pub fn preflight_tx_workflow(
+        &mut self,
+        workflow: &TxWorkflow,
+        sys: Option<&String>,
+    ) -> PreflightReport {
+        let mut workflow = workflow.clone();
+        for block in &mut workflow.blocks {
+            self.fill_missing_modes(block, sys);
+        }
+        let mut report = preflight_workflow(&mut self.handler, &workflow, sys);
+        report.reachable = self.is_connected();
+        if !report.reachable {
+            report.issues.insert(
+                0,
+                PreflightIssue::Unreachable {
+                    reason: ConnectError::ConnectClosedError.to_string(),
+                },
+            );
+        }
+        report
+    }
+}
+
+impl SshConnectionManager {
+    /// Connect and check `workflow` without executing it.
+    ///
+    /// Connection failures are reported as [`PreflightIssue::Unreachable`]
+    /// rather than returned as errors.
+    pub async fn preflight_tx_workflow_with_context(
+        &self,
+        request: ConnectionRequest,
+        workflow: &TxWorkflow,
+        context: ExecutionContext,
+    ) -> PreflightReport {
+        let device_addr = request.device_addr();
+        if let Err(err) = self
+            .get_with_request_and_recording(request, &context, None)
+            .await
+        {
+            return PreflightReport::unreachable(workflow, &err);
+        }
+        let Some(pooled) = self.cache.get(&device_addr).await else {
+            return PreflightReport::unreachable(
+                workflow,
+                &ConnectError::InternalServerError("connection cache miss".to_string()),
+            );
+        };
+        let mut client = pooled.client.write().await;
+        client.set_job_scope(JobScope::from(&context));
+        client.preflight_tx_workflow(workflow, context.sys.as_ref())
+    }
+}
+
+/// Plan every command of `workflow` from the handler's current state.
+///
+/// Each mode is checked once unless a command brings its own dyn_params,
+/// which are applied for that command only, as during execution.
+fn preflight_workflow(
+    handler: &mut DeviceHandler,
+    workflow: &TxWorkflow,
+    sys: Option<&String>,
+) -> PreflightReport {
+    let mut report = PreflightReport {
+        workflow_name: workflow.name.clone(),
+        reachable: true,
+        current_state: handler.current_state().to_string(),
+        issues: Vec::new(),
+        warnings: Vec::new(),
+    };
+    if let Err(err) = workflow.validate() {
+        report.issues.push(PreflightIssue::InvalidWorkflow {
+            reason: err.to_string(),
+        });
+        return report;
+    }
+
+    let mut checked = HashSet::new();
+    for (block_idx, block) in workflow.blocks.iter().enumerate() {
+        let mut operations = Vec::new();
+        for (step_idx, step) in block.steps.iter().enumerate() {
+            operations.push((Some(step_idx), &step.run));
+            operations.extend(step.rollback.as_ref().map(|op| (Some(step_idx), op)));
+        }
+        let snapshot;
+        let candidate_operations;
+        match &block.rollback_policy {
+            RollbackPolicy::WholeResource { rollback, .. } => {
+                operations.push((None, rollback.as_ref()));
+            }
+            RollbackPolicy::ConfigDiff {
+                snapshot: command,
+                grammar,
+            } => {
+                // The generated rollback runs in the grammar's mode.
+                let mut probe = command.as_ref().clone();
+                probe.mode = grammar.mode.clone();
+                snapshot = [
+                    SessionOperation::Command(command.as_ref().clone()),
+                    SessionOperation::Command(probe),
+                ];
+                operations.extend(snapshot.iter().map(|operation| (None, operation)));
+            }
+            RollbackPolicy::Candidate(candidate) => {
+                candidate_operations = candidate.operations();
+                operations.extend(
+                    candidate_operations
+                        .iter()
+                        .map(|operation| (None, operation)),
+                );
+            }
+            _ => {}
+        }
+
+        for (step, operation) in operations {
+            let flow = match operation.to_command_flow() {
+                Ok(flow) => flow,
+                Err(err) => {
+                    report.issues.push(PreflightIssue::InvalidWorkflow {
+                        reason: format!("block[{block_idx}] step {step:?}: {err}"),
+                    });
+                    continue;
+                }
+            };
+            for command in &flow.steps {
+                // Exec-channel commands never change mode.
+                if command.exec_channel == Some(true) {
+                    continue;
+                }
+                if let Some(issue) = handler.check_syntax(&command.mode, &command.command) {
+                    report.warnings.push(PreflightIssue::UnknownCommand {
+                        block: block_idx,
+                        step,
+                        mode: issue.mode,
+                        command: issue.command,
+                        word: issue.word,
+                        suggestion: issue.suggestion,
+                    });
+                }
+                let mode = command.mode.to_ascii_lowercase();
+                let overrides = command.dyn_params.runtime_values();
+                if overrides.is_empty() && !checked.insert(mode.clone()) {
+                    continue;
+                }
+                let saved = (!overrides.is_empty()).then(|| handler.dyn_param.clone());
+                handler.dyn_param.extend(overrides);
+                let planned = handler.trans_state_write(&mode, sys);
+                if let Some(saved) = saved {
+                    handler.dyn_param = saved;
+                }
+                match planned {
+                    Ok(_) => {}
+                    Err(ConnectError::UnresolvedPlaceholders {
+                        command: transition,
+                        keys,
+                    }) => {
+                        report.issues.push(PreflightIssue::MissingParams {
+                            block: block_idx,
+                            step,
+                            mode: command.mode.clone(),
+                            command: transition,
+                            keys,
+                        });
+                    }
+                    Err(_) => report.issues.push(PreflightIssue::UnreachableMode {
+                        block: block_idx,
+                        step,
+                        mode: command.mode.clone(),
+                    }),
+                }
+            }
+        }
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device::{DeviceHandlerConfig, prompt_rule, transition_rule};
+
+    fn handler() -> DeviceHandler {
+        let mut handler = DeviceHandlerConfig {
+            prompt: vec![
+                prompt_rule("Enable", &[r"^\S+#\s*$"]),
+                prompt_rule("Config", &[r"^\S+\(config\)#\s*$"]),
+                prompt_rule("Context", &[r"^\S+/\S+#\s*$"]),
+                prompt_rule("Isolated", &[r"^\S+\$\s*$"]),
+            ],
+            edges: vec![
+                transition_rule("Enable", "configure terminal", "Config", false, false),
+                transition_rule("Config", "end", "Enable", true, false),
+                transition_rule("Enable", "changeto {ctx}", "Context", false, true),
+            ],
+            ..DeviceHandlerConfig::default()
+        }
+        .build()
+        .expect("handler");
+        handler.read("fw-01#");
+        handler
+    }
+
+    fn workflow(steps: Vec<TxStep>) -> TxWorkflow {
+        TxWorkflow {
+            name: "push".to_string(),
+            blocks: vec![TxBlock {
+                name: "objects".to_string(),
+                kind: CommandBlockKind::Config,
+                rollback_policy: RollbackPolicy::PerStep,
+                steps,
+                fail_fast: true,
+            }],
+            fail_fast: true,
+            preflight: true,
+            idempotency_key: None,
+        }
+    }
+
+    #[test]
+    fn reports_unreachable_modes_and_missing_params() {
+        let mut handler = handler();
+        let report = preflight_workflow(
+            &mut handler,
+            &workflow(vec![
+                TxStep::new(Command::new("Config", "vlan 10"))
+                    .with_rollback(Command::new("Config", "no vlan 10")),
+                TxStep::new(Command::new("Context", "object web"))
+                    .with_rollback(Command::new("Isolated", "rm web")),
+            ]),
+            None,
+        );
+        assert_eq!(report.current_state, "enable");
+        assert!(!report.passed());
+        assert_eq!(
+            report.issues,
+            [
+                PreflightIssue::MissingParams {
+                    block: 0,
+                    step: Some(1),
+                    mode: "Context".to_string(),
+                    command: "changeto {ctx}".to_string(),
+                    keys: vec!["ctx".to_string()],
+                },
+                PreflightIssue::UnreachableMode {
+                    block: 0,
+                    step: Some(1),
+                    mode: "Isolated".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn command_dyn_params_satisfy_transitions() {
+        let mut handler = handler();
+        let mut context = Command::new("Context", "object web");
+        context.dyn_params.insert_extra("ctx", "admin");
+        let report = preflight_workflow(
+            &mut handler,
+            &workflow(vec![
+                TxStep::new(context).with_rollback(Command::new("Config", "no object web")),
+            ]),
+            None,
+        );
+        assert!(report.passed(), "{:?}", report.issues);
+        assert!(!handler.dyn_param.contains_key("ctx"));
+        assert!(report.into_result().is_ok());
+    }
+
+    #[test]
+    fn warns_about_commands_outside_the_template_stems() {
+        let mut handler = crate::templates::cisco().expect("cisco");
+        handler.read("core-01#");
+        let report = preflight_workflow(
+            &mut handler,
+            &workflow(vec![
+                TxStep::new(Command::new("Config", "int Gi0/1"))
+                    .with_rollback(Command::new("Config", "no interface Gi0/1")),
+                TxStep::new(Command::new("Enable", "shw version")),
+            ]),
+            None,
+        );
+        assert!(report.passed(), "{:?}", report.issues);
+        assert_eq!(
+            report.warnings,
+            [PreflightIssue::UnknownCommand {
+                block: 0,
+                step: Some(1),
+                mode: "Enable".to_string(),
+                command: "shw version".to_string(),
+                word: "shw".to_string(),
+                suggestion: Some("show".to_string()),
+            }]
+        );
+    }
+
+    #[test]
+    fn real_sub_mode_configs_pass_the_builtin_stems() {
+        let mut cisco = crate::templates::cisco().expect("cisco");
+        cisco.read("core-01#");
+        let cisco_steps = [
+            "router ospf 1",
+            "router-id 10.0.0.1",
+            "area 0 authentication message-digest",
+            "router bgp 65000",
+            "bgp log-neighbor-changes",
+            "vlan 10",
+            "name users",
+            "line vty 0 4",
+            "access-class MGMT in",
+            "policy-map WAN",
+            "class VOICE",
+            "priority percent 20",
+            "class class-default",
+            "shape average 50000000",
+        ]
+        .map(|command| TxStep::new(Command::new("Config", command)));
+        let mut huawei = crate::templates::huawei().expect("huawei");
+        huawei.read("<core-01>");
+        let huawei_steps = [
+            "interface Eth-Trunk1",
+            "mode lacp",
+            "interface GigabitEthernet0/0/1",
+            "port-security enable",
+        ]
+        .map(|command| TxStep::new(Command::new("Config", command)));
+
+        for (handler, steps) in [
+            (&mut cisco, cisco_steps.to_vec()),
+            (&mut huawei, huawei_steps.to_vec()),
+        ] {
+            let report = preflight_workflow(handler, &workflow(steps), None);
+            assert!(report.passed(), "{:?}", report.issues);
+            assert!(report.warnings.is_empty(), "{:?}", report.warnings);
+        }
+        for command in [
+            "install add file flash:image.bin",
+            "license boot level network-advantage",
+            "monitor capture CAP start",
+        ] {
+            assert_eq!(cisco.check_syntax("Enable", command), None);
+        }
+    }
+}
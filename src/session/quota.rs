@@ -0,0 +1,343 @@
+//! Per-tag pool quotas.
+//!
+//! Tenants share one pool ([`Settings::pool_max_connections`](crate::config::Settings::pool_max_connections)).
+//! A [`TagQuota`] set with [`SshConnectionManager::set_tag_quota`] caps how
+//! many pooled connections carry a tag and how many commands run at once on
+//! them, so one tenant's bulk job cannot take every slot.
+
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use super::*;
+
+/// The manager's tag quotas, shared with every connection it pools.
+pub(crate) type TagQuotas = Arc<std::sync::RwLock<HashMap<String, QuotaSlot>>>;
+
+/// Connections being opened or tagged, per limited tag: device cache key
+/// and the number of requests working on it.
+pub(crate) type ConnectionReservations =
+    Arc<std::sync::Mutex<HashMap<String, HashMap<String, usize>>>>;
+
+/// Holds a connection slot of every limited tag of one request until the
+/// connection is pooled or the attempt fails.
+pub(crate) struct ConnectionReservation {
+    reservations: ConnectionReservations,
+    device_addr: String,
+    tags: Vec<String>,
+}
+
+impl Drop for ConnectionReservation {
+    fn drop(&mut self) {
+        let mut reservations = self
+            .reservations
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        for tag in &self.tags {
+            let Some(devices) = reservations.get_mut(tag) else {
+                continue;
+            };
+            if let Some(count) = devices.get_mut(&self.device_addr) {
+                *count -= 1;
+                if *count == 0 {
+                    devices.remove(&self.device_addr);
+                }
+            }
+            if devices.is_empty() {
+                reservations.remove(tag);
+            }
+        }
+    }
+}
+
+/// A quota and the command slots it hands out.
+#[derive(Debug)]
+pub(crate) struct QuotaSlot {
+    quota: TagQuota,
+    commands: Option<Arc<Semaphore>>,
+}
+
+/// Limits for the connections carrying one tag; `None` leaves a limit off.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct TagQuota {
+    /// Pooled connections carrying the tag, counting those still being
+    /// opened. Opening one more fails with [`ConnectError::QuotaExceeded`].
+    #[serde(default)]
+    pub max_connections: Option<usize>,
+    /// Commands running at once on those connections; further commands wait
+    /// for a slot. At least 1.
+    #[serde(default)]
+    pub max_concurrent_commands: Option<usize>,
+}
+
+impl TagQuota {
+    /// No limits.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cap pooled connections carrying the tag.
+    pub fn with_max_connections(mut self, max: usize) -> Self {
+        self.max_connections = Some(max);
+        self
+    }
+
+    /// Cap commands running at once on connections carrying the tag.
+    pub fn with_max_concurrent_commands(mut self, max: usize) -> Self {
+        self.max_concurrent_commands = Some(max);
+        self
+    }
+}
+
+/// Wait for a command slot of every quota `tags` fall under.
+///
+/// Tags are taken in sorted order so two connections sharing several
+/// limited tags cannot each hold a slot the other waits for.
+pub(crate) async fn acquire_command_slots(
+    quotas: &TagQuotas,
+    tags: &std::collections::BTreeSet<String>,
+) -> Vec<OwnedSemaphorePermit> {
+    let semaphores: Vec<(String, Arc<Semaphore>)> = {
+        let quotas = quotas
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        tags.iter()
+            .filter_map(|tag| {
+                let commands = quotas.get(tag)?.commands.clone()?;
+                Some((tag.clone(), commands))
+            })
+            .collect()
+    };
+    let mut permits = Vec::with_capacity(semaphores.len());
+    for (tag, semaphore) in semaphores {
+        if semaphore.available_permits() == 0 {
+            debug!("Waiting for a command slot of tag {}", tag);
+        }
+        // Quota semaphores are never closed.
+        if let Ok(permit) = semaphore.acquire_owned().await {
+            permits.push(permit);
+        }
+    }
+    permits
+}
+
+impl SshConnectionManager {
+    /// Set the quota of `tag`, or remove it with `None`.
+    ///
+    /// Connection limits apply when a connection is opened for or tagged by
+    /// a request. Command limits apply to the next command on every pooled
+    /// connection carrying the tag; commands already running keep their slot.
+    pub fn set_tag_quota(&self, tag: impl Into<String>, quota: Option<TagQuota>) {
+        let tag = tag.into();
+        let mut quotas = self
+            .tag_quotas
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        match quota {
+            Some(quota) => {
+                let commands = quota
+                    .max_concurrent_commands
+                    .map(|max| Arc::new(Semaphore::new(max.max(1))));
+                quotas.insert(tag, QuotaSlot { quota, commands });
+            }
+            None => {
+                quotas.remove(&tag);
+            }
+        }
+    }
+
+    /// The quota of `tag`, if any.
+    pub fn tag_quota(&self, tag: &str) -> Option<TagQuota> {
+        self.tag_quotas
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .get(tag)
+            .map(|slot| slot.quota)
+    }
+
+    /// Reserve a connection slot of every quota `tags` fall under, or fail
+    /// when one more connection would exceed it.
+    ///
+    /// Pooled connections and those still being opened both count, so
+    /// concurrent requests cannot overshoot a limit while they connect. The
+    /// connection at `device_addr` is counted once at most: it is the one
+    /// being opened, replaced or tagged. The slot is released when the
+    /// reservation is dropped, after the connection is pooled or failed.
+    pub(super) fn reserve_connection_slot(
+        &self,
+        device_addr: &str,
+        tags: &[String],
+    ) -> Result<ConnectionReservation, ConnectError> {
+        let limits: Vec<(&String, usize)> = {
+            let quotas = self
+                .tag_quotas
+                .read()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+            tags.iter()
+                .filter_map(|tag| Some((tag, quotas.get(tag)?.quota.max_connections?)))
+                .collect()
+        };
+        let mut reservations = self
+            .connection_reservations
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        for &(tag, limit) in &limits {
+            let mut devices: std::collections::HashSet<String> = self
+                .cache
+                .iter()
+                .filter(|(_, pooled)| pooled.status.has_tag(tag))
+                .map(|(addr, _)| addr.as_str().to_string())
+                .collect();
+            if let Some(connecting) = reservations.get(tag) {
+                devices.extend(connecting.keys().cloned());
+            }
+            devices.remove(device_addr);
+            if devices.len() >= limit {
+                return Err(ConnectError::QuotaExceeded {
+                    tag: tag.clone(),
+                    limit,
+                });
+            }
+        }
+        let tags: Vec<String> = limits.into_iter().map(|(tag, _)| tag.clone()).collect();
+        for tag in &tags {
+            *reservations
+                .entry(tag.clone())
+                .or_default()
+                .entry(device_addr.to_string())
+                .or_default() += 1;
+        }
+        Ok(ConnectionReservation {
+            reservations: self.connection_reservations.clone(),
+            device_addr: device_addr.to_string(),
+            tags,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn command_slots_follow_the_tag_quotas() {
+        let manager = SshConnectionManager::new();
+        let tags: std::collections::BTreeSet<String> =
+            ["site:fra1".to_string(), "tenant:acme".to_string()].into();
+        assert!(
+            acquire_command_slots(&manager.tag_quotas, &tags)
+                .await
+                .is_empty()
+        );
+
+        manager.set_tag_quota(
+            "tenant:acme",
+            Some(
+                TagQuota::new()
+                    .with_max_connections(0)
+                    .with_max_concurrent_commands(1),
+            ),
+        );
+        assert_eq!(
+            manager
+                .tag_quota("tenant:acme")
+                .and_then(|q| q.max_connections),
+            Some(0)
+        );
+        let request_tags: Vec<String> = tags.iter().cloned().collect();
+        assert!(matches!(
+            manager.reserve_connection_slot("admin@192.0.2.1:22", &request_tags),
+            Err(ConnectError::QuotaExceeded { tag, limit: 0 }) if tag == "tenant:acme"
+        ));
+        assert!(
+            manager
+                .reserve_connection_slot("admin@192.0.2.1:22", &["site:fra1".to_string()])
+                .is_ok()
+        );
+
+        let held = acquire_command_slots(&manager.tag_quotas, &tags).await;
+        assert_eq!(held.len(), 1);
+        let waiting = tokio::time::timeout(
+            Duration::from_millis(50),
+            acquire_command_slots(&manager.tag_quotas, &tags),
+        )
+        .await;
+        assert!(waiting.is_err());
+        drop(held);
+        assert_eq!(
+            acquire_command_slots(&manager.tag_quotas, &tags)
+                .await
+                .len(),
+            1
+        );
+
+        manager.set_tag_quota("tenant:acme", None);
+        assert_eq!(manager.tag_quota("tenant:acme"), None);
+    }
+
+    #[test]
+    fn refused_reuse_leaves_the_pooled_status_unchanged() {
+        let manager = SshConnectionManager::new();
+        manager.set_tag_quota("tenant:acme", Some(TagQuota::new().with_max_connections(0)));
+        manager.set_default_command_timeout(Duration::from_secs(7));
+        let status = status::ConnectionStatus::new("admin@192.0.2.1:22".to_string(), None);
+        let timeout = status.command_timeout();
+        let recorder = SessionRecorder::new(SessionRecordLevel::Full);
+
+        assert!(matches!(
+            manager.adopt_pooled(
+                "admin@192.0.2.1:22",
+                &status,
+                &["tenant:acme".to_string()],
+                Some(&recorder),
+                CommandPolicy::default(),
+            ),
+            Err(ConnectError::QuotaExceeded { .. })
+        ));
+        assert!(status.recorder().is_none());
+        assert!(!status.has_tag("tenant:acme"));
+        assert_eq!(status.command_timeout(), timeout);
+
+        manager
+            .adopt_pooled(
+                "admin@192.0.2.1:22",
+                &status,
+                &["site:fra1".to_string()],
+                Some(&recorder),
+                CommandPolicy::default(),
+            )
+            .expect("admitted");
+        assert!(status.recorder().is_some());
+        assert!(status.has_tag("site:fra1"));
+        assert_eq!(status.command_timeout(), Duration::from_secs(7));
+    }
+
+    #[test]
+    fn connecting_requests_hold_their_slot_until_released() {
+        let manager = SshConnectionManager::new();
+        manager.set_tag_quota("tenant:acme", Some(TagQuota::new().with_max_connections(1)));
+        let tags = ["tenant:acme".to_string()];
+
+        let connecting = manager
+            .reserve_connection_slot("admin@192.0.2.1:22", &tags)
+            .expect("first slot");
+        assert!(matches!(
+            manager.reserve_connection_slot("admin@192.0.2.2:22", &tags),
+            Err(ConnectError::QuotaExceeded { limit: 1, .. })
+        ));
+        // A second request for the same device shares its slot.
+        let same_device = manager
+            .reserve_connection_slot("admin@192.0.2.1:22", &tags)
+            .expect("same device");
+        drop(connecting);
+        assert!(
+            manager
+                .reserve_connection_slot("admin@192.0.2.2:22", &tags)
+                .is_err()
+        );
+        drop(same_device);
+        assert!(
+            manager
+                .reserve_connection_slot("admin@192.0.2.2:22", &tags)
+                .is_ok()
+        );
+    }
+}
@@ -0,0 +1,272 @@
+//! Waiting for a device to come back after `reload`/`reboot`.
+//!
+//! Run the reboot itself as a [`Command`] with
+//! [`expect_disconnect`](Command::expect_disconnect) set, then call
+//! [`SshConnectionManager::wait_for_reboot`]: it probes the SSH port until
+//! the device answers again, reconnects (which re-detects the prompt) and
+//! optionally runs a verification command such as `show version`.
+
+use regex::Regex;
+
+use super::*;
+use crate::probe;
+
+fn default_initial_delay_secs() -> u64 {
+    30
+}
+
+fn default_reboot_poll_interval_secs() -> u64 {
+    10
+}
+
+fn default_reboot_timeout_secs() -> u64 {
+    900
+}
+
+/// How [`SshConnectionManager::wait_for_reboot`] waits and what it verifies.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq, Eq)]
+pub struct RebootWait {
+    /// Delay before the first probe, so the port is not caught still open
+    /// while the device shuts down.
+    #[serde(default = "default_initial_delay_secs")]
+    pub initial_delay_secs: u64,
+    /// Delay between TCP probes and reconnect attempts.
+    #[serde(default = "default_reboot_poll_interval_secs")]
+    pub poll_interval_secs: u64,
+    /// Overall deadline, counted from the call.
+    #[serde(default = "default_reboot_timeout_secs")]
+    pub timeout_secs: u64,
+    /// Command run once reconnected, e.g. `show version`.
+    #[serde(default)]
+    pub verify: Option<Command>,
+    /// Regex the verification output must match, e.g. the expected version.
+    #[serde(default)]
+    pub expect_pattern: Option<String>,
+}
+
+impl Default for RebootWait {
+    fn default() -> Self {
+        Self {
+            initial_delay_secs: default_initial_delay_secs(),
+            poll_interval_secs: default_reboot_poll_interval_secs(),
+            timeout_secs: default_reboot_timeout_secs(),
+            verify: None,
+            expect_pattern: None,
+        }
+    }
+}
+
+impl RebootWait {
+    /// Probe every 10 seconds after a 30 second delay, for up to 15 minutes.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_initial_delay_secs(mut self, initial_delay_secs: u64) -> Self {
+        self.initial_delay_secs = initial_delay_secs;
+        self
+    }
+
+    pub fn with_poll_interval_secs(mut self, poll_interval_secs: u64) -> Self {
+        self.poll_interval_secs = poll_interval_secs;
+        self
+    }
+
+    pub fn with_timeout_secs(mut self, timeout_secs: u64) -> Self {
+        self.timeout_secs = timeout_secs;
+        self
+    }
+
+    /// Run `command` after reconnecting and require its output to match
+    /// `expect_pattern`, when given.
+    pub fn with_verify(mut self, command: Command, expect_pattern: Option<String>) -> Self {
+        self.verify = Some(command);
+        self.expect_pattern = expect_pattern;
+        self
+    }
+
+    fn expect_regex(&self) -> Result<Option<Regex>, ConnectError> {
+        self.expect_pattern
+            .as_deref()
+            .map(|pattern| {
+                Regex::new(pattern).map_err(|err| {
+                    ConnectError::InvalidRequest(format!(
+                        "invalid reboot expect pattern '{pattern}': {err}"
+                    ))
+                })
+            })
+            .transpose()
+    }
+}
+
+/// Result of [`SshConnectionManager::wait_for_reboot`].
+#[derive(Debug, Clone)]
+pub struct RebootOutcome {
+    /// Time from the call until the new session was ready.
+    pub elapsed: Duration,
+    /// Prompt detected on the new session.
+    pub prompt: Option<String>,
+    /// Output of [`RebootWait::verify`].
+    pub verify: Option<Output>,
+}
+
+impl SshConnectionManager {
+    /// Wait for a rebooted device to accept SSH again and reconnect to it.
+    ///
+    /// `make_request` is called again for every reconnect attempt, since a
+    /// request is consumed by each try.
+    ///
+    /// Fails with [`ConnectError::DeviceTimeout`] when the device is not back
+    /// within [`RebootWait::timeout_secs`], and with
+    /// [`ConnectError::RebootVerifyFailed`] when the verification command
+    /// fails or its output does not match.
+    pub async fn wait_for_reboot(
+        &self,
+        make_request: impl Fn() -> Result<ConnectionRequest, ConnectError>,
+        wait: RebootWait,
+        context: ExecutionContext,
+    ) -> Result<RebootOutcome, ConnectError> {
+        let expect = wait.expect_regex()?;
+        let started = Instant::now();
+        let deadline = Duration::from_secs(wait.timeout_secs);
+        let interval = Duration::from_secs(wait.poll_interval_secs);
+        let sys = context.sys.clone();
+        let request = make_request()?;
+        let (device_addr, addr, port) = (request.device_addr(), request.addr.clone(), request.port);
+
+        // The pooled session died with the reboot; never hand it out again.
+        self.cache.invalidate(&device_addr).await;
+        tokio::time::sleep(Duration::from_secs(wait.initial_delay_secs).min(deadline)).await;
+        loop {
+            let probe_timeout = interval.max(Duration::from_secs(1));
+            let resolver = self.host_resolver();
+            let answered = probe::tcp_check_with(
+                &addr,
+                port,
+                probe_timeout,
+                context.address_family,
+                resolver.as_deref(),
+            )
+            .await;
+            match answered {
+                Ok(_) => break,
+                Err(err) if started.elapsed() + interval > deadline => {
+                    debug!("{} still down: {}", device_addr, err);
+                    return Err(ConnectError::DeviceTimeout(deadline));
+                }
+                Err(_) => tokio::time::sleep(interval).await,
+            }
+        }
+        debug!("{} answers on port {} again", device_addr, port);
+
+        // The SSH daemon can lag behind the open port.
+        let mut request = request;
+        let sender = loop {
+            match self.get_with_context(request, context.clone()).await {
+                Ok(sender) => break sender,
+                Err(err) if err.is_retryable() && started.elapsed() + interval <= deadline => {
+                    debug!("{} not ready yet: {}", device_addr, err);
+                    tokio::time::sleep(interval).await;
+                    request = make_request()?;
+                }
+                Err(err) => return Err(err),
+            }
+        };
+
+        let prompt = match self.cache.get(&device_addr).await {
+            Some(pooled) => Some(pooled.client.read().await.prompt.clone()),
+            None => None,
+        };
+        let verify = match wait.verify {
+            Some(command) => {
+                let (responder, result) = oneshot::channel();
+                sender
+                    .send(CmdJob {
+                        data: command,
+                        sys,
+                        recorder: None,
+                        responder,
+                        idempotency_key: None,
+                        scope: JobScope::from(&context),
+                    })
+                    .await
+                    .map_err(|_| ConnectError::ConnectClosedError)?;
+                let output = result
+                    .await
+                    .map_err(|_| ConnectError::ConnectClosedError)??;
+                check_verify_output(&output, expect.as_ref())?;
+                Some(output)
+            }
+            None => None,
+        };
+        Ok(RebootOutcome {
+            elapsed: started.elapsed(),
+            prompt,
+            verify,
+        })
+    }
+}
+
+fn check_verify_output(output: &Output, expect: Option<&Regex>) -> Result<(), ConnectError> {
+    if !output.success {
+        return Err(ConnectError::RebootVerifyFailed(
+            "verification command failed".to_string(),
+        ));
+    }
+    match expect {
+        Some(re) if !re.is_match(&output.content) => Err(ConnectError::RebootVerifyFailed(
+            format!("output does not match '{}'", re.as_str()),
+        )),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn output(success: bool, content: &str) -> Output {
+        Output {
+            success,
+            exit_code: None,
+            content: content.to_string(),
+            segments: vec![content.to_string()],
+            prompt: None,
+            spilled: None,
+            device_addr: None,
+            sys: None,
+            async_messages: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn expect_disconnect_defaults_to_off() {
+        let reload: Command =
+            serde_json::from_str(r#"{"mode":"Enable","command":"reload","timeout":null}"#)
+                .expect("command");
+        assert!(!reload.expect_disconnect);
+        assert!(reload.with_expect_disconnect().expect_disconnect);
+    }
+
+    #[test]
+    fn verification_requires_success_and_expected_output() {
+        let wait = RebootWait::new().with_verify(
+            Command::show("show version"),
+            Some(r"Version 17\.9\.4".to_string()),
+        );
+        let expect = wait.expect_regex().expect("pattern");
+        let upgraded = output(true, "Cisco IOS XE Software, Version 17.9.4a");
+        assert!(check_verify_output(&upgraded, expect.as_ref()).is_ok());
+        assert!(matches!(
+            check_verify_output(&output(true, "Version 17.6.1"), expect.as_ref()),
+            Err(ConnectError::RebootVerifyFailed(_))
+        ));
+        assert!(check_verify_output(&output(false, "Version 17.9.4"), None).is_err());
+        assert!(
+            RebootWait::new()
+                .with_verify(Command::show("show version"), Some("(".to_string()))
+                .expect_regex()
+                .is_err()
+        );
+    }
+}
@@ -1,5 +1,7 @@
 use super::*;
-use std::sync::{Arc, Mutex};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, Weak};
 use std::time::{SystemTime, UNIX_EPOCH};
 use tokio::sync::broadcast;
 
@@ -20,6 +22,16 @@ pub enum SessionRecordLevel {
 /// A single recorded session event.
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct SessionRecordEntry {
+    /// Position in the recording, starting at 1; replays order by this.
+    ///
+    /// Zero in recordings made before sequence numbers were added.
+    #[serde(default)]
+    pub seq: u64,
+    /// Milliseconds since the recorder was created, from a monotonic clock.
+    #[serde(default)]
+    pub mono_ms: u64,
+    /// Wall-clock Unix milliseconds. Metadata only: it can jump backwards
+    /// when the system clock is stepped.
     pub ts_ms: u128,
     pub event: SessionEvent,
 }
@@ -51,6 +63,9 @@ impl Default for NormalizeOptions {
 pub enum SessionEvent {
     ConnectionEstablished {
         device_addr: String,
+        /// Resolved socket address the session was opened to.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        remote_addr: Option<String>,
         #[serde(alias = "prompt")]
         prompt_after: String,
         #[serde(alias = "state")]
@@ -62,6 +77,8 @@ pub enum SessionEvent {
         prompt_before: Option<String>,
         #[serde(default)]
         fsm_prompt_before: Option<String>,
+        #[serde(default)]
+        stats: Option<ConnectionStats>,
     },
     CommandOutput {
         command: String,
@@ -77,6 +94,9 @@ pub enum SessionEvent {
         success: bool,
         #[serde(default)]
         exit_code: Option<i32>,
+        /// Time from sending the command to its prompt, from a monotonic clock.
+        #[serde(default)]
+        duration_ms: Option<u64>,
         content: String,
         all: String,
     },
@@ -97,6 +117,17 @@ pub enum SessionEvent {
         #[serde(default)]
         error: Option<String>,
     },
+    FileDownloadStarted {
+        remote_path: String,
+        local_path: String,
+    },
+    FileDownloadFinished {
+        remote_path: String,
+        local_path: String,
+        success: bool,
+        #[serde(default)]
+        error: Option<String>,
+    },
     /// Transaction block execution started.
     TxBlockStarted {
         block_name: String,
@@ -163,17 +194,181 @@ pub enum SessionEvent {
         rollback_attempted: bool,
         rollback_succeeded: bool,
     },
+    /// Command rejected by the guardrail policy without being sent.
+    CommandBlocked {
+        command: String,
+        mode: String,
+        pattern: String,
+    },
+    /// Output that arrived between commands, parsed before the next command.
+    ResidualOutput {
+        content: String,
+        #[serde(default)]
+        fsm_prompt_after: Option<String>,
+    },
+    /// Command still running after the slow-command threshold elapsed.
+    SlowCommand {
+        command: String,
+        mode: String,
+        elapsed_ms: u64,
+        timeout_ms: u64,
+    },
+    /// Output stopped on a question no input rule answers; `answer` is what
+    /// was sent, `None` when the command failed instead.
+    UnmodeledPrompt {
+        command: String,
+        mode: String,
+        question: String,
+        #[serde(default)]
+        answer: Option<String>,
+    },
+    /// Comment carried by a command list, recorded instead of being sent.
+    Annotation {
+        text: String,
+    },
     RawChunk {
         data: String,
     },
 }
 
+/// Which commands a recorder keeps, for always-on recording with bounded
+/// storage.
+///
+/// A command is its output together with the raw chunks, prompt and state
+/// changes and slow-command notices recorded while it ran. Connection,
+/// transfer and transaction events are always kept. Events of a command
+/// still running are held back until its output is recorded.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum RecordingSampling {
+    /// Keep every command.
+    #[default]
+    All,
+    /// Keep the first command and every `n`th after it.
+    OneInN { n: u32 },
+    /// Keep failed commands with up to `before` commands preceding and
+    /// `after` commands following each failure.
+    FailuresWithContext { before: usize, after: usize },
+}
+
+/// An event waiting for its command's sampling decision, with the times it
+/// arrived at.
+type HeldEvent = (u64, u128, SessionEvent);
+
+/// Sampling state shared by a recorder's clones.
+#[derive(Debug, Default)]
+struct Sampler {
+    commands: u64,
+    /// Events of the command in progress.
+    pending: Vec<HeldEvent>,
+    /// Recently dropped commands, kept as context for the next failure.
+    dropped: VecDeque<Vec<HeldEvent>>,
+    /// Commands still kept after the last failure.
+    keep_after: usize,
+}
+
+impl Sampler {
+    /// Events to store now that `held` arrived.
+    fn admit(&mut self, sampling: RecordingSampling, held: HeldEvent) -> Vec<HeldEvent> {
+        let success = match &held.2 {
+            SessionEvent::CommandOutput { success, .. } => *success,
+            SessionEvent::RawChunk { .. }
+            | SessionEvent::PromptChanged { .. }
+            | SessionEvent::StateChanged { .. }
+            | SessionEvent::SlowCommand { .. }
+            | SessionEvent::UnmodeledPrompt { .. }
+            | SessionEvent::ResidualOutput { .. } => {
+                self.pending.push(held);
+                return Vec::new();
+            }
+            _ => return vec![held],
+        };
+        self.pending.push(held);
+        let command = std::mem::take(&mut self.pending);
+        self.commands += 1;
+        match sampling {
+            RecordingSampling::All => command,
+            RecordingSampling::OneInN { n } => {
+                if (self.commands - 1).is_multiple_of(u64::from(n.max(1))) {
+                    command
+                } else {
+                    Vec::new()
+                }
+            }
+            RecordingSampling::FailuresWithContext { before, after } => {
+                if !success {
+                    self.keep_after = after;
+                    let mut kept: Vec<_> = self.dropped.drain(..).flatten().collect();
+                    kept.extend(command);
+                    kept
+                } else if self.keep_after > 0 {
+                    self.keep_after -= 1;
+                    command
+                } else {
+                    if before > 0 {
+                        if self.dropped.len() == before {
+                            self.dropped.pop_front();
+                        }
+                        self.dropped.push_back(command);
+                    }
+                    Vec::new()
+                }
+            }
+        }
+    }
+}
+
 /// In-memory session recorder.
+///
+/// Clones share the same entries. A connection never owns a recorder handed
+/// to it: it keeps an [`AttachedRecorder`] and records into it only while the
+/// caller still holds a clone, so several callers can record the same pooled
+/// connection side by side and a dropped recorder detaches itself.
 #[derive(Debug, Clone)]
 pub struct SessionRecorder {
     level: SessionRecordLevel,
     entries: Arc<Mutex<Vec<SessionRecordEntry>>>,
     subscribers: broadcast::Sender<SessionRecordEntry>,
+    /// Last assigned sequence number; only advanced under the `entries` lock.
+    last_seq: Arc<AtomicU64>,
+    started: Instant,
+    /// Recorders every event is forwarded to instead of being stored here.
+    targets: Arc<[SessionRecorder]>,
+    sampling: RecordingSampling,
+    sampler: Arc<Mutex<Sampler>>,
+}
+
+/// Non-owning reference a connection keeps to a caller's recorder.
+#[derive(Debug, Clone)]
+pub(crate) struct AttachedRecorder {
+    level: SessionRecordLevel,
+    entries: Weak<Mutex<Vec<SessionRecordEntry>>>,
+    subscribers: broadcast::WeakSender<SessionRecordEntry>,
+    last_seq: Weak<AtomicU64>,
+    started: Instant,
+    targets: Arc<[SessionRecorder]>,
+    sampling: RecordingSampling,
+    sampler: Weak<Mutex<Sampler>>,
+}
+
+impl AttachedRecorder {
+    /// The recorder, or `None` once every caller-held clone was dropped.
+    pub(crate) fn upgrade(&self) -> Option<SessionRecorder> {
+        Some(SessionRecorder {
+            level: self.level,
+            entries: self.entries.upgrade()?,
+            subscribers: self.subscribers.upgrade()?,
+            last_seq: self.last_seq.upgrade()?,
+            started: self.started,
+            targets: self.targets.clone(),
+            sampling: self.sampling,
+            sampler: self.sampler.upgrade()?,
+        })
+    }
+
+    pub(crate) fn is_attached(&self, recorder: &SessionRecorder) -> bool {
+        Weak::ptr_eq(&self.entries, &Arc::downgrade(&recorder.entries))
+    }
 }
 
 impl SessionRecorder {
@@ -184,6 +379,46 @@ impl SessionRecorder {
             level,
             entries: Arc::new(Mutex::new(Vec::new())),
             subscribers,
+            last_seq: Arc::new(AtomicU64::new(0)),
+            started: Instant::now(),
+            targets: Arc::new([]),
+            sampling: RecordingSampling::All,
+            sampler: Arc::new(Mutex::new(Sampler::default())),
+        }
+    }
+
+    /// Keep only the commands `sampling` selects.
+    pub fn with_sampling(mut self, sampling: RecordingSampling) -> Self {
+        self.sampling = sampling;
+        self
+    }
+
+    /// A recorder that forwards every event to each of `targets`, which
+    /// apply their own levels.
+    pub(crate) fn fan_out(targets: Vec<SessionRecorder>) -> Self {
+        let level = if targets.iter().any(|t| t.level == SessionRecordLevel::Full) {
+            SessionRecordLevel::Full
+        } else if targets.iter().any(|t| t.level != SessionRecordLevel::Off) {
+            SessionRecordLevel::KeyEventsOnly
+        } else {
+            SessionRecordLevel::Off
+        };
+        Self {
+            targets: targets.into(),
+            ..Self::new(level)
+        }
+    }
+
+    pub(crate) fn downgrade(&self) -> AttachedRecorder {
+        AttachedRecorder {
+            level: self.level,
+            entries: Arc::downgrade(&self.entries),
+            subscribers: self.subscribers.downgrade(),
+            last_seq: Arc::downgrade(&self.last_seq),
+            started: self.started,
+            targets: self.targets.clone(),
+            sampling: self.sampling,
+            sampler: Arc::downgrade(&self.sampler),
         }
     }
 
@@ -192,6 +427,11 @@ impl SessionRecorder {
         self.level
     }
 
+    /// Current sampling policy.
+    pub fn sampling(&self) -> RecordingSampling {
+        self.sampling
+    }
+
     /// Subscribe to future recorded events in real time.
     ///
     /// The returned receiver only yields events recorded after the subscription
@@ -202,27 +442,63 @@ impl SessionRecorder {
 
     /// Record a key-level event.
     pub fn record_event(&self, event: SessionEvent) -> Result<(), ConnectError> {
+        if !self.targets.is_empty() {
+            return self
+                .targets
+                .iter()
+                .try_for_each(|target| target.record_event(event.clone()));
+        }
         if self.level == SessionRecordLevel::Off {
             return Ok(());
         }
-        let entry = SessionRecordEntry {
-            ts_ms: now_ms(),
-            event,
-        };
+        let mono_ms = u64::try_from(self.started.elapsed().as_millis()).unwrap_or(u64::MAX);
+        if self.sampling == RecordingSampling::All {
+            return self.store(vec![(mono_ms, now_ms(), event)]);
+        }
+        let ready = self
+            .sampler
+            .lock()
+            .map_err(|e| ConnectError::InternalServerError(format!("record lock error: {e}")))?
+            .admit(self.sampling, (mono_ms, now_ms(), event));
+        self.store(ready)
+    }
+
+    fn store(&self, events: Vec<HeldEvent>) -> Result<(), ConnectError> {
+        if events.is_empty() {
+            return Ok(());
+        }
         let mut guard = self
             .entries
             .lock()
             .map_err(|e| ConnectError::InternalServerError(format!("record lock error: {e}")))?;
-        guard.push(entry.clone());
+        let mut stored = Vec::with_capacity(events.len());
+        for (mono_ms, ts_ms, event) in events {
+            let entry = SessionRecordEntry {
+                seq: self.last_seq.fetch_add(1, Ordering::Relaxed) + 1,
+                mono_ms,
+                ts_ms,
+                event,
+            };
+            guard.push(entry.clone());
+            stored.push(entry);
+        }
         drop(guard);
 
         // Best-effort fan-out: if nobody is listening, keep snapshot recording only.
-        let _ = self.subscribers.send(entry);
+        for entry in stored {
+            let _ = self.subscribers.send(entry);
+        }
         Ok(())
     }
 
     /// Record raw shell data chunk when enabled.
     pub fn record_raw_chunk(&self, data: String) -> Result<(), ConnectError> {
+        if !self.targets.is_empty() {
+            return self
+                .targets
+                .iter()
+                .try_for_each(|target| target.record_raw_chunk(data.clone()));
+        }
         if self.level != SessionRecordLevel::Full {
             return Ok(());
         }
@@ -261,6 +537,12 @@ impl SessionRecorder {
         Ok(lines.join("\n"))
     }
 
+    /// Export records as a standalone HTML page; see
+    /// [`recording_to_html`](crate::report::recording_to_html).
+    pub fn to_html_report(&self) -> Result<String, ConnectError> {
+        Ok(crate::report::recording_to_html(&self.entries()?))
+    }
+
     /// Restore recorder from JSONL lines.
     pub fn from_jsonl(jsonl: &str) -> Result<Self, ConnectError> {
         let recorder = Self::new(SessionRecordLevel::Full);
@@ -283,6 +565,8 @@ impl SessionRecorder {
             .entries
             .lock()
             .map_err(|e| ConnectError::InternalServerError(format!("record lock error: {e}")))?;
+        let last_seq = parsed.iter().map(|entry| entry.seq).max().unwrap_or(0);
+        recorder.last_seq.store(last_seq, Ordering::Relaxed);
         *guard = parsed;
         drop(guard);
 
@@ -291,8 +575,9 @@ impl SessionRecorder {
 
     /// Normalize JSONL recording content into a stable fixture representation.
     ///
-    /// This helper sorts events by timestamp and can filter out noisy events
-    /// such as raw shell chunks.
+    /// This helper sorts events by sequence number (falling back to the
+    /// wall-clock timestamp for older recordings) and can filter out noisy
+    /// events such as raw shell chunks.
     pub fn normalize_jsonl(jsonl: &str, options: NormalizeOptions) -> Result<String, ConnectError> {
         let recorder = Self::from_jsonl(jsonl)?;
         let mut indexed = recorder
@@ -301,8 +586,12 @@ impl SessionRecorder {
             .enumerate()
             .collect::<Vec<(usize, SessionRecordEntry)>>();
 
-        indexed
-            .sort_by(|(idx_a, a), (idx_b, b)| a.ts_ms.cmp(&b.ts_ms).then_with(|| idx_a.cmp(idx_b)));
+        indexed.sort_by(|(idx_a, a), (idx_b, b)| {
+            a.seq
+                .cmp(&b.seq)
+                .then_with(|| a.ts_ms.cmp(&b.ts_ms))
+                .then_with(|| idx_a.cmp(idx_b))
+        });
 
         let filtered = indexed
             .into_iter()
@@ -338,6 +627,34 @@ pub struct SessionReplayer {
     cursor: usize,
 }
 
+/// A recorded state that a handler does not reproduce.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct ReplayDivergence {
+    /// Sequence number of the diverging entry.
+    pub seq: u64,
+    /// Command whose output ended here; `None` for the login prompt.
+    pub command: Option<String>,
+    /// `fsm_prompt_after` as recorded.
+    pub recorded: String,
+    /// State the handler reached on the same output.
+    pub recomputed: String,
+}
+
+/// Result of [`SessionReplayer::verify_with_handler`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct ReplayConformance {
+    /// Recorded states compared.
+    pub checked: usize,
+    pub divergences: Vec<ReplayDivergence>,
+}
+
+impl ReplayConformance {
+    /// True when every recorded state was reproduced.
+    pub fn passed(&self) -> bool {
+        self.divergences.is_empty()
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ReplayContext {
     pub device_addr: String,
@@ -365,6 +682,7 @@ impl SessionReplayer {
                 device_addr,
                 prompt_after,
                 fsm_prompt_after,
+                ..
             } = &entry.event
             {
                 return Some(ReplayContext {
@@ -400,6 +718,67 @@ impl SessionReplayer {
         Ok(outputs)
     }
 
+    /// Re-read the recorded output with a fresh copy of `handler` and
+    /// compare the states it reaches with the recorded `fsm_prompt_after`
+    /// values.
+    ///
+    /// Raw chunks are fed when the recording has them (`Full` level);
+    /// otherwise each command's recorded output is. Run it against
+    /// historical sessions to catch template regressions.
+    pub fn verify_with_handler(&self, handler: &DeviceHandler) -> ReplayConformance {
+        let mut handler = handler.fresh();
+        let mut lines = lines::LineSplitter::for_handler(&handler);
+        let raw = self
+            .entries
+            .iter()
+            .any(|entry| matches!(entry.event, SessionEvent::RawChunk { .. }));
+        let mut report = ReplayConformance {
+            checked: 0,
+            divergences: Vec::new(),
+        };
+
+        for entry in &self.entries {
+            let (command, recorded) = match &entry.event {
+                SessionEvent::RawChunk { data } if raw => {
+                    replay_read(&mut handler, &mut lines, data);
+                    continue;
+                }
+                SessionEvent::ConnectionEstablished {
+                    prompt_after,
+                    fsm_prompt_after,
+                    ..
+                } => {
+                    replay_read(&mut handler, &mut lines, prompt_after);
+                    (None, fsm_prompt_after)
+                }
+                SessionEvent::CommandOutput {
+                    command,
+                    all,
+                    fsm_prompt_after: Some(fsm_prompt_after),
+                    ..
+                } => {
+                    if !raw {
+                        replay_read(&mut handler, &mut lines, all);
+                    }
+                    (Some(command.clone()), fsm_prompt_after)
+                }
+                _ => continue,
+            };
+            // Every command starts reading on a new line.
+            lines = lines::LineSplitter::for_handler(&handler);
+            report.checked += 1;
+            if !handler.current_state().eq_ignore_ascii_case(recorded) {
+                report.divergences.push(ReplayDivergence {
+                    seq: entry.seq,
+                    command,
+                    recorded: recorded.clone(),
+                    recomputed: handler.current_state().to_string(),
+                });
+            }
+        }
+        report
+    }
+
     fn replay_next_internal(
         &mut self,
         command: &str,
@@ -431,8 +810,12 @@ impl SessionReplayer {
                     success: *success,
                     exit_code: *exit_code,
                     content: content.clone(),
-                    all: all.clone(),
+                    segments: vec![all.clone()],
                     prompt: prompt_after.clone(),
+                    spilled: None,
+                    device_addr: None,
+                    sys: None,
+                    async_messages: Vec::new(),
                 });
             }
         }
@@ -446,6 +829,19 @@ impl SessionReplayer {
     }
 }
 
+/// Feed `data` to `handler` the way a running session reads shell output.
+fn replay_read(handler: &mut DeviceHandler, lines: &mut lines::LineSplitter, data: &str) {
+    lines.push(data.as_bytes());
+    while let Some(line) = lines.next_line() {
+        let line = lines::decode_line(&line);
+        handler.read(IGNORE_START_LINE.replace(&line, "").trim_end());
+    }
+    let pending = lines.pending();
+    if !pending.is_empty() && handler.read_prompt(&pending) {
+        handler.read(&pending);
+    }
+}
+
 fn now_ms() -> u128 {
     SystemTime::now()
         .duration_since(UNIX_EPOCH)
@@ -498,6 +894,7 @@ mod tests {
                 fsm_prompt_after: Some("enable".to_string()),
                 success: true,
                 exit_code: None,
+                duration_ms: None,
                 content: "ok".to_string(),
                 all: "show version\nok\nrouter#".to_string(),
             })
@@ -516,6 +913,7 @@ mod tests {
         recorder
             .record_event(SessionEvent::ConnectionEstablished {
                 device_addr: "admin@192.168.1.1:22".to_string(),
+                remote_addr: Some("192.168.1.1:22".to_string()),
                 prompt_after: "router#".to_string(),
                 fsm_prompt_after: "enable".to_string(),
             })
@@ -542,6 +940,7 @@ mod tests {
                 fsm_prompt_after: Some("enable".to_string()),
                 success: true,
                 exit_code: None,
+                duration_ms: None,
                 content: "".to_string(),
                 all: "terminal length 0\nrouter#".to_string(),
             })
@@ -556,6 +955,7 @@ mod tests {
                 fsm_prompt_after: Some("enable".to_string()),
                 success: true,
                 exit_code: None,
+                duration_ms: None,
                 content: "Version 1.0".to_string(),
                 all: "show version\nVersion 1.0\nrouter#".to_string(),
             })
@@ -563,18 +963,8 @@ mod tests {
 
         let mut replayer = SessionReplayer::from_recorder(&recorder);
         let script = vec![
-            Command {
-                mode: "enable".to_string(),
-                command: "terminal length 0".to_string(),
-                timeout: None,
-                ..Command::default()
-            },
-            Command {
-                mode: "enable".to_string(),
-                command: "show version".to_string(),
-                timeout: None,
-                ..Command::default()
-            },
+            Command::new("enable", "terminal length 0"),
+            Command::new("enable", "show version"),
         ];
         let outputs = replayer.replay_script(&script).expect("replay script");
         assert_eq!(outputs.len(), 2);
@@ -594,6 +984,7 @@ mod tests {
                 fsm_prompt_after: Some("config".to_string()),
                 success: true,
                 exit_code: None,
+                duration_ms: None,
                 content: "ok".to_string(),
                 all: "show version\nok\nrouter#".to_string(),
             })
@@ -738,6 +1129,7 @@ mod tests {
                 fsm_prompt_after: Some("enable".to_string()),
                 success: true,
                 exit_code: None,
+                duration_ms: None,
                 content: "12:00:00".to_string(),
                 all: "show clock\n12:00:00\nrouter#".to_string(),
             })
@@ -813,6 +1205,51 @@ mod tests {
         assert!(entries[0].ts_ms <= entries[1].ts_ms && entries[1].ts_ms <= entries[2].ts_ms);
     }
 
+    #[test]
+    fn sequence_numbers_order_entries_when_wall_clock_steps_back() {
+        let recorder = SessionRecorder::new(SessionRecordLevel::Full);
+        for state in ["enable", "config"] {
+            recorder
+                .record_event(SessionEvent::StateChanged {
+                    state: state.to_string(),
+                })
+                .expect("record state");
+        }
+        let entries = recorder.entries().expect("entries");
+        assert_eq!(entries[0].seq, 1);
+        assert_eq!(entries[1].seq, 2);
+        assert!(entries[0].mono_ms <= entries[1].mono_ms);
+
+        // Simulate an NTP step between the two events.
+        let stepped = r#"{"seq":1,"ts_ms":900,"event":{"kind":"state_changed","state":"enable"}}
+{"seq":2,"ts_ms":100,"event":{"kind":"state_changed","state":"config"}}"#;
+        let normalized = SessionRecorder::normalize_jsonl(stepped, NormalizeOptions::default())
+            .expect("normalize");
+        let restored = SessionRecorder::from_jsonl(&normalized).expect("restore");
+        restored
+            .record_event(SessionEvent::StateChanged {
+                state: "enable".to_string(),
+            })
+            .expect("record state");
+        let states: Vec<_> = restored
+            .entries()
+            .expect("entries")
+            .into_iter()
+            .map(|entry| match entry.event {
+                SessionEvent::StateChanged { state } => (entry.seq, state),
+                other => panic!("unexpected event: {other:?}"),
+            })
+            .collect();
+        assert_eq!(
+            states,
+            [
+                (1, "enable".to_string()),
+                (2, "config".to_string()),
+                (3, "enable".to_string())
+            ]
+        );
+    }
+
     #[test]
     fn normalize_jsonl_can_keep_all_event_types() {
         let options = NormalizeOptions {
@@ -872,6 +1309,7 @@ mod tests {
                 fsm_prompt_after: Some("user".to_string()),
                 success: false,
                 exit_code: Some(2),
+                duration_ms: None,
                 content: "ls: cannot access '/missing': No such file or directory".to_string(),
                 all: "ls /missing\nls: cannot access '/missing': No such file or directory\nuser@host$"
                     .to_string(),
@@ -886,4 +1324,130 @@ mod tests {
         assert!(!output.success);
         assert_eq!(output.exit_code, Some(2));
     }
+
+    #[test]
+    fn verify_with_handler_reports_state_divergences() {
+        use crate::device::{DeviceHandlerConfig, prompt_rule};
+
+        let recording = r#"{"seq":1,"ts_ms":1,"event":{"kind":"connection_established","device_addr":"admin@10.0.0.1:22","prompt_after":"router#","fsm_prompt_after":"enable"}}
+{"seq":2,"ts_ms":2,"event":{"kind":"command_output","command":"configure terminal","mode":"Config","fsm_prompt_after":"config","success":true,"content":"","all":"configure terminal\nrouter(config)#"}}
+{"seq":3,"ts_ms":3,"event":{"kind":"command_output","command":"end","mode":"Enable","fsm_prompt_after":"enable","success":true,"content":"","all":"end\nrouter#"}}
+"#;
+        let replayer = SessionReplayer::from_jsonl(recording).expect("parse");
+        let handler = |config: &str| {
+            DeviceHandlerConfig {
+                prompt: vec![
+                    prompt_rule("Enable", &[r"^\w+#\s*$"]),
+                    prompt_rule("Config", &[config]),
+                ],
+                ..DeviceHandlerConfig::default()
+            }
+            .build()
+            .expect("handler")
+        };
+
+        let report = replayer.verify_with_handler(&handler(r"^\S+\(config\)#\s*$"));
+        assert_eq!(report.checked, 3);
+        assert!(report.passed(), "{:?}", report.divergences);
+
+        // A regressed Config prompt that no longer matches.
+        let report = replayer.verify_with_handler(&handler(r"^\S+\(cfg\)#\s*$"));
+        assert_eq!(
+            report.divergences,
+            [ReplayDivergence {
+                seq: 2,
+                command: Some("configure terminal".to_string()),
+                recorded: "config".to_string(),
+                recomputed: "output".to_string(),
+            }]
+        );
+    }
+
+    fn record_command(recorder: &SessionRecorder, command: &str, success: bool) {
+        recorder
+            .record_raw_chunk(format!("{command}\nrouter#"))
+            .expect("record chunk");
+        recorder
+            .record_event(SessionEvent::CommandOutput {
+                command: command.to_string(),
+                mode: "Enable".to_string(),
+                prompt_before: None,
+                prompt_after: None,
+                fsm_prompt_before: None,
+                fsm_prompt_after: None,
+                success,
+                exit_code: None,
+                duration_ms: None,
+                content: String::new(),
+                all: String::new(),
+            })
+            .expect("record output");
+    }
+
+    fn recorded_commands(recorder: &SessionRecorder) -> Vec<String> {
+        recorder
+            .entries()
+            .expect("entries")
+            .into_iter()
+            .filter_map(|entry| match entry.event {
+                SessionEvent::CommandOutput { command, .. } => Some(command),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn sampling_keeps_one_in_n_commands() {
+        let recorder = SessionRecorder::new(SessionRecordLevel::Full)
+            .with_sampling(RecordingSampling::OneInN { n: 3 });
+        for index in 0..7 {
+            record_command(&recorder, &format!("show {index}"), true);
+        }
+        recorder
+            .record_event(SessionEvent::ConnectionClosed {
+                reason: "done".to_string(),
+                prompt_before: None,
+                fsm_prompt_before: None,
+                stats: None,
+            })
+            .expect("record close");
+
+        assert_eq!(recorded_commands(&recorder), ["show 0", "show 3", "show 6"]);
+        let entries = recorder.entries().expect("entries");
+        // Raw chunks of kept commands stay; connection events are never sampled.
+        assert_eq!(entries.len(), 7);
+        assert!(matches!(
+            &entries[0].event,
+            SessionEvent::RawChunk { data } if data.starts_with("show 0")
+        ));
+        assert!(
+            entries
+                .windows(2)
+                .all(|pair| pair[0].seq + 1 == pair[1].seq)
+        );
+    }
+
+    #[test]
+    fn sampling_keeps_failures_with_context() {
+        let recorder = SessionRecorder::new(SessionRecordLevel::KeyEventsOnly).with_sampling(
+            RecordingSampling::FailuresWithContext {
+                before: 2,
+                after: 1,
+            },
+        );
+        for (command, success) in [
+            ("show 0", true),
+            ("show 1", true),
+            ("show 2", true),
+            ("vlan 10", false),
+            ("show 3", true),
+            ("show 4", true),
+        ] {
+            record_command(&recorder, command, success);
+        }
+        assert_eq!(
+            recorded_commands(&recorder),
+            ["show 1", "show 2", "vlan 10", "show 3"]
+        );
+    }
 }
@@ -0,0 +1,80 @@
+//! Sessions the device closes right after login.
+//!
+//! Some devices accept authentication, print a notice such as
+//! `Too many sessions` and close the shell. Output followed by EOF before the
+//! first prompt fails with
+//! [`ConnectError::SessionRejected`](crate::error::ConnectError::SessionRejected)
+//! carrying that notice; with a
+//! [session reject retry](super::ExecutionContext::with_session_reject_retry)
+//! the manager waits and connects again.
+
+/// Words that mark a line as the reason for closing the session.
+const REJECTION_HINTS: &[&str] = &[
+    "too many",
+    "maximum",
+    "limit",
+    "exceed",
+    "denied",
+    "not allowed",
+    "refused",
+    "busy",
+    "try again",
+    "try later",
+    "unavailable",
+];
+
+/// The line of `output` explaining why the device closed the session.
+///
+/// Prefers the last line naming a limit or refusal, then the last non-empty
+/// line.
+pub(crate) fn rejection_reason(output: &str) -> String {
+    let lines: Vec<&str> = output
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect();
+    lines
+        .iter()
+        .rev()
+        .find(|line| {
+            let lower = line.to_ascii_lowercase();
+            REJECTION_HINTS.iter().any(|hint| lower.contains(hint))
+        })
+        .or(lines.last())
+        .map(|line| line.to_string())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ConnectError;
+
+    #[test]
+    fn reason_prefers_the_line_naming_the_limit() {
+        assert_eq!(
+            rejection_reason(
+                "\r\n*** Authorized access only ***\r\n\
+                 % Too many sessions (5 of 5 in use), closing connection\r\n\
+                 Goodbye\r\n"
+            ),
+            "% Too many sessions (5 of 5 in use), closing connection"
+        );
+        assert_eq!(
+            rejection_reason("Welcome\nConnection closed by peer\n\n"),
+            "Connection closed by peer"
+        );
+        assert_eq!(rejection_reason(""), "");
+    }
+
+    #[test]
+    fn rejected_sessions_are_retryable_and_drop_the_connection() {
+        let err = ConnectError::SessionRejected("Too many sessions".to_string());
+        assert!(err.is_retryable());
+        assert!(err.is_fatal_for_connection());
+        assert_eq!(
+            err.to_string(),
+            "session rejected by device: Too many sessions"
+        );
+    }
+}
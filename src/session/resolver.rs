@@ -0,0 +1,129 @@
+//! Pluggable host name resolution for connection establishment.
+//!
+//! A [`HostResolver`] installed on the manager is asked for the addresses of
+//! every host name before system DNS, so lab setups and split-horizon DNS
+//! can point `router1` at the right management address. Names it returns
+//! nothing for fall through to system DNS.
+
+use std::future::Future;
+use std::net::IpAddr;
+use std::pin::Pin;
+
+use super::*;
+
+/// Boxed future returned by [`HostResolver::resolve`].
+pub type ResolveFuture<'a> =
+    Pin<Box<dyn Future<Output = Result<Vec<IpAddr>, ConnectError>> + Send + 'a>>;
+
+/// Source of device addresses consulted before system DNS.
+///
+/// IP literals in a request are used as they are and never passed here.
+pub trait HostResolver: Send + Sync {
+    /// Addresses of `host`, in the order to try them; empty to defer to
+    /// system DNS.
+    fn resolve<'a>(&'a self, host: &'a str) -> ResolveFuture<'a>;
+}
+
+/// Resolver answering from a fixed host-to-address map.
+///
+/// Names are matched case-insensitively.
+#[derive(Debug, Clone, Default)]
+pub struct StaticHostResolver {
+    hosts: HashMap<String, Vec<IpAddr>>,
+}
+
+impl StaticHostResolver {
+    /// Build an empty map.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Map `host` to `addr` as well; repeat for dual-stack hosts.
+    pub fn with_host(mut self, host: impl Into<String>, addr: IpAddr) -> Self {
+        self.hosts
+            .entry(host.into().to_ascii_lowercase())
+            .or_default()
+            .push(addr);
+        self
+    }
+
+    /// Addresses mapped to `host`, if any.
+    pub fn get(&self, host: &str) -> &[IpAddr] {
+        self.hosts
+            .get(&host.to_ascii_lowercase())
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+}
+
+impl HostResolver for StaticHostResolver {
+    fn resolve<'a>(&'a self, host: &'a str) -> ResolveFuture<'a> {
+        Box::pin(async move { Ok(self.get(host).to_vec()) })
+    }
+}
+
+impl SshConnectionManager {
+    /// Install a resolver consulted before system DNS whenever a connection
+    /// is (re)established.
+    ///
+    /// Cache keys and credential lookups keep using the requested name.
+    pub fn set_host_resolver(&self, resolver: Arc<dyn HostResolver>) {
+        *self
+            .host_resolver
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(resolver);
+    }
+
+    /// Remove the installed resolver and fall back to system DNS.
+    pub fn clear_host_resolver(&self) {
+        *self
+            .host_resolver
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = None;
+    }
+
+    /// Returns the currently installed host resolver, if any.
+    pub fn host_resolver(&self) -> Option<Arc<dyn HostResolver>> {
+        self.host_resolver
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn static_resolver_maps_names_before_dns() {
+        let resolver = StaticHostResolver::new()
+            .with_host("Router1", "10.10.0.1".parse().expect("v4"))
+            .with_host("router1", "2001:db8::1".parse().expect("v6"));
+        let manager = SshConnectionManager::new();
+        manager.set_host_resolver(Arc::new(resolver));
+        let resolver = manager.host_resolver().expect("resolver");
+
+        let resolved = address::ResolvedAddrs::lookup(
+            "ROUTER1",
+            22,
+            AddressFamily::PreferV6,
+            Some(resolver.as_ref()),
+        )
+        .await
+        .expect("resolved");
+        assert_eq!(
+            async_ssh2_tokio::ToSocketAddrsWithHostname::to_socket_addrs(&resolved).expect("addrs"),
+            [
+                "[2001:db8::1]:22"
+                    .parse::<std::net::SocketAddr>()
+                    .expect("v6"),
+                "10.10.0.1:22".parse().expect("v4"),
+            ]
+        );
+        assert!(resolver.resolve("switch9").await.expect("empty").is_empty());
+
+        manager.clear_host_resolver();
+        assert!(manager.host_resolver().is_none());
+    }
+}
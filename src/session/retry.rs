@@ -0,0 +1,30 @@
+//! Wait-and-retry policies.
+//!
+//! One [`RetryPolicy`] shape serves every retry the context can ask for:
+//! commands hitting a configuration lock
+//! ([`ExecutionContext::with_config_lock_retry`]) and sessions the device
+//! rejects after login ([`ExecutionContext::with_session_reject_retry`]).
+
+use super::*;
+
+/// How often to retry and how long to wait in between.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct RetryPolicy {
+    /// Retries after the first attempt.
+    pub attempts: u32,
+    /// Wait before each retry.
+    pub interval_secs: u64,
+}
+
+impl RetryPolicy {
+    pub fn new(attempts: u32, interval_secs: u64) -> Self {
+        Self {
+            attempts,
+            interval_secs,
+        }
+    }
+
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs(self.interval_secs)
+    }
+}
@@ -9,6 +9,8 @@ pub enum SecurityLevel {
     Balanced,
     /// Maximum compatibility with legacy devices.
     LegacyCompatible,
+    /// FIPS-approved algorithms only (no ChaCha20/Curve25519, SHA-2 MACs).
+    Fips,
 }
 
 /// Connection security options for SSH establishment.
@@ -51,6 +53,14 @@ impl ConnectionSecurityOptions {
         }
     }
 
+    /// FIPS-restricted profile for regulated environments.
+    pub fn fips() -> Self {
+        Self {
+            level: SecurityLevel::Fips,
+            server_check: ServerCheckMethod::DefaultKnownHostsFile,
+        }
+    }
+
     pub(super) fn preferred(&self) -> Preferred {
         match self.level {
             SecurityLevel::Secure => Preferred {
@@ -74,6 +84,13 @@ impl ConnectionSecurityOptions {
                 mac: Cow::Borrowed(config::LEGACY_MAC_ALGORITHMS),
                 compression: Cow::Borrowed(config::DEFAULT_COMPRESSION_ALGORITHMS),
             },
+            SecurityLevel::Fips => Preferred {
+                kex: Cow::Borrowed(config::FIPS_KEX_ORDER),
+                key: Cow::Borrowed(config::FIPS_KEY_TYPES),
+                cipher: Cow::Borrowed(config::FIPS_CIPHERS),
+                mac: Cow::Borrowed(config::FIPS_MAC_ALGORITHMS),
+                compression: Cow::Borrowed(config::DEFAULT_COMPRESSION_ALGORITHMS),
+            },
         }
     }
 }
@@ -82,6 +99,7 @@ impl ConnectionSecurityOptions {
 mod tests {
     use super::{ConnectionSecurityOptions, SecurityLevel};
     use async_ssh2_tokio::ServerCheckMethod;
+    use russh::keys::Algorithm;
     use russh::{cipher, kex, mac};
 
     #[test]
@@ -111,6 +129,25 @@ mod tests {
         assert!(preferred.mac.iter().all(|alg| *alg != mac::NONE));
     }
 
+    #[test]
+    fn fips_profile_excludes_unapproved_algorithms() {
+        let options = ConnectionSecurityOptions::fips();
+        assert_eq!(options.level, SecurityLevel::Fips);
+        let preferred = options.preferred();
+
+        assert!(!preferred.kex.contains(&kex::CURVE25519));
+        assert!(!preferred.kex.contains(&kex::CURVE25519_PRE_RFC_8731));
+        assert!(!preferred.kex.contains(&kex::DH_G14_SHA1));
+        assert!(!preferred.cipher.contains(&cipher::CHACHA20_POLY1305));
+        assert!(!preferred.key.contains(&Algorithm::Ed25519));
+        assert!(
+            preferred
+                .mac
+                .iter()
+                .all(|alg| alg.as_ref().starts_with("hmac-sha2-"))
+        );
+    }
+
     #[test]
     fn legacy_profile_keeps_broad_compatibility_algorithms() {
         let preferred = ConnectionSecurityOptions::legacy_compatible().preferred();
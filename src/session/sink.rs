@@ -0,0 +1,210 @@
+//! Streaming destinations for fan-out results.
+//!
+//! [`SshConnectionManager::run_on_many_into`] and
+//! [`SshConnectionManager::run_workflow_on_many_into`] hand each device's
+//! outcome to an [`OutcomeSink`] as soon as it finishes instead of
+//! collecting every result first, so large audits keep only a small window
+//! of results in memory. [`JsonLinesSink`] writes one [`OutcomeRecord`] per
+//! line:
+//!
+//! ```json
+//! {"index":3,"device_addr":"admin@192.0.2.4:22","elapsed_ms":812,"result":{...},"error":null}
+//! ```
+
+use std::fs::File;
+use std::future::Future;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::pin::Pin;
+
+use super::*;
+
+/// Completion of [`OutcomeSink::finish`].
+pub type SinkFuture<'a> = Pin<Box<dyn Future<Output = Result<(), ConnectError>> + Send + 'a>>;
+
+/// Receives fan-out outcomes in completion order.
+pub trait OutcomeSink<T>: Send {
+    /// Store the outcome of the device at `index` in the request list.
+    ///
+    /// An error stops the fan-out from handing over further outcomes.
+    fn write(&mut self, index: usize, outcome: &DeviceOutcome<T>) -> Result<(), ConnectError>;
+
+    /// Flush or upload what was written; called once after the last outcome.
+    fn finish(&mut self) -> SinkFuture<'_> {
+        Box::pin(async { Ok(()) })
+    }
+}
+
+/// Serialized form of one [`DeviceOutcome`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OutcomeRecord<T> {
+    /// Position of the device in the request list.
+    pub index: usize,
+    pub device_addr: String,
+    pub elapsed_ms: u64,
+    pub result: Option<T>,
+    pub error: Option<String>,
+}
+
+impl<'a, T> OutcomeRecord<&'a T> {
+    /// Record borrowing the result of `outcome`.
+    pub fn new(index: usize, outcome: &'a DeviceOutcome<T>) -> Self {
+        let (result, error) = match &outcome.result {
+            Ok(result) => (Some(result), None),
+            Err(err) => (None, Some(err.to_string())),
+        };
+        Self {
+            index,
+            device_addr: outcome.device_addr.clone(),
+            elapsed_ms: u64::try_from(outcome.elapsed.as_millis()).unwrap_or(u64::MAX),
+            result,
+            error,
+        }
+    }
+}
+
+/// Counts of a fan-out run written to a sink.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FanoutSummary {
+    pub total: usize,
+    pub succeeded: usize,
+    pub failed: usize,
+}
+
+impl FanoutSummary {
+    /// Count `outcome`; a device that answered still failed unless
+    /// `succeeded` accepts its result, e.g. an [`Output`] with `success`
+    /// unset.
+    pub(crate) fn add<T>(&mut self, outcome: &DeviceOutcome<T>, succeeded: impl Fn(&T) -> bool) {
+        self.total += 1;
+        if outcome.result.as_ref().is_ok_and(succeeded) {
+            self.succeeded += 1;
+        } else {
+            self.failed += 1;
+        }
+    }
+}
+
+pub(crate) fn sink_error(err: impl std::fmt::Display) -> ConnectError {
+    ConnectError::OutputSinkError(err.to_string())
+}
+
+/// Writes each outcome as one JSON line.
+#[derive(Debug)]
+pub struct JsonLinesSink<W> {
+    writer: W,
+}
+
+impl<W: Write + Send> JsonLinesSink<W> {
+    pub fn new(writer: W) -> Self {
+        Self { writer }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl JsonLinesSink<BufWriter<File>> {
+    /// Create or truncate the file at `path`.
+    pub fn create(path: impl AsRef<Path>) -> Result<Self, ConnectError> {
+        let path = path.as_ref();
+        let file = File::create(path)
+            .map_err(|err| sink_error(format!("cannot create {}: {err}", path.display())))?;
+        Ok(Self::new(BufWriter::new(file)))
+    }
+}
+
+impl<T, W> OutcomeSink<T> for JsonLinesSink<W>
+where
+    T: Serialize,
+    W: Write + Send,
+{
+    fn write(&mut self, index: usize, outcome: &DeviceOutcome<T>) -> Result<(), ConnectError> {
+        serde_json::to_writer(&mut self.writer, &OutcomeRecord::new(index, outcome))
+            .map_err(sink_error)?;
+        self.writer.write_all(b"\n").map_err(sink_error)
+    }
+
+    fn finish(&mut self) -> SinkFuture<'_> {
+        let flushed = self.writer.flush().map_err(sink_error);
+        Box::pin(async move { flushed })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn json_lines_sink_writes_one_record_per_outcome() {
+        let mut sink = JsonLinesSink::new(Vec::new());
+        let outcomes = [
+            DeviceOutcome {
+                device_addr: "admin@192.0.2.2:22".to_string(),
+                result: Ok("up".to_string()),
+                elapsed: Duration::from_millis(40),
+            },
+            DeviceOutcome {
+                device_addr: "admin@192.0.2.1:22".to_string(),
+                result: Err(ConnectError::DeviceTimeout(Duration::from_secs(5))),
+                elapsed: Duration::from_secs(5),
+            },
+        ];
+        let mut summary = FanoutSummary::default();
+        for (index, outcome) in [1, 0].into_iter().zip(&outcomes) {
+            sink.write(index, outcome).expect("write");
+            summary.add(outcome, |_| true);
+        }
+        OutcomeSink::<String>::finish(&mut sink)
+            .await
+            .expect("finish");
+
+        let text = String::from_utf8(sink.into_inner()).expect("utf8");
+        let records: Vec<OutcomeRecord<String>> = text
+            .lines()
+            .map(|line| serde_json::from_str(line).expect("record"))
+            .collect();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].index, 1);
+        assert_eq!(records[0].result.as_deref(), Some("up"));
+        assert_eq!(records[1].elapsed_ms, 5000);
+        assert_eq!(
+            records[1].error.as_deref(),
+            Some("device did not finish within 5s")
+        );
+        assert_eq!(
+            summary,
+            FanoutSummary {
+                total: 2,
+                succeeded: 1,
+                failed: 1
+            }
+        );
+    }
+
+    #[test]
+    fn summary_counts_rejected_commands_as_failed() {
+        let outcome = DeviceOutcome {
+            device_addr: "admin@192.0.2.1:22".to_string(),
+            result: Ok(Output {
+                success: false,
+                exit_code: None,
+                content: "% Invalid input detected".to_string(),
+                segments: Vec::new(),
+                prompt: None,
+                spilled: None,
+                device_addr: None,
+                sys: None,
+                async_messages: Vec::new(),
+            }),
+            elapsed: Duration::from_millis(40),
+        };
+        let mut summary = FanoutSummary::default();
+
+        summary.add(&outcome, |output| output.success);
+
+        assert_eq!(summary.succeeded, 0);
+        assert_eq!(summary.failed, 1);
+    }
+}
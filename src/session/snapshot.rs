@@ -0,0 +1,167 @@
+//! Before/after snapshots around a change.
+//!
+//! [`SshConnectionManager::with_snapshot`] runs a set of show commands,
+//! awaits the caller's change, runs the same commands again and reports
+//! which lines of each output appeared or disappeared. Outputs are
+//! normalized with the template's
+//! [`volatile_regex`](crate::device::DeviceHandlerConfig::volatile_regex)
+//! first, so uptime or counters ticking over are not reported as changes.
+
+use std::collections::HashMap;
+use std::future::Future;
+
+use super::*;
+
+/// How the output of one snapshot command changed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct SnapshotDiff {
+    pub mode: String,
+    pub command: String,
+    pub before: String,
+    pub after: String,
+    /// Lines only in `before`, in their original order.
+    pub removed: Vec<String>,
+    /// Lines only in `after`, in their original order.
+    pub added: Vec<String>,
+}
+
+impl SnapshotDiff {
+    /// Compare two outputs of `command` line by line.
+    ///
+    /// Lines are matched regardless of position, so reordered output is not
+    /// a change; a line repeated more often after than before is.
+    pub fn new(command: &Command, before: String, after: String) -> Self {
+        let mut remaining: HashMap<&str, usize> = HashMap::new();
+        for line in after.lines() {
+            *remaining.entry(line.trim_end()).or_default() += 1;
+        }
+        let mut removed = Vec::new();
+        for line in before.lines().map(str::trim_end) {
+            match remaining.get_mut(line) {
+                Some(count) if *count > 0 => *count -= 1,
+                _ => removed.push(line.to_string()),
+            }
+        }
+        let mut added = Vec::new();
+        for line in after.lines().map(str::trim_end) {
+            if let Some(count) = remaining.get_mut(line).filter(|count| **count > 0) {
+                *count -= 1;
+                added.push(line.to_string());
+            }
+        }
+        Self {
+            mode: command.mode.clone(),
+            command: command.command.clone(),
+            before,
+            after,
+            removed,
+            added,
+        }
+    }
+
+    /// True when both outputs hold the same lines.
+    pub fn is_empty(&self) -> bool {
+        self.removed.is_empty() && self.added.is_empty()
+    }
+}
+
+/// Result of [`SshConnectionManager::with_snapshot`].
+#[derive(Debug, Clone)]
+pub struct Snapshotted<T> {
+    /// What the change returned.
+    pub value: T,
+    /// One entry per snapshot command, in the order given.
+    pub diffs: Vec<SnapshotDiff>,
+}
+
+impl<T> Snapshotted<T> {
+    /// True when no snapshot command's output changed.
+    pub fn unchanged(&self) -> bool {
+        self.diffs.iter().all(SnapshotDiff::is_empty)
+    }
+}
+
+impl SshConnectionManager {
+    /// Run `commands` before and after `change` and diff their outputs.
+    ///
+    /// `make_request` is called for every snapshot command, since a request
+    /// is consumed by each call. A failing snapshot before the change fails
+    /// the call without running `change`; one after it is returned as the
+    /// error although the change already ran. Both outputs are passed
+    /// through [`DeviceHandler::normalize_output`] of the request's handler.
+    pub async fn with_snapshot<T, Fut>(
+        &self,
+        make_request: impl Fn() -> Result<ConnectionRequest, ConnectError>,
+        commands: &[Command],
+        context: ExecutionContext,
+        change: impl FnOnce() -> Fut,
+    ) -> Result<Snapshotted<T>, ConnectError>
+    where
+        Fut: Future<Output = T>,
+    {
+        let mut before = Vec::with_capacity(commands.len());
+        for command in commands {
+            before.push(
+                self.normalized_snapshot(make_request()?, command, &context)
+                    .await?,
+            );
+        }
+        let value = change().await;
+        let mut diffs = Vec::with_capacity(commands.len());
+        for (command, before) in commands.iter().zip(before) {
+            let after = self
+                .normalized_snapshot(make_request()?, command, &context)
+                .await?;
+            diffs.push(SnapshotDiff::new(command, before, after));
+        }
+        Ok(Snapshotted { value, diffs })
+    }
+
+    async fn normalized_snapshot(
+        &self,
+        request: ConnectionRequest,
+        command: &Command,
+        context: &ExecutionContext,
+    ) -> Result<String, ConnectError> {
+        let normalize = request.handler.fresh();
+        let output = self
+            .execute_command_with_context(request, command.clone(), context.clone())
+            .await?;
+        Ok(normalize.normalize_output(&output.content))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diff_reports_lines_that_appeared_or_disappeared() {
+        let command = Command::new("Enable", "show ip interface brief");
+        let diff = SnapshotDiff::new(
+            &command,
+            "Gi0/1  10.0.0.1  up  up\nGi0/2  unassigned  up  up\nGi0/3  unassigned  up  up\n"
+                .to_string(),
+            "Gi0/3  unassigned  up  up\nGi0/1  10.0.0.1  up  up\n\
+             Gi0/2  unassigned  administratively down  down\n"
+                .to_string(),
+        );
+        assert_eq!(diff.removed, ["Gi0/2  unassigned  up  up"]);
+        assert_eq!(
+            diff.added,
+            ["Gi0/2  unassigned  administratively down  down"]
+        );
+
+        let same = SnapshotDiff::new(&command, "a\nb\n".to_string(), "b\na\n".to_string());
+        assert!(same.is_empty());
+        let repeated = SnapshotDiff::new(&command, "a\n".to_string(), "a\na\n".to_string());
+        assert_eq!(repeated.added, ["a"]);
+        assert!(
+            Snapshotted {
+                value: (),
+                diffs: vec![same],
+            }
+            .unchanged()
+        );
+    }
+}
@@ -0,0 +1,178 @@
+//! Spill-to-disk for very large command output.
+//!
+//! Collection commands such as `show tech-support` can produce many megabytes.
+//! When a command sets [`Command::spill_threshold`], captured output beyond the
+//! threshold is streamed into a temporary file instead of being kept in memory.
+
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use super::*;
+
+static TEMP_FILE_SEQUENCE: AtomicU64 = AtomicU64::new(0);
+
+/// Command transcript that was written to disk instead of memory.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct SpilledOutput {
+    /// Temporary file holding the full transcript; the caller owns and removes it.
+    pub path: PathBuf,
+    /// Number of bytes written to `path`.
+    pub bytes: u64,
+}
+
+impl SpilledOutput {
+    /// Read the spilled transcript back into memory.
+    pub fn read_to_string(&self) -> Result<String, ConnectError> {
+        std::fs::read_to_string(&self.path).map_err(ConnectError::OutputSpillError)
+    }
+}
+
+/// Incremental writer moving captured output to a temp file once it grows too large.
+#[derive(Debug)]
+pub(crate) struct OutputSpill {
+    threshold: usize,
+    file: Option<(PathBuf, File)>,
+    bytes: u64,
+}
+
+impl OutputSpill {
+    pub(crate) fn new(threshold: usize) -> Self {
+        Self {
+            threshold,
+            file: None,
+            bytes: 0,
+        }
+    }
+
+    /// Move all but the last captured line to disk once `buffer` exceeds the threshold.
+    ///
+    /// The last line stays in memory so trailing markers can still be parsed
+    /// when the command finishes.
+    pub(crate) fn relieve(&mut self, buffer: &mut String) -> Result<(), ConnectError> {
+        if buffer.len() <= self.threshold {
+            return Ok(());
+        }
+        let body = buffer.strip_suffix('\n').unwrap_or(buffer);
+        let Some(split) = body.rfind('\n') else {
+            return Ok(());
+        };
+        self.write(&buffer[..=split])?;
+        buffer.drain(..=split);
+        Ok(())
+    }
+
+    /// Returns true once any output has been written to disk.
+    pub(crate) fn is_spilled(&self) -> bool {
+        self.file.is_some()
+    }
+
+    /// Write the remaining output and close the file.
+    pub(crate) fn finish(mut self, tail: &str) -> Result<Option<SpilledOutput>, ConnectError> {
+        if self.file.is_none() {
+            return Ok(None);
+        }
+        self.write(tail)?;
+        let (path, mut file) = self.file.take().expect("spill file exists");
+        file.flush().map_err(ConnectError::OutputSpillError)?;
+        Ok(Some(SpilledOutput {
+            path,
+            bytes: self.bytes,
+        }))
+    }
+
+    fn write(&mut self, data: &str) -> Result<(), ConnectError> {
+        if self.file.is_none() {
+            let (path, file) = create_spill_file()?;
+            debug!("Spilling command output to {}", path.display());
+            self.file = Some((path, file));
+        }
+        let (_, file) = self.file.as_mut().expect("spill file exists");
+        file.write_all(data.as_bytes())
+            .map_err(ConnectError::OutputSpillError)?;
+        self.bytes += data.len() as u64;
+        Ok(())
+    }
+}
+
+/// Open a new temp file for spilled output.
+fn create_spill_file() -> Result<(PathBuf, File), ConnectError> {
+    create_temp_file("rneter-output", "log").map_err(ConnectError::OutputSpillError)
+}
+
+/// Create `{prefix}-{pid}-{n}.{extension}` in the system temp directory,
+/// readable only by the current user.
+///
+/// Never opens an existing file, so a file planted under the predictable
+/// name by another user is skipped instead of followed or clobbered.
+pub(crate) fn create_temp_file(prefix: &str, extension: &str) -> std::io::Result<(PathBuf, File)> {
+    loop {
+        let path = std::env::temp_dir().join(format!(
+            "{prefix}-{}-{}.{extension}",
+            std::process::id(),
+            TEMP_FILE_SEQUENCE.fetch_add(1, Ordering::Relaxed)
+        ));
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        match options.open(&path) {
+            Ok(file) => return Ok((path, file)),
+            Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => continue,
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn small_output_stays_in_memory() {
+        let mut spill = OutputSpill::new(1024);
+        let mut buffer = "show clock\n12:00\n".to_string();
+
+        spill.relieve(&mut buffer).expect("relieve");
+
+        assert!(!spill.is_spilled());
+        assert_eq!(buffer, "show clock\n12:00\n");
+        assert!(spill.finish(&buffer).expect("finish").is_none());
+    }
+
+    #[test]
+    fn large_output_moves_to_temp_file_keeping_last_line() {
+        let mut spill = OutputSpill::new(16);
+        let mut buffer = "show tech\nline one\nline two\n".to_string();
+
+        spill.relieve(&mut buffer).expect("relieve");
+        assert!(spill.is_spilled());
+        assert_eq!(buffer, "line two\n");
+
+        buffer.push_str("router#");
+        let spilled = spill.finish(&buffer).expect("finish").expect("spilled");
+
+        let content = spilled.read_to_string().expect("read");
+        assert_eq!(content, "show tech\nline one\nline two\nrouter#");
+        assert_eq!(spilled.bytes, content.len() as u64);
+        std::fs::remove_file(&spilled.path).expect("cleanup");
+    }
+
+    #[test]
+    fn planted_spill_file_is_skipped_not_clobbered() {
+        let planted = std::env::temp_dir().join(format!(
+            "rneter-output-{}-{}.log",
+            std::process::id(),
+            TEMP_FILE_SEQUENCE.load(Ordering::Relaxed)
+        ));
+        std::fs::write(&planted, "planted").expect("plant");
+
+        let (path, _file) = create_spill_file().expect("spill file");
+
+        assert_ne!(path, planted);
+        assert_eq!(std::fs::read_to_string(&planted).expect("read"), "planted");
+        std::fs::remove_file(&planted).expect("cleanup");
+        std::fs::remove_file(&path).expect("cleanup");
+    }
+}
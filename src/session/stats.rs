@@ -0,0 +1,133 @@
+//! Per-connection traffic and command counters.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::*;
+
+/// Point-in-time statistics for one connection.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct ConnectionStats {
+    /// Bytes written to the remote shell.
+    pub bytes_sent: u64,
+    /// Bytes read from the remote shell.
+    pub bytes_received: u64,
+    /// Commands executed through the connection, including failed ones.
+    pub commands_executed: u64,
+    /// Commands that returned an error or an unsuccessful output.
+    pub commands_failed: u64,
+    /// Sum of command latencies in milliseconds.
+    pub total_latency_ms: u64,
+    /// Unix timestamp in milliseconds of the last shell traffic, if any.
+    #[serde(default)]
+    pub last_activity_ms: Option<u64>,
+}
+
+impl ConnectionStats {
+    /// Mean command latency, or `None` before the first command.
+    pub fn average_latency(&self) -> Option<Duration> {
+        (self.commands_executed > 0)
+            .then(|| Duration::from_millis(self.total_latency_ms / self.commands_executed))
+    }
+}
+
+/// Live view of one pooled connection, returned by [`SshConnectionManager::connections`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConnectionInfo {
+    /// Connection cache key (`user@addr:port`).
+    pub device_addr: String,
+    /// Resolved address the session is connected to.
+    pub remote_addr: Option<std::net::SocketAddr>,
+    /// Whether the SSH transport is still open.
+    pub connected: bool,
+    /// Time since the session was established.
+    pub session_age: Duration,
+    pub stats: ConnectionStats,
+    /// Tags of the requests served by the connection, sorted.
+    pub tags: Vec<String>,
+}
+
+/// Lock-free counters shared between a client and its I/O task.
+#[derive(Debug, Default)]
+pub(crate) struct ConnectionStatsCounters {
+    bytes_sent: AtomicU64,
+    bytes_received: AtomicU64,
+    commands_executed: AtomicU64,
+    commands_failed: AtomicU64,
+    total_latency_ms: AtomicU64,
+    last_activity_ms: AtomicU64,
+}
+
+impl ConnectionStatsCounters {
+    pub(crate) fn record_sent(&self, bytes: usize) {
+        self.bytes_sent.fetch_add(bytes as u64, Ordering::Relaxed);
+        self.touch();
+    }
+
+    pub(crate) fn record_received(&self, bytes: usize) {
+        self.bytes_received
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+        self.touch();
+    }
+
+    pub(crate) fn record_command(&self, elapsed: Duration, success: bool) {
+        self.commands_executed.fetch_add(1, Ordering::Relaxed);
+        if !success {
+            self.commands_failed.fetch_add(1, Ordering::Relaxed);
+        }
+        let elapsed_ms = u64::try_from(elapsed.as_millis()).unwrap_or(u64::MAX);
+        self.total_latency_ms
+            .fetch_add(elapsed_ms, Ordering::Relaxed);
+    }
+
+    pub(crate) fn snapshot(&self) -> ConnectionStats {
+        let last_activity_ms = self.last_activity_ms.load(Ordering::Relaxed);
+        ConnectionStats {
+            bytes_sent: self.bytes_sent.load(Ordering::Relaxed),
+            bytes_received: self.bytes_received.load(Ordering::Relaxed),
+            commands_executed: self.commands_executed.load(Ordering::Relaxed),
+            commands_failed: self.commands_failed.load(Ordering::Relaxed),
+            total_latency_ms: self.total_latency_ms.load(Ordering::Relaxed),
+            last_activity_ms: (last_activity_ms > 0).then_some(last_activity_ms),
+        }
+    }
+
+    fn touch(&self) {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| u64::try_from(d.as_millis()).unwrap_or(u64::MAX))
+            .unwrap_or(0);
+        self.last_activity_ms.store(now, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counters_snapshot_traffic_and_latency() {
+        let counters = ConnectionStatsCounters::default();
+        assert_eq!(counters.snapshot(), ConnectionStats::default());
+        assert_eq!(counters.snapshot().average_latency(), None);
+
+        counters.record_sent(12);
+        counters.record_received(300);
+        counters.record_command(Duration::from_millis(100), true);
+        counters.record_command(Duration::from_millis(300), false);
+
+        let stats = counters.snapshot();
+        assert_eq!(stats.bytes_sent, 12);
+        assert_eq!(stats.bytes_received, 300);
+        assert_eq!(stats.commands_executed, 2);
+        assert_eq!(stats.commands_failed, 1);
+        assert_eq!(stats.average_latency(), Some(Duration::from_millis(200)));
+        assert!(stats.last_activity_ms.is_some());
+    }
+
+    #[test]
+    fn manager_lists_no_connections_when_pool_is_empty() {
+        let manager = SshConnectionManager::new();
+        assert!(manager.connections().is_empty());
+    }
+}
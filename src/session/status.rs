@@ -0,0 +1,462 @@
+//! Connection state readable without taking the client execution lock.
+//!
+//! [`SharedSshClient`] sits behind an async `RwLock` that a running command
+//! holds for its whole duration. Anything a health check or the pool
+//! introspection API needs lives here instead, as atomics or short-lived
+//! synchronous locks, and so do the settings the manager's reuse path
+//! updates. Matching a reused connection's parameters still takes the
+//! client lock, as it compares the device handler.
+
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+use super::*;
+
+/// FSM state and prompt of a session, published by
+/// [`SshConnectionManager::watch_state`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct SessionState {
+    /// Lowercase FSM state name, e.g. `config`.
+    pub state: String,
+    /// Last prompt line matched, e.g. `R1(config)#`.
+    pub prompt: String,
+}
+
+/// One [`ConnectionStatus::attach_recorder`] call, undone by
+/// [`ConnectionStatus::detach_recorder`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct RecorderAttachment(u64);
+
+/// Shared status of one live connection.
+#[derive(Debug)]
+pub(crate) struct ConnectionStatus {
+    device_addr: String,
+    /// Address the SSH connection was made to, once known.
+    remote_addr: std::sync::OnceLock<std::net::SocketAddr>,
+    connected_at: Instant,
+    connected: AtomicBool,
+    stats: stats::ConnectionStatsCounters,
+    close_reason: std::sync::Mutex<Option<String>>,
+    /// Caller-owned recorders; see [`SessionRecorder`] for the ownership model.
+    recorders: std::sync::RwLock<Vec<(RecorderAttachment, recording::AttachedRecorder)>>,
+    next_attachment: AtomicU64,
+    command_policy: std::sync::RwLock<CommandPolicy>,
+    /// The manager's freeze switch.
+    change_freeze: std::sync::RwLock<Option<freeze::FreezeSwitch>>,
+    slow_command_threshold: std::sync::RwLock<Option<Duration>>,
+    command_timeout: std::sync::RwLock<Duration>,
+    state: tokio::sync::watch::Sender<SessionState>,
+    /// Sys values captured from prompts since connecting.
+    seen_sys: std::sync::RwLock<std::collections::BTreeSet<String>>,
+    /// Tags of the requests served by this connection.
+    tags: std::sync::RwLock<std::collections::BTreeSet<String>>,
+    tag_quotas: std::sync::RwLock<Option<quota::TagQuotas>>,
+}
+
+impl ConnectionStatus {
+    pub(crate) fn new(device_addr: String, recorder: Option<SessionRecorder>) -> Self {
+        Self {
+            device_addr,
+            remote_addr: std::sync::OnceLock::new(),
+            connected_at: Instant::now(),
+            connected: AtomicBool::new(true),
+            stats: stats::ConnectionStatsCounters::default(),
+            close_reason: std::sync::Mutex::new(None),
+            recorders: std::sync::RwLock::new(
+                recorder
+                    .iter()
+                    .map(|recorder| (RecorderAttachment(0), recorder.downgrade()))
+                    .collect(),
+            ),
+            next_attachment: AtomicU64::new(1),
+            command_policy: std::sync::RwLock::new(CommandPolicy::default()),
+            change_freeze: std::sync::RwLock::new(None),
+            slow_command_threshold: std::sync::RwLock::new(None),
+            command_timeout: std::sync::RwLock::new(Duration::from_secs(60)),
+            state: tokio::sync::watch::Sender::new(SessionState::default()),
+            seen_sys: std::sync::RwLock::new(std::collections::BTreeSet::new()),
+            tags: std::sync::RwLock::new(std::collections::BTreeSet::new()),
+            tag_quotas: std::sync::RwLock::new(None),
+        }
+    }
+
+    /// Connection cache key (`user@addr:port`).
+    pub(crate) fn device_addr(&self) -> &str {
+        &self.device_addr
+    }
+
+    pub(crate) fn set_remote_addr(&self, addr: std::net::SocketAddr) {
+        let _ = self.remote_addr.set(addr);
+    }
+
+    pub(crate) fn session_age(&self) -> Duration {
+        self.connected_at.elapsed()
+    }
+
+    /// False once the shell I/O task has ended or the connection was closed.
+    pub(crate) fn is_connected(&self) -> bool {
+        self.connected.load(Ordering::Acquire)
+    }
+
+    pub(crate) fn mark_disconnected(&self) {
+        self.connected.store(false, Ordering::Release);
+    }
+
+    pub(crate) fn stats(&self) -> &stats::ConnectionStatsCounters {
+        &self.stats
+    }
+
+    /// Remember why the connection is being closed; the first reason wins.
+    pub(crate) fn set_close_reason(&self, reason: &str) {
+        self.close_reason
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .get_or_insert_with(|| reason.to_string());
+    }
+
+    pub(crate) fn take_close_reason(&self) -> Option<String> {
+        self.close_reason
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .take()
+    }
+
+    /// Every recorder still held by a caller, combined into one; a recorder
+    /// attached more than once records each entry once.
+    pub(crate) fn recorder(&self) -> Option<SessionRecorder> {
+        let mut live: Vec<SessionRecorder> = Vec::new();
+        for (_, attached) in self
+            .recorders
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .iter()
+        {
+            if live.iter().any(|recorder| attached.is_attached(recorder)) {
+                continue;
+            }
+            live.extend(attached.upgrade());
+        }
+        match live.len() {
+            0 => None,
+            1 => live.pop(),
+            _ => Some(SessionRecorder::fan_out(live)),
+        }
+    }
+
+    /// Record into `recorder` as well, for as long as the caller holds it
+    /// or until this attachment is detached.
+    pub(crate) fn attach_recorder(&self, recorder: &SessionRecorder) -> RecorderAttachment {
+        let attachment = RecorderAttachment(self.next_attachment.fetch_add(1, Ordering::Relaxed));
+        let mut recorders = self
+            .recorders
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        recorders.retain(|(_, attached)| attached.upgrade().is_some());
+        recorders.push((attachment, recorder.downgrade()));
+        attachment
+    }
+
+    /// Undo one attachment; other attachments of the same recorder stay.
+    pub(crate) fn detach_recorder(&self, attachment: RecorderAttachment) {
+        self.recorders
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .retain(|(id, attached)| *id != attachment && attached.upgrade().is_some());
+    }
+
+    /// Check `command` against the connection's guardrail policy.
+    pub(crate) fn check_command(&self, command: &str) -> Result<(), ConnectError> {
+        self.command_policy
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .check(command)
+    }
+
+    pub(crate) fn set_command_policy(&self, command_policy: CommandPolicy) {
+        *self
+            .command_policy
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = command_policy;
+    }
+
+    pub(crate) fn set_change_freeze(&self, switch: freeze::FreezeSwitch) {
+        *self
+            .change_freeze
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(switch);
+    }
+
+    /// The active change freeze, if any. Whether a job may override it is
+    /// decided per job, see [`JobScope::freeze_override`].
+    pub(crate) fn change_freeze(&self) -> Option<ChangeFreeze> {
+        self.change_freeze
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .as_ref()?
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+    }
+
+    pub(crate) fn slow_command_threshold(&self) -> Option<Duration> {
+        *self
+            .slow_command_threshold
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub(crate) fn set_slow_command_threshold(&self, threshold: Option<Duration>) {
+        *self
+            .slow_command_threshold
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = threshold;
+    }
+
+    /// Timeout for commands that do not set one.
+    pub(crate) fn command_timeout(&self) -> Duration {
+        *self
+            .command_timeout
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub(crate) fn set_command_timeout(&self, timeout: Duration) {
+        *self
+            .command_timeout
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = timeout;
+    }
+
+    /// Publish the FSM state and prompt; watchers only wake on a change.
+    pub(crate) fn publish_state(&self, state: &str, prompt: &str) {
+        self.state.send_if_modified(|current| {
+            if current.state == state && current.prompt == prompt {
+                return false;
+            }
+            current.state = state.to_string();
+            current.prompt = prompt.to_string();
+            true
+        });
+    }
+
+    pub(crate) fn watch_state(&self) -> tokio::sync::watch::Receiver<SessionState> {
+        self.state.subscribe()
+    }
+
+    /// Remember the sys value captured from the current prompt, if any.
+    pub(crate) fn record_sys(&self, sys: Option<&str>) {
+        let Some(sys) = sys else {
+            return;
+        };
+        if self
+            .seen_sys
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .contains(sys)
+        {
+            return;
+        }
+        self.seen_sys
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .insert(sys.to_string());
+    }
+
+    /// Sys values seen since connecting, sorted.
+    pub(crate) fn seen_sys(&self) -> Vec<String> {
+        self.seen_sys
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .iter()
+            .cloned()
+            .collect()
+    }
+
+    pub(crate) fn add_tags(&self, tags: &[String]) {
+        if tags.is_empty() {
+            return;
+        }
+        self.tags
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .extend(tags.iter().cloned());
+    }
+
+    pub(crate) fn has_tag(&self, tag: &str) -> bool {
+        self.tags
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .contains(tag)
+    }
+
+    pub(crate) fn set_tag_quotas(&self, quotas: quota::TagQuotas) {
+        *self
+            .tag_quotas
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(quotas);
+    }
+
+    /// Wait for a command slot of every tag quota this connection falls under.
+    pub(crate) async fn acquire_command_slots(&self) -> Vec<tokio::sync::OwnedSemaphorePermit> {
+        let quotas = self
+            .tag_quotas
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone();
+        let Some(quotas) = quotas else {
+            return Vec::new();
+        };
+        let tags = self
+            .tags
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone();
+        quota::acquire_command_slots(&quotas, &tags).await
+    }
+
+    /// Snapshot for the pool introspection API.
+    pub(crate) fn info(&self) -> ConnectionInfo {
+        ConnectionInfo {
+            device_addr: self.device_addr.clone(),
+            remote_addr: self.remote_addr.get().copied(),
+            connected: self.is_connected(),
+            session_age: self.session_age(),
+            stats: self.stats.snapshot(),
+            tags: self
+                .tags
+                .read()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .iter()
+                .cloned()
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn status_tracks_connection_and_close_reason() {
+        let status = ConnectionStatus::new("admin@192.0.2.1:22".to_string(), None);
+        assert!(status.is_connected());
+        assert!(status.recorder().is_none());
+
+        status.set_close_reason("connection_params_changed");
+        status.set_close_reason("client_close_called");
+        status.mark_disconnected();
+
+        let info = status.info();
+        assert_eq!(info.device_addr, "admin@192.0.2.1:22");
+        assert!(!info.connected);
+        assert_eq!(
+            status.take_close_reason().as_deref(),
+            Some("connection_params_changed")
+        );
+        assert!(status.take_close_reason().is_none());
+    }
+
+    #[test]
+    fn status_fans_out_to_recorders_while_callers_hold_them() {
+        let first = SessionRecorder::new(SessionRecordLevel::KeyEventsOnly);
+        let status = ConnectionStatus::new("admin@192.0.2.1:22".to_string(), Some(first.clone()));
+
+        let second = SessionRecorder::new(SessionRecordLevel::Full);
+        status.attach_recorder(&second);
+        let job = status.attach_recorder(&second);
+        let combined = status.recorder().expect("recorder");
+        combined
+            .record_raw_chunk("show clock\n".to_string())
+            .expect("raw chunk");
+        combined
+            .record_event(SessionEvent::ResidualOutput {
+                content: "Router#".to_string(),
+                fsm_prompt_after: None,
+            })
+            .expect("event");
+        assert_eq!(first.entries().expect("first").len(), 1);
+        assert_eq!(second.entries().expect("second").len(), 2);
+
+        drop(combined);
+        // Detaching the job's attachment keeps the earlier one.
+        status.detach_recorder(job);
+        status
+            .recorder()
+            .expect("recorder")
+            .record_raw_chunk("show clock\n".to_string())
+            .expect("raw chunk");
+        assert_eq!(second.entries().expect("second").len(), 3);
+
+        drop(second);
+        assert_eq!(
+            status.recorder().map(|r| r.level()),
+            Some(SessionRecordLevel::KeyEventsOnly)
+        );
+        drop(first);
+        assert!(status.recorder().is_none());
+    }
+
+    #[test]
+    fn status_applies_command_policy_without_client_lock() {
+        let status = ConnectionStatus::new("admin@192.0.2.1:22".to_string(), None);
+        assert!(status.check_command("reload").is_ok());
+
+        status.set_command_policy(CommandPolicy::destructive_defaults());
+
+        assert!(matches!(
+            status.check_command("reload"),
+            Err(ConnectError::CommandBlockedByPolicy { .. })
+        ));
+    }
+
+    #[test]
+    fn state_watchers_only_wake_on_changes() {
+        let status = ConnectionStatus::new("admin@192.0.2.1:22".to_string(), None);
+        let mut watcher = status.watch_state();
+        status.publish_state("enable", "R1#");
+        assert!(watcher.has_changed().expect("sender alive"));
+        assert_eq!(
+            *watcher.borrow_and_update(),
+            SessionState {
+                state: "enable".to_string(),
+                prompt: "R1#".to_string(),
+            }
+        );
+
+        status.publish_state("enable", "R1#");
+        assert!(!watcher.has_changed().expect("sender alive"));
+        status.publish_state("config", "R1(config)#");
+        assert_eq!(watcher.borrow_and_update().state, "config");
+    }
+
+    #[test]
+    fn seen_sys_values_are_kept_once_and_sorted() {
+        let status = ConnectionStatus::new("admin@192.0.2.1:22".to_string(), None);
+        status.record_sys(None);
+        status.record_sys(Some("vsys2"));
+        status.record_sys(Some("vsys1"));
+        status.record_sys(Some("vsys2"));
+        assert_eq!(status.seen_sys(), ["vsys1", "vsys2"]);
+    }
+
+    #[test]
+    fn status_follows_the_shared_change_freeze_switch() {
+        let status = ConnectionStatus::new("admin@192.0.2.1:22".to_string(), None);
+        let switch: freeze::FreezeSwitch = Arc::new(std::sync::RwLock::new(None));
+        status.set_change_freeze(switch.clone());
+        assert!(status.change_freeze().is_none());
+
+        *switch.write().expect("switch") = Some(ChangeFreeze::new("audit"));
+        assert_eq!(
+            status.change_freeze().map(|freeze| freeze.reason),
+            Some("audit".to_string())
+        );
+        *switch.write().expect("switch") =
+            Some(ChangeFreeze::new("audit").with_override_token("CHG-1"));
+        assert!(
+            status
+                .change_freeze()
+                .is_some_and(|freeze| freeze.is_overridden_by(Some("CHG-1")))
+        );
+    }
+}
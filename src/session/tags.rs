@@ -0,0 +1,113 @@
+//! Tag-scoped pool operations.
+//!
+//! Requests carry tags such as `site:fra1`, `role:edge` or `tenant:acme`
+//! ([`ConnectionRequest::with_tag`]); the connection serving a request
+//! keeps them. Controllers shared by several teams list, close or drain
+//! every session of one tenant at once.
+
+use super::*;
+
+impl SshConnectionManager {
+    /// Pooled connections carrying `tag`, sorted by cache key.
+    pub fn connections_by_tag(&self, tag: &str) -> Vec<ConnectionInfo> {
+        let mut connections: Vec<_> = self
+            .cache
+            .iter()
+            .filter(|(_, pooled)| pooled.status.has_tag(tag))
+            .map(|(_, pooled)| pooled.status.info())
+            .collect();
+        connections.sort_by(|a, b| a.device_addr.cmp(&b.device_addr));
+        connections
+    }
+
+    fn pooled_by_tag(&self, tag: &str) -> Vec<(String, PooledConnection)> {
+        self.cache
+            .iter()
+            .filter(|(_, pooled)| pooled.status.has_tag(tag))
+            .map(|(device_addr, pooled)| (device_addr.as_ref().clone(), pooled))
+            .collect()
+    }
+
+    /// Close and forget every pooled connection carrying `tag`, returning
+    /// how many were closed.
+    ///
+    /// Like [`disconnect`](Self::disconnect), each close waits for the
+    /// command running on that connection; queued jobs then fail.
+    pub async fn disconnect_by_tag(&self, tag: &str) -> usize {
+        let tagged = self.pooled_by_tag(tag);
+        for (device_addr, pooled) in &tagged {
+            self.cache.invalidate(device_addr).await;
+            let _ = self
+                .safely_disconnect_cached_connection(
+                    device_addr,
+                    pooled.client.clone(),
+                    "disconnect_called",
+                )
+                .await;
+        }
+        tagged.len()
+    }
+
+    /// Take every connection carrying `tag` out of the pool, let the jobs
+    /// already queued on them finish, then close them. Returns how many
+    /// were drained.
+    ///
+    /// New requests no longer reach the drained sessions as soon as this is
+    /// called; they open fresh connections instead.
+    pub async fn drain_by_tag(&self, tag: &str) -> usize {
+        let tagged = self.pooled_by_tag(tag);
+        for (device_addr, _) in &tagged {
+            self.cache.invalidate(device_addr).await;
+        }
+        for (device_addr, pooled) in &tagged {
+            while pooled.status.is_connected()
+                && pooled.sender.capacity() < pooled.sender.max_capacity()
+            {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+            debug!("Drained {}", device_addr);
+            let _ = self
+                .safely_disconnect_cached_connection(
+                    device_addr,
+                    pooled.client.clone(),
+                    "drain_called",
+                )
+                .await;
+        }
+        tagged.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn tags_accumulate_and_scope_pool_operations() {
+        let status = status::ConnectionStatus::new("admin@192.0.2.1:22".to_string(), None);
+        status.add_tags(&["tenant:acme".to_string(), "site:fra1".to_string()]);
+        status.add_tags(&["tenant:acme".to_string(), "role:edge".to_string()]);
+        assert!(status.has_tag("role:edge"));
+        assert!(!status.has_tag("tenant:globex"));
+        assert_eq!(
+            status.info().tags,
+            ["role:edge", "site:fra1", "tenant:acme"]
+        );
+
+        let request = ConnectionRequest::new(
+            "admin".to_string(),
+            "192.0.2.1".to_string(),
+            22,
+            "secret".to_string(),
+            None,
+            crate::templates::cisco().expect("cisco"),
+        )
+        .with_tag("tenant:acme");
+        assert_eq!(request.tags, ["tenant:acme"]);
+
+        let manager = SshConnectionManager::new();
+        assert!(manager.connections_by_tag("tenant:acme").is_empty());
+        assert_eq!(manager.disconnect_by_tag("tenant:acme").await, 0);
+        assert_eq!(manager.drain_by_tag("tenant:acme").await, 0);
+    }
+}
@@ -0,0 +1,348 @@
+//! VT100 screen model for devices that redraw instead of printing lines.
+//!
+//! Some console servers and OLT shells draw menus with cursor positioning
+//! and erase sequences, so their byte stream has no usable line structure.
+//! With [`DeviceHandlerConfig::terminal_emulation`] enabled, output is
+//! replayed onto this screen and logical lines are rebuilt from it: a row is
+//! emitted once the cursor has left it downwards, and the row holding the
+//! cursor is the pending line (usually the prompt).
+//!
+//! Only the subset needed to recover text is modelled: cursor movement,
+//! erase, save/restore and scrolling. Attributes, character sets and modes
+//! are consumed and ignored.
+
+/// Screen height, matching the PTY requested on connect.
+const SCREEN_ROWS: usize = 600;
+
+#[derive(Debug, Default)]
+struct Row {
+    cells: Vec<char>,
+    /// Written since the row was last emitted.
+    dirty: bool,
+}
+
+impl Row {
+    fn text(&self, min_len: usize) -> String {
+        let len = self
+            .cells
+            .iter()
+            .rposition(|c| *c != ' ')
+            .map_or(0, |pos| pos + 1)
+            .max(min_len.min(self.cells.len()));
+        self.cells[..len].iter().collect()
+    }
+
+    fn erase(&mut self, range: std::ops::Range<usize>) {
+        let end = range.end.min(self.cells.len());
+        for cell in self.cells.iter_mut().take(end).skip(range.start) {
+            *cell = ' ';
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+enum ParseState {
+    #[default]
+    Ground,
+    Escape,
+    /// `ESC` followed by an intermediate byte; the next byte ends it.
+    EscapeIntermediate,
+    Csi(String),
+    /// OSC or DCS string; `true` once an `ESC` of a possible `ESC \` was seen.
+    Str(bool),
+}
+
+/// Incremental VT100 screen fed with raw shell output.
+#[derive(Debug)]
+pub(crate) struct Screen {
+    /// Width in columns, matching the PTY requested on connect.
+    cols: usize,
+    rows: Vec<Row>,
+    row: usize,
+    col: usize,
+    saved: (usize, usize),
+    state: ParseState,
+    /// Incomplete UTF-8 sequence carried to the next chunk.
+    partial: Vec<u8>,
+}
+
+impl Default for Screen {
+    fn default() -> Self {
+        Self::new(crate::device::DEFAULT_TERMINAL_WIDTH)
+    }
+}
+
+impl Screen {
+    pub(crate) fn new(cols: u32) -> Self {
+        Self {
+            cols: (cols as usize).max(1),
+            rows: Vec::new(),
+            row: 0,
+            col: 0,
+            saved: (0, 0),
+            state: ParseState::default(),
+            partial: Vec::new(),
+        }
+    }
+
+    /// Apply `chunk` and append the text it produced to `out`.
+    ///
+    /// `out` receives every completed row followed by `\n`, then the current
+    /// cursor row without a newline.
+    pub(crate) fn feed(&mut self, chunk: &[u8], out: &mut String) {
+        self.partial.extend_from_slice(chunk);
+        let bytes = std::mem::take(&mut self.partial);
+        let valid = match std::str::from_utf8(&bytes) {
+            Ok(text) => text,
+            Err(err) if err.error_len().is_none() => {
+                let (valid, rest) = bytes.split_at(err.valid_up_to());
+                self.partial = rest.to_vec();
+                std::str::from_utf8(valid).unwrap_or_default()
+            }
+            Err(_) => {
+                let text = String::from_utf8_lossy(&bytes).into_owned();
+                text.chars().for_each(|c| self.input(c, out));
+                return self.pending(out);
+            }
+        };
+        valid.chars().for_each(|c| self.input(c, out));
+        self.pending(out);
+    }
+
+    /// Forget the cursor row's text, as when a password prompt was answered.
+    pub(crate) fn consume_pending(&mut self) {
+        let col = self.col;
+        let row = self.row_mut();
+        row.erase(0..col);
+        row.dirty = false;
+    }
+
+    fn pending(&mut self, out: &mut String) {
+        for row in self.rows.iter_mut().take(self.row) {
+            if row.dirty {
+                out.push_str(&row.text(0));
+                out.push('\n');
+                row.dirty = false;
+            }
+        }
+        if let Some(row) = self.rows.get(self.row) {
+            out.push_str(&row.text(self.col));
+        }
+    }
+
+    fn row_mut(&mut self) -> &mut Row {
+        if self.rows.len() <= self.row {
+            self.rows.resize_with(self.row + 1, Row::default);
+        }
+        &mut self.rows[self.row]
+    }
+
+    fn input(&mut self, c: char, out: &mut String) {
+        match std::mem::take(&mut self.state) {
+            ParseState::Ground => self.ground(c, out),
+            ParseState::Escape => self.escape(c, out),
+            ParseState::EscapeIntermediate => {}
+            ParseState::Csi(mut params) => {
+                if ('\u{40}'..='\u{7e}').contains(&c) {
+                    self.csi(&params, c);
+                } else {
+                    params.push(c);
+                    self.state = ParseState::Csi(params);
+                }
+            }
+            ParseState::Str(after_escape) => {
+                let ended = c == '\u{7}' || (after_escape && c == '\\');
+                if !ended {
+                    self.state = ParseState::Str(c == '\u{1b}');
+                }
+            }
+        }
+    }
+
+    fn ground(&mut self, c: char, out: &mut String) {
+        match c {
+            '\u{1b}' => self.state = ParseState::Escape,
+            '\r' => self.col = 0,
+            // Treat a bare line feed as newline mode, like most shells send it.
+            '\n' | '\u{b}' | '\u{c}' => {
+                self.row_mut().dirty = true;
+                self.col = 0;
+                self.line_feed(out);
+            }
+            '\u{8}' => self.col = self.col.saturating_sub(1),
+            '\t' => self.col = ((self.col / 8 + 1) * 8).min(self.cols - 1),
+            c if c.is_control() => {}
+            c => {
+                if self.col >= self.cols {
+                    self.col = 0;
+                    self.line_feed(out);
+                }
+                let col = self.col;
+                let row = self.row_mut();
+                if row.cells.len() <= col {
+                    row.cells.resize(col + 1, ' ');
+                }
+                row.cells[col] = c;
+                row.dirty = true;
+                self.col += 1;
+            }
+        }
+    }
+
+    fn line_feed(&mut self, out: &mut String) {
+        if self.row + 1 < SCREEN_ROWS {
+            self.row += 1;
+            return;
+        }
+        if !self.rows.is_empty() {
+            let top = self.rows.remove(0);
+            if top.dirty {
+                out.push_str(&top.text(0));
+                out.push('\n');
+            }
+        }
+    }
+
+    fn escape(&mut self, c: char, out: &mut String) {
+        match c {
+            '[' => self.state = ParseState::Csi(String::new()),
+            ']' | 'P' | '_' | '^' => self.state = ParseState::Str(false),
+            ' '..='/' => self.state = ParseState::EscapeIntermediate,
+            'D' => self.line_feed(out),
+            'E' => {
+                self.col = 0;
+                self.line_feed(out);
+            }
+            'M' => self.row = self.row.saturating_sub(1),
+            '7' => self.saved = (self.row, self.col),
+            '8' => (self.row, self.col) = self.saved,
+            'c' => {
+                self.rows.iter_mut().for_each(|row| row.erase(0..self.cols));
+                (self.row, self.col) = (0, 0);
+            }
+            _ => {}
+        }
+    }
+
+    fn csi(&mut self, params: &str, action: char) {
+        let values: Vec<usize> = params
+            .trim_start_matches(['?', '>', '='])
+            .split(';')
+            .map(|value| value.parse().unwrap_or(0))
+            .collect();
+        let arg = |index: usize, default: usize| match values.get(index) {
+            Some(0) | None => default,
+            Some(value) => *value,
+        };
+        match action {
+            'A' => self.row = self.row.saturating_sub(arg(0, 1)),
+            'B' => self.row = (self.row + arg(0, 1)).min(SCREEN_ROWS - 1),
+            'C' => self.col = (self.col + arg(0, 1)).min(self.cols - 1),
+            'D' => self.col = self.col.saturating_sub(arg(0, 1)),
+            'E' => {
+                self.row = (self.row + arg(0, 1)).min(SCREEN_ROWS - 1);
+                self.col = 0;
+            }
+            'F' => {
+                self.row = self.row.saturating_sub(arg(0, 1));
+                self.col = 0;
+            }
+            'G' | '`' => self.col = (arg(0, 1) - 1).min(self.cols - 1),
+            'd' => self.row = (arg(0, 1) - 1).min(SCREEN_ROWS - 1),
+            'H' | 'f' => {
+                self.row = (arg(0, 1) - 1).min(SCREEN_ROWS - 1);
+                self.col = (arg(1, 1) - 1).min(self.cols - 1);
+            }
+            'J' => {
+                let (row, col, cols) = (self.row, self.col, self.cols);
+                let rows = match values.first().copied().unwrap_or(0) {
+                    0 => {
+                        self.row_mut().erase(col..cols);
+                        row + 1..self.rows.len()
+                    }
+                    1 => {
+                        self.row_mut().erase(0..col + 1);
+                        0..row
+                    }
+                    _ => 0..self.rows.len(),
+                };
+                for row in self.rows.iter_mut().take(rows.end).skip(rows.start) {
+                    row.erase(0..self.cols);
+                    row.dirty = false;
+                }
+            }
+            'K' => {
+                let col = self.col;
+                let range = match values.first().copied().unwrap_or(0) {
+                    0 => col..self.cols,
+                    1 => 0..col + 1,
+                    _ => 0..self.cols,
+                };
+                self.row_mut().erase(range);
+            }
+            's' => self.saved = (self.row, self.col),
+            'u' => (self.row, self.col) = self.saved,
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feed(screen: &mut Screen, chunk: &str) -> String {
+        let mut out = String::new();
+        screen.feed(chunk.as_bytes(), &mut out);
+        out
+    }
+
+    #[test]
+    fn plain_output_passes_through_as_lines() {
+        let mut screen = Screen::default();
+        assert_eq!(
+            feed(&mut screen, "show clock\r\n10:00 UTC\r\n\r\nolt#"),
+            "show clock\n10:00 UTC\n\nolt#"
+        );
+        assert_eq!(feed(&mut screen, " "), "olt# ");
+    }
+
+    #[test]
+    fn redrawn_menu_is_rebuilt_from_the_screen() {
+        let mut screen = Screen::default();
+        let out = feed(
+            &mut screen,
+            "\x1b[2J\x1b[1;1H\x1b[7mMain Menu\x1b[0m\
+             \x1b[3;5H2) Ports\x1b[2;5H1) Status\
+             \x1b[5;1HSelect: ",
+        );
+        assert_eq!(out, "Main Menu\n    1) Status\n    2) Ports\nSelect: ");
+
+        // A status field is redrawn in place; only that row is emitted again.
+        let out = feed(&mut screen, "\x1b7\x1b[2;15H\x1b[Kup\x1b8");
+        assert_eq!(out, "    1) Status up\nSelect: ");
+    }
+
+    #[test]
+    fn text_wraps_at_the_configured_width() {
+        let mut screen = Screen::new(8);
+        assert_eq!(feed(&mut screen, "permit ip any"), "permit i\np any");
+    }
+
+    #[test]
+    fn split_escape_and_utf8_sequences_wait_for_the_next_chunk() {
+        let mut screen = Screen::default();
+        let text = "接口\x1b[1;10Hok".as_bytes();
+        let out = {
+            let mut out = String::new();
+            screen.feed(&text[..4], &mut out);
+            screen.feed(&text[4..9], &mut out);
+            screen.feed(&text[9..], &mut out);
+            out
+        };
+        assert!(out.ends_with("接口       ok"));
+
+        screen.consume_pending();
+        assert_eq!(feed(&mut screen, ""), "           ");
+    }
+}
@@ -1,4 +1,5 @@
 use super::*;
+use crate::templates::{CandidateConfig, ConfigGrammar};
 
 /// High-level command block type.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
@@ -25,6 +26,20 @@ pub enum RollbackPolicy {
     },
     /// Roll back each executed step in reverse order.
     PerStep,
+    /// Snapshot the config with `snapshot` before the block and, on failure,
+    /// undo whatever a second snapshot shows changed, in `grammar`'s syntax.
+    ///
+    /// For vendors where inverting each step is unreliable.
+    ConfigDiff {
+        /// Command printing the affected config, e.g. `show running-config interface`.
+        snapshot: Box<Command>,
+        grammar: ConfigGrammar,
+    },
+    /// Load the steps into the vendor's candidate config, then validate and
+    /// commit; on failure, abort the candidate instead of undoing each step.
+    ///
+    /// A committed block is undone with the candidate's `revert` commands.
+    Candidate(CandidateConfig),
 }
 
 fn default_whole_resource_trigger_step_index() -> usize {
@@ -66,7 +81,7 @@ pub struct TxBlock {
 pub struct PlannedRollback {
     /// Step index associated with this rollback operation.
     ///
-    /// `None` means the rollback is block-level (`WholeResource`).
+    /// `None` means the rollback is block-level (`WholeResource`, `ConfigDiff`).
     pub step_index: Option<usize>,
     /// Rollback operation to execute.
     pub operation: SessionOperation,
@@ -125,6 +140,9 @@ pub struct TxOperationStepResult {
     pub all: String,
     /// Prompt observed after the child step finished.
     pub prompt: Option<String>,
+    /// Temp file holding the transcript when it exceeded the spill threshold.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub spilled: Option<SpilledOutput>,
 }
 
 impl From<SessionOperationStepOutput> for TxOperationStepResult {
@@ -138,6 +156,7 @@ impl From<SessionOperationStepOutput> for TxOperationStepResult {
             content: value.content,
             all: value.all,
             prompt: value.prompt,
+            spilled: value.spilled,
         }
     }
 }
@@ -153,6 +172,7 @@ impl From<TxOperationStepResult> for SessionOperationStepOutput {
             content: value.content,
             all: value.all,
             prompt: value.prompt,
+            spilled: value.spilled,
         }
     }
 }
@@ -213,6 +233,9 @@ pub struct TxResult {
     /// Per-step execution and rollback details in block order.
     #[serde(default)]
     pub step_results: Vec<TxStepResult>,
+    /// Config captured before the block ran under [`RollbackPolicy::ConfigDiff`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub config_snapshot: Option<String>,
 }
 
 /// Multi-block workflow transaction.
@@ -224,6 +247,15 @@ pub struct TxWorkflow {
     pub blocks: Vec<TxBlock>,
     /// Stop at first failed block (recommended true).
     pub fail_fast: bool,
+    /// Check reachability, mode paths and dyn_params before the first block
+    /// and fail with [`ConnectError::PreflightFailed`] instead of running.
+    #[serde(default)]
+    pub preflight: bool,
+    /// Key under which the workflow is journaled; takes precedence over
+    /// [`ExecutionContext::idempotency_key`]. A workflow whose key already has
+    /// a result in the manager's journal returns it without running again.
+    #[serde(default)]
+    pub idempotency_key: Option<String>,
 }
 
 /// Workflow execution result.
@@ -297,6 +329,20 @@ impl TxStepResult {
 }
 
 impl SessionOperation {
+    /// Give commands that name no mode `mode`; templates keep their own.
+    pub(crate) fn fill_missing_modes(&mut self, mode: &str) {
+        let commands = match self {
+            SessionOperation::Command(command) => std::slice::from_mut(command),
+            SessionOperation::Flow(flow) => flow.steps.as_mut_slice(),
+            SessionOperation::Template { .. } => return,
+        };
+        for command in commands {
+            if command.mode.trim().is_empty() {
+                command.mode = mode.to_string();
+            }
+        }
+    }
+
     pub fn to_command_flow(&self) -> Result<CommandFlow, ConnectError> {
         match self {
             SessionOperation::Command(command) => {
@@ -457,6 +503,44 @@ pub fn failed_block_rollback_summary(
 }
 
 impl TxBlock {
+    /// Fill in modes the steps omit: forward steps of `show` blocks run in
+    /// `show_mode`, those of `config` blocks and every rollback in
+    /// `config_mode`.
+    pub(crate) fn fill_missing_modes(&mut self, show_mode: &str, config_mode: &str) {
+        let forward_mode = match self.kind {
+            CommandBlockKind::Show => show_mode,
+            CommandBlockKind::Config => config_mode,
+        };
+        for step in &mut self.steps {
+            step.run.fill_missing_modes(forward_mode);
+            if let Some(rollback) = step.rollback.as_mut() {
+                rollback.fill_missing_modes(config_mode);
+            }
+        }
+        match &mut self.rollback_policy {
+            RollbackPolicy::WholeResource { rollback, .. } => {
+                rollback.fill_missing_modes(config_mode);
+            }
+            RollbackPolicy::ConfigDiff { snapshot, .. } if snapshot.mode.trim().is_empty() => {
+                snapshot.mode = show_mode.to_string();
+            }
+            RollbackPolicy::Candidate(candidate) if candidate.mode.trim().is_empty() => {
+                candidate.mode = config_mode.to_string();
+            }
+            _ => {}
+        }
+    }
+
+    /// Use `operator` as the commit comment of a candidate block that sets
+    /// none.
+    pub(crate) fn attach_operator(&mut self, operator: &str) {
+        if let RollbackPolicy::Candidate(candidate) = &mut self.rollback_policy
+            && candidate.comment.is_none()
+        {
+            candidate.comment = Some(operator.to_string());
+        }
+    }
+
     /// Validate cross-field invariants before execution.
     ///
     /// Key rule: `show` blocks must not define rollback; `config` blocks must.
@@ -502,6 +586,12 @@ impl TxBlock {
                 }
             }
             (CommandBlockKind::Config, RollbackPolicy::PerStep) => {}
+            (CommandBlockKind::Config, RollbackPolicy::ConfigDiff { snapshot, .. }) => {
+                validate_command(snapshot, "config_diff snapshot command")?;
+            }
+            (CommandBlockKind::Config, RollbackPolicy::Candidate(candidate)) => {
+                candidate.validate_spec()?;
+            }
         }
 
         Ok(())
@@ -513,7 +603,10 @@ impl TxBlock {
         failed_step_index: Option<usize>,
     ) -> Result<Vec<PlannedRollback>, ConnectError> {
         match &self.rollback_policy {
-            RollbackPolicy::None => Ok(Vec::new()),
+            // Planned from the snapshots at run time.
+            RollbackPolicy::None
+            | RollbackPolicy::ConfigDiff { .. }
+            | RollbackPolicy::Candidate(_) => Ok(Vec::new()),
             RollbackPolicy::WholeResource {
                 rollback,
                 trigger_step_index,
@@ -578,6 +671,18 @@ impl TxBlock {
                 "whole_resource rollback skipped: trigger_step_index={} was not executed successfully",
                 trigger_step_index
             )],
+            RollbackPolicy::ConfigDiff { .. } => {
+                vec![
+                    "config_diff rollback skipped: config unchanged since the block started"
+                        .to_string(),
+                ]
+            }
+            RollbackPolicy::Candidate(_) => {
+                vec![
+                    "candidate rollback skipped: no revert commands for a committed candidate"
+                        .to_string(),
+                ]
+            }
             RollbackPolicy::PerStep => {
                 let mut reasons = Vec::new();
 
@@ -633,6 +738,7 @@ impl TxResult {
             block_rollback_operation_summary: None,
             block_rollback_steps: Vec::new(),
             step_results: Vec::new(),
+            config_snapshot: None,
         }
     }
 
@@ -664,11 +770,7 @@ mod tests {
     use super::*;
 
     fn command(mode: &str, command: &str) -> Command {
-        Command {
-            mode: mode.to_string(),
-            command: command.to_string(),
-            ..Command::default()
-        }
+        Command::new(mode, command)
     }
 
     fn per_step_block() -> TxBlock {
@@ -810,6 +912,8 @@ mod tests {
             name: "fw-policy".to_string(),
             blocks: vec![],
             fail_fast: true,
+            preflight: false,
+            idempotency_key: None,
         };
         let err = workflow
             .validate()
@@ -830,6 +934,8 @@ mod tests {
             name: "wf".to_string(),
             blocks: vec![invalid_block],
             fail_fast: true,
+            preflight: false,
+            idempotency_key: None,
         };
         let err = workflow.validate().expect_err("invalid nested block");
         assert!(matches!(err, ConnectError::InvalidTransaction(_)));
@@ -927,6 +1033,7 @@ mod tests {
             block_rollback_operation_summary: None,
             block_rollback_steps: Vec::new(),
             step_results: Vec::new(),
+            config_snapshot: None,
         };
         let (attempted, succeeded, errors) = failed_block_rollback_summary(Some(&failed));
         assert!(attempted);
@@ -949,6 +1056,7 @@ mod tests {
             block_rollback_operation_summary: None,
             block_rollback_steps: Vec::new(),
             step_results: Vec::new(),
+            config_snapshot: None,
         };
         let (attempted, succeeded, errors) = failed_block_rollback_summary(Some(&failed));
         assert!(!attempted);
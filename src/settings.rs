@@ -0,0 +1,293 @@
+//! Global defaults loaded from a config file and environment variables.
+//!
+//! [`Settings::load`] reads the JSON file named by `RNETER_CONFIG` (when
+//! set) and then applies `RNETER_*` overrides:
+//!
+//! | Variable | Field |
+//! |----------|-------|
+//! | `RNETER_COMMAND_TIMEOUT_SECS` | [`Settings::command_timeout_secs`] |
+//! | `RNETER_CONNECT_TIMEOUT_SECS` | [`Settings::connect_timeout_secs`] |
+//! | `RNETER_SECURITY_LEVEL` | [`Settings::security_level`], e.g. `legacy_compatible` |
+//! | `RNETER_POOL_MAX_CONNECTIONS` | [`Settings::pool_max_connections`] |
+//! | `RNETER_POOL_IDLE_TIMEOUT_SECS` | [`Settings::pool_idle_timeout_secs`] |
+//! | `RNETER_RECORD_LEVEL` | [`Settings::record_level`], e.g. `key_events_only` |
+//! | `RNETER_TEMPLATE_DIRS` | [`Settings::template_dirs`], `PATH`-style list |
+//! | `RNETER_OPERATOR` | [`Settings::operator`] |
+//!
+//! The global [`MANAGER`](crate::session::MANAGER) is built from
+//! [`Settings::load`]; other managers use [`Settings::build_manager`].
+
+use std::path::{Path, PathBuf};
+
+use log::warn;
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::device::{DeviceHandler, DeviceHandlerConfig};
+use crate::error::ConnectError;
+use crate::session::{
+    ConnectionSecurityOptions, ExecutionContext, SecurityLevel, SessionRecordLevel,
+    SessionRecorder, SshConnectionManager,
+};
+use crate::templates;
+
+/// Environment variable naming the settings file.
+pub const CONFIG_ENV: &str = "RNETER_CONFIG";
+
+/// Library-wide defaults for managers and sessions.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct Settings {
+    /// Timeout for commands that do not set one.
+    pub command_timeout_secs: u64,
+    /// Limit for establishing a connection, including login and prompt detection.
+    pub connect_timeout_secs: u64,
+    /// Security level of [`Settings::execution_context`].
+    pub security_level: SecurityLevel,
+    /// Maximum number of pooled connections.
+    pub pool_max_connections: u64,
+    /// Idle time after which a pooled connection is evicted.
+    pub pool_idle_timeout_secs: u64,
+    /// Level of [`Settings::recorder`]; `Off` disables recording.
+    pub record_level: SessionRecordLevel,
+    /// Directories searched for `<template>.json` handler configs before the
+    /// built-in templates.
+    pub template_dirs: Vec<PathBuf>,
+    /// Operator identity of [`Settings::execution_context`], written into
+    /// commit comments.
+    pub operator: Option<String>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            command_timeout_secs: 60,
+            connect_timeout_secs: 60,
+            security_level: SecurityLevel::Secure,
+            pool_max_connections: 100,
+            pool_idle_timeout_secs: 5 * 60,
+            record_level: SessionRecordLevel::Off,
+            template_dirs: Vec::new(),
+            operator: None,
+        }
+    }
+}
+
+impl Settings {
+    /// Parse settings from JSON; missing fields keep their defaults.
+    pub fn from_json_str(content: &str) -> Result<Self, ConnectError> {
+        serde_json::from_str(content)
+            .map_err(|e| ConnectError::InvalidRequest(format!("settings JSON: {e}")))
+    }
+
+    /// Load a JSON settings file.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, ConnectError> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path).map_err(|e| {
+            ConnectError::InvalidRequest(format!("cannot read {}: {e}", path.display()))
+        })?;
+        Self::from_json_str(&content)
+    }
+
+    /// The file named by `RNETER_CONFIG` (or defaults), with `RNETER_*`
+    /// environment overrides applied.
+    pub fn load() -> Result<Self, ConnectError> {
+        let settings = match std::env::var_os(CONFIG_ENV) {
+            Some(path) => Self::from_file(path)?,
+            None => Self::default(),
+        };
+        settings.with_env()
+    }
+
+    /// Apply `RNETER_*` environment overrides.
+    pub fn with_env(self) -> Result<Self, ConnectError> {
+        self.with_vars(std::env::vars())
+    }
+
+    fn with_vars(
+        mut self,
+        vars: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<Self, ConnectError> {
+        for (name, value) in vars {
+            match name.as_str() {
+                "RNETER_COMMAND_TIMEOUT_SECS" => {
+                    self.command_timeout_secs = parse_number(&name, &value)?
+                }
+                "RNETER_CONNECT_TIMEOUT_SECS" => {
+                    self.connect_timeout_secs = parse_number(&name, &value)?
+                }
+                "RNETER_SECURITY_LEVEL" => self.security_level = parse_variant(&name, &value)?,
+                "RNETER_POOL_MAX_CONNECTIONS" => {
+                    self.pool_max_connections = parse_number(&name, &value)?
+                }
+                "RNETER_POOL_IDLE_TIMEOUT_SECS" => {
+                    self.pool_idle_timeout_secs = parse_number(&name, &value)?
+                }
+                "RNETER_RECORD_LEVEL" => self.record_level = parse_variant(&name, &value)?,
+                "RNETER_TEMPLATE_DIRS" => {
+                    self.template_dirs = std::env::split_paths(&value)
+                        .filter(|dir| !dir.as_os_str().is_empty())
+                        .collect()
+                }
+                "RNETER_OPERATOR" => {
+                    self.operator = Some(value.trim().to_string()).filter(|op| !op.is_empty())
+                }
+                _ => {}
+            }
+        }
+        Ok(self)
+    }
+
+    /// A manager whose pool and timeouts follow these settings.
+    pub fn build_manager(&self) -> SshConnectionManager {
+        SshConnectionManager::with_settings(self)
+    }
+
+    /// Security options for [`Settings::security_level`].
+    pub fn security_options(&self) -> ConnectionSecurityOptions {
+        match self.security_level {
+            SecurityLevel::Secure => ConnectionSecurityOptions::secure_default(),
+            SecurityLevel::Balanced => ConnectionSecurityOptions::balanced(),
+            SecurityLevel::LegacyCompatible => ConnectionSecurityOptions::legacy_compatible(),
+            SecurityLevel::Fips => ConnectionSecurityOptions::fips(),
+        }
+    }
+
+    /// Execution context using the configured security options and operator.
+    pub fn execution_context(&self) -> ExecutionContext {
+        let context = ExecutionContext::new().with_security_options(self.security_options());
+        match &self.operator {
+            Some(operator) => context.with_operator(operator.clone()),
+            None => context,
+        }
+    }
+
+    /// A recorder at [`Settings::record_level`], or `None` when it is `Off`.
+    pub fn recorder(&self) -> Option<SessionRecorder> {
+        (self.record_level != SessionRecordLevel::Off)
+            .then(|| SessionRecorder::new(self.record_level))
+    }
+
+    /// Handler for `name`: the first `<name>.json` found in
+    /// [`Settings::template_dirs`], else the built-in template.
+    pub fn template(&self, name: &str) -> Result<DeviceHandler, ConnectError> {
+        let file_name = format!("{}.json", name.to_ascii_lowercase());
+        for dir in &self.template_dirs {
+            let path = dir.join(&file_name);
+            if !path.is_file() {
+                continue;
+            }
+            let content = std::fs::read_to_string(&path).map_err(|e| {
+                ConnectError::InvalidRequest(format!("cannot read {}: {e}", path.display()))
+            })?;
+            let config: DeviceHandlerConfig = serde_json::from_str(&content).map_err(|e| {
+                ConnectError::InvalidDeviceHandlerConfig(format!("{}: {e}", path.display()))
+            })?;
+            return config.build();
+        }
+        templates::by_name(name)
+    }
+
+    /// [`Settings::load`], falling back to defaults with a warning.
+    pub(crate) fn load_or_default() -> Self {
+        Self::load().unwrap_or_else(|err| {
+            warn!("ignoring rneter settings: {err}");
+            Self::default()
+        })
+    }
+}
+
+fn parse_number(name: &str, value: &str) -> Result<u64, ConnectError> {
+    value
+        .trim()
+        .parse()
+        .map_err(|e| ConnectError::InvalidRequest(format!("{name}='{value}': {e}")))
+}
+
+/// Parse an enum variant given as `snake_case` or `PascalCase`.
+fn parse_variant<T: DeserializeOwned>(name: &str, value: &str) -> Result<T, ConnectError> {
+    let variant: String = value
+        .trim()
+        .split(['_', '-'])
+        .map(|word| {
+            let mut chars = word.chars();
+            chars
+                .next()
+                .map(|first| first.to_uppercase().chain(chars).collect::<String>())
+                .unwrap_or_default()
+        })
+        .collect();
+    serde_json::from_value(serde_json::Value::String(variant))
+        .map_err(|e| ConnectError::InvalidRequest(format!("{name}='{value}': {e}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_and_env_values_override_defaults() {
+        let settings =
+            Settings::from_json_str(r#"{"command_timeout_secs": 120, "security_level": "Fips"}"#)
+                .expect("settings");
+        assert_eq!(settings.command_timeout_secs, 120);
+        assert_eq!(settings.pool_max_connections, 100);
+        assert!(settings.recorder().is_none());
+
+        let vars = [
+            ("RNETER_SECURITY_LEVEL", "legacy_compatible"),
+            ("RNETER_RECORD_LEVEL", "key_events_only"),
+            ("RNETER_POOL_MAX_CONNECTIONS", " 500 "),
+            ("RNETER_OPERATOR", "netops-bot"),
+            ("HOME", "/root"),
+        ];
+        let settings = settings
+            .with_vars(vars.map(|(k, v)| (k.to_string(), v.to_string())))
+            .expect("env");
+        assert_eq!(settings.command_timeout_secs, 120);
+        assert_eq!(settings.security_level, SecurityLevel::LegacyCompatible);
+        assert_eq!(
+            settings.execution_context().security_options,
+            ConnectionSecurityOptions::legacy_compatible()
+        );
+        assert_eq!(settings.record_level, SessionRecordLevel::KeyEventsOnly);
+        assert_eq!(settings.pool_max_connections, 500);
+        assert_eq!(
+            settings.execution_context().operator.as_deref(),
+            Some("netops-bot")
+        );
+
+        let invalid = Settings::default().with_vars([(
+            "RNETER_CONNECT_TIMEOUT_SECS".to_string(),
+            "soon".to_string(),
+        )]);
+        assert!(matches!(invalid, Err(ConnectError::InvalidRequest(_))));
+    }
+
+    #[test]
+    fn template_dirs_take_precedence_over_builtins() {
+        let dir = std::env::temp_dir().join(format!("rneter-settings-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).expect("temp dir");
+        let mut config = templates::by_name_config("cisco").expect("cisco");
+        config.terminal_width = Some(132);
+        std::fs::write(
+            dir.join("edge.json"),
+            serde_json::to_string(&config).expect("encode"),
+        )
+        .expect("write template");
+
+        let settings = Settings {
+            template_dirs: vec![dir.clone()],
+            ..Settings::default()
+        };
+        let edge = settings.template("Edge").expect("edge template");
+        assert_eq!(edge.terminal_width(), 132);
+        assert!(settings.template("huawei").is_ok());
+        assert!(matches!(
+            settings.template("nope"),
+            Err(ConnectError::TemplateNotFound(_))
+        ));
+        std::fs::remove_dir_all(dir).expect("cleanup");
+    }
+}
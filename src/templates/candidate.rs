@@ -0,0 +1,345 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::error::ConnectError;
+use crate::session::{Command, CommandFlow, SessionOperation};
+
+use super::catalog::template_metadata;
+
+/// Regexes failing `validate` and `commit` output, compiled once when built.
+///
+/// (De)serializes as a list of pattern strings; an invalid pattern fails
+/// [`Self::new`] and deserialization alike.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(try_from = "Vec<String>", into = "Vec<String>")]
+pub struct CandidateFailurePatterns {
+    patterns: Vec<regex::Regex>,
+}
+
+impl CandidateFailurePatterns {
+    /// Compile `patterns`, failing on the first invalid one.
+    pub fn new<I, S>(patterns: I) -> Result<Self, ConnectError>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let patterns = patterns
+            .into_iter()
+            .map(|pattern| {
+                let pattern = pattern.as_ref();
+                regex::Regex::new(pattern).map_err(|err| {
+                    ConnectError::InvalidTransaction(format!(
+                        "invalid candidate failure pattern '{pattern}': {err}"
+                    ))
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self { patterns })
+    }
+
+    fn builtin(pattern: &str) -> Self {
+        Self::new([pattern]).expect("built-in candidate failure pattern")
+    }
+
+    /// The pattern strings, in order.
+    pub fn as_strs(&self) -> impl Iterator<Item = &str> {
+        self.patterns.iter().map(regex::Regex::as_str)
+    }
+
+    /// Whether no pattern is set.
+    pub fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+    }
+
+    /// First line of `output` matching a pattern, trimmed.
+    fn first_match(&self, output: &str) -> Option<String> {
+        output
+            .lines()
+            .find(|line| self.patterns.iter().any(|pattern| pattern.is_match(line)))
+            .map(|line| line.trim().to_string())
+    }
+}
+
+impl PartialEq for CandidateFailurePatterns {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_strs().eq(other.as_strs())
+    }
+}
+
+impl Eq for CandidateFailurePatterns {}
+
+impl TryFrom<Vec<String>> for CandidateFailurePatterns {
+    type Error = ConnectError;
+
+    fn try_from(patterns: Vec<String>) -> Result<Self, Self::Error> {
+        Self::new(patterns)
+    }
+}
+
+impl From<CandidateFailurePatterns> for Vec<String> {
+    fn from(patterns: CandidateFailurePatterns) -> Self {
+        patterns.as_strs().map(str::to_string).collect()
+    }
+}
+
+/// Candidate-config commands of a vendor that stages changes before
+/// activating them (Junos, IOS-XR, PAN-OS).
+///
+/// Commands loaded in `mode` only touch the candidate; `commit` activates
+/// them and `abort` throws them away.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct CandidateConfig {
+    /// Mode editing the candidate config.
+    pub mode: String,
+    /// Command checking the candidate without activating it.
+    pub validate: Option<String>,
+    pub commit: String,
+    /// Commit command carrying `comment`, with `{comment}` and `{label}`
+    /// placeholders; used instead of `commit` when a comment is set.
+    #[serde(default)]
+    pub commit_with_comment: Option<String>,
+    /// Comment attached to the commit, e.g. who or what made the change.
+    #[serde(default)]
+    pub comment: Option<String>,
+    /// Command discarding uncommitted changes while staying in `mode`.
+    pub abort: String,
+    /// Commands undoing the last commit, when the vendor keeps commit history.
+    #[serde(default)]
+    pub revert: Vec<String>,
+    /// Output matching any of these regexes fails `validate` and `commit`
+    /// even when no template error pattern matched.
+    #[serde(default)]
+    #[schemars(with = "Vec<String>")]
+    pub failure_patterns: CandidateFailurePatterns,
+    /// Timeout for `validate` and `commit`, which can take minutes.
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+}
+
+impl CandidateConfig {
+    /// Junos: `commit check`, `commit`, `rollback 0`.
+    pub fn junos() -> Self {
+        Self {
+            mode: "Config".to_string(),
+            validate: Some("commit check".to_string()),
+            commit: "commit".to_string(),
+            commit_with_comment: Some("commit comment \"{comment}\"".to_string()),
+            comment: None,
+            abort: "rollback 0".to_string(),
+            revert: vec!["rollback 1".to_string(), "commit".to_string()],
+            failure_patterns: CandidateFailurePatterns::builtin(
+                r"(?i)commit check failed|configuration check-out failed",
+            ),
+            timeout_secs: Some(300),
+        }
+    }
+
+    /// IOS-XR: `commit` validates on its own; `clear` empties the target config.
+    pub fn iosxr() -> Self {
+        Self {
+            mode: "Config".to_string(),
+            validate: None,
+            commit: "commit".to_string(),
+            commit_with_comment: Some("commit label {label} comment {comment}".to_string()),
+            comment: None,
+            abort: "clear".to_string(),
+            revert: Vec::new(),
+            failure_patterns: CandidateFailurePatterns::builtin(r"(?i)% Failed to commit"),
+            timeout_secs: Some(300),
+        }
+    }
+
+    /// PAN-OS: `validate full`, `commit`, `revert config`.
+    pub fn panos() -> Self {
+        Self {
+            mode: "Config".to_string(),
+            validate: Some("validate full".to_string()),
+            commit: "commit".to_string(),
+            commit_with_comment: Some("commit description \"{comment}\"".to_string()),
+            comment: None,
+            abort: "revert config".to_string(),
+            revert: Vec::new(),
+            failure_patterns: CandidateFailurePatterns::builtin(
+                r"(?i)validation error|commit failed",
+            ),
+            timeout_secs: Some(600),
+        }
+    }
+
+    /// Candidate commands of a built-in template.
+    ///
+    /// Only `juniper` and `paloalto` have a candidate config; use
+    /// [`CandidateConfig::iosxr`] with a custom IOS-XR template.
+    pub fn for_template(template: &str) -> Result<Self, ConnectError> {
+        let template_key = template.to_ascii_lowercase();
+        let _ = template_metadata(&template_key)?;
+        match template_key.as_str() {
+            "juniper" => Ok(Self::junos()),
+            "paloalto" => Ok(Self::panos()),
+            other => Err(ConnectError::InvalidTransaction(format!(
+                "template '{other}' has no candidate config"
+            ))),
+        }
+    }
+
+    /// Attach `comment` to the commit.
+    pub fn with_comment(mut self, comment: impl Into<String>) -> Self {
+        self.comment = Some(comment.into());
+        self
+    }
+
+    pub(crate) fn validate_spec(&self) -> Result<(), ConnectError> {
+        let invalid = |reason: &str| Err(ConnectError::InvalidTransaction(reason.to_string()));
+        if self.mode.trim().is_empty() {
+            return invalid("candidate mode is empty");
+        }
+        if self.commit.trim().is_empty() {
+            return invalid("candidate commit command is empty");
+        }
+        if self.abort.trim().is_empty() {
+            return invalid("candidate abort command is empty");
+        }
+        Ok(())
+    }
+
+    fn command(&self, command: &str, timeout: Option<u64>) -> Command {
+        Command {
+            timeout,
+            ..Command::new(self.mode.clone(), command)
+        }
+    }
+
+    pub fn validate_command(&self) -> Option<Command> {
+        self.validate
+            .as_deref()
+            .map(|validate| self.command(validate, self.timeout_secs))
+    }
+
+    /// The commit, carrying `comment` when both it and
+    /// `commit_with_comment` are set.
+    pub fn commit_command(&self) -> Command {
+        match (&self.commit_with_comment, &self.comment) {
+            (Some(template), Some(comment)) => {
+                // Quotes would end the quoted argument early.
+                let comment = comment.replace(['"', '\n', '\r'], " ");
+                let label: String = comment
+                    .chars()
+                    .map(|ch| {
+                        if ch.is_ascii_alphanumeric() || ch == '-' || ch == '_' {
+                            ch
+                        } else {
+                            '_'
+                        }
+                    })
+                    .collect();
+                let command = template
+                    .replace("{comment}", comment.trim())
+                    .replace("{label}", label.trim_matches('_'));
+                self.command(&command, self.timeout_secs)
+            }
+            _ => self.command(&self.commit, self.timeout_secs),
+        }
+    }
+
+    pub fn abort_command(&self) -> Command {
+        self.command(&self.abort, None)
+    }
+
+    /// Operation undoing the last commit, or `None` without `revert` commands.
+    pub fn revert_operation(&self) -> Option<SessionOperation> {
+        let mut commands: Vec<Command> = self
+            .revert
+            .iter()
+            .map(|command| self.command(command, self.timeout_secs))
+            .collect();
+        match commands.len() {
+            0 => None,
+            1 => Some(commands.remove(0).into()),
+            _ => Some(CommandFlow::new(commands).into()),
+        }
+    }
+
+    /// Every operation a candidate block may send besides its steps.
+    pub fn operations(&self) -> Vec<SessionOperation> {
+        self.validate_command()
+            .into_iter()
+            .chain([self.commit_command(), self.abort_command()])
+            .map(SessionOperation::Command)
+            .chain(self.revert_operation())
+            .collect()
+    }
+
+    /// Why the output of `validate` or `commit` counts as failed, if it does.
+    pub fn check_output(&self, success: bool, content: &str) -> Option<String> {
+        if let Some(line) = self.failure_patterns.first_match(content) {
+            return Some(line);
+        }
+        (!success).then(|| content.trim().to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtin_candidates_cover_juniper_and_paloalto() {
+        let junos = CandidateConfig::for_template("Juniper").expect("juniper");
+        assert_eq!(junos.validate.as_deref(), Some("commit check"));
+        assert_eq!(junos.abort_command().command, "rollback 0");
+        assert!(matches!(
+            junos.revert_operation(),
+            Some(SessionOperation::Flow(_))
+        ));
+        let panos = CandidateConfig::for_template("paloalto").expect("paloalto");
+        assert_eq!(panos.abort, "revert config");
+        assert_eq!(panos.revert_operation(), None);
+        assert!(CandidateConfig::for_template("cisco").is_err());
+        assert!(CandidateConfig::iosxr().validate_spec().is_ok());
+    }
+
+    #[test]
+    fn commit_comments_follow_the_vendor_syntax() {
+        let junos = CandidateConfig::junos().with_comment("ops: \"change\" CHG-42");
+        assert_eq!(
+            junos.commit_command().command,
+            r#"commit comment "ops:  change  CHG-42""#
+        );
+        let iosxr = CandidateConfig::iosxr().with_comment("netops job 7");
+        assert_eq!(
+            iosxr.commit_command().command,
+            "commit label netops_job_7 comment netops job 7"
+        );
+        assert_eq!(CandidateConfig::panos().commit_command().command, "commit");
+    }
+
+    #[test]
+    fn failure_patterns_fail_successful_output() {
+        let junos = CandidateConfig::junos();
+        assert_eq!(
+            junos
+                .check_output(true, "[edit]\nerror: commit check failed\n")
+                .as_deref(),
+            Some("error: commit check failed")
+        );
+        assert_eq!(
+            junos.check_output(true, "configuration check succeeds\n"),
+            None
+        );
+    }
+
+    #[test]
+    fn failure_patterns_are_compiled_when_built() {
+        assert!(matches!(
+            CandidateFailurePatterns::new(["commit failed", "(unclosed"]),
+            Err(ConnectError::InvalidTransaction(reason)) if reason.contains("(unclosed")
+        ));
+        let json = serde_json::to_value(CandidateConfig::iosxr()).expect("encode");
+        assert_eq!(json["failure_patterns"][0], r"(?i)% Failed to commit");
+        let mut invalid = json.clone();
+        invalid["failure_patterns"] = serde_json::json!(["(unclosed"]);
+        assert!(serde_json::from_value::<CandidateConfig>(invalid).is_err());
+        let decoded: CandidateConfig = serde_json::from_value(json).expect("decode");
+        assert_eq!(decoded, CandidateConfig::iosxr());
+    }
+}
@@ -0,0 +1,265 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::error::ConnectError;
+use crate::session::{
+    Command, CommandBlockKind, CommandLibrary, RollbackPolicy, TxBlock, TxStep, TxWorkflow,
+};
+
+use super::config_diff::ConfigGrammar;
+use super::registry::by_name;
+use super::transaction::classify_command;
+
+/// Lines entering config mode; the session switches modes by itself.
+const ENTER_CONFIG_LINES: &[&str] = &[
+    "configure terminal",
+    "configure",
+    "conf t",
+    "config t",
+    "system-view",
+    "sys",
+];
+
+/// Lines saving the running config.
+const SAVE_LINES: &[&str] = &[
+    "write memory",
+    "write",
+    "wr",
+    "copy running-config startup-config",
+    "copy run start",
+    "save",
+];
+
+/// How one line of a change script was read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ScriptLineKind {
+    Blank,
+    Comment,
+    /// Enters or leaves config mode; left to the session's transitions.
+    ModeSwitch,
+    /// Saves the config; left out so it only happens once the change is
+    /// verified.
+    Save,
+    Show,
+    Config,
+}
+
+/// One line of a change script and how it was classified.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct ScriptLine {
+    /// 1-based line number in the script.
+    pub line_no: usize,
+    /// The line with surrounding whitespace removed.
+    pub text: String,
+    pub kind: ScriptLineKind,
+}
+
+/// A change script turned into a workflow.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct ChangeScript {
+    pub lines: Vec<ScriptLine>,
+    pub workflow: TxWorkflow,
+}
+
+/// Turn a pasted vendor change script into a ready-to-run workflow.
+///
+/// Each run of consecutive show lines becomes a show block and each run of
+/// config lines a config block, named after the script lines it covers.
+/// Config blocks roll back with [`RollbackPolicy::ConfigDiff`], snapshotting
+/// the template's running config, so no inverse command has to be written.
+/// Steps run in the template's show and config states. Templates without a
+/// [`ConfigGrammar`] return an error.
+pub fn parse_change_script(
+    template: &str,
+    name: &str,
+    script: &str,
+) -> Result<ChangeScript, ConnectError> {
+    let template_key = template.to_ascii_lowercase();
+    let grammar = ConfigGrammar::for_template(&template_key)?;
+    let snapshot = CommandLibrary::builtin().resolve("backup_config", &template_key)?;
+
+    let mut lines = Vec::new();
+    for (index, raw) in script.lines().enumerate() {
+        let text = raw.trim();
+        let kind = classify_script_line(&template_key, &grammar, text)?;
+        lines.push(ScriptLine {
+            line_no: index + 1,
+            text: text.to_string(),
+            kind,
+        });
+    }
+
+    let mut blocks: Vec<TxBlock> = Vec::new();
+    let mut open: Option<(CommandBlockKind, usize, usize)> = None;
+    for line in &lines {
+        let kind = match line.kind {
+            ScriptLineKind::Show => CommandBlockKind::Show,
+            ScriptLineKind::Config => CommandBlockKind::Config,
+            _ => continue,
+        };
+        let step = TxStep::new(Command::new("", line.text.clone()));
+        match (&mut open, blocks.last_mut()) {
+            (Some((open_kind, _, last)), Some(block)) if *open_kind == kind => {
+                *last = line.line_no;
+                block.steps.push(step);
+            }
+            _ => {
+                let rollback_policy = match kind {
+                    CommandBlockKind::Show => RollbackPolicy::None,
+                    CommandBlockKind::Config => RollbackPolicy::ConfigDiff {
+                        snapshot: Box::new(snapshot.clone()),
+                        grammar: grammar.clone(),
+                    },
+                };
+                close_block(&open, blocks.last_mut());
+                open = Some((kind, line.line_no, line.line_no));
+                blocks.push(TxBlock {
+                    name: String::new(),
+                    kind,
+                    rollback_policy,
+                    steps: vec![step],
+                    fail_fast: true,
+                });
+            }
+        }
+    }
+    close_block(&open, blocks.last_mut());
+    if blocks.is_empty() {
+        return Err(ConnectError::InvalidTransaction(
+            "change script has no commands".to_string(),
+        ));
+    }
+    let handler = by_name(&template_key)?;
+    let mode = |config: bool| {
+        handler.default_mode(config, false).ok_or_else(|| {
+            ConnectError::InvalidTransaction(format!(
+                "template '{template_key}' has no state to derive a mode from"
+            ))
+        })
+    };
+    let (show_mode, config_mode) = (mode(false)?, mode(true)?);
+    for block in &mut blocks {
+        block.fill_missing_modes(&show_mode, &config_mode);
+    }
+
+    Ok(ChangeScript {
+        lines,
+        workflow: TxWorkflow {
+            name: name.to_string(),
+            blocks,
+            fail_fast: true,
+            preflight: true,
+            idempotency_key: None,
+        },
+    })
+}
+
+/// Name the last block after the script lines it covers.
+fn close_block(open: &Option<(CommandBlockKind, usize, usize)>, block: Option<&mut TxBlock>) {
+    if let (Some((_, first, last)), Some(block)) = (open, block) {
+        block.name = if first == last {
+            format!("line {first}")
+        } else {
+            format!("lines {first}-{last}")
+        };
+    }
+}
+
+fn classify_script_line(
+    template: &str,
+    grammar: &ConfigGrammar,
+    text: &str,
+) -> Result<ScriptLineKind, ConnectError> {
+    if text.is_empty() {
+        return Ok(ScriptLineKind::Blank);
+    }
+    if grammar
+        .comment_prefixes
+        .iter()
+        .any(|prefix| text.starts_with(prefix.as_str()))
+    {
+        return Ok(ScriptLineKind::Comment);
+    }
+    let lower = text.to_ascii_lowercase();
+    if ENTER_CONFIG_LINES.contains(&lower.as_str()) || grammar.ignored_lines.contains(&lower) {
+        return Ok(ScriptLineKind::ModeSwitch);
+    }
+    if SAVE_LINES.contains(&lower.as_str()) {
+        return Ok(ScriptLineKind::Save);
+    }
+    Ok(match classify_command(template, text)? {
+        CommandBlockKind::Show => ScriptLineKind::Show,
+        CommandBlockKind::Config => ScriptLineKind::Config,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session::SessionOperation;
+
+    #[test]
+    fn groups_consecutive_lines_into_blocks_with_config_diff_rollback() {
+        let script = "! CHG-1234 uplink description\n\
+                      show interface status\n\
+                      \n\
+                      configure terminal\n\
+                      interface Gi0/1\n \
+                      description uplink\n\
+                      exit\n\
+                      end\n\
+                      show interface Gi0/1\n\
+                      write memory\n";
+        let parsed = parse_change_script("cisco", "CHG-1234", script).expect("script");
+
+        let kinds: Vec<_> = parsed.lines.iter().map(|line| line.kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                ScriptLineKind::Comment,
+                ScriptLineKind::Show,
+                ScriptLineKind::Blank,
+                ScriptLineKind::ModeSwitch,
+                ScriptLineKind::Config,
+                ScriptLineKind::Config,
+                ScriptLineKind::Config,
+                ScriptLineKind::ModeSwitch,
+                ScriptLineKind::Show,
+                ScriptLineKind::Save,
+            ]
+        );
+
+        let workflow = &parsed.workflow;
+        let names: Vec<_> = workflow.blocks.iter().map(|b| b.name.as_str()).collect();
+        assert_eq!(names, ["line 2", "lines 5-7", "line 9"]);
+        let config = &workflow.blocks[1];
+        assert_eq!(config.kind, CommandBlockKind::Config);
+        let commands: Vec<_> = config
+            .steps
+            .iter()
+            .map(|step| match &step.run {
+                SessionOperation::Command(command) => command.command.as_str(),
+                other => panic!("unexpected operation {other:?}"),
+            })
+            .collect();
+        assert_eq!(commands, ["interface Gi0/1", "description uplink", "exit"]);
+        match &config.rollback_policy {
+            RollbackPolicy::ConfigDiff { snapshot, grammar } => {
+                assert_eq!(snapshot.command, "show running-config");
+                assert_eq!(grammar.negate_prefix, "no ");
+            }
+            other => panic!("unexpected policy {other:?}"),
+        }
+        assert!(workflow.validate().is_ok());
+    }
+
+    #[test]
+    fn scripts_without_commands_or_grammar_are_rejected() {
+        assert!(matches!(
+            parse_change_script("huawei", "empty", "#\nsystem-view\nreturn\nsave\n"),
+            Err(ConnectError::InvalidTransaction(_))
+        ));
+        assert!(parse_change_script("linux", "shell", "touch /tmp/x").is_err());
+    }
+}
@@ -239,11 +239,8 @@ impl CommandFlowTemplate {
             }
 
             steps.push(Command {
-                mode,
-                command,
                 timeout: step.timeout_secs,
-                dyn_params: Default::default(),
-                interaction: CommandInteraction { prompts },
+                ..Command::new(mode, command).with_interaction(CommandInteraction { prompts })
             });
         }
 
@@ -0,0 +1,559 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::error::ConnectError;
+use crate::session::{
+    Command, CommandBlockKind, CommandFlow, ConnectionRequest, ExecutionContext, RollbackPolicy,
+    SessionOperation, SshConnectionManager, TxBlock, TxStep,
+};
+
+use super::catalog::template_metadata;
+
+/// Config syntax used to diff and remediate a vendor's running config.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct ConfigGrammar {
+    /// Mode that remediation commands run in.
+    pub mode: String,
+    /// Prefix that negates a line (`no `, `undo `, `delete `).
+    pub negate_prefix: String,
+    /// Leading keywords swapped on negation, e.g. Junos `set ` -> `delete `.
+    pub negate_replacements: Vec<(String, String)>,
+    /// Command leaving one nesting level; `None` treats the config as flat.
+    pub exit_command: Option<String>,
+    /// Lines starting with any of these are ignored.
+    pub comment_prefixes: Vec<String>,
+    /// Lines equal to any of these (after trimming) are ignored.
+    pub ignored_lines: Vec<String>,
+}
+
+impl ConfigGrammar {
+    /// Grammar for a built-in template.
+    ///
+    /// Templates without a line-oriented config mode (`linux`, `fortinet`,
+    /// `checkpoint`, `topsec`) return an error.
+    pub fn for_template(template: &str) -> Result<Self, ConnectError> {
+        let template_key = template.to_ascii_lowercase();
+        let _ = template_metadata(&template_key)?;
+
+        let grammar = match template_key.as_str() {
+            "cisco" | "arista" | "array" | "hillstone" | "maipu" | "venustech" | "chaitin"
+            | "qianxin" => Self::hierarchical(
+                "no ",
+                "exit",
+                &["!", "Building configuration", "Current configuration"],
+                &["end"],
+            ),
+            "huawei" | "h3c" | "dptech" => Self::hierarchical("undo ", "quit", &["#"], &["return"]),
+            "juniper" | "paloalto" => Self {
+                mode: "Config".to_string(),
+                negate_prefix: "delete ".to_string(),
+                negate_replacements: vec![("set ".to_string(), "delete ".to_string())],
+                exit_command: None,
+                comment_prefixes: vec!["#".to_string()],
+                ignored_lines: Vec::new(),
+            },
+            other => {
+                return Err(ConnectError::InvalidTransaction(format!(
+                    "template '{other}' has no config diff grammar"
+                )));
+            }
+        };
+        Ok(grammar)
+    }
+
+    fn hierarchical(
+        negate_prefix: &str,
+        exit_command: &str,
+        comment_prefixes: &[&str],
+        ignored_lines: &[&str],
+    ) -> Self {
+        Self {
+            mode: "Config".to_string(),
+            negate_prefix: negate_prefix.to_string(),
+            negate_replacements: Vec::new(),
+            exit_command: Some(exit_command.to_string()),
+            comment_prefixes: comment_prefixes.iter().map(|p| p.to_string()).collect(),
+            ignored_lines: ignored_lines.iter().map(|l| l.to_string()).collect(),
+        }
+    }
+
+    /// Command that undoes `line`.
+    ///
+    /// Already negated lines are un-negated (`no shutdown` -> `shutdown`).
+    pub fn negate(&self, line: &str) -> String {
+        for (from, to) in &self.negate_replacements {
+            if let Some(rest) = line.strip_prefix(from.as_str()) {
+                return format!("{to}{rest}");
+            }
+        }
+        match line.strip_prefix(self.negate_prefix.as_str()) {
+            Some(rest) => rest.to_string(),
+            None => format!("{}{line}", self.negate_prefix),
+        }
+    }
+
+    /// Parse `config` into a tree of lines nested by indentation.
+    pub fn parse(&self, config: &str) -> Vec<ConfigNode> {
+        let mut roots: Vec<ConfigNode> = Vec::new();
+        // Indentation of each currently open ancestor.
+        let mut open: Vec<usize> = Vec::new();
+
+        for raw in config.lines() {
+            let text = raw.trim();
+            if text.is_empty()
+                || self
+                    .comment_prefixes
+                    .iter()
+                    .any(|prefix| text.starts_with(prefix.as_str()))
+                || self.ignored_lines.iter().any(|line| line == text)
+            {
+                continue;
+            }
+            let indent = if self.exit_command.is_some() {
+                raw.len() - raw.trim_start().len()
+            } else {
+                0
+            };
+            while open.last().is_some_and(|&parent| parent >= indent) {
+                open.pop();
+            }
+            let mut siblings = &mut roots;
+            for _ in 0..open.len() {
+                siblings = &mut siblings
+                    .last_mut()
+                    .expect("open ancestors always exist")
+                    .children;
+            }
+            siblings.push(ConfigNode::new(text));
+            open.push(indent);
+        }
+        roots
+    }
+
+    /// Diff `running` against `intended`.
+    pub fn diff(&self, running: &str, intended: &str) -> ConfigDiff {
+        let mut diff = ConfigDiff::default();
+        diff_nodes(
+            &self.parse(running),
+            &self.parse(intended),
+            &mut Vec::new(),
+            &mut diff,
+        );
+        diff
+    }
+
+    /// Changes from an approved `baseline` to the `current` config, grouped
+    /// by the top-level section they fall under.
+    ///
+    /// Sections keep the order in which their first change appears.
+    pub fn drift_sections(&self, baseline: &str, current: &str) -> Vec<DriftSection> {
+        let diff = self.diff(baseline, current);
+        let mut sections: Vec<DriftSection> = Vec::new();
+        let changes = diff
+            .removed
+            .iter()
+            .map(|change| (change, true))
+            .chain(diff.added.iter().map(|change| (change, false)));
+        for (change, removed) in changes {
+            let name = change.parents.first().unwrap_or(&change.node.line);
+            let index = match sections.iter().position(|s| &s.section == name) {
+                Some(index) => index,
+                None => {
+                    sections.push(DriftSection {
+                        section: name.clone(),
+                        removed: Vec::new(),
+                        added: Vec::new(),
+                    });
+                    sections.len() - 1
+                }
+            };
+            let section = &mut sections[index];
+            let lines = if removed {
+                &mut section.removed
+            } else {
+                &mut section.added
+            };
+            outline(&change.node, change.parents.len(), lines);
+        }
+        sections
+    }
+
+    /// Commands entering `parents`, running `body`, then returning to the top level.
+    fn wrap(&self, parents: &[String], body: Vec<String>, extra_depth: usize) -> Vec<String> {
+        let mut commands = parents.to_vec();
+        commands.extend(body);
+        if let Some(exit) = self.exit_command.as_ref() {
+            commands.extend(std::iter::repeat_n(
+                exit.clone(),
+                parents.len() + extra_depth,
+            ));
+        }
+        commands
+    }
+
+    /// Lines recreating `node` and its children.
+    fn render(&self, node: &ConfigNode, out: &mut Vec<String>) {
+        out.push(node.line.clone());
+        for child in &node.children {
+            self.render(child, out);
+            if !child.children.is_empty()
+                && let Some(exit) = self.exit_command.as_ref()
+            {
+                out.push(exit.clone());
+            }
+        }
+    }
+
+    fn add_commands(&self, change: &ConfigChange) -> Vec<String> {
+        let mut body = Vec::new();
+        self.render(&change.node, &mut body);
+        let depth = usize::from(!change.node.children.is_empty());
+        self.wrap(&change.parents, body, depth)
+    }
+
+    fn remove_commands(&self, change: &ConfigChange) -> Vec<String> {
+        self.wrap(&change.parents, vec![self.negate(&change.node.line)], 0)
+    }
+
+    /// One operation turning `running` back into `intended`; `None` when
+    /// they already match.
+    pub(crate) fn restore_operation(
+        &self,
+        running: &str,
+        intended: &str,
+        timeout_secs: Option<u64>,
+    ) -> Option<SessionOperation> {
+        let diff = self.diff(running, intended);
+        if diff.is_empty() {
+            return None;
+        }
+        let removals = diff
+            .removed
+            .iter()
+            .flat_map(|change| self.remove_commands(change));
+        let additions = diff
+            .added
+            .iter()
+            .flat_map(|change| self.add_commands(change));
+        Some(step_operation(
+            &self.mode,
+            removals.chain(additions).collect(),
+            timeout_secs,
+        ))
+    }
+}
+
+/// One config line with the lines nested under it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigNode {
+    pub line: String,
+    pub children: Vec<ConfigNode>,
+}
+
+impl ConfigNode {
+    fn new(line: &str) -> Self {
+        Self {
+            line: line.to_string(),
+            children: Vec::new(),
+        }
+    }
+}
+
+/// A line (with its subtree) to add or remove under `parents`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigChange {
+    /// Enclosing block lines, outermost first.
+    pub parents: Vec<String>,
+    pub node: ConfigNode,
+}
+
+/// Lines missing from the running config and lines it should not have.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConfigDiff {
+    pub removed: Vec<ConfigChange>,
+    pub added: Vec<ConfigChange>,
+}
+
+impl ConfigDiff {
+    pub fn is_empty(&self) -> bool {
+        self.removed.is_empty() && self.added.is_empty()
+    }
+}
+
+/// Lines of one top-level config section that changed since its baseline.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct DriftSection {
+    /// Top-level line of the section, e.g. `interface Gi0/1`.
+    pub section: String,
+    /// Baseline lines no longer present, indented one space per level.
+    pub removed: Vec<String>,
+    /// Lines not in the baseline, indented one space per level.
+    pub added: Vec<String>,
+}
+
+/// `node` and its subtree, indented one space per nesting level.
+fn outline(node: &ConfigNode, depth: usize, out: &mut Vec<String>) {
+    out.push(format!("{}{}", " ".repeat(depth), node.line));
+    for child in &node.children {
+        outline(child, depth + 1, out);
+    }
+}
+
+fn diff_nodes(
+    running: &[ConfigNode],
+    intended: &[ConfigNode],
+    parents: &mut Vec<String>,
+    diff: &mut ConfigDiff,
+) {
+    for node in running {
+        if !intended.iter().any(|wanted| wanted.line == node.line) {
+            diff.removed.push(ConfigChange {
+                parents: parents.clone(),
+                node: node.clone(),
+            });
+        }
+    }
+    for wanted in intended {
+        match running.iter().find(|node| node.line == wanted.line) {
+            Some(node) => {
+                parents.push(wanted.line.clone());
+                diff_nodes(&node.children, &wanted.children, parents, diff);
+                parents.pop();
+            }
+            None => diff.added.push(ConfigChange {
+                parents: parents.clone(),
+                node: wanted.clone(),
+            }),
+        }
+    }
+}
+
+fn step_operation(
+    mode: &str,
+    commands: Vec<String>,
+    timeout_secs: Option<u64>,
+) -> SessionOperation {
+    let mut commands: Vec<Command> = commands
+        .into_iter()
+        .map(|cmd| Command {
+            timeout: timeout_secs,
+            ..Command::new(mode, cmd)
+        })
+        .collect();
+    if commands.len() == 1 {
+        commands.remove(0).into()
+    } else {
+        CommandFlow::new(commands).into()
+    }
+}
+
+/// Build a remediation block turning `running` into `intended`.
+///
+/// Removals come first, then additions in intended order. Each step carries
+/// its inverse, so the block uses [`RollbackPolicy::PerStep`]. The block has
+/// no steps when the configs already match.
+pub fn plan_remediation(
+    grammar: &ConfigGrammar,
+    block_name: &str,
+    running: &str,
+    intended: &str,
+    timeout_secs: Option<u64>,
+) -> TxBlock {
+    let diff = grammar.diff(running, intended);
+    let step = |forward: Vec<String>, rollback: Vec<String>| {
+        TxStep::new(step_operation(&grammar.mode, forward, timeout_secs))
+            .with_rollback(step_operation(&grammar.mode, rollback, timeout_secs))
+    };
+
+    let removals = diff.removed.iter().map(|change| {
+        step(
+            grammar.remove_commands(change),
+            grammar.add_commands(change),
+        )
+    });
+    let additions = diff.added.iter().map(|change| {
+        step(
+            grammar.add_commands(change),
+            grammar.remove_commands(change),
+        )
+    });
+
+    TxBlock {
+        name: block_name.to_string(),
+        kind: CommandBlockKind::Config,
+        rollback_policy: RollbackPolicy::PerStep,
+        steps: removals.chain(additions).collect(),
+        fail_fast: true,
+    }
+}
+
+impl SshConnectionManager {
+    /// Fetch the running config with `fetch` and plan a block reaching `intended`.
+    pub async fn plan_remediation_with_context(
+        &self,
+        request: ConnectionRequest,
+        fetch: Command,
+        context: ExecutionContext,
+        grammar: &ConfigGrammar,
+        intended: &str,
+    ) -> Result<TxBlock, ConnectError> {
+        let output = self
+            .execute_command_with_context(request, fetch, context)
+            .await?;
+        let running = crate::parsers::output_text(output)?;
+        Ok(plan_remediation(
+            grammar,
+            "remediation",
+            &running,
+            intended,
+            None,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RUNNING: &str = "!\n\
+hostname r1\n\
+ip http server\n\
+interface Gi0/1\n \
+description old\n \
+shutdown\n\
+!\n\
+end\n";
+
+    const INTENDED: &str = "hostname r1\n\
+interface Gi0/1\n \
+description uplink\n \
+shutdown\n\
+router ospf 1\n \
+network 10.0.0.0 0.0.0.255 area 0\n";
+
+    fn commands(operation: &SessionOperation) -> Vec<String> {
+        match operation {
+            SessionOperation::Command(command) => vec![command.command.clone()],
+            SessionOperation::Flow(flow) => flow.steps.iter().map(|c| c.command.clone()).collect(),
+            other => panic!("unexpected operation {other:?}"),
+        }
+    }
+
+    #[test]
+    fn drift_sections_group_changes_by_top_level_block() {
+        let grammar = ConfigGrammar::for_template("cisco").expect("grammar");
+        assert!(grammar.drift_sections(RUNNING, RUNNING).is_empty());
+        let sections = grammar.drift_sections(RUNNING, INTENDED);
+        assert_eq!(
+            sections,
+            [
+                DriftSection {
+                    section: "ip http server".to_string(),
+                    removed: vec!["ip http server".to_string()],
+                    added: Vec::new(),
+                },
+                DriftSection {
+                    section: "interface Gi0/1".to_string(),
+                    removed: vec![" description old".to_string()],
+                    added: vec![" description uplink".to_string()],
+                },
+                DriftSection {
+                    section: "router ospf 1".to_string(),
+                    removed: Vec::new(),
+                    added: vec![
+                        "router ospf 1".to_string(),
+                        " network 10.0.0.0 0.0.0.255 area 0".to_string(),
+                    ],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn restore_operation_undoes_changes_in_one_flow() {
+        let grammar = ConfigGrammar::for_template("cisco").expect("grammar");
+        assert!(grammar.restore_operation(RUNNING, RUNNING, None).is_none());
+        let operation = grammar
+            .restore_operation(INTENDED, RUNNING, Some(5))
+            .expect("operation");
+        assert_eq!(
+            commands(&operation),
+            [
+                "no router ospf 1",
+                "interface Gi0/1",
+                "no description uplink",
+                "exit",
+                "ip http server",
+                "interface Gi0/1",
+                "description old",
+                "exit",
+            ]
+        );
+    }
+
+    #[test]
+    fn cisco_plan_removes_then_adds_with_nesting_and_inverse_rollback() {
+        let grammar = ConfigGrammar::for_template("cisco").expect("grammar");
+        let block = plan_remediation(&grammar, "push", RUNNING, INTENDED, Some(10));
+
+        let forward: Vec<_> = block.steps.iter().map(|s| commands(&s.run)).collect();
+        assert_eq!(
+            forward,
+            vec![
+                vec!["no ip http server".to_string()],
+                vec![
+                    "interface Gi0/1".to_string(),
+                    "no description old".to_string(),
+                    "exit".to_string(),
+                ],
+                vec![
+                    "interface Gi0/1".to_string(),
+                    "description uplink".to_string(),
+                    "exit".to_string(),
+                ],
+                vec![
+                    "router ospf 1".to_string(),
+                    "network 10.0.0.0 0.0.0.255 area 0".to_string(),
+                    "exit".to_string(),
+                ],
+            ]
+        );
+        assert_eq!(
+            commands(block.steps[0].rollback.as_ref().expect("rollback")),
+            ["ip http server"]
+        );
+        assert_eq!(
+            commands(block.steps[3].rollback.as_ref().expect("rollback")),
+            ["no router ospf 1"]
+        );
+        assert!(matches!(block.rollback_policy, RollbackPolicy::PerStep));
+    }
+
+    #[test]
+    fn grammars_negate_per_vendor() {
+        let huawei = ConfigGrammar::for_template("huawei").expect("huawei");
+        let juniper = ConfigGrammar::for_template("juniper").expect("juniper");
+        let cisco = ConfigGrammar::for_template("cisco").expect("cisco");
+
+        assert_eq!(huawei.negate("sysname r1"), "undo sysname r1");
+        assert_eq!(
+            juniper.negate("set system host-name r1"),
+            "delete system host-name r1"
+        );
+        assert_eq!(cisco.negate("no shutdown"), "shutdown");
+        assert!(ConfigGrammar::for_template("linux").is_err());
+    }
+
+    #[test]
+    fn matching_configs_produce_empty_plan() {
+        let grammar = ConfigGrammar::for_template("huawei").expect("grammar");
+        let config = "#\nsysname r1\n#\ninterface GE0/0/1\n description uplink\n#\nreturn\n";
+
+        assert!(grammar.diff(config, config).is_empty());
+        assert!(
+            plan_remediation(&grammar, "noop", config, config, None)
+                .steps
+                .is_empty()
+        );
+    }
+}
@@ -0,0 +1,145 @@
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::error::ConnectError;
+use crate::session::Command;
+
+use super::catalog::template_metadata;
+
+/// Redundancy role of one HA pair member.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum HaRole {
+    Active,
+    Standby,
+    /// The probe output matched neither role.
+    Unknown,
+}
+
+/// Command reporting a device's HA role and the patterns reading it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct HaRoleProbe {
+    /// Mode the probe command runs in.
+    pub mode: String,
+    pub command: String,
+    /// Regexes meaning this device is the active member.
+    pub active: Vec<String>,
+    /// Regexes meaning this device is the standby member.
+    pub standby: Vec<String>,
+}
+
+impl HaRoleProbe {
+    /// Probe running `command` in `mode`, without patterns yet.
+    pub fn new(mode: impl Into<String>, command: impl Into<String>) -> Self {
+        Self {
+            mode: mode.into(),
+            command: command.into(),
+            active: Vec::new(),
+            standby: Vec::new(),
+        }
+    }
+
+    /// Add a regex meaning the device is active.
+    pub fn with_active(mut self, pattern: impl Into<String>) -> Self {
+        self.active.push(pattern.into());
+        self
+    }
+
+    /// Add a regex meaning the device is standby.
+    pub fn with_standby(mut self, pattern: impl Into<String>) -> Self {
+        self.standby.push(pattern.into());
+        self
+    }
+
+    /// Probe for a built-in template.
+    ///
+    /// Templates without a known HA status command return an error; build
+    /// one with [`HaRoleProbe::new`] instead.
+    pub fn for_template(template: &str) -> Result<Self, ConnectError> {
+        let template_key = template.to_ascii_lowercase();
+        let _ = template_metadata(&template_key)?;
+
+        let probe = match template_key.as_str() {
+            "cisco" => Self::new("Enable", "show redundancy state")
+                .with_active(r"(?im)^\s*my state\s*=\s*\d+\s*-\s*active")
+                .with_standby(r"(?im)^\s*my state\s*=\s*\d+\s*-\s*standby"),
+            "huawei" => Self::new("Enable", "display hrp state")
+                .with_active(r"(?im)^\s*role:\s*active")
+                .with_standby(r"(?im)^\s*role:\s*standby"),
+            "fortinet" => Self::new("Enable", "get system ha status")
+                .with_active(r"(?im)^current ha mode:.*\b(primary|master)\b")
+                .with_standby(r"(?im)^current ha mode:.*\b(secondary|backup|slave)\b"),
+            // The local member is listed before its peer.
+            "paloalto" => Self::new("Enable", "show high-availability state")
+                .with_active(r"(?im)^\s*state:\s*active\b")
+                .with_standby(r"(?im)^\s*state:\s*passive\b"),
+            "checkpoint" => Self::new("Enable", "cphaprob state")
+                .with_active(r"(?im)\(local\).*\bactive\b")
+                .with_standby(r"(?im)\(local\).*\bstandby\b"),
+            other => {
+                return Err(ConnectError::InvalidRequest(format!(
+                    "template '{other}' has no HA role probe"
+                )));
+            }
+        };
+        Ok(probe)
+    }
+
+    /// The probe as a command.
+    pub fn command(&self) -> Command {
+        Command::new(&self.mode, &self.command)
+    }
+
+    /// Role reported by `output`; when both roles match, the earlier match
+    /// wins, since devices list themselves before their peer.
+    pub fn classify(&self, output: &str) -> Result<HaRole, ConnectError> {
+        let first_match = |patterns: &[String]| -> Result<Option<usize>, ConnectError> {
+            let mut first = None;
+            for pattern in patterns {
+                let re = Regex::new(pattern).map_err(|err| {
+                    ConnectError::InvalidRequest(format!("invalid HA role pattern: {err}"))
+                })?;
+                if let Some(found) = re.find(output) {
+                    first =
+                        Some(first.map_or(found.start(), |start: usize| start.min(found.start())));
+                }
+            }
+            Ok(first)
+        };
+        let role = match (first_match(&self.active)?, first_match(&self.standby)?) {
+            (Some(active), Some(standby)) if active < standby => HaRole::Active,
+            (Some(_), Some(_)) => HaRole::Standby,
+            (Some(_), None) => HaRole::Active,
+            (None, Some(_)) => HaRole::Standby,
+            (None, None) => HaRole::Unknown,
+        };
+        Ok(role)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtin_probes_read_the_local_role() {
+        let cisco = HaRoleProbe::for_template("cisco").expect("probe");
+        let output = "       my state = 13 -ACTIVE \n     peer state = 8  -STANDBY HOT \n";
+        assert_eq!(cisco.classify(output).expect("role"), HaRole::Active);
+
+        let paloalto = HaRoleProbe::for_template("paloalto").expect("probe");
+        let output = "Local Information:\n    State: passive (last 2 days)\n\
+                      Peer Information:\n    State: active (last 2 days)\n";
+        assert_eq!(paloalto.classify(output).expect("role"), HaRole::Standby);
+
+        let fortinet = HaRoleProbe::for_template("fortinet").expect("probe");
+        assert_eq!(
+            fortinet
+                .classify("Model: FortiGate-VM64\nMode: HA A-P\n")
+                .expect("role"),
+            HaRole::Unknown
+        );
+        assert!(HaRoleProbe::for_template("topsec").is_err());
+    }
+}
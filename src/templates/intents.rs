@@ -0,0 +1,547 @@
+use std::net::Ipv4Addr;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::error::ConnectError;
+use crate::session::{Command, CommandFlow, RollbackPolicy, TxBlock};
+
+use super::catalog::template_metadata;
+use super::transaction::build_tx_block;
+
+/// Forward and rollback commands generated from an intent.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct IntentCommands {
+    /// Mode the commands run in (`Config`, or `Enable` for templates without one).
+    pub mode: String,
+    pub forward: Vec<String>,
+    /// Commands removing exactly what `forward` created.
+    pub rollback: Vec<String>,
+}
+
+/// A VLAN on a switch.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct VlanIntent {
+    pub id: u16,
+    #[serde(default)]
+    pub name: Option<String>,
+}
+
+/// Whether an ACL rule lets traffic through.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AclAction {
+    Permit,
+    Deny,
+}
+
+/// One rule of a named IPv4 ACL (firewall filter term on Junos).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct AclRuleIntent {
+    pub acl: String,
+    /// Rule sequence number, also used to remove the rule again.
+    pub sequence: u32,
+    pub action: AclAction,
+    /// `ip`, `tcp`, `udp` or `icmp`.
+    pub protocol: String,
+    /// `any`, a host address or a CIDR prefix.
+    pub source: String,
+    /// `any`, a host address or a CIDR prefix.
+    pub destination: String,
+    /// Destination port for `tcp`/`udp` rules.
+    #[serde(default)]
+    pub destination_port: Option<u16>,
+}
+
+/// A named firewall address object.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct AddressObjectIntent {
+    pub name: String,
+    /// Host address or CIDR prefix.
+    pub address: String,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+/// Any supported intent.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Intent {
+    Vlan(VlanIntent),
+    AclRule(AclRuleIntent),
+    AddressObject(AddressObjectIntent),
+}
+
+fn unsupported(intent: &str, template: &str) -> ConnectError {
+    ConnectError::InvalidTransaction(format!(
+        "{intent} intent is not supported for template '{template}'"
+    ))
+}
+
+fn template_key(template: &str) -> Result<String, ConnectError> {
+    let template_key = template.to_ascii_lowercase();
+    let _ = template_metadata(&template_key)?;
+    Ok(template_key)
+}
+
+fn config_mode(template_key: &str) -> String {
+    match template_key {
+        "fortinet" | "checkpoint" | "topsec" => "Enable",
+        _ => "Config",
+    }
+    .to_string()
+}
+
+/// IPv4 prefix parsed from `any`, `a.b.c.d` or `a.b.c.d/len`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Prefix {
+    addr: Ipv4Addr,
+    len: u8,
+}
+
+impl Prefix {
+    fn parse(text: &str) -> Result<Option<Self>, ConnectError> {
+        let text = text.trim();
+        if text.eq_ignore_ascii_case("any") {
+            return Ok(None);
+        }
+        let invalid = || ConnectError::InvalidRequest(format!("invalid IPv4 address '{text}'"));
+        let (addr, len) = match text.split_once('/') {
+            Some((addr, len)) => (addr, len.parse().map_err(|_| invalid())?),
+            None => (text, 32),
+        };
+        let addr: Ipv4Addr = addr.parse().map_err(|_| invalid())?;
+        if len > 32 {
+            return Err(invalid());
+        }
+        Ok(Some(Self { addr, len }))
+    }
+
+    fn mask(self) -> Ipv4Addr {
+        Ipv4Addr::from(u32::MAX.checked_shl(32 - u32::from(self.len)).unwrap_or(0))
+    }
+
+    fn wildcard(self) -> Ipv4Addr {
+        Ipv4Addr::from(!u32::from(self.mask()))
+    }
+
+    fn cidr(self) -> String {
+        format!("{}/{}", self.addr, self.len)
+    }
+
+    /// Cisco ACL operand: `host A` or `A WILDCARD`.
+    fn cisco(prefix: Option<Self>) -> String {
+        match prefix {
+            None => "any".to_string(),
+            Some(p) if p.len == 32 => format!("host {}", p.addr),
+            Some(p) => format!("{} {}", p.addr, p.wildcard()),
+        }
+    }
+}
+
+impl VlanIntent {
+    pub fn new(id: u16) -> Self {
+        Self { id, name: None }
+    }
+
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Commands creating and removing this VLAN on `template`.
+    pub fn commands(&self, template: &str) -> Result<IntentCommands, ConnectError> {
+        if !(1..=4094).contains(&self.id) {
+            return Err(ConnectError::InvalidRequest(format!(
+                "VLAN ID {} is outside 1-4094",
+                self.id
+            )));
+        }
+        let key = template_key(template)?;
+        let id = self.id;
+        let (forward, rollback) = match key.as_str() {
+            "cisco" | "arista" | "maipu" | "venustech" => {
+                let mut forward = vec![format!("vlan {id}")];
+                forward.extend(self.name.iter().map(|name| format!("name {name}")));
+                forward.push("exit".to_string());
+                (forward, vec![format!("no vlan {id}")])
+            }
+            "huawei" | "h3c" => {
+                let mut forward = vec![format!("vlan {id}")];
+                forward.extend(self.name.iter().map(|name| format!("name {name}")));
+                forward.push("quit".to_string());
+                (forward, vec![format!("undo vlan {id}")])
+            }
+            "juniper" => {
+                let name = self.name.clone().unwrap_or_else(|| format!("vlan{id}"));
+                (
+                    vec![format!("set vlans {name} vlan-id {id}")],
+                    vec![format!("delete vlans {name}")],
+                )
+            }
+            _ => return Err(unsupported("VLAN", template)),
+        };
+        Ok(IntentCommands {
+            mode: config_mode(&key),
+            forward,
+            rollback,
+        })
+    }
+}
+
+impl AclRuleIntent {
+    /// Commands adding and removing this rule on `template`.
+    pub fn commands(&self, template: &str) -> Result<IntentCommands, ConnectError> {
+        let key = template_key(template)?;
+        let protocol = self.protocol.to_ascii_lowercase();
+        if !matches!(protocol.as_str(), "ip" | "tcp" | "udp" | "icmp") {
+            return Err(ConnectError::InvalidRequest(format!(
+                "unsupported ACL protocol '{}'",
+                self.protocol
+            )));
+        }
+        if self.destination_port.is_some() && !matches!(protocol.as_str(), "tcp" | "udp") {
+            return Err(ConnectError::InvalidRequest(
+                "destination_port requires protocol tcp or udp".to_string(),
+            ));
+        }
+        let source = Prefix::parse(&self.source)?;
+        let destination = Prefix::parse(&self.destination)?;
+        let (acl, seq) = (&self.acl, self.sequence);
+        let permit = self.action == AclAction::Permit;
+
+        let (forward, rollback) = match key.as_str() {
+            "cisco" | "arista" | "maipu" | "venustech" => {
+                let action = if permit { "permit" } else { "deny" };
+                let port = self
+                    .destination_port
+                    .map(|port| format!(" eq {port}"))
+                    .unwrap_or_default();
+                (
+                    vec![
+                        format!("ip access-list extended {acl}"),
+                        format!(
+                            "{seq} {action} {protocol} {} {}{port}",
+                            Prefix::cisco(source),
+                            Prefix::cisco(destination)
+                        ),
+                        "exit".to_string(),
+                    ],
+                    vec![
+                        format!("ip access-list extended {acl}"),
+                        format!("no {seq}"),
+                        "exit".to_string(),
+                    ],
+                )
+            }
+            "huawei" | "h3c" => {
+                let enter = if key == "huawei" {
+                    format!("acl name {acl} advance")
+                } else {
+                    format!("acl advanced name {acl}")
+                };
+                let action = if permit { "permit" } else { "deny" };
+                let operand = |keyword: &str, prefix: Option<Prefix>| match prefix {
+                    None => String::new(),
+                    Some(p) => format!(" {keyword} {} {}", p.addr, p.wildcard()),
+                };
+                let port = self
+                    .destination_port
+                    .map(|port| format!(" destination-port eq {port}"))
+                    .unwrap_or_default();
+                (
+                    vec![
+                        enter.clone(),
+                        format!(
+                            "rule {seq} {action} {protocol}{}{}{port}",
+                            operand("source", source),
+                            operand("destination", destination)
+                        ),
+                        "quit".to_string(),
+                    ],
+                    vec![enter, format!("undo rule {seq}"), "quit".to_string()],
+                )
+            }
+            "juniper" => {
+                let term = format!("set firewall family inet filter {acl} term {seq}");
+                let mut forward = Vec::new();
+                if let Some(p) = source {
+                    forward.push(format!("{term} from source-address {}", p.cidr()));
+                }
+                if let Some(p) = destination {
+                    forward.push(format!("{term} from destination-address {}", p.cidr()));
+                }
+                if protocol != "ip" {
+                    forward.push(format!("{term} from protocol {protocol}"));
+                }
+                if let Some(port) = self.destination_port {
+                    forward.push(format!("{term} from destination-port {port}"));
+                }
+                let action = if permit { "accept" } else { "discard" };
+                forward.push(format!("{term} then {action}"));
+                (
+                    forward,
+                    vec![format!(
+                        "delete firewall family inet filter {acl} term {seq}"
+                    )],
+                )
+            }
+            _ => return Err(unsupported("ACL rule", template)),
+        };
+        Ok(IntentCommands {
+            mode: config_mode(&key),
+            forward,
+            rollback,
+        })
+    }
+}
+
+impl AddressObjectIntent {
+    pub fn new(name: impl Into<String>, address: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            address: address.into(),
+            description: None,
+        }
+    }
+
+    pub fn with_description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    /// Commands creating and removing this address object on `template`.
+    pub fn commands(&self, template: &str) -> Result<IntentCommands, ConnectError> {
+        let key = template_key(template)?;
+        let prefix = Prefix::parse(&self.address)?.ok_or_else(|| {
+            ConnectError::InvalidRequest("address objects need a concrete address".to_string())
+        })?;
+        let name = &self.name;
+        let description = self.description.as_deref();
+
+        let (forward, rollback) = match key.as_str() {
+            "cisco" => {
+                let mut forward = vec![format!("object network {name}")];
+                forward.push(if prefix.len == 32 {
+                    format!("host {}", prefix.addr)
+                } else {
+                    format!("subnet {} {}", prefix.addr, prefix.mask())
+                });
+                forward.extend(description.map(|text| format!("description {text}")));
+                forward.push("exit".to_string());
+                (forward, vec![format!("no object network {name}")])
+            }
+            "hillstone" => {
+                let mut forward = vec![format!("address {name}")];
+                forward.push(format!("ip {}", prefix.cidr()));
+                forward.extend(description.map(|text| format!("description \"{text}\"")));
+                forward.push("exit".to_string());
+                (forward, vec![format!("no address {name}")])
+            }
+            "huawei" => {
+                let mut forward = vec![format!("ip address-set {name} type object")];
+                forward.extend(description.map(|text| format!("description {text}")));
+                forward.push(format!("address 0 {} mask {}", prefix.addr, prefix.len));
+                forward.push("quit".to_string());
+                (forward, vec![format!("undo ip address-set {name}")])
+            }
+            "fortinet" => {
+                let mut forward = vec![
+                    "config firewall address".to_string(),
+                    format!("edit \"{name}\""),
+                    format!("set subnet {} {}", prefix.addr, prefix.mask()),
+                ];
+                forward.extend(description.map(|text| format!("set comment \"{text}\"")));
+                forward.extend(["next".to_string(), "end".to_string()]);
+                (
+                    forward,
+                    vec![
+                        "config firewall address".to_string(),
+                        format!("delete \"{name}\""),
+                        "end".to_string(),
+                    ],
+                )
+            }
+            "paloalto" => {
+                let mut forward = vec![format!("set address {name} ip-netmask {}", prefix.cidr())];
+                forward.extend(
+                    description.map(|text| format!("set address {name} description \"{text}\"")),
+                );
+                (forward, vec![format!("delete address {name}")])
+            }
+            "juniper" => (
+                vec![format!(
+                    "set security address-book global address {name} {}",
+                    prefix.cidr()
+                )],
+                vec![format!(
+                    "delete security address-book global address {name}"
+                )],
+            ),
+            _ => return Err(unsupported("address object", template)),
+        };
+        Ok(IntentCommands {
+            mode: config_mode(&key),
+            forward,
+            rollback,
+        })
+    }
+}
+
+impl Intent {
+    /// Short name used for the generated block.
+    pub fn block_name(&self) -> String {
+        match self {
+            Self::Vlan(vlan) => format!("vlan-{}", vlan.id),
+            Self::AclRule(rule) => format!("acl-{}-{}", rule.acl, rule.sequence),
+            Self::AddressObject(object) => format!("address-{}", object.name),
+        }
+    }
+
+    /// Forward and rollback commands for `template`.
+    pub fn commands(&self, template: &str) -> Result<IntentCommands, ConnectError> {
+        match self {
+            Self::Vlan(vlan) => vlan.commands(template),
+            Self::AclRule(rule) => rule.commands(template),
+            Self::AddressObject(object) => object.commands(template),
+        }
+    }
+
+    /// Build a config [`TxBlock`] through [`build_tx_block`].
+    ///
+    /// Multi-command rollbacks become a whole-resource command flow.
+    pub fn to_tx_block(
+        &self,
+        template: &str,
+        timeout_secs: Option<u64>,
+    ) -> Result<TxBlock, ConnectError> {
+        let commands = self.commands(template)?;
+        let mut block = build_tx_block(
+            template,
+            &self.block_name(),
+            &commands.mode,
+            &commands.forward,
+            timeout_secs,
+            commands.rollback.first().cloned(),
+        )?;
+        if commands.rollback.len() > 1
+            && let RollbackPolicy::WholeResource { rollback, .. } = &mut block.rollback_policy
+        {
+            let steps = commands
+                .rollback
+                .iter()
+                .map(|command| Command {
+                    timeout: timeout_secs,
+                    ..Command::new(commands.mode.clone(), command.clone())
+                })
+                .collect();
+            **rollback = CommandFlow::new(steps).into();
+        }
+        Ok(block)
+    }
+}
+
+impl From<VlanIntent> for Intent {
+    fn from(intent: VlanIntent) -> Self {
+        Self::Vlan(intent)
+    }
+}
+
+impl From<AclRuleIntent> for Intent {
+    fn from(intent: AclRuleIntent) -> Self {
+        Self::AclRule(intent)
+    }
+}
+
+impl From<AddressObjectIntent> for Intent {
+    fn from(intent: AddressObjectIntent) -> Self {
+        Self::AddressObject(intent)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session::SessionOperation;
+
+    #[test]
+    fn vlan_intent_generates_vendor_commands() {
+        let vlan = VlanIntent::new(120).with_name("users");
+
+        assert_eq!(
+            vlan.commands("cisco").expect("cisco").forward,
+            ["vlan 120", "name users", "exit"]
+        );
+        assert_eq!(
+            vlan.commands("huawei").expect("huawei").rollback,
+            ["undo vlan 120"]
+        );
+        assert_eq!(
+            vlan.commands("juniper").expect("juniper").forward,
+            ["set vlans users vlan-id 120"]
+        );
+        assert!(vlan.commands("fortinet").is_err());
+        assert!(VlanIntent::new(0).commands("cisco").is_err());
+    }
+
+    #[test]
+    fn acl_rule_intent_formats_wildcards_and_multi_line_rollback() {
+        let rule = Intent::AclRule(AclRuleIntent {
+            acl: "WEB-IN".to_string(),
+            sequence: 10,
+            action: AclAction::Permit,
+            protocol: "tcp".to_string(),
+            source: "10.0.0.0/24".to_string(),
+            destination: "192.0.2.10".to_string(),
+            destination_port: Some(443),
+        });
+
+        let cisco = rule.commands("cisco").expect("cisco");
+        assert_eq!(
+            cisco.forward[1],
+            "10 permit tcp 10.0.0.0 0.0.0.255 host 192.0.2.10 eq 443"
+        );
+        let huawei = rule.commands("huawei").expect("huawei");
+        assert_eq!(
+            huawei.forward[1],
+            "rule 10 permit tcp source 10.0.0.0 0.0.0.255 destination 192.0.2.10 0.0.0.0 destination-port eq 443"
+        );
+
+        let block = rule.to_tx_block("cisco", Some(30)).expect("block");
+        assert_eq!(block.name, "acl-WEB-IN-10");
+        let RollbackPolicy::WholeResource { rollback, .. } = &block.rollback_policy else {
+            panic!("expected whole-resource rollback");
+        };
+        let SessionOperation::Flow(flow) = rollback.as_ref() else {
+            panic!("expected rollback flow");
+        };
+        assert_eq!(flow.steps[1].command, "no 10");
+    }
+
+    #[test]
+    fn address_object_intent_uses_enable_mode_on_fortinet() {
+        let object = AddressObjectIntent::new("WEB01", "192.0.2.10").with_description("web");
+
+        let fortinet = object.commands("fortinet").expect("fortinet");
+        assert_eq!(fortinet.mode, "Enable");
+        assert!(
+            fortinet
+                .forward
+                .contains(&"set subnet 192.0.2.10 255.255.255.255".to_string())
+        );
+
+        let block = Intent::from(object.clone())
+            .to_tx_block("paloalto", None)
+            .expect("block");
+        assert!(matches!(
+            block.rollback_policy,
+            RollbackPolicy::WholeResource { .. }
+        ));
+        assert!(object.commands("array").is_err());
+        assert!(
+            AddressObjectIntent::new("X", "any")
+                .commands("cisco")
+                .is_err()
+        );
+    }
+}
@@ -20,6 +20,10 @@ pub struct LinuxTemplateConfig {
     pub custom_prompts: Option<CustomPrompts>,
     /// Shell flavor used for exit-status capture wrappers.
     pub shell_flavor: DeviceShellFlavor,
+    /// Run commands on SSH exec channels instead of the interactive shell.
+    ///
+    /// Commands then run as the login user; `Root` mode transitions are skipped.
+    pub exec_channel: bool,
 }
 
 impl Default for LinuxTemplateConfig {
@@ -29,6 +33,7 @@ impl Default for LinuxTemplateConfig {
             sudo_password: None,
             custom_prompts: None,
             shell_flavor: DeviceShellFlavor::Posix,
+            exec_channel: false,
         }
     }
 }
@@ -176,6 +181,7 @@ pub fn linux_handler_config(config: LinuxTemplateConfig) -> DeviceHandlerConfig
     }
 
     DeviceHandlerConfig {
+        name: Some("linux".to_string()),
         prompt: vec![
             prompt_rule("Root", &root_prompts),
             prompt_rule("User", &user_prompts),
@@ -217,10 +223,26 @@ pub fn linux_handler_config(config: LinuxTemplateConfig) -> DeviceHandlerConfig
         edges,
         ignore_errors: Vec::new(),
         dyn_param,
-        command_execution: DeviceCommandExecutionConfig::ShellExitStatus {
-            marker: LINUX_EXIT_CODE_MARKER.to_string(),
-            shell_flavor: config.shell_flavor,
+        command_execution: if config.exec_channel {
+            DeviceCommandExecutionConfig::ExecChannel
+        } else {
+            DeviceCommandExecutionConfig::ShellExitStatus {
+                marker: LINUX_EXIT_CODE_MARKER.to_string(),
+                shell_flavor: config.shell_flavor,
+            }
         },
+        terminal_emulation: false,
+        login_chain: Vec::new(),
+        terminal_width: None,
+        terminal_setup: Vec::new(),
+        logout_command: None,
+        pacing: None,
+        config_lock_regex: Vec::new(),
+        syslog_regex: Vec::new(),
+        comment_prefixes: vec!["#".to_string()],
+        command_stems: HashMap::new(),
+        unmodeled_prompt: None,
+        volatile_regex: Vec::new(),
     }
 }
 
@@ -500,4 +522,20 @@ mod tests {
         assert!(wrapped.contains(LINUX_EXIT_CODE_MARKER));
         assert!(wrapped.contains("\"$status\""));
     }
+
+    #[test]
+    fn linux_template_can_run_commands_on_exec_channels() {
+        let handler = linux_with_config(LinuxTemplateConfig {
+            exec_channel: true,
+            ..LinuxTemplateConfig::default()
+        })
+        .expect("create exec-channel linux template");
+
+        assert!(handler.uses_exec_channel());
+        assert!(!linux().expect("create linux template").uses_exec_channel());
+        assert_eq!(
+            handler.prepare_command_for_execution("uptime", true),
+            "uptime"
+        );
+    }
 }
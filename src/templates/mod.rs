@@ -3,23 +3,38 @@
 //! Concrete vendor implementations live in submodules. This root module keeps
 //! the public exports stable while the implementation is split by concern.
 
+mod candidate;
 mod catalog;
+mod change_script;
 mod command_flow_template;
+mod config_diff;
+mod ha;
+mod intents;
 mod linux;
 mod network;
 mod registry;
 mod transaction;
 mod transfer;
+mod unmatched;
 
+pub use candidate::{CandidateConfig, CandidateFailurePatterns};
 pub use catalog::{
     BUILTIN_TEMPLATES, TemplateCapability, TemplateMetadata, available_templates, template_catalog,
     template_metadata,
 };
+pub use change_script::{ChangeScript, ScriptLine, ScriptLineKind, parse_change_script};
 pub use command_flow_template::{
     CommandFlowTemplate, CommandFlowTemplatePrompt, CommandFlowTemplateRuntime,
     CommandFlowTemplateStep, CommandFlowTemplateText, CommandFlowTemplateVar,
     CommandFlowTemplateVarKind,
 };
+pub use config_diff::{
+    ConfigChange, ConfigDiff, ConfigGrammar, ConfigNode, DriftSection, plan_remediation,
+};
+pub use ha::{HaRole, HaRoleProbe};
+pub use intents::{
+    AclAction, AclRuleIntent, AddressObjectIntent, Intent, IntentCommands, VlanIntent,
+};
 pub use linux::{
     CustomPrompts, LinuxCommandType, LinuxTemplateConfig, SudoMode, classify_linux_command, linux,
     linux_handler_config, linux_with_config,
@@ -34,5 +49,10 @@ pub use network::{
 pub use registry::{
     by_name, by_name_config, diagnose_all_templates_json, diagnose_template, diagnose_template_json,
 };
-pub use transaction::{build_tx_block, classify_command};
+pub use transaction::{build_tx_block, classify_command, is_read_only_command};
 pub use transfer::cisco_like_copy_template;
+pub(crate) use unmatched::record_unmatched_line;
+pub use unmatched::{
+    CUSTOM_TEMPLATE, TemplateUnmatchedLines, UnmatchedLine, UnmatchedLineKind,
+    clear_unmatched_lines, unmatched_lines_report, unmatched_lines_report_json,
+};
@@ -15,6 +15,7 @@ pub fn arista_config() -> DeviceHandlerConfig {
     )];
 
     DeviceHandlerConfig {
+        name: Some("arista".to_string()),
         prompt: vec![
             prompt_rule("Config", &[r"^\r{0,1}\S+\(\S+\)#\s*$"]),
             prompt_rule("Enable", &[r"^\r{0,1}[^\s#]+#\s*$"]),
@@ -41,6 +42,13 @@ pub fn arista_config() -> DeviceHandlerConfig {
             transition_rule("Enable", "exit", "Login", true, false),
         ],
         dyn_param: HashMap::new(),
+        terminal_setup: vec!["terminal width 32767".to_string()],
+        // `Oct 16 10:02:11 sw1 Ebra: %LINEPROTO-5-UPDOWN: ...`
+        syslog_regex: vec![
+            r"^[A-Z][a-z]{2}\s+\d{1,2}\s+\d{2}:\d{2}:\d{2}\s+\S+\s+\S+:\s+%[A-Z0-9_]+-\d-[A-Z0-9_]+:"
+                .to_string(),
+        ],
+        comment_prefixes: vec!["!".to_string()],
         ..Default::default()
     }
 }
@@ -10,6 +10,7 @@ use std::collections::HashMap;
 /// Exports the underlying handler configuration for Array Networks devices.
 pub fn array_config() -> DeviceHandlerConfig {
     DeviceHandlerConfig {
+        name: Some("array".to_string()),
         prompt: vec![
             prompt_rule("Login", &[r"^[^\s<]+>\s*$"]),
             prompt_rule("Enable", &[r"^[^\s#]+#\s*$"]),
@@ -62,6 +63,7 @@ pub fn array_config() -> DeviceHandlerConfig {
             transition_rule("VSiteEnable", "exit", "Enable", true, false),
         ],
         dyn_param: HashMap::new(),
+        comment_prefixes: vec!["!".to_string()],
         ..Default::default()
     }
 }
@@ -7,6 +7,7 @@ use std::collections::HashMap;
 /// Exports the underlying handler configuration for Check Point Security Gateway devices.
 pub fn checkpoint_config() -> DeviceHandlerConfig {
     DeviceHandlerConfig {
+        name: Some("checkpoint".to_string()),
         prompt: vec![prompt_rule("Enable", &[r"^\r{0,1}\S+\s*>\s*$"])],
         more_regex: vec![r"-- More --".to_string()],
         error_regex: vec![
@@ -14,6 +15,8 @@ pub fn checkpoint_config() -> DeviceHandlerConfig {
             r".+Invalid command:.+".to_string(),
         ],
         dyn_param: HashMap::new(),
+        config_lock_regex: vec![r"(?i)configuration lock present".to_string()],
+        comment_prefixes: vec!["#".to_string()],
         ..Default::default()
     }
 }
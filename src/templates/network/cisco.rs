@@ -1,9 +1,157 @@
 //! Cisco IOS/IOS-XE device template.
 
-use crate::device::{DeviceHandler, DeviceHandlerConfig, input_rule, prompt_rule, transition_rule};
+use crate::device::{
+    DeviceHandler, DeviceHandlerConfig, command_stems, input_rule, prompt_rule, transition_rule,
+};
 use crate::error::ConnectError;
 use std::collections::HashMap;
 
+/// First words of IOS commands in `Enable` mode.
+const ENABLE_STEMS: &[&str] = &[
+    "archive",
+    "clear",
+    "clock",
+    "configure",
+    "copy",
+    "debug",
+    "delete",
+    "dir",
+    "disable",
+    "enable",
+    "end",
+    "erase",
+    "exit",
+    "install",
+    "license",
+    "logout",
+    "mkdir",
+    "monitor",
+    "more",
+    "no",
+    "ping",
+    "reload",
+    "rename",
+    "send",
+    "show",
+    "ssh",
+    "telnet",
+    "terminal",
+    "test",
+    "traceroute",
+    "undebug",
+    "verify",
+    "write",
+];
+
+/// First words of IOS commands in `Config` mode, sub-modes included.
+const CONFIG_STEMS: &[&str] = &[
+    "aaa",
+    "access-class",
+    "access-list",
+    "address-family",
+    "alias",
+    "archive",
+    "area",
+    "authentication",
+    "auto-summary",
+    "bandwidth",
+    "banner",
+    "bfd",
+    "bgp",
+    "boot",
+    "cdp",
+    "channel-group",
+    "class",
+    "class-map",
+    "clock",
+    "crypto",
+    "default",
+    "default-information",
+    "deny",
+    "description",
+    "distance",
+    "do",
+    "dot1x",
+    "duplex",
+    "enable",
+    "encapsulation",
+    "end",
+    "errdisable",
+    "event",
+    "exec-timeout",
+    "exit",
+    "exit-address-family",
+    "flowcontrol",
+    "hostname",
+    "interface",
+    "ip",
+    "ipv6",
+    "keepalive",
+    "key",
+    "license",
+    "line",
+    "lldp",
+    "load-interval",
+    "log-adjacency-changes",
+    "logging",
+    "login",
+    "mac",
+    "match",
+    "maximum-paths",
+    "media-type",
+    "mls",
+    "monitor",
+    "mtu",
+    "name",
+    "neighbor",
+    "network",
+    "no",
+    "ntp",
+    "object-group",
+    "passive-interface",
+    "password",
+    "permit",
+    "police",
+    "policy-map",
+    "port-channel",
+    "power",
+    "priority",
+    "priority-queue",
+    "privilege",
+    "qos",
+    "radius-server",
+    "rd",
+    "redistribute",
+    "remark",
+    "route-map",
+    "route-target",
+    "router",
+    "router-id",
+    "service",
+    "service-policy",
+    "set",
+    "shape",
+    "shutdown",
+    "snmp-server",
+    "spanning-tree",
+    "speed",
+    "standby",
+    "storm-control",
+    "switchport",
+    "tacacs-server",
+    "timers",
+    "track",
+    "transport",
+    "tunnel",
+    "udld",
+    "username",
+    "version",
+    "vlan",
+    "vrf",
+    "vrrp",
+    "vtp",
+];
+
 /// Exports the underlying handler configuration for Cisco IOS/IOS-XE devices.
 pub fn cisco_config() -> DeviceHandlerConfig {
     let write = vec![input_rule(
@@ -15,6 +163,7 @@ pub fn cisco_config() -> DeviceHandlerConfig {
     )];
 
     DeviceHandlerConfig {
+        name: Some("cisco".to_string()),
         prompt: vec![
             prompt_rule("Config", &[r"^\S+\(\S+\)#\s*$"]),
             prompt_rule("Enable", &[r"^[^\s#]+#\s*$"]),
@@ -43,6 +192,29 @@ pub fn cisco_config() -> DeviceHandlerConfig {
             transition_rule("Enable", "exit", "Login", true, false),
         ],
         dyn_param: HashMap::new(),
+        terminal_setup: vec!["terminal width 512".to_string()],
+        config_lock_regex: vec![
+            r"(?i)configuration mode (?:is )?locked\b.*?\buser '(?P<holder>[^']+)'".to_string(),
+        ],
+        // `*Oct 16 10:02:11.123: %LINK-3-UPDOWN: ...`, optionally numbered,
+        // or bare `%LINK-3-UPDOWN: ...` without service timestamps.
+        syslog_regex: vec![
+            r"^(?:\d+:\s+)?[*.]?[A-Z][a-z]{2}\s+\d{1,2}\s+(?:\d{4}\s+)?\d{2}:\d{2}:\d{2}(?:\.\d+)?(?:\s+[A-Z]{3,4})?:\s+%[A-Z0-9_]+-\d-[A-Z0-9_]+:"
+                .to_string(),
+            r"^%[A-Z][A-Z0-9_]*-\d-[A-Z0-9_]+:".to_string(),
+        ],
+        comment_prefixes: vec!["!".to_string()],
+        command_stems: command_stems(&[("Enable", ENABLE_STEMS), ("Config", CONFIG_STEMS)]),
+        // `uptime is ...`, clocks, interface rates and packet counters.
+        // Clocks need their time zone, e.g. `*10:02:11.123 UTC`, so the
+        // groups of IPv6 addresses like `fe80::1:23:45` are left alone.
+        volatile_regex: vec![
+            r"(?i)\buptime is (.+)$".to_string(),
+            r"(?:^|[\s*.])(\d{1,2}:\d{2}:\d{2}(?:\.\d+)?) [A-Z]{2,5}\b".to_string(),
+            r"(?i)\b(?:input|output) rate (\d+) bits/sec, (\d+) packets/sec".to_string(),
+            r"(?i)^\s*(\d+) packets (?:input|output), (\d+) bytes".to_string(),
+            r"(?i)\blast input (\S+), output (\S+), output hang (\S+)".to_string(),
+        ],
         ..Default::default()
     }
 }
@@ -7,6 +7,7 @@ use std::collections::HashMap;
 /// Exports the underlying handler configuration for DPTech devices.
 pub fn dptech_config() -> DeviceHandlerConfig {
     DeviceHandlerConfig {
+        name: Some("dptech".to_string()),
         prompt: vec![
             prompt_rule("Config", &[r"^\r{0,1}\[.+\]\s*$"]),
             prompt_rule("Enable", &[r"^\r{0,1}<.+>\s*$"]),
@@ -28,6 +29,7 @@ pub fn dptech_config() -> DeviceHandlerConfig {
             transition_rule("Config", "end", "Enable", true, false),
         ],
         dyn_param: HashMap::new(),
+        comment_prefixes: vec!["#".to_string()],
         ..Default::default()
     }
 }
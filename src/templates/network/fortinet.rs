@@ -7,6 +7,7 @@ use std::collections::HashMap;
 /// Exports the underlying handler configuration for Fortinet FortiGate devices.
 pub fn fortinet_config() -> DeviceHandlerConfig {
     DeviceHandlerConfig {
+        name: Some("fortinet".to_string()),
         prompt: vec![prompt_rule("Enable", &[r"^\r{0,1}\S+\s*#\s*$"])],
         prompt_with_sys: vec![prompt_with_sys_rule(
             "VDOMEnable",
@@ -19,6 +20,7 @@ pub fn fortinet_config() -> DeviceHandlerConfig {
             r"Command fail.*".to_string(),
         ],
         dyn_param: HashMap::new(),
+        comment_prefixes: vec!["#".to_string()],
         ..Default::default()
     }
 }
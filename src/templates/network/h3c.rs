@@ -7,6 +7,7 @@ use std::collections::HashMap;
 /// Exports the underlying handler configuration for H3C devices.
 pub fn h3c_config() -> DeviceHandlerConfig {
     DeviceHandlerConfig {
+        name: Some("h3c".to_string()),
         prompt: vec![
             prompt_rule("Config", &[r"^(RBM_P|RBM_S)?\[.+\]\s*$"]),
             prompt_rule("Enable", &[r"^(RBM_P|RBM_S)?<.+>\s*$"]),
@@ -24,6 +25,17 @@ pub fn h3c_config() -> DeviceHandlerConfig {
             transition_rule("Config", "exit", "Enable", true, false),
         ],
         dyn_param: HashMap::new(),
+        logout_command: Some("quit".to_string()),
+        config_lock_regex: vec![
+            r"(?i)(?:system|configuration) (?:is|has been) locked by (?:an)?other user(?:.*?user name is (?P<holder>\S+))?"
+                .to_string(),
+        ],
+        // `%Oct 16 10:02:11:123 2026 H3C IFNET/3/PHY_UPDOWN: ...`
+        syslog_regex: vec![
+            r"^%[A-Z][a-z]{2}\s+\d{1,2}\s+\d{2}:\d{2}:\d{2}:\d{3}\s+\d{4}\s+\S+\s+[A-Z0-9_]+/\d/[A-Z0-9_]+"
+                .to_string(),
+        ],
+        comment_prefixes: vec!["#".to_string()],
         ..Default::default()
     }
 }
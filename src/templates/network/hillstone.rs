@@ -7,6 +7,7 @@ use std::collections::HashMap;
 /// Exports the underlying handler configuration for Hillstone devices.
 pub fn hillstone_config() -> DeviceHandlerConfig {
     DeviceHandlerConfig {
+        name: Some("hillstone".to_string()),
         prompt: vec![
             prompt_rule("Enable", &[r"^.+#\s\r{0,1}$"]),
             prompt_rule("Config", &[r"^.+\(config.*\)\s*#\s\r{0,1}$"]),
@@ -40,6 +41,7 @@ pub fn hillstone_config() -> DeviceHandlerConfig {
             transition_rule("Config", "exit", "Enable", true, false),
         ],
         dyn_param: HashMap::new(),
+        comment_prefixes: vec!["!".to_string()],
         ..Default::default()
     }
 }
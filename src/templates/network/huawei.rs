@@ -1,12 +1,150 @@
 //! Huawei VRP device template.
 
-use crate::device::{DeviceHandler, DeviceHandlerConfig, input_rule, prompt_rule, transition_rule};
+use crate::device::{
+    DeviceHandler, DeviceHandlerConfig, command_stems, input_rule, prompt_rule, transition_rule,
+};
 use crate::error::ConnectError;
 use std::collections::HashMap;
 
+/// First words of VRP commands in `Enable` mode.
+const ENABLE_STEMS: &[&str] = &[
+    "cd",
+    "check",
+    "clock",
+    "compare",
+    "copy",
+    "debugging",
+    "delete",
+    "dir",
+    "display",
+    "format",
+    "ftp",
+    "language-mode",
+    "lock",
+    "mkdir",
+    "more",
+    "move",
+    "ping",
+    "pwd",
+    "quit",
+    "reboot",
+    "refresh",
+    "rename",
+    "reset",
+    "return",
+    "rmdir",
+    "save",
+    "schedule",
+    "scp",
+    "screen-length",
+    "send",
+    "sftp",
+    "ssh",
+    "startup",
+    "stelnet",
+    "super",
+    "system-view",
+    "telnet",
+    "terminal",
+    "tftp",
+    "tracert",
+    "undo",
+    "upgrade",
+];
+
+/// First words of VRP commands in `Config` mode, sub-modes included.
+const CONFIG_STEMS: &[&str] = &[
+    "aaa",
+    "accounting-scheme",
+    "acl",
+    "action",
+    "add",
+    "address-set",
+    "apply",
+    "area",
+    "authentication-mode",
+    "authentication-scheme",
+    "authorization-scheme",
+    "bgp",
+    "clock",
+    "commit",
+    "default-route-advertise",
+    "description",
+    "destination-address",
+    "destination-zone",
+    "dhcp",
+    "display",
+    "dns",
+    "domain",
+    "duplex",
+    "eth-trunk",
+    "exit",
+    "filter-policy",
+    "firewall",
+    "ftp",
+    "hrp",
+    "idle-timeout",
+    "if-match",
+    "import-route",
+    "info-center",
+    "interface",
+    "ip",
+    "ipv6",
+    "isis",
+    "lacp",
+    "lldp",
+    "load-balance",
+    "local-user",
+    "mode",
+    "mtu",
+    "name",
+    "nat",
+    "nat-policy",
+    "negotiation",
+    "network",
+    "ntp-service",
+    "ospf",
+    "peer",
+    "policy",
+    "port",
+    "port-group",
+    "port-security",
+    "preference",
+    "profile",
+    "protocol",
+    "quit",
+    "return",
+    "route-policy",
+    "rule",
+    "security-policy",
+    "service",
+    "service-set",
+    "set",
+    "shutdown",
+    "silent-interface",
+    "snmp-agent",
+    "source-address",
+    "source-zone",
+    "speed",
+    "ssh",
+    "stelnet",
+    "stp",
+    "sysname",
+    "telnet",
+    "time-range",
+    "traffic-policy",
+    "trunkport",
+    "undo",
+    "user",
+    "user-interface",
+    "vlan",
+    "vrrp",
+];
+
 /// Exports the underlying handler configuration for Huawei VRP devices.
 pub fn huawei_config() -> DeviceHandlerConfig {
     DeviceHandlerConfig {
+        name: Some("huawei".to_string()),
         prompt: vec![
             prompt_rule("Config", &[r"^(HRP_M|HRP_S){0,1}\[.+]+\s*$"]),
             prompt_rule("Enable", &[r"^(HRP_M|HRP_S){0,1}<.+>\s*$"]),
@@ -29,6 +167,27 @@ pub fn huawei_config() -> DeviceHandlerConfig {
             transition_rule("Config", "exit", "Enable", true, false),
         ],
         dyn_param: HashMap::new(),
+        logout_command: Some("quit".to_string()),
+        config_lock_regex: vec![
+            r"(?i)(?:system|configuration) (?:is|has been) locked by (?:an)?other user(?:.*?user name is (?P<holder>\S+))?"
+                .to_string(),
+        ],
+        // `Oct 16 2026 10:02:11+08:00 HUAWEI %%01IFNET/4/LINK_STATE(l)[0]:...`
+        syslog_regex: vec![
+            r"^[A-Z][a-z]{2}\s+\d{1,2}\s+\d{4}\s+\d{2}:\d{2}:\d{2}(?:\.\d+)?(?:[+-]\d{2}:\d{2})?\s+\S+\s+%%\d+[A-Z0-9_]+/\d/[A-Z0-9_]+"
+                .to_string(),
+        ],
+        comment_prefixes: vec!["#".to_string()],
+        command_stems: command_stems(&[("Enable", ENABLE_STEMS), ("Config", CONFIG_STEMS)]),
+        // `uptime is ...`, clocks, interface rates and packet counters.
+        // Clocks stand apart from colons, e.g. `10:02:11+08:00`, so the
+        // groups of IPv6 addresses like `FE80::1:23:45` are left alone.
+        volatile_regex: vec![
+            r"(?i)\buptime is (.+)$".to_string(),
+            r"(?:^|\s)(\d{1,2}:\d{2}:\d{2}(?:\.\d+)?)(?:[+-]\d{2}:\d{2})?(?:\s|$)".to_string(),
+            r"(?i)\b(?:input|output) rate:? (\d+) bits/sec, (\d+) packets/sec".to_string(),
+            r"(?i)^\s*(?:input|output):\s*(\d+) packets,\s*(\d+) bytes".to_string(),
+        ],
         ..Default::default()
     }
 }
@@ -1,12 +1,68 @@
 //! Juniper JunOS device template.
 
-use crate::device::{DeviceHandler, DeviceHandlerConfig, input_rule, prompt_rule, transition_rule};
+use crate::device::{
+    DeviceHandler, DeviceHandlerConfig, command_stems, input_rule, prompt_rule, transition_rule,
+};
 use crate::error::ConnectError;
 use std::collections::HashMap;
 
+/// First words of Junos commands in `Enable` mode.
+const ENABLE_STEMS: &[&str] = &[
+    "clear",
+    "configure",
+    "edit",
+    "exit",
+    "file",
+    "help",
+    "monitor",
+    "op",
+    "ping",
+    "quit",
+    "request",
+    "restart",
+    "set",
+    "show",
+    "ssh",
+    "start",
+    "system-view",
+    "telnet",
+    "test",
+    "traceroute",
+];
+
+/// First words of Junos commands in `Config` mode, sub-modes included.
+const CONFIG_STEMS: &[&str] = &[
+    "activate",
+    "annotate",
+    "commit",
+    "copy",
+    "deactivate",
+    "delete",
+    "edit",
+    "exit",
+    "help",
+    "insert",
+    "load",
+    "protect",
+    "quit",
+    "rename",
+    "replace",
+    "rollback",
+    "run",
+    "save",
+    "set",
+    "show",
+    "status",
+    "top",
+    "unprotect",
+    "up",
+    "wildcard",
+];
+
 /// Exports the underlying handler configuration for Juniper JunOS devices.
 pub fn juniper_config() -> DeviceHandlerConfig {
     DeviceHandlerConfig {
+        name: Some("juniper".to_string()),
         prompt: vec![
             prompt_rule("Config", &[r"^\S+@\S+#\s*$"]),
             prompt_rule("Enable", &[r"^\S+@\S+>\s*$"]),
@@ -36,6 +92,19 @@ pub fn juniper_config() -> DeviceHandlerConfig {
             transition_rule("Config", "exit", "Enable", true, false),
         ],
         dyn_param: HashMap::new(),
+        // The holder follows on the next line.
+        config_lock_regex: vec![
+            r"(?i)configuration database (?:is )?locked by:?\s*(?P<holder>\S+)".to_string(),
+        ],
+        comment_prefixes: vec!["#".to_string()],
+        command_stems: command_stems(&[("Enable", ENABLE_STEMS), ("Config", CONFIG_STEMS)]),
+        // Timestamps, `(1w2d 03:04 ago)`, interface rates and traffic counters.
+        volatile_regex: vec![
+            r"\b\d{4}-\d{2}-\d{2} \d{2}:\d{2}:\d{2}\b".to_string(),
+            r"\(([^()]+) ago\)".to_string(),
+            r"(?i)\b(?:input|output) rate\s*:\s*(\d+) bps \((\d+) pps\)".to_string(),
+            r"(?i)^\s*(?:input|output) (?:bytes|packets)\s*:\s*(\d+)".to_string(),
+        ],
         ..Default::default()
     }
 }
@@ -15,6 +15,7 @@ pub fn maipu_config() -> DeviceHandlerConfig {
     )];
 
     DeviceHandlerConfig {
+        name: Some("maipu".to_string()),
         prompt: vec![
             prompt_rule("Config", &[r"^\r{0,1}\S+\(\S+\)#\s*$"]),
             prompt_rule("Enable", &[r"^\r{0,1}[^\s#]+#\s*$"]),
@@ -29,6 +30,7 @@ pub fn maipu_config() -> DeviceHandlerConfig {
             transition_rule("Enable", "exit", "Login", true, false),
         ],
         dyn_param: HashMap::new(),
+        comment_prefixes: vec!["!".to_string()],
         ..Default::default()
     }
 }
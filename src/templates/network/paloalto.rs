@@ -7,6 +7,7 @@ use std::collections::HashMap;
 /// Exports the underlying handler configuration for Palo Alto Networks devices.
 pub fn paloalto_config() -> DeviceHandlerConfig {
     DeviceHandlerConfig {
+        name: Some("paloalto".to_string()),
         prompt: vec![
             prompt_rule("Config", &[r"^\r{0,1}\S+@\S+#\s*$"]),
             prompt_rule("Enable", &[r"^\r{0,1}\S+@\S+>\s*$"]),
@@ -24,6 +25,11 @@ pub fn paloalto_config() -> DeviceHandlerConfig {
             transition_rule("Config", "exit", "Enable", true, false),
         ],
         dyn_param: HashMap::new(),
+        config_lock_regex: vec![
+            r"(?i)(?:config|commit)(?: for scope \S+)? is currently locked by (?P<holder>\S+)"
+                .to_string(),
+        ],
+        comment_prefixes: vec!["#".to_string()],
         ..Default::default()
     }
 }
@@ -7,6 +7,7 @@ use std::collections::HashMap;
 /// Exports the underlying handler configuration for QiAnXin NSG devices.
 pub fn qianxin_config() -> DeviceHandlerConfig {
     DeviceHandlerConfig {
+        name: Some("qianxin".to_string()),
         prompt: vec![
             prompt_rule("Config", &[r"^\S+-config.*]\s*$"]),
             prompt_rule("Enable", &[r"^\S+>\s*$"]),
@@ -30,6 +31,7 @@ pub fn qianxin_config() -> DeviceHandlerConfig {
             transition_rule("Config", "end", "Enable", true, false),
         ],
         dyn_param: HashMap::new(),
+        comment_prefixes: vec!["!".to_string()],
         ..Default::default()
     }
 }
@@ -7,6 +7,7 @@ use std::collections::HashMap;
 /// Exports the underlying handler configuration for TopSec NGFW devices.
 pub fn topsec_config() -> DeviceHandlerConfig {
     DeviceHandlerConfig {
+        name: Some("topsec".to_string()),
         prompt: vec![prompt_rule("Enable", &[r"^\r{0,1}\S+[#%]\s*$"])],
         more_regex: vec![r"--More--".to_string()],
         error_regex: vec![r"^error".to_string()],
@@ -15,6 +15,7 @@ pub fn venustech_config() -> DeviceHandlerConfig {
     )];
 
     DeviceHandlerConfig {
+        name: Some("venustech".to_string()),
         prompt: vec![
             prompt_rule("Config", &[r"^\r{0,1}\S+\(\S+\)#\s*$"]),
             prompt_rule("Enable", &[r"^\r{0,1}[^\s#]+#\s*$"]),
@@ -30,6 +31,7 @@ pub fn venustech_config() -> DeviceHandlerConfig {
             transition_rule("Enable", "exit", "Login", true, false),
         ],
         dyn_param: HashMap::new(),
+        comment_prefixes: vec!["!".to_string()],
         ..Default::default()
     }
 }
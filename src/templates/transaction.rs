@@ -3,6 +3,7 @@ use crate::session::{Command, CommandBlockKind, RollbackPolicy, TxBlock, TxStep}
 
 use super::catalog::template_metadata;
 use super::linux::{LinuxCommandType, classify_linux_command};
+use super::registry::by_name;
 
 /// Classify a command for a specific template.
 ///
@@ -21,20 +22,34 @@ pub fn classify_command(template: &str, command: &str) -> Result<CommandBlockKin
         });
     }
 
-    let cmd = command.trim().to_ascii_lowercase();
-    let show_prefixes = ["show ", "display ", "ping ", "traceroute "];
-    if show_prefixes.iter().any(|prefix| cmd.starts_with(prefix)) {
+    if is_show_command(command) {
         return Ok(CommandBlockKind::Show);
     }
     Ok(CommandBlockKind::Config)
 }
 
+/// True when `command` reads state on any template: a network device show
+/// command or a read-only Linux command.
+pub fn is_read_only_command(command: &str) -> bool {
+    is_show_command(command) || classify_linux_command(command) == LinuxCommandType::ReadOnly
+}
+
+fn is_show_command(command: &str) -> bool {
+    let cmd = command.trim().to_ascii_lowercase();
+    let show_prefixes = ["show ", "display ", "ping ", "traceroute "];
+    show_prefixes.iter().any(|prefix| cmd.starts_with(prefix))
+}
+
 /// Build a transaction-like block from template + command list.
 ///
 /// Behavior:
 /// - If all commands are `show`-like, build a `show` block with no rollback.
 /// - Otherwise build a `config` block with `WholeResource` rollback policy.
 /// - Users must provide `resource_rollback_command` for config blocks.
+/// - An empty `mode` is derived per command from the template: show
+///   commands run in its show state (`Enable`), config commands and the
+///   rollback in its config state (`Config`).
+/// - Blank lines and the template's comment lines (`!`, `#`) are dropped.
 pub fn build_tx_block(
     template: &str,
     block_name: &str,
@@ -45,6 +60,8 @@ pub fn build_tx_block(
 ) -> Result<TxBlock, ConnectError> {
     let template_key = template.to_ascii_lowercase();
     let _ = template_metadata(&template_key)?;
+    let handler = by_name(&template_key)?;
+    let (commands, _) = handler.strip_comments(commands);
 
     if commands.is_empty() {
         return Err(ConnectError::InvalidTransaction(
@@ -58,22 +75,40 @@ pub fn build_tx_block(
         .collect::<Result<Vec<_>, _>>()?;
     let all_show = kinds.iter().all(|k| *k == CommandBlockKind::Show);
 
+    let derived = if mode.trim().is_empty() {
+        let derive = |config: bool| {
+            handler.default_mode(config, false).ok_or_else(|| {
+                ConnectError::InvalidTransaction(format!(
+                    "template '{template_key}' has no state to derive a mode from"
+                ))
+            })
+        };
+        Some((derive(false)?, derive(true)?))
+    } else {
+        None
+    };
+    let mode_for = |kind: CommandBlockKind| match (&derived, kind) {
+        (Some((show, _)), CommandBlockKind::Show) => show.as_str(),
+        (Some((_, config)), CommandBlockKind::Config) => config.as_str(),
+        (None, _) => mode,
+    };
+    let steps = commands
+        .into_iter()
+        .zip(&kinds)
+        .map(|(cmd, kind)| {
+            TxStep::new(Command {
+                timeout: timeout_secs,
+                ..Command::new(mode_for(*kind), cmd)
+            })
+        })
+        .collect();
+
     if all_show {
         return Ok(TxBlock {
             name: block_name.to_string(),
             kind: CommandBlockKind::Show,
             rollback_policy: RollbackPolicy::None,
-            steps: commands
-                .iter()
-                .map(|cmd| {
-                    TxStep::new(Command {
-                        mode: mode.to_string(),
-                        command: cmd.clone(),
-                        timeout: timeout_secs,
-                        ..Command::default()
-                    })
-                })
-                .collect(),
+            steps,
             fail_fast: true,
         });
     }
@@ -84,28 +119,14 @@ pub fn build_tx_block(
         ));
     };
 
-    let steps = commands
-        .iter()
-        .map(|cmd| {
-            TxStep::new(Command {
-                mode: mode.to_string(),
-                command: cmd.clone(),
-                timeout: timeout_secs,
-                ..Command::default()
-            })
-        })
-        .collect();
-
     Ok(TxBlock {
         name: block_name.to_string(),
         kind: CommandBlockKind::Config,
         rollback_policy: RollbackPolicy::WholeResource {
             rollback: Box::new(
                 Command {
-                    mode: mode.to_string(),
-                    command: undo,
                     timeout: timeout_secs,
-                    ..Command::default()
+                    ..Command::new(mode_for(CommandBlockKind::Config), undo)
                 }
                 .into(),
             ),
@@ -119,6 +140,7 @@ pub fn build_tx_block(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::session::SessionOperation;
 
     #[test]
     fn classify_show_command_returns_show_kind() {
@@ -169,4 +191,88 @@ mod tests {
                 .contains("require resource_rollback_command")
         );
     }
+
+    #[test]
+    fn build_tx_block_derives_omitted_modes_per_command() {
+        let commands = vec![
+            "show running-config | include ntp".to_string(),
+            "ntp server 192.0.2.10".to_string(),
+        ];
+        let tx = build_tx_block(
+            "cisco",
+            "ntp",
+            "",
+            &commands,
+            None,
+            Some("no ntp server 192.0.2.10".to_string()),
+        )
+        .expect("build config tx");
+        let modes: Vec<_> = tx
+            .steps
+            .iter()
+            .map(|step| match &step.run {
+                SessionOperation::Command(command) => command.mode.clone(),
+                other => panic!("unexpected operation: {other:?}"),
+            })
+            .collect();
+        assert_eq!(modes, ["enable", "config"]);
+        match &tx.rollback_policy {
+            RollbackPolicy::WholeResource { rollback, .. } => {
+                assert!(
+                    matches!(rollback.as_ref(), SessionOperation::Command(c) if c.mode == "config")
+                );
+            }
+            other => panic!("unexpected policy: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn build_tx_block_drops_blank_and_comment_lines() {
+        let commands = vec![
+            "! CHG-1234".to_string(),
+            String::new(),
+            "show clock".to_string(),
+            "  !".to_string(),
+        ];
+        let tx = build_tx_block("cisco", "show", "Enable", &commands, None, None)
+            .expect("build show tx");
+        assert_eq!(tx.steps.len(), 1);
+
+        let commands = vec!["#".to_string(), "display version".to_string()];
+        let tx =
+            build_tx_block("huawei", "show", "", &commands, None, None).expect("build show tx");
+        assert_eq!(tx.steps.len(), 1);
+
+        let only_comments = vec!["!".to_string(), " ".to_string()];
+        assert!(matches!(
+            build_tx_block("cisco", "empty", "Enable", &only_comments, None, None),
+            Err(ConnectError::InvalidTransaction(_))
+        ));
+    }
+
+    #[test]
+    fn tx_steps_without_mode_take_the_block_kind_mode() {
+        let mut block: TxBlock = serde_json::from_str(
+            r#"{
+                "name": "vlan",
+                "kind": "config",
+                "rollback_policy": "per_step",
+                "steps": [
+                    {"run": {"kind": "command", "command": "vlan 10", "timeout": null},
+                     "rollback": {"kind": "command", "command": "no vlan 10", "timeout": null}},
+                    {"run": {"kind": "command", "mode": "Enable", "command": "write memory", "timeout": null},
+                     "rollback": null}
+                ],
+                "fail_fast": true
+            }"#,
+        )
+        .expect("block");
+        block.fill_missing_modes("enable", "config");
+        let SessionOperation::Command(first) = &block.steps[0].run else {
+            panic!("command step");
+        };
+        assert_eq!(first.mode, "config");
+        assert!(matches!(&block.steps[1].run, SessionOperation::Command(c) if c.mode == "Enable"));
+        assert!(block.validate().is_ok());
+    }
 }
@@ -0,0 +1,183 @@
+//! Lines templates failed to recognize, aggregated across sessions.
+//!
+//! Every question left to an [`UnmodeledPromptPolicy`](crate::device::UnmodeledPromptPolicy)
+//! and every trailing line of a command that timed out without a prompt
+//! is counted under the template name of its session. The report lists the
+//! most frequent lines first, so template maintainers see which prompt or
+//! input patterns to add.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use once_cell::sync::Lazy;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::error::ConnectError;
+
+/// Group of handlers built without [`DeviceHandlerConfig::name`](crate::device::DeviceHandlerConfig::name).
+pub const CUSTOM_TEMPLATE: &str = "custom";
+
+/// Distinct lines kept per template; later new lines are dropped.
+const MAX_LINES_PER_TEMPLATE: usize = 256;
+
+/// Characters kept of each line.
+const MAX_LINE_CHARS: usize = 200;
+
+/// Lines of one template keyed by kind and text.
+type TemplateLines = HashMap<(UnmatchedLineKind, String), UnmatchedLine>;
+
+static UNMATCHED: Lazy<Mutex<HashMap<String, TemplateLines>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// How a line went unrecognized.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum UnmatchedLineKind {
+    /// Output stopped on it and no input rule answered.
+    UnmodeledPrompt,
+    /// The command timed out with it as the last line; no prompt matched.
+    PromptNotMatched,
+}
+
+/// One unrecognized line and how often it was seen.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct UnmatchedLine {
+    pub kind: UnmatchedLineKind,
+    pub line: String,
+    pub count: u64,
+    /// Command that last produced the line.
+    pub last_command: String,
+    pub last_seen_unix_ms: u64,
+}
+
+/// Unrecognized lines of one template, most frequent first.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct TemplateUnmatchedLines {
+    pub template: String,
+    /// Sightings of all lines together.
+    pub total: u64,
+    pub lines: Vec<UnmatchedLine>,
+}
+
+pub(crate) fn record_unmatched_line(
+    template: Option<&str>,
+    kind: UnmatchedLineKind,
+    line: &str,
+    command: &str,
+) {
+    let line: String = line.trim().chars().take(MAX_LINE_CHARS).collect();
+    if line.is_empty() {
+        return;
+    }
+    let last_seen_unix_ms = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| u64::try_from(elapsed.as_millis()).unwrap_or(u64::MAX))
+        .unwrap_or_default();
+    let template = template.unwrap_or(CUSTOM_TEMPLATE).to_ascii_lowercase();
+    let mut unmatched = UNMATCHED.lock().unwrap_or_else(|e| e.into_inner());
+    let lines = unmatched.entry(template).or_default();
+    let key = (kind, line);
+    if let Some(entry) = lines.get_mut(&key) {
+        entry.count += 1;
+        entry.last_command = command.to_string();
+        entry.last_seen_unix_ms = last_seen_unix_ms;
+    } else if lines.len() < MAX_LINES_PER_TEMPLATE {
+        let line = key.1.clone();
+        lines.insert(
+            key,
+            UnmatchedLine {
+                kind,
+                line,
+                count: 1,
+                last_command: command.to_string(),
+                last_seen_unix_ms,
+            },
+        );
+    }
+}
+
+/// Unrecognized lines seen in this process, per template.
+///
+/// Templates are sorted by name; lines by count, then most recent first.
+pub fn unmatched_lines_report() -> Vec<TemplateUnmatchedLines> {
+    let unmatched = UNMATCHED.lock().unwrap_or_else(|e| e.into_inner());
+    let mut report: Vec<TemplateUnmatchedLines> = unmatched
+        .iter()
+        .map(|(template, lines)| {
+            let mut lines: Vec<UnmatchedLine> = lines.values().cloned().collect();
+            lines.sort_by(|a, b| {
+                b.count
+                    .cmp(&a.count)
+                    .then(b.last_seen_unix_ms.cmp(&a.last_seen_unix_ms))
+                    .then_with(|| a.line.cmp(&b.line))
+            });
+            TemplateUnmatchedLines {
+                template: template.clone(),
+                total: lines.iter().map(|line| line.count).sum(),
+                lines,
+            }
+        })
+        .collect();
+    report.sort_by(|a, b| a.template.cmp(&b.template));
+    report
+}
+
+/// Exports [`unmatched_lines_report`] as pretty JSON.
+pub fn unmatched_lines_report_json() -> Result<String, ConnectError> {
+    serde_json::to_string_pretty(&unmatched_lines_report())
+        .map_err(|e| ConnectError::InternalServerError(format!("encode unmatched lines json: {e}")))
+}
+
+/// Forget every line recorded so far, e.g. after exporting a report.
+pub fn clear_unmatched_lines() {
+    UNMATCHED.lock().unwrap_or_else(|e| e.into_inner()).clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_orders_lines_by_frequency_per_template() {
+        let question = "Proceed with zeroize? [yes/no]:";
+        for _ in 0..3 {
+            record_unmatched_line(
+                Some("Unmatched-Test"),
+                UnmatchedLineKind::UnmodeledPrompt,
+                &format!("  {question}  "),
+                "crypto key zeroize",
+            );
+        }
+        record_unmatched_line(
+            Some("unmatched-test"),
+            UnmatchedLineKind::PromptNotMatched,
+            "edge-01(config-if-range)#",
+            "interface range Gi0/1-4",
+        );
+        record_unmatched_line(
+            Some("unmatched-test"),
+            UnmatchedLineKind::PromptNotMatched,
+            "   ",
+            "show clock",
+        );
+
+        let report = unmatched_lines_report();
+        let template = report
+            .iter()
+            .find(|template| template.template == "unmatched-test")
+            .expect("template");
+        assert_eq!(template.total, 4);
+        assert_eq!(template.lines.len(), 2);
+        assert_eq!(template.lines[0].line, question);
+        assert_eq!(template.lines[0].count, 3);
+        assert_eq!(template.lines[0].last_command, "crypto key zeroize");
+        assert_eq!(template.lines[1].kind, UnmatchedLineKind::PromptNotMatched);
+        assert!(
+            unmatched_lines_report_json()
+                .expect("json")
+                .contains("\"kind\": \"unmodeled_prompt\"")
+        );
+    }
+}
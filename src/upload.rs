@@ -0,0 +1,244 @@
+//! Upload fan-out results to S3-compatible object storage.
+//!
+//! Enabled with the `upload` feature. A [`PresignedUploadSink`] spools
+//! outcomes as JSON Lines to a temporary file while the fan-out runs and
+//! PUTs the file to a presigned URL (S3, MinIO, Ceph RGW, GCS) once the
+//! last device finished:
+//!
+//! ```rust,no_run
+//! use rneter::session::{Command, ExecutionContext, FanoutOptions, MANAGER};
+//! use rneter::upload::PresignedUploadSink;
+//!
+//! # async fn audit(requests: Vec<rneter::session::ConnectionRequest>, url: String)
+//! # -> Result<(), rneter::error::ConnectError> {
+//! let mut sink = PresignedUploadSink::new(url)?;
+//! let summary = MANAGER
+//!     .run_on_many_into(
+//!         requests,
+//!         Command::show("show version"),
+//!         ExecutionContext::new(),
+//!         FanoutOptions::new().with_max_concurrency(64),
+//!         &mut sink,
+//!     )
+//!     .await?;
+//! println!("{} devices, {} failed", summary.total, summary.failed);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use reqwest::header::CONTENT_LENGTH;
+use serde::Serialize;
+use tokio_util::io::ReaderStream;
+
+use crate::error::ConnectError;
+use crate::session::{DeviceOutcome, JsonLinesSink, OutcomeSink, SinkFuture, create_temp_file};
+
+fn upload_error(err: impl std::fmt::Display) -> ConnectError {
+    ConnectError::OutputSinkError(err.to_string())
+}
+
+/// `url` without its query string or fragment, which carry the presigned
+/// signature and must not end up in logs.
+fn redacted(url: &str) -> &str {
+    url.split(['?', '#']).next().unwrap_or_default()
+}
+
+/// JSON Lines spooled to disk and PUT to a presigned URL on finish.
+pub struct PresignedUploadSink {
+    url: String,
+    headers: Vec<(String, String)>,
+    timeout: Duration,
+    spool_path: PathBuf,
+    spool: JsonLinesSink<BufWriter<File>>,
+}
+
+impl PresignedUploadSink {
+    /// Spool to a new file in the system temp directory, readable only by
+    /// the current user; upload with a 5 minute timeout.
+    pub fn new(url: impl Into<String>) -> Result<Self, ConnectError> {
+        let (spool_path, file) = create_temp_file("rneter-upload", "jsonl")
+            .map_err(|err| upload_error(format!("cannot create upload spool: {err}")))?;
+        Ok(Self {
+            url: url.into(),
+            headers: vec![(
+                "content-type".to_string(),
+                "application/x-ndjson".to_string(),
+            )],
+            timeout: Duration::from_secs(300),
+            spool: JsonLinesSink::new(BufWriter::new(file)),
+            spool_path,
+        })
+    }
+
+    /// Add a request header, e.g. one the presigned URL was signed with.
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Timeout for the upload request.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Stream the spool to the URL; object stores reject chunked PUTs, so
+    /// the length is sent up front.
+    async fn upload(&self) -> Result<(), ConnectError> {
+        let spool = tokio::fs::File::open(&self.spool_path)
+            .await
+            .map_err(upload_error)?;
+        let length = spool.metadata().await.map_err(upload_error)?.len();
+        let client = reqwest::Client::builder()
+            .timeout(self.timeout)
+            .build()
+            .map_err(upload_error)?;
+        let mut request = client
+            .put(&self.url)
+            .header(CONTENT_LENGTH, length)
+            .body(reqwest::Body::wrap_stream(ReaderStream::new(spool)));
+        for (name, value) in &self.headers {
+            request = request.header(name, value);
+        }
+        let response = request
+            .send()
+            .await
+            .map_err(|err| upload_error(err.without_url()))?;
+        if !response.status().is_success() {
+            return Err(upload_error(format!(
+                "upload to {} failed: HTTP {}",
+                redacted(&self.url),
+                response.status()
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for PresignedUploadSink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PresignedUploadSink")
+            .field("url", &redacted(&self.url))
+            .field("timeout", &self.timeout)
+            .field("spool_path", &self.spool_path)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T: Serialize> OutcomeSink<T> for PresignedUploadSink {
+    fn write(&mut self, index: usize, outcome: &DeviceOutcome<T>) -> Result<(), ConnectError> {
+        self.spool.write(index, outcome)
+    }
+
+    fn finish(&mut self) -> SinkFuture<'_> {
+        Box::pin(async move {
+            OutcomeSink::<T>::finish(&mut self.spool).await?;
+            self.upload().await
+        })
+    }
+}
+
+impl Drop for PresignedUploadSink {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.spool_path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn finish_puts_the_spooled_lines() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.expect("bind");
+        let url = format!(
+            "http://{}/audit.jsonl?X-Amz-Signature=abc",
+            listener.local_addr().expect("addr")
+        );
+        let server = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.expect("accept");
+            let mut request = Vec::new();
+            let mut buffer = [0u8; 4096];
+            while !String::from_utf8_lossy(&request).contains("\"error\":null}\n") {
+                let n = socket.read(&mut buffer).await.expect("read");
+                if n == 0 {
+                    break;
+                }
+                request.extend_from_slice(&buffer[..n]);
+            }
+            socket
+                .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n")
+                .await
+                .expect("write");
+            String::from_utf8(request).expect("utf8")
+        });
+
+        let mut sink = PresignedUploadSink::new(url).expect("sink");
+        let spool_path = sink.spool_path.clone();
+        let outcome = DeviceOutcome {
+            device_addr: "admin@192.0.2.1:22".to_string(),
+            result: Ok(true),
+            elapsed: Duration::from_millis(5),
+        };
+        sink.write(0, &outcome).expect("write");
+        OutcomeSink::<bool>::finish(&mut sink)
+            .await
+            .expect("upload");
+
+        let request = server.await.expect("server");
+        assert!(request.starts_with("PUT /audit.jsonl?X-Amz-Signature=abc "));
+        assert!(request.contains("content-type: application/x-ndjson"));
+        assert!(request.contains("content-length: "));
+        assert!(!request.contains("transfer-encoding: chunked"));
+        assert!(request.contains(r#"{"index":0,"device_addr":"admin@192.0.2.1:22""#));
+        drop(sink);
+        assert!(!spool_path.exists());
+    }
+
+    #[tokio::test]
+    async fn failed_uploads_do_not_leak_the_signature() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.expect("bind");
+        let url = format!(
+            "http://{}/audit.jsonl?X-Amz-Signature=secret",
+            listener.local_addr().expect("addr")
+        );
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.expect("accept");
+            let mut buffer = [0u8; 4096];
+            let _ = socket.read(&mut buffer).await;
+            let _ = socket
+                .write_all(b"HTTP/1.1 403 Forbidden\r\ncontent-length: 0\r\n\r\n")
+                .await;
+        });
+
+        let mut sink = PresignedUploadSink::new(url).expect("sink");
+        let err = OutcomeSink::<bool>::finish(&mut sink)
+            .await
+            .expect_err("forbidden");
+
+        assert!(err.to_string().contains("/audit.jsonl failed: HTTP 403"));
+        assert!(!err.to_string().contains("secret"));
+        assert!(!format!("{sink:?}").contains("secret"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn spool_is_private_to_the_user() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let sink = PresignedUploadSink::new("http://192.0.2.1/audit.jsonl").expect("sink");
+        let mode = std::fs::metadata(&sink.spool_path)
+            .expect("spool")
+            .permissions()
+            .mode();
+
+        assert_eq!(mode & 0o777, 0o600);
+    }
+}
@@ -0,0 +1,307 @@
+//! HTTP webhook notifications for connection and transaction events.
+//!
+//! Enabled with the `webhook` feature. A [`WebhookSink`] subscribes to the
+//! manager's [`ConnectionEvent`] stream and POSTs each event as JSON, so
+//! ticketing or chatops systems learn about failed changes and dropped
+//! sessions without polling:
+//!
+//! ```json
+//! {"source":"rneter","timestamp":1760000000,"event":{"kind":"transaction_finished", ...}}
+//! ```
+//!
+//! Deliveries run in a background task and never block command execution.
+//! A delivery that still fails after the configured retries is logged and
+//! dropped.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::task::JoinHandle;
+
+use crate::error::ConnectError;
+use crate::session::{ConnectionEvent, SshConnectionManager};
+
+/// Body POSTed for each delivered event.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WebhookPayload {
+    /// Always `rneter`, so receivers shared with other tools can route on it.
+    pub source: String,
+    /// Unix seconds when the event was delivered.
+    pub timestamp: u64,
+    pub event: ConnectionEvent,
+}
+
+impl WebhookPayload {
+    fn new(event: ConnectionEvent) -> Self {
+        Self {
+            source: "rneter".to_string(),
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            event,
+        }
+    }
+}
+
+/// Whether an event reports something an operator should look at.
+///
+/// Failed commands, transactions that did not commit, and sessions that
+/// ended without an explicit close count as failures.
+pub fn is_failure_event(event: &ConnectionEvent) -> bool {
+    match event {
+        ConnectionEvent::CommandFailed { .. } => true,
+        ConnectionEvent::TransactionFinished { committed, .. } => !committed,
+        ConnectionEvent::Disconnected { reason, .. } => reason != "client_close_called",
+        ConnectionEvent::Connected { .. }
+        | ConnectionEvent::Reconnected { .. }
+        | ConnectionEvent::Evicted { .. }
+        | ConnectionEvent::SlowCommand { .. }
+        | ConnectionEvent::UnmodeledPrompt { .. } => false,
+    }
+}
+
+/// `url` without credentials, query or fragment, for logs and errors.
+fn redacted(url: &str) -> String {
+    match reqwest::Url::parse(url) {
+        Ok(url) => match url.host_str() {
+            Some(host) => match url.port() {
+                Some(port) => format!("{}://{host}:{port}{}", url.scheme(), url.path()),
+                None => format!("{}://{host}{}", url.scheme(), url.path()),
+            },
+            None => url.scheme().to_string(),
+        },
+        Err(_) => "<invalid url>".to_string(),
+    }
+}
+
+/// Webhook endpoint receiving connection events.
+#[derive(Clone)]
+pub struct WebhookSink {
+    url: String,
+    headers: Vec<(String, String)>,
+    timeout: Duration,
+    max_retries: u32,
+    failures_only: bool,
+}
+
+impl WebhookSink {
+    /// POST events to `url` with a 10 second timeout and 2 retries.
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            headers: Vec::new(),
+            timeout: Duration::from_secs(10),
+            max_retries: 2,
+            failures_only: false,
+        }
+    }
+
+    /// Add a request header, e.g. an authorization token.
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Per-request timeout.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Retries after a failed delivery, one second apart.
+    pub fn with_max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Deliver only events matching [`is_failure_event`].
+    pub fn with_failures_only(mut self, failures_only: bool) -> Self {
+        self.failures_only = failures_only;
+        self
+    }
+
+    /// Whether `event` passes this sink's filter.
+    pub fn accepts(&self, event: &ConnectionEvent) -> bool {
+        !self.failures_only || is_failure_event(event)
+    }
+
+    /// POST one event, retrying on transport errors and non-2xx responses.
+    pub async fn deliver(&self, event: ConnectionEvent) -> Result<(), ConnectError> {
+        let client = reqwest::Client::builder()
+            .timeout(self.timeout)
+            .build()
+            .map_err(|e| ConnectError::InternalServerError(format!("webhook client: {e}")))?;
+        self.deliver_with(&client, &WebhookPayload::new(event))
+            .await
+    }
+
+    async fn deliver_with(
+        &self,
+        client: &reqwest::Client,
+        payload: &WebhookPayload,
+    ) -> Result<(), ConnectError> {
+        let mut attempt = 0;
+        loop {
+            let mut request = client.post(&self.url).json(payload);
+            for (name, value) in &self.headers {
+                request = request.header(name, value);
+            }
+            let error = match request.send().await {
+                Ok(response) if response.status().is_success() => return Ok(()),
+                Ok(response) => format!("HTTP {}", response.status()),
+                Err(err) => err.without_url().to_string(),
+            };
+            if attempt >= self.max_retries {
+                return Err(ConnectError::InternalServerError(format!(
+                    "webhook delivery to {} failed: {error}",
+                    redacted(&self.url)
+                )));
+            }
+            attempt += 1;
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+    }
+
+    /// Subscribe to `manager`'s events and deliver them until stopped.
+    pub fn start(self, manager: &SshConnectionManager) -> Result<WebhookHandle, ConnectError> {
+        let client = reqwest::Client::builder()
+            .timeout(self.timeout)
+            .build()
+            .map_err(|e| ConnectError::InternalServerError(format!("webhook client: {e}")))?;
+        let mut events = manager.subscribe_events();
+        let task = tokio::spawn(async move {
+            loop {
+                let event = match events.recv().await {
+                    Ok(event) => event,
+                    Err(RecvError::Lagged(skipped)) => {
+                        log::warn!(
+                            "Webhook {} skipped {skipped} lagging events",
+                            redacted(&self.url)
+                        );
+                        continue;
+                    }
+                    Err(RecvError::Closed) => break,
+                };
+                if !self.accepts(&event) {
+                    continue;
+                }
+                if let Err(err) = self
+                    .deliver_with(&client, &WebhookPayload::new(event))
+                    .await
+                {
+                    log::warn!("{err}");
+                }
+            }
+        });
+        Ok(WebhookHandle { task })
+    }
+}
+
+impl std::fmt::Debug for WebhookSink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let header_names: Vec<&str> = self.headers.iter().map(|(name, _)| name.as_str()).collect();
+        f.debug_struct("WebhookSink")
+            .field("url", &redacted(&self.url))
+            .field("headers", &header_names)
+            .field("timeout", &self.timeout)
+            .field("max_retries", &self.max_retries)
+            .field("failures_only", &self.failures_only)
+            .finish()
+    }
+}
+
+/// Handle to a started [`WebhookSink`] delivery loop.
+pub struct WebhookHandle {
+    task: JoinHandle<()>,
+}
+
+impl WebhookHandle {
+    /// Stop delivering events; an in-flight delivery is abandoned.
+    pub fn stop(self) {
+        self.task.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    fn tx_failed() -> ConnectionEvent {
+        ConnectionEvent::TransactionFinished {
+            device_addr: "admin@192.0.2.1:22".to_string(),
+            name: "add-vlan".to_string(),
+            committed: false,
+            rollback_attempted: true,
+            rollback_succeeded: true,
+            failure_reason: Some("step 1 failed".to_string()),
+        }
+    }
+
+    #[test]
+    fn failures_only_filter_keeps_failed_changes_and_dropped_sessions() {
+        let sink = WebhookSink::new("http://127.0.0.1:9/hook").with_failures_only(true);
+        assert!(sink.accepts(&tx_failed()));
+        assert!(!sink.accepts(&ConnectionEvent::Connected {
+            device_addr: "admin@192.0.2.1:22".to_string(),
+        }));
+        assert!(!sink.accepts(&ConnectionEvent::Disconnected {
+            device_addr: "admin@192.0.2.1:22".to_string(),
+            reason: "client_close_called".to_string(),
+            stats: Default::default(),
+        }));
+        assert!(sink.accepts(&ConnectionEvent::Disconnected {
+            device_addr: "admin@192.0.2.1:22".to_string(),
+            reason: "channel_closed".to_string(),
+            stats: Default::default(),
+        }));
+    }
+
+    #[test]
+    fn debug_hides_url_secrets_and_header_values() {
+        let sink = WebhookSink::new("https://bot:pw@chat.example.com:8443/hook?token=s3cret")
+            .with_header("Authorization", "Bearer s3cret");
+        let debug = format!("{sink:?}");
+        assert!(debug.contains("https://chat.example.com:8443/hook"));
+        assert!(debug.contains("Authorization"));
+        assert!(!debug.contains("s3cret"));
+        assert!(!debug.contains("pw@"));
+    }
+
+    #[tokio::test]
+    async fn delivers_json_payload_with_headers() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.expect("bind");
+        let url = format!("http://{}/hook", listener.local_addr().expect("addr"));
+        let server = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.expect("accept");
+            let mut request = Vec::new();
+            let mut buffer = [0u8; 4096];
+            while !String::from_utf8_lossy(&request).contains("step 1 failed\"}}") {
+                let n = socket.read(&mut buffer).await.expect("read");
+                if n == 0 {
+                    break;
+                }
+                request.extend_from_slice(&buffer[..n]);
+            }
+            socket
+                .write_all(b"HTTP/1.1 204 No Content\r\ncontent-length: 0\r\n\r\n")
+                .await
+                .expect("write");
+            String::from_utf8(request).expect("utf8")
+        });
+
+        WebhookSink::new(url)
+            .with_header("x-token", "secret")
+            .deliver(tx_failed())
+            .await
+            .expect("deliver");
+
+        let request = server.await.expect("server").to_ascii_lowercase();
+        assert!(request.starts_with("post /hook"));
+        assert!(request.contains("x-token: secret"));
+        assert!(request.contains("\"kind\":\"transaction_finished\""));
+    }
+}
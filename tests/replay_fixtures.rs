@@ -40,18 +40,8 @@ fn fixture_exposes_connection_context() {
 fn fixture_replays_script_without_ssh() {
     let mut replayer = SessionReplayer::from_jsonl(BASIC_FIXTURE).expect("load fixture");
     let script = vec![
-        Command {
-            mode: "Enable".to_string(),
-            command: "terminal length 0".to_string(),
-            timeout: None,
-            ..Command::default()
-        },
-        Command {
-            mode: "Enable".to_string(),
-            command: "show version".to_string(),
-            timeout: None,
-            ..Command::default()
-        },
+        Command::show("terminal length 0"),
+        Command::show("show version"),
     ];
 
     let outputs = replayer.replay_script(&script).expect("replay script");
@@ -105,18 +95,8 @@ fn legacy_fixture_still_replays_after_schema_changes() {
 fn replay_script_returns_error_when_middle_command_missing() {
     let mut replayer = SessionReplayer::from_jsonl(NOISY_FIXTURE).expect("load noisy fixture");
     let script = vec![
-        Command {
-            mode: "Enable".to_string(),
-            command: "show ip int br".to_string(),
-            timeout: None,
-            ..Command::default()
-        },
-        Command {
-            mode: "Enable".to_string(),
-            command: "show version".to_string(),
-            timeout: None,
-            ..Command::default()
-        },
+        Command::show("show ip int br"),
+        Command::show("show version"),
     ];
 
     let err = match replayer.replay_script(&script) {
@@ -130,18 +110,8 @@ fn replay_script_returns_error_when_middle_command_missing() {
 fn replay_script_snapshot_matches_expected_output_sequence() {
     let mut replayer = SessionReplayer::from_jsonl(BASIC_FIXTURE).expect("load fixture");
     let script = vec![
-        Command {
-            mode: "Enable".to_string(),
-            command: "terminal length 0".to_string(),
-            timeout: None,
-            ..Command::default()
-        },
-        Command {
-            mode: "Enable".to_string(),
-            command: "show version".to_string(),
-            timeout: None,
-            ..Command::default()
-        },
+        Command::show("terminal length 0"),
+        Command::show("show version"),
     ];
 
     let outputs = replayer.replay_script(&script).expect("replay script");